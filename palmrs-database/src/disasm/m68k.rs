@@ -0,0 +1,694 @@
+//! 68000 instruction decoder
+//!
+//! Only the original 68000 instruction set is decoded, which is all the Dragonball processors in
+//! 68k Palm OS devices implement. Output uses Motorola syntax, with `sp` for `a7`.
+
+use crate::disasm::traps::{TrapTable, SYSTRAP_BASE, SYSTRAP_LAST};
+
+/// The `trap #15` opcode, used to call Palm OS system traps
+pub const SYSTRAP_OPCODE: u16 = 0x4E4F;
+
+/// Condition code names, indexed by the 4-bit condition field
+const CONDITIONS: [&str; 16] = [
+	"t", "f", "hi", "ls", "cc", "cs", "ne", "eq", "vc", "vs", "pl", "mi", "ge", "lt", "gt", "le",
+];
+
+/// Something an instruction operand refers to, which may be worth annotating
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Reference {
+	/// An address within the code being disassembled (a branch target or PC-relative operand)
+	Code(u32),
+
+	/// An A5-relative offset - a global variable if negative, otherwise the application
+	/// parameters or the jump table
+	A5(i32),
+
+	/// A Palm OS system trap
+	SysTrap(u16),
+}
+
+/// A single decoded instruction, before annotation
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Decoded {
+	pub len: usize,
+	pub mnemonic: String,
+	pub operands: String,
+	pub references: Vec<Reference>,
+}
+
+/// Operand size of an instruction
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Size {
+	Byte,
+	Word,
+	Long,
+}
+
+impl Size {
+	/// Decode the common two-bit size field (`00` byte, `01` word, `10` long)
+	fn from_bits(bits: u16) -> Option<Self> {
+		match bits & 0x3 {
+			0 => Some(Self::Byte),
+			1 => Some(Self::Word),
+			2 => Some(Self::Long),
+			_ => None,
+		}
+	}
+
+	fn suffix(self) -> &'static str {
+		match self {
+			Self::Byte => ".b",
+			Self::Word => ".w",
+			Self::Long => ".l",
+		}
+	}
+}
+
+/// Format an address register name
+fn areg(reg: u16) -> String {
+	match reg & 0x7 {
+		7 => "sp".to_string(),
+		x => format!("a{}", x),
+	}
+}
+
+/// Format an unsigned value, in decimal if it is small and hexadecimal otherwise
+fn hex(value: u32) -> String {
+	if value < 10 {
+		format!("{}", value)
+	} else {
+		format!("${:X}", value)
+	}
+}
+
+/// Format a register list from a `movem` mask, where bit 0 is `d0` and bit 15 is `a7`
+fn register_list(mask: u16) -> String {
+	let mut groups = Vec::new();
+	for (prefix, bits) in [("d", mask & 0xFF), ("a", mask >> 8)].iter() {
+		let mut reg = 0;
+		while reg < 8 {
+			if bits & (1 << reg) == 0 {
+				reg += 1;
+				continue;
+			}
+
+			let start = reg;
+			while reg < 8 && bits & (1 << reg) != 0 {
+				reg += 1;
+			}
+
+			let name = |x: u16| match (*prefix, x) {
+				("a", 7) => "sp".to_string(),
+				(p, x) => format!("{}{}", p, x),
+			};
+			if reg - start == 1 {
+				groups.push(name(start));
+			} else {
+				groups.push(format!("{}-{}", name(start), name(reg - 1)));
+			}
+		}
+	}
+
+	groups.join("/")
+}
+
+struct Decoder<'a> {
+	code: &'a [u8],
+	base: u32,
+	pos: usize,
+	references: Vec<Reference>,
+}
+
+impl<'a> Decoder<'a> {
+	/// Address of the next unread word
+	fn address(&self) -> u32 {
+		self.base.wrapping_add(self.pos as u32)
+	}
+
+	fn word(&mut self) -> Option<u16> {
+		let bytes = self.code.get(self.pos..(self.pos + 2))?;
+		self.pos += 2;
+		Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+	}
+
+	fn long(&mut self) -> Option<u32> {
+		let high = self.word()? as u32;
+		let low = self.word()? as u32;
+		Some((high << 16) | low)
+	}
+
+	fn immediate(&mut self, size: Size) -> Option<String> {
+		let value = match size {
+			Size::Byte => (self.word()? & 0xFF) as u32,
+			Size::Word => self.word()? as u32,
+			Size::Long => self.long()?,
+		};
+
+		Some(format!("#{}", hex(value)))
+	}
+
+	/// Decode a brief extension word index operand, for the `d8(An,Xn)` and `d8(pc,Xn)` modes
+	fn index(&mut self, base: &str) -> Option<String> {
+		let ext = self.word()?;
+		if ext & 0x0100 != 0 {
+			// Full extension word format, 68020 and later only
+			return None;
+		}
+
+		let reg = (ext >> 12) & 0x7;
+		let index = if ext & 0x8000 != 0 {
+			areg(reg)
+		} else {
+			format!("d{}", reg)
+		};
+		let size = if ext & 0x0800 != 0 { "l" } else { "w" };
+
+		Some(format!("{}({},{}.{})", ext as u8 as i8, base, index, size))
+	}
+
+	/// Decode an effective address operand, reading any extension words
+	fn ea(&mut self, mode: u16, reg: u16, size: Size) -> Option<String> {
+		let reg = reg & 0x7;
+		let operand = match mode & 0x7 {
+			0 => format!("d{}", reg),
+			1 => areg(reg),
+			2 => format!("({})", areg(reg)),
+			3 => format!("({})+", areg(reg)),
+			4 => format!("-({})", areg(reg)),
+			5 => {
+				let disp = self.word()? as i16;
+				if reg == 5 {
+					self.references.push(Reference::A5(disp as i32));
+				}
+
+				format!("{}({})", disp, areg(reg))
+			}
+			6 => self.index(&areg(reg))?,
+			_ => match reg {
+				0 => format!("(${:04X}).w", self.word()?),
+				1 => format!("(${:08X}).l", self.long()?),
+				2 => {
+					let pc = self.address();
+					let target = pc.wrapping_add(self.word()? as i16 as u32);
+					self.references.push(Reference::Code(target));
+					format!("${:X}(pc)", target)
+				}
+				3 => self.index("pc")?,
+				4 => self.immediate(size)?,
+				_ => return None,
+			},
+		};
+
+		Some(operand)
+	}
+
+	/// Decode the effective address in the low six bits of `opcode`
+	fn ea_low(&mut self, opcode: u16, size: Size) -> Option<String> {
+		self.ea((opcode >> 3) & 0x7, opcode & 0x7, size)
+	}
+
+	/// Decode a branch displacement, relative to the address after the opcode
+	fn branch_target(&mut self, disp8: u8) -> Option<String> {
+		let pc = self.address();
+		let disp = match disp8 {
+			0 => self.word()? as i16 as u32,
+			x => x as i8 as u32,
+		};
+
+		let target = pc.wrapping_add(disp);
+		self.references.push(Reference::Code(target));
+		Some(format!("${:X}", target))
+	}
+}
+
+/// Decode a single instruction at `pos` within `code`, which is loaded at address `base`
+///
+/// Returns `None` if the data at `pos` is not a valid 68000 instruction, or is truncated.
+pub(super) fn decode(code: &[u8], base: u32, pos: usize, traps: &TrapTable) -> Option<Decoded> {
+	let mut decoder = Decoder {
+		code,
+		base,
+		pos,
+		references: Vec::new(),
+	};
+
+	let opcode = decoder.word()?;
+	let (mnemonic, operands) = decode_opcode(&mut decoder, opcode, traps)?;
+
+	Some(Decoded {
+		len: decoder.pos - pos,
+		mnemonic,
+		operands,
+		references: decoder.references,
+	})
+}
+
+fn decode_opcode(d: &mut Decoder, op: u16, traps: &TrapTable) -> Option<(String, String)> {
+	let reg_hi = (op >> 9) & 0x7;
+	let mode_lo = (op >> 3) & 0x7;
+	let reg_lo = op & 0x7;
+
+	let result = match op >> 12 {
+		0x0 => decode_immediate_bit(d, op)?,
+
+		// move / movea
+		0x1..=0x3 => {
+			let size = match op >> 12 {
+				0x1 => Size::Byte,
+				0x3 => Size::Word,
+				_ => Size::Long,
+			};
+
+			let dest_mode = (op >> 6) & 0x7;
+			if dest_mode == 1 && size == Size::Byte {
+				return None;
+			}
+
+			let src = d.ea_low(op, size)?;
+			let dest = d.ea(dest_mode, reg_hi, size)?;
+			let mnemonic = if dest_mode == 1 { "movea" } else { "move" };
+
+			(
+				format!("{}{}", mnemonic, size.suffix()),
+				format!("{},{}", src, dest),
+			)
+		}
+
+		0x4 => decode_misc(d, op, traps)?,
+
+		// addq / subq / Scc / DBcc
+		0x5 => match Size::from_bits(op >> 6) {
+			Some(size) => {
+				let data = match reg_hi {
+					0 => 8,
+					x => x,
+				};
+				let mnemonic = if op & 0x0100 != 0 { "subq" } else { "addq" };
+
+				(
+					format!("{}{}", mnemonic, size.suffix()),
+					format!("#{},{}", data, d.ea_low(op, size)?),
+				)
+			}
+			None => {
+				let cond = CONDITIONS[((op >> 8) & 0xF) as usize];
+				if mode_lo == 1 {
+					let target = d.branch_target(0)?;
+					(format!("db{}", cond), format!("d{},{}", reg_lo, target))
+				} else {
+					(format!("s{}", cond), d.ea_low(op, Size::Byte)?)
+				}
+			}
+		},
+
+		// bra / bsr / Bcc
+		0x6 => {
+			let mnemonic = match (op >> 8) & 0xF {
+				0 => "bra".to_string(),
+				1 => "bsr".to_string(),
+				x => format!("b{}", CONDITIONS[x as usize]),
+			};
+			let suffix = if op & 0xFF == 0 { ".w" } else { ".s" };
+
+			(
+				format!("{}{}", mnemonic, suffix),
+				d.branch_target(op as u8)?,
+			)
+		}
+
+		// moveq
+		0x7 => {
+			if op & 0x0100 != 0 {
+				return None;
+			}
+
+			(
+				"moveq".to_string(),
+				format!("#{},d{}", op as u8 as i8, reg_hi),
+			)
+		}
+
+		// or / divu / divs / sbcd
+		0x8 => match (op >> 6) & 0x7 {
+			3 => (
+				"divu.w".to_string(),
+				format!("{},d{}", d.ea_low(op, Size::Word)?, reg_hi),
+			),
+			7 => (
+				"divs.w".to_string(),
+				format!("{},d{}", d.ea_low(op, Size::Word)?, reg_hi),
+			),
+			4 if mode_lo <= 1 => ("sbcd".to_string(), bcd_operands(op)),
+			_ => logical(d, op, "or")?,
+		},
+
+		// sub / suba / subx
+		0x9 => arithmetic(d, op, "sub")?,
+
+		// A-line traps
+		0xA => {
+			d.references.push(Reference::SysTrap(op));
+			("dc.w".to_string(), format!("${:04X}", op))
+		}
+
+		// cmp / cmpa / cmpm / eor
+		0xB => match ((op >> 6) & 0x7, mode_lo) {
+			(3, _) | (7, _) => {
+				let size = if op & 0x0100 != 0 {
+					Size::Long
+				} else {
+					Size::Word
+				};
+				(
+					format!("cmpa{}", size.suffix()),
+					format!("{},{}", d.ea_low(op, size)?, areg(reg_hi)),
+				)
+			}
+			(opmode, _) if opmode < 3 => {
+				let size = Size::from_bits(opmode)?;
+				(
+					format!("cmp{}", size.suffix()),
+					format!("{},d{}", d.ea_low(op, size)?, reg_hi),
+				)
+			}
+			(opmode, 1) => {
+				let size = Size::from_bits(opmode)?;
+				(
+					format!("cmpm{}", size.suffix()),
+					format!("({})+,({})+", areg(reg_lo), areg(reg_hi)),
+				)
+			}
+			(opmode, _) => {
+				let size = Size::from_bits(opmode)?;
+				(
+					format!("eor{}", size.suffix()),
+					format!("d{},{}", reg_hi, d.ea_low(op, size)?),
+				)
+			}
+		},
+
+		// and / mulu / muls / abcd / exg
+		0xC => match ((op >> 6) & 0x7, mode_lo) {
+			(3, _) => (
+				"mulu.w".to_string(),
+				format!("{},d{}", d.ea_low(op, Size::Word)?, reg_hi),
+			),
+			(7, _) => (
+				"muls.w".to_string(),
+				format!("{},d{}", d.ea_low(op, Size::Word)?, reg_hi),
+			),
+			(4, 0) | (4, 1) => ("abcd".to_string(), bcd_operands(op)),
+			(5, 0) => ("exg".to_string(), format!("d{},d{}", reg_hi, reg_lo)),
+			(5, 1) => (
+				"exg".to_string(),
+				format!("{},{}", areg(reg_hi), areg(reg_lo)),
+			),
+			(6, 1) => ("exg".to_string(), format!("d{},{}", reg_hi, areg(reg_lo))),
+			_ => logical(d, op, "and")?,
+		},
+
+		// add / adda / addx
+		0xD => arithmetic(d, op, "add")?,
+
+		0xE => decode_shift(d, op)?,
+
+		// F-line (coprocessor) instructions are not used on the 68000
+		_ => return None,
+	};
+
+	Some(result)
+}
+
+/// Operands of the `abcd` / `sbcd` / `addx` / `subx` instructions
+fn bcd_operands(op: u16) -> String {
+	let (rx, ry) = ((op >> 9) & 0x7, op & 0x7);
+	if op & 0x0008 != 0 {
+		format!("-({}),-({})", areg(ry), areg(rx))
+	} else {
+		format!("d{},d{}", ry, rx)
+	}
+}
+
+/// Decode the `and` / `or` instructions
+fn logical(d: &mut Decoder, op: u16, mnemonic: &str) -> Option<(String, String)> {
+	let size = Size::from_bits(op >> 6)?;
+	let reg = (op >> 9) & 0x7;
+	let ea = d.ea_low(op, size)?;
+	let operands = if op & 0x0100 != 0 {
+		format!("d{},{}", reg, ea)
+	} else {
+		format!("{},d{}", ea, reg)
+	};
+
+	Some((format!("{}{}", mnemonic, size.suffix()), operands))
+}
+
+/// Decode the `add` / `sub` family of instructions
+fn arithmetic(d: &mut Decoder, op: u16, mnemonic: &str) -> Option<(String, String)> {
+	let reg = (op >> 9) & 0x7;
+	let mode = (op >> 3) & 0x7;
+
+	match Size::from_bits(op >> 6) {
+		None => {
+			let size = if op & 0x0100 != 0 {
+				Size::Long
+			} else {
+				Size::Word
+			};
+			Some((
+				format!("{}a{}", mnemonic, size.suffix()),
+				format!("{},{}", d.ea_low(op, size)?, areg(reg)),
+			))
+		}
+		Some(size) if op & 0x0100 != 0 && mode <= 1 => {
+			Some((format!("{}x{}", mnemonic, size.suffix()), bcd_operands(op)))
+		}
+		Some(size) => {
+			let ea = d.ea_low(op, size)?;
+			let operands = if op & 0x0100 != 0 {
+				format!("d{},{}", reg, ea)
+			} else {
+				format!("{},d{}", ea, reg)
+			};
+
+			Some((format!("{}{}", mnemonic, size.suffix()), operands))
+		}
+	}
+}
+
+/// Decode the immediate, bit manipulation and `movep` instructions (opcodes `0x0xxx`)
+fn decode_immediate_bit(d: &mut Decoder, op: u16) -> Option<(String, String)> {
+	const BIT_OPS: [&str; 4] = ["btst", "bchg", "bclr", "bset"];
+	let reg_hi = (op >> 9) & 0x7;
+
+	// movep
+	if op & 0x0138 == 0x0108 {
+		let size = if op & 0x0040 != 0 {
+			Size::Long
+		} else {
+			Size::Word
+		};
+		let disp = d.word()? as i16;
+		let mem = format!("{}({})", disp, areg(op & 0x7));
+		let operands = if op & 0x0080 != 0 {
+			format!("d{},{}", reg_hi, mem)
+		} else {
+			format!("{},d{}", mem, reg_hi)
+		};
+
+		return Some((format!("movep{}", size.suffix()), operands));
+	}
+
+	// Dynamic bit operations
+	if op & 0x0100 != 0 {
+		let mnemonic = BIT_OPS[((op >> 6) & 0x3) as usize];
+		let ea = d.ea_low(op, Size::Byte)?;
+		return Some((mnemonic.to_string(), format!("d{},{}", reg_hi, ea)));
+	}
+
+	// Static bit operations
+	if reg_hi == 4 {
+		let mnemonic = BIT_OPS[((op >> 6) & 0x3) as usize];
+		let bit = d.word()? & 0xFF;
+		let ea = d.ea_low(op, Size::Byte)?;
+		return Some((mnemonic.to_string(), format!("#{},{}", bit, ea)));
+	}
+
+	let mnemonic = match reg_hi {
+		0 => "ori",
+		1 => "andi",
+		2 => "subi",
+		3 => "addi",
+		5 => "eori",
+		6 => "cmpi",
+		_ => return None,
+	};
+	let size = Size::from_bits(op >> 6)?;
+
+	// ori / andi / eori to ccr and sr
+	if op & 0x003F == 0x003C {
+		let dest = match (reg_hi, size) {
+			(0, Size::Byte) | (1, Size::Byte) | (5, Size::Byte) => "ccr",
+			(0, Size::Word) | (1, Size::Word) | (5, Size::Word) => "sr",
+			_ => return None,
+		};
+
+		let imm = d.immediate(size)?;
+		return Some((mnemonic.to_string(), format!("{},{}", imm, dest)));
+	}
+
+	let imm = d.immediate(size)?;
+	let ea = d.ea_low(op, size)?;
+	Some((
+		format!("{}{}", mnemonic, size.suffix()),
+		format!("{},{}", imm, ea),
+	))
+}
+
+/// Decode the miscellaneous instructions (opcodes `0x4xxx`)
+fn decode_misc(d: &mut Decoder, op: u16, traps: &TrapTable) -> Option<(String, String)> {
+	let reg_hi = (op >> 9) & 0x7;
+	let reg_lo = op & 0x7;
+	let none = String::new();
+
+	let result = match op {
+		0x4AFC => ("illegal".to_string(), none),
+		0x4E70 => ("reset".to_string(), none),
+		0x4E71 => ("nop".to_string(), none),
+		0x4E72 => ("stop".to_string(), format!("#${:04X}", d.word()?)),
+		0x4E73 => ("rte".to_string(), none),
+		0x4E75 => ("rts".to_string(), none),
+		0x4E76 => ("trapv".to_string(), none),
+		0x4E77 => ("rtr".to_string(), none),
+
+		// Palm OS system trap: `trap #15` followed by the trap word
+		SYSTRAP_OPCODE
+			if matches!(
+				d.code.get(d.pos..(d.pos + 2)),
+				Some(&[hi, lo]) if (SYSTRAP_BASE..=SYSTRAP_LAST).contains(&u16::from_be_bytes([hi, lo]))
+			) =>
+		{
+			let trap = d.word()?;
+			d.references.push(Reference::SysTrap(trap));
+			let name = match traps.name(trap) {
+				Some(name) => name.to_string(),
+				None => format!("${:04X}", trap),
+			};
+
+			("systrap".to_string(), name)
+		}
+
+		x if x & 0xFFF0 == 0x4E40 => ("trap".to_string(), format!("#{}", x & 0xF)),
+		x if x & 0xFFF8 == 0x4E50 => (
+			"link".to_string(),
+			format!("{},#{}", areg(reg_lo), d.word()? as i16),
+		),
+		x if x & 0xFFF8 == 0x4E58 => ("unlk".to_string(), areg(reg_lo)),
+		x if x & 0xFFF8 == 0x4E60 => ("move.l".to_string(), format!("{},usp", areg(reg_lo))),
+		x if x & 0xFFF8 == 0x4E68 => ("move.l".to_string(), format!("usp,{}", areg(reg_lo))),
+		x if x & 0xFFC0 == 0x4E80 => ("jsr".to_string(), d.ea_low(op, Size::Long)?),
+		x if x & 0xFFC0 == 0x4EC0 => ("jmp".to_string(), d.ea_low(op, Size::Long)?),
+		x if x & 0xF1C0 == 0x41C0 => (
+			"lea".to_string(),
+			format!("{},{}", d.ea_low(op, Size::Long)?, areg(reg_hi)),
+		),
+		x if x & 0xF1C0 == 0x4180 => (
+			"chk.w".to_string(),
+			format!("{},d{}", d.ea_low(op, Size::Word)?, reg_hi),
+		),
+		x if x & 0xFFC0 == 0x40C0 => (
+			"move.w".to_string(),
+			format!("sr,{}", d.ea_low(op, Size::Word)?),
+		),
+		x if x & 0xFFC0 == 0x44C0 => (
+			"move.w".to_string(),
+			format!("{},ccr", d.ea_low(op, Size::Word)?),
+		),
+		x if x & 0xFFC0 == 0x46C0 => (
+			"move.w".to_string(),
+			format!("{},sr", d.ea_low(op, Size::Word)?),
+		),
+		x if x & 0xFFC0 == 0x4AC0 => ("tas".to_string(), d.ea_low(op, Size::Byte)?),
+		x if x & 0xFFC0 == 0x4800 => ("nbcd".to_string(), d.ea_low(op, Size::Byte)?),
+		x if x & 0xFFF8 == 0x4840 => ("swap".to_string(), format!("d{}", reg_lo)),
+		x if x & 0xFFC0 == 0x4840 => ("pea".to_string(), d.ea_low(op, Size::Long)?),
+		x if x & 0xFFF8 == 0x4880 => ("ext.w".to_string(), format!("d{}", reg_lo)),
+		x if x & 0xFFF8 == 0x48C0 => ("ext.l".to_string(), format!("d{}", reg_lo)),
+
+		// movem
+		x if x & 0xFB80 == 0x4880 => {
+			let size = if op & 0x0040 != 0 {
+				Size::Long
+			} else {
+				Size::Word
+			};
+			let mask = d.word()?;
+			let mode = (op >> 3) & 0x7;
+			// The predecrement mode uses a reversed mask, with bit 0 being `a7`
+			let list = register_list(if mode == 4 { mask.reverse_bits() } else { mask });
+			let ea = d.ea_low(op, size)?;
+			let operands = if op & 0x0400 != 0 {
+				format!("{},{}", ea, list)
+			} else {
+				format!("{},{}", list, ea)
+			};
+
+			(format!("movem{}", size.suffix()), operands)
+		}
+
+		// negx / clr / neg / not / tst
+		x if x & 0xF900 == 0x4000 || x & 0xFF00 == 0x4A00 => {
+			let mnemonic = match (x >> 8) & 0xF {
+				0x0 => "negx",
+				0x2 => "clr",
+				0x4 => "neg",
+				0x6 => "not",
+				_ => "tst",
+			};
+			let size = Size::from_bits(op >> 6)?;
+
+			(
+				format!("{}{}", mnemonic, size.suffix()),
+				d.ea_low(op, size)?,
+			)
+		}
+
+		_ => return None,
+	};
+
+	Some(result)
+}
+
+/// Decode the shift and rotate instructions (opcodes `0xExxx`)
+fn decode_shift(d: &mut Decoder, op: u16) -> Option<(String, String)> {
+	const SHIFT_OPS: [&str; 4] = ["as", "ls", "rox", "ro"];
+	let dir = if op & 0x0100 != 0 { "l" } else { "r" };
+
+	match Size::from_bits(op >> 6) {
+		// Memory shifts, by a single bit
+		None => {
+			if op & 0x0800 != 0 {
+				return None;
+			}
+
+			let mnemonic = SHIFT_OPS[((op >> 9) & 0x3) as usize];
+			Some((format!("{}{}.w", mnemonic, dir), d.ea_low(op, Size::Word)?))
+		}
+
+		// Register shifts
+		Some(size) => {
+			let mnemonic = SHIFT_OPS[((op >> 3) & 0x3) as usize];
+			let count = (op >> 9) & 0x7;
+			let count = if op & 0x0020 != 0 {
+				format!("d{}", count)
+			} else {
+				format!("#{}", if count == 0 { 8 } else { count })
+			};
+
+			Some((
+				format!("{}{}{}", mnemonic, dir, size.suffix()),
+				format!("{},d{}", count, op & 0x7),
+			))
+		}
+	}
+}
@@ -0,0 +1,201 @@
+//! 68000 disassembler for `code` resources
+//!
+//! This module is only available with the `disasm` feature enabled. The [`Disassembler`] decodes
+//! 68k machine code into [`Instruction`]s, resolving Palm OS system trap calls (`trap #15`
+//! followed by a trap word) to their names through a [`TrapTable`], and, when given the
+//! application's [`JumpTable`], annotating calls made through the jump table with the segment and
+//! offset of the routine being called.
+//!
+//! Code segments commonly contain data (string constants, switch tables, debugger symbols) in
+//! between routines. The disassembler makes no attempt to tell code and data apart, so any data
+//! will show up as instructions, or as `dc.w` directives where it doesn't decode.
+
+use core::fmt;
+
+use crate::resource::code::{CodeSegment, JumpTable, JUMP_ENTRY_LENGTH};
+
+mod m68k;
+pub mod traps;
+
+pub use self::{
+	m68k::{Reference, SYSTRAP_OPCODE},
+	traps::TrapTable,
+};
+
+/// A single disassembled instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+	/// Address of the instruction, relative to the base address given to the disassembler
+	pub address: u32,
+
+	/// The raw instruction bytes, including any extension words
+	pub bytes: Vec<u8>,
+
+	pub mnemonic: String,
+	pub operands: String,
+
+	/// Addresses, A5 offsets and traps referred to by this instruction
+	pub references: Vec<Reference>,
+
+	/// Indexes of the jump table entries pointing at this instruction
+	pub entry_points: Vec<usize>,
+
+	/// Annotation for the instruction, such as the target of a jump table call
+	pub comment: Option<String>,
+}
+
+impl Instruction {
+	/// Whether this instruction is a `dc.w` / `dc.b` directive for data that didn't decode
+	pub fn is_data(&self) -> bool {
+		self.mnemonic.starts_with("dc.")
+	}
+}
+
+impl fmt::Display for Instruction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let bytes = self
+			.bytes
+			.chunks(2)
+			.map(|x| x.iter().map(|b| format!("{:02X}", b)).collect::<String>())
+			.collect::<Vec<_>>()
+			.join(" ");
+
+		let text = format!("{:<8} {}", self.mnemonic, self.operands);
+		write!(f, "{:08X}  {:<20} {}", self.address, bytes, text.trim_end())?;
+		if let Some(comment) = &self.comment {
+			write!(f, "  ; {}", comment)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Disassembler for 68k code segments
+#[derive(Debug, Clone)]
+pub struct Disassembler<'a> {
+	traps: &'a TrapTable,
+	jump_table: Option<&'a JumpTable>,
+}
+
+impl<'a> Disassembler<'a> {
+	/// Create a disassembler resolving system trap names from the given trap table
+	pub fn new(traps: &'a TrapTable) -> Self {
+		Self {
+			traps,
+			jump_table: None,
+		}
+	}
+
+	/// Annotate references to the given jump table
+	pub fn with_jump_table(mut self, jump_table: &'a JumpTable) -> Self {
+		self.jump_table = Some(jump_table);
+		self
+	}
+
+	/// Disassemble the given machine code, as if it were loaded at address `base`
+	pub fn disassemble(&self, code: &[u8], base: u32) -> Vec<Instruction> {
+		let mut instructions = Vec::new();
+		let mut pos = 0;
+		while pos < code.len() {
+			let instruction = match m68k::decode(code, base, pos, self.traps) {
+				Some(decoded) => {
+					let mut instruction = Instruction {
+						address: base.wrapping_add(pos as u32),
+						bytes: code[pos..(pos + decoded.len)].to_vec(),
+						mnemonic: decoded.mnemonic,
+						operands: decoded.operands,
+						references: decoded.references,
+						entry_points: Vec::new(),
+						comment: None,
+					};
+
+					instruction.comment = self.annotate(&instruction);
+					instruction
+				}
+
+				None => {
+					let bytes = code[pos..code.len().min(pos + 2)].to_vec();
+					let (mnemonic, operands) = match bytes.as_slice() {
+						[hi, lo] => ("dc.w", format!("${:02X}{:02X}", hi, lo)),
+						[x] => ("dc.b", format!("${:02X}", x)),
+						_ => unreachable!(),
+					};
+
+					Instruction {
+						address: base.wrapping_add(pos as u32),
+						bytes,
+						mnemonic: mnemonic.to_string(),
+						operands,
+						references: Vec::new(),
+						entry_points: Vec::new(),
+						comment: None,
+					}
+				}
+			};
+
+			pos += instruction.bytes.len();
+			instructions.push(instruction);
+		}
+
+		instructions
+	}
+
+	/// Disassemble the given code segment, starting from address zero
+	///
+	/// If a jump table has been given, the segment header (if any) is skipped, and the
+	/// instructions that jump table entries point at are marked in
+	/// [`entry_points`][Instruction::entry_points].
+	pub fn disassemble_segment(&self, segment: &CodeSegment) -> Vec<Instruction> {
+		let jump_table = match self.jump_table {
+			Some(x) => x,
+			None => return self.disassemble(&segment.data, 0),
+		};
+
+		let mut instructions = self.disassemble(segment.code(jump_table), 0);
+		for (idx, offset) in segment.entry_points(jump_table) {
+			if let Ok(pos) = instructions.binary_search_by_key(&offset, |x| x.address) {
+				instructions[pos].entry_points.push(idx);
+			}
+		}
+
+		instructions
+	}
+
+	/// Build the annotation for the given instruction from its references
+	fn annotate(&self, instruction: &Instruction) -> Option<String> {
+		let mut notes = Vec::new();
+		for reference in instruction.references.iter() {
+			match reference {
+				// `systrap` instructions already have the trap name as their operand
+				Reference::SysTrap(trap) if instruction.is_data() => {
+					if let Some(name) = self.traps.name(*trap) {
+						notes.push(name.to_string());
+					}
+				}
+
+				Reference::A5(a5_offset) => {
+					let jump_table = match self.jump_table {
+						Some(x) => x,
+						None => continue,
+					};
+
+					if let Some(entry) = jump_table.entry_at_a5_offset(*a5_offset) {
+						let idx = (*a5_offset as u32 - jump_table.offset) / JUMP_ENTRY_LENGTH;
+						notes.push(format!(
+							"jump table entry {}: code {} + ${:X}",
+							idx, entry.segment, entry.offset
+						));
+					}
+				}
+
+				_ => {}
+			}
+		}
+
+		if notes.is_empty() {
+			None
+		} else {
+			Some(notes.join(", "))
+		}
+	}
+}
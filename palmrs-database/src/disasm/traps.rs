@@ -0,0 +1,849 @@
+//! Palm OS system trap table
+//!
+//! Palm OS applications call into the OS with a `trap #15` instruction followed by a trap word in the
+//! range `0xA000` to `0xAFFF`, the `sysTrap` number. The built-in table contains the trap names
+//! from the Palm OS SDK (without the `sysTrap` prefix) for the Palm OS 1.0 to 3.0 traps; names for
+//! other traps can be loaded from a text file with [`TrapTable::parse`].
+
+use std::{collections::HashMap, io};
+
+/// The first system trap number (`sysTrapBase`)
+pub const SYSTRAP_BASE: u16 = 0xA000;
+
+/// The last system trap number
+pub const SYSTRAP_LAST: u16 = 0xAFFF;
+
+/// Built-in system trap names, starting from [`SYSTRAP_BASE`]
+const SYSTRAP_NAMES: &[&str] = &[
+	// 0xA000
+	"MemInit",
+	"MemInitHeapTable",
+	"MemStoreInit",
+	"MemCardFormat",
+	"MemCardInfo",
+	"MemStoreInfo",
+	"MemStoreSetInfo",
+	"MemNumHeaps",
+	"MemNumRAMHeaps",
+	"MemHeapID",
+	"MemHeapPtr",
+	"MemHeapFreeBytes",
+	"MemHeapSize",
+	"MemHeapFlags",
+	"MemHeapCompact",
+	"MemHeapInit",
+	// 0xA010
+	"MemHeapFreeByOwnerID",
+	"MemChunkNew",
+	"MemChunkFree",
+	"MemPtrNew",
+	"MemPtrRecoverHandle",
+	"MemPtrFlags",
+	"MemPtrSize",
+	"MemPtrOwner",
+	"MemPtrHeapID",
+	"MemPtrCardNo",
+	"MemPtrToLocalID",
+	"MemPtrSetOwner",
+	"MemPtrResize",
+	"MemPtrResetLock",
+	"MemHandleNew",
+	"MemHandleLockCount",
+	// 0xA020
+	"MemHandleToLocalID",
+	"MemHandleLock",
+	"MemHandleUnlock",
+	"MemLocalIDToGlobal",
+	"MemLocalIDKind",
+	"MemLocalIDToPtr",
+	"MemMove",
+	"MemSet",
+	"MemStoreSearch",
+	"Reserved6",
+	"MemKernelInit",
+	"MemHandleFree",
+	"MemHandleFlags",
+	"MemHandleSize",
+	"MemHandleOwner",
+	"MemHandleHeapID",
+	// 0xA030
+	"MemHandleDataStorage",
+	"MemHandleCardNo",
+	"MemHandleSetOwner",
+	"MemHandleResize",
+	"MemHandleResetLock",
+	"MemPtrUnlock",
+	"MemLocalIDToLockedPtr",
+	"MemSetDebugMode",
+	"MemHeapScramble",
+	"MemHeapCheck",
+	"MemNumCards",
+	"MemDebugMode",
+	"MemSemaphoreReserve",
+	"MemSemaphoreRelease",
+	"MemHeapDynamic",
+	"MemNVParams",
+	// 0xA040
+	"DmInit",
+	"DmCreateDatabase",
+	"DmDeleteDatabase",
+	"DmNumDatabases",
+	"DmGetDatabase",
+	"DmFindDatabase",
+	"DmDatabaseInfo",
+	"DmSetDatabaseInfo",
+	"DmDatabaseSize",
+	"DmOpenDatabase",
+	"DmCloseDatabase",
+	"DmNextOpenDatabase",
+	"DmOpenDatabaseInfo",
+	"DmResetRecordStates",
+	"DmGetLastErr",
+	"DmNumRecords",
+	// 0xA050
+	"DmRecordInfo",
+	"DmSetRecordInfo",
+	"DmAttachRecord",
+	"DmDetachRecord",
+	"DmMoveRecord",
+	"DmNewRecord",
+	"DmRemoveRecord",
+	"DmDeleteRecord",
+	"DmArchiveRecord",
+	"DmNewHandle",
+	"DmRemoveSecretRecords",
+	"DmQueryRecord",
+	"DmGetRecord",
+	"DmResizeRecord",
+	"DmReleaseRecord",
+	"DmGetResource",
+	// 0xA060
+	"DmGet1Resource",
+	"DmReleaseResource",
+	"DmResizeResource",
+	"DmNextOpenResDatabase",
+	"DmFindResourceType",
+	"DmFindResource",
+	"DmSearchResource",
+	"DmNumResources",
+	"DmResourceInfo",
+	"DmSetResourceInfo",
+	"DmAttachResource",
+	"DmDetachResource",
+	"DmNewResource",
+	"DmRemoveResource",
+	"DmGetResourceIndex",
+	"DmQuickSort",
+	// 0xA070
+	"DmQueryNextInCategory",
+	"DmNumRecordsInCategory",
+	"DmPositionInCategory",
+	"DmSeekRecordInCategory",
+	"DmMoveCategory",
+	"DmOpenDatabaseByTypeCreator",
+	"DmWrite",
+	"DmStrCopy",
+	"DmGetNextDatabaseByTypeCreator",
+	"DmWriteCheck",
+	"DmMoveOpenDBContext",
+	"DmFindRecordByID",
+	"DmGetAppInfoID",
+	"DmFindSortPositionV10",
+	"DmSet",
+	"DmCreateDatabaseFromImage",
+	// 0xA080
+	"DbgSrcMessage",
+	"DbgMessage",
+	"DbgGetMessage",
+	"DbgCommSettings",
+	"ErrDisplayFileLineMsg",
+	"ErrSetJump",
+	"ErrLongJump",
+	"ErrThrow",
+	"ErrExceptionList",
+	"SysBroadcastActionCode",
+	"SysUnimplemented",
+	"SysColdBoot",
+	"SysReset",
+	"SysDoze",
+	"SysAppLaunch",
+	"SysAppStartup",
+	// 0xA090
+	"SysAppExit",
+	"SysSetA5",
+	"SysSetTrapAddress",
+	"SysGetTrapAddress",
+	"SysTranslateKernelErr",
+	"SysSemaphoreCreate",
+	"SysSemaphoreDelete",
+	"SysSemaphoreWait",
+	"SysSemaphoreSignal",
+	"SysTimerCreate",
+	"SysTimerWrite",
+	"SysTaskCreate",
+	"SysTaskDelete",
+	"SysTaskTrigger",
+	"SysTaskID",
+	"SysTaskUserInfoPtr",
+	// 0xA0A0
+	"SysTaskDelay",
+	"SysTaskSetTermProc",
+	"SysUILaunch",
+	"SysNewOwnerID",
+	"SysSemaphoreSet",
+	"SysDisableInts",
+	"SysRestoreStatus",
+	"SysUIAppSwitch",
+	"SysCurAppInfoPV20",
+	"SysHandleEvent",
+	"SysInit",
+	"SysQSort",
+	"SysCurAppDatabase",
+	"SysFatalAlert",
+	"SysResSemaphoreCreate",
+	"SysResSemaphoreDelete",
+	// 0xA0B0
+	"SysResSemaphoreReserve",
+	"SysResSemaphoreRelease",
+	"SysSleep",
+	"SysKeyboardDialogV10",
+	"SysAppLauncherDialog",
+	"SysSetPerformance",
+	"SysBatteryInfoV20",
+	"SysLibInstall",
+	"SysLibRemove",
+	"SysLibTblEntry",
+	"SysLibFind",
+	"SysBatteryDialog",
+	"SysCopyStringResource",
+	"SysKernelInfo",
+	"SysLaunchConsole",
+	"SysTimerDelete",
+	// 0xA0C0
+	"SysSetAutoOffTime",
+	"SysFormPointerArrayToStrings",
+	"SysRandom",
+	"SysTaskSwitching",
+	"SysTimerRead",
+	"StrCopy",
+	"StrCat",
+	"StrLen",
+	"StrCompare",
+	"StrIToA",
+	"StrCaselessCompare",
+	"StrIToH",
+	"StrChr",
+	"StrStr",
+	"StrAToI",
+	"StrToLower",
+	// 0xA0D0
+	"SerReceiveISP",
+	"SlkOpen",
+	"SlkClose",
+	"SlkOpenSocket",
+	"SlkCloseSocket",
+	"SlkSocketRefNum",
+	"SlkSocketSetTimeout",
+	"SlkFlushSocket",
+	"SlkSetSocketListener",
+	"SlkSendPacket",
+	"SlkReceivePacket",
+	"SlkSysPktDefaultResponse",
+	"SlkProcessRPC",
+	"ConPutS",
+	"ConGetS",
+	"FplInit",
+	// 0xA0E0
+	"FplFree",
+	"FplFToA",
+	"FplAToF",
+	"FplBase10Info",
+	"FplLongToFloat",
+	"FplFloatToLong",
+	"FplFloatToULong",
+	"FplMul",
+	"FplAdd",
+	"FplSub",
+	"FplDiv",
+	"WinScreenInit",
+	"ScrCopyRectangle",
+	"ScrDrawChars",
+	"ScrLineRoutine",
+	"ScrRectangleRoutine",
+	// 0xA0F0
+	"ScrScreenInfo",
+	"ScrDrawNotify",
+	"ScrSendUpdateArea",
+	"ScrCompressScanLine",
+	"ScrDeCompressScanLine",
+	"TimGetSeconds",
+	"TimSetSeconds",
+	"TimGetTicks",
+	"TimInit",
+	"TimSetAlarm",
+	"TimGetAlarm",
+	"TimHandleInterrupt",
+	"TimSecondsToDateTime",
+	"TimDateTimeToSeconds",
+	"TimAdjust",
+	"TimSleep",
+	// 0xA100
+	"TimWake",
+	"CategoryCreateListV10",
+	"CategoryFreeListV10",
+	"CategoryFind",
+	"CategoryGetName",
+	"CategoryEditV10",
+	"CategorySelectV10",
+	"CategoryGetNext",
+	"CategorySetTriggerLabel",
+	"CategoryTruncateName",
+	"ClipboardAddItem",
+	"ClipboardCheckIfItemExist",
+	"ClipboardGetItem",
+	"CtlDrawControl",
+	"CtlEraseControl",
+	"CtlHideControl",
+	// 0xA110
+	"CtlShowControl",
+	"CtlGetValue",
+	"CtlSetValue",
+	"CtlGetLabel",
+	"CtlSetLabel",
+	"CtlHandleEvent",
+	"CtlHitControl",
+	"CtlSetEnabled",
+	"CtlSetUsable",
+	"CtlEnabled",
+	"EvtInitialize",
+	"EvtAddEventToQueue",
+	"EvtCopyEvent",
+	"EvtGetEvent",
+	"EvtGetPen",
+	"EvtSysInit",
+	// 0xA120
+	"EvtGetSysEvent",
+	"EvtProcessSoftKeyStroke",
+	"EvtGetPenBtnList",
+	"EvtSetPenQueuePtr",
+	"EvtPenQueueSize",
+	"EvtFlushPenQueue",
+	"EvtEnqueuePenPoint",
+	"EvtDequeuePenStrokeInfo",
+	"EvtDequeuePenPoint",
+	"EvtFlushNextPenStroke",
+	"EvtSetKeyQueuePtr",
+	"EvtKeyQueueSize",
+	"EvtFlushKeyQueue",
+	"EvtEnqueueKey",
+	"EvtDequeueKeyEvent",
+	"EvtWakeup",
+	// 0xA130
+	"EvtResetAutoOffTimer",
+	"EvtKeyQueueEmpty",
+	"EvtEnableGraffiti",
+	"FldCopy",
+	"FldCut",
+	"FldDrawField",
+	"FldEraseField",
+	"FldFreeMemory",
+	"FldGetBounds",
+	"FldGetTextPtr",
+	"FldGetSelection",
+	"FldHandleEvent",
+	"FldPaste",
+	"FldRecalculateField",
+	"FldSetBounds",
+	"FldSetText",
+	// 0xA140
+	"FldGetFont",
+	"FldSetFont",
+	"FldSetSelection",
+	"FldGrabFocus",
+	"FldReleaseFocus",
+	"FldGetInsPtPosition",
+	"FldSetInsPtPosition",
+	"FldSetScrollPosition",
+	"FldGetScrollPosition",
+	"FldGetTextHeight",
+	"FldGetTextAllocatedSize",
+	"FldGetTextLength",
+	"FldScrollField",
+	"FldScrollable",
+	"FldGetVisibleLines",
+	"FldGetAttributes",
+	// 0xA150
+	"FldSetAttributes",
+	"FldSendChangeNotification",
+	"FldCalcFieldHeight",
+	"FldGetTextHandle",
+	"FldCompactText",
+	"FldDirty",
+	"FldWordWrap",
+	"FldSetTextAllocatedSize",
+	"FldSetTextHandle",
+	"FldSetTextPtr",
+	"FldGetMaxChars",
+	"FldSetMaxChars",
+	"FldSetUsable",
+	"FldInsert",
+	"FldDelete",
+	"FldUndo",
+	// 0xA160
+	"FldSetDirty",
+	"FldSendHeightChangeNotification",
+	"FldMakeFullyVisible",
+	"FntGetFont",
+	"FntSetFont",
+	"FntGetFontPtr",
+	"FntBaseLine",
+	"FntCharHeight",
+	"FntLineHeight",
+	"FntAverageCharWidth",
+	"FntCharWidth",
+	"FntCharsWidth",
+	"FntDescenderHeight",
+	"FntCharsInWidth",
+	"FntLineWidth",
+	"FrmInitForm",
+	// 0xA170
+	"FrmDeleteForm",
+	"FrmDrawForm",
+	"FrmEraseForm",
+	"FrmGetActiveForm",
+	"FrmSetActiveForm",
+	"FrmGetActiveFormID",
+	"FrmGetUserModifiedState",
+	"FrmSetNotUserModified",
+	"FrmGetFocus",
+	"FrmSetFocus",
+	"FrmHandleEvent",
+	"FrmGetFormBounds",
+	"FrmGetWindowHandle",
+	"FrmGetFormId",
+	"FrmGetFormPtr",
+	"FrmGetNumberOfObjects",
+	// 0xA180
+	"FrmGetObjectIndex",
+	"FrmGetObjectId",
+	"FrmGetObjectType",
+	"FrmGetObjectPtr",
+	"FrmHideObject",
+	"FrmShowObject",
+	"FrmGetObjectPosition",
+	"FrmSetObjectPosition",
+	"FrmGetControlValue",
+	"FrmSetControlValue",
+	"FrmGetControlGroupSelection",
+	"FrmSetControlGroupSelection",
+	"FrmCopyLabel",
+	"FrmSetLabel",
+	"FrmGetLabel",
+	"FrmSetCategoryLabel",
+	// 0xA190
+	"FrmGetTitle",
+	"FrmSetTitle",
+	"FrmAlert",
+	"FrmDoDialog",
+	"FrmCustomAlert",
+	"FrmHelp",
+	"FrmUpdateScrollers",
+	"FrmGetFirstForm",
+	"FrmVisible",
+	"FrmGetObjectBounds",
+	"FrmCopyTitle",
+	"FrmGotoForm",
+	"FrmPopupForm",
+	"FrmUpdateForm",
+	"FrmReturnToForm",
+	"FrmSetEventHandler",
+	// 0xA1A0
+	"FrmDispatchEvent",
+	"FrmCloseAllForms",
+	"FrmSaveAllForms",
+	"FrmGetGadgetData",
+	"FrmSetGadgetData",
+	"FrmSetCategoryTrigger",
+	"UIInitialize",
+	"UIReset",
+	"InsPtInitialize",
+	"InsPtSetLocation",
+	"InsPtGetLocation",
+	"InsPtEnable",
+	"InsPtEnabled",
+	"InsPtSetHeight",
+	"InsPtGetHeight",
+	"InsPtCheckBlink",
+	// 0xA1B0
+	"LstSetDrawFunction",
+	"LstDrawList",
+	"LstEraseList",
+	"LstGetSelection",
+	"LstGetSelectionText",
+	"LstHandleEvent",
+	"LstSetHeight",
+	"LstSetSelection",
+	"LstSetListChoices",
+	"LstMakeItemVisible",
+	"LstGetNumberOfItems",
+	"LstPopupList",
+	"LstSetPosition",
+	"MenuInit",
+	"MenuDispose",
+	"MenuHandleEvent",
+	// 0xA1C0
+	"MenuDrawMenu",
+	"MenuEraseStatus",
+	"MenuGetActiveMenu",
+	"MenuSetActiveMenu",
+	"RctSetRectangle",
+	"RctCopyRectangle",
+	"RctInsetRectangle",
+	"RctOffsetRectangle",
+	"RctPtInRectangle",
+	"RctGetIntersection",
+	"TblDrawTable",
+	"TblEraseTable",
+	"TblHandleEvent",
+	"TblGetItemBounds",
+	"TblSelectItem",
+	"TblGetItemInt",
+	// 0xA1D0
+	"TblSetItemInt",
+	"TblSetItemStyle",
+	"TblUnhighlightSelection",
+	"TblSetRowUsable",
+	"TblGetNumberOfRows",
+	"TblSetCustomDrawProcedure",
+	"TblSetRowSelectable",
+	"TblRowSelectable",
+	"TblSetLoadDataProcedure",
+	"TblSetSaveDataProcedure",
+	"TblGetBounds",
+	"TblSetRowHeight",
+	"TblGetColumnWidth",
+	"TblGetRowID",
+	"TblSetRowID",
+	"TblMarkRowInvalid",
+	// 0xA1E0
+	"TblMarkTableInvalid",
+	"TblGetSelection",
+	"TblInsertRow",
+	"TblRemoveRow",
+	"TblRowInvalid",
+	"TblRedrawTable",
+	"TblRowUsable",
+	"TblReleaseFocus",
+	"TblEditing",
+	"TblGetCurrentField",
+	"TblSetColumnUsable",
+	"TblGetRowHeight",
+	"TblSetColumnWidth",
+	"TblGrabFocus",
+	"TblSetItemPtr",
+	"TblFindRowID",
+	// 0xA1F0
+	"TblGetLastUsableRow",
+	"TblGetColumnSpacing",
+	"TblFindRowData",
+	"TblGetRowData",
+	"TblSetRowData",
+	"TblSetColumnSpacing",
+	"WinCreateWindow",
+	"WinCreateOffscreenWindow",
+	"WinDeleteWindow",
+	"WinInitializeWindow",
+	"WinAddWindow",
+	"WinRemoveWindow",
+	"WinSetActiveWindow",
+	"WinSetDrawWindow",
+	"WinGetDrawWindow",
+	"WinGetActiveWindow",
+	// 0xA200
+	"WinGetDisplayWindow",
+	"WinGetFirstWindow",
+	"WinEnableWindow",
+	"WinDisableWindow",
+	"WinGetWindowFrameRect",
+	"WinDrawWindowFrame",
+	"WinEraseWindow",
+	"WinSaveBits",
+	"WinRestoreBits",
+	"WinCopyRectangle",
+	"WinScrollRectangle",
+	"WinGetDisplayExtent",
+	"WinGetWindowExtent",
+	"WinDisplayToWindowPt",
+	"WinWindowToDisplayPt",
+	"WinGetClip",
+	// 0xA210
+	"WinSetClip",
+	"WinResetClip",
+	"WinClipRectangle",
+	"WinDrawLine",
+	"WinDrawGrayLine",
+	"WinEraseLine",
+	"WinInvertLine",
+	"WinFillLine",
+	"WinDrawRectangle",
+	"WinEraseRectangle",
+	"WinInvertRectangle",
+	"WinDrawRectangleFrame",
+	"WinDrawGrayRectangleFrame",
+	"WinEraseRectangleFrame",
+	"WinInvertRectangleFrame",
+	"WinGetFramesRectangle",
+	// 0xA220
+	"WinDrawChars",
+	"WinEraseChars",
+	"WinInvertChars",
+	"WinGetPattern",
+	"WinSetPattern",
+	"WinSetUnderlineMode",
+	"WinDrawBitmap",
+	"WinModal",
+	"WinGetDrawWindowBounds",
+	"WinFillRectangle",
+	"WinDrawInvertedChars",
+	"PrefOpenPreferenceDBV10",
+	"PrefGetPreferences",
+	"PrefSetPreferences",
+	"PrefGetAppPreferencesV10",
+	"PrefSetAppPreferencesV10",
+	// 0xA230
+	"SndInit",
+	"SndSetDefaultVolume",
+	"SndGetDefaultVolume",
+	"SndDoCmd",
+	"SndPlaySystemSound",
+	"AlmInit",
+	"AlmCancelAll",
+	"AlmAlarmCallback",
+	"AlmSetAlarm",
+	"AlmGetAlarm",
+	"AlmDisplayAlarm",
+	"AlmEnableNotification",
+	"HwrGetRAMMapping",
+	"HwrMemWritable",
+	"HwrMemReadable",
+	"HwrDoze",
+	// 0xA240
+	"HwrSleep",
+	"HwrWake",
+	"HwrSetSystemClock",
+	"HwrSetCPUDutyCycle",
+	"HwrDisplayInit",
+	"HwrDisplaySleep",
+	"HwrTimerInit",
+	"HwrCursorV33",
+	"HwrBatteryLevel",
+	"HwrDelay",
+	"HwrEnableDataWrites",
+	"HwrDisableDataWrites",
+	"HwrLCDBaseAddrV33",
+	"HwrDisplayDrawBootScreen",
+	"HwrTimerSleep",
+	"HwrTimerWake",
+	// 0xA250
+	"HwrDisplayWake",
+	"HwrIRQ1Handler",
+	"HwrIRQ2Handler",
+	"HwrIRQ3Handler",
+	"HwrIRQ4Handler",
+	"HwrIRQ5Handler",
+	"HwrIRQ6Handler",
+	"HwrDockSignals",
+	"HwrPluggedIn",
+	"Crc16CalcBlock",
+	"SelectDayV10",
+	"SelectTime",
+	"DayDrawDaySelector",
+	"DayHandleEvent",
+	"DayDrawDays",
+	"DayOfWeek",
+	// 0xA260
+	"DaysInMonth",
+	"DayOfMonth",
+	"DateDaysToDate",
+	"DateToDays",
+	"DateAdjust",
+	"DateSecondsToDate",
+	"DateToAscii",
+	"DateToDOWDMFormat",
+	"TimeToAscii",
+	"Find",
+	"FindStrInStr",
+	"FindSaveMatch",
+	"FindGetLineBounds",
+	"FindDrawHeader",
+	"PenOpen",
+	"PenClose",
+	// 0xA270
+	"PenGetRawPen",
+	"PenCalibrate",
+	"PenRawToScreen",
+	"PenScreenToRaw",
+	"PenResetCalibration",
+	"PenSleep",
+	"PenWake",
+	"ResLoadForm",
+	"ResLoadMenu",
+	"FtrInit",
+	"FtrUnregister",
+	"FtrGet",
+	"FtrSet",
+	"FtrGetByIndex",
+	"GrfInit",
+	"GrfFree",
+	// 0xA280
+	"GrfGetState",
+	"GrfSetState",
+	"GrfFlushPoints",
+	"GrfAddPoint",
+	"GrfInitState",
+	"GrfCleanState",
+	"GrfMatch",
+	"GrfGetMacro",
+	"GrfFilterPoints",
+	"GrfGetNumPoints",
+	"GrfGetPoint",
+	"GrfFindBranch",
+	"GrfMatchGlyph",
+	"GrfGetGlyphMapping",
+	"GrfGetMacroName",
+	"GrfDeleteMacro",
+	// 0xA290
+	"GrfAddMacro",
+	"GrfGetAndExpandMacro",
+	"GrfProcessStroke",
+	"GrfFieldChange",
+	"GetCharSortValue",
+	"GetCharAttr",
+	"GetCharCaselessValue",
+	"PwdExists",
+	"PwdVerify",
+	"PwdSet",
+	"PwdRemove",
+	"GsiInitialize",
+	"GsiSetLocation",
+	"GsiEnable",
+	"GsiEnabled",
+	"GsiSetShiftState",
+	// 0xA2A0
+	"KeyInit",
+	"KeyHandleInterrupt",
+	"KeyCurrentState",
+	"KeyResetDoubleTap",
+	"KeyRates",
+	"KeySleep",
+	"KeyWake",
+	"DlkControl",
+	"DlkStartServer",
+	"DlkGetSyncInfo",
+	"DlkSetLogEntry",
+	"IntlDispatch",
+	"SysLibLoad",
+	"SndPlaySmf",
+	"SndCreateMidiList",
+	"AbtShowAbout",
+	// 0xA2B0
+	"MdmDial",
+	"MdmHangUp",
+	"DmSearchRecord",
+	"SysInsertionSort",
+	"DmInsertionSort",
+	"LstSetTopItem",
+];
+
+/// Parse a trap number, in either hexadecimal (with a `0x` or `$` prefix) or decimal
+fn parse_trap_number(value: &str) -> Option<u16> {
+	if let Some(hex) = value
+		.strip_prefix("0x")
+		.or_else(|| value.strip_prefix("0X"))
+		.or_else(|| value.strip_prefix('$'))
+	{
+		u16::from_str_radix(hex, 16).ok()
+	} else {
+		value.parse().ok()
+	}
+}
+
+/// A mapping of system trap numbers to names
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TrapTable {
+	names: HashMap<u16, String>,
+}
+
+impl TrapTable {
+	/// Create an empty trap table
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a trap table containing the built-in trap names
+	pub fn builtin() -> Self {
+		let names = (SYSTRAP_BASE..)
+			.zip(SYSTRAP_NAMES.iter())
+			.map(|(trap, name)| (trap, name.to_string()))
+			.collect();
+
+		Self { names }
+	}
+
+	/// Parse trap names from the given text, adding them to (or replacing them in) this table
+	///
+	/// Each line contains a trap name and a trap number, in either order, optionally separated by
+	/// `=` and followed by `,` - so both `0xA2B6 LstGetTopItem` and the `sysTrapLstGetTopItem =
+	/// 0xA2B6,` lines from the SDK's `CoreTraps.h` are accepted. Empty lines, and lines starting with
+	/// `#` or `//`, are ignored. A `sysTrap` prefix on the name is removed.
+	pub fn parse(&mut self, text: &str) -> Result<(), io::Error> {
+		for (idx, line) in text.lines().enumerate() {
+			let line = line.trim();
+			if line.is_empty() || line.starts_with('#') || line.starts_with("//") {
+				continue;
+			}
+
+			let fields = line
+				.split(|c: char| c.is_whitespace() || c == '=' || c == ',')
+				.filter(|x| !x.is_empty())
+				.collect::<Vec<_>>();
+
+			let (trap, name) = match fields.as_slice() {
+				[a, b] => match (parse_trap_number(a), parse_trap_number(b)) {
+					(Some(trap), None) => (trap, *b),
+					(None, Some(trap)) => (trap, *a),
+					_ => (0, ""),
+				},
+				_ => (0, ""),
+			};
+
+			if name.is_empty() || !(SYSTRAP_BASE..=SYSTRAP_LAST).contains(&trap) {
+				return Err(io::Error::other(format!(
+					"invalid trap table entry on line {}: {:?}",
+					idx + 1,
+					line
+				)));
+			}
+
+			let name = name.strip_prefix("sysTrap").unwrap_or(name);
+			self.names.insert(trap, name.to_string());
+		}
+
+		Ok(())
+	}
+
+	/// Return the name of the given system trap, if known
+	pub fn name(&self, trap: u16) -> Option<&str> {
+		self.names.get(&trap).map(|x| x.as_str())
+	}
+
+	/// Number of trap names in this table
+	pub fn len(&self) -> usize {
+		self.names.len()
+	}
+
+	/// Whether this table contains no trap names
+	pub fn is_empty(&self) -> bool {
+		self.names.is_empty()
+	}
+}
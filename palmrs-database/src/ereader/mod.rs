@@ -0,0 +1,395 @@
+//! eReader (Peanut Press) books (`PNRd`/`PPrs`)
+//!
+//! Record `0` of an eReader book is a header giving the compression type, and the location of
+//! each section of the book within the database: the text records, followed by the chapter,
+//! image, metadata, footnote and sidebar records. The text itself is [PML][pml], compressed
+//! record by record with either PalmDoc compression or zlib.
+//!
+//! Each chapter record holds the offset of the chapter within the text (a 32-bit big-endian
+//! value), followed by the NUL-terminated title of the chapter.
+//!
+//! Only unencrypted books are supported - books with DRM use a different compression type, and
+//! are rejected by [`EReaderBook::from_bytes`].
+
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+
+use crate::{
+	palmdoc,
+	text::{palm_to_string, trim_null},
+	PalmDatabase,
+	PdbDatabase,
+};
+
+pub mod pml;
+
+use self::pml::{PmlChapter, PmlToken};
+
+/// Database type code of eReader books
+pub const EREADER_TYPE_CODE: &[u8; 4] = b"PNRd";
+
+/// Creator code of eReader books
+pub const EREADER_CREATOR_CODE: &[u8; 4] = b"PPrs";
+
+/// Compression type: text records are PalmDoc compressed
+pub const EREADER_COMPRESSION_PALMDOC: u16 = 2;
+
+/// Compression type: text records are zlib compressed
+pub const EREADER_COMPRESSION_ZLIB: u16 = 10;
+
+/// Signature at the start of each image record
+pub const EREADER_IMAGE_SIGNATURE: &[u8; 4] = b"PNG ";
+
+/// Offset of the image data within an image record
+const IMAGE_DATA_OFFSET: usize = 62;
+
+/// Length of the image name field within an image record
+const IMAGE_NAME_LENGTH: usize = 32;
+
+/// Offset of the title within a chapter record
+const CHAPTER_TITLE_OFFSET: usize = 4;
+
+/// The eReader header (record `0`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EReaderHeader {
+	pub compression: u16,
+
+	/// Index of the first record after the text records
+	pub non_text_offset: u16,
+
+	pub chapter_count: u16,
+	pub image_count: u16,
+	pub link_count: u16,
+	pub has_metadata: bool,
+
+	/// Number of footnote records, including the footnote ID record
+	pub footnote_count: u16,
+
+	/// Number of sidebar records, including the sidebar ID record
+	pub sidebar_count: u16,
+
+	pub chapter_offset: u16,
+	pub image_data_offset: u16,
+	pub link_offset: u16,
+	pub metadata_offset: u16,
+	pub footnote_offset: u16,
+	pub sidebar_offset: u16,
+	pub last_data_offset: u16,
+}
+
+impl EReaderHeader {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let compression = rdr.read_u16::<BigEndian>()?;
+
+		rdr.seek(SeekFrom::Start(12))?;
+		let non_text_offset = rdr.read_u16::<BigEndian>()?;
+		let chapter_count = rdr.read_u16::<BigEndian>()?;
+
+		rdr.seek(SeekFrom::Start(20))?;
+		let image_count = rdr.read_u16::<BigEndian>()?;
+		let link_count = rdr.read_u16::<BigEndian>()?;
+		let has_metadata = rdr.read_u16::<BigEndian>()? != 0;
+
+		rdr.seek(SeekFrom::Start(28))?;
+		let footnote_count = rdr.read_u16::<BigEndian>()?;
+		let sidebar_count = rdr.read_u16::<BigEndian>()?;
+		let chapter_offset = rdr.read_u16::<BigEndian>()?;
+
+		rdr.seek(SeekFrom::Start(40))?;
+		let image_data_offset = rdr.read_u16::<BigEndian>()?;
+		let link_offset = rdr.read_u16::<BigEndian>()?;
+		let metadata_offset = rdr.read_u16::<BigEndian>()?;
+
+		rdr.seek(SeekFrom::Start(48))?;
+		let footnote_offset = rdr.read_u16::<BigEndian>()?;
+		let sidebar_offset = rdr.read_u16::<BigEndian>()?;
+		let last_data_offset = rdr.read_u16::<BigEndian>()?;
+
+		Ok(Self {
+			compression,
+			non_text_offset,
+			chapter_count,
+			image_count,
+			link_count,
+			has_metadata,
+			footnote_count,
+			sidebar_count,
+			chapter_offset,
+			image_data_offset,
+			link_offset,
+			metadata_offset,
+			footnote_offset,
+			sidebar_offset,
+			last_data_offset,
+		})
+	}
+}
+
+/// Book metadata
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct EReaderMetadata {
+	pub title: String,
+	pub author: String,
+	pub copyright: String,
+	pub publisher: String,
+	pub isbn: String,
+}
+
+/// An image embedded in a book
+#[derive(Debug, Clone, PartialEq)]
+pub struct EReaderImage {
+	/// Name of the image, as referred to by the `\m` PML tag
+	pub name: String,
+
+	/// PNG image data
+	pub data: Vec<u8>,
+}
+
+/// A footnote or sidebar
+#[derive(Debug, Clone, PartialEq)]
+pub struct EReaderNote {
+	/// ID of the note, as referred to by the `\Fn` or `\Sd` PML tag
+	pub id: String,
+
+	/// PML text of the note
+	pub pml: String,
+}
+
+/// An unencrypted eReader book
+#[derive(Debug)]
+pub struct EReaderBook {
+	pub database: PalmDatabase<PdbDatabase>,
+	pub header: EReaderHeader,
+}
+
+impl EReaderBook {
+	pub fn from_database(database: PalmDatabase<PdbDatabase>) -> Result<Self, io::Error> {
+		if &database.header.type_code != EREADER_TYPE_CODE {
+			return Err(io::Error::other(format!(
+				"database type {:?} is not an eReader book",
+				String::from_utf8_lossy(&database.header.type_code)
+			)));
+		}
+
+		let header = match database.list_records_resources().first() {
+			Some((_, data)) => EReaderHeader::from_bytes(&mut Cursor::new(data))?,
+			None => return Err(io::Error::other("eReader book has no header record")),
+		};
+
+		match header.compression {
+			EREADER_COMPRESSION_PALMDOC | EREADER_COMPRESSION_ZLIB => {}
+			other => {
+				return Err(io::Error::other(format!(
+					"unsupported eReader compression type {} (the book may be encrypted)",
+					other
+				)))
+			}
+		}
+
+		Ok(Self { database, header })
+	}
+
+	/// Read an eReader book from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbDatabase>::from_bytes(data)?)
+	}
+
+	fn record(&self, index: usize) -> Result<&[u8], io::Error> {
+		self.database
+			.list_records_resources()
+			.get(index)
+			.map(|(_, data)| data.as_slice())
+			.ok_or_else(|| io::Error::other(format!("eReader book has no record {}", index)))
+	}
+
+	/// Decompress a text record
+	fn decompress_record(&self, index: usize) -> Result<String, io::Error> {
+		let data = self.record(index)?;
+		let text = match self.header.compression {
+			EREADER_COMPRESSION_ZLIB => {
+				let mut out = Vec::new();
+				ZlibDecoder::new(data).read_to_end(&mut out)?;
+				out
+			}
+
+			_ => palmdoc::decompress(data)?,
+		};
+
+		Ok(palm_to_string(&text))
+	}
+
+	/// Return the PML text of the book
+	pub fn pml(&self) -> Result<String, io::Error> {
+		let mut out = String::new();
+		for index in 1..(self.header.non_text_offset as usize) {
+			out.push_str(&self.decompress_record(index)?);
+		}
+
+		Ok(out)
+	}
+
+	/// Return the book metadata, if the book has any
+	pub fn metadata(&self) -> Result<Option<EReaderMetadata>, io::Error> {
+		if !self.header.has_metadata {
+			return Ok(None);
+		}
+
+		let data = self.record(self.header.metadata_offset as usize)?;
+		let mut fields = data.split(|x| *x == 0).map(palm_to_string);
+
+		Ok(Some(EReaderMetadata {
+			title: fields.next().unwrap_or_default(),
+			author: fields.next().unwrap_or_default(),
+			copyright: fields.next().unwrap_or_default(),
+			publisher: fields.next().unwrap_or_default(),
+			isbn: fields.next().unwrap_or_default(),
+		}))
+	}
+
+	/// Return the images embedded in the book
+	pub fn images(&self) -> Result<Vec<EReaderImage>, io::Error> {
+		let start = self.header.image_data_offset as usize;
+		let mut images = Vec::new();
+		for index in start..(start + self.header.image_count as usize) {
+			let data = self.record(index)?;
+			if !data.starts_with(EREADER_IMAGE_SIGNATURE) || data.len() < IMAGE_DATA_OFFSET {
+				return Err(io::Error::other(format!(
+					"eReader record {} is not an image",
+					index
+				)));
+			}
+
+			let name = &data[4..(4 + IMAGE_NAME_LENGTH)];
+			images.push(EReaderImage {
+				name: palm_to_string(trim_null(name)),
+				data: data[IMAGE_DATA_OFFSET..].to_vec(),
+			});
+		}
+
+		Ok(images)
+	}
+
+	/// Read a set of notes: an ID record, followed by a text record for each ID
+	fn notes(&self, offset: u16, count: u16) -> Result<Vec<EReaderNote>, io::Error> {
+		if count == 0 {
+			return Ok(Vec::new());
+		}
+
+		let ids = self.record(offset as usize)?;
+		let ids = ids
+			.split(|x| *x == 0)
+			.filter(|x| !x.is_empty())
+			.map(palm_to_string);
+
+		let mut notes = Vec::new();
+		for (id, index) in ids.zip((offset as usize + 1)..(offset as usize + count as usize)) {
+			notes.push(EReaderNote {
+				id,
+				pml: self.decompress_record(index)?,
+			});
+		}
+
+		Ok(notes)
+	}
+
+	/// Return the footnotes of the book
+	pub fn footnotes(&self) -> Result<Vec<EReaderNote>, io::Error> {
+		self.notes(self.header.footnote_offset, self.header.footnote_count)
+	}
+
+	/// Return the sidebars of the book
+	pub fn sidebars(&self) -> Result<Vec<EReaderNote>, io::Error> {
+		self.notes(self.header.sidebar_offset, self.header.sidebar_count)
+	}
+
+	/// Return the chapter index of the book
+	///
+	/// The index is read from the chapter records, or from the chapter markers in the text if the
+	/// book has no chapter records. Chapter records don't give a nesting level, so each of their
+	/// chapters is at the top level.
+	pub fn chapters(&self) -> Result<Vec<PmlChapter>, io::Error> {
+		if self.header.chapter_count == 0 {
+			return Ok(pml::chapters(&pml::tokenize(&self.pml()?)));
+		}
+
+		let start = self.header.chapter_offset as usize;
+		let mut chapters = Vec::new();
+		for index in start..(start + self.header.chapter_count as usize) {
+			let data = self.record(index)?;
+			if data.len() < CHAPTER_TITLE_OFFSET {
+				return Err(io::Error::other(format!(
+					"eReader record {} is not a chapter",
+					index
+				)));
+			}
+
+			let title = palm_to_string(trim_null(&data[CHAPTER_TITLE_OFFSET..]));
+			chapters.push(PmlChapter {
+				level: 0,
+				title: pml::to_text(&pml::tokenize(&title)).trim().to_string(),
+			});
+		}
+
+		Ok(chapters)
+	}
+
+	/// Convert the book to plain text, with the footnotes and sidebars at the end
+	pub fn to_text(&self) -> Result<String, io::Error> {
+		let mut out = pml::to_text(&pml::tokenize(&self.pml()?));
+		for note in self.footnotes()?.iter().chain(self.sidebars()?.iter()) {
+			out.push_str(&format!(
+				"\n\n[{}] {}",
+				note.id,
+				pml::to_text(&pml::tokenize(&note.pml))
+			));
+		}
+
+		Ok(out)
+	}
+
+	/// Convert the book to a standalone HTML document
+	///
+	/// Images are referred to by name, so should be written out alongside the document.
+	pub fn to_html(&self) -> Result<String, io::Error> {
+		let title = match self.metadata()? {
+			Some(x) if !x.title.is_empty() => x.title,
+			_ => self
+				.database
+				.header
+				.name_try_str()
+				.unwrap_or("")
+				.to_string(),
+		};
+
+		let mut out = format!(
+			"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n</head>\n<body>\n",
+			pml::escape_html(&title)
+		);
+		out.push_str(&pml::to_html(&pml::tokenize(&self.pml()?)));
+
+		for (kind, notes) in [
+			("footnote", self.footnotes()?),
+			("sidebar", self.sidebars()?),
+		] {
+			for note in notes.iter() {
+				let tokens = pml::tokenize(&note.pml);
+				out.push_str(&format!(
+					"\n<div id=\"{}-{}\">{}</div>",
+					kind,
+					pml::escape_html(&note.id),
+					pml::to_html(&tokens)
+				));
+			}
+		}
+
+		out.push_str("\n</body>\n</html>\n");
+		Ok(out)
+	}
+
+	/// Return the tokens of the book's PML text
+	pub fn tokens(&self) -> Result<Vec<PmlToken>, io::Error> {
+		Ok(pml::tokenize(&self.pml()?))
+	}
+}
@@ -0,0 +1,335 @@
+//! Palm Markup Language (PML)
+//!
+//! PML is the markup used by eReader books. Tags start with a backslash, and are a single letter
+//! (`\i`), or a letter followed by a level or variant (`\X1`, `\Sp`). Some tags take an argument
+//! in the form `="value"` (`\m="image.png"`). Most formatting tags are toggles: the same tag turns
+//! the formatting on, and then off again.
+
+use crate::text::palm_to_string;
+
+/// A single PML token
+#[derive(Debug, Clone, PartialEq)]
+pub enum PmlToken {
+	Text(String),
+	Tag {
+		name: String,
+		argument: Option<String>,
+	},
+}
+
+/// An entry in the chapter index of a PML document
+#[derive(Debug, Clone, PartialEq)]
+pub struct PmlChapter {
+	/// Chapter nesting level, where `0` is the top level
+	pub level: u8,
+
+	pub title: String,
+}
+
+/// Tags that toggle formatting on and off
+const TOGGLE_TAGS: &[&str] = &[
+	"x", "X0", "X1", "X2", "X3", "X4", "c", "r", "i", "u", "o", "v", "t", "b", "B", "k", "l", "s",
+	"Sp", "Sb", "q", "Fn", "Sd", "I",
+];
+
+/// Append text to the token list, merging it with any preceding text token
+fn push_text(tokens: &mut Vec<PmlToken>, text: &str) {
+	if let Some(PmlToken::Text(last)) = tokens.last_mut() {
+		last.push_str(text);
+	} else {
+		tokens.push(PmlToken::Text(text.to_string()));
+	}
+}
+
+/// Split a PML document into text and tags
+///
+/// Character escapes (`\\`, `\aNNN` and `\UXXXX`) are converted to text.
+pub fn tokenize(pml: &str) -> Vec<PmlToken> {
+	let chars = pml.chars().collect::<Vec<char>>();
+	let mut tokens = Vec::new();
+
+	let mut idx = 0;
+	while idx < chars.len() {
+		if chars[idx] != '\\' {
+			let start = idx;
+			while idx < chars.len() && chars[idx] != '\\' {
+				idx += 1;
+			}
+
+			push_text(&mut tokens, &chars[start..idx].iter().collect::<String>());
+			continue;
+		}
+
+		idx += 1;
+		let tag = match chars.get(idx) {
+			Some(ch) => *ch,
+			None => break,
+		};
+		idx += 1;
+
+		let name = match tag {
+			'\\' => {
+				push_text(&mut tokens, "\\");
+				continue;
+			}
+
+			// Palm OS character, as three decimal digits
+			'a' => {
+				let digits = chars.iter().skip(idx).take(3).collect::<String>();
+				idx += digits.len();
+				if let Ok(value) = digits.parse::<u8>() {
+					push_text(&mut tokens, &palm_to_string(&[value]));
+				}
+
+				continue;
+			}
+
+			// Unicode character, as four hex digits
+			'U' => {
+				let digits = chars.iter().skip(idx).take(4).collect::<String>();
+				idx += digits.len();
+				if let Some(ch) = u32::from_str_radix(&digits, 16)
+					.ok()
+					.and_then(core::char::from_u32)
+				{
+					push_text(&mut tokens, &ch.to_string());
+				}
+
+				continue;
+			}
+
+			'X' | 'C' => match chars.get(idx) {
+				Some(level) if level.is_ascii_digit() => {
+					idx += 1;
+					format!("{}{}", tag, level)
+				}
+				_ => tag.to_string(),
+			},
+
+			'S' => match chars.get(idx) {
+				Some(variant) if "pbd".contains(*variant) => {
+					idx += 1;
+					format!("S{}", variant)
+				}
+				_ => tag.to_string(),
+			},
+
+			'F' if chars.get(idx) == Some(&'n') => {
+				idx += 1;
+				"Fn".to_string()
+			}
+
+			_ => tag.to_string(),
+		};
+
+		let mut argument = None;
+		if chars.get(idx) == Some(&'=') && chars.get(idx + 1) == Some(&'"') {
+			let start = idx + 2;
+			let end = chars
+				.iter()
+				.skip(start)
+				.position(|x| *x == '"')
+				.map(|x| x + start)
+				.unwrap_or_else(|| chars.len());
+
+			argument = Some(chars[start..end].iter().collect());
+			idx = end + 1;
+		}
+
+		tokens.push(PmlToken::Tag { name, argument });
+	}
+
+	tokens
+}
+
+/// Return the chapter index of a PML document
+///
+/// Chapters are marked either by a visible title (`\x` or `\Xn`), or an invisible chapter marker
+/// (`\Cn="title"`).
+pub fn chapters(tokens: &[PmlToken]) -> Vec<PmlChapter> {
+	let mut chapters = Vec::new();
+	let mut current: Option<(String, String)> = None;
+
+	for token in tokens.iter() {
+		match token {
+			PmlToken::Tag { name, argument } => {
+				if let Some((open, title)) = current.as_ref() {
+					if open == name {
+						chapters.push(PmlChapter {
+							level: chapter_level(name).unwrap_or(0),
+							title: title.trim().to_string(),
+						});
+						current = None;
+					}
+
+					continue;
+				}
+
+				match (name.as_str(), argument) {
+					("x", _) => current = Some((name.clone(), String::new())),
+					(x, _) if x.starts_with('X') => {
+						current = Some((name.clone(), String::new()));
+					}
+					(x, Some(title)) if x.starts_with('C') => chapters.push(PmlChapter {
+						level: chapter_level(x).unwrap_or(0),
+						title: title.clone(),
+					}),
+					_ => {}
+				}
+			}
+
+			PmlToken::Text(text) => {
+				if let Some((_, title)) = current.as_mut() {
+					title.push_str(text);
+				}
+			}
+		}
+	}
+
+	chapters
+}
+
+/// Return the level of a chapter tag (`x`, `Xn`, or `Cn`)
+fn chapter_level(name: &str) -> Option<u8> {
+	match name {
+		"x" => Some(0),
+		_ => name.get(1..)?.parse().ok(),
+	}
+}
+
+/// Convert a PML document to plain text
+pub fn to_text(tokens: &[PmlToken]) -> String {
+	let mut out = String::new();
+	let mut hidden = false;
+
+	for token in tokens.iter() {
+		match token {
+			PmlToken::Text(text) if !hidden => out.push_str(text),
+			PmlToken::Text(_) => {}
+			PmlToken::Tag { name, .. } if hidden && name != "v" => {}
+			PmlToken::Tag { name, .. } => match name.as_str() {
+				"v" => hidden = !hidden,
+				"p" => out.push_str("\n\n"),
+				"x" | "X0" | "X1" | "X2" | "X3" | "X4" => out.push('\n'),
+				_ => {}
+			},
+		}
+	}
+
+	out
+}
+
+/// Escape text for inclusion in HTML
+pub fn escape_html(text: &str) -> String {
+	let mut out = String::with_capacity(text.len());
+	for ch in text.chars() {
+		match ch {
+			'&' => out.push_str("&amp;"),
+			'<' => out.push_str("&lt;"),
+			'>' => out.push_str("&gt;"),
+			'"' => out.push_str("&quot;"),
+			_ => out.push(ch),
+		}
+	}
+
+	out
+}
+
+/// Return the opening and closing HTML for a toggle tag
+fn toggle_html(name: &str, argument: Option<&str>) -> (String, String) {
+	let simple = |tag: &str| (format!("<{}>", tag), format!("</{}>", tag));
+	let styled = |tag: &str, style: &str| {
+		(
+			format!("<{} style=\"{}\">", tag, style),
+			format!("</{}>", tag),
+		)
+	};
+
+	match name {
+		"x" => simple("h1"),
+		"X0" | "X1" | "X2" | "X3" | "X4" => {
+			simple(&format!("h{}", chapter_level(name).unwrap_or(0) + 1))
+		}
+		"c" => styled("div", "text-align: center"),
+		"r" => styled("div", "text-align: right"),
+		"t" => styled("div", "margin-left: 5%"),
+		"i" => simple("i"),
+		"u" => simple("u"),
+		"o" => simple("del"),
+		"b" | "B" => simple("b"),
+		"k" => styled("span", "font-variant: small-caps"),
+		"l" => styled("span", "font-size: larger"),
+		"s" => styled("span", "font-size: smaller"),
+		"Sp" => simple("sup"),
+		"Sb" => simple("sub"),
+		"q" => (
+			format!("<a href=\"{}\">", escape_html(argument.unwrap_or_default())),
+			"</a>".to_string(),
+		),
+		"Fn" => (
+			format!(
+				"<a href=\"#footnote-{}\">",
+				escape_html(argument.unwrap_or_default())
+			),
+			"</a>".to_string(),
+		),
+		"Sd" => (
+			format!(
+				"<a href=\"#sidebar-{}\">",
+				escape_html(argument.unwrap_or_default())
+			),
+			"</a>".to_string(),
+		),
+		_ => (String::new(), String::new()),
+	}
+}
+
+/// Convert a PML document to an HTML fragment
+///
+/// Images are referred to by their name within the book.
+pub fn to_html(tokens: &[PmlToken]) -> String {
+	let mut out = String::new();
+	let mut open: Vec<(String, String)> = Vec::new();
+
+	for token in tokens.iter() {
+		let hidden = open.iter().any(|(name, _)| name == "v");
+		match token {
+			PmlToken::Text(_) if hidden => {}
+			PmlToken::Tag { name, .. } if hidden && name != "v" => {}
+			PmlToken::Text(text) => {
+				out.push_str(&escape_html(text).replace('\n', "<br />\n"));
+			}
+
+			PmlToken::Tag { name, argument } if TOGGLE_TAGS.contains(&name.as_str()) => {
+				if let Some(pos) = open.iter().rposition(|(x, _)| x == name) {
+					// Close any formatting opened inside this tag, then the tag itself
+					for (_, close) in open.drain(pos..).rev() {
+						out.push_str(&close);
+					}
+				} else {
+					let (start, close) = toggle_html(name, argument.as_deref());
+					out.push_str(&start);
+					open.push((name.clone(), close));
+				}
+			}
+
+			PmlToken::Tag { name, argument } => {
+				let argument = escape_html(argument.as_deref().unwrap_or_default());
+				match name.as_str() {
+					"p" => out.push_str("<div style=\"page-break-after: always\"></div>\n"),
+					"m" => out.push_str(&format!("<img src=\"{}\" />", argument)),
+					"Q" => out.push_str(&format!("<a id=\"{}\"></a>", argument)),
+					"w" => out.push_str(&format!("<hr width=\"{}\" />", argument)),
+					"-" => out.push_str("&shy;"),
+					_ => {}
+				}
+			}
+		}
+	}
+
+	for (_, close) in open.drain(..).rev() {
+		out.push_str(&close);
+	}
+
+	out
+}
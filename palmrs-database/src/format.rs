@@ -14,7 +14,8 @@ use crate::{
 	info::{category::AppInfoCategories, ExtraInfoRecord, NullExtraInfo},
 	record::{
 		pdb_record::{PdbRecordHeader, RecordAttributes},
-		DatabaseRecord, DatabaseRecordHelpers,
+		DatabaseRecord,
+		DatabaseRecordHelpers,
 	},
 };
 
@@ -36,6 +37,7 @@ pub trait DatabaseFormat {
 }
 
 /// Implementation of [`DatabaseFormat`] for PRC databases
+#[derive(Clone, PartialEq)]
 pub struct PrcDatabase;
 impl DatabaseFormat for PrcDatabase {
 	const USES_COMPAT_PADDING: bool = false;
@@ -52,6 +54,7 @@ impl DatabaseFormat for PrcDatabase {
 }
 
 /// Implementation of [`DatabaseFormat`] for PDB databases
+#[derive(Clone, PartialEq)]
 pub struct PdbDatabase;
 impl DatabaseFormat for PdbDatabase {
 	const USES_COMPAT_PADDING: bool = false;
@@ -68,6 +71,7 @@ impl DatabaseFormat for PdbDatabase {
 }
 
 /// Implementation of [`DatabaseFormat`] for PDB databases that contain category information
+#[derive(Clone, PartialEq)]
 pub struct PdbWithCategoriesDatabase;
 impl DatabaseFormat for PdbWithCategoriesDatabase {
 	const USES_COMPAT_PADDING: bool = true;
@@ -103,6 +107,23 @@ pub struct PalmDatabase<T: DatabaseFormat> {
 }
 
 impl<T: DatabaseFormat> PalmDatabase<T> {
+	/// Create a new database, containing no records, with the given header and app info
+	///
+	/// The record count and offsets in the header are kept up to date as records are added.
+	pub fn new(header: DatabaseHeader, app_info: T::AppInfoRecord) -> Self {
+		let mut this = Self {
+			header,
+			app_info,
+			application_reserved: Vec::new(),
+			records: Vec::new(),
+			original_data: Vec::new(),
+			_marker: PhantomData,
+		};
+
+		this.update_layout();
+		this
+	}
+
 	pub fn from_bytes<'a>(data: &'a [u8]) -> Result<Self, io::Error> {
 		let mut rdr = Cursor::new(data);
 		let header = DatabaseHeader::from_bytes(&mut rdr)?;
@@ -193,6 +214,49 @@ impl<T: DatabaseFormat> PalmDatabase<T> {
 		&self.records
 	}
 
+	/// Return the data between the app info record and the first record's data
+	///
+	/// Applications that extend the standard app info record (such as the PIM applications,
+	/// which add their own fields after the categories) have those extra fields here.
+	pub fn application_reserved(&self) -> &[u8] {
+		&self.application_reserved
+	}
+
+	/// Replace the data between the app info record and the first record's data
+	pub fn set_application_reserved(&mut self, data: Vec<u8>) {
+		self.application_reserved = data;
+		self.update_layout();
+	}
+
+	/// Recalculate the record count, app info offset, and record data offsets
+	///
+	/// Record data is laid out contiguously, directly after the record headers, app info, and any
+	/// reserved data, which is how [`to_bytes`][PalmDatabase::to_bytes] writes it out.
+	fn update_layout(&mut self) {
+		let mut offset = DatabaseHeader::SIZE
+			+ self
+				.records
+				.iter()
+				.map(|(hdr, _)| hdr.struct_len())
+				.sum::<usize>();
+		if T::USES_COMPAT_PADDING {
+			offset += COMPAT_PADDING_LEN;
+		}
+
+		self.header.record_count = self.records.len() as u16;
+		if !self.app_info.data_empty() || !self.application_reserved.is_empty() {
+			self.header.app_info_id = offset as u32;
+		}
+
+		offset += self.app_info.to_bytes().map(|x| x.len()).unwrap_or(0);
+		offset += self.application_reserved.len();
+
+		for (hdr, data) in self.records.iter_mut() {
+			hdr.set_data_offset(offset as u32);
+			offset += data.len();
+		}
+	}
+
 	/// Create a new record in the database, returning the ID of the new record
 	pub fn insert_record(&mut self, attributes: RecordAttributes, data: &[u8]) -> u32 {
 		let headers = self
@@ -222,10 +286,60 @@ impl<T: DatabaseFormat> PalmDatabase<T> {
 		};
 
 		self.records.push((record, data.to_owned()));
+		self.update_layout();
 
 		unique_id
 	}
 
+	/// Create a new record in the database with the given unique ID
+	///
+	/// Returns an error if the database already contains a record with that ID.
+	pub fn insert_record_with_id(
+		&mut self,
+		attributes: RecordAttributes,
+		unique_id: u32,
+		data: &[u8],
+	) -> Result<(), io::Error> {
+		let exists = self
+			.records
+			.iter()
+			.any(|(hdr, _)| hdr.unique_id() == Some(unique_id));
+		if exists {
+			return Err(io::Error::other(format!(
+				"database already contains record {}",
+				unique_id
+			)));
+		}
+
+		let data_len = match data.len() {
+			0 => None,
+			other => Some(other as u32),
+		};
+		let record = T::RecordHeader::construct_record(attributes, unique_id, 0, data_len);
+
+		self.records.push((record, data.to_owned()));
+		self.update_layout();
+
+		Ok(())
+	}
+
+	/// Replace the data of the record or resource at the given index, keeping its header
+	pub fn set_record_data(&mut self, index: usize, data: &[u8]) -> Result<(), io::Error> {
+		let (hdr, record_data) = self
+			.records
+			.get_mut(index)
+			.ok_or_else(|| io::Error::other(format!("no record at index {}", index)))?;
+
+		hdr.set_data_len(match data.len() {
+			0 => None,
+			other => Some(other as u32),
+		});
+		*record_data = data.to_owned();
+		self.update_layout();
+
+		Ok(())
+	}
+
 	/// Create a new resource in the database, returning the ID of the new record
 	pub fn insert_resource(&mut self, name: &[u8; 4], data: &[u8]) -> u16 {
 		let headers = self
@@ -255,9 +369,43 @@ impl<T: DatabaseFormat> PalmDatabase<T> {
 		};
 
 		self.records.push((record, data.to_owned()));
+		self.update_layout();
 
 		unique_id
 	}
+
+	/// Create a new resource in the database with the given type and resource ID
+	///
+	/// Returns an error if the database already contains a resource with that type and ID.
+	pub fn insert_resource_with_id(
+		&mut self,
+		name: &[u8; 4],
+		resource_id: u16,
+		data: &[u8],
+	) -> Result<(), io::Error> {
+		let exists = self.records.iter().any(|(hdr, _)| {
+			hdr.name_str().map(str::as_bytes) == Some(&name[..])
+				&& hdr.resource_id() == Some(resource_id)
+		});
+		if exists {
+			return Err(io::Error::other(format!(
+				"database already contains resource {:?} {}",
+				String::from_utf8_lossy(name),
+				resource_id
+			)));
+		}
+
+		let data_len = match data.len() {
+			0 => None,
+			other => Some(other as u32),
+		};
+		let record = T::RecordHeader::construct_resource(name, resource_id, 0, data_len);
+
+		self.records.push((record, data.to_owned()));
+		self.update_layout();
+
+		Ok(())
+	}
 }
 
 impl<T: DatabaseFormat> Debug for PalmDatabase<T> {
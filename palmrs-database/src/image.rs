@@ -0,0 +1,189 @@
+//! Simple in-memory images, used when rendering resources
+//!
+//! These exist so that things like fonts and bitmaps can be previewed and converted without
+//! pulling in an image processing library. [`MonoImage::to_pbm`] produces a binary PBM file, which
+//! nearly every image tool can read, and [`RgbaImage::to_png`] produces a PNG file.
+
+use core::fmt;
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression, Crc};
+
+/// A 1-bit-per-pixel image, where `true` is a set (black) pixel
+#[derive(Clone, PartialEq, Default)]
+pub struct MonoImage {
+	width: usize,
+	height: usize,
+	pixels: Vec<bool>,
+}
+
+impl MonoImage {
+	/// Create a blank (all white) image of the given size
+	pub fn new(width: usize, height: usize) -> Self {
+		Self {
+			width,
+			height,
+			pixels: vec![false; width * height],
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	/// Return the pixel at the given position, or `false` if it is outside the image
+	pub fn get(&self, x: usize, y: usize) -> bool {
+		if x >= self.width || y >= self.height {
+			return false;
+		}
+
+		self.pixels[(y * self.width) + x]
+	}
+
+	/// Set the pixel at the given position, ignoring positions outside the image
+	pub fn set(&mut self, x: usize, y: usize, value: bool) {
+		if x >= self.width || y >= self.height {
+			return;
+		}
+
+		self.pixels[(y * self.width) + x] = value;
+	}
+
+	/// Encode the image as a binary (`P4`) PBM file
+	pub fn to_pbm(&self) -> Vec<u8> {
+		let mut out = format!("P4\n{} {}\n", self.width, self.height).into_bytes();
+		let row_bytes = self.width.div_ceil(8);
+		for y in 0..self.height {
+			let mut row = vec![0_u8; row_bytes];
+			for x in 0..self.width {
+				if self.get(x, y) {
+					row[x / 8] |= 0x80 >> (x % 8);
+				}
+			}
+
+			out.extend_from_slice(&row);
+		}
+
+		out
+	}
+}
+
+/// Debug output draws the image as text, one line per row, with `#` for set pixels
+impl fmt::Debug for MonoImage {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "MonoImage {}x{}", self.width, self.height)?;
+		for y in 0..self.height {
+			let row = (0..self.width)
+				.map(|x| if self.get(x, y) { '#' } else { '.' })
+				.collect::<String>();
+			writeln!(f, "{}", row)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// An 8-bit-per-channel RGBA image
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RgbaImage {
+	width: usize,
+	height: usize,
+	pixels: Vec<[u8; 4]>,
+}
+
+impl RgbaImage {
+	/// Create a blank (transparent) image of the given size
+	pub fn new(width: usize, height: usize) -> Self {
+		Self {
+			width,
+			height,
+			pixels: vec![[0; 4]; width * height],
+		}
+	}
+
+	pub fn width(&self) -> usize {
+		self.width
+	}
+
+	pub fn height(&self) -> usize {
+		self.height
+	}
+
+	/// Return the pixel at the given position, or transparent if it is outside the image
+	pub fn get(&self, x: usize, y: usize) -> [u8; 4] {
+		if x >= self.width || y >= self.height {
+			return [0; 4];
+		}
+
+		self.pixels[(y * self.width) + x]
+	}
+
+	/// Set the pixel at the given position, ignoring positions outside the image
+	pub fn set(&mut self, x: usize, y: usize, value: [u8; 4]) {
+		if x >= self.width || y >= self.height {
+			return;
+		}
+
+		self.pixels[(y * self.width) + x] = value;
+	}
+
+	/// Encode the image as a PNG file
+	pub fn to_png(&self) -> Vec<u8> {
+		let mut out = b"\x89PNG\r\n\x1a\n".to_vec();
+
+		let mut header = Vec::new();
+		header.extend_from_slice(&(self.width as u32).to_be_bytes());
+		header.extend_from_slice(&(self.height as u32).to_be_bytes());
+		// 8 bits per channel, RGBA, default compression, filter and interlace methods
+		header.extend_from_slice(&[8, 6, 0, 0, 0]);
+		write_png_chunk(&mut out, b"IHDR", &header);
+
+		// Each row is preceded by its filter type, which is always `None` here
+		let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+		for row in self.pixels.chunks(self.width.max(1)) {
+			let mut data = Vec::with_capacity((row.len() * 4) + 1);
+			data.push(0);
+			for pixel in row.iter() {
+				data.extend_from_slice(pixel);
+			}
+
+			// Writing to a Vec can't fail
+			encoder.write_all(&data).unwrap();
+		}
+
+		write_png_chunk(&mut out, b"IDAT", &encoder.finish().unwrap());
+		write_png_chunk(&mut out, b"IEND", &[]);
+
+		out
+	}
+}
+
+impl From<&MonoImage> for RgbaImage {
+	fn from(image: &MonoImage) -> Self {
+		let mut out = Self::new(image.width, image.height);
+		for y in 0..image.height {
+			for x in 0..image.width {
+				let value = if image.get(x, y) { 0x00 } else { 0xFF };
+				out.set(x, y, [value, value, value, 0xFF]);
+			}
+		}
+
+		out
+	}
+}
+
+/// Append a PNG chunk, with its length and checksum, to `out`
+fn write_png_chunk(out: &mut Vec<u8>, chunk_type: &[u8; 4], data: &[u8]) {
+	let mut crc = Crc::new();
+	crc.update(chunk_type);
+	crc.update(data);
+
+	out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+	out.extend_from_slice(chunk_type);
+	out.extend_from_slice(data);
+	out.extend_from_slice(&crc.sum().to_be_bytes());
+}
@@ -7,7 +7,17 @@ use std::io::{self, Cursor, Read, Write};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::{header::DatabaseHeader, info::ExtraInfoRecord};
+use crate::{
+	header::DatabaseHeader,
+	info::ExtraInfoRecord,
+	text::{palm_to_string, trim_null},
+};
+
+/// Type codes of databases whose app info block starts with the standard categories
+///
+/// Most applications use `DATA` for their record databases, but some (such as GNU KeyRing, with
+/// `Gkyr`) have their own type code.
+const CATEGORY_TYPE_CODES: &[&[u8; 4]] = &[b"DATA", b"Gkyr"];
 
 /// Representation of an item category
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -69,7 +79,7 @@ pub struct AppInfoCategories {
 impl AppInfoCategories {
 	pub fn from_bytes(hdr: &DatabaseHeader, rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
 		// Do a quick check by type/creator codes for whether we should actually have categories
-		if &hdr.type_code[..] != b"DATA" {
+		if !CATEGORY_TYPE_CODES.contains(&&hdr.type_code) {
 			return Ok(Default::default());
 		}
 
@@ -110,6 +120,14 @@ impl AppInfoCategories {
 			is_data: true,
 		})
 	}
+
+	/// Return the name of the category with the given ID
+	pub fn category_name(&self, category_id: u8) -> Option<String> {
+		self.categories
+			.iter()
+			.find(|x| x.category_id == category_id)
+			.map(|x| palm_to_string(trim_null(&x.name)))
+	}
 }
 
 impl ExtraInfoRecord for AppInfoCategories {
@@ -0,0 +1,749 @@
+//! GNU KeyRing password databases (`Keys-Gtkr`)
+//!
+//! Each KeyRing record holds a single entry: a name, which is stored in the clear so that the list
+//! of entries can be shown while the database is locked, followed by the account, password, notes
+//! and date of the last change, which are encrypted with a key derived from the master password.
+//! There are two formats, told apart by the database version:
+//!
+//! - Version 4 (KeyRing 1.x) keeps a salted MD5 hash of the master password in the first record.
+//!   Entries are encrypted with two-key triple DES in ECB mode, keyed with the MD5 hash of the
+//!   master password, and hold the NUL-terminated name, then the encrypted NUL-terminated account,
+//!   password and notes, and the packed date.
+//! - Version 5 (KeyRing 2.x) keeps the salt, the number of PBKDF2 iterations, the cipher, and a
+//!   check hash of the key in the app info block, after the categories. The key is derived with
+//!   PBKDF2-HMAC-SHA1, and entries are encrypted with triple DES or AES in CBC mode. Records are
+//!   lists of labelled fields: the name field, then the IV, then the other fields, encrypted.
+//!
+//! [`KeyringDatabase::unlock`] checks a master password, returning the [`KeyringKey`] that
+//! entries are decrypted and encrypted with. This module is only available with the `keyring`
+//! feature enabled.
+
+use std::{
+	fmt::{self, Debug},
+	io::{self, Cursor, Read, Write},
+};
+
+use aes::{Aes128, Aes256};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use cbc::cipher::{
+	block_padding::NoPadding,
+	generic_array::GenericArray,
+	BlockCipher,
+	BlockDecrypt,
+	BlockDecryptMut,
+	BlockEncrypt,
+	BlockEncryptMut,
+	KeyInit,
+	KeyIvInit,
+};
+use des::{TdesEde2, TdesEde3};
+use hmac::Hmac;
+use md5::{Digest, Md5};
+use sha1::Sha1;
+
+use crate::{
+	pim::{check_creator, PackedDate},
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	text::{palm_to_string, read_cstr, string_to_palm, trim_null},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the KeyRing database
+pub const KEYRING_DATABASE_NAME: &str = "Keys-Gtkr";
+
+/// Database type code of the KeyRing database
+pub const KEYRING_TYPE_CODE: &[u8; 4] = b"Gkyr";
+
+/// Creator code of the KeyRing database
+pub const KEYRING_CREATOR_CODE: &[u8; 4] = b"Gtkr";
+
+/// Database version of the KeyRing 1.x (MD5 and DES) format
+pub const KEYRING_VERSION_LEGACY: u16 = 4;
+
+/// Database version of the KeyRing 2.x (PBKDF2, and DES or AES) format
+pub const KEYRING_VERSION: u16 = 5;
+
+/// Size of the salt in the password record of a version 4 database
+const LEGACY_SALT_SIZE: usize = 4;
+
+/// Size of the salted password that is hashed in a version 4 database, which is padded with zeros
+const LEGACY_HASH_BLOCK_SIZE: usize = 64;
+
+/// Field label: entry name
+const FIELD_NAME: u8 = 0;
+
+/// Field label: account name
+const FIELD_ACCOUNT: u8 = 1;
+
+/// Field label: password
+const FIELD_PASSWORD: u8 = 2;
+
+/// Field label: date of the last change
+const FIELD_DATE: u8 = 3;
+
+/// Field label: notes
+const FIELD_NOTES: u8 = 255;
+
+/// Field length marking the end of the fields of a record
+const FIELD_END: u16 = 0xFFFF;
+
+fn crypto_error(message: &str) -> io::Error {
+	io::Error::other(message.to_string())
+}
+
+/// Cipher used to encrypt the entries of a version 5 database
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum KeyringCipher {
+	/// Entries are not encrypted
+	None,
+
+	/// Three-key triple DES (EDE) in CBC mode
+	TripleDes,
+
+	/// AES with a 128-bit key in CBC mode
+	Aes128,
+
+	/// AES with a 256-bit key in CBC mode
+	Aes256,
+
+	Unknown(u16),
+}
+
+impl KeyringCipher {
+	/// Length of the key derived from the master password, in bytes
+	pub fn key_len(&self) -> Option<usize> {
+		match self {
+			Self::None => Some(8),
+			Self::TripleDes => Some(24),
+			Self::Aes128 => Some(16),
+			Self::Aes256 => Some(32),
+			Self::Unknown(_) => None,
+		}
+	}
+
+	/// Block size of the cipher, which is also the size of the IV of each record, in bytes
+	pub fn block_size(&self) -> Option<usize> {
+		match self {
+			Self::None => Some(1),
+			Self::TripleDes => Some(8),
+			Self::Aes128 | Self::Aes256 => Some(16),
+			Self::Unknown(_) => None,
+		}
+	}
+}
+
+impl From<u16> for KeyringCipher {
+	fn from(value: u16) -> Self {
+		match value {
+			0 => Self::None,
+			1 => Self::TripleDes,
+			2 => Self::Aes128,
+			3 => Self::Aes256,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<KeyringCipher> for u16 {
+	fn from(value: KeyringCipher) -> Self {
+		match value {
+			KeyringCipher::None => 0,
+			KeyringCipher::TripleDes => 1,
+			KeyringCipher::Aes128 => 2,
+			KeyringCipher::Aes256 => 3,
+			KeyringCipher::Unknown(x) => x,
+		}
+	}
+}
+
+/// The app info fields of a version 5 database, following the standard categories
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyringAppInfo {
+	pub salt: [u8; 8],
+	pub iterations: u16,
+	pub cipher: KeyringCipher,
+
+	/// The first 8 bytes of the SHA-1 hash of the key followed by the salt
+	pub hash: [u8; 8],
+}
+
+impl KeyringAppInfo {
+	/// Size of the fields, in bytes
+	pub const SIZE: usize = 20;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut salt = [0u8; 8];
+		rdr.read_exact(&mut salt)?;
+		let iterations = rdr.read_u16::<BigEndian>()?;
+		let cipher = KeyringCipher::from(rdr.read_u16::<BigEndian>()?);
+		let mut hash = [0u8; 8];
+		rdr.read_exact(&mut hash)?;
+
+		Ok(Self {
+			salt,
+			iterations,
+			cipher,
+			hash,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(Self::SIZE));
+		cursor.write_all(&self.salt)?;
+		cursor.write_u16::<BigEndian>(self.iterations)?;
+		cursor.write_u16::<BigEndian>(self.cipher.into())?;
+		cursor.write_all(&self.hash)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+#[derive(Clone, PartialEq)]
+enum KeyKind {
+	/// MD5 hash of the master password, used as a two-key triple DES key
+	Legacy([u8; 16]),
+
+	/// Key derived with PBKDF2 for the given cipher
+	Derived { cipher: KeyringCipher, key: Vec<u8> },
+}
+
+/// The key that the entries of an unlocked KeyRing database are encrypted with
+#[derive(Clone, PartialEq)]
+pub struct KeyringKey(KeyKind);
+
+impl KeyringKey {
+	/// Key of a version 4 database
+	fn legacy(password: &[u8]) -> Self {
+		let mut key = [0u8; 16];
+		key.copy_from_slice(&Md5::digest(password));
+		Self(KeyKind::Legacy(key))
+	}
+
+	/// Key of a version 5 database, derived from the salt and iteration count in the app info
+	fn derive(password: &[u8], app_info: &KeyringAppInfo) -> Result<Self, io::Error> {
+		let key_len = app_info.cipher.key_len().ok_or_else(|| {
+			io::Error::other(format!("unsupported KeyRing cipher {:?}", app_info.cipher))
+		})?;
+
+		let mut key = vec![0u8; key_len];
+		pbkdf2::pbkdf2::<Hmac<Sha1>>(
+			password,
+			&app_info.salt,
+			app_info.iterations as u32,
+			&mut key,
+		)
+		.map_err(|_| crypto_error("invalid KeyRing key length"))?;
+
+		Ok(Self(KeyKind::Derived {
+			cipher: app_info.cipher,
+			key,
+		}))
+	}
+
+	/// Hash that a version 5 database checks the derived key against
+	fn check_hash(&self, salt: &[u8]) -> [u8; 8] {
+		let mut hasher = Sha1::new();
+		if let KeyKind::Derived { key, .. } = &self.0 {
+			hasher.update(key);
+		}
+		hasher.update(salt);
+
+		let mut hash = [0u8; 8];
+		hash.copy_from_slice(&hasher.finalize()[..8]);
+		hash
+	}
+
+	fn is_legacy(&self) -> bool {
+		matches!(self.0, KeyKind::Legacy(_))
+	}
+
+	/// Encrypt the given data, padding it with zeros to a whole number of blocks
+	///
+	/// For version 5 databases, a random IV is generated and returned before the encrypted data.
+	fn encrypt(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+		let (cipher, key) = match &self.0 {
+			KeyKind::Legacy(key) => {
+				let mut buf = pad_to_block(data, 8);
+				let des = TdesEde2::new_from_slice(key)
+					.map_err(|_| crypto_error("invalid KeyRing key length"))?;
+				for block in buf.chunks_exact_mut(8) {
+					des.encrypt_block(GenericArray::from_mut_slice(block));
+				}
+
+				return Ok(buf);
+			}
+			KeyKind::Derived { cipher, key } => (cipher, key),
+		};
+
+		let block_size = cipher
+			.block_size()
+			.ok_or_else(|| crypto_error("unsupported KeyRing cipher"))?;
+		let mut iv = vec![0u8; block_size];
+		fill_random(&mut iv)?;
+
+		let mut buf = pad_to_block(data, block_size);
+		match cipher {
+			KeyringCipher::TripleDes => cbc_encrypt::<TdesEde3>(key, &iv, &mut buf)?,
+			KeyringCipher::Aes128 => cbc_encrypt::<Aes128>(key, &iv, &mut buf)?,
+			KeyringCipher::Aes256 => cbc_encrypt::<Aes256>(key, &iv, &mut buf)?,
+			_ => {}
+		}
+
+		iv.extend_from_slice(&buf);
+		Ok(iv)
+	}
+
+	/// Decrypt the given data, which for version 5 databases starts with the IV
+	fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+		let (cipher, key) = match &self.0 {
+			KeyKind::Legacy(key) => {
+				if !data.len().is_multiple_of(8) {
+					return Err(crypto_error(
+						"encrypted data is not a whole number of blocks",
+					));
+				}
+
+				let mut buf = data.to_vec();
+				let des = TdesEde2::new_from_slice(key)
+					.map_err(|_| crypto_error("invalid KeyRing key length"))?;
+				for block in buf.chunks_exact_mut(8) {
+					des.decrypt_block(GenericArray::from_mut_slice(block));
+				}
+
+				return Ok(buf);
+			}
+			KeyKind::Derived { cipher, key } => (cipher, key),
+		};
+
+		let block_size = cipher
+			.block_size()
+			.ok_or_else(|| crypto_error("unsupported KeyRing cipher"))?;
+		if data.len() < block_size || !data.len().is_multiple_of(block_size) {
+			return Err(crypto_error(
+				"encrypted data is not a whole number of blocks",
+			));
+		}
+
+		let (iv, data) = data.split_at(block_size);
+		let mut buf = data.to_vec();
+		match cipher {
+			KeyringCipher::TripleDes => cbc_decrypt::<TdesEde3>(key, iv, &mut buf)?,
+			KeyringCipher::Aes128 => cbc_decrypt::<Aes128>(key, iv, &mut buf)?,
+			KeyringCipher::Aes256 => cbc_decrypt::<Aes256>(key, iv, &mut buf)?,
+			_ => {}
+		}
+
+		Ok(buf)
+	}
+}
+
+impl Debug for KeyringKey {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		// Don't leak the key into logs
+		f.write_str("KeyringKey { .. }")
+	}
+}
+
+/// Fill the buffer with random data, for a salt or IV
+fn fill_random(buf: &mut [u8]) -> Result<(), io::Error> {
+	getrandom::getrandom(buf)
+		.map_err(|e| io::Error::other(format!("failed to generate random data: {}", e)))
+}
+
+fn pad_to_block(data: &[u8], block_size: usize) -> Vec<u8> {
+	let mut buf = data.to_vec();
+	let padding = (block_size - data.len() % block_size) % block_size;
+	buf.resize(data.len() + padding, 0);
+	buf
+}
+
+fn cbc_encrypt<C>(key: &[u8], iv: &[u8], buf: &mut [u8]) -> Result<(), io::Error>
+where
+	C: BlockCipher + BlockEncrypt + KeyInit,
+{
+	let len = buf.len();
+	cbc::Encryptor::<C>::new_from_slices(key, iv)
+		.map_err(|_| crypto_error("invalid KeyRing key length"))?
+		.encrypt_padded_mut::<NoPadding>(buf, len)
+		.map_err(|_| crypto_error("encrypted data is not a whole number of blocks"))?;
+
+	Ok(())
+}
+
+fn cbc_decrypt<C>(key: &[u8], iv: &[u8], buf: &mut [u8]) -> Result<(), io::Error>
+where
+	C: BlockCipher + BlockDecrypt + KeyInit,
+{
+	cbc::Decryptor::<C>::new_from_slices(key, iv)
+		.map_err(|_| crypto_error("invalid KeyRing key length"))?
+		.decrypt_padded_mut::<NoPadding>(buf)
+		.map_err(|_| crypto_error("encrypted data is not a whole number of blocks"))?;
+
+	Ok(())
+}
+
+/// Hash of the salted master password, as kept in the first record of a version 4 database
+fn legacy_password_hash(salt: &[u8], password: &[u8]) -> [u8; 16] {
+	let mut data = [salt, password].concat();
+	if data.len() < LEGACY_HASH_BLOCK_SIZE {
+		data.resize(LEGACY_HASH_BLOCK_SIZE, 0);
+	}
+
+	let mut hash = [0u8; 16];
+	hash.copy_from_slice(&Md5::digest(&data));
+	hash
+}
+
+/// Read a version 5 record field, returning its label and data
+fn read_field(rdr: &mut Cursor<&[u8]>) -> Result<Option<(u8, Vec<u8>)>, io::Error> {
+	let len = match rdr.read_u16::<BigEndian>()? {
+		FIELD_END => return Ok(None),
+		x => x as usize,
+	};
+	let label = rdr.read_u8()?;
+	let _font = rdr.read_u8()?;
+
+	let mut data = vec![0u8; len];
+	rdr.read_exact(&mut data)?;
+
+	// Fields are padded to an even length
+	if len % 2 != 0 {
+		rdr.read_u8()?;
+	}
+
+	Ok(Some((label, data)))
+}
+
+fn write_field<W: Write>(writer: &mut W, label: u8, data: &[u8]) -> Result<(), io::Error> {
+	if data.len() >= FIELD_END as usize {
+		return Err(crypto_error("KeyRing field is too long"));
+	}
+
+	writer.write_u16::<BigEndian>(data.len() as u16)?;
+	writer.write_u8(label)?;
+	writer.write_u8(0)?;
+	writer.write_all(data)?;
+	if !data.len().is_multiple_of(2) {
+		writer.write_u8(0)?;
+	}
+
+	Ok(())
+}
+
+/// A single, decrypted, KeyRing entry
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct KeyringEntry {
+	pub name: String,
+	pub account: String,
+	pub password: String,
+	pub notes: String,
+
+	/// Date the entry was last changed
+	pub date: Option<PackedDate>,
+}
+
+impl KeyringEntry {
+	/// Read and decrypt an entry, using the key of the database it is from
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>, key: &KeyringKey) -> Result<Self, io::Error> {
+		if key.is_legacy() {
+			let name = palm_to_string(&read_cstr(rdr)?);
+			let mut encrypted = Vec::new();
+			rdr.read_to_end(&mut encrypted)?;
+
+			let data = key.decrypt(&encrypted)?;
+			let mut rdr = Cursor::new(&data[..]);
+			let account = palm_to_string(&read_cstr(&mut rdr)?);
+			let password = palm_to_string(&read_cstr(&mut rdr)?);
+			let notes = palm_to_string(&read_cstr(&mut rdr)?);
+
+			// Entries from early versions have no date, leaving just the zero padding
+			let date = match rdr.read_u16::<BigEndian>() {
+				Ok(0) | Err(_) => None,
+				Ok(x) => Some(PackedDate::from_u16(x)),
+			};
+
+			return Ok(Self {
+				name,
+				account,
+				password,
+				notes,
+				date,
+			});
+		}
+
+		let mut entry = Self::default();
+		match read_field(rdr)? {
+			Some((FIELD_NAME, data)) => entry.name = palm_to_string(trim_null(&data)),
+			_ => return Err(crypto_error("KeyRing record does not start with a name")),
+		}
+
+		let mut encrypted = Vec::new();
+		rdr.read_to_end(&mut encrypted)?;
+		let data = key.decrypt(&encrypted)?;
+		let mut rdr = Cursor::new(&data[..]);
+		while let Some((label, data)) = read_field(&mut rdr)? {
+			match label {
+				FIELD_ACCOUNT => entry.account = palm_to_string(trim_null(&data)),
+				FIELD_PASSWORD => entry.password = palm_to_string(trim_null(&data)),
+				FIELD_NOTES => entry.notes = palm_to_string(trim_null(&data)),
+				FIELD_DATE if data.len() >= 2 => {
+					entry.date = Some(PackedDate::from_u16(u16::from_be_bytes([data[0], data[1]])))
+				}
+				_ => {}
+			}
+		}
+
+		Ok(entry)
+	}
+
+	/// Encrypt and write out the entry, using the key of the database it is for
+	pub fn to_bytes(&self, key: &KeyringKey) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+
+		if key.is_legacy() {
+			cursor.write_all(&string_to_palm(&self.name))?;
+			cursor.write_u8(0)?;
+
+			let mut data = Vec::new();
+			for value in [&self.account, &self.password, &self.notes].iter() {
+				data.extend_from_slice(&string_to_palm(value));
+				data.push(0);
+			}
+			if let Some(date) = self.date {
+				data.write_u16::<BigEndian>(date.to_u16())?;
+			}
+
+			cursor.write_all(&key.encrypt(&data)?)?;
+			return Ok(cursor.into_inner());
+		}
+
+		write_field(&mut cursor, FIELD_NAME, &string_to_palm(&self.name))?;
+
+		let mut data = Vec::new();
+		write_field(&mut data, FIELD_ACCOUNT, &string_to_palm(&self.account))?;
+		write_field(&mut data, FIELD_PASSWORD, &string_to_palm(&self.password))?;
+		if let Some(date) = self.date {
+			write_field(&mut data, FIELD_DATE, &date.to_u16().to_be_bytes())?;
+		}
+		if !self.notes.is_empty() {
+			write_field(&mut data, FIELD_NOTES, &string_to_palm(&self.notes))?;
+		}
+		data.write_u16::<BigEndian>(FIELD_END)?;
+
+		cursor.write_all(&key.encrypt(&data)?)?;
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A KeyRing database, along with the app info fields of version 5 databases
+#[derive(Debug, Clone)]
+pub struct KeyringDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+
+	/// The salt, iteration count, cipher and check hash (only in version 5 databases)
+	pub app_info: Option<KeyringAppInfo>,
+}
+
+impl KeyringDatabase {
+	/// Wrap the given database, reading the app info fields of version 5 databases
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		check_creator(&database, &[KEYRING_CREATOR_CODE], "KeyRing")?;
+
+		let app_info = match database.header.version {
+			KEYRING_VERSION_LEGACY => None,
+			KEYRING_VERSION => match database.application_reserved() {
+				x if x.len() >= KeyringAppInfo::SIZE => {
+					Some(KeyringAppInfo::from_bytes(&mut Cursor::new(x))?)
+				}
+				_ => None,
+			},
+			x => {
+				return Err(io::Error::other(format!(
+					"unsupported KeyRing database version {}",
+					x
+				)))
+			}
+		};
+
+		Ok(Self { database, app_info })
+	}
+
+	/// Read the KeyRing database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Write the database out, including the app info fields of version 5 databases
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut database = self.database.clone();
+		if let Some(app_info) = &self.app_info {
+			database.set_application_reserved(app_info.to_bytes()?);
+		}
+
+		database.to_bytes()
+	}
+
+	/// Whether this is a version 4 (KeyRing 1.x) database
+	pub fn is_legacy(&self) -> bool {
+		self.database.header.version == KEYRING_VERSION_LEGACY
+	}
+
+	/// Check the given master password, returning the key to decrypt the entries with
+	pub fn unlock(&self, password: &str) -> Result<KeyringKey, io::Error> {
+		let password = string_to_palm(password);
+
+		if self.is_legacy() {
+			let record = match self.database.list_records_resources().first() {
+				Some((_, x)) if x.len() >= LEGACY_SALT_SIZE + 16 => x,
+				_ => return Err(crypto_error("KeyRing database has no password record")),
+			};
+
+			let (salt, hash) = record.split_at(LEGACY_SALT_SIZE);
+			if legacy_password_hash(salt, &password) != hash[..16] {
+				return Err(crypto_error("incorrect master password"));
+			}
+
+			return Ok(KeyringKey::legacy(&password));
+		}
+
+		let app_info = self
+			.app_info
+			.as_ref()
+			.ok_or_else(|| crypto_error("KeyRing database has no key parameters"))?;
+		let key = KeyringKey::derive(&password, app_info)?;
+		if key.check_hash(&app_info.salt) != app_info.hash {
+			return Err(crypto_error("incorrect master password"));
+		}
+
+		Ok(key)
+	}
+
+	/// Indices of the records holding entries, skipping deleted records and the password record
+	/// of version 4 databases
+	fn entry_indices(&self) -> Vec<usize> {
+		let records = self.database.list_records_resources().iter().enumerate();
+		records
+			.filter(|(idx, (hdr, data))| {
+				let password_record = self.is_legacy() && *idx == 0;
+				let deleted = hdr.attributes().map(|x| x.delete).unwrap_or(false);
+				!password_record && !deleted && !data.is_empty()
+			})
+			.map(|(idx, _)| idx)
+			.collect()
+	}
+
+	/// Decrypt the entries, along with their record indices and attributes
+	fn decrypt_entries(
+		&self,
+		key: &KeyringKey,
+	) -> Result<Vec<(usize, RecordAttributes, KeyringEntry)>, io::Error> {
+		let records = self.database.list_records_resources();
+		let mut entries = Vec::new();
+		for idx in self.entry_indices() {
+			let (hdr, data) = &records[idx];
+			let entry = KeyringEntry::from_bytes(&mut Cursor::new(data), key)?;
+			entries.push((idx, hdr.attributes().unwrap_or_default(), entry));
+		}
+
+		Ok(entries)
+	}
+
+	/// Decrypt all of the entries in the database, with their record attributes (giving the
+	/// category), skipping deleted records and the password record of version 4 databases
+	pub fn entries(
+		&self,
+		key: &KeyringKey,
+	) -> Result<Vec<(RecordAttributes, KeyringEntry)>, io::Error> {
+		Ok(self
+			.decrypt_entries(key)?
+			.into_iter()
+			.map(|(_, attributes, entry)| (attributes, entry))
+			.collect())
+	}
+
+	/// Encrypt and add a new entry, returning the unique ID of its record
+	pub fn insert_entry(
+		&mut self,
+		key: &KeyringKey,
+		attributes: RecordAttributes,
+		entry: &KeyringEntry,
+	) -> Result<u32, io::Error> {
+		let data = entry.to_bytes(key)?;
+		Ok(self.database.insert_record(attributes, &data))
+	}
+
+	/// Encrypt and replace the entry in the record at the given index
+	pub fn set_entry(
+		&mut self,
+		key: &KeyringKey,
+		index: usize,
+		entry: &KeyringEntry,
+	) -> Result<(), io::Error> {
+		if self.is_legacy() && index == 0 {
+			return Err(crypto_error("the first record holds the master password"));
+		}
+
+		let data = entry.to_bytes(key)?;
+		self.database.set_record_data(index, &data)
+	}
+
+	/// Set a new master password, re-encrypting the existing entries with the new key
+	///
+	/// The current key is needed if the database has any entries. The cipher and number of PBKDF2
+	/// iterations are only used by version 5 databases.
+	pub fn set_master_password(
+		&mut self,
+		current: Option<&KeyringKey>,
+		password: &str,
+		cipher: KeyringCipher,
+		iterations: u16,
+	) -> Result<KeyringKey, io::Error> {
+		let entries = match current {
+			Some(key) => self.decrypt_entries(key)?,
+			None if self.entry_indices().is_empty() => Vec::new(),
+			None => {
+				return Err(crypto_error(
+					"the current key is needed to re-encrypt entries",
+				))
+			}
+		};
+
+		let password = string_to_palm(password);
+		let key = if self.is_legacy() {
+			let mut salt = [0u8; LEGACY_SALT_SIZE];
+			fill_random(&mut salt)?;
+
+			let record = [&salt[..], &legacy_password_hash(&salt, &password)].concat();
+			if self.database.list_records_resources().is_empty() {
+				self.database
+					.insert_record(RecordAttributes::default(), &record);
+			} else {
+				self.database.set_record_data(0, &record)?;
+			}
+
+			KeyringKey::legacy(&password)
+		} else {
+			let mut app_info = KeyringAppInfo {
+				salt: [0u8; 8],
+				iterations,
+				cipher,
+				hash: [0u8; 8],
+			};
+			fill_random(&mut app_info.salt)?;
+
+			let key = KeyringKey::derive(&password, &app_info)?;
+			app_info.hash = key.check_hash(&app_info.salt);
+			self.app_info = Some(app_info);
+			key
+		};
+
+		for (idx, _, entry) in entries.iter() {
+			self.set_entry(&key, *idx, entry)?;
+		}
+
+		Ok(key)
+	}
+}
@@ -1,9 +1,25 @@
 //! Support for reading, and eventually writing, the Palm OS database formats (PRC and PDB)
 
+#[cfg(feature = "disasm")]
+pub mod disasm;
+pub mod ereader;
 mod format;
 pub mod header;
+pub mod image;
 pub mod info;
+#[cfg(feature = "keyring")]
+pub mod keyring;
+pub mod notepad;
+pub mod overlay;
+pub mod palmdoc;
+pub mod pim;
+pub mod plucker;
+pub mod pqa;
+pub mod prefs;
 pub mod record;
+pub mod resource;
+pub mod stream;
+pub mod text;
 pub mod time;
 
 pub use self::format::{
@@ -0,0 +1,289 @@
+//! Note Pad databases (`npadDB`)
+//!
+//! Each Note Pad record holds a single handwritten note: the creation and modification dates, an
+//! optional alarm and title, and the sketch itself. A flags word says which of the optional parts
+//! are present. The sketch is a monochrome bitmap, stored either run-length encoded (as pairs of
+//! a repeat count and a byte of pixels, the same as the `RLE` bitmap compression type) or, on
+//! later devices, as a PNG image.
+
+use std::{
+	convert::TryFrom,
+	io::{self, Cursor, Read},
+};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::{
+	image::RgbaImage,
+	record::DatabaseRecord,
+	resource::{
+		bitmap::{Bitmap, BITMAP_FLAG_COMPRESSED, COMPRESSION_RLE},
+		font::DENSITY_STANDARD,
+	},
+	text::palm_to_string,
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the Note Pad database
+pub const NOTEPAD_DATABASE_NAME: &str = "npadDB";
+
+/// Database type code of the Note Pad database
+pub const NOTEPAD_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Creator code of the Note Pad database
+pub const NOTEPAD_CREATOR_CODE: &[u8; 4] = b"npad";
+
+/// Record flag: the record contains a sketch
+pub const NOTEPAD_FLAG_BODY: u16 = 0x0001;
+
+/// Record flag: the record contains a title
+pub const NOTEPAD_FLAG_NAME: u16 = 0x0002;
+
+/// Record flag: the record contains an alarm date
+pub const NOTEPAD_FLAG_ALARM: u16 = 0x0004;
+
+/// Sketch data type: run-length encoded monochrome bitmap
+pub const NOTEPAD_DATA_BITS: u32 = 0;
+
+/// Sketch data type: PNG image
+pub const NOTEPAD_DATA_PNG: u32 = 1;
+
+/// A date and time, as stored in Note Pad records
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct NotePadDate {
+	pub second: u16,
+	pub minute: u16,
+	pub hour: u16,
+	pub day: u16,
+	pub month: u16,
+	pub year: u16,
+	pub weekday: u16,
+}
+
+impl NotePadDate {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let second = rdr.read_u16::<BigEndian>()?;
+		let minute = rdr.read_u16::<BigEndian>()?;
+		let hour = rdr.read_u16::<BigEndian>()?;
+		let day = rdr.read_u16::<BigEndian>()?;
+		let month = rdr.read_u16::<BigEndian>()?;
+		let year = rdr.read_u16::<BigEndian>()?;
+		let weekday = rdr.read_u16::<BigEndian>()?;
+
+		Ok(Self {
+			second,
+			minute,
+			hour,
+			day,
+			month,
+			year,
+			weekday,
+		})
+	}
+
+	/// Convert to a [`NaiveDateTime`], if the date is valid
+	pub fn to_datetime(&self) -> Option<NaiveDateTime> {
+		NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)?.and_hms_opt(
+			self.hour as u32,
+			self.minute as u32,
+			self.second as u32,
+		)
+	}
+}
+
+/// The sketch from a Note Pad record
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotePadSketch {
+	pub width: u32,
+	pub height: u32,
+
+	/// Format of the sketch data ([`NOTEPAD_DATA_BITS`] or [`NOTEPAD_DATA_PNG`])
+	pub data_type: u32,
+
+	pub data: Vec<u8>,
+}
+
+impl NotePadSketch {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let _body_len = rdr.read_u32::<BigEndian>()?;
+		let width = rdr.read_u32::<BigEndian>()?;
+		let height = rdr.read_u32::<BigEndian>()?;
+		let _unknown = rdr.read_u32::<BigEndian>()?;
+		let data_type = rdr.read_u32::<BigEndian>()?;
+		let data_len = rdr.read_u32::<BigEndian>()? as usize;
+
+		let remaining = rdr.get_ref().len() - (rdr.position() as usize).min(rdr.get_ref().len());
+		if data_len > remaining {
+			return Err(io::Error::new(
+				io::ErrorKind::UnexpectedEof,
+				format!(
+					"Note Pad sketch of {} bytes is longer than its record ({} bytes left)",
+					data_len, remaining
+				),
+			));
+		}
+
+		let mut data = vec![0_u8; data_len];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			width,
+			height,
+			data_type,
+			data,
+		})
+	}
+
+	/// Return the sketch as a compressed [`Bitmap`], if it is stored as a run-length encoded
+	/// bitmap
+	///
+	/// The length of the bitmap rows is worked out from the length of the sketch data, as the rows
+	/// are padded beyond the width stored in the record. The padding is cropped off again by
+	/// using the stored width for the bitmap.
+	pub fn to_bitmap(&self) -> Option<Bitmap> {
+		if self.data_type != NOTEPAD_DATA_BITS || self.height == 0 {
+			return None;
+		}
+
+		let length = self.data.chunks(2).map(|x| x[0] as usize).sum::<usize>();
+		let row_bytes = length / self.height as usize;
+		if self.width as usize > row_bytes * 8 {
+			return None;
+		}
+
+		Some(Bitmap {
+			width: u16::try_from(self.width).ok()?,
+			height: u16::try_from(self.height).ok()?,
+			row_bytes: u16::try_from(row_bytes).ok()?,
+			flags: BITMAP_FLAG_COMPRESSED,
+			pixel_size: 1,
+			version: 2,
+			density: DENSITY_STANDARD,
+			compression_type: COMPRESSION_RLE,
+			pixel_format: 0,
+			transparent_value: 0,
+			color_table: Vec::new(),
+			data: self.data.clone(),
+		})
+	}
+
+	/// Convert a run-length encoded sketch to an [`RgbaImage`]
+	pub fn to_image(&self) -> Option<RgbaImage> {
+		self.to_bitmap()?.to_image()
+	}
+
+	/// Return the sketch as a PNG file
+	pub fn to_png(&self) -> Result<Vec<u8>, io::Error> {
+		match self.data_type {
+			NOTEPAD_DATA_PNG => Ok(self.data.clone()),
+			_ => self.to_image().map(|x| x.to_png()).ok_or_else(|| {
+				io::Error::other(format!(
+					"can't convert Note Pad sketch of type {}",
+					self.data_type
+				))
+			}),
+		}
+	}
+}
+
+/// A single Note Pad record
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotePadRecord {
+	pub created: NotePadDate,
+	pub modified: NotePadDate,
+	pub flags: u16,
+	pub alarm: Option<NotePadDate>,
+	pub title: Option<String>,
+	pub sketch: Option<NotePadSketch>,
+}
+
+impl NotePadRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let created = NotePadDate::from_bytes(rdr)?;
+		let modified = NotePadDate::from_bytes(rdr)?;
+		let flags = rdr.read_u16::<BigEndian>()?;
+
+		let alarm = if flags & NOTEPAD_FLAG_ALARM != 0 {
+			Some(NotePadDate::from_bytes(rdr)?)
+		} else {
+			None
+		};
+
+		// The title is NUL-terminated, and padded to an even length
+		let title = if flags & NOTEPAD_FLAG_NAME != 0 {
+			let mut buf = Vec::new();
+			loop {
+				match rdr.read_u8()? {
+					0 => break,
+					x => buf.push(x),
+				}
+			}
+
+			if buf.len() % 2 == 0 {
+				rdr.read_u8()?;
+			}
+
+			Some(palm_to_string(&buf))
+		} else {
+			None
+		};
+
+		let sketch = if flags & NOTEPAD_FLAG_BODY != 0 {
+			Some(NotePadSketch::from_bytes(rdr)?)
+		} else {
+			None
+		};
+
+		Ok(Self {
+			created,
+			modified,
+			flags,
+			alarm,
+			title,
+			sketch,
+		})
+	}
+}
+
+/// The Note Pad database
+#[derive(Debug)]
+pub struct NotePadDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl NotePadDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		if &database.header.creator_code != NOTEPAD_CREATOR_CODE {
+			return Err(io::Error::other(format!(
+				"database creator {:?} is not Note Pad",
+				String::from_utf8_lossy(&database.header.creator_code)
+			)));
+		}
+
+		Ok(Self { database })
+	}
+
+	/// Read the Note Pad database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Decode all of the notes in the database, skipping deleted records
+	pub fn notes(&self) -> Result<Vec<NotePadRecord>, io::Error> {
+		let mut notes = Vec::new();
+		for (hdr, data) in self.database.list_records_resources().iter() {
+			let deleted = hdr.attributes().map(|x| x.delete).unwrap_or(false);
+			if deleted || data.is_empty() {
+				continue;
+			}
+
+			notes.push(NotePadRecord::from_bytes(&mut Cursor::new(data))?);
+		}
+
+		Ok(notes)
+	}
+}
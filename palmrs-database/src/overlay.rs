@@ -0,0 +1,379 @@
+//! Localisation overlay databases
+//!
+//! Palm OS 3.5 and later support overlay databases (type `ovly`), which hold localised copies of
+//! some of the resources of a base PRC. Each overlay contains an `xprf` resource (an
+//! [`OverlaySpec`]) naming the base database's type and creator, the target locale, and every
+//! resource it overlays, along with a checksum of each base resource it replaces so that an
+//! overlay built for a different version of the base is not applied.
+//!
+//! Resource checksums are CRC-32 (IEEE) checksums of the resource data, and the combined base
+//! checksum is the CRC-32 of the big-endian checksums of every base resource the specification
+//! describes, in order.
+//!
+//! [`OverlaidDatabase`] resolves resources through an overlay the same way the Overlay Manager
+//! does, and [`generate_overlay`] builds a new overlay from a localised copy of a base database.
+
+use std::io::{self, Cursor};
+
+use crate::{
+	record::DatabaseRecord,
+	resource::{
+		overlay::{Locale, OverlayKind, OverlayResource, OverlaySpec, OVERLAY_SPEC_VERSION},
+		ResourceType,
+	},
+	PalmDatabase,
+	PrcDatabase,
+};
+
+/// Database type code of overlay databases
+pub const OVERLAY_TYPE_CODE: &[u8; 4] = b"ovly";
+
+/// Resource type code of the overlay specification
+pub const OVERLAY_SPEC_TYPE_CODE: &[u8; 4] = b"xprf";
+
+/// Resource ID of the overlay specification
+pub const OVERLAY_SPEC_RESOURCE_ID: u16 = 0;
+
+/// Resource types the Overlay Manager never overlays
+const NON_OVERLAYABLE_TYPES: &[&[u8; 4]] = &[b"code", b"data", b"rloc", OVERLAY_SPEC_TYPE_CODE];
+
+/// Maximum length of a database name, excluding the terminating null byte
+const DATABASE_NAME_MAX_LEN: usize = 31;
+
+/// Calculate the CRC-32 (IEEE 802.3) checksum of the given data
+pub fn crc32(data: &[u8]) -> u32 {
+	let mut crc = 0xFFFF_FFFF_u32;
+	for byte in data.iter() {
+		crc ^= *byte as u32;
+		for _ in 0..8 {
+			crc = if crc & 1 != 0 {
+				(crc >> 1) ^ 0xEDB8_8320
+			} else {
+				crc >> 1
+			};
+		}
+	}
+
+	!crc
+}
+
+/// Calculate the combined base checksum for the given overlay specification entries
+pub fn base_checksum(resources: &[OverlayResource]) -> u32 {
+	let checksums = resources
+		.iter()
+		.filter(|x| x.describes_base())
+		.flat_map(|x| x.checksum.to_be_bytes().to_vec())
+		.collect::<Vec<u8>>();
+
+	crc32(&checksums)
+}
+
+/// Find a resource by type code and ID within a PRC database
+fn find_resource<'a>(
+	database: &'a PalmDatabase<PrcDatabase>,
+	type_code: &[u8; 4],
+	resource_id: u16,
+) -> Option<&'a [u8]> {
+	database
+		.list_records_resources()
+		.iter()
+		.find(|(hdr, _)| {
+			hdr.name_str().map(str::as_bytes) == Some(&type_code[..])
+				&& hdr.resource_id() == Some(resource_id)
+		})
+		.map(|(_, data)| data.as_slice())
+}
+
+/// Return the type code and ID of every resource in a PRC database
+fn resource_keys(database: &PalmDatabase<PrcDatabase>) -> Vec<([u8; 4], u16)> {
+	database
+		.list_records_resources()
+		.iter()
+		.filter_map(|(hdr, _)| {
+			let mut type_code = [0_u8; 4];
+			type_code.copy_from_slice(hdr.name_str()?.as_bytes().get(0..4)?);
+			Some((type_code, hdr.resource_id()?))
+		})
+		.collect()
+}
+
+fn overlay_error(message: String) -> io::Error {
+	io::Error::other(message)
+}
+
+/// An overlay database, along with its decoded overlay specification
+#[derive(Debug)]
+pub struct OverlayDatabase {
+	pub database: PalmDatabase<PrcDatabase>,
+	pub spec: OverlaySpec,
+}
+
+impl OverlayDatabase {
+	/// Wrap the given overlay database, decoding its overlay specification
+	pub fn from_database(database: PalmDatabase<PrcDatabase>) -> Result<Self, io::Error> {
+		if &database.header.type_code != OVERLAY_TYPE_CODE {
+			return Err(overlay_error(format!(
+				"database type {:?} is not an overlay",
+				String::from_utf8_lossy(&database.header.type_code)
+			)));
+		}
+
+		let spec = match find_resource(&database, OVERLAY_SPEC_TYPE_CODE, OVERLAY_SPEC_RESOURCE_ID)
+		{
+			Some(data) => OverlaySpec::from_bytes(&mut Cursor::new(data))?,
+			None => return Err(overlay_error("overlay has no xprf resource".to_string())),
+		};
+
+		Ok(Self { database, spec })
+	}
+
+	/// Read an overlay database from the given PRC file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PrcDatabase>::from_bytes(data)?)
+	}
+
+	/// Check that this overlay applies to the given base database
+	///
+	/// This checks the base database type and creator, that every base resource the overlay hides
+	/// or replaces is present with the length and checksum recorded in the overlay, that every
+	/// resource the overlay adds or replaces is present in the overlay, and the combined base
+	/// checksum.
+	pub fn validate(&self, base: &PalmDatabase<PrcDatabase>) -> Result<(), io::Error> {
+		let spec = &self.spec;
+		if spec.version > OVERLAY_SPEC_VERSION {
+			return Err(overlay_error(format!(
+				"unsupported overlay version {}",
+				spec.version
+			)));
+		}
+
+		if spec.base_type_code != base.header.type_code
+			|| spec.base_creator_code != base.header.creator_code
+			|| self.database.header.creator_code != base.header.creator_code
+		{
+			return Err(overlay_error(
+				"overlay type and creator don't match the base database".to_string(),
+			));
+		}
+
+		for entry in spec.resources.iter() {
+			let name = format!(
+				"{:?} {}",
+				String::from_utf8_lossy(&entry.type_code),
+				entry.resource_id
+			);
+
+			if entry.describes_base() {
+				let data = find_resource(base, &entry.type_code, entry.resource_id)
+					.ok_or_else(|| overlay_error(format!("base has no resource {}", name)))?;
+
+				if data.len() as u32 != entry.length || crc32(data) != entry.checksum {
+					return Err(overlay_error(format!(
+						"base resource {} doesn't match the overlay checksum",
+						name
+					)));
+				}
+			}
+
+			if let OverlayKind::Add | OverlayKind::Replace = entry.kind {
+				if find_resource(&self.database, &entry.type_code, entry.resource_id).is_none() {
+					return Err(overlay_error(format!("overlay has no resource {}", name)));
+				}
+			}
+		}
+
+		if base_checksum(&spec.resources) != spec.base_checksum {
+			return Err(overlay_error(
+				"overlay base checksum doesn't match".to_string(),
+			));
+		}
+
+		Ok(())
+	}
+}
+
+/// A base database, with an overlay applied to it
+#[derive(Debug, Clone, Copy)]
+pub struct OverlaidDatabase<'a> {
+	pub base: &'a PalmDatabase<PrcDatabase>,
+	pub overlay: Option<&'a OverlayDatabase>,
+}
+
+impl<'a> OverlaidDatabase<'a> {
+	/// Apply the overlay for the given locale to the base database
+	///
+	/// Like the Overlay Manager, overlays that don't target the locale or don't validate against
+	/// the base are skipped, and the base is used on its own if there is no usable overlay - unless
+	/// the base has been stripped of its localisable resources, in which case an error is
+	/// returned.
+	pub fn new(
+		base: &'a PalmDatabase<PrcDatabase>,
+		overlays: &'a [OverlayDatabase],
+		locale: Locale,
+	) -> Result<Self, io::Error> {
+		let overlay = overlays
+			.iter()
+			.filter(|x| x.spec.target_locale == locale)
+			.find(|x| x.validate(base).is_ok());
+
+		if overlay.is_none() {
+			let base_spec = find_resource(base, OVERLAY_SPEC_TYPE_CODE, OVERLAY_SPEC_RESOURCE_ID)
+				.map(|data| OverlaySpec::from_bytes(&mut Cursor::new(data)))
+				.transpose()?;
+
+			if base_spec.map(|x| x.is_stripped()).unwrap_or(false) {
+				return Err(overlay_error(format!(
+					"base database is stripped, and has no valid overlay for locale {:?}",
+					locale
+				)));
+			}
+		}
+
+		Ok(Self { base, overlay })
+	}
+
+	/// Look up a resource, taking the overlay into account
+	pub fn resource(&self, type_code: &[u8; 4], resource_id: u16) -> Option<&'a [u8]> {
+		if let Some(overlay) = self.overlay {
+			match overlay
+				.spec
+				.resource(type_code, resource_id)
+				.map(|x| x.kind)
+			{
+				Some(OverlayKind::Hide) => return None,
+				Some(OverlayKind::Add) | Some(OverlayKind::Replace) => {
+					return find_resource(&overlay.database, type_code, resource_id);
+				}
+				_ => {}
+			}
+		}
+
+		find_resource(self.base, type_code, resource_id)
+	}
+
+	/// Return every visible resource, as a type code, resource ID, and data
+	///
+	/// Base resources come first, in base database order, followed by any resources the overlay
+	/// adds.
+	pub fn resources(&self) -> Vec<([u8; 4], u16, &'a [u8])> {
+		let mut keys = resource_keys(self.base);
+		if let Some(overlay) = self.overlay {
+			keys.extend(
+				overlay
+					.spec
+					.resources
+					.iter()
+					.filter(|x| x.kind == OverlayKind::Add)
+					.map(|x| (x.type_code, x.resource_id)),
+			);
+		}
+
+		keys.into_iter()
+			.filter_map(|(type_code, id)| Some((type_code, id, self.resource(&type_code, id)?)))
+			.collect()
+	}
+}
+
+/// Generate an overlay database from a localised copy of a base database
+///
+/// Resources of `localised` that differ from the base are replaced, resources only present in
+/// `localised` are added, and base resources missing from `localised` are hidden. The overlay is
+/// named after the base database, with the locale suffix appended. Code and data resources can't
+/// be overlaid, so it is an error for them to differ.
+pub fn generate_overlay(
+	base: &PalmDatabase<PrcDatabase>,
+	localised: &PalmDatabase<PrcDatabase>,
+	locale: Locale,
+) -> Result<OverlayDatabase, io::Error> {
+	let mut entries = Vec::new();
+	let mut overlay_data = Vec::new();
+
+	let base_keys = resource_keys(base);
+	let localised_keys = resource_keys(localised);
+
+	for (type_code, resource_id) in base_keys.iter().chain(localised_keys.iter()) {
+		if entries
+			.iter()
+			.any(|x: &OverlayResource| &x.type_code == type_code && x.resource_id == *resource_id)
+		{
+			continue;
+		}
+
+		let base_data = find_resource(base, type_code, *resource_id);
+		let localised_data = find_resource(localised, type_code, *resource_id);
+		let (kind, described) = match (base_data, localised_data) {
+			(Some(a), Some(b)) if a == b => continue,
+			(Some(a), Some(_)) => (OverlayKind::Replace, a),
+			(Some(a), None) => (OverlayKind::Hide, a),
+			(None, Some(b)) => (OverlayKind::Add, b),
+			(None, None) => continue,
+		};
+
+		if NON_OVERLAYABLE_TYPES.contains(&type_code) {
+			return Err(overlay_error(format!(
+				"resource {:?} {} differs, but can't be overlaid",
+				String::from_utf8_lossy(type_code),
+				resource_id
+			)));
+		}
+
+		entries.push(OverlayResource {
+			kind,
+			type_code: *type_code,
+			resource_id: *resource_id,
+			length: described.len() as u32,
+			checksum: crc32(described),
+		});
+
+		if let Some(data) = localised_data.filter(|_| kind != OverlayKind::Hide) {
+			overlay_data.push((*type_code, *resource_id, data));
+		}
+	}
+
+	let spec = OverlaySpec {
+		version: OVERLAY_SPEC_VERSION,
+		flags: 0,
+		base_checksum: base_checksum(&entries),
+		target_locale: locale,
+		base_type_code: base.header.type_code,
+		base_creator_code: base.header.creator_code,
+		base_creation_time: base.header.creation_time,
+		base_modification_time: base.header.modification_time,
+		resources: entries,
+	};
+
+	// Name the overlay after the base, truncating the base name to leave room for the suffix
+	let suffix = match locale.suffix() {
+		Some(x) => x,
+		None => format!("{:02}{:02}", locale.language, locale.country),
+	};
+	let base_name = base.header.name_trimmed();
+	let base_len = base_name
+		.len()
+		.min(DATABASE_NAME_MAX_LEN - suffix.len() - 1);
+	let mut name = [0_u8; 32];
+	name[..base_len].copy_from_slice(&base_name[..base_len]);
+	name[base_len] = b'_';
+	name[(base_len + 1)..(base_len + 1 + suffix.len())].copy_from_slice(suffix.as_bytes());
+
+	let mut header = base.header;
+	header.name = name;
+	header.type_code = *OVERLAY_TYPE_CODE;
+	header.modification_number = 0;
+	header.app_info_id = 0;
+	header.sort_info_id = 0;
+	header.unique_id_seed = 0;
+	header.next_record_list = 0;
+
+	let mut database = PalmDatabase::<PrcDatabase>::new(header, base.app_info);
+	database.insert_resource_with_id(
+		OVERLAY_SPEC_TYPE_CODE,
+		OVERLAY_SPEC_RESOURCE_ID,
+		&spec.to_bytes()?,
+	)?;
+	for (type_code, resource_id, data) in overlay_data {
+		database.insert_resource_with_id(&type_code, resource_id, data)?;
+	}
+
+	Ok(OverlayDatabase { database, spec })
+}
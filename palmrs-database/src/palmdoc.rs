@@ -0,0 +1,176 @@
+//! PalmDoc text databases (`TEXt`/`REAd`)
+//!
+//! A PalmDoc database holds a single text document. Record `0` is a header describing the
+//! compression and length of the text, and each following record holds a chunk of the text (at
+//! most `record_size` bytes, once decompressed).
+//!
+//! PalmDoc compression is a simple LZ77 variant, also used by other e-book formats (such as
+//! eReader); [`decompress`] is exposed for their use.
+
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{text::palm_to_string, PalmDatabase, PdbDatabase};
+
+/// Database type code of PalmDoc databases
+pub const PALMDOC_TYPE_CODE: &[u8; 4] = b"TEXt";
+
+/// Creator code of PalmDoc databases
+pub const PALMDOC_CREATOR_CODE: &[u8; 4] = b"REAd";
+
+/// Compression type: the text records are uncompressed
+pub const COMPRESSION_NONE: u16 = 1;
+
+/// Compression type: the text records are PalmDoc compressed
+pub const COMPRESSION_PALMDOC: u16 = 2;
+
+/// Maximum ratio of decompressed to compressed length: a 2-byte back-reference expands to at
+/// most 10 bytes
+const MAX_EXPANSION: usize = 5;
+
+/// Decompress PalmDoc compressed data
+///
+/// Returns an error if the data contains a back-reference to before the start of the output.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, io::Error> {
+	let mut out = Vec::with_capacity(data.len() * 2);
+	let mut idx = 0;
+	while idx < data.len() {
+		let byte = data[idx];
+		idx += 1;
+
+		match byte {
+			// Copy the following 1 to 8 bytes literally
+			0x01..=0x08 => {
+				let end = (idx + byte as usize).min(data.len());
+				out.extend_from_slice(&data[idx..end]);
+				idx = end;
+			}
+
+			// Back-reference: 11 bits of distance, and 3 bits of length (minus 3)
+			0x80..=0xBF => {
+				let next = *data.get(idx).unwrap_or(&0);
+				idx += 1;
+
+				let pair = ((byte as usize) << 8) | next as usize;
+				let distance = (pair >> 3) & 0x07FF;
+				let length = (pair & 0x07) + 3;
+				if distance == 0 || distance > out.len() {
+					return Err(io::Error::other(format!(
+						"invalid PalmDoc back-reference distance {}",
+						distance
+					)));
+				}
+
+				let start = out.len() - distance;
+				for offset in 0..length {
+					out.push(out[start + offset]);
+				}
+			}
+
+			// A space, followed by the character in the low 7 bits
+			0xC0..=0xFF => {
+				out.push(b' ');
+				out.push(byte ^ 0x80);
+			}
+
+			_ => out.push(byte),
+		}
+	}
+
+	Ok(out)
+}
+
+/// The PalmDoc header (record `0`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PalmDocHeader {
+	pub compression: u16,
+
+	/// Length of the uncompressed text, in bytes
+	pub text_length: u32,
+
+	/// Number of text records
+	pub record_count: u16,
+
+	/// Maximum length of each uncompressed text record, in bytes
+	pub record_size: u16,
+
+	/// Last reading position within the text
+	pub position: u32,
+}
+
+impl PalmDocHeader {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let compression = rdr.read_u16::<BigEndian>()?;
+		let _unused = rdr.read_u16::<BigEndian>()?;
+		let text_length = rdr.read_u32::<BigEndian>()?;
+		let record_count = rdr.read_u16::<BigEndian>()?;
+		let record_size = rdr.read_u16::<BigEndian>()?;
+		let position = rdr.read_u32::<BigEndian>()?;
+
+		Ok(Self {
+			compression,
+			text_length,
+			record_count,
+			record_size,
+			position,
+		})
+	}
+}
+
+/// A PalmDoc database
+#[derive(Debug)]
+pub struct PalmDocDatabase {
+	pub database: PalmDatabase<PdbDatabase>,
+	pub header: PalmDocHeader,
+}
+
+impl PalmDocDatabase {
+	pub fn from_database(database: PalmDatabase<PdbDatabase>) -> Result<Self, io::Error> {
+		let header = match database.list_records_resources().first() {
+			Some((_, data)) => PalmDocHeader::from_bytes(&mut Cursor::new(data))?,
+			None => return Err(io::Error::other("PalmDoc database has no header record")),
+		};
+
+		Ok(Self { database, header })
+	}
+
+	/// Read a PalmDoc database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbDatabase>::from_bytes(data)?)
+	}
+
+	/// Return the raw (decompressed) text, in the Palm OS character set
+	pub fn text_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let records = self
+			.database
+			.list_records_resources()
+			.iter()
+			.skip(1)
+			.take(self.header.record_count as usize);
+
+		// The text length is only trusted as far as the records could decompress to
+		let stored: usize = records.clone().map(|(_, data)| data.len()).sum();
+		let mut out =
+			Vec::with_capacity((self.header.text_length as usize).min(stored * MAX_EXPANSION));
+		for (_, data) in records {
+			match self.header.compression {
+				COMPRESSION_NONE => out.extend_from_slice(data),
+				COMPRESSION_PALMDOC => out.extend_from_slice(&decompress(data)?),
+				other => {
+					return Err(io::Error::other(format!(
+						"unsupported PalmDoc compression type {}",
+						other
+					)))
+				}
+			}
+		}
+
+		Ok(out)
+	}
+
+	/// Return the text of the document
+	pub fn text(&self) -> Result<String, io::Error> {
+		Ok(palm_to_string(&self.text_bytes()?))
+	}
+}
@@ -0,0 +1,553 @@
+//! Address Book (`AddressDB`) and Contacts (`ContactsDB-PAdd`)
+//!
+//! A classic Address Book record starts with the labels of its five phone numbers (and which of
+//! them is shown in the list view), followed by a bitmask of which of its 19 fields are present,
+//! and the offset of the company name (used for sorting). The fields that are present follow,
+//! each NUL-terminated.
+//!
+//! The Palm OS 5 Contacts application extends this to 39 fields, with seven phone numbers, two
+//! instant messaging names, a website, nine custom fields, and three addresses. Two bitmasks are
+//! used for the fields, the second of which also flags an optional birthday. Blobs, such as the
+//! contact's picture, may follow the fields.
+
+use std::io::{self, Cursor, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+	pim::{check_creator, decode_records, PackedDate, PimBlob},
+	record::pdb_record::RecordAttributes,
+	text::{palm_to_string, read_cstr, string_to_palm},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the classic Address Book database
+pub const ADDRESS_DATABASE_NAME: &str = "AddressDB";
+
+/// Creator code of the classic Address Book database
+pub const ADDRESS_CREATOR_CODE: &[u8; 4] = b"addr";
+
+/// Database name of the Palm OS 5 Contacts database
+pub const CONTACTS_DATABASE_NAME: &str = "ContactsDB-PAdd";
+
+/// Creator code of the Palm OS 5 Contacts database
+pub const CONTACTS_CREATOR_CODE: &[u8; 4] = b"PAdd";
+
+/// Database type code of both the Address Book and Contacts databases
+pub const ADDRESS_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Blob code of a contact's picture
+pub const CONTACTS_BLOB_PICTURE: &[u8; 4] = b"Bd00";
+
+/// Names of the phone number labels
+pub const PHONE_LABEL_NAMES: [&str; 8] = [
+	"Work", "Home", "Fax", "Other", "E-mail", "Main", "Pager", "Mobile",
+];
+
+/// Names of the instant messaging labels
+pub const IM_LABEL_NAMES: [&str; 5] = ["IM", "AIM", "MSN", "Yahoo", "AOL ICQ"];
+
+/// Names of the address labels
+pub const ADDRESS_LABEL_NAMES: [&str; 3] = ["Work", "Home", "Other"];
+
+/// Flag in the second Contacts field bitmask: the contact has a birthday
+const CONTACTS_FLAG_BIRTHDAY: u32 = 0x0800;
+
+/// Flag in the second Contacts field bitmask: the birthday has a reminder
+const CONTACTS_FLAG_REMINDER: u32 = 0x2000;
+
+/// Read the fields flagged as present in the given bitmask
+fn read_fields(
+	rdr: &mut Cursor<&[u8]>,
+	present: u32,
+	count: usize,
+) -> Result<Vec<String>, io::Error> {
+	let mut fields = Vec::new();
+	for idx in 0..count {
+		if present & (1 << idx) != 0 {
+			fields.push(palm_to_string(&read_cstr(rdr)?));
+		} else {
+			fields.push(String::new());
+		}
+	}
+
+	Ok(fields)
+}
+
+/// Return the bitmask of which of the given fields are present (not empty)
+fn field_mask(fields: &[&String]) -> u32 {
+	fields
+		.iter()
+		.enumerate()
+		.filter(|(_, x)| !x.is_empty())
+		.fold(0, |mask, (idx, _)| mask | (1 << idx))
+}
+
+/// Write the fields that are present, returning the company field offset
+///
+/// The company field offset is one more than the offset of the company name from the start of
+/// the fields, or zero if there is no company name. The company name is always the third field.
+fn write_fields<W: Write>(writer: &mut W, fields: &[&String]) -> Result<u8, io::Error> {
+	let mut offset = 0;
+	let mut company_offset = 0;
+	for (idx, field) in fields.iter().enumerate().filter(|(_, x)| !x.is_empty()) {
+		if idx == 2 {
+			company_offset = (offset + 1).min(0xFF) as u8;
+		}
+
+		let data = string_to_palm(field);
+		writer.write_all(&data)?;
+		writer.write_u8(0)?;
+		offset += data.len() + 1;
+	}
+
+	Ok(company_offset)
+}
+
+/// A single classic Address Book record
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AddressRecord {
+	/// Labels of the phone numbers (see [`PHONE_LABEL_NAMES`])
+	pub phone_labels: [u8; 5],
+
+	/// Index of the phone number shown in the list view
+	pub show_phone: u8,
+
+	pub last_name: String,
+	pub first_name: String,
+	pub company: String,
+	pub phones: [String; 5],
+	pub address: String,
+	pub city: String,
+	pub state: String,
+	pub zip: String,
+	pub country: String,
+	pub title: String,
+	pub custom: [String; 4],
+	pub note: String,
+}
+
+impl AddressRecord {
+	/// Number of fields in a classic Address Book record
+	pub const FIELD_COUNT: usize = 19;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let options = rdr.read_u32::<BigEndian>()?;
+		let present = rdr.read_u32::<BigEndian>()?;
+		let _company_offset = rdr.read_u8()?;
+
+		let mut phone_labels = [0_u8; 5];
+		for (idx, label) in phone_labels.iter_mut().enumerate() {
+			*label = ((options >> (idx * 4)) & 0x0F) as u8;
+		}
+
+		let mut fields = read_fields(rdr, present, Self::FIELD_COUNT)?.into_iter();
+		let mut next = || fields.next().unwrap_or_default();
+
+		Ok(Self {
+			phone_labels,
+			show_phone: ((options >> 20) & 0x0F) as u8,
+			last_name: next(),
+			first_name: next(),
+			company: next(),
+			phones: [next(), next(), next(), next(), next()],
+			address: next(),
+			city: next(),
+			state: next(),
+			zip: next(),
+			country: next(),
+			title: next(),
+			custom: [next(), next(), next(), next()],
+			note: next(),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut fields = vec![&self.last_name, &self.first_name, &self.company];
+		fields.extend(self.phones.iter());
+		fields.extend_from_slice(&[
+			&self.address,
+			&self.city,
+			&self.state,
+			&self.zip,
+			&self.country,
+			&self.title,
+		]);
+		fields.extend(self.custom.iter());
+		fields.push(&self.note);
+
+		let mut options = (self.show_phone as u32 & 0x0F) << 20;
+		for (idx, label) in self.phone_labels.iter().enumerate() {
+			options |= (*label as u32 & 0x0F) << (idx * 4);
+		}
+
+		let mut strings = Vec::new();
+		let company_offset = write_fields(&mut strings, &fields)?;
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u32::<BigEndian>(options)?;
+		cursor.write_u32::<BigEndian>(field_mask(&fields))?;
+		cursor.write_u8(company_offset)?;
+		cursor.write_all(&strings)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// One of the addresses of a contact
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ContactAddress {
+	/// Label of the address (see [`ADDRESS_LABEL_NAMES`])
+	pub label: u8,
+
+	pub street: String,
+	pub city: String,
+	pub state: String,
+	pub zip: String,
+	pub country: String,
+}
+
+/// The birthday of a contact
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ContactBirthday {
+	pub date: PackedDate,
+
+	/// Number of days before the birthday to show a reminder, if a reminder is set
+	pub reminder_days: Option<u8>,
+}
+
+/// A single Palm OS 5 Contacts record
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ContactRecord {
+	/// Labels of the phone numbers (see [`PHONE_LABEL_NAMES`])
+	pub phone_labels: [u8; 7],
+
+	/// Index of the phone number shown in the list view
+	pub show_phone: u8,
+
+	/// Labels of the instant messaging names (see [`IM_LABEL_NAMES`])
+	pub im_labels: [u8; 2],
+
+	pub last_name: String,
+	pub first_name: String,
+	pub company: String,
+	pub title: String,
+	pub phones: [String; 7],
+	pub im: [String; 2],
+	pub website: String,
+	pub custom: [String; 9],
+	pub addresses: [ContactAddress; 3],
+	pub note: String,
+
+	pub birthday: Option<ContactBirthday>,
+	pub blobs: Vec<PimBlob>,
+}
+
+impl ContactRecord {
+	/// Number of fields in a Contacts record
+	pub const FIELD_COUNT: usize = 39;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let phone_options = rdr.read_u32::<BigEndian>()?;
+		let label_options = rdr.read_u32::<BigEndian>()?;
+		let present_low = rdr.read_u32::<BigEndian>()?;
+		let present_high = rdr.read_u32::<BigEndian>()?;
+		let _company_offset = rdr.read_u8()?;
+
+		let mut phone_labels = [0_u8; 7];
+		for (idx, label) in phone_labels.iter_mut().enumerate() {
+			*label = ((phone_options >> (idx * 4)) & 0x0F) as u8;
+		}
+
+		let mut fields = read_fields(rdr, present_low, 28)?;
+		fields.extend(read_fields(rdr, present_high, Self::FIELD_COUNT - 28)?);
+		let mut fields = fields.into_iter();
+		let mut next = || fields.next().unwrap_or_default();
+
+		let last_name = next();
+		let first_name = next();
+		let company = next();
+		let title = next();
+		let phones = [next(), next(), next(), next(), next(), next(), next()];
+		let im = [next(), next()];
+		let website = next();
+		let custom = [
+			next(),
+			next(),
+			next(),
+			next(),
+			next(),
+			next(),
+			next(),
+			next(),
+			next(),
+		];
+
+		let mut addresses = [
+			ContactAddress::default(),
+			ContactAddress::default(),
+			ContactAddress::default(),
+		];
+		for (idx, address) in addresses.iter_mut().enumerate() {
+			*address = ContactAddress {
+				label: ((label_options >> (16 + idx * 4)) & 0x0F) as u8,
+				street: next(),
+				city: next(),
+				state: next(),
+				zip: next(),
+				country: next(),
+			};
+		}
+
+		let note = next();
+
+		let birthday = if present_high & CONTACTS_FLAG_BIRTHDAY != 0 {
+			let date = PackedDate::from_u16(rdr.read_u16::<BigEndian>()?);
+			let _reserved = rdr.read_u8()?;
+			let reminder_days = if present_high & CONTACTS_FLAG_REMINDER != 0 {
+				Some(rdr.read_u8()?)
+			} else {
+				None
+			};
+
+			Some(ContactBirthday {
+				date,
+				reminder_days,
+			})
+		} else {
+			None
+		};
+
+		Ok(Self {
+			phone_labels,
+			show_phone: ((phone_options >> 28) & 0x0F) as u8,
+			im_labels: [
+				(label_options & 0x0F) as u8,
+				((label_options >> 4) & 0x0F) as u8,
+			],
+			last_name,
+			first_name,
+			company,
+			title,
+			phones,
+			im,
+			website,
+			custom,
+			addresses,
+			note,
+			birthday,
+			blobs: PimBlob::read_all(rdr)?,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut fields = vec![
+			&self.last_name,
+			&self.first_name,
+			&self.company,
+			&self.title,
+		];
+		fields.extend(self.phones.iter());
+		fields.extend(self.im.iter());
+		fields.push(&self.website);
+		fields.extend(self.custom.iter());
+		for address in self.addresses.iter() {
+			fields.extend_from_slice(&[
+				&address.street,
+				&address.city,
+				&address.state,
+				&address.zip,
+				&address.country,
+			]);
+		}
+		fields.push(&self.note);
+
+		let mut phone_options = (self.show_phone as u32 & 0x0F) << 28;
+		for (idx, label) in self.phone_labels.iter().enumerate() {
+			phone_options |= (*label as u32 & 0x0F) << (idx * 4);
+		}
+
+		let mut label_options =
+			(self.im_labels[0] as u32 & 0x0F) | ((self.im_labels[1] as u32 & 0x0F) << 4);
+		for (idx, address) in self.addresses.iter().enumerate() {
+			label_options |= (address.label as u32 & 0x0F) << (16 + idx * 4);
+		}
+
+		let mut present_high = field_mask(&fields[28..]);
+		if let Some(birthday) = self.birthday {
+			present_high |= CONTACTS_FLAG_BIRTHDAY;
+			if birthday.reminder_days.is_some() {
+				present_high |= CONTACTS_FLAG_REMINDER;
+			}
+		}
+
+		let mut strings = Vec::new();
+		let company_offset = write_fields(&mut strings, &fields)?;
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u32::<BigEndian>(phone_options)?;
+		cursor.write_u32::<BigEndian>(label_options)?;
+		cursor.write_u32::<BigEndian>(field_mask(&fields[..28]))?;
+		cursor.write_u32::<BigEndian>(present_high)?;
+		cursor.write_u8(company_offset)?;
+		cursor.write_all(&strings)?;
+
+		if let Some(birthday) = self.birthday {
+			cursor.write_u16::<BigEndian>(birthday.date.to_u16())?;
+			cursor.write_u8(0)?;
+			if let Some(days) = birthday.reminder_days {
+				cursor.write_u8(days)?;
+			}
+		}
+
+		for blob in self.blobs.iter() {
+			cursor.write_all(&blob.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Return the contact's picture (usually a JPEG image), if there is one
+	pub fn picture(&self) -> Option<&[u8]> {
+		// The picture data is preceded by a 16-bit "dirty" flag
+		self.blobs
+			.iter()
+			.find(|x| &x.code == CONTACTS_BLOB_PICTURE)
+			.and_then(|x| x.data.get(2..))
+			.filter(|x| !x.is_empty())
+	}
+}
+
+impl From<AddressRecord> for ContactRecord {
+	fn from(record: AddressRecord) -> Self {
+		let mut phone_labels = [0_u8; 7];
+		phone_labels[..5].copy_from_slice(&record.phone_labels);
+
+		let [phone1, phone2, phone3, phone4, phone5] = record.phones;
+		let [custom1, custom2, custom3, custom4] = record.custom;
+
+		Self {
+			phone_labels,
+			show_phone: record.show_phone,
+			last_name: record.last_name,
+			first_name: record.first_name,
+			company: record.company,
+			title: record.title,
+			phones: [
+				phone1,
+				phone2,
+				phone3,
+				phone4,
+				phone5,
+				String::new(),
+				String::new(),
+			],
+			custom: [
+				custom1,
+				custom2,
+				custom3,
+				custom4,
+				String::new(),
+				String::new(),
+				String::new(),
+				String::new(),
+				String::new(),
+			],
+			addresses: [
+				ContactAddress {
+					label: 0,
+					street: record.address,
+					city: record.city,
+					state: record.state,
+					zip: record.zip,
+					country: record.country,
+				},
+				ContactAddress::default(),
+				ContactAddress::default(),
+			],
+			note: record.note,
+			..Self::default()
+		}
+	}
+}
+
+/// Map a contact to a classic Address Book record, keeping the first five phone numbers, the
+/// first address and the first four custom fields, and dropping the other fields, birthday and
+/// blobs
+impl From<ContactRecord> for AddressRecord {
+	fn from(record: ContactRecord) -> Self {
+		let mut phone_labels = [0_u8; 5];
+		phone_labels.copy_from_slice(&record.phone_labels[..5]);
+
+		let [phone1, phone2, phone3, phone4, phone5, _, _] = record.phones;
+		let [custom1, custom2, custom3, custom4, _, _, _, _, _] = record.custom;
+		let [address, _, _] = record.addresses;
+
+		Self {
+			phone_labels,
+			show_phone: if record.show_phone < 5 {
+				record.show_phone
+			} else {
+				0
+			},
+			last_name: record.last_name,
+			first_name: record.first_name,
+			company: record.company,
+			phones: [phone1, phone2, phone3, phone4, phone5],
+			address: address.street,
+			city: address.city,
+			state: address.state,
+			zip: address.zip,
+			country: address.country,
+			title: record.title,
+			custom: [custom1, custom2, custom3, custom4],
+			note: record.note,
+		}
+	}
+}
+
+/// An Address Book or Contacts database
+#[derive(Debug)]
+pub struct AddressDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl AddressDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		check_creator(
+			&database,
+			&[ADDRESS_CREATOR_CODE, CONTACTS_CREATOR_CODE],
+			"Address Book or Contacts",
+		)?;
+
+		Ok(Self { database })
+	}
+
+	/// Read the Address Book or Contacts database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Returns whether this is a Palm OS 5 Contacts database, rather than a classic Address Book
+	/// database
+	pub fn is_contacts(&self) -> bool {
+		&self.database.header.creator_code == CONTACTS_CREATOR_CODE
+	}
+
+	/// Decode all of the contacts in the database, with their record attributes (giving the
+	/// category), skipping deleted records
+	///
+	/// Records from a classic Address Book database are mapped to [`ContactRecord`]s.
+	pub fn contacts(&self) -> Result<Vec<(RecordAttributes, ContactRecord)>, io::Error> {
+		if self.is_contacts() {
+			decode_records(&self.database, ContactRecord::from_bytes)
+		} else {
+			decode_records(&self.database, |rdr| {
+				AddressRecord::from_bytes(rdr).map(ContactRecord::from)
+			})
+		}
+	}
+}
@@ -0,0 +1,434 @@
+//! Date Book (`DatebookDB`) and Calendar (`CalendarDB-PDat`)
+//!
+//! A classic Date Book record starts with the start and end times and the date of the
+//! appointment, followed by a flags byte saying which of the alarm, repeat settings, repeat
+//! exceptions, description and note follow.
+//!
+//! The Palm OS 5 Calendar application uses the same layout, with an extra flag for a location
+//! (which follows the note), and blobs at the end of the record. The event's time zone is stored
+//! in one of these blobs.
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::NaiveDateTime;
+
+use crate::{
+	pim::{check_creator, decode_records, PackedDate, PimBlob, RepeatInfo, TimeOfDay},
+	record::pdb_record::RecordAttributes,
+	text::{palm_to_string, read_cstr, string_to_palm},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the classic Date Book database
+pub const DATEBOOK_DATABASE_NAME: &str = "DatebookDB";
+
+/// Creator code of the classic Date Book database
+pub const DATEBOOK_CREATOR_CODE: &[u8; 4] = b"date";
+
+/// Database name of the Palm OS 5 Calendar database
+pub const CALENDAR_DATABASE_NAME: &str = "CalendarDB-PDat";
+
+/// Creator code of the Palm OS 5 Calendar database
+pub const CALENDAR_CREATOR_CODE: &[u8; 4] = b"PDat";
+
+/// Database type code of both the Date Book and Calendar databases
+pub const DATEBOOK_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Flag: the appointment has an alarm
+pub const DATEBOOK_FLAG_ALARM: u8 = 0x40;
+
+/// Flag: the appointment repeats
+pub const DATEBOOK_FLAG_REPEAT: u8 = 0x20;
+
+/// Flag: the appointment has a note
+pub const DATEBOOK_FLAG_NOTE: u8 = 0x10;
+
+/// Flag: the appointment has repeat exceptions
+pub const DATEBOOK_FLAG_EXCEPTIONS: u8 = 0x08;
+
+/// Flag: the appointment has a description
+pub const DATEBOOK_FLAG_DESCRIPTION: u8 = 0x04;
+
+/// Flag: the event has a location (Calendar only)
+pub const CALENDAR_FLAG_LOCATION: u8 = 0x02;
+
+/// Blob code of an event's time zone
+pub const CALENDAR_BLOB_TIME_ZONE: &[u8; 4] = b"Bd00";
+
+/// Unit of an alarm's advance time
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AlarmUnit {
+	Minutes,
+	Hours,
+	Days,
+	Unknown(u8),
+}
+
+impl From<u8> for AlarmUnit {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::Minutes,
+			1 => Self::Hours,
+			2 => Self::Days,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<AlarmUnit> for u8 {
+	fn from(value: AlarmUnit) -> Self {
+		match value {
+			AlarmUnit::Minutes => 0,
+			AlarmUnit::Hours => 1,
+			AlarmUnit::Days => 2,
+			AlarmUnit::Unknown(x) => x,
+		}
+	}
+}
+
+/// The alarm of an appointment
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct EventAlarm {
+	/// How long before the start of the appointment the alarm sounds
+	pub advance: i8,
+	pub unit: AlarmUnit,
+}
+
+/// A single classic Date Book record
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppointmentRecord {
+	pub date: PackedDate,
+
+	/// Start time, or `None` if the appointment is untimed
+	pub start_time: Option<TimeOfDay>,
+
+	/// End time, or `None` if the appointment is untimed
+	pub end_time: Option<TimeOfDay>,
+
+	pub alarm: Option<EventAlarm>,
+	pub repeat: Option<RepeatInfo>,
+
+	/// Dates on which a repeating appointment doesn't occur
+	pub exceptions: Vec<PackedDate>,
+
+	pub description: String,
+	pub note: String,
+}
+
+impl AppointmentRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		Ok(Self::read(rdr)?.0)
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		self.write(&mut cursor, 0)?;
+		Ok(cursor.into_inner())
+	}
+
+	/// Return the date and time the appointment starts, if it is timed and valid
+	pub fn start(&self) -> Option<NaiveDateTime> {
+		let time = self.start_time?.to_naive_time()?;
+		Some(self.date.to_naive_date()?.and_time(time))
+	}
+
+	/// Read the record, returning it along with the flags byte
+	fn read(rdr: &mut Cursor<&[u8]>) -> Result<(Self, u8), io::Error> {
+		let start_time = TimeOfDay::from_bytes(rdr)?;
+		let end_time = TimeOfDay::from_bytes(rdr)?;
+		let date = PackedDate::from_u16(rdr.read_u16::<BigEndian>()?);
+		let flags = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+
+		let alarm = if flags & DATEBOOK_FLAG_ALARM != 0 {
+			Some(EventAlarm {
+				advance: rdr.read_i8()?,
+				unit: AlarmUnit::from(rdr.read_u8()?),
+			})
+		} else {
+			None
+		};
+
+		let repeat = if flags & DATEBOOK_FLAG_REPEAT != 0 {
+			Some(RepeatInfo::from_bytes(rdr)?)
+		} else {
+			None
+		};
+
+		let mut exceptions = Vec::new();
+		if flags & DATEBOOK_FLAG_EXCEPTIONS != 0 {
+			for _ in 0..rdr.read_u16::<BigEndian>()? {
+				exceptions.push(PackedDate::from_u16(rdr.read_u16::<BigEndian>()?));
+			}
+		}
+
+		let mut string = |flag: u8| -> Result<String, io::Error> {
+			if flags & flag != 0 {
+				Ok(palm_to_string(&read_cstr(rdr)?))
+			} else {
+				Ok(String::new())
+			}
+		};
+
+		let record = Self {
+			date,
+			start_time: start_time.filter(|_| end_time.is_some()),
+			end_time: end_time.filter(|_| start_time.is_some()),
+			alarm,
+			repeat,
+			exceptions,
+			description: string(DATEBOOK_FLAG_DESCRIPTION)?,
+			note: string(DATEBOOK_FLAG_NOTE)?,
+		};
+
+		Ok((record, flags))
+	}
+
+	/// Write the record, with the given extra flags set
+	fn write<W: Write>(&self, writer: &mut W, extra_flags: u8) -> Result<(), io::Error> {
+		let mut flags = extra_flags;
+		for (set, flag) in [
+			(self.alarm.is_some(), DATEBOOK_FLAG_ALARM),
+			(self.repeat.is_some(), DATEBOOK_FLAG_REPEAT),
+			(!self.note.is_empty(), DATEBOOK_FLAG_NOTE),
+			(!self.exceptions.is_empty(), DATEBOOK_FLAG_EXCEPTIONS),
+			(!self.description.is_empty(), DATEBOOK_FLAG_DESCRIPTION),
+		]
+		.iter()
+		{
+			if *set {
+				flags |= flag;
+			}
+		}
+
+		// Untimed appointments have neither a start nor an end time
+		let (start_time, end_time) = match (self.start_time, self.end_time) {
+			(Some(start), end) => (Some(start), Some(end.unwrap_or(start))),
+			(None, _) => (None, None),
+		};
+
+		writer.write_all(&TimeOfDay::to_bytes(start_time))?;
+		writer.write_all(&TimeOfDay::to_bytes(end_time))?;
+		writer.write_u16::<BigEndian>(self.date.to_u16())?;
+		writer.write_u8(flags)?;
+		writer.write_u8(0)?;
+
+		if let Some(alarm) = self.alarm {
+			writer.write_i8(alarm.advance)?;
+			writer.write_u8(u8::from(alarm.unit))?;
+		}
+
+		if let Some(repeat) = self.repeat {
+			writer.write_all(&repeat.to_bytes()?)?;
+		}
+
+		if !self.exceptions.is_empty() {
+			writer.write_u16::<BigEndian>(self.exceptions.len() as u16)?;
+			for date in self.exceptions.iter() {
+				writer.write_u16::<BigEndian>(date.to_u16())?;
+			}
+		}
+
+		for value in [&self.description, &self.note].iter() {
+			if !value.is_empty() {
+				writer.write_all(&string_to_palm(value))?;
+				writer.write_u8(0)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// The time zone of a Calendar event
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarTimeZone {
+	/// Offset from UTC, in minutes
+	pub offset: i16,
+
+	/// Start of daylight saving time, as the day of the week, week of the month, month, and a
+	/// reserved byte
+	pub dst_start: [u8; 4],
+
+	/// End of daylight saving time, in the same format as `dst_start`
+	pub dst_end: [u8; 4],
+
+	/// Whether daylight saving time is observed
+	pub dst_observed: bool,
+
+	pub name: String,
+
+	/// Bytes whose meaning isn't known, kept so that the time zone is written back unchanged
+	pub unknown: [u8; 3],
+}
+
+impl CalendarTimeZone {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let offset = rdr.read_i16::<BigEndian>()?;
+		let unknown_first = rdr.read_u8()?;
+		let mut dst_start = [0_u8; 4];
+		rdr.read_exact(&mut dst_start)?;
+		let mut dst_end = [0_u8; 4];
+		rdr.read_exact(&mut dst_end)?;
+		let dst_observed = rdr.read_u8()? != 0;
+		let unknown_second = rdr.read_u8()?;
+		let unknown_third = rdr.read_u8()?;
+
+		Ok(Self {
+			offset,
+			dst_start,
+			dst_end,
+			dst_observed,
+			name: palm_to_string(&read_cstr(rdr)?),
+			unknown: [unknown_first, unknown_second, unknown_third],
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_i16::<BigEndian>(self.offset)?;
+		cursor.write_u8(self.unknown[0])?;
+		cursor.write_all(&self.dst_start)?;
+		cursor.write_all(&self.dst_end)?;
+		cursor.write_u8(self.dst_observed as u8)?;
+		cursor.write_u8(self.unknown[1])?;
+		cursor.write_u8(self.unknown[2])?;
+		cursor.write_all(&string_to_palm(&self.name))?;
+		cursor.write_u8(0)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A single Palm OS 5 Calendar record
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+	/// The fields shared with the classic Date Book
+	pub appointment: AppointmentRecord,
+
+	pub location: String,
+	pub time_zone: Option<CalendarTimeZone>,
+
+	/// Blobs other than the time zone
+	pub blobs: Vec<PimBlob>,
+}
+
+impl CalendarEvent {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let (appointment, flags) = AppointmentRecord::read(rdr)?;
+		let location = if flags & CALENDAR_FLAG_LOCATION != 0 {
+			palm_to_string(&read_cstr(rdr)?)
+		} else {
+			String::new()
+		};
+
+		let mut time_zone = None;
+		let mut blobs = Vec::new();
+		for blob in PimBlob::read_all(rdr)? {
+			if &blob.code == CALENDAR_BLOB_TIME_ZONE && time_zone.is_none() {
+				time_zone = Some(CalendarTimeZone::from_bytes(&mut Cursor::new(&blob.data))?);
+			} else {
+				blobs.push(blob);
+			}
+		}
+
+		Ok(Self {
+			appointment,
+			location,
+			time_zone,
+			blobs,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		if self.location.is_empty() {
+			self.appointment.write(&mut cursor, 0)?;
+		} else {
+			self.appointment
+				.write(&mut cursor, CALENDAR_FLAG_LOCATION)?;
+			cursor.write_all(&string_to_palm(&self.location))?;
+			cursor.write_u8(0)?;
+		}
+
+		if let Some(time_zone) = &self.time_zone {
+			let blob = PimBlob {
+				code: *CALENDAR_BLOB_TIME_ZONE,
+				data: time_zone.to_bytes()?,
+			};
+
+			cursor.write_all(&blob.to_bytes()?)?;
+		}
+
+		for blob in self.blobs.iter() {
+			cursor.write_all(&blob.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+impl From<AppointmentRecord> for CalendarEvent {
+	fn from(appointment: AppointmentRecord) -> Self {
+		Self {
+			appointment,
+			location: String::new(),
+			time_zone: None,
+			blobs: Vec::new(),
+		}
+	}
+}
+
+/// Map an event to a classic Date Book record, dropping the location, time zone and blobs
+impl From<CalendarEvent> for AppointmentRecord {
+	fn from(event: CalendarEvent) -> Self {
+		event.appointment
+	}
+}
+
+/// A Date Book or Calendar database
+#[derive(Debug)]
+pub struct DatebookDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl DatebookDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		check_creator(
+			&database,
+			&[DATEBOOK_CREATOR_CODE, CALENDAR_CREATOR_CODE],
+			"Date Book or Calendar",
+		)?;
+
+		Ok(Self { database })
+	}
+
+	/// Read the Date Book or Calendar database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Returns whether this is a Palm OS 5 Calendar database, rather than a classic Date Book
+	/// database
+	pub fn is_calendar(&self) -> bool {
+		&self.database.header.creator_code == CALENDAR_CREATOR_CODE
+	}
+
+	/// Decode all of the events in the database, with their record attributes (giving the
+	/// category), skipping deleted records
+	///
+	/// Records from a classic Date Book database are mapped to [`CalendarEvent`]s.
+	pub fn events(&self) -> Result<Vec<(RecordAttributes, CalendarEvent)>, io::Error> {
+		if self.is_calendar() {
+			decode_records(&self.database, CalendarEvent::from_bytes)
+		} else {
+			decode_records(&self.database, |rdr| {
+				AppointmentRecord::from_bytes(rdr).map(CalendarEvent::from)
+			})
+		}
+	}
+}
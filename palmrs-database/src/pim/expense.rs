@@ -0,0 +1,697 @@
+//! Expense (`ExpenseDB`)
+//!
+//! Each Expense record holds a single expense: the date, expense type, payment type and currency,
+//! followed by the amount, vendor, city, attendees and note strings, each NUL-terminated. The
+//! amount is stored as text, as it was entered, using the decimal separator of the device's
+//! number format.
+//!
+//! The app info block extends the standard categories with the sort order and up to four custom
+//! currencies, which records refer to as currencies [`EXPENSE_CURRENCY_CUSTOM`] onwards.
+//!
+//! Expenses can be exported as CSV (with [`ExpenseDatabase::write_csv`]) or as a plain-text
+//! ledger journal (with [`ExpenseDatabase::write_ledger`]).
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+	pim::PackedDate,
+	prefs::NumberFormat,
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	text::{palm_to_string, read_cstr, string_to_palm, trim_null},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the Expense database
+pub const EXPENSE_DATABASE_NAME: &str = "ExpenseDB";
+
+/// Database type code of the Expense database
+pub const EXPENSE_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Creator code of the Expense database
+pub const EXPENSE_CREATOR_CODE: &[u8; 4] = b"exps";
+
+/// Currency of the first custom currency in the app info block
+pub const EXPENSE_CURRENCY_CUSTOM: u8 = 128;
+
+/// Currency of the Euro, which was added after the other built-in currencies
+pub const EXPENSE_CURRENCY_EURO: u8 = 133;
+
+/// Number of custom currencies in the app info block
+pub const EXPENSE_CUSTOM_CURRENCY_COUNT: usize = 4;
+
+/// ISO 4217 codes of the built-in currencies, in the order used by the Expense application
+const BUILTIN_CURRENCIES: [&str; 33] = [
+	"AUD", "ATS", "BEF", "BRL", "CAD", "DKK", "FIM", "FRF", "DEM", "HKD", "ISK", "IEP", "ITL",
+	"JPY", "LUF", "MXN", "NLG", "NZD", "NOK", "ESP", "SEK", "CHF", "GBP", "USD", "INR", "IDR",
+	"KRW", "MYR", "CNY", "PHP", "SGD", "THB", "TWD",
+];
+
+/// Return the ISO 4217 code of the given built-in currency
+pub fn builtin_currency_code(currency: u8) -> Option<&'static str> {
+	match currency {
+		EXPENSE_CURRENCY_EURO => Some("EUR"),
+		x => BUILTIN_CURRENCIES.get(x as usize).copied(),
+	}
+}
+
+/// Type of an expense
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExpenseType {
+	Airfare,
+	Breakfast,
+	Bus,
+	BusinessMeals,
+	CarRental,
+	Dinner,
+	Entertainment,
+	Fax,
+	Gas,
+	Gifts,
+	Hotel,
+	Incidentals,
+	Laundry,
+	Limo,
+	Lodging,
+	Lunch,
+	Mileage,
+	Other,
+	Parking,
+	Postage,
+	Snack,
+	Subway,
+	Supplies,
+	Taxi,
+	Telephone,
+	Tips,
+	Tolls,
+	Train,
+	Unknown(u8),
+}
+
+const EXPENSE_TYPES: [ExpenseType; 28] = [
+	ExpenseType::Airfare,
+	ExpenseType::Breakfast,
+	ExpenseType::Bus,
+	ExpenseType::BusinessMeals,
+	ExpenseType::CarRental,
+	ExpenseType::Dinner,
+	ExpenseType::Entertainment,
+	ExpenseType::Fax,
+	ExpenseType::Gas,
+	ExpenseType::Gifts,
+	ExpenseType::Hotel,
+	ExpenseType::Incidentals,
+	ExpenseType::Laundry,
+	ExpenseType::Limo,
+	ExpenseType::Lodging,
+	ExpenseType::Lunch,
+	ExpenseType::Mileage,
+	ExpenseType::Other,
+	ExpenseType::Parking,
+	ExpenseType::Postage,
+	ExpenseType::Snack,
+	ExpenseType::Subway,
+	ExpenseType::Supplies,
+	ExpenseType::Taxi,
+	ExpenseType::Telephone,
+	ExpenseType::Tips,
+	ExpenseType::Tolls,
+	ExpenseType::Train,
+];
+
+impl ExpenseType {
+	/// Return the name of the expense type, as shown by the Expense application
+	pub fn name(&self) -> String {
+		match self {
+			Self::Airfare => "Airfare",
+			Self::Breakfast => "Breakfast",
+			Self::Bus => "Bus",
+			Self::BusinessMeals => "Business Meals",
+			Self::CarRental => "Car Rental",
+			Self::Dinner => "Dinner",
+			Self::Entertainment => "Entertainment",
+			Self::Fax => "Fax",
+			Self::Gas => "Gas",
+			Self::Gifts => "Gifts",
+			Self::Hotel => "Hotel",
+			Self::Incidentals => "Incidentals",
+			Self::Laundry => "Laundry",
+			Self::Limo => "Limo",
+			Self::Lodging => "Lodging",
+			Self::Lunch => "Lunch",
+			Self::Mileage => "Mileage",
+			Self::Other => "Other",
+			Self::Parking => "Parking",
+			Self::Postage => "Postage",
+			Self::Snack => "Snack",
+			Self::Subway => "Subway",
+			Self::Supplies => "Supplies",
+			Self::Taxi => "Taxi",
+			Self::Telephone => "Telephone",
+			Self::Tips => "Tips",
+			Self::Tolls => "Tolls",
+			Self::Train => "Train",
+			Self::Unknown(x) => return format!("Type {}", x),
+		}
+		.to_string()
+	}
+}
+
+impl From<u8> for ExpenseType {
+	fn from(value: u8) -> Self {
+		EXPENSE_TYPES
+			.get(value as usize)
+			.copied()
+			.unwrap_or(Self::Unknown(value))
+	}
+}
+
+impl From<ExpenseType> for u8 {
+	fn from(value: ExpenseType) -> Self {
+		match value {
+			ExpenseType::Unknown(x) => x,
+			x => EXPENSE_TYPES.iter().position(|y| *y == x).unwrap() as u8,
+		}
+	}
+}
+
+/// How an expense was paid
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExpensePayment {
+	AmEx,
+	Cash,
+	Check,
+	CreditCard,
+	MasterCard,
+	Prepaid,
+	Visa,
+	Unfiled,
+	Unknown(u8),
+}
+
+impl ExpensePayment {
+	/// Return the name of the payment type, as shown by the Expense application
+	pub fn name(&self) -> String {
+		match self {
+			Self::AmEx => "AmEx",
+			Self::Cash => "Cash",
+			Self::Check => "Check",
+			Self::CreditCard => "Credit Card",
+			Self::MasterCard => "MasterCard",
+			Self::Prepaid => "Prepaid",
+			Self::Visa => "VISA",
+			Self::Unfiled => "Unfiled",
+			Self::Unknown(x) => return format!("Payment {}", x),
+		}
+		.to_string()
+	}
+
+	/// Return the ledger account the payment is made from
+	fn ledger_account(&self) -> String {
+		match self {
+			Self::Cash => "Assets:Cash".to_string(),
+			Self::Check => "Assets:Checking".to_string(),
+			Self::Prepaid => "Assets:Prepaid".to_string(),
+			Self::AmEx | Self::CreditCard | Self::MasterCard | Self::Visa => {
+				format!("Liabilities:{}", self.name())
+			}
+			Self::Unfiled | Self::Unknown(_) => "Assets:Unknown".to_string(),
+		}
+	}
+}
+
+impl From<u8> for ExpensePayment {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::AmEx,
+			1 => Self::Cash,
+			2 => Self::Check,
+			3 => Self::CreditCard,
+			4 => Self::MasterCard,
+			5 => Self::Prepaid,
+			6 => Self::Visa,
+			7 => Self::Unfiled,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<ExpensePayment> for u8 {
+	fn from(value: ExpensePayment) -> Self {
+		match value {
+			ExpensePayment::AmEx => 0,
+			ExpensePayment::Cash => 1,
+			ExpensePayment::Check => 2,
+			ExpensePayment::CreditCard => 3,
+			ExpensePayment::MasterCard => 4,
+			ExpensePayment::Prepaid => 5,
+			ExpensePayment::Visa => 6,
+			ExpensePayment::Unfiled => 7,
+			ExpensePayment::Unknown(x) => x,
+		}
+	}
+}
+
+/// A single Expense record
+///
+/// Empty strings are stored as such, and mean the field isn't set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpenseRecord {
+	pub date: PackedDate,
+	pub expense_type: ExpenseType,
+	pub payment: ExpensePayment,
+
+	/// A built-in currency (see [`builtin_currency_code`]), or a custom currency from
+	/// [`EXPENSE_CURRENCY_CUSTOM`] onwards
+	pub currency: u8,
+
+	pub amount: String,
+	pub vendor: String,
+	pub city: String,
+	pub attendees: String,
+	pub note: String,
+}
+
+impl ExpenseRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let date = PackedDate::from_u16(rdr.read_u16::<BigEndian>()?);
+		let expense_type = ExpenseType::from(rdr.read_u8()?);
+		let payment = ExpensePayment::from(rdr.read_u8()?);
+		let currency = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+
+		let mut string = || -> Result<String, io::Error> { Ok(palm_to_string(&read_cstr(rdr)?)) };
+
+		Ok(Self {
+			date,
+			expense_type,
+			payment,
+			currency,
+			amount: string()?,
+			vendor: string()?,
+			city: string()?,
+			attendees: string()?,
+			note: string()?,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u16::<BigEndian>(self.date.to_u16())?;
+		cursor.write_u8(u8::from(self.expense_type))?;
+		cursor.write_u8(u8::from(self.payment))?;
+		cursor.write_u8(self.currency)?;
+		cursor.write_u8(0)?;
+
+		for value in [
+			&self.amount,
+			&self.vendor,
+			&self.city,
+			&self.attendees,
+			&self.note,
+		]
+		.iter()
+		{
+			cursor.write_all(&string_to_palm(value))?;
+			cursor.write_u8(0)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Parse the amount, which was entered using the given number format
+	///
+	/// Thousands separators are ignored in the whole part of the amount. Returns `None` if the
+	/// amount is empty or isn't a number in that format.
+	pub fn amount_value(&self, format: NumberFormat) -> Option<f64> {
+		let (thousands, decimal) = format.separators().unwrap_or((',', '.'));
+		let amount = self.amount.trim();
+		if amount.is_empty() {
+			return None;
+		}
+
+		let (whole, fraction) = match amount.split_once(decimal) {
+			Some((whole, fraction)) => (whole, fraction),
+			None => (amount, "0"),
+		};
+
+		if !fraction.chars().all(|c| c.is_ascii_digit()) {
+			return None;
+		}
+
+		let whole = whole
+			.chars()
+			.filter(|c| *c != thousands && !c.is_whitespace())
+			.collect::<String>();
+		format!("{}.{}", whole, fraction)
+			.parse::<f64>()
+			.ok()
+			.filter(|x| x.is_finite())
+	}
+}
+
+/// Read a fixed-length, NUL-padded string
+fn read_fixed(rdr: &mut Cursor<&[u8]>, len: usize) -> Result<String, io::Error> {
+	let mut buf = vec![0_u8; len];
+	rdr.read_exact(&mut buf)?;
+	Ok(palm_to_string(trim_null(&buf)))
+}
+
+/// Write a fixed-length, NUL-padded string, truncating it to leave room for a NUL
+fn write_fixed<W: Write>(writer: &mut W, value: &str, len: usize) -> Result<(), io::Error> {
+	let mut buf = string_to_palm(value);
+	buf.resize(len - 1, 0);
+	buf.push(0);
+	writer.write_all(&buf)
+}
+
+/// A custom currency, from the Expense app info block
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ExpenseCustomCurrency {
+	/// Name of the country, up to 15 characters
+	pub name: String,
+
+	/// Currency symbol, up to 3 characters
+	pub symbol: String,
+
+	/// Exchange rate, as entered, up to 7 characters
+	pub rate: String,
+}
+
+impl ExpenseCustomCurrency {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		Ok(Self {
+			name: read_fixed(rdr, 16)?,
+			symbol: read_fixed(rdr, 4)?,
+			rate: read_fixed(rdr, 8)?,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		write_fixed(&mut cursor, &self.name, 16)?;
+		write_fixed(&mut cursor, &self.symbol, 4)?;
+		write_fixed(&mut cursor, &self.rate, 8)?;
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Sort order of the Expense application's list view
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ExpenseSortOrder {
+	Date,
+	Type,
+	Unknown(u8),
+}
+
+impl From<u8> for ExpenseSortOrder {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::Date,
+			1 => Self::Type,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<ExpenseSortOrder> for u8 {
+	fn from(value: ExpenseSortOrder) -> Self {
+		match value {
+			ExpenseSortOrder::Date => 0,
+			ExpenseSortOrder::Type => 1,
+			ExpenseSortOrder::Unknown(x) => x,
+		}
+	}
+}
+
+/// The Expense-specific fields of the app info block, which follow the categories
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExpenseAppInfo {
+	pub sort_order: ExpenseSortOrder,
+
+	/// The custom currencies; there are always [`EXPENSE_CUSTOM_CURRENCY_COUNT`] of these
+	pub currencies: Vec<ExpenseCustomCurrency>,
+}
+
+impl Default for ExpenseAppInfo {
+	fn default() -> Self {
+		Self {
+			sort_order: ExpenseSortOrder::Date,
+			currencies: vec![ExpenseCustomCurrency::default(); EXPENSE_CUSTOM_CURRENCY_COUNT],
+		}
+	}
+}
+
+impl ExpenseAppInfo {
+	/// Size of the Expense-specific fields, in bytes
+	pub const SIZE: usize = 2 + EXPENSE_CUSTOM_CURRENCY_COUNT * (16 + 4 + 8);
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let sort_order = ExpenseSortOrder::from(rdr.read_u8()?);
+		let _reserved = rdr.read_u8()?;
+
+		let mut currencies = Vec::new();
+		for _ in 0..EXPENSE_CUSTOM_CURRENCY_COUNT {
+			currencies.push(ExpenseCustomCurrency::from_bytes(rdr)?);
+		}
+
+		Ok(Self {
+			sort_order,
+			currencies,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(u8::from(self.sort_order))?;
+		cursor.write_u8(0)?;
+
+		let blank = ExpenseCustomCurrency::default();
+		for idx in 0..EXPENSE_CUSTOM_CURRENCY_COUNT {
+			let currency = self.currencies.get(idx).unwrap_or(&blank);
+			cursor.write_all(&currency.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// The Expense database
+#[derive(Debug)]
+pub struct ExpenseDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+	pub app_info: ExpenseAppInfo,
+}
+
+impl ExpenseDatabase {
+	/// Wrap the given database, reading the Expense-specific app info fields
+	///
+	/// A database without records doesn't keep the data after the categories (see
+	/// [`PalmDatabase::application_reserved`]), so has the default app info fields.
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		if &database.header.creator_code != EXPENSE_CREATOR_CODE {
+			return Err(io::Error::other(format!(
+				"database creator {:?} is not Expense",
+				String::from_utf8_lossy(&database.header.creator_code)
+			)));
+		}
+
+		let app_info = match database.application_reserved() {
+			x if x.len() >= ExpenseAppInfo::SIZE => {
+				ExpenseAppInfo::from_bytes(&mut Cursor::new(x))?
+			}
+			_ => ExpenseAppInfo::default(),
+		};
+
+		Ok(Self { database, app_info })
+	}
+
+	/// Read the Expense database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Write the database out, including the Expense-specific app info fields
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut database = self.database.clone();
+		database.set_application_reserved(self.app_info.to_bytes()?);
+		database.to_bytes()
+	}
+
+	/// Decode all of the expenses in the database, with their record attributes (giving the
+	/// category), skipping deleted records
+	pub fn expenses(&self) -> Result<Vec<(RecordAttributes, ExpenseRecord)>, io::Error> {
+		let mut expenses = Vec::new();
+		for (hdr, data) in self.database.list_records_resources().iter() {
+			let attributes = hdr.attributes().unwrap_or_default();
+			if attributes.delete || data.is_empty() {
+				continue;
+			}
+
+			expenses.push((
+				attributes,
+				ExpenseRecord::from_bytes(&mut Cursor::new(data))?,
+			));
+		}
+
+		Ok(expenses)
+	}
+
+	/// Return the name of the given currency: the ISO 4217 code of a built-in currency, or the
+	/// symbol of a custom currency
+	pub fn currency_name(&self, currency: u8) -> Option<String> {
+		if let Some(code) = builtin_currency_code(currency) {
+			return Some(code.to_string());
+		}
+
+		let custom = self
+			.app_info
+			.currencies
+			.get(currency.checked_sub(EXPENSE_CURRENCY_CUSTOM)? as usize)?;
+		Some(custom.symbol.clone()).filter(|x| !x.is_empty())
+	}
+
+	/// Write the expenses as CSV, with a header row
+	///
+	/// Records are separated by CRLF, as in RFC 4180. Amounts are written as they were entered.
+	pub fn write_csv<W: Write>(&self, writer: &mut W) -> Result<(), io::Error> {
+		writer.write_all(
+			b"Date,Category,Type,Payment,Currency,Amount,Vendor,City,Attendees,Note\r\n",
+		)?;
+
+		for (attributes, expense) in self.expenses()? {
+			let date = expense
+				.date
+				.to_naive_date()
+				.map(|x| x.format("%Y-%m-%d").to_string())
+				.unwrap_or_default();
+			let fields = [
+				date,
+				self.database
+					.app_info
+					.category_name(attributes.category)
+					.unwrap_or_default(),
+				expense.expense_type.name(),
+				expense.payment.name(),
+				self.currency_name(expense.currency).unwrap_or_default(),
+				expense.amount,
+				expense.vendor,
+				expense.city,
+				expense.attendees,
+				expense.note,
+			];
+
+			let line = fields
+				.iter()
+				.map(|x| csv_field(x))
+				.collect::<Vec<_>>()
+				.join(",");
+			writer.write_all(line.as_bytes())?;
+			writer.write_all(b"\r\n")?;
+		}
+
+		Ok(())
+	}
+
+	/// Write the expenses as a plain-text ledger journal, in date order
+	///
+	/// Each expense is posted to `Expenses:<category>:<type>` (leaving out the category if it is
+	/// the first, "Unfiled", category) from an account for its payment type. The vendor is the
+	/// payee, and the city, attendees and note are comments. Amounts are parsed using the given
+	/// number format; expenses without a valid amount or date are written as a comment instead.
+	pub fn write_ledger<W: Write>(
+		&self,
+		writer: &mut W,
+		format: NumberFormat,
+	) -> Result<(), io::Error> {
+		let mut expenses = self.expenses()?;
+		expenses.sort_by_key(|(_, x)| x.date);
+
+		for (attributes, expense) in expenses {
+			let payee = match expense.vendor.trim() {
+				"" => expense.expense_type.name(),
+				x => x.replace(&['\r', '\n'][..], " "),
+			};
+
+			let (date, amount) = match (expense.date.to_naive_date(), expense.amount_value(format))
+			{
+				(Some(date), Some(amount)) => (date, amount),
+				_ => {
+					writeln!(
+						writer,
+						"; skipped {}: invalid date or amount {:?}\n",
+						payee, expense.amount
+					)?;
+					continue;
+				}
+			};
+
+			writeln!(writer, "{} {}", date.format("%Y-%m-%d"), payee)?;
+			for (name, value) in [
+				("City", &expense.city),
+				("Attendees", &expense.attendees),
+				("Note", &expense.note),
+			]
+			.iter()
+			{
+				for line in value.lines().filter(|x| !x.trim().is_empty()) {
+					writeln!(writer, "    ; {}: {}", name, line.trim())?;
+				}
+			}
+
+			let mut account = String::from("Expenses");
+			if attributes.category != 0 {
+				if let Some(category) = self.database.app_info.category_name(attributes.category) {
+					account.push(':');
+					account.push_str(&ledger_account_name(&category));
+				}
+			}
+			account.push(':');
+			account.push_str(&ledger_account_name(&expense.expense_type.name()));
+
+			let commodity = self
+				.currency_name(expense.currency)
+				.map(|x| ledger_commodity(&x))
+				.unwrap_or_default();
+			writeln!(writer, "    {}  {:.2} {}", account, amount, commodity)?;
+			writeln!(writer, "    {}", expense.payment.ledger_account())?;
+			writeln!(writer)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Quote a CSV field, if it needs quoting
+fn csv_field(value: &str) -> String {
+	if value.contains(&[',', '"', '\r', '\n'][..]) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}
+
+/// Make a name safe to use as part of a ledger account name
+fn ledger_account_name(name: &str) -> String {
+	name.replace(':', "-")
+		.split_whitespace()
+		.collect::<Vec<_>>()
+		.join(" ")
+}
+
+/// Quote a ledger commodity, if it contains anything other than letters and currency symbols
+fn ledger_commodity(name: &str) -> String {
+	if name
+		.chars()
+		.all(|c| c.is_alphabetic() || "$£€¥".contains(c))
+	{
+		name.to_string()
+	} else {
+		format!("\"{}\"", name.replace('"', ""))
+	}
+}
@@ -0,0 +1,533 @@
+//! Mail (`MailDB`)
+//!
+//! Each Mail record holds a single message: the date and time, a flags byte, and the subject,
+//! from, to, cc, bcc, reply-to, sent-to and body strings, each NUL-terminated. The record
+//! categories are the mail folders (Inbox, Outbox, Deleted, Filed and Draft).
+//!
+//! Messages can be exported as RFC 5322 messages, either to a Maildir (with [`write_maildir`]) or
+//! an mbox file (with [`write_mbox`]). Palm OS doesn't store a time zone alongside the date, so
+//! exported messages use the `-0000` "unknown local time" zone.
+
+use std::{
+	fs,
+	io::{self, Cursor, Write},
+	path::Path,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{NaiveDateTime, Timelike};
+
+use crate::{
+	pim::PackedDate,
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	text::{palm_to_string, read_cstr, string_to_palm},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the Mail database
+pub const MAIL_DATABASE_NAME: &str = "MailDB";
+
+/// Database type code of the Mail database
+pub const MAIL_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Creator code of the Mail database
+pub const MAIL_CREATOR_CODE: &[u8; 4] = b"mail";
+
+/// Flag: the message has been read
+pub const MAIL_FLAG_READ: u8 = 0x80;
+
+/// Flag: the signature is appended to the message when sent
+pub const MAIL_FLAG_SIGNATURE: u8 = 0x40;
+
+/// Flag: a read confirmation is requested
+pub const MAIL_FLAG_CONFIRM_READ: u8 = 0x20;
+
+/// Flag: a delivery confirmation is requested
+pub const MAIL_FLAG_CONFIRM_DELIVERY: u8 = 0x10;
+
+/// Category of the Inbox folder
+pub const MAIL_FOLDER_INBOX: u8 = 0;
+
+/// Category of the Outbox folder
+pub const MAIL_FOLDER_OUTBOX: u8 = 1;
+
+/// Category of the Deleted folder
+pub const MAIL_FOLDER_DELETED: u8 = 2;
+
+/// Category of the Filed folder
+pub const MAIL_FOLDER_FILED: u8 = 3;
+
+/// Category of the Draft folder
+pub const MAIL_FOLDER_DRAFT: u8 = 4;
+
+/// Message priority
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MailPriority {
+	High,
+	Normal,
+	Low,
+	Unknown(u8),
+}
+
+impl From<u8> for MailPriority {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::High,
+			1 => Self::Normal,
+			2 => Self::Low,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<MailPriority> for u8 {
+	fn from(value: MailPriority) -> Self {
+		match value {
+			MailPriority::High => 0,
+			MailPriority::Normal => 1,
+			MailPriority::Low => 2,
+			MailPriority::Unknown(x) => x,
+		}
+	}
+}
+
+/// How the message was addressed to the device's owner
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MailAddressing {
+	To,
+	Cc,
+	Bcc,
+	Unknown(u8),
+}
+
+impl From<u8> for MailAddressing {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::To,
+			1 => Self::Cc,
+			2 => Self::Bcc,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<MailAddressing> for u8 {
+	fn from(value: MailAddressing) -> Self {
+		match value {
+			MailAddressing::To => 0,
+			MailAddressing::Cc => 1,
+			MailAddressing::Bcc => 2,
+			MailAddressing::Unknown(x) => x,
+		}
+	}
+}
+
+/// The date and time of a message
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MailDateTime {
+	pub date: PackedDate,
+	pub hour: u8,
+	pub minute: u8,
+}
+
+impl MailDateTime {
+	/// Convert to a [`NaiveDateTime`], if the date and time are valid
+	pub fn to_datetime(&self) -> Option<NaiveDateTime> {
+		self.date
+			.to_naive_date()?
+			.and_hms_opt(self.hour as u32, self.minute as u32, 0)
+	}
+}
+
+impl From<NaiveDateTime> for MailDateTime {
+	fn from(datetime: NaiveDateTime) -> Self {
+		Self {
+			date: PackedDate::from(datetime.date()),
+			hour: datetime.hour() as u8,
+			minute: datetime.minute() as u8,
+		}
+	}
+}
+
+/// A single Mail record
+///
+/// Empty strings are stored as such, and mean the field isn't set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MailRecord {
+	pub date: Option<MailDateTime>,
+
+	pub read: bool,
+	pub signature: bool,
+	pub confirm_read: bool,
+	pub confirm_delivery: bool,
+	pub priority: MailPriority,
+	pub addressing: MailAddressing,
+
+	pub subject: String,
+	pub from: String,
+	pub to: String,
+	pub cc: String,
+	pub bcc: String,
+	pub reply_to: String,
+	pub sent_to: String,
+	pub body: String,
+}
+
+impl Default for MailRecord {
+	fn default() -> Self {
+		Self {
+			date: None,
+			read: false,
+			signature: false,
+			confirm_read: false,
+			confirm_delivery: false,
+			priority: MailPriority::Normal,
+			addressing: MailAddressing::To,
+			subject: String::new(),
+			from: String::new(),
+			to: String::new(),
+			cc: String::new(),
+			bcc: String::new(),
+			reply_to: String::new(),
+			sent_to: String::new(),
+			body: String::new(),
+		}
+	}
+}
+
+impl MailRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let packed = rdr.read_u16::<BigEndian>()?;
+		let hour = rdr.read_u8()?;
+		let minute = rdr.read_u8()?;
+		let flags = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+
+		// A zero date means the message isn't dated
+		let date = match packed {
+			0 => None,
+			x => Some(MailDateTime {
+				date: PackedDate::from_u16(x),
+				hour,
+				minute,
+			}),
+		};
+
+		let mut string = || -> Result<String, io::Error> { Ok(palm_to_string(&read_cstr(rdr)?)) };
+
+		Ok(Self {
+			date,
+			read: flags & MAIL_FLAG_READ != 0,
+			signature: flags & MAIL_FLAG_SIGNATURE != 0,
+			confirm_read: flags & MAIL_FLAG_CONFIRM_READ != 0,
+			confirm_delivery: flags & MAIL_FLAG_CONFIRM_DELIVERY != 0,
+			priority: MailPriority::from((flags >> 2) & 0x03),
+			addressing: MailAddressing::from(flags & 0x03),
+			subject: string()?,
+			from: string()?,
+			to: string()?,
+			cc: string()?,
+			bcc: string()?,
+			reply_to: string()?,
+			sent_to: string()?,
+			body: string()?,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+
+		match self.date {
+			Some(x) => {
+				cursor.write_u16::<BigEndian>(x.date.to_u16())?;
+				cursor.write_u8(x.hour)?;
+				cursor.write_u8(x.minute)?;
+			}
+			None => cursor.write_u32::<BigEndian>(0)?,
+		}
+
+		let mut flags =
+			((u8::from(self.priority) & 0x03) << 2) | (u8::from(self.addressing) & 0x03);
+		for (set, flag) in [
+			(self.read, MAIL_FLAG_READ),
+			(self.signature, MAIL_FLAG_SIGNATURE),
+			(self.confirm_read, MAIL_FLAG_CONFIRM_READ),
+			(self.confirm_delivery, MAIL_FLAG_CONFIRM_DELIVERY),
+		]
+		.iter()
+		{
+			if *set {
+				flags |= flag;
+			}
+		}
+
+		cursor.write_u8(flags)?;
+		cursor.write_u8(0)?;
+
+		for value in [
+			&self.subject,
+			&self.from,
+			&self.to,
+			&self.cc,
+			&self.bcc,
+			&self.reply_to,
+			&self.sent_to,
+			&self.body,
+		]
+		.iter()
+		{
+			cursor.write_all(&string_to_palm(value))?;
+			cursor.write_u8(0)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Convert the message to an RFC 5322 message, with Unix line endings
+	pub fn to_rfc5322(&self) -> String {
+		let mut out = String::new();
+		if let Some(date) = self.date.and_then(|x| x.to_datetime()) {
+			out.push_str(&format!(
+				"Date: {}\n",
+				date.format("%a, %d %b %Y %H:%M:%S -0000")
+			));
+		}
+
+		for (name, value) in [
+			("From", &self.from),
+			("To", &self.to),
+			("Cc", &self.cc),
+			("Bcc", &self.bcc),
+			("Reply-To", &self.reply_to),
+			("Subject", &self.subject),
+		]
+		.iter()
+		{
+			if !value.is_empty() {
+				out.push_str(&format!("{}: {}\n", name, encode_header(value)));
+			}
+		}
+
+		match self.priority {
+			MailPriority::High => out.push_str("X-Priority: 1 (Highest)\n"),
+			MailPriority::Low => out.push_str("X-Priority: 5 (Lowest)\n"),
+			_ => {}
+		}
+
+		if self.confirm_read && !self.from.is_empty() {
+			out.push_str(&format!(
+				"Disposition-Notification-To: {}\n",
+				encode_header(&self.from)
+			));
+		}
+
+		out.push_str("MIME-Version: 1.0\n");
+		out.push_str("Content-Type: text/plain; charset=utf-8\n");
+		out.push_str("Content-Transfer-Encoding: 8bit\n");
+		out.push('\n');
+
+		out.push_str(&self.body.replace("\r\n", "\n"));
+		if !out.ends_with('\n') {
+			out.push('\n');
+		}
+
+		out
+	}
+
+	/// Return the bare address of the sender, for the mbox `From ` line
+	fn sender_address(&self) -> String {
+		let from = self.from.trim();
+		if let (Some(start), Some(end)) = (from.rfind('<'), from.rfind('>')) {
+			if start < end {
+				return from[(start + 1)..end].to_string();
+			}
+		}
+
+		match from.split_whitespace().find(|x| x.contains('@')) {
+			Some(x) => x.to_string(),
+			None => "MAILER-DAEMON".to_string(),
+		}
+	}
+}
+
+/// Encode a header value, using an RFC 2047 encoded word if it isn't plain ASCII
+fn encode_header(value: &str) -> String {
+	let value = value.replace(&['\r', '\n'][..], " ");
+	if value.chars().all(|c| (' '..='~').contains(&c)) {
+		return value;
+	}
+
+	let mut out = String::from("=?utf-8?Q?");
+	for byte in value.bytes() {
+		match byte {
+			b' ' => out.push('_'),
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'!' | b'*' | b'+' | b'-' | b'/' => {
+				out.push(byte as char)
+			}
+			_ => out.push_str(&format!("={:02X}", byte)),
+		}
+	}
+
+	out.push_str("?=");
+	out
+}
+
+/// Write messages to an mbox file
+///
+/// Lines of the message bodies starting with `From ` (after any number of `>`) are quoted with
+/// an extra `>`, as in the `mboxrd` format.
+pub fn write_mbox<W: Write>(writer: &mut W, messages: &[MailRecord]) -> Result<(), io::Error> {
+	for message in messages.iter() {
+		let date = message
+			.date
+			.and_then(|x| x.to_datetime())
+			.map(|x| x.format("%a %b %e %H:%M:%S %Y").to_string())
+			.unwrap_or_else(|| "Thu Jan  1 00:00:00 1970".to_string());
+		writeln!(writer, "From {} {}", message.sender_address(), date)?;
+
+		for line in message.to_rfc5322().lines() {
+			if line.trim_start_matches('>').starts_with("From ") {
+				writer.write_all(b">")?;
+			}
+
+			writeln!(writer, "{}", line)?;
+		}
+
+		writeln!(writer)?;
+	}
+
+	Ok(())
+}
+
+/// Write messages to a Maildir, creating the Maildir if it doesn't exist
+///
+/// Messages are delivered through `tmp` into `cur`, with the `S` (seen) flag set on messages
+/// that have been read.
+pub fn write_maildir(path: &Path, messages: &[MailRecord]) -> Result<(), io::Error> {
+	for dir in ["tmp", "new", "cur"].iter() {
+		fs::create_dir_all(path.join(dir))?;
+	}
+
+	for (idx, message) in messages.iter().enumerate() {
+		let timestamp = message
+			.date
+			.and_then(|x| x.to_datetime())
+			.map(|x| x.timestamp())
+			.unwrap_or(0);
+		let name = format!("{}.P{}Q{}.palmrs", timestamp, std::process::id(), idx);
+		let flags = if message.read { "S" } else { "" };
+
+		let tmp = path.join("tmp").join(&name);
+		fs::write(&tmp, message.to_rfc5322())?;
+		fs::rename(&tmp, path.join("cur").join(format!("{}:2,{}", name, flags)))?;
+	}
+
+	Ok(())
+}
+
+/// The Mail database
+#[derive(Debug)]
+pub struct MailDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl MailDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		if &database.header.creator_code != MAIL_CREATOR_CODE {
+			return Err(io::Error::other(format!(
+				"database creator {:?} is not Mail",
+				String::from_utf8_lossy(&database.header.creator_code)
+			)));
+		}
+
+		Ok(Self { database })
+	}
+
+	/// Read the Mail database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Decode all of the messages in the database, with their record attributes (giving the
+	/// folder), skipping deleted records
+	pub fn messages(&self) -> Result<Vec<(RecordAttributes, MailRecord)>, io::Error> {
+		let mut messages = Vec::new();
+		for (hdr, data) in self.database.list_records_resources().iter() {
+			let attributes = hdr.attributes().unwrap_or_default();
+			if attributes.delete || data.is_empty() {
+				continue;
+			}
+
+			messages.push((attributes, MailRecord::from_bytes(&mut Cursor::new(data))?));
+		}
+
+		Ok(messages)
+	}
+
+	/// Return the name of the folder with the given category
+	pub fn folder_name(&self, category: u8) -> Option<String> {
+		self.database.app_info.category_name(category)
+	}
+
+	/// Group the messages in the database by folder name, in category order
+	pub fn folders(&self) -> Result<Vec<(String, Vec<MailRecord>)>, io::Error> {
+		let mut folders: Vec<(u8, String, Vec<MailRecord>)> = Vec::new();
+		for (attributes, message) in self.messages()? {
+			let category = attributes.category;
+			match folders.iter_mut().find(|(x, _, _)| *x == category) {
+				Some((_, _, messages)) => messages.push(message),
+				None => {
+					let name = self
+						.folder_name(category)
+						.unwrap_or_else(|| format!("Folder {}", category));
+					folders.push((category, name, vec![message]));
+				}
+			}
+		}
+
+		folders.sort_by_key(|(category, _, _)| *category);
+		Ok(folders
+			.into_iter()
+			.map(|(_, name, messages)| (name, messages))
+			.collect())
+	}
+
+	/// Export each folder as a Maildir within the given directory
+	pub fn export_maildir(&self, path: &Path) -> Result<(), io::Error> {
+		for (name, messages) in self.folders()? {
+			write_maildir(&path.join(folder_file_name(&name)), &messages)?;
+		}
+
+		Ok(())
+	}
+
+	/// Export each folder as an mbox file (named after the folder, with an `.mbox` extension)
+	/// within the given directory
+	pub fn export_mbox(&self, path: &Path) -> Result<(), io::Error> {
+		fs::create_dir_all(path)?;
+		for (name, messages) in self.folders()? {
+			let mut file =
+				fs::File::create(path.join(format!("{}.mbox", folder_file_name(&name))))?;
+			write_mbox(&mut file, &messages)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Make a folder name safe to use as a file name
+fn folder_file_name(name: &str) -> String {
+	let name = name
+		.trim()
+		.trim_start_matches('.')
+		.replace(|c: char| c == '/' || c == '\\' || c.is_control(), "_");
+
+	if name.is_empty() {
+		"_".to_string()
+	} else {
+		name
+	}
+}
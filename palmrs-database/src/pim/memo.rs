@@ -0,0 +1,92 @@
+//! Memo Pad (`MemoDB`) and Memos (`MemosDB-PMem`)
+//!
+//! Each record holds a single NUL-terminated memo, the first line of which is shown as its title.
+//! The Palm OS 5 Memos application uses the same record layout as the classic Memo Pad, but allows
+//! longer memos.
+
+use std::io::{self, Cursor};
+
+use crate::{
+	pim::{check_creator, decode_records},
+	record::pdb_record::RecordAttributes,
+	text::{palm_to_string, read_cstr, string_to_palm},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the classic Memo Pad database
+pub const MEMO_DATABASE_NAME: &str = "MemoDB";
+
+/// Creator code of the classic Memo Pad database
+pub const MEMO_CREATOR_CODE: &[u8; 4] = b"memo";
+
+/// Database name of the Palm OS 5 Memos database
+pub const MEMOS_DATABASE_NAME: &str = "MemosDB-PMem";
+
+/// Creator code of the Palm OS 5 Memos database
+pub const MEMOS_CREATOR_CODE: &[u8; 4] = b"PMem";
+
+/// Database type code of both the Memo Pad and Memos databases
+pub const MEMO_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// A single Memo Pad or Memos record
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MemoRecord {
+	pub text: String,
+}
+
+impl MemoRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		Ok(Self {
+			text: palm_to_string(&read_cstr(rdr)?),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut buf = string_to_palm(&self.text);
+		buf.push(0);
+		Ok(buf)
+	}
+
+	/// Return the title of the memo (its first line)
+	pub fn title(&self) -> &str {
+		self.text.lines().next().unwrap_or("")
+	}
+}
+
+/// A Memo Pad or Memos database
+#[derive(Debug)]
+pub struct MemoDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl MemoDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		check_creator(
+			&database,
+			&[MEMO_CREATOR_CODE, MEMOS_CREATOR_CODE],
+			"Memo Pad or Memos",
+		)?;
+
+		Ok(Self { database })
+	}
+
+	/// Read the Memo Pad or Memos database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Returns whether this is a Palm OS 5 Memos database, rather than a classic Memo Pad
+	/// database
+	pub fn is_memos(&self) -> bool {
+		&self.database.header.creator_code == MEMOS_CREATOR_CODE
+	}
+
+	/// Decode all of the memos in the database, with their record attributes (giving the
+	/// category), skipping deleted records
+	pub fn memos(&self) -> Result<Vec<(RecordAttributes, MemoRecord)>, io::Error> {
+		decode_records(&self.database, MemoRecord::from_bytes)
+	}
+}
@@ -0,0 +1,298 @@
+//! Record codecs for the built-in Personal Information Management (PIM) applications
+//!
+//! The PIM applications all store their data in PDB databases with categories, and share a
+//! handful of on-disk types, such as [`PackedDate`], which live in this module.
+//!
+//! Palm OS 5 devices (from Garnet onwards) replaced the classic Address Book, Date Book, To Do
+//! List and Memo Pad with Contacts, Calendar, Tasks and Memos, which use new databases with
+//! extended record layouts. The [`address`], [`datebook`], [`todo`] and [`memo`] modules read
+//! both the classic and the extended databases, and map between the two record types.
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{Datelike, NaiveDate, NaiveTime};
+
+use crate::{
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+pub mod address;
+pub mod datebook;
+pub mod expense;
+pub mod mail;
+pub mod memo;
+pub mod todo;
+
+/// A date packed into 16 bits (the Palm OS `DateType`)
+///
+/// The top 7 bits are the year (as an offset from 1904), followed by 4 bits of month, and 5 bits
+/// of day.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PackedDate {
+	pub year: u16,
+	pub month: u8,
+	pub day: u8,
+}
+
+impl PackedDate {
+	pub fn from_u16(value: u16) -> Self {
+		Self {
+			year: ((value >> 9) & 0x7F) + 1904,
+			month: ((value >> 5) & 0x0F) as u8,
+			day: (value & 0x1F) as u8,
+		}
+	}
+
+	pub fn to_u16(&self) -> u16 {
+		((self.year.saturating_sub(1904) & 0x7F) << 9)
+			| ((self.month as u16 & 0x0F) << 5)
+			| (self.day as u16 & 0x1F)
+	}
+
+	/// Convert to a [`NaiveDate`], if the date is valid
+	pub fn to_naive_date(&self) -> Option<NaiveDate> {
+		NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+	}
+}
+
+impl From<NaiveDate> for PackedDate {
+	fn from(date: NaiveDate) -> Self {
+		Self {
+			year: date.year() as u16,
+			month: date.month() as u8,
+			day: date.day() as u8,
+		}
+	}
+}
+
+/// A packed date which may be unset, stored as `0xFFFF` if so
+pub(crate) fn read_optional_date(rdr: &mut Cursor<&[u8]>) -> Result<Option<PackedDate>, io::Error> {
+	Ok(match rdr.read_u16::<BigEndian>()? {
+		0xFFFF => None,
+		x => Some(PackedDate::from_u16(x)),
+	})
+}
+
+pub(crate) fn write_optional_date<W: Write>(
+	writer: &mut W,
+	date: Option<PackedDate>,
+) -> Result<(), io::Error> {
+	writer.write_u16::<BigEndian>(date.map(|x| x.to_u16()).unwrap_or(0xFFFF))
+}
+
+/// A time of day (the Palm OS `TimeType`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TimeOfDay {
+	pub hour: u8,
+	pub minute: u8,
+}
+
+impl TimeOfDay {
+	/// Read a time of day, which is unset if both the hour and minute are `0xFF`
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Option<Self>, io::Error> {
+		let hour = rdr.read_u8()?;
+		let minute = rdr.read_u8()?;
+
+		Ok(match (hour, minute) {
+			(0xFF, 0xFF) => None,
+			(hour, minute) => Some(Self { hour, minute }),
+		})
+	}
+
+	pub fn to_bytes(time: Option<Self>) -> [u8; 2] {
+		match time {
+			Some(x) => [x.hour, x.minute],
+			None => [0xFF, 0xFF],
+		}
+	}
+
+	/// Convert to a [`NaiveTime`], if the time is valid
+	pub fn to_naive_time(&self) -> Option<NaiveTime> {
+		NaiveTime::from_hms_opt(self.hour as u32, self.minute as u32, 0)
+	}
+}
+
+/// How often a repeating event or task repeats
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RepeatType {
+	None,
+	Daily,
+	Weekly,
+	MonthlyByDay,
+	MonthlyByDate,
+	Yearly,
+	Unknown(u8),
+}
+
+impl From<u8> for RepeatType {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::None,
+			1 => Self::Daily,
+			2 => Self::Weekly,
+			3 => Self::MonthlyByDay,
+			4 => Self::MonthlyByDate,
+			5 => Self::Yearly,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<RepeatType> for u8 {
+	fn from(value: RepeatType) -> Self {
+		match value {
+			RepeatType::None => 0,
+			RepeatType::Daily => 1,
+			RepeatType::Weekly => 2,
+			RepeatType::MonthlyByDay => 3,
+			RepeatType::MonthlyByDate => 4,
+			RepeatType::Yearly => 5,
+			RepeatType::Unknown(x) => x,
+		}
+	}
+}
+
+/// Repeat settings of an event or task (the Palm OS `RepeatInfoType`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RepeatInfo {
+	pub repeat_type: RepeatType,
+
+	/// Date of the last repeat, or `None` to repeat forever
+	pub end_date: Option<PackedDate>,
+
+	/// Number of days, weeks, months or years between repeats
+	pub frequency: u8,
+
+	/// For weekly repeats, a bitmask of the days of the week (with Sunday as bit 0); for monthly
+	/// by day repeats, the week of the month (times 7) plus the day of the week
+	pub repeat_on: u8,
+
+	/// First day of the week, for weekly repeats (0 for Sunday, 1 for Monday)
+	pub start_of_week: u8,
+}
+
+impl RepeatInfo {
+	/// Size of the repeat settings, in bytes
+	pub const SIZE: usize = 8;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let repeat_type = RepeatType::from(rdr.read_u8()?);
+		let _reserved = rdr.read_u8()?;
+		let end_date = read_optional_date(rdr)?;
+		let frequency = rdr.read_u8()?;
+		let repeat_on = rdr.read_u8()?;
+		let start_of_week = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+
+		Ok(Self {
+			repeat_type,
+			end_date,
+			frequency,
+			repeat_on,
+			start_of_week,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(u8::from(self.repeat_type))?;
+		cursor.write_u8(0)?;
+		write_optional_date(&mut cursor, self.end_date)?;
+		cursor.write_u8(self.frequency)?;
+		cursor.write_u8(self.repeat_on)?;
+		cursor.write_u8(self.start_of_week)?;
+		cursor.write_u8(0)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A blob attached to the end of an extended (Palm OS 5) PIM record
+///
+/// Blobs are identified by a four-character code. Blobs with codes starting with `Bd` belong to
+/// the built-in applications; other codes are used by third-party applications to attach their
+/// own data to records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PimBlob {
+	pub code: [u8; 4],
+	pub data: Vec<u8>,
+}
+
+impl PimBlob {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut code = [0_u8; 4];
+		rdr.read_exact(&mut code)?;
+
+		let mut data = vec![0_u8; rdr.read_u16::<BigEndian>()? as usize];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self { code, data })
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		if self.data.len() > u16::MAX as usize {
+			return Err(io::Error::other(format!(
+				"blob {:?} is too large ({} bytes)",
+				String::from_utf8_lossy(&self.code),
+				self.data.len()
+			)));
+		}
+
+		let mut buf = self.code.to_vec();
+		buf.extend_from_slice(&(self.data.len() as u16).to_be_bytes());
+		buf.extend_from_slice(&self.data);
+		Ok(buf)
+	}
+
+	/// Read blobs up to the end of the record
+	pub(crate) fn read_all(rdr: &mut Cursor<&[u8]>) -> Result<Vec<Self>, io::Error> {
+		let mut blobs = Vec::new();
+		while (rdr.position() as usize) < rdr.get_ref().len() {
+			blobs.push(Self::from_bytes(rdr)?);
+		}
+
+		Ok(blobs)
+	}
+}
+
+/// Check that the database was created by one of the given applications
+pub(crate) fn check_creator(
+	database: &PalmDatabase<PdbWithCategoriesDatabase>,
+	creator_codes: &[&[u8; 4]],
+	application: &str,
+) -> Result<(), io::Error> {
+	if creator_codes.contains(&&database.header.creator_code) {
+		return Ok(());
+	}
+
+	Err(io::Error::other(format!(
+		"database creator {:?} is not {}",
+		String::from_utf8_lossy(&database.header.creator_code),
+		application
+	)))
+}
+
+/// Decode all of the records in the database with the given function, along with their record
+/// attributes, skipping deleted records
+pub(crate) fn decode_records<T, F>(
+	database: &PalmDatabase<PdbWithCategoriesDatabase>,
+	decode: F,
+) -> Result<Vec<(RecordAttributes, T)>, io::Error>
+where
+	F: Fn(&mut Cursor<&[u8]>) -> Result<T, io::Error>,
+{
+	let mut records = Vec::new();
+	for (hdr, data) in database.list_records_resources().iter() {
+		let attributes = hdr.attributes().unwrap_or_default();
+		if attributes.delete || data.is_empty() {
+			continue;
+		}
+
+		records.push((attributes, decode(&mut Cursor::new(data))?));
+	}
+
+	Ok(records)
+}
@@ -0,0 +1,291 @@
+//! To Do List (`ToDoDB`) and Tasks (`TasksDB-PTod`)
+//!
+//! A classic To Do record holds the due date, a priority byte (with the top bit set if the item is
+//! complete), and the NUL-terminated description and note.
+//!
+//! The Palm OS 5 Tasks application adds a flags word after the priority, saying which of the
+//! completion date, alarm and repeat settings follow, before the description and note.
+
+use std::io::{self, Cursor, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+	pim::{
+		check_creator,
+		decode_records,
+		read_optional_date,
+		write_optional_date,
+		PackedDate,
+		RepeatInfo,
+		TimeOfDay,
+	},
+	record::pdb_record::RecordAttributes,
+	text::{palm_to_string, read_cstr, string_to_palm},
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database name of the classic To Do List database
+pub const TODO_DATABASE_NAME: &str = "ToDoDB";
+
+/// Creator code of the classic To Do List database
+pub const TODO_CREATOR_CODE: &[u8; 4] = b"todo";
+
+/// Database name of the Palm OS 5 Tasks database
+pub const TASKS_DATABASE_NAME: &str = "TasksDB-PTod";
+
+/// Creator code of the Palm OS 5 Tasks database
+pub const TASKS_CREATOR_CODE: &[u8; 4] = b"PTod";
+
+/// Database type code of both the To Do List and Tasks databases
+pub const TODO_TYPE_CODE: &[u8; 4] = b"DATA";
+
+/// Priority flag: the item is complete
+const PRIORITY_COMPLETE: u8 = 0x80;
+
+/// Tasks flag: the task has a due date
+pub const TASKS_FLAG_DUE_DATE: u16 = 0x8000;
+
+/// Tasks flag: the task has a completion date
+pub const TASKS_FLAG_COMPLETION_DATE: u16 = 0x4000;
+
+/// Tasks flag: the task has an alarm
+pub const TASKS_FLAG_ALARM: u16 = 0x2000;
+
+/// Tasks flag: the task repeats
+pub const TASKS_FLAG_REPEAT: u16 = 0x1000;
+
+/// A single classic To Do record
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToDoRecord {
+	pub due_date: Option<PackedDate>,
+
+	/// Priority, from 1 (highest) to 5
+	pub priority: u8,
+	pub completed: bool,
+
+	pub description: String,
+	pub note: String,
+}
+
+impl ToDoRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let due_date = read_optional_date(rdr)?;
+		let priority = rdr.read_u8()?;
+
+		Ok(Self {
+			due_date,
+			priority: priority & !PRIORITY_COMPLETE,
+			completed: priority & PRIORITY_COMPLETE != 0,
+			description: palm_to_string(&read_cstr(rdr)?),
+			note: palm_to_string(&read_cstr(rdr)?),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		write_optional_date(&mut cursor, self.due_date)?;
+		cursor.write_u8(priority_byte(self.priority, self.completed))?;
+		write_strings(&mut cursor, &[&self.description, &self.note])?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+fn priority_byte(priority: u8, completed: bool) -> u8 {
+	let mut value = priority & !PRIORITY_COMPLETE;
+	if completed {
+		value |= PRIORITY_COMPLETE;
+	}
+
+	value
+}
+
+fn write_strings<W: Write>(writer: &mut W, values: &[&String]) -> Result<(), io::Error> {
+	for value in values.iter() {
+		writer.write_all(&string_to_palm(value))?;
+		writer.write_u8(0)?;
+	}
+
+	Ok(())
+}
+
+/// The alarm of a task
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TaskAlarm {
+	/// Time of day the alarm sounds
+	pub time: TimeOfDay,
+
+	/// Number of days before the due date the alarm sounds
+	pub advance_days: u16,
+}
+
+/// A single Palm OS 5 Tasks record
+#[derive(Debug, Clone, PartialEq)]
+pub struct TaskRecord {
+	pub due_date: Option<PackedDate>,
+
+	/// Priority, from 1 (highest) to 5
+	pub priority: u8,
+	pub completed: bool,
+
+	pub completion_date: Option<PackedDate>,
+	pub alarm: Option<TaskAlarm>,
+	pub repeat: Option<RepeatInfo>,
+
+	pub description: String,
+	pub note: String,
+}
+
+impl TaskRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let due_date = read_optional_date(rdr)?;
+		let priority = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+		let flags = rdr.read_u16::<BigEndian>()?;
+
+		let completion_date = if flags & TASKS_FLAG_COMPLETION_DATE != 0 {
+			read_optional_date(rdr)?
+		} else {
+			None
+		};
+
+		let alarm = if flags & TASKS_FLAG_ALARM != 0 {
+			let time = TimeOfDay::from_bytes(rdr)?;
+			let advance_days = rdr.read_u16::<BigEndian>()?;
+			time.map(|time| TaskAlarm { time, advance_days })
+		} else {
+			None
+		};
+
+		let repeat = if flags & TASKS_FLAG_REPEAT != 0 {
+			Some(RepeatInfo::from_bytes(rdr)?)
+		} else {
+			None
+		};
+
+		Ok(Self {
+			due_date: due_date.filter(|_| flags & TASKS_FLAG_DUE_DATE != 0),
+			priority: priority & !PRIORITY_COMPLETE,
+			completed: priority & PRIORITY_COMPLETE != 0,
+			completion_date,
+			alarm,
+			repeat,
+			description: palm_to_string(&read_cstr(rdr)?),
+			note: palm_to_string(&read_cstr(rdr)?),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut flags = 0;
+		for (set, flag) in [
+			(self.due_date.is_some(), TASKS_FLAG_DUE_DATE),
+			(self.completion_date.is_some(), TASKS_FLAG_COMPLETION_DATE),
+			(self.alarm.is_some(), TASKS_FLAG_ALARM),
+			(self.repeat.is_some(), TASKS_FLAG_REPEAT),
+		]
+		.iter()
+		{
+			if *set {
+				flags |= flag;
+			}
+		}
+
+		let mut cursor = Cursor::new(Vec::new());
+		write_optional_date(&mut cursor, self.due_date)?;
+		cursor.write_u8(priority_byte(self.priority, self.completed))?;
+		cursor.write_u8(0)?;
+		cursor.write_u16::<BigEndian>(flags)?;
+
+		if self.completion_date.is_some() {
+			write_optional_date(&mut cursor, self.completion_date)?;
+		}
+
+		if let Some(alarm) = self.alarm {
+			cursor.write_all(&TimeOfDay::to_bytes(Some(alarm.time)))?;
+			cursor.write_u16::<BigEndian>(alarm.advance_days)?;
+		}
+
+		if let Some(repeat) = self.repeat {
+			cursor.write_all(&repeat.to_bytes()?)?;
+		}
+
+		write_strings(&mut cursor, &[&self.description, &self.note])?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+impl From<ToDoRecord> for TaskRecord {
+	fn from(record: ToDoRecord) -> Self {
+		Self {
+			due_date: record.due_date,
+			priority: record.priority,
+			completed: record.completed,
+			completion_date: None,
+			alarm: None,
+			repeat: None,
+			description: record.description,
+			note: record.note,
+		}
+	}
+}
+
+/// Map a task to a classic To Do record, dropping the completion date, alarm and repeat settings
+impl From<TaskRecord> for ToDoRecord {
+	fn from(record: TaskRecord) -> Self {
+		Self {
+			due_date: record.due_date,
+			priority: record.priority,
+			completed: record.completed,
+			description: record.description,
+			note: record.note,
+		}
+	}
+}
+
+/// A To Do List or Tasks database
+#[derive(Debug)]
+pub struct ToDoDatabase {
+	pub database: PalmDatabase<PdbWithCategoriesDatabase>,
+}
+
+impl ToDoDatabase {
+	pub fn from_database(
+		database: PalmDatabase<PdbWithCategoriesDatabase>,
+	) -> Result<Self, io::Error> {
+		check_creator(
+			&database,
+			&[TODO_CREATOR_CODE, TASKS_CREATOR_CODE],
+			"To Do List or Tasks",
+		)?;
+
+		Ok(Self { database })
+	}
+
+	/// Read the To Do List or Tasks database from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(data)?)
+	}
+
+	/// Returns whether this is a Palm OS 5 Tasks database, rather than a classic To Do List
+	/// database
+	pub fn is_tasks(&self) -> bool {
+		&self.database.header.creator_code == TASKS_CREATOR_CODE
+	}
+
+	/// Decode all of the tasks in the database, with their record attributes (giving the
+	/// category), skipping deleted records
+	///
+	/// Records from a classic To Do List database are mapped to [`TaskRecord`]s.
+	pub fn tasks(&self) -> Result<Vec<(RecordAttributes, TaskRecord)>, io::Error> {
+		if self.is_tasks() {
+			decode_records(&self.database, TaskRecord::from_bytes)
+		} else {
+			decode_records(&self.database, |rdr| {
+				ToDoRecord::from_bytes(rdr).map(TaskRecord::from)
+			})
+		}
+	}
+}
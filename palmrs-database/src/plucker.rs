@@ -0,0 +1,992 @@
+//! Plucker documents (`Data`/`Plkr`)
+//!
+//! Record `0` of a Plucker document is the index record, giving the compression used by the
+//! document and the IDs of the reserved records (such as the home page and the metadata record).
+//! Every other record starts with an 8-byte header giving its unique ID and type, followed by
+//! the record data, which may be compressed with either PalmDoc compression or zlib.
+//!
+//! Text records hold a list of paragraph headers, followed by the text of the paragraphs. Within
+//! the text, a NUL byte introduces a function code, which handles formatting, links, and
+//! embedded images; the low three bits of the function code give the length of its arguments.
+//!
+//! Links to pages that aren't part of the document are stored as URLs in the links records,
+//! which are found through the link index record.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use flate2::read::ZlibDecoder;
+
+use crate::{
+	ereader::pml::escape_html,
+	palmdoc,
+	resource::{bitmap::BitmapFamily, ResourceType},
+	text::{palm_to_string, trim_null},
+	PalmDatabase,
+	PdbDatabase,
+};
+
+/// Database type code of Plucker documents
+pub const PLUCKER_TYPE_CODE: &[u8; 4] = b"Data";
+
+/// Creator code of Plucker documents
+pub const PLUCKER_CREATOR_CODE: &[u8; 4] = b"Plkr";
+
+/// Index record version: compressed records use PalmDoc compression
+pub const PLUCKER_COMPRESSION_DOC: u16 = 1;
+
+/// Index record version: compressed records use zlib
+pub const PLUCKER_COMPRESSION_ZLIB: u16 = 2;
+
+/// Reserved record name of the home page
+pub const RESERVED_HOME: u16 = 0;
+
+/// Reserved record name of the metadata record
+pub const RESERVED_METADATA: u16 = 5;
+
+/// Metadata type: character set of the document, as an IANA MIBenum
+pub const METADATA_CHARSET: u16 = 1;
+
+/// Metadata type: document author
+pub const METADATA_AUTHOR: u16 = 4;
+
+/// Metadata type: document title
+pub const METADATA_TITLE: u16 = 5;
+
+/// IANA MIBenum of UTF-8
+pub const CHARSET_UTF8: u16 = 106;
+
+/// Length of the header at the start of each data record
+const RECORD_HEADER_LENGTH: usize = 8;
+
+/// The Plucker index record (record `0`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluckerIndex {
+	pub uid: u16,
+
+	/// Compression used by the document ([`PLUCKER_COMPRESSION_DOC`] or
+	/// [`PLUCKER_COMPRESSION_ZLIB`])
+	pub version: u16,
+
+	/// Reserved record names, and the unique IDs of the records
+	pub reserved: Vec<(u16, u16)>,
+}
+
+impl PluckerIndex {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let uid = rdr.read_u16::<BigEndian>()?;
+		let version = rdr.read_u16::<BigEndian>()?;
+		let count = rdr.read_u16::<BigEndian>()?;
+
+		let mut reserved = Vec::new();
+		for _ in 0..count {
+			let name = rdr.read_u16::<BigEndian>()?;
+			let id = rdr.read_u16::<BigEndian>()?;
+			reserved.push((name, id));
+		}
+
+		Ok(Self {
+			uid,
+			version,
+			reserved,
+		})
+	}
+
+	/// Return the unique ID of the reserved record with the given name
+	pub fn reserved_record(&self, name: u16) -> Option<u16> {
+		self.reserved
+			.iter()
+			.find(|(x, _)| *x == name)
+			.map(|(_, id)| *id)
+	}
+}
+
+/// Plucker record type
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PluckerRecordType {
+	Text,
+	TextCompressed,
+	Image,
+	ImageCompressed,
+	Mailto,
+	LinkIndex,
+	Links,
+	LinksCompressed,
+	Bookmarks,
+	Category,
+	Metadata,
+	StyleSheet,
+	FontPage,
+	Table,
+	TableCompressed,
+	CompositeImage,
+	PageListMetadata,
+	SortedUrlIndex,
+	SortedUrl,
+	SortedUrlCompressed,
+	ExternalAnchorIndex,
+	ExternalAnchor,
+	ExternalAnchorCompressed,
+	Unknown(u8),
+}
+
+impl From<u8> for PluckerRecordType {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::Text,
+			1 => Self::TextCompressed,
+			2 => Self::Image,
+			3 => Self::ImageCompressed,
+			4 => Self::Mailto,
+			5 => Self::LinkIndex,
+			6 => Self::Links,
+			7 => Self::LinksCompressed,
+			8 => Self::Bookmarks,
+			9 => Self::Category,
+			10 => Self::Metadata,
+			11 => Self::StyleSheet,
+			12 => Self::FontPage,
+			13 => Self::Table,
+			14 => Self::TableCompressed,
+			15 => Self::CompositeImage,
+			16 => Self::PageListMetadata,
+			17 => Self::SortedUrlIndex,
+			18 => Self::SortedUrl,
+			19 => Self::SortedUrlCompressed,
+			20 => Self::ExternalAnchorIndex,
+			21 => Self::ExternalAnchor,
+			22 => Self::ExternalAnchorCompressed,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl PluckerRecordType {
+	/// Whether the record data (after the record header) is compressed
+	pub fn is_compressed(&self) -> bool {
+		matches!(
+			self,
+			Self::TextCompressed
+				| Self::ImageCompressed
+				| Self::LinksCompressed
+				| Self::TableCompressed
+				| Self::SortedUrlCompressed
+				| Self::ExternalAnchorCompressed
+		)
+	}
+}
+
+/// The header at the start of each Plucker data record
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PluckerRecordHeader {
+	pub uid: u16,
+
+	/// Number of paragraphs (text records), or of entries (other record types)
+	pub paragraphs: u16,
+
+	/// Uncompressed length of the record data
+	pub size: u16,
+
+	pub record_type: PluckerRecordType,
+	pub flags: u8,
+}
+
+impl PluckerRecordHeader {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let uid = rdr.read_u16::<BigEndian>()?;
+		let paragraphs = rdr.read_u16::<BigEndian>()?;
+		let size = rdr.read_u16::<BigEndian>()?;
+		let record_type = PluckerRecordType::from(rdr.read_u8()?);
+		let flags = rdr.read_u8()?;
+
+		Ok(Self {
+			uid,
+			paragraphs,
+			size,
+			record_type,
+			flags,
+		})
+	}
+}
+
+/// A single token from the text of a Plucker paragraph
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluckerToken {
+	Text(String),
+
+	/// Start of a link to another record
+	PageLink(u16),
+
+	/// Start of a link to a paragraph within another record
+	ParagraphLink {
+		record: u16,
+		paragraph: u16,
+	},
+
+	LinkEnd,
+
+	/// Change of text style: `0` is normal, `1` to `6` are headings, and `7` to `11` are bold,
+	/// fixed width, small, subscript and superscript
+	Style(u8),
+
+	/// An embedded image record
+	Image(u16),
+
+	/// An embedded composite image record, with an alternate image record for viewers that
+	/// don't support composite images
+	MultiImage {
+		alternate: u16,
+		record: u16,
+	},
+
+	Margins {
+		left: u8,
+		right: u8,
+	},
+
+	/// Paragraph alignment: left, right, center, or justified
+	Alignment(u8),
+
+	HorizontalRule {
+		height: u8,
+		width: u8,
+		percent: u8,
+	},
+
+	NewLine,
+	Italic(bool),
+	Underline(bool),
+	Strikethrough(bool),
+	Color([u8; 3]),
+
+	/// A table record
+	Table(u16),
+
+	/// Any other function code, along with its arguments
+	Unknown {
+		code: u8,
+		arguments: Vec<u8>,
+	},
+}
+
+/// A paragraph of a Plucker text record
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluckerParagraph {
+	pub attributes: u16,
+	pub tokens: Vec<PluckerToken>,
+}
+
+/// A mailto link record
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PluckerMailto {
+	pub to: Option<String>,
+	pub cc: Option<String>,
+	pub subject: Option<String>,
+	pub body: Option<String>,
+}
+
+impl PluckerMailto {
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		let mut rdr = Cursor::new(data);
+		let mut field = || -> Result<Option<String>, io::Error> {
+			let offset = rdr.read_u16::<BigEndian>()? as usize;
+			if offset == 0 {
+				return Ok(None);
+			}
+
+			Ok(data.get(offset..).map(|x| palm_to_string(trim_null(x))))
+		};
+
+		Ok(Self {
+			to: field()?,
+			cc: field()?,
+			subject: field()?,
+			body: field()?,
+		})
+	}
+
+	/// Return a `mailto:` URL for the link
+	pub fn to_url(&self) -> String {
+		let mut url = format!("mailto:{}", self.to.as_deref().unwrap_or_default());
+		let params = [
+			("cc", &self.cc),
+			("subject", &self.subject),
+			("body", &self.body),
+		];
+
+		let mut separator = '?';
+		for (name, value) in params.iter() {
+			if let Some(value) = value {
+				url.push_str(&format!("{}{}={}", separator, name, percent_encode(value)));
+				separator = '&';
+			}
+		}
+
+		url
+	}
+}
+
+/// Percent-encode a URL query parameter value
+fn percent_encode(value: &str) -> String {
+	let mut out = String::new();
+	for byte in value.bytes() {
+		match byte {
+			b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'@' => {
+				out.push(byte as char)
+			}
+			_ => out.push_str(&format!("%{:02X}", byte)),
+		}
+	}
+
+	out
+}
+
+/// An entry in the link index, giving the links record holding the URLs up to `last_uid`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PluckerLinkIndexEntry {
+	pub last_uid: u16,
+	pub record_uid: u16,
+}
+
+/// Document metadata
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PluckerMetadata {
+	/// Character set of the document text, as an IANA MIBenum
+	pub charset: Option<u16>,
+
+	pub author: Option<String>,
+	pub title: Option<String>,
+
+	/// All metadata entries, including those decoded above, as type and raw data
+	pub entries: Vec<(u16, Vec<u8>)>,
+}
+
+impl PluckerMetadata {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut metadata = Self::default();
+
+		let count = rdr.read_u16::<BigEndian>()?;
+		for _ in 0..count {
+			let entry_type = rdr.read_u16::<BigEndian>()?;
+			let words = rdr.read_u16::<BigEndian>()? as usize;
+			let mut data = vec![0_u8; words * 2];
+			rdr.read_exact(&mut data)?;
+
+			match entry_type {
+				METADATA_CHARSET => {
+					metadata.charset = data.get(0..2).map(|x| u16::from_be_bytes([x[0], x[1]]));
+				}
+				METADATA_AUTHOR => metadata.author = Some(palm_to_string(trim_null(&data))),
+				METADATA_TITLE => metadata.title = Some(palm_to_string(trim_null(&data))),
+				_ => {}
+			}
+
+			metadata.entries.push((entry_type, data));
+		}
+
+		Ok(metadata)
+	}
+}
+
+/// A decoded Plucker record
+#[derive(Debug, Clone, PartialEq)]
+pub enum PluckerRecord {
+	Text(Vec<PluckerParagraph>),
+	Image(BitmapFamily),
+	Mailto(PluckerMailto),
+	LinkIndex(Vec<PluckerLinkIndexEntry>),
+	Links(Vec<String>),
+	Metadata(PluckerMetadata),
+
+	/// Any other record type, as its (decompressed) data
+	Other(Vec<u8>),
+}
+
+/// An image from a Plucker document, converted to PNG
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluckerImage {
+	/// File name of the image, as referred to by the HTML export
+	pub name: String,
+
+	/// PNG image data
+	pub data: Vec<u8>,
+}
+
+/// Split the text of a Plucker paragraph into text and function codes
+///
+/// Text is decoded as UTF-8 if `charset` is [`CHARSET_UTF8`], and from the Palm OS character set
+/// otherwise.
+pub fn tokenize(data: &[u8], charset: Option<u16>) -> Vec<PluckerToken> {
+	let decode = |text: &[u8]| match charset {
+		Some(CHARSET_UTF8) => String::from_utf8_lossy(text).to_string(),
+		_ => palm_to_string(text),
+	};
+
+	let mut tokens = Vec::new();
+	let push_text = |tokens: &mut Vec<PluckerToken>, text: String| {
+		if let Some(PluckerToken::Text(last)) = tokens.last_mut() {
+			last.push_str(&text);
+		} else if !text.is_empty() {
+			tokens.push(PluckerToken::Text(text));
+		}
+	};
+
+	let mut idx = 0;
+	while idx < data.len() {
+		if data[idx] != 0 {
+			let start = idx;
+			while idx < data.len() && data[idx] != 0 {
+				idx += 1;
+			}
+
+			push_text(&mut tokens, decode(&data[start..idx]));
+			continue;
+		}
+
+		let code = match data.get(idx + 1) {
+			Some(x) => *x,
+			None => break,
+		};
+
+		let start = idx + 2;
+		let end = (start + (code & 0x07) as usize).min(data.len());
+		let args = &data[start..end];
+		idx = end;
+
+		let arg = |n: usize| *args.get(n).unwrap_or(&0);
+		let word = |n: usize| u16::from_be_bytes([arg(n), arg(n + 1)]);
+
+		let token = match code {
+			0x0A => PluckerToken::PageLink(word(0)),
+			0x0C => PluckerToken::ParagraphLink {
+				record: word(0),
+				paragraph: word(2),
+			},
+			0x08 => PluckerToken::LinkEnd,
+			0x11 => PluckerToken::Style(arg(0)),
+			0x1A => PluckerToken::Image(word(0)),
+			0x22 => PluckerToken::Margins {
+				left: arg(0),
+				right: arg(1),
+			},
+			0x29 => PluckerToken::Alignment(arg(0)),
+			0x33 => PluckerToken::HorizontalRule {
+				height: arg(0),
+				width: arg(1),
+				percent: arg(2),
+			},
+			0x38 => PluckerToken::NewLine,
+			0x40 | 0x48 => PluckerToken::Italic(code == 0x40),
+			0x53 => PluckerToken::Color([arg(0), arg(1), arg(2)]),
+			0x5C => PluckerToken::MultiImage {
+				alternate: word(0),
+				record: word(2),
+			},
+			0x60 | 0x68 => PluckerToken::Underline(code == 0x60),
+			0x70 | 0x78 => PluckerToken::Strikethrough(code == 0x70),
+			0x90 => PluckerToken::Table(word(0)),
+
+			// Unicode characters are followed by alternate text for viewers that can't display
+			// them, with the length of the alternate text as the first argument
+			0x83 | 0x85 => {
+				let value = match code {
+					0x83 => word(1) as u32,
+					_ => u32::from_be_bytes([arg(1), arg(2), arg(3), arg(4)]),
+				};
+
+				idx = (idx + arg(0) as usize).min(data.len());
+				if let Some(ch) = core::char::from_u32(value) {
+					push_text(&mut tokens, ch.to_string());
+				}
+
+				continue;
+			}
+
+			_ => PluckerToken::Unknown {
+				code,
+				arguments: args.to_vec(),
+			},
+		};
+
+		tokens.push(token);
+	}
+
+	tokens
+}
+
+/// A Plucker document
+#[derive(Debug)]
+pub struct PluckerDocument {
+	pub database: PalmDatabase<PdbDatabase>,
+	pub index: PluckerIndex,
+}
+
+impl PluckerDocument {
+	pub fn from_database(database: PalmDatabase<PdbDatabase>) -> Result<Self, io::Error> {
+		if &database.header.type_code != PLUCKER_TYPE_CODE
+			|| &database.header.creator_code != PLUCKER_CREATOR_CODE
+		{
+			return Err(io::Error::other(format!(
+				"database type {:?} is not a Plucker document",
+				String::from_utf8_lossy(&database.header.type_code)
+			)));
+		}
+
+		let index = match database.list_records_resources().first() {
+			Some((_, data)) => PluckerIndex::from_bytes(&mut Cursor::new(data))?,
+			None => return Err(io::Error::other("Plucker document has no index record")),
+		};
+
+		match index.version {
+			PLUCKER_COMPRESSION_DOC | PLUCKER_COMPRESSION_ZLIB => {}
+			other => {
+				return Err(io::Error::other(format!(
+					"unsupported Plucker compression type {}",
+					other
+				)))
+			}
+		}
+
+		Ok(Self { database, index })
+	}
+
+	/// Read a Plucker document from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Self::from_database(PalmDatabase::<PdbDatabase>::from_bytes(data)?)
+	}
+
+	/// Return the header and raw data of each data record (every record except the index)
+	pub fn raw_records(&self) -> Result<Vec<(PluckerRecordHeader, &[u8])>, io::Error> {
+		let mut records = Vec::new();
+		for (_, data) in self.database.list_records_resources().iter().skip(1) {
+			let header = PluckerRecordHeader::from_bytes(&mut Cursor::new(data))?;
+			records.push((header, &data[RECORD_HEADER_LENGTH..]));
+		}
+
+		Ok(records)
+	}
+
+	/// Return the header and raw data of the record with the given unique ID
+	fn raw_record(&self, uid: u16) -> Result<Option<(PluckerRecordHeader, &[u8])>, io::Error> {
+		Ok(self
+			.raw_records()?
+			.into_iter()
+			.find(|(header, _)| header.uid == uid))
+	}
+
+	/// Decompress record data, using the document's compression type
+	pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, io::Error> {
+		match self.index.version {
+			PLUCKER_COMPRESSION_ZLIB => {
+				let mut out = Vec::new();
+				ZlibDecoder::new(data).read_to_end(&mut out)?;
+				Ok(out)
+			}
+
+			_ => palmdoc::decompress(data),
+		}
+	}
+
+	/// Decode a record, given its header and raw data
+	pub fn decode_record(
+		&self,
+		header: &PluckerRecordHeader,
+		data: &[u8],
+	) -> Result<PluckerRecord, io::Error> {
+		// Text records are compressed after the paragraph headers, everything else is compressed
+		// as a whole
+		let paragraph_len = match header.record_type {
+			PluckerRecordType::Text | PluckerRecordType::TextCompressed => {
+				header.paragraphs as usize * 4
+			}
+			_ => 0,
+		};
+
+		let prefix = data.get(..paragraph_len).ok_or_else(|| {
+			io::Error::other(format!("Plucker record {} is truncated", header.uid))
+		})?;
+
+		let body = if header.record_type.is_compressed() {
+			self.decompress(&data[paragraph_len..])?
+		} else {
+			data[paragraph_len..].to_vec()
+		};
+
+		let record = match header.record_type {
+			PluckerRecordType::Text | PluckerRecordType::TextCompressed => {
+				let charset = self.metadata()?.and_then(|x| x.charset);
+				let mut rdr = Cursor::new(prefix);
+				let mut paragraphs = Vec::new();
+				let mut offset = 0;
+				for _ in 0..header.paragraphs {
+					let size = rdr.read_u16::<BigEndian>()? as usize;
+					let attributes = rdr.read_u16::<BigEndian>()?;
+					let end = (offset + size).min(body.len());
+					paragraphs.push(PluckerParagraph {
+						attributes,
+						tokens: tokenize(&body[offset.min(end)..end], charset),
+					});
+					offset = end;
+				}
+
+				PluckerRecord::Text(paragraphs)
+			}
+
+			PluckerRecordType::Image | PluckerRecordType::ImageCompressed => {
+				PluckerRecord::Image(BitmapFamily::from_bytes(&mut Cursor::new(&body))?)
+			}
+
+			PluckerRecordType::Mailto => PluckerRecord::Mailto(PluckerMailto::from_bytes(&body)?),
+
+			PluckerRecordType::LinkIndex => {
+				let mut rdr = Cursor::new(body.as_slice());
+				let mut entries = Vec::new();
+				for _ in 0..(body.len() / 4) {
+					let last_uid = rdr.read_u16::<BigEndian>()?;
+					let record_uid = rdr.read_u16::<BigEndian>()?;
+					entries.push(PluckerLinkIndexEntry {
+						last_uid,
+						record_uid,
+					});
+				}
+
+				PluckerRecord::LinkIndex(entries)
+			}
+
+			PluckerRecordType::Links | PluckerRecordType::LinksCompressed => PluckerRecord::Links(
+				body.split(|x| *x == 0)
+					.take(header.paragraphs as usize)
+					.map(palm_to_string)
+					.collect(),
+			),
+
+			PluckerRecordType::Metadata => {
+				PluckerRecord::Metadata(PluckerMetadata::from_bytes(&mut Cursor::new(&body))?)
+			}
+
+			_ => PluckerRecord::Other(body),
+		};
+
+		Ok(record)
+	}
+
+	/// Decode the record with the given unique ID
+	pub fn record(
+		&self,
+		uid: u16,
+	) -> Result<Option<(PluckerRecordHeader, PluckerRecord)>, io::Error> {
+		match self.raw_record(uid)? {
+			Some((header, data)) => Ok(Some((header, self.decode_record(&header, data)?))),
+			None => Ok(None),
+		}
+	}
+
+	/// Return the unique ID of the home page record
+	pub fn home(&self) -> Option<u16> {
+		self.index.reserved_record(RESERVED_HOME)
+	}
+
+	/// Return the document metadata, if the document has any
+	pub fn metadata(&self) -> Result<Option<PluckerMetadata>, io::Error> {
+		let uid = match self.index.reserved_record(RESERVED_METADATA) {
+			Some(x) => x,
+			None => return Ok(None),
+		};
+
+		match self.raw_record(uid)? {
+			Some((header, data)) if header.record_type == PluckerRecordType::Metadata => {
+				Ok(Some(PluckerMetadata::from_bytes(&mut Cursor::new(data))?))
+			}
+			_ => Ok(None),
+		}
+	}
+
+	/// Return the URL of an external link, given the unique ID it was assigned
+	pub fn url(&self, uid: u16) -> Result<Option<String>, io::Error> {
+		let index = self
+			.raw_records()?
+			.into_iter()
+			.find(|(header, _)| header.record_type == PluckerRecordType::LinkIndex);
+		let entries = match index {
+			Some((header, data)) => match self.decode_record(&header, data)? {
+				PluckerRecord::LinkIndex(x) => x,
+				_ => return Ok(None),
+			},
+			None => return Ok(None),
+		};
+
+		// Each links record holds the URLs from just after the previous entry's last ID
+		let mut first = 1;
+		for entry in entries.iter() {
+			if uid <= entry.last_uid {
+				if uid < first {
+					return Ok(None);
+				}
+
+				return match self.record(entry.record_uid)? {
+					Some((_, PluckerRecord::Links(urls))) => {
+						Ok(urls.get((uid - first) as usize).cloned())
+					}
+					_ => Ok(None),
+				};
+			}
+
+			first = entry.last_uid.saturating_add(1);
+		}
+
+		Ok(None)
+	}
+
+	/// Return the document title: the title from the metadata if there is one, or the database
+	/// name otherwise
+	pub fn title(&self) -> Result<String, io::Error> {
+		match self.metadata()?.and_then(|x| x.title) {
+			Some(x) if !x.is_empty() => Ok(x),
+			_ => Ok(self
+				.database
+				.header
+				.name_try_str()
+				.unwrap_or("")
+				.to_string()),
+		}
+	}
+
+	/// Convert the document to a standalone HTML document, along with the images it contains
+	///
+	/// Each text record becomes a section of the HTML document, starting with the home page.
+	/// Images are converted to PNG, and referred to by name, so should be written out alongside
+	/// the document.
+	pub fn to_html(&self) -> Result<(String, Vec<PluckerImage>), io::Error> {
+		let mut records = Vec::new();
+		for (header, data) in self.raw_records()? {
+			records.push((header, self.decode_record(&header, data)?));
+		}
+
+		let mut images = Vec::new();
+		for (header, record) in records.iter() {
+			if let PluckerRecord::Image(family) = record {
+				if let Some(image) = family.to_image() {
+					images.push(PluckerImage {
+						name: image_name(header.uid),
+						data: image.to_png(),
+					});
+				}
+			}
+		}
+
+		let home = self.home();
+		records.sort_by_key(|(header, _)| Some(header.uid) != home);
+
+		let mut out = format!(
+			"<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n<title>{}</title>\n</head>\n<body>\n",
+			escape_html(&self.title()?)
+		);
+
+		for (header, record) in records.iter() {
+			let paragraphs = match record {
+				PluckerRecord::Text(x) => x,
+				_ => continue,
+			};
+
+			out.push_str(&format!("<div id=\"record-{}\">\n", header.uid));
+			let mut writer = HtmlWriter::default();
+			for (idx, paragraph) in paragraphs.iter().enumerate() {
+				out.push_str(&format!("<p id=\"record-{}-{}\">", header.uid, idx));
+				out.push_str(&writer.paragraph(self, &records, &images, &paragraph.tokens)?);
+				out.push_str("</p>\n");
+			}
+
+			out.push_str("</div>\n");
+		}
+
+		out.push_str("</body>\n</html>\n");
+		Ok((out, images))
+	}
+}
+
+/// Return the file name used for an image record in the HTML export
+fn image_name(uid: u16) -> String {
+	format!("image-{}.png", uid)
+}
+
+/// Formatting state carried between the paragraphs of a text record during HTML export
+#[derive(Default)]
+struct HtmlWriter {
+	/// Current text style
+	style: u8,
+
+	/// Open inline formatting tags, other than the style
+	open: Vec<&'static str>,
+
+	/// Whether a link is open
+	link: bool,
+}
+
+impl HtmlWriter {
+	fn style_tag(style: u8) -> Option<&'static str> {
+		match style {
+			1 => Some("h1"),
+			2 => Some("h2"),
+			3 => Some("h3"),
+			4 => Some("h4"),
+			5 => Some("h5"),
+			6 => Some("h6"),
+			7 => Some("b"),
+			8 => Some("tt"),
+			9 => Some("small"),
+			10 => Some("sub"),
+			11 => Some("sup"),
+			_ => None,
+		}
+	}
+
+	/// Open or close an inline formatting tag
+	fn toggle(&mut self, out: &mut String, tag: &'static str, on: bool) {
+		let pos = self.open.iter().rposition(|x| *x == tag);
+		match (on, pos) {
+			(true, None) => {
+				out.push_str(&format!("<{}>", tag));
+				self.open.push(tag);
+			}
+
+			(false, Some(pos)) => {
+				// Close anything opened inside this tag, then reopen it afterwards
+				let inner = self.open.split_off(pos + 1);
+				for x in inner.iter().rev() {
+					out.push_str(&format!("</{}>", x));
+				}
+
+				out.push_str(&format!("</{}>", tag));
+				self.open.pop();
+
+				for x in inner.iter() {
+					out.push_str(&format!("<{}>", x));
+				}
+
+				self.open.extend(inner);
+			}
+
+			_ => {}
+		}
+	}
+
+	/// Return the link target for a link to the given record
+	fn link_target(
+		document: &PluckerDocument,
+		records: &[(PluckerRecordHeader, PluckerRecord)],
+		uid: u16,
+		paragraph: Option<u16>,
+	) -> Result<String, io::Error> {
+		let record = records.iter().find(|(header, _)| header.uid == uid);
+		Ok(match (record, paragraph) {
+			(Some((_, PluckerRecord::Mailto(mailto))), _) => mailto.to_url(),
+			(Some((_, PluckerRecord::Text(_))), Some(paragraph)) => {
+				format!("#record-{}-{}", uid, paragraph)
+			}
+			(Some((_, PluckerRecord::Text(_))), None) => format!("#record-{}", uid),
+			(Some((_, PluckerRecord::Image(_))), _) => image_name(uid),
+			_ => document.url(uid)?.unwrap_or_default(),
+		})
+	}
+
+	/// Convert the tokens of a paragraph to HTML
+	fn paragraph(
+		&mut self,
+		document: &PluckerDocument,
+		records: &[(PluckerRecordHeader, PluckerRecord)],
+		images: &[PluckerImage],
+		tokens: &[PluckerToken],
+	) -> Result<String, io::Error> {
+		let mut out = String::new();
+
+		// Reopen the formatting carried over from the previous paragraph
+		if let Some(tag) = Self::style_tag(self.style) {
+			out.push_str(&format!("<{}>", tag));
+		}
+		for tag in self.open.iter() {
+			out.push_str(&format!("<{}>", tag));
+		}
+
+		let image = |uid: u16| {
+			let name = image_name(uid);
+			if images.iter().any(|x| x.name == name) {
+				format!("<img src=\"{}\" />", name)
+			} else {
+				String::new()
+			}
+		};
+
+		for token in tokens.iter() {
+			match token {
+				PluckerToken::Text(text) => out.push_str(&escape_html(text)),
+				PluckerToken::NewLine => out.push_str("<br />\n"),
+
+				PluckerToken::PageLink(uid) | PluckerToken::ParagraphLink { record: uid, .. } => {
+					let paragraph = match token {
+						PluckerToken::ParagraphLink { paragraph, .. } => Some(*paragraph),
+						_ => None,
+					};
+
+					if self.link {
+						out.push_str("</a>");
+					}
+
+					let target = Self::link_target(document, records, *uid, paragraph)?;
+					out.push_str(&format!("<a href=\"{}\">", escape_html(&target)));
+					self.link = true;
+				}
+
+				PluckerToken::LinkEnd => {
+					if self.link {
+						out.push_str("</a>");
+						self.link = false;
+					}
+				}
+
+				PluckerToken::Style(style) => {
+					if let Some(tag) = Self::style_tag(self.style) {
+						out.push_str(&format!("</{}>", tag));
+					}
+					if let Some(tag) = Self::style_tag(*style) {
+						out.push_str(&format!("<{}>", tag));
+					}
+
+					self.style = *style;
+				}
+
+				PluckerToken::Image(uid) => out.push_str(&image(*uid)),
+				PluckerToken::MultiImage { alternate, .. } => out.push_str(&image(*alternate)),
+
+				PluckerToken::HorizontalRule { percent, width, .. } => match (percent, width) {
+					(0, 0) => out.push_str("<hr />"),
+					(0, width) => out.push_str(&format!("<hr width=\"{}\" />", width)),
+					(percent, _) => out.push_str(&format!("<hr width=\"{}%\" />", percent)),
+				},
+
+				PluckerToken::Italic(on) => self.toggle(&mut out, "i", *on),
+				PluckerToken::Underline(on) => self.toggle(&mut out, "u", *on),
+				PluckerToken::Strikethrough(on) => self.toggle(&mut out, "del", *on),
+
+				PluckerToken::Margins { .. }
+				| PluckerToken::Alignment(_)
+				| PluckerToken::Color(_)
+				| PluckerToken::Table(_)
+				| PluckerToken::Unknown { .. } => {}
+			}
+		}
+
+		// Links never span paragraphs; other formatting is reopened in the next paragraph
+		if self.link {
+			out.push_str("</a>");
+			self.link = false;
+		}
+		for tag in self.open.iter().rev() {
+			out.push_str(&format!("</{}>", tag));
+		}
+		if let Some(tag) = Self::style_tag(self.style) {
+			out.push_str(&format!("</{}>", tag));
+		}
+
+		Ok(out)
+	}
+}
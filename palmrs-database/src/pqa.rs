@@ -0,0 +1,573 @@
+//! Palm Query Applications (PQA web clipping applications)
+//!
+//! A PQA is a PDB with the `pqa ` type and the `clpr` creator (the Clipper viewer). Its app info
+//! block holds a launcher header (starting with a `lnch` signature) containing the application's
+//! version string, title, and icons, which the Launcher displays as if the PQA were a PRC.
+//!
+//! Each record holds one of the files that make up the application, as fetched from its URL: a
+//! [`PqaRecordHeader`] gives the URL, the content type, and the compression of the file data.
+//! Pages are stored in Palm's Compressed Markup Language (CML), and the images they show are
+//! stored as separate records, usually as Palm OS bitmaps.
+//!
+//! CML is a byte stream in the Palm OS character set, where every byte below `0x20` is a tag
+//! opcode, followed by the tag's arguments (single bytes, big-endian words, and NUL-terminated
+//! strings). Container tags, such as links and headings, are closed by [`CML_END`];
+//! [`tokenize`] decodes a page into [`PqaToken`]s.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	header::DatabaseHeader,
+	image::MonoImage,
+	palmdoc,
+	record::DatabaseRecordHelpers,
+	resource::{bitmap::BitmapFamily, ResourceType},
+	text::{palm_to_string, trim_null},
+	PalmDatabase,
+	PdbDatabase,
+};
+
+/// Database type code of PQA databases
+pub const PQA_TYPE_CODE: &[u8; 4] = b"pqa ";
+
+/// Creator code of PQA databases (the Clipper application)
+pub const PQA_CREATOR_CODE: &[u8; 4] = b"clpr";
+
+/// Signature at the start of the PQA launcher header
+pub const PQA_LAUNCHER_SIGNATURE: &[u8; 4] = b"lnch";
+
+/// Content type: plain text
+pub const CONTENT_TYPE_TEXT: u16 = 0;
+
+/// Content type: HTML
+pub const CONTENT_TYPE_HTML: u16 = 1;
+
+/// Content type: GIF image
+pub const CONTENT_TYPE_GIF: u16 = 2;
+
+/// Content type: JPEG image
+pub const CONTENT_TYPE_JPEG: u16 = 3;
+
+/// Content type: a CML page
+pub const CONTENT_TYPE_CML: u16 = 4;
+
+/// Content type: Palm OS bitmap
+pub const CONTENT_TYPE_PALM_BITMAP: u16 = 5;
+
+/// Compression type: the data is stored uncompressed
+pub const COMPRESSION_NONE: u16 = 0;
+
+/// Compression type: the data is compressed with LZ77 (the same scheme as PalmDoc)
+pub const COMPRESSION_LZ77: u16 = 2;
+
+/// CML opcode: end of the page, anything after this is ignored
+pub const CML_END_OF_PAGE: u8 = 0x00;
+
+/// CML opcode: start of the page title
+pub const CML_TITLE: u8 = 0x01;
+
+/// CML opcode: start of a heading, with the heading level (`1` to `6`) as a byte
+pub const CML_HEADING: u8 = 0x02;
+
+/// CML opcode: paragraph break
+pub const CML_PARAGRAPH: u8 = 0x03;
+
+/// CML opcode: line break
+pub const CML_LINE_BREAK: u8 = 0x04;
+
+/// CML opcode: start of a link, with the target URL as a string
+pub const CML_LINK: u8 = 0x05;
+
+/// CML opcode: image, with the width and height as words, then the URL and alternate text as
+/// strings
+pub const CML_IMAGE: u8 = 0x06;
+
+/// CML opcode: horizontal rule
+pub const CML_RULE: u8 = 0x07;
+
+/// CML opcode: start of bold text
+pub const CML_BOLD: u8 = 0x08;
+
+/// CML opcode: start of italic text
+pub const CML_ITALIC: u8 = 0x09;
+
+/// CML opcode: start of underlined text
+pub const CML_UNDERLINE: u8 = 0x0A;
+
+/// CML opcode: start of fixed width text
+pub const CML_FIXED: u8 = 0x0B;
+
+/// CML opcode: start of a list, with a byte which is non-zero for ordered lists
+pub const CML_LIST: u8 = 0x0C;
+
+/// CML opcode: list item
+pub const CML_LIST_ITEM: u8 = 0x0D;
+
+/// CML opcode: start of a table
+pub const CML_TABLE: u8 = 0x0E;
+
+/// CML opcode: start of a table row
+pub const CML_TABLE_ROW: u8 = 0x0F;
+
+/// CML opcode: start of a table cell
+pub const CML_TABLE_CELL: u8 = 0x10;
+
+/// CML opcode: start of a form, with the action URL as a string
+pub const CML_FORM: u8 = 0x11;
+
+/// CML opcode: form input, with the input type as a byte, then the name and initial value as
+/// strings
+pub const CML_INPUT: u8 = 0x12;
+
+/// CML opcode: start of centered text
+pub const CML_CENTER: u8 = 0x13;
+
+/// CML opcode: end of the most recently started container tag
+pub const CML_END: u8 = 0x1F;
+
+/// Read a length-prefixed field from the launcher header, where the length is given in 16-bit
+/// words
+fn read_words(rdr: &mut Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+	let words = rdr.read_u16::<BigEndian>()? as usize;
+	let mut buf = vec![0_u8; words * 2];
+	rdr.read_exact(&mut buf)?;
+
+	Ok(buf)
+}
+
+/// The PQA launcher header, stored in the app info block
+#[derive(Debug, Clone, PartialEq)]
+pub struct PqaHeader {
+	pub header_version: u16,
+	pub encoding_version: u16,
+
+	/// Application version string
+	pub version: String,
+
+	/// Application title, as shown in the Launcher
+	pub title: String,
+
+	/// Raw bitmap data of the large icon
+	pub icon: Vec<u8>,
+
+	/// Raw bitmap data of the small (list view) icon
+	pub small_icon: Vec<u8>,
+}
+
+impl PqaHeader {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut signature = [0_u8; 4];
+		rdr.read_exact(&mut signature)?;
+		if &signature != PQA_LAUNCHER_SIGNATURE {
+			return Err(io::Error::other(format!(
+				"invalid PQA launcher header signature {:?}",
+				String::from_utf8_lossy(&signature)
+			)));
+		}
+
+		let header_version = rdr.read_u16::<BigEndian>()?;
+		let encoding_version = rdr.read_u16::<BigEndian>()?;
+		let version = palm_to_string(trim_null(&read_words(rdr)?));
+		let title = palm_to_string(trim_null(&read_words(rdr)?));
+		let icon = read_words(rdr)?;
+		let small_icon = read_words(rdr)?;
+
+		Ok(Self {
+			header_version,
+			encoding_version,
+			version,
+			title,
+			icon,
+			small_icon,
+		})
+	}
+
+	/// Decode the large icon bitmap
+	pub fn icon_bitmap(&self) -> Result<BitmapFamily, io::Error> {
+		BitmapFamily::from_bytes(&mut Cursor::new(&self.icon))
+	}
+
+	/// Decode the small icon bitmap
+	pub fn small_icon_bitmap(&self) -> Result<BitmapFamily, io::Error> {
+		BitmapFamily::from_bytes(&mut Cursor::new(&self.small_icon))
+	}
+}
+
+/// The header at the start of each PQA record
+///
+/// The offsets are from the start of the record.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PqaRecordHeader {
+	pub url_offset: u16,
+	pub url_length: u16,
+	pub data_offset: u16,
+	pub data_length: u16,
+
+	/// Type of the file data (one of the `CONTENT_TYPE_*` constants)
+	pub content_type: u16,
+
+	/// Compression of the file data (one of the `COMPRESSION_*` constants)
+	pub compression_type: u16,
+
+	pub uncompressed_length: u32,
+	pub flags: u8,
+}
+
+impl PqaRecordHeader {
+	/// Length of the record header
+	pub const SIZE: usize = 18;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let url_offset = rdr.read_u16::<BigEndian>()?;
+		let url_length = rdr.read_u16::<BigEndian>()?;
+		let data_offset = rdr.read_u16::<BigEndian>()?;
+		let data_length = rdr.read_u16::<BigEndian>()?;
+		let content_type = rdr.read_u16::<BigEndian>()?;
+		let compression_type = rdr.read_u16::<BigEndian>()?;
+		let uncompressed_length = rdr.read_u32::<BigEndian>()?;
+		let flags = rdr.read_u8()?;
+		let _reserved = rdr.read_u8()?;
+
+		Ok(Self {
+			url_offset,
+			url_length,
+			data_offset,
+			data_length,
+			content_type,
+			compression_type,
+			uncompressed_length,
+			flags,
+		})
+	}
+}
+
+/// A single file from a PQA
+#[derive(Debug, Clone, PartialEq)]
+pub struct PqaRecord {
+	pub header: PqaRecordHeader,
+	pub url: String,
+
+	/// File data, as stored in the record (possibly compressed)
+	pub data: Vec<u8>,
+}
+
+impl PqaRecord {
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		let header = PqaRecordHeader::from_bytes(&mut Cursor::new(data))?;
+
+		let field = |offset: u16, length: u16, name: &str| {
+			let start = offset as usize;
+			data.get(start..(start + length as usize)).ok_or_else(|| {
+				io::Error::other(format!(
+					"PQA record {} runs past the end of the record",
+					name
+				))
+			})
+		};
+
+		let url = palm_to_string(trim_null(field(
+			header.url_offset,
+			header.url_length,
+			"URL",
+		)?));
+		let data = field(header.data_offset, header.data_length, "data")?.to_vec();
+
+		Ok(Self { header, url, data })
+	}
+
+	/// Return the file data, decompressed if necessary
+	pub fn decompress(&self) -> Result<Vec<u8>, io::Error> {
+		let data = match self.header.compression_type {
+			COMPRESSION_NONE => self.data.clone(),
+			COMPRESSION_LZ77 => palmdoc::decompress(&self.data)?,
+			other => {
+				return Err(io::Error::other(format!(
+					"unsupported PQA compression type {}",
+					other
+				)))
+			}
+		};
+
+		if data.len() != self.header.uncompressed_length as usize {
+			return Err(io::Error::other(format!(
+				"PQA record {:?} decompressed to {} bytes, expected {}",
+				self.url,
+				data.len(),
+				self.header.uncompressed_length
+			)));
+		}
+
+		Ok(data)
+	}
+}
+
+/// A single token from a CML page
+#[derive(Debug, Clone, PartialEq)]
+pub enum PqaToken {
+	/// A run of text, converted from the Palm OS character set
+	Text(String),
+
+	/// Start of the page title
+	Title,
+
+	/// Start of a heading, with its level
+	Heading(u8),
+
+	Paragraph,
+	LineBreak,
+
+	/// Start of a link to the given URL
+	Link(String),
+
+	Image {
+		width: u16,
+		height: u16,
+		url: String,
+		alt: String,
+	},
+
+	HorizontalRule,
+	Bold,
+	Italic,
+	Underline,
+	Fixed,
+
+	/// Start of a list, which is ordered if the value is `true`
+	List(bool),
+
+	ListItem,
+	Table,
+	TableRow,
+	TableCell,
+
+	/// Start of a form, with its action URL
+	Form(String),
+
+	Input {
+		input_type: u8,
+		name: String,
+		value: String,
+	},
+
+	Center,
+
+	/// End of the most recently started container tag
+	End,
+
+	/// Any other opcode
+	Unknown(u8),
+}
+
+/// Decode a CML page into text and tags
+///
+/// Decoding stops at the end of page tag, or at the end of the data; arguments which are cut
+/// short by the end of the data are read as zero or empty.
+pub fn tokenize(data: &[u8]) -> Vec<PqaToken> {
+	let mut tokens = Vec::new();
+	let mut idx = 0;
+
+	let byte = |idx: &mut usize| {
+		let value = *data.get(*idx).unwrap_or(&0);
+		*idx = (*idx + 1).min(data.len());
+		value
+	};
+	let word = |idx: &mut usize| u16::from_be_bytes([byte(idx), byte(idx)]);
+	let string = |idx: &mut usize| {
+		let start = *idx;
+		while *idx < data.len() && data[*idx] != 0 {
+			*idx += 1;
+		}
+
+		let value = palm_to_string(&data[start..*idx]);
+		*idx = (*idx + 1).min(data.len());
+		value
+	};
+
+	while idx < data.len() {
+		if data[idx] >= 0x20 {
+			let start = idx;
+			while idx < data.len() && data[idx] >= 0x20 {
+				idx += 1;
+			}
+
+			tokens.push(PqaToken::Text(palm_to_string(&data[start..idx])));
+			continue;
+		}
+
+		let token = match byte(&mut idx) {
+			CML_END_OF_PAGE => break,
+			CML_TITLE => PqaToken::Title,
+			CML_HEADING => PqaToken::Heading(byte(&mut idx)),
+			CML_PARAGRAPH => PqaToken::Paragraph,
+			CML_LINE_BREAK => PqaToken::LineBreak,
+			CML_LINK => PqaToken::Link(string(&mut idx)),
+			CML_IMAGE => PqaToken::Image {
+				width: word(&mut idx),
+				height: word(&mut idx),
+				url: string(&mut idx),
+				alt: string(&mut idx),
+			},
+			CML_RULE => PqaToken::HorizontalRule,
+			CML_BOLD => PqaToken::Bold,
+			CML_ITALIC => PqaToken::Italic,
+			CML_UNDERLINE => PqaToken::Underline,
+			CML_FIXED => PqaToken::Fixed,
+			CML_LIST => PqaToken::List(byte(&mut idx) != 0),
+			CML_LIST_ITEM => PqaToken::ListItem,
+			CML_TABLE => PqaToken::Table,
+			CML_TABLE_ROW => PqaToken::TableRow,
+			CML_TABLE_CELL => PqaToken::TableCell,
+			CML_FORM => PqaToken::Form(string(&mut idx)),
+			CML_INPUT => PqaToken::Input {
+				input_type: byte(&mut idx),
+				name: string(&mut idx),
+				value: string(&mut idx),
+			},
+			CML_CENTER => PqaToken::Center,
+			CML_END => PqaToken::End,
+			other => PqaToken::Unknown(other),
+		};
+
+		tokens.push(token);
+	}
+
+	tokens
+}
+
+/// An image from a PQA
+#[derive(Debug, Clone, PartialEq)]
+pub struct PqaImage {
+	/// URL of the image, as referred to by [`PqaToken::Image`]
+	pub url: String,
+
+	/// Type of the image data: [`CONTENT_TYPE_GIF`] or [`CONTENT_TYPE_JPEG`] for images stored
+	/// as-is, or [`CONTENT_TYPE_PALM_BITMAP`] for Palm OS bitmaps, which are converted to PNG
+	pub content_type: u16,
+
+	pub data: Vec<u8>,
+}
+
+/// A PQA database
+#[derive(Debug)]
+pub struct PqaDatabase {
+	pub database: PalmDatabase<PdbDatabase>,
+	pub header: PqaHeader,
+}
+
+impl PqaDatabase {
+	/// Read a PQA from the given PDB file data
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		let database = PalmDatabase::<PdbDatabase>::from_bytes(data)?;
+		if &database.header.type_code != PQA_TYPE_CODE {
+			return Err(io::Error::other(format!(
+				"database type {:?} is not a PQA",
+				String::from_utf8_lossy(&database.header.type_code)
+			)));
+		}
+
+		// The app info block runs from its offset up to the first record's data, or the end of
+		// the file if there are no records
+		let app_info_start = database.header.app_info_id as usize;
+		let app_info_end = database
+			.list_records_resources()
+			.first()
+			.map(|(hdr, _)| hdr.data_offset() as usize)
+			.unwrap_or_else(|| data.len());
+		let app_info = data
+			.get(app_info_start.max(DatabaseHeader::SIZE)..app_info_end)
+			.filter(|_| app_info_start != 0)
+			.ok_or_else(|| io::Error::other("PQA has no app info block"))?;
+
+		let header = PqaHeader::from_bytes(&mut Cursor::new(app_info))?;
+
+		Ok(Self { database, header })
+	}
+
+	pub fn title(&self) -> &str {
+		&self.header.title
+	}
+
+	pub fn version(&self) -> &str {
+		&self.header.version
+	}
+
+	/// Return the standard-density monochrome version of the large icon, if there is one
+	pub fn icon_image(&self) -> Option<MonoImage> {
+		self.header.icon_bitmap().ok()?.to_mono_image()
+	}
+
+	/// Return the raw data of the record with the given index
+	pub fn content(&self, index: usize) -> Option<&[u8]> {
+		self.database
+			.list_records_resources()
+			.get(index)
+			.map(|(_, data)| data.as_slice())
+	}
+
+	/// Decode the record with the given index
+	pub fn record(&self, index: usize) -> Result<Option<PqaRecord>, io::Error> {
+		self.content(index).map(PqaRecord::from_bytes).transpose()
+	}
+
+	/// Decode all of the records
+	pub fn records(&self) -> Result<Vec<PqaRecord>, io::Error> {
+		self.database
+			.list_records_resources()
+			.iter()
+			.map(|(_, data)| PqaRecord::from_bytes(data))
+			.collect()
+	}
+
+	/// Return the record with the given URL
+	pub fn find_url(&self, url: &str) -> Result<Option<PqaRecord>, io::Error> {
+		Ok(self.records()?.into_iter().find(|x| x.url == url))
+	}
+
+	/// Decompress and decode the CML page in the record with the given index
+	pub fn content_tokens(&self, index: usize) -> Result<Option<Vec<PqaToken>>, io::Error> {
+		let record = match self.record(index)? {
+			Some(x) => x,
+			None => return Ok(None),
+		};
+
+		if record.header.content_type != CONTENT_TYPE_CML {
+			return Err(io::Error::other(format!(
+				"PQA record {:?} is not a CML page (content type {})",
+				record.url, record.header.content_type
+			)));
+		}
+
+		Ok(Some(tokenize(&record.decompress()?)))
+	}
+
+	/// Extract the images from the PQA
+	///
+	/// Palm OS bitmaps are converted to PNG; GIF and JPEG images are returned as they are.
+	pub fn images(&self) -> Result<Vec<PqaImage>, io::Error> {
+		let mut images = Vec::new();
+		for record in self.records()? {
+			let data = match record.header.content_type {
+				CONTENT_TYPE_GIF | CONTENT_TYPE_JPEG => record.decompress()?,
+				CONTENT_TYPE_PALM_BITMAP => {
+					let data = record.decompress()?;
+					let family = BitmapFamily::from_bytes(&mut Cursor::new(&data))?;
+					match family.to_image() {
+						Some(x) => x.to_png(),
+						None => continue,
+					}
+				}
+				_ => continue,
+			};
+
+			images.push(PqaImage {
+				url: record.url,
+				content_type: record.header.content_type,
+				data,
+			});
+		}
+
+		Ok(images)
+	}
+}
@@ -0,0 +1,504 @@
+//! Preferences databases (`Saved Preferences`, `Unsaved Preferences`)
+//!
+//! Palm OS keeps application preferences as resources in two PRC databases: `Saved Preferences`,
+//! which is backed up during a HotSync, and `Unsaved Preferences`, which isn't. Each resource is
+//! keyed by the creator code of the application that owns it (as the resource type) and a
+//! preference ID chosen by the application, and the data starts with a version number, also
+//! chosen by the application.
+//!
+//! The system preferences (`SystemPreferencesType`) are stored in `Saved Preferences` under the
+//! system creator code (`psys`) with ID `0`, and hold the device's country and its date, time,
+//! and number formats, among other things. [`SystemPreferences`] decodes them, and provides
+//! helpers for formatting values the way the device would display them.
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+use chrono::{FixedOffset, NaiveDate, NaiveTime, Weekday};
+
+use crate::{
+	record::DatabaseRecord,
+	resource::overlay::Locale,
+	text::{palm_to_string, trim_null},
+	PalmDatabase,
+	PrcDatabase,
+};
+
+/// Database type code of the preferences databases
+pub const PREFS_TYPE_CODE: &[u8; 4] = b"sprf";
+
+/// Creator code the system preferences are stored under
+pub const SYSTEM_PREFS_CREATOR_CODE: &[u8; 4] = b"psys";
+
+/// Preference ID of the system preferences
+pub const SYSTEM_PREFS_ID: u16 = 0;
+
+/// Creator code of the Owner preferences panel, which stores the owner text
+pub const OWNER_PREFS_CREATOR_CODE: &[u8; 4] = b"ownr";
+
+/// Length, in bytes, of the system preferences fields added in version 3
+const SYSTEM_PREFS_V3_LENGTH: u64 = 8;
+
+/// Date display format (`DateFormatType`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DateFormat {
+	/// `12/31/95`
+	MdyWithSlashes,
+	/// `31/12/95`
+	DmyWithSlashes,
+	/// `31.12.95`
+	DmyWithDots,
+	/// `31-12-95`
+	DmyWithDashes,
+	/// `95/12/31`
+	YmdWithSlashes,
+	/// `95.12.31`
+	YmdWithDots,
+	/// `95-12-31`
+	YmdWithDashes,
+	/// `Dec 31, 1995`
+	MdyLongWithComma,
+	/// `31 Dec 1995`
+	DmyLong,
+	/// `31. Dec 1995`
+	DmyLongWithDot,
+	/// `Dec 1995`
+	DmyLongNoDay,
+	/// `31 Dec, 1995`
+	DmyLongWithComma,
+	/// `1995.12.31`
+	YmdLongWithDot,
+	/// `1995 Dec 31`
+	YmdLongWithSpace,
+	/// `Dec '95`
+	MyMed,
+	/// `Dec 95`
+	MyMedNoPost,
+	/// `12-31-95`
+	MdyWithDashes,
+	Unknown(u8),
+}
+
+impl DateFormat {
+	/// Return the `chrono` format string equivalent to this format
+	pub fn pattern(&self) -> Option<&'static str> {
+		Some(match self {
+			Self::MdyWithSlashes => "%-m/%-d/%y",
+			Self::DmyWithSlashes => "%-d/%-m/%y",
+			Self::DmyWithDots => "%-d.%-m.%y",
+			Self::DmyWithDashes => "%-d-%-m-%y",
+			Self::YmdWithSlashes => "%y/%-m/%-d",
+			Self::YmdWithDots => "%y.%-m.%-d",
+			Self::YmdWithDashes => "%y-%-m-%-d",
+			Self::MdyLongWithComma => "%b %-d, %Y",
+			Self::DmyLong => "%-d %b %Y",
+			Self::DmyLongWithDot => "%-d. %b %Y",
+			Self::DmyLongNoDay => "%b %Y",
+			Self::DmyLongWithComma => "%-d %b, %Y",
+			Self::YmdLongWithDot => "%Y.%-m.%-d",
+			Self::YmdLongWithSpace => "%Y %b %-d",
+			Self::MyMed => "%b '%y",
+			Self::MyMedNoPost => "%b %y",
+			Self::MdyWithDashes => "%-m-%-d-%y",
+			Self::Unknown(_) => return None,
+		})
+	}
+
+	/// Format the given date, if the format is known
+	pub fn format(&self, date: &NaiveDate) -> Option<String> {
+		Some(date.format(self.pattern()?).to_string())
+	}
+}
+
+impl From<u8> for DateFormat {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::MdyWithSlashes,
+			1 => Self::DmyWithSlashes,
+			2 => Self::DmyWithDots,
+			3 => Self::DmyWithDashes,
+			4 => Self::YmdWithSlashes,
+			5 => Self::YmdWithDots,
+			6 => Self::YmdWithDashes,
+			7 => Self::MdyLongWithComma,
+			8 => Self::DmyLong,
+			9 => Self::DmyLongWithDot,
+			10 => Self::DmyLongNoDay,
+			11 => Self::DmyLongWithComma,
+			12 => Self::YmdLongWithDot,
+			13 => Self::YmdLongWithSpace,
+			14 => Self::MyMed,
+			15 => Self::MyMedNoPost,
+			16 => Self::MdyWithDashes,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+/// Time display format (`TimeFormatType`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeFormat {
+	/// `1:00`
+	Colon,
+	/// `1:00 pm`
+	ColonAmPm,
+	/// `13:00`
+	Colon24h,
+	/// `1.00`
+	Dot,
+	/// `1.00 pm`
+	DotAmPm,
+	/// `13.00`
+	Dot24h,
+	/// `1 pm`
+	HoursAmPm,
+	/// `13`
+	Hours24h,
+	/// `13,00`
+	Comma24h,
+	Unknown(u8),
+}
+
+impl TimeFormat {
+	/// Whether this format uses the 24-hour clock
+	pub fn is_24_hour(&self) -> bool {
+		matches!(
+			self,
+			Self::Colon24h | Self::Dot24h | Self::Hours24h | Self::Comma24h
+		)
+	}
+
+	/// Return the `chrono` format string equivalent to this format
+	pub fn pattern(&self) -> Option<&'static str> {
+		Some(match self {
+			Self::Colon => "%-I:%M",
+			Self::ColonAmPm => "%-I:%M %P",
+			Self::Colon24h => "%-H:%M",
+			Self::Dot => "%-I.%M",
+			Self::DotAmPm => "%-I.%M %P",
+			Self::Dot24h => "%-H.%M",
+			Self::HoursAmPm => "%-I %P",
+			Self::Hours24h => "%-H",
+			Self::Comma24h => "%-H,%M",
+			Self::Unknown(_) => return None,
+		})
+	}
+
+	/// Format the given time, if the format is known
+	pub fn format(&self, time: &NaiveTime) -> Option<String> {
+		Some(time.format(self.pattern()?).to_string())
+	}
+}
+
+impl From<u8> for TimeFormat {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::Colon,
+			1 => Self::ColonAmPm,
+			2 => Self::Colon24h,
+			3 => Self::Dot,
+			4 => Self::DotAmPm,
+			5 => Self::Dot24h,
+			6 => Self::HoursAmPm,
+			7 => Self::Hours24h,
+			8 => Self::Comma24h,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+/// Number display format (`NumberFormatType`)
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NumberFormat {
+	/// `1,000.00`
+	CommaPeriod,
+	/// `1.000,00`
+	PeriodComma,
+	/// `1 000,00`
+	SpaceComma,
+	/// `1'000.00`
+	ApostrophePeriod,
+	/// `1'000,00`
+	ApostropheComma,
+	Unknown(u8),
+}
+
+impl NumberFormat {
+	/// Return the thousands and decimal separators of this format, if it is known
+	pub fn separators(&self) -> Option<(char, char)> {
+		Some(match self {
+			Self::CommaPeriod => (',', '.'),
+			Self::PeriodComma => ('.', ','),
+			Self::SpaceComma => (' ', ','),
+			Self::ApostrophePeriod => ('\'', '.'),
+			Self::ApostropheComma => ('\'', ','),
+			Self::Unknown(_) => return None,
+		})
+	}
+
+	/// Format the given number with the given number of decimal places
+	///
+	/// Unknown formats are treated as [`NumberFormat::CommaPeriod`].
+	pub fn format(&self, value: f64, places: usize) -> String {
+		let (thousands, decimal) = self.separators().unwrap_or((',', '.'));
+		let digits = format!("{:.*}", places, value.abs());
+		let (whole, fraction) = match digits.split_once('.') {
+			Some((whole, fraction)) => (whole, Some(fraction)),
+			None => (digits.as_str(), None),
+		};
+
+		let mut out = String::new();
+		if value < 0.0 && digits.chars().any(|x| x != '0' && x != '.') {
+			out.push('-');
+		}
+
+		for (idx, ch) in whole.chars().enumerate() {
+			if idx > 0 && (whole.len() - idx) % 3 == 0 {
+				out.push(thousands);
+			}
+
+			out.push(ch);
+		}
+
+		if let Some(fraction) = fraction {
+			out.push(decimal);
+			out.push_str(fraction);
+		}
+
+		out
+	}
+}
+
+impl From<u8> for NumberFormat {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::CommaPeriod,
+			1 => Self::PeriodComma,
+			2 => Self::SpaceComma,
+			3 => Self::ApostrophePeriod,
+			4 => Self::ApostropheComma,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+/// The system preferences (`SystemPreferencesType`)
+///
+/// Only the fields common to every Palm OS version are decoded - fields added in later versions
+/// are kept, undecoded, in `extra`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SystemPreferences {
+	pub version: u16,
+
+	/// Country number, as used in [`Locale`]
+	pub country: u8,
+
+	pub date_format: DateFormat,
+	pub long_date_format: DateFormat,
+
+	/// First day of the week, where `0` is Sunday
+	pub week_start_day: i8,
+
+	pub time_format: TimeFormat,
+	pub number_format: NumberFormat,
+
+	/// Minutes of inactivity before the device turns itself off
+	pub auto_off_duration: u8,
+
+	pub sys_sound_level: u8,
+	pub game_sound_level: u8,
+	pub alarm_sound_level: u8,
+	pub hide_secret_records: bool,
+	pub device_locked: bool,
+	pub sys_pref_flags: u16,
+	pub battery_kind: u8,
+
+	/// Minutes west of UTC of the device's time zone (version 3 and later)
+	pub minutes_west_of_gmt: Option<u32>,
+
+	/// Daylight saving time rule (`DaylightSavingsTypes`, version 3 and later)
+	pub daylight_savings: Option<u8>,
+
+	/// Character that launches the Graffiti ShortCut ("ronamatic") stroke (version 3 and later)
+	pub ronamatic_char: Option<u16>,
+
+	pub extra: Vec<u8>,
+}
+
+impl SystemPreferences {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let version = rdr.read_u16::<BigEndian>()?;
+		let country = rdr.read_u8()?;
+		let date_format = rdr.read_u8()?.into();
+		let long_date_format = rdr.read_u8()?.into();
+		let week_start_day = rdr.read_i8()?;
+		let time_format = rdr.read_u8()?.into();
+		let number_format = rdr.read_u8()?.into();
+		let auto_off_duration = rdr.read_u8()?;
+		let sys_sound_level = rdr.read_u8()?;
+		let game_sound_level = rdr.read_u8()?;
+		let alarm_sound_level = rdr.read_u8()?;
+		let hide_secret_records = rdr.read_u8()? != 0;
+		let device_locked = rdr.read_u8()? != 0;
+		let _reserved1 = rdr.read_u8()?;
+		let sys_pref_flags = rdr.read_u16::<BigEndian>()?;
+		let battery_kind = rdr.read_u8()?;
+		let _reserved2 = rdr.read_u8()?;
+
+		let remaining = rdr.get_ref().len() as u64 - rdr.position();
+		let (minutes_west_of_gmt, daylight_savings, ronamatic_char) =
+			if version >= 3 && remaining >= SYSTEM_PREFS_V3_LENGTH {
+				let minutes_west_of_gmt = rdr.read_u32::<BigEndian>()?;
+				let daylight_savings = rdr.read_u8()?;
+				let _reserved3 = rdr.read_u8()?;
+				let ronamatic_char = rdr.read_u16::<BigEndian>()?;
+
+				(
+					Some(minutes_west_of_gmt),
+					Some(daylight_savings),
+					Some(ronamatic_char),
+				)
+			} else {
+				(None, None, None)
+			};
+
+		let mut extra = Vec::new();
+		rdr.read_to_end(&mut extra)?;
+
+		Ok(Self {
+			version,
+			country,
+			date_format,
+			long_date_format,
+			week_start_day,
+			time_format,
+			number_format,
+			auto_off_duration,
+			sys_sound_level,
+			game_sound_level,
+			alarm_sound_level,
+			hide_secret_records,
+			device_locked,
+			sys_pref_flags,
+			battery_kind,
+			minutes_west_of_gmt,
+			daylight_savings,
+			ronamatic_char,
+			extra,
+		})
+	}
+
+	/// Return the ISO 3166 code of the device's country, if it is known
+	pub fn country_code(&self) -> Option<&'static str> {
+		Locale::new(0, self.country as u16).country_code()
+	}
+
+	/// Return the first day of the week
+	pub fn week_start(&self) -> Weekday {
+		match self.week_start_day {
+			1 => Weekday::Mon,
+			_ => Weekday::Sun,
+		}
+	}
+
+	/// Return the offset from UTC of the device's time zone, if it is known
+	pub fn utc_offset(&self) -> Option<FixedOffset> {
+		let minutes_east = -(self.minutes_west_of_gmt? as i32);
+		FixedOffset::east_opt(minutes_east * 60)
+	}
+}
+
+/// A single application's preferences
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppPreferences {
+	pub creator_code: [u8; 4],
+	pub id: u16,
+
+	/// Preferences version, as passed to `PrefSetAppPreferences`
+	pub version: u16,
+
+	pub data: Vec<u8>,
+}
+
+impl AppPreferences {
+	/// Decode a preferences resource, returning `None` if it is too short to have a version
+	pub fn from_resource(creator_code: [u8; 4], id: u16, data: &[u8]) -> Option<Self> {
+		let version = u16::from_be_bytes([*data.first()?, *data.get(1)?]);
+
+		Some(Self {
+			creator_code,
+			id,
+			version,
+			data: data[2..].to_vec(),
+		})
+	}
+
+	/// Return the creator code as a string, if it is valid UTF-8
+	pub fn creator_code_str(&self) -> Option<&str> {
+		core::str::from_utf8(&self.creator_code).ok()
+	}
+}
+
+/// A preferences database
+#[derive(Debug)]
+pub struct PreferencesDatabase {
+	pub database: PalmDatabase<PrcDatabase>,
+}
+
+impl PreferencesDatabase {
+	pub fn from_database(database: PalmDatabase<PrcDatabase>) -> Self {
+		Self { database }
+	}
+
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		Ok(Self::from_database(
+			PalmDatabase::<PrcDatabase>::from_bytes(data)?,
+		))
+	}
+
+	/// Return the preferences of every application
+	pub fn app_preferences(&self) -> Vec<AppPreferences> {
+		self.database
+			.list_records_resources()
+			.iter()
+			.filter_map(|(hdr, data)| {
+				let mut creator_code = [0_u8; 4];
+				creator_code.copy_from_slice(hdr.name_str()?.as_bytes().get(0..4)?);
+				AppPreferences::from_resource(creator_code, hdr.resource_id()?, data)
+			})
+			.collect()
+	}
+
+	/// Return the preferences with the given creator code and ID
+	pub fn app_preference(&self, creator_code: &[u8; 4], id: u16) -> Option<AppPreferences> {
+		self.app_preferences()
+			.into_iter()
+			.find(|x| &x.creator_code == creator_code && x.id == id)
+	}
+
+	/// Decode the system preferences, if the database contains them
+	pub fn system_preferences(&self) -> Result<Option<SystemPreferences>, io::Error> {
+		let data = self
+			.database
+			.list_records_resources()
+			.iter()
+			.find(|(hdr, _)| {
+				hdr.name_str().map(str::as_bytes) == Some(&SYSTEM_PREFS_CREATOR_CODE[..])
+					&& hdr.resource_id() == Some(SYSTEM_PREFS_ID)
+			})
+			.map(|(_, data)| data.as_slice());
+
+		match data {
+			Some(data) => Ok(Some(SystemPreferences::from_bytes(&mut Cursor::new(data))?)),
+			None => Ok(None),
+		}
+	}
+
+	/// Return the owner text set in the Owner preferences panel, if there is one
+	pub fn owner(&self) -> Option<String> {
+		self.app_preferences()
+			.into_iter()
+			.find(|x| &x.creator_code == OWNER_PREFS_CREATOR_CODE)
+			.map(|x| palm_to_string(trim_null(&x.data)))
+	}
+}
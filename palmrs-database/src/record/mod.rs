@@ -60,6 +60,10 @@ mod sealed {
 		fn next_entry_data_offset(&self) -> usize;
 
 		fn data_offset(&self) -> u32;
+
+		fn set_data_offset(&mut self, data_offset: u32);
+
+		fn set_data_len(&mut self, data_len: Option<u32>);
 	}
 }
 
@@ -130,6 +130,20 @@ impl DatabaseRecordHelpers for PdbRecordHeader {
 			Self::Resource { data_offset, .. } => *data_offset,
 		}
 	}
+
+	fn set_data_offset(&mut self, new_offset: u32) {
+		match self {
+			Self::Record { data_offset, .. } => *data_offset = new_offset,
+			Self::Resource { data_offset, .. } => *data_offset = new_offset,
+		}
+	}
+
+	fn set_data_len(&mut self, new_len: Option<u32>) {
+		match self {
+			Self::Record { data_len, .. } => *data_len = new_len,
+			Self::Resource { data_len, .. } => *data_len = new_len,
+		}
+	}
 }
 
 impl DatabaseRecord for PdbRecordHeader {
@@ -346,7 +360,11 @@ mod test {
 	use crate::{
 		header::DATABASE_HEADER_LENGTH,
 		record::{pdb_record::PdbRecordHeader, DatabaseRecord, DatabaseRecordHelpers},
-		DatabaseFormat, PalmDatabase, PdbDatabase, PdbWithCategoriesDatabase, PrcDatabase,
+		DatabaseFormat,
+		PalmDatabase,
+		PdbDatabase,
+		PdbWithCategoriesDatabase,
+		PrcDatabase,
 	};
 
 	const EXAMPLE_PRC: &'static [u8] = include_bytes!("../../../test-data/hello-v1.prc");
@@ -0,0 +1,90 @@
+//! Alert resources (`Talt`)
+
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	resource::ResourceType,
+	text::{palm_to_string, read_cstr},
+};
+
+/// The kind of an alert, which determines the icon shown alongside the message
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AlertKind {
+	Information,
+	Confirmation,
+	Warning,
+	Error,
+	Unknown(u16),
+}
+
+impl From<u16> for AlertKind {
+	fn from(value: u16) -> Self {
+		match value {
+			0 => Self::Information,
+			1 => Self::Confirmation,
+			2 => Self::Warning,
+			3 => Self::Error,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+/// An alert resource (`Talt`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+	pub kind: AlertKind,
+
+	/// Resource ID of the `tSTR` help text for this alert, or zero if there is none
+	pub help_rsc_id: u16,
+
+	/// Index of the button to use if the alert is dismissed without a button being tapped
+	pub default_button: u16,
+
+	pub title: String,
+
+	/// Alert message text
+	///
+	/// This may contain the `^1`, `^2` and `^3` placeholders used by `FrmCustomAlert`.
+	pub message: String,
+
+	pub buttons: Vec<String>,
+}
+
+impl Alert {
+	/// Return the title, message, and button text of this alert
+	pub fn strings(&self) -> Vec<String> {
+		let mut strings = vec![self.title.clone(), self.message.clone()];
+		strings.extend(self.buttons.iter().cloned());
+		strings
+	}
+}
+
+impl ResourceType for Alert {
+	const TYPE_CODES: &'static [&'static str] = &["Talt"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let kind = AlertKind::from(rdr.read_u16::<BigEndian>()?);
+		let help_rsc_id = rdr.read_u16::<BigEndian>()?;
+		let button_count = rdr.read_u16::<BigEndian>()?;
+		let default_button = rdr.read_u16::<BigEndian>()?;
+
+		let title = palm_to_string(&read_cstr(rdr)?);
+		let message = palm_to_string(&read_cstr(rdr)?);
+
+		let mut buttons = Vec::with_capacity(button_count as usize);
+		for _ in 0..button_count {
+			buttons.push(palm_to_string(&read_cstr(rdr)?));
+		}
+
+		Ok(Self {
+			kind,
+			help_rsc_id,
+			default_button,
+			title,
+			message,
+			buttons,
+		})
+	}
+}
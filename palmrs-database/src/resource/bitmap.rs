@@ -0,0 +1,434 @@
+//! Bitmaps (`Tbmp` and `tAIB`)
+//!
+//! Palm OS bitmaps are stored as a family: a chain of bitmaps of the same image at different bit
+//! depths (and, from Palm OS 5, densities), each linked to the next by an offset in its header.
+//! Version 0 bitmaps are always monochrome; versions 1 and 2 add the pixel size and the link to
+//! the next bitmap, and version 3 adds the density.
+//!
+//! Bitmaps of up to 8 bits per pixel are indexed: 1, 2 and 4 bit bitmaps are greyscale (unless
+//! they carry their own colour table), and 8 bit bitmaps use either their own colour table or the
+//! Palm OS system palette. 16 bit bitmaps are direct colour, in RGB565 format.
+
+use core::fmt;
+use std::io::{self, Cursor, Read, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	image::{MonoImage, RgbaImage},
+	resource::{font::DENSITY_STANDARD, ResourceType},
+};
+
+/// Bitmap flag: the bitmap data is compressed
+pub const BITMAP_FLAG_COMPRESSED: u16 = 0x8000;
+
+/// Bitmap flag: a colour table follows the header
+pub const BITMAP_FLAG_HAS_COLOR_TABLE: u16 = 0x4000;
+
+/// Bitmap flag: pixels with the transparent value should not be drawn
+pub const BITMAP_FLAG_HAS_TRANSPARENCY: u16 = 0x2000;
+
+/// Bitmap flag: the bitmap is direct colour (version 2 only)
+pub const BITMAP_FLAG_DIRECT_COLOR: u16 = 0x0400;
+
+/// Compression type: each row is stored as the changes from the previous row
+pub const COMPRESSION_SCANLINE: u8 = 0;
+
+/// Compression type: run-length encoded
+pub const COMPRESSION_RLE: u8 = 1;
+
+/// Compression type: PackBits encoded
+pub const COMPRESSION_PACKBITS: u8 = 2;
+
+/// Pixel format (version 3): 16 bit pixels are little-endian RGB565
+const PIXEL_FORMAT_RGB565_LE: u8 = 3;
+
+/// Pixel size of the placeholder bitmap separating low-density and high-density family members
+const PIXEL_SIZE_PLACEHOLDER: u8 = 0xFF;
+
+/// Length of the direct colour information following a version 2 direct colour bitmap header
+const DIRECT_INFO_LENGTH: i64 = 8;
+
+/// Return the colour of the given index in the Palm OS 8-bit system palette
+///
+/// The first 216 entries are a colour cube, followed by extra greys, a handful of named colours,
+/// and black for the remainder.
+pub fn system_palette(index: u8) -> [u8; 3] {
+	const CUBE: [u8; 6] = [0xFF, 0xCC, 0x99, 0x66, 0x33, 0x00];
+	const EXTRA: [[u8; 3]; 15] = [
+		[0x11, 0x11, 0x11],
+		[0x22, 0x22, 0x22],
+		[0x44, 0x44, 0x44],
+		[0x55, 0x55, 0x55],
+		[0x77, 0x77, 0x77],
+		[0x88, 0x88, 0x88],
+		[0xAA, 0xAA, 0xAA],
+		[0xBB, 0xBB, 0xBB],
+		[0xDD, 0xDD, 0xDD],
+		[0xEE, 0xEE, 0xEE],
+		[0xC0, 0xC0, 0xC0],
+		[0x80, 0x00, 0x00],
+		[0x80, 0x00, 0x80],
+		[0x00, 0x80, 0x00],
+		[0x00, 0x80, 0x80],
+	];
+
+	let index = index as usize;
+	if index < 216 {
+		[CUBE[index / 36], CUBE[index % 6], CUBE[(index / 6) % 6]]
+	} else {
+		*EXTRA.get(index - 216).unwrap_or(&[0, 0, 0])
+	}
+}
+
+/// A single bitmap, from a bitmap family
+#[derive(Clone, PartialEq)]
+pub struct Bitmap {
+	pub width: u16,
+	pub height: u16,
+	pub row_bytes: u16,
+	pub flags: u16,
+	pub pixel_size: u8,
+	pub version: u8,
+
+	/// Screen density, in dots per inch (always the standard density before version 3)
+	pub density: u16,
+
+	/// Compression type, if the bitmap is compressed (always scanline compression before
+	/// version 2)
+	pub compression_type: u8,
+
+	/// Pixel format (always indexed or big-endian before version 3)
+	pub pixel_format: u8,
+
+	/// Raw pixel value that is transparent, if the transparency flag is set
+	pub transparent_value: u32,
+
+	/// The bitmap's own colour table, if it has one
+	pub color_table: Vec<[u8; 3]>,
+
+	/// Raw bitmap data, without any compressed size prefix
+	pub data: Vec<u8>,
+}
+
+impl Bitmap {
+	/// Whether the bitmap data is compressed
+	pub fn is_compressed(&self) -> bool {
+		self.flags & BITMAP_FLAG_COMPRESSED != 0
+	}
+
+	/// Return the uncompressed bitmap data
+	pub fn pixels(&self) -> Result<Vec<u8>, io::Error> {
+		let length = self.row_bytes as usize * self.height as usize;
+		if !self.is_compressed() {
+			return Ok(self.data.clone());
+		}
+
+		let mut out = Vec::with_capacity(length);
+		let mut rdr = Cursor::new(self.data.as_slice());
+		match self.compression_type {
+			COMPRESSION_SCANLINE => {
+				let row_bytes = self.row_bytes as usize;
+				for row in 0..(self.height as usize) {
+					for group in (0..row_bytes).step_by(8) {
+						let changed = rdr.read_u8()?;
+						for offset in group..(group + 8).min(row_bytes) {
+							if changed & (0x80 >> (offset - group)) != 0 {
+								out.push(rdr.read_u8()?);
+							} else if row == 0 {
+								out.push(0);
+							} else {
+								out.push(out[((row - 1) * row_bytes) + offset]);
+							}
+						}
+					}
+				}
+			}
+
+			COMPRESSION_RLE => {
+				while out.len() < length {
+					let count = rdr.read_u8()? as usize;
+					let value = rdr.read_u8()?;
+					out.resize(out.len() + count, value);
+				}
+			}
+
+			COMPRESSION_PACKBITS => {
+				// 16 bit bitmaps are packed in units of whole pixels
+				let unit = if self.pixel_size == 16 { 2 } else { 1 };
+				while out.len() < length {
+					let count = rdr.read_i8()?;
+					match count {
+						-128 => {}
+						0..=127 => {
+							let mut buf = vec![0_u8; (count as usize + 1) * unit];
+							rdr.read_exact(&mut buf)?;
+							out.extend_from_slice(&buf);
+						}
+						_ => {
+							let mut buf = vec![0_u8; unit];
+							rdr.read_exact(&mut buf)?;
+							for _ in 0..(1 - count as isize) {
+								out.extend_from_slice(&buf);
+							}
+						}
+					}
+				}
+			}
+
+			other => {
+				return Err(io::Error::other(format!(
+					"unsupported bitmap compression type {}",
+					other
+				)))
+			}
+		}
+
+		out.truncate(length);
+		Ok(out)
+	}
+
+	/// Convert a monochrome bitmap to a [`MonoImage`]
+	pub fn to_mono_image(&self) -> Option<MonoImage> {
+		if self.pixel_size != 1 {
+			return None;
+		}
+
+		let data = self.pixels().ok()?;
+		let mut image = MonoImage::new(self.width as usize, self.height as usize);
+		for y in 0..(self.height as usize) {
+			for x in 0..(self.width as usize) {
+				let byte = data.get((y * self.row_bytes as usize) + (x / 8))?;
+				image.set(x, y, byte & (0x80 >> (x % 8)) != 0);
+			}
+		}
+
+		Some(image)
+	}
+
+	/// Return the colour of the given pixel value of an indexed bitmap
+	fn index_color(&self, value: u8) -> [u8; 3] {
+		if let Some(color) = self.color_table.get(value as usize) {
+			return *color;
+		}
+
+		match self.pixel_size {
+			8 => system_palette(value),
+			depth => {
+				// Greyscale, where zero is white and the largest value is black
+				let max = (1_u16 << depth) - 1;
+				let grey = 0xFF - ((value as u16 * 0xFF) / max) as u8;
+				[grey, grey, grey]
+			}
+		}
+	}
+
+	/// Convert the bitmap to an [`RgbaImage`]
+	///
+	/// Returns `None` if the pixel size is unsupported, or the bitmap data is invalid.
+	pub fn to_image(&self) -> Option<RgbaImage> {
+		if ![1, 2, 4, 8, 16].contains(&self.pixel_size) {
+			return None;
+		}
+
+		let data = self.pixels().ok()?;
+		let transparent = self.flags & BITMAP_FLAG_HAS_TRANSPARENCY != 0;
+		let depth = self.pixel_size as usize;
+
+		let mut image = RgbaImage::new(self.width as usize, self.height as usize);
+		for y in 0..(self.height as usize) {
+			let row = data.get((y * self.row_bytes as usize)..)?;
+			for x in 0..(self.width as usize) {
+				let (value, [r, g, b]) = if depth == 16 {
+					let bytes = [*row.get(x * 2)?, *row.get((x * 2) + 1)?];
+					let value = match self.pixel_format {
+						PIXEL_FORMAT_RGB565_LE => u16::from_le_bytes(bytes),
+						_ => u16::from_be_bytes(bytes),
+					};
+
+					let r = ((value >> 11) & 0x1F) as u8;
+					let g = ((value >> 5) & 0x3F) as u8;
+					let b = (value & 0x1F) as u8;
+					let rgb = [
+						(r << 3) | (r >> 2),
+						(g << 2) | (g >> 4),
+						(b << 3) | (b >> 2),
+					];
+					(value as u32, rgb)
+				} else {
+					let bit = x * depth;
+					let byte = *row.get(bit / 8)?;
+					let value = (byte >> (8 - depth - (bit % 8))) & ((1 << depth) - 1) as u8;
+					(value as u32, self.index_color(value))
+				};
+
+				let alpha = if transparent && value == self.transparent_value {
+					0x00
+				} else {
+					0xFF
+				};
+
+				image.set(x, y, [r, g, b, alpha]);
+			}
+		}
+
+		Some(image)
+	}
+}
+
+/// Debug output omits the bitmap data, which would otherwise swamp everything else
+impl fmt::Debug for Bitmap {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Bitmap")
+			.field("width", &self.width)
+			.field("height", &self.height)
+			.field("row_bytes", &self.row_bytes)
+			.field("flags", &self.flags)
+			.field("pixel_size", &self.pixel_size)
+			.field("version", &self.version)
+			.field("density", &self.density)
+			.field("compression_type", &self.compression_type)
+			.field("color_table_len", &self.color_table.len())
+			.field("data_len", &self.data.len())
+			.finish()
+	}
+}
+
+/// A bitmap family
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct BitmapFamily {
+	pub bitmaps: Vec<Bitmap>,
+}
+
+impl BitmapFamily {
+	/// Return the monochrome, standard-density member of the family as a [`MonoImage`], if there
+	/// is one
+	pub fn to_mono_image(&self) -> Option<MonoImage> {
+		self.bitmaps
+			.iter()
+			.filter(|x| x.density == DENSITY_STANDARD)
+			.find_map(|x| x.to_mono_image())
+	}
+
+	/// Return the deepest standard-density member of the family that can be converted as an
+	/// [`RgbaImage`], if there is one
+	pub fn to_image(&self) -> Option<RgbaImage> {
+		let mut bitmaps = self
+			.bitmaps
+			.iter()
+			.filter(|x| x.density == DENSITY_STANDARD)
+			.collect::<Vec<_>>();
+		bitmaps.sort_by_key(|x| core::cmp::Reverse(x.pixel_size));
+
+		bitmaps.iter().find_map(|x| x.to_image())
+	}
+}
+
+impl ResourceType for BitmapFamily {
+	const TYPE_CODES: &'static [&'static str] = &["Tbmp", "tAIB"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut bitmaps = Vec::new();
+		loop {
+			let start = rdr.position();
+			let width = rdr.read_u16::<BigEndian>()?;
+			let height = rdr.read_u16::<BigEndian>()?;
+			let row_bytes = rdr.read_u16::<BigEndian>()?;
+			let flags = rdr.read_u16::<BigEndian>()?;
+			let pixel_size = rdr.read_u8()?;
+			let version = rdr.read_u8()?;
+
+			// The remainder of the header differs between versions; work out where the next
+			// bitmap in the family starts, and skip to the end of the header
+			let mut density = DENSITY_STANDARD;
+			let mut compression_type = COMPRESSION_SCANLINE;
+			let mut pixel_format = 0;
+			let mut transparent_value = 0;
+			let (next_offset, header_len) = match version {
+				0 => (0, 16),
+				1 => {
+					let next_words = rdr.read_u16::<BigEndian>()? as u64;
+					(next_words * 4, 16)
+				}
+				2 => {
+					let next_words = rdr.read_u16::<BigEndian>()? as u64;
+					transparent_value = rdr.read_u8()? as u32;
+					compression_type = rdr.read_u8()?;
+					(next_words * 4, 16)
+				}
+				_ => {
+					let header_len = rdr.read_u8()? as u64;
+					pixel_format = rdr.read_u8()?;
+					let _unused = rdr.read_u8()?;
+					compression_type = rdr.read_u8()?;
+					density = rdr.read_u16::<BigEndian>()?;
+					transparent_value = rdr.read_u32::<BigEndian>()?;
+					let next_offset = rdr.read_u32::<BigEndian>()? as u64;
+					(next_offset, header_len)
+				}
+			};
+
+			rdr.seek(SeekFrom::Start(start + header_len))?;
+
+			if pixel_size != PIXEL_SIZE_PLACEHOLDER {
+				let mut color_table = Vec::new();
+				if flags & BITMAP_FLAG_HAS_COLOR_TABLE != 0 {
+					let entries = rdr.read_u16::<BigEndian>()?;
+					for _ in 0..entries {
+						let _index = rdr.read_u8()?;
+						let r = rdr.read_u8()?;
+						let g = rdr.read_u8()?;
+						let b = rdr.read_u8()?;
+						color_table.push([r, g, b]);
+					}
+				}
+
+				if version == 2 && flags & BITMAP_FLAG_DIRECT_COLOR != 0 {
+					// The transparent colour of a version 2 direct colour bitmap is given as RGB,
+					// after the bit counts of each component
+					rdr.seek(SeekFrom::Current(DIRECT_INFO_LENGTH - 3))?;
+					let r = rdr.read_u8()? as u32;
+					let g = rdr.read_u8()? as u32;
+					let b = rdr.read_u8()? as u32;
+					transparent_value = ((r >> 3) << 11) | ((g >> 2) << 5) | (b >> 3);
+				}
+
+				let length = if flags & BITMAP_FLAG_COMPRESSED != 0 {
+					// The compressed size includes the size field itself before version 3
+					match version {
+						0..=2 => (rdr.read_u16::<BigEndian>()? as usize).saturating_sub(2),
+						_ => rdr.read_u32::<BigEndian>()? as usize,
+					}
+				} else {
+					row_bytes as usize * height as usize
+				};
+
+				let mut data = vec![0_u8; length];
+				rdr.read_exact(&mut data)?;
+
+				bitmaps.push(Bitmap {
+					width,
+					height,
+					row_bytes,
+					flags,
+					pixel_size: if version == 0 { 1 } else { pixel_size },
+					version,
+					density,
+					compression_type,
+					pixel_format,
+					transparent_value,
+					color_table,
+					data,
+				});
+			}
+
+			if next_offset == 0 {
+				break;
+			}
+
+			rdr.seek(SeekFrom::Start(start + next_offset))?;
+		}
+
+		Ok(Self { bitmaps })
+	}
+}
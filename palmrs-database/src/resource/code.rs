@@ -0,0 +1,202 @@
+//! 68k code resources (`code`)
+//!
+//! A 68k Palm OS application is split into one or more code segments. Segment `0` is not code at
+//! all - it holds the sizes of the application's A5 world (its globals, and the area above A5
+//! containing the jump table), and the jump table itself. Segments `1` and above contain the
+//! actual 68k machine code, with execution starting at offset zero of segment `1`.
+//!
+//! The jump table is made up of 8-byte "unloaded" entries, each of the form:
+//!
+//! ```text
+//! dc.w  offset        ; offset of the routine within its segment
+//! move.w #segment,-(sp)
+//! _LoadSeg            ; A-line trap 0xA9F0
+//! ```
+
+use std::io::{self, Cursor, Read};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+/// The opcode of `move.w #imm,-(sp)`, found in unloaded jump table entries
+pub const JUMP_ENTRY_MOVE_OPCODE: u16 = 0x3F3C;
+
+/// The `_LoadSeg` trap word, found in unloaded jump table entries
+pub const JUMP_ENTRY_LOADSEG_TRAP: u16 = 0xA9F0;
+
+/// Length, in bytes, of a single jump table entry
+pub const JUMP_ENTRY_LENGTH: u32 = 8;
+
+/// Length, in bytes, of a near-model code segment header
+const SEGMENT_HEADER_LENGTH: usize = 4;
+
+/// A single jump table entry
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct JumpTableEntry {
+	/// Offset of the routine within its segment
+	pub offset: u16,
+
+	/// The code segment (`code` resource ID) the routine is in
+	pub segment: u16,
+
+	/// Whether the entry has the standard unloaded-entry `move.w` / `_LoadSeg` instruction pair
+	pub well_formed: bool,
+}
+
+/// The jump table and A5 world sizes of an application (`code` resource `0`)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct JumpTable {
+	/// Size of the area above A5 (application parameters plus the jump table)
+	pub above_a5: u32,
+
+	/// Size of the area below A5 (the application's global variables)
+	pub below_a5: u32,
+
+	/// Offset of the jump table from A5
+	pub offset: u32,
+
+	pub entries: Vec<JumpTableEntry>,
+}
+
+impl JumpTable {
+	/// Read the jump table from the given `code` resource `0` data
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let above_a5 = rdr.read_u32::<BigEndian>()?;
+		let below_a5 = rdr.read_u32::<BigEndian>()?;
+		let size = rdr.read_u32::<BigEndian>()?;
+		let offset = rdr.read_u32::<BigEndian>()?;
+
+		// The entry count comes from the resource, so don't reserve more than it can hold
+		let count = size / JUMP_ENTRY_LENGTH;
+		let remaining = rdr.get_ref().len() as u64 - rdr.position().min(rdr.get_ref().len() as u64);
+		let mut entries =
+			Vec::with_capacity((count as u64).min(remaining / JUMP_ENTRY_LENGTH as u64) as usize);
+		for _ in 0..count {
+			let entry_offset = rdr.read_u16::<BigEndian>()?;
+			let opcode = rdr.read_u16::<BigEndian>()?;
+			let segment = rdr.read_u16::<BigEndian>()?;
+			let trap = rdr.read_u16::<BigEndian>()?;
+
+			entries.push(JumpTableEntry {
+				offset: entry_offset,
+				segment,
+				well_formed: opcode == JUMP_ENTRY_MOVE_OPCODE && trap == JUMP_ENTRY_LOADSEG_TRAP,
+			});
+		}
+
+		Ok(Self {
+			above_a5,
+			below_a5,
+			offset,
+			entries,
+		})
+	}
+
+	/// Return the A5-relative offset of the jump table entry with the given index
+	pub fn entry_a5_offset(&self, index: usize) -> u32 {
+		self.offset + (index as u32 * JUMP_ENTRY_LENGTH)
+	}
+
+	/// Look up the jump table entry at the given A5-relative offset
+	///
+	/// This is how calls through the jump table (`jsr offset(a5)`) are resolved.
+	pub fn entry_at_a5_offset(&self, a5_offset: i32) -> Option<&JumpTableEntry> {
+		let relative = a5_offset.checked_sub(self.offset as i32)?;
+		if relative < 0 || !(relative as u32).is_multiple_of(JUMP_ENTRY_LENGTH) {
+			return None;
+		}
+
+		self.entries
+			.get((relative as u32 / JUMP_ENTRY_LENGTH) as usize)
+	}
+
+	/// Return the entries of the jump table that point into the given segment
+	pub fn entries_for_segment(
+		&self,
+		segment: u16,
+	) -> impl Iterator<Item = (usize, &JumpTableEntry)> {
+		self.entries
+			.iter()
+			.enumerate()
+			.filter(move |(_, entry)| entry.segment == segment)
+	}
+}
+
+/// Header of a near-model code segment
+///
+/// Segments built with the classic Mac OS near model start with this header, describing the range
+/// of jump table entries belonging to the segment. Most Palm OS toolchains don't emit it - in
+/// particular, segment `1` must start directly with code - so [`CodeSegment::header`] only
+/// returns it when it is consistent with the application's jump table.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SegmentHeader {
+	/// Offset (from the start of the jump table) of the first jump table entry for this segment
+	pub first_entry_offset: u16,
+
+	/// Number of jump table entries belonging to this segment
+	pub entry_count: u16,
+}
+
+/// A code segment (`code` resource `1` and above)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeSegment {
+	/// The segment number (the resource ID of the `code` resource)
+	pub segment: u16,
+
+	/// Raw segment data, including the header (if any)
+	pub data: Vec<u8>,
+}
+
+impl CodeSegment {
+	/// Read a code segment from the given `code` resource data
+	pub fn from_bytes(segment: u16, rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut data = Vec::new();
+		rdr.read_to_end(&mut data)?;
+
+		Ok(Self { segment, data })
+	}
+
+	/// Return the near-model segment header, if this segment has one
+	pub fn header(&self, jump_table: &JumpTable) -> Option<SegmentHeader> {
+		if self.segment < 2 || self.data.len() < SEGMENT_HEADER_LENGTH {
+			return None;
+		}
+
+		let header = SegmentHeader {
+			first_entry_offset: u16::from_be_bytes([self.data[0], self.data[1]]),
+			entry_count: u16::from_be_bytes([self.data[2], self.data[3]]),
+		};
+
+		if header.entry_count == 0
+			|| !(header.first_entry_offset as u32).is_multiple_of(JUMP_ENTRY_LENGTH)
+		{
+			return None;
+		}
+
+		let first = (header.first_entry_offset as u32 / JUMP_ENTRY_LENGTH) as usize;
+		let entries = jump_table
+			.entries
+			.get(first..(first + header.entry_count as usize))?;
+		if entries.iter().any(|x| x.segment != self.segment) {
+			return None;
+		}
+
+		Some(header)
+	}
+
+	/// Return the segment's machine code, without the segment header (if any)
+	pub fn code<'x>(&'x self, jump_table: &JumpTable) -> &'x [u8] {
+		match self.header(jump_table) {
+			Some(_) => &self.data[SEGMENT_HEADER_LENGTH..],
+			None => &self.data[..],
+		}
+	}
+
+	/// Return the offsets (within [`code`][CodeSegment::code]) of the routines in this segment
+	/// that are referenced by the jump table, along with their jump table entry index
+	pub fn entry_points(&self, jump_table: &JumpTable) -> Vec<(usize, u32)> {
+		jump_table
+			.entries_for_segment(self.segment)
+			.map(|(idx, entry)| (idx, entry.offset as u32))
+			.collect()
+	}
+}
@@ -0,0 +1,350 @@
+//! Initialised application data (`data` resource `0`)
+//!
+//! The `data 0` resource holds the initial values of an application's global variables, along with
+//! the relocations that need to be applied to any globals containing pointers. It is laid out as:
+//!
+//! - a 32-bit offset (from the start of the resource) of the relocation tables;
+//! - three compressed data blocks, each starting with a 32-bit A5-relative offset at which the
+//!   block is to be decompressed, followed by the compressed data stream, terminated by a zero
+//!   byte;
+//! - three relocation tables (A5-relative data pointers, pointers into code segment `1`, and
+//!   pointers into other code), each a 32-bit entry count followed by the compressed entries.
+//!
+//! See [`decompress_block`] for the compression scheme, and [`A5World`] for turning the resource
+//! into a memory image of the application's globals.
+
+use core::convert::TryFrom;
+use std::io::{self, Cursor, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	record::DatabaseRecord,
+	resource::code::{
+		JumpTable,
+		JUMP_ENTRY_LENGTH,
+		JUMP_ENTRY_LOADSEG_TRAP,
+		JUMP_ENTRY_MOVE_OPCODE,
+	},
+	PalmDatabase,
+	PrcDatabase,
+};
+
+/// Number of compressed data blocks in a `data 0` resource
+const DATA_BLOCK_COUNT: usize = 3;
+
+/// Number of relocation tables in a `data 0` resource
+const RELOCATION_TABLE_COUNT: usize = 3;
+
+/// Largest A5 world accepted, which is well beyond the globals and jump table of any real
+/// application
+pub const MAX_A5_WORLD_SIZE: u32 = 0x0040_0000;
+
+/// Decompress a single compressed data block
+///
+/// The compression scheme is a byte-oriented run-length encoding, where each control byte is one
+/// of:
+///
+/// - `0x80 | n`: `n + 1` literal bytes follow
+/// - `0x40 | n`: `n + 1` zero bytes
+/// - `0x20 | n`: `n + 2` repeats of the following byte
+/// - `0x10 | n`: `n + 1` `0xFF` bytes
+/// - `0x01`: `00 00 00 00 FF FF`, followed by 2 literal bytes
+/// - `0x02`: `00 00 00 00 FF`, followed by 3 literal bytes
+/// - `0x03`: `A9 F0 00 00`, followed by 2 literal bytes, `00`, and 1 literal byte
+/// - `0x04`: `A9 F0 00`, followed by 3 literal bytes, `00`, and 1 literal byte
+/// - `0x00`: end of block
+pub fn decompress_block(rdr: &mut Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+	let mut out = Vec::new();
+
+	fn literal(rdr: &mut Cursor<&[u8]>, out: &mut Vec<u8>, count: usize) -> Result<(), io::Error> {
+		for _ in 0..count {
+			out.push(rdr.read_u8()?);
+		}
+
+		Ok(())
+	}
+
+	loop {
+		let control = rdr.read_u8()?;
+		match control {
+			0x00 => break,
+			0x80..=0xFF => literal(rdr, &mut out, (control & 0x7F) as usize + 1)?,
+			0x40..=0x7F => out.resize(out.len() + (control & 0x3F) as usize + 1, 0x00),
+			0x20..=0x3F => {
+				let value = rdr.read_u8()?;
+				out.resize(out.len() + (control & 0x1F) as usize + 2, value);
+			}
+			0x10..=0x1F => out.resize(out.len() + (control & 0x0F) as usize + 1, 0xFF),
+			0x01 => {
+				out.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF]);
+				literal(rdr, &mut out, 2)?;
+			}
+			0x02 => {
+				out.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0xFF]);
+				literal(rdr, &mut out, 3)?;
+			}
+			0x03 => {
+				out.extend_from_slice(&[0xA9, 0xF0, 0x00, 0x00]);
+				literal(rdr, &mut out, 2)?;
+				out.push(0x00);
+				literal(rdr, &mut out, 1)?;
+			}
+			0x04 => {
+				out.extend_from_slice(&[0xA9, 0xF0, 0x00]);
+				literal(rdr, &mut out, 3)?;
+				out.push(0x00);
+				literal(rdr, &mut out, 1)?;
+			}
+
+			x => {
+				return Err(io::Error::other(format!(
+					"invalid data compression control byte {:#04X}",
+					x
+				)));
+			}
+		}
+	}
+
+	Ok(out)
+}
+
+/// Decode a compressed relocation table into a list of A5-relative offsets
+///
+/// Each entry is a signed delta from the previous offset (starting from zero), stored in one of
+/// three forms, distinguished by the top bits of the first byte:
+///
+/// - `1xxxxxxx`: 7-bit delta, in words
+/// - `01xxxxxx xxxxxxxx`: 14-bit delta, in words
+/// - `00xxxxxx xxxxxxxx xxxxxxxx xxxxxxxx`: 30-bit delta, in words
+fn decode_relocations(rdr: &mut Cursor<&[u8]>) -> Result<Vec<i32>, io::Error> {
+	let count = rdr.read_u32::<BigEndian>()?;
+
+	// Each entry takes at least a byte, so don't reserve more than the rest of the resource holds
+	let remaining = rdr.get_ref().len() as u64 - rdr.position().min(rdr.get_ref().len() as u64);
+	let mut offsets = Vec::with_capacity((count as u64).min(remaining) as usize);
+	let mut offset: i32 = 0;
+	for _ in 0..count {
+		let first = rdr.read_u8()?;
+		let delta = if first & 0x80 != 0 {
+			((first << 1) as i8) as i32
+		} else if first & 0x40 != 0 {
+			let value = ((first as u16) << 8) | rdr.read_u8()? as u16;
+			((value << 2) as i16 >> 1) as i32
+		} else {
+			let value = ((first as u32) << 24) | rdr.read_u24::<BigEndian>()?;
+			((value << 2) as i32) >> 1
+		};
+
+		offset = offset.wrapping_add(delta);
+		offsets.push(offset);
+	}
+
+	Ok(offsets)
+}
+
+/// A decoded `data 0` resource
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DataResource {
+	/// The decompressed data blocks, along with the A5-relative offset of each block
+	pub blocks: Vec<(i32, Vec<u8>)>,
+
+	/// A5-relative offsets of globals containing pointers to other globals
+	pub data_relocations: Vec<i32>,
+
+	/// A5-relative offsets of globals containing pointers into code segment `1`
+	pub code1_relocations: Vec<i32>,
+
+	/// A5-relative offsets of globals containing pointers into other code
+	pub code_relocations: Vec<i32>,
+}
+
+impl DataResource {
+	/// Read and decompress the given `data 0` resource data
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let relocations_offset = rdr.read_u32::<BigEndian>()? as u64;
+
+		let mut blocks = Vec::with_capacity(DATA_BLOCK_COUNT);
+		while blocks.len() < DATA_BLOCK_COUNT && rdr.position() < relocations_offset {
+			let a5_offset = rdr.read_i32::<BigEndian>()?;
+			let block = decompress_block(rdr)?;
+			blocks.push((a5_offset, block));
+		}
+
+		let mut tables = Vec::with_capacity(RELOCATION_TABLE_COUNT);
+		rdr.seek(SeekFrom::Start(relocations_offset))?;
+		for _ in 0..RELOCATION_TABLE_COUNT {
+			// Older toolchains omit the trailing tables entirely if they're empty
+			if rdr.position() >= rdr.get_ref().len() as u64 {
+				tables.push(Vec::new());
+				continue;
+			}
+
+			tables.push(decode_relocations(rdr)?);
+		}
+
+		let code_relocations = tables.pop().unwrap_or_default();
+		let code1_relocations = tables.pop().unwrap_or_default();
+		let data_relocations = tables.pop().unwrap_or_default();
+
+		Ok(Self {
+			blocks,
+			data_relocations,
+			code1_relocations,
+			code_relocations,
+		})
+	}
+}
+
+/// A memory image of an application's A5 world
+///
+/// The A5 world is the block of memory an application's A5 register points into: the global
+/// variables live below A5, and the application parameters and jump table live above it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct A5World {
+	/// Size of the area below A5
+	pub below_a5: u32,
+
+	/// Size of the area above A5
+	pub above_a5: u32,
+
+	/// The memory image, starting from the lowest address (`A5 - below_a5`)
+	pub memory: Vec<u8>,
+}
+
+impl A5World {
+	/// Create a zeroed A5 world with the unloaded jump table in place
+	///
+	/// Returns an error if the A5 world would be larger than [`MAX_A5_WORLD_SIZE`].
+	pub fn new(jump_table: &JumpTable) -> Result<Self, io::Error> {
+		let size = jump_table
+			.below_a5
+			.checked_add(jump_table.above_a5)
+			.filter(|x| *x <= MAX_A5_WORLD_SIZE)
+			.ok_or_else(|| {
+				io::Error::other(format!(
+					"A5 world of {} bytes below A5 and {} above is too large",
+					jump_table.below_a5, jump_table.above_a5
+				))
+			})?;
+
+		let mut this = Self {
+			below_a5: jump_table.below_a5,
+			above_a5: jump_table.above_a5,
+			memory: vec![0_u8; size as usize],
+		};
+
+		for (idx, entry) in jump_table.entries.iter().enumerate() {
+			let start = this.a5() + jump_table.entry_a5_offset(idx) as usize;
+			if let Some(dest) = this
+				.memory
+				.get_mut(start..(start + JUMP_ENTRY_LENGTH as usize))
+			{
+				dest[0..2].copy_from_slice(&entry.offset.to_be_bytes());
+				dest[2..4].copy_from_slice(&JUMP_ENTRY_MOVE_OPCODE.to_be_bytes());
+				dest[4..6].copy_from_slice(&entry.segment.to_be_bytes());
+				dest[6..8].copy_from_slice(&JUMP_ENTRY_LOADSEG_TRAP.to_be_bytes());
+			}
+		}
+
+		Ok(this)
+	}
+
+	/// Build the initial A5 world of the application in the given PRC database
+	///
+	/// This uses the `code 0` resource for the A5 world layout, and the `data 0` resource (if
+	/// present) for the initial values of the globals. Relocations are not applied.
+	pub fn from_database(database: &PalmDatabase<PrcDatabase>) -> Result<Self, io::Error> {
+		let find = |name: &str| {
+			database
+				.list_records_resources()
+				.iter()
+				.find(|(hdr, _)| hdr.name_str() == Some(name) && hdr.resource_id() == Some(0))
+				.map(|(_, data)| data.as_slice())
+		};
+
+		let jump_table = match find("code") {
+			Some(data) => JumpTable::from_bytes(&mut Cursor::new(data))?,
+			None => {
+				return Err(io::Error::other("database has no code 0 resource"));
+			}
+		};
+
+		let mut this = Self::new(&jump_table)?;
+		if let Some(data) = find("data") {
+			this.load_data(&DataResource::from_bytes(&mut Cursor::new(data))?)?;
+		}
+
+		Ok(this)
+	}
+
+	/// Offset of A5 within [`memory`][A5World::memory]
+	pub fn a5(&self) -> usize {
+		self.below_a5 as usize
+	}
+
+	/// The global variables area of the memory image (everything below A5)
+	pub fn globals(&self) -> &[u8] {
+		&self.memory[..self.a5()]
+	}
+
+	/// Return the memory image slice starting at the given A5-relative offset, if it's all within
+	/// the image
+	pub fn at_a5_offset(&self, a5_offset: i32, len: usize) -> Option<&[u8]> {
+		let start = usize::try_from(self.a5() as i64 + a5_offset as i64).ok()?;
+		self.memory.get(start..start.checked_add(len)?)
+	}
+
+	/// Copy the decompressed data blocks into the memory image
+	pub fn load_data(&mut self, data: &DataResource) -> Result<(), io::Error> {
+		for (a5_offset, block) in data.blocks.iter() {
+			let start = self.a5() as i64 + *a5_offset as i64;
+			let end = start + block.len() as i64;
+			if start < 0 || end > self.memory.len() as i64 {
+				return Err(io::Error::other(format!(
+					"data block at A5{:+} is outside the A5 world",
+					a5_offset
+				)));
+			}
+
+			self.memory[(start as usize)..(end as usize)].copy_from_slice(block);
+		}
+
+		Ok(())
+	}
+
+	/// Apply the relocations from the given data resource, as if the A5 world and code segment `1`
+	/// were loaded at the given addresses
+	///
+	/// The code relocations table is not applied, as the load addresses of other code segments
+	/// are not known until they are loaded.
+	pub fn relocate(
+		&mut self,
+		data: &DataResource,
+		a5_address: u32,
+		code1_address: u32,
+	) -> Result<(), io::Error> {
+		let tables = [
+			(&data.data_relocations, a5_address),
+			(&data.code1_relocations, code1_address),
+		];
+
+		for (offsets, base) in tables.iter() {
+			for a5_offset in offsets.iter() {
+				let start = self.a5() as i64 + *a5_offset as i64;
+				if start < 0 || start + 4 > self.memory.len() as i64 {
+					return Err(io::Error::other(format!(
+						"relocation at A5{:+} is outside the A5 world",
+						a5_offset
+					)));
+				}
+
+				let dest = &mut self.memory[(start as usize)..(start as usize + 4)];
+				let value = u32::from_be_bytes([dest[0], dest[1], dest[2], dest[3]]);
+				dest.copy_from_slice(&value.wrapping_add(*base).to_be_bytes());
+			}
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,333 @@
+//! Font resources (`NFNT`, `nfnt`)
+//!
+//! Palm OS fonts use the classic Mac OS `NFNT` layout: a `FontType` header containing the font
+//! metrics, followed by the bit-image strike (every glyph side by side in one wide 1-bit-per-pixel
+//! image), the location table (the horizontal position of each glyph within the strike), and the
+//! offset/width table (the kerning offset and advance width of each glyph).
+//!
+//! Palm OS 5 added the extended (`nfnt`) format, which holds a font family: the header is
+//! followed by a table of densities, each with its own strike, scaled up from the standard
+//! 72 dpi density. The location and offset/width tables are shared, in standard-density
+//! coordinates.
+
+use core::fmt;
+use std::io::{self, Cursor, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{image::MonoImage, resource::ResourceType, text::string_to_palm};
+
+/// Length, in bytes, of the `FontType` header
+const FONT_HEADER_LENGTH: usize = 26;
+
+/// Offset of the `owTLoc` field, which the offset/width table location is relative to
+const FONT_OWTLOC_OFFSET: u64 = 16;
+
+/// The `fontType` flag set on extended (multi-density) fonts
+pub const FONT_EXTENDED_FLAG: u16 = 0x0200;
+
+/// The standard screen density, which all font metrics are given in
+pub const DENSITY_STANDARD: u16 = 72;
+
+/// Offset/width table entry value marking a character as missing from the font
+const MISSING_GLYPH: u16 = 0xFFFF;
+
+/// The position and metrics of a single glyph
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glyph {
+	/// Horizontal position of the glyph image within the standard-density strike
+	pub location: u16,
+
+	/// Width of the glyph image within the strike
+	pub image_width: u16,
+
+	/// Offset from the pen position (plus the font's `kern_max`) at which the image is drawn
+	pub offset: i8,
+
+	/// Advance width of the glyph
+	pub width: u8,
+}
+
+/// The bit-image strike of a font, at a single density
+#[derive(Clone, PartialEq)]
+pub struct FontStrike {
+	/// Screen density of the strike, in dots per inch
+	pub density: u16,
+
+	/// Length, in bytes, of each row of the strike
+	pub row_bytes: usize,
+
+	/// Number of rows in the strike
+	pub height: usize,
+
+	pub data: Vec<u8>,
+}
+
+impl FontStrike {
+	/// Scale a standard-density coordinate to this strike's density
+	pub fn scale(&self, value: usize) -> usize {
+		value * self.density as usize / DENSITY_STANDARD as usize
+	}
+
+	/// Return the pixel at the given position within the strike
+	pub fn get(&self, x: usize, y: usize) -> bool {
+		if y >= self.height || x >= self.row_bytes * 8 {
+			return false;
+		}
+
+		self.data
+			.get((y * self.row_bytes) + (x / 8))
+			.map(|b| b & (0x80 >> (x % 8)) != 0)
+			.unwrap_or(false)
+	}
+}
+
+/// Debug output omits the strike data, which would otherwise swamp everything else
+impl fmt::Debug for FontStrike {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("FontStrike")
+			.field("density", &self.density)
+			.field("row_bytes", &self.row_bytes)
+			.field("height", &self.height)
+			.field("data_len", &self.data.len())
+			.finish()
+	}
+}
+
+/// A font resource (`NFNT` or `nfnt`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Font {
+	pub font_type: u16,
+	pub first_char: u16,
+	pub last_char: u16,
+	pub max_width: i16,
+	pub kern_max: i16,
+	pub n_descent: i16,
+	pub rect_width: i16,
+	pub rect_height: i16,
+	pub ascent: i16,
+	pub descent: i16,
+	pub leading: i16,
+
+	/// Glyphs for each character from `first_char` to `last_char`, followed by the glyph drawn
+	/// for missing characters; `None` for characters that are not in the font
+	pub glyphs: Vec<Option<Glyph>>,
+
+	/// The strikes of the font, one per density
+	pub strikes: Vec<FontStrike>,
+}
+
+impl Font {
+	/// Whether this is an extended (multi-density) font
+	pub fn is_extended(&self) -> bool {
+		self.font_type & FONT_EXTENDED_FLAG != 0
+	}
+
+	/// Return the densities this font has strikes for
+	pub fn densities(&self) -> Vec<u16> {
+		self.strikes.iter().map(|x| x.density).collect()
+	}
+
+	/// Return the strike for the given density, if the font has one
+	pub fn strike(&self, density: u16) -> Option<&FontStrike> {
+		self.strikes.iter().find(|x| x.density == density)
+	}
+
+	/// Return the glyph for the given character, falling back to the missing-character glyph
+	pub fn glyph(&self, ch: u8) -> Option<&Glyph> {
+		let ch = ch as u16;
+		let found = if ch >= self.first_char && ch <= self.last_char {
+			self.glyphs
+				.get((ch - self.first_char) as usize)
+				.and_then(|x| x.as_ref())
+		} else {
+			None
+		};
+
+		found.or_else(|| self.glyphs.last().and_then(|x| x.as_ref()))
+	}
+
+	/// Line height of the font (the font rectangle height plus the leading)
+	pub fn line_height(&self) -> i16 {
+		self.rect_height + self.leading
+	}
+
+	/// Advance width of the given character
+	pub fn char_width(&self, ch: u8) -> u16 {
+		self.glyph(ch).map(|x| x.width as u16).unwrap_or(0)
+	}
+
+	/// Width of the given string, after conversion to the Palm OS character set
+	pub fn string_width(&self, text: &str) -> u16 {
+		string_to_palm(text)
+			.iter()
+			.map(|ch| self.char_width(*ch))
+			.sum()
+	}
+
+	/// Draw a single glyph into `image`, with the pen at `pen_x` (in strike pixels)
+	fn draw_glyph(&self, strike: &FontStrike, glyph: &Glyph, image: &mut MonoImage, pen_x: usize) {
+		let kern = (self.kern_max as isize + glyph.offset as isize) * strike.density as isize
+			/ DENSITY_STANDARD as isize;
+		let origin = pen_x as isize + kern;
+		let start = strike.scale(glyph.location as usize);
+		let width = strike.scale(glyph.image_width as usize);
+
+		for y in 0..strike.height {
+			for x in 0..width {
+				let image_x = origin + x as isize;
+				if image_x >= 0 && strike.get(start + x, y) {
+					image.set(image_x as usize, y, true);
+				}
+			}
+		}
+	}
+
+	/// Render a single character at the given density
+	///
+	/// The image is the advance width of the character wide, and the font rectangle height tall.
+	/// Returns `None` if the font has no strike for the density, or no glyph for the character.
+	pub fn render_glyph(&self, ch: u8, density: u16) -> Option<MonoImage> {
+		let strike = self.strike(density)?;
+		let glyph = self.glyph(ch)?;
+
+		let mut image = MonoImage::new(strike.scale(glyph.width as usize), strike.height);
+		self.draw_glyph(strike, glyph, &mut image, 0);
+
+		Some(image)
+	}
+
+	/// Render a string on a single line at the given density
+	///
+	/// The string is converted to the Palm OS character set first. Returns `None` if the font has
+	/// no strike for the density.
+	pub fn render_string(&self, text: &str, density: u16) -> Option<MonoImage> {
+		let strike = self.strike(density)?;
+
+		let mut image = MonoImage::new(
+			strike.scale(self.string_width(text) as usize),
+			strike.height,
+		);
+
+		let mut pen_x = 0;
+		for ch in string_to_palm(text) {
+			if let Some(glyph) = self.glyph(ch) {
+				self.draw_glyph(strike, glyph, &mut image, pen_x);
+				pen_x += strike.scale(glyph.width as usize);
+			}
+		}
+
+		Some(image)
+	}
+}
+
+impl ResourceType for Font {
+	const TYPE_CODES: &'static [&'static str] = &["NFNT", "nfnt"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let font_type = rdr.read_u16::<BigEndian>()?;
+		let first_char = rdr.read_u16::<BigEndian>()?;
+		let last_char = rdr.read_u16::<BigEndian>()?;
+		let max_width = rdr.read_i16::<BigEndian>()?;
+		let kern_max = rdr.read_i16::<BigEndian>()?;
+		let n_descent = rdr.read_i16::<BigEndian>()?;
+		let rect_width = rdr.read_i16::<BigEndian>()?;
+		let rect_height = rdr.read_i16::<BigEndian>()?;
+		let ow_t_loc = rdr.read_u16::<BigEndian>()?;
+		let ascent = rdr.read_i16::<BigEndian>()?;
+		let descent = rdr.read_i16::<BigEndian>()?;
+		let leading = rdr.read_i16::<BigEndian>()?;
+		let row_words = rdr.read_u16::<BigEndian>()?;
+
+		if last_char < first_char || rect_height < 0 {
+			return Err(io::Error::other(format!(
+				"invalid font character range or height ({}..={}, {})",
+				first_char, last_char, rect_height
+			)));
+		}
+
+		// Strike locations: a single standard-density strike directly after the header, or the
+		// density table of an extended font
+		let mut strike_offsets = Vec::new();
+		if font_type & FONT_EXTENDED_FLAG != 0 {
+			let _flags = rdr.read_u16::<BigEndian>()?;
+			let _version = rdr.read_u16::<BigEndian>()?;
+			let density_count = rdr.read_u16::<BigEndian>()?;
+			for _ in 0..density_count {
+				let density = rdr.read_u16::<BigEndian>()?;
+				let offset = rdr.read_u32::<BigEndian>()? as usize;
+				strike_offsets.push((density, offset));
+			}
+		} else {
+			strike_offsets.push((DENSITY_STANDARD, FONT_HEADER_LENGTH));
+		}
+
+		// The location table directly precedes the offset/width table; both have an entry for
+		// each character, one for the missing-character glyph, and a terminating entry
+		let entry_count = (last_char - first_char) as u64 + 3;
+		let ow_offset = FONT_OWTLOC_OFFSET + (ow_t_loc as u64 * 2);
+		let location_offset = ow_offset
+			.checked_sub(entry_count * 2)
+			.ok_or_else(|| io::Error::other("invalid font offset/width table location"))?;
+
+		rdr.seek(SeekFrom::Start(location_offset))?;
+		let mut locations = Vec::with_capacity(entry_count as usize);
+		for _ in 0..entry_count {
+			locations.push(rdr.read_u16::<BigEndian>()?);
+		}
+
+		let mut glyphs = Vec::with_capacity(entry_count as usize - 1);
+		for idx in 0..(entry_count as usize - 1) {
+			let entry = rdr.read_u16::<BigEndian>()?;
+			if entry == MISSING_GLYPH {
+				glyphs.push(None);
+				continue;
+			}
+
+			glyphs.push(Some(Glyph {
+				location: locations[idx],
+				image_width: locations[idx + 1].saturating_sub(locations[idx]),
+				offset: (entry >> 8) as u8 as i8,
+				width: entry as u8,
+			}));
+		}
+
+		// Read the strikes, scaling the standard-density row width and height to each density
+		let data = *rdr.get_ref();
+		let mut strikes = Vec::with_capacity(strike_offsets.len());
+		for (density, offset) in strike_offsets {
+			let scale = |x: usize| x * density as usize / DENSITY_STANDARD as usize;
+			let row_bytes = scale(row_words as usize * 16).div_ceil(16) * 2;
+			let height = scale(rect_height as usize);
+
+			let strike = data
+				.get(offset..(offset + (row_bytes * height)))
+				.ok_or_else(|| {
+					io::Error::other(format!("font strike for density {} is truncated", density))
+				})?;
+
+			strikes.push(FontStrike {
+				density,
+				row_bytes,
+				height,
+				data: strike.to_vec(),
+			});
+		}
+
+		Ok(Self {
+			font_type,
+			first_char,
+			last_char,
+			max_width,
+			kern_max,
+			n_descent,
+			rect_width,
+			rect_height,
+			ascent,
+			descent,
+			leading,
+			glyphs,
+			strikes,
+		})
+	}
+}
@@ -0,0 +1,553 @@
+//! Form resources (`tFRM`)
+//!
+//! A form resource contains the 68k `FormType` structure, followed by the form's object list (an
+//! array of `FormObjListType` entries, each giving an object kind and the offset of the object
+//! within the resource), followed by the objects themselves. Any strings belonging to an object
+//! (control text, label text, list items, the form title) directly follow that object's structure
+//! unless the object has an explicit text offset.
+
+use std::io::{self, Cursor, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	resource::{Point, Rectangle, ResourceType},
+	text::{palm_to_string, read_cstr},
+};
+
+/// Length, in bytes, of the 68k `FormType` structure (including the embedded `WindowType`)
+const FORM_LENGTH: u64 = 68;
+
+/// Length, in bytes, of a 68k `FormObjListType` entry
+const FORM_OBJ_LIST_ENTRY_LENGTH: u64 = 6;
+
+/// Length, in bytes, of the 68k `FieldType` structure
+const FIELD_LENGTH: u64 = 40;
+
+/// Length, in bytes, of the 68k `ControlType` structure
+const CONTROL_LENGTH: u64 = 20;
+
+/// Length, in bytes, of the 68k `ListType` structure
+const LIST_LENGTH: u64 = 32;
+
+/// Length, in bytes, of the 68k `TableType` structure
+const TABLE_LENGTH: u64 = 74;
+
+/// Length, in bytes, of the 68k `FormBitmapType` structure
+const BITMAP_LENGTH: u64 = 8;
+
+/// Length, in bytes, of the 68k `FormLabelType` structure
+const LABEL_LENGTH: u64 = 14;
+
+/// Length, in bytes, of the 68k `FormTitleType` structure
+const TITLE_LENGTH: u64 = 12;
+
+/// Length, in bytes, of the 68k `FormPopupType` structure
+const POPUP_LENGTH: u64 = 4;
+
+/// Length, in bytes, of the 68k `FormGraffitiStateType` structure
+const GRAFFITI_STATE_LENGTH: u64 = 4;
+
+/// Length, in bytes, of the 68k `FormGadgetType` structure
+const GADGET_LENGTH: u64 = 20;
+
+/// Length, in bytes, of the 68k `ScrollBarType` structure
+const SCROLL_BAR_LENGTH: u64 = 24;
+
+/// The visual style of a control
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ControlStyle {
+	Button,
+	PushButton,
+	Checkbox,
+	PopupTrigger,
+	SelectorTrigger,
+	RepeatingButton,
+	Slider,
+	FeedbackSlider,
+	Unknown(u8),
+}
+
+impl From<u8> for ControlStyle {
+	fn from(value: u8) -> Self {
+		match value {
+			0 => Self::Button,
+			1 => Self::PushButton,
+			2 => Self::Checkbox,
+			3 => Self::PopupTrigger,
+			4 => Self::SelectorTrigger,
+			5 => Self::RepeatingButton,
+			6 => Self::Slider,
+			7 => Self::FeedbackSlider,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+/// A control object (button, push button, checkbox, trigger)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormControl {
+	pub id: u16,
+	pub bounds: Rectangle,
+	pub attributes: u16,
+	pub style: ControlStyle,
+	pub font_id: u8,
+	pub group: u8,
+	pub text: String,
+}
+
+/// A text field object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormField {
+	pub id: u16,
+	pub bounds: Rectangle,
+	pub attributes: u16,
+	pub max_chars: u16,
+	pub font_id: u8,
+	pub max_visible_lines: u8,
+}
+
+/// A list object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormList {
+	pub id: u16,
+	pub bounds: Rectangle,
+	pub attributes: u16,
+	pub font_id: u8,
+	pub items: Vec<String>,
+}
+
+/// A table object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormTable {
+	pub id: u16,
+	pub bounds: Rectangle,
+	pub attributes: u16,
+	pub column_count: u16,
+	pub row_count: u16,
+}
+
+/// A bitmap object, referencing a bitmap resource
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormBitmap {
+	pub attributes: u16,
+	pub position: Point,
+
+	/// Resource ID of the `Tbmp` (or `tbmf` family) resource to draw
+	pub rsc_id: u16,
+}
+
+/// A static text label object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormLabel {
+	pub id: u16,
+	pub position: Point,
+	pub attributes: u16,
+	pub font_id: u8,
+	pub text: String,
+}
+
+/// A form title object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormTitle {
+	pub bounds: Rectangle,
+	pub text: String,
+}
+
+/// A popup object, linking a popup trigger control to a list
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormPopup {
+	pub control_id: u16,
+	pub list_id: u16,
+}
+
+/// A gadget object (an application-drawn area of the form)
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormGadget {
+	pub id: u16,
+	pub attributes: u16,
+	pub bounds: Rectangle,
+}
+
+/// A scroll bar object
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormScrollBar {
+	pub id: u16,
+	pub bounds: Rectangle,
+	pub attributes: u16,
+	pub value: i16,
+	pub min_value: i16,
+	pub max_value: i16,
+	pub page_size: i16,
+}
+
+/// An object within a form
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormObject {
+	Field(FormField),
+	Control(FormControl),
+	List(FormList),
+	Table(FormTable),
+	Bitmap(FormBitmap),
+	Label(FormLabel),
+	Title(FormTitle),
+	Popup(FormPopup),
+	GraffitiState(Point),
+	Gadget(FormGadget),
+	ScrollBar(FormScrollBar),
+
+	/// An object kind that isn't decoded (lines, frames, rectangles, or unknown kinds)
+	Unknown {
+		kind: u8,
+		offset: u32,
+	},
+}
+
+impl FormObject {
+	/// Return the user-visible text contained within this object
+	pub fn strings(&self) -> Vec<String> {
+		match self {
+			Self::Control(x) if !x.text.is_empty() => vec![x.text.clone()],
+			Self::List(x) => x.items.clone(),
+			Self::Label(x) => vec![x.text.clone()],
+			Self::Title(x) => vec![x.text.clone()],
+			_ => Vec::new(),
+		}
+	}
+
+	/// Read an object of the given kind from the cursor
+	///
+	/// The cursor should be positioned at the start of the object structure. Returns the object,
+	/// and the offset of the end of the object (including any trailing strings).
+	fn from_bytes(kind: u8, rdr: &mut Cursor<&[u8]>) -> Result<(Self, u64), io::Error> {
+		let start = rdr.position();
+
+		// Read a string either from the explicit offset (if set), or directly after the object
+		// structure, returning the string and the new end-of-object offset
+		let read_text = |rdr: &mut Cursor<&[u8]>,
+		                 ptr: u32,
+		                 struct_len: u64|
+		 -> Result<(String, u64), io::Error> {
+			if ptr != 0 {
+				rdr.seek(SeekFrom::Start(ptr as u64))?;
+				let text = palm_to_string(&read_cstr(rdr)?);
+				Ok((text, start + struct_len))
+			} else {
+				rdr.seek(SeekFrom::Start(start + struct_len))?;
+				let text = palm_to_string(&read_cstr(rdr)?);
+				Ok((text, rdr.position()))
+			}
+		};
+
+		let object = match kind {
+			0 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				rdr.seek(SeekFrom::Current(12 + 4))?;
+				let max_chars = rdr.read_u16::<BigEndian>()?;
+				rdr.seek(SeekFrom::Current(8))?;
+				let font_id = rdr.read_u8()?;
+				let max_visible_lines = rdr.read_u8()?;
+
+				let field = FormField {
+					id,
+					bounds,
+					attributes,
+					max_chars,
+					font_id,
+					max_visible_lines,
+				};
+
+				(Self::Field(field), start + FIELD_LENGTH)
+			}
+
+			1 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let text_ptr = rdr.read_u32::<BigEndian>()?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let style = ControlStyle::from(rdr.read_u8()?);
+				let font_id = rdr.read_u8()?;
+				let group = rdr.read_u8()?;
+
+				// Sliders and graphical controls have no text
+				let (text, end) = match style {
+					ControlStyle::Slider | ControlStyle::FeedbackSlider => {
+						(String::new(), start + CONTROL_LENGTH)
+					}
+					_ => read_text(rdr, text_ptr, CONTROL_LENGTH)?,
+				};
+
+				let control = FormControl {
+					id,
+					bounds,
+					attributes,
+					style,
+					font_id,
+					group,
+					text,
+				};
+
+				(Self::Control(control), end)
+			}
+
+			2 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				rdr.seek(SeekFrom::Current(4))?;
+				let item_count = rdr.read_i16::<BigEndian>()?.max(0);
+				rdr.seek(SeekFrom::Current(4))?;
+				let font_id = rdr.read_u8()?;
+
+				rdr.seek(SeekFrom::Start(start + LIST_LENGTH))?;
+				let mut items = Vec::with_capacity(item_count as usize);
+				for _ in 0..item_count {
+					items.push(palm_to_string(&read_cstr(rdr)?));
+				}
+
+				let list = FormList {
+					id,
+					bounds,
+					attributes,
+					font_id,
+					items,
+				};
+
+				(Self::List(list), rdr.position())
+			}
+
+			3 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let column_count = rdr.read_u16::<BigEndian>()?;
+				let row_count = rdr.read_u16::<BigEndian>()?;
+
+				let table = FormTable {
+					id,
+					bounds,
+					attributes,
+					column_count,
+					row_count,
+				};
+
+				(Self::Table(table), start + TABLE_LENGTH)
+			}
+
+			4 => {
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let position = Point::from_bytes(rdr)?;
+				let rsc_id = rdr.read_u16::<BigEndian>()?;
+
+				let bitmap = FormBitmap {
+					attributes,
+					position,
+					rsc_id,
+				};
+
+				(Self::Bitmap(bitmap), start + BITMAP_LENGTH)
+			}
+
+			8 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let position = Point::from_bytes(rdr)?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let font_id = rdr.read_u8()?;
+				rdr.read_u8()?;
+				let text_ptr = rdr.read_u32::<BigEndian>()?;
+				let (text, end) = read_text(rdr, text_ptr, LABEL_LENGTH)?;
+
+				let label = FormLabel {
+					id,
+					position,
+					attributes,
+					font_id,
+					text,
+				};
+
+				(Self::Label(label), end)
+			}
+
+			9 => {
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let text_ptr = rdr.read_u32::<BigEndian>()?;
+				let (text, end) = read_text(rdr, text_ptr, TITLE_LENGTH)?;
+
+				(Self::Title(FormTitle { bounds, text }), end)
+			}
+
+			10 => {
+				let control_id = rdr.read_u16::<BigEndian>()?;
+				let list_id = rdr.read_u16::<BigEndian>()?;
+
+				let popup = FormPopup {
+					control_id,
+					list_id,
+				};
+
+				(Self::Popup(popup), start + POPUP_LENGTH)
+			}
+
+			11 => {
+				let position = Point::from_bytes(rdr)?;
+
+				(Self::GraffitiState(position), start + GRAFFITI_STATE_LENGTH)
+			}
+
+			12 => {
+				let id = rdr.read_u16::<BigEndian>()?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let bounds = Rectangle::from_bytes(rdr)?;
+
+				let gadget = FormGadget {
+					id,
+					attributes,
+					bounds,
+				};
+
+				(Self::Gadget(gadget), start + GADGET_LENGTH)
+			}
+
+			13 => {
+				let bounds = Rectangle::from_bytes(rdr)?;
+				let id = rdr.read_u16::<BigEndian>()?;
+				let attributes = rdr.read_u16::<BigEndian>()?;
+				let value = rdr.read_i16::<BigEndian>()?;
+				let min_value = rdr.read_i16::<BigEndian>()?;
+				let max_value = rdr.read_i16::<BigEndian>()?;
+				let page_size = rdr.read_i16::<BigEndian>()?;
+
+				let scroll_bar = FormScrollBar {
+					id,
+					bounds,
+					attributes,
+					value,
+					min_value,
+					max_value,
+					page_size,
+				};
+
+				(Self::ScrollBar(scroll_bar), start + SCROLL_BAR_LENGTH)
+			}
+
+			kind => {
+				let unknown = Self::Unknown {
+					kind,
+					offset: start as u32,
+				};
+
+				(unknown, start)
+			}
+		};
+
+		Ok(object)
+	}
+}
+
+/// A form resource (`tFRM`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Form {
+	pub form_id: u16,
+	pub bounds: Rectangle,
+	pub window_flags: u16,
+	pub frame_type: u16,
+	pub attributes: u16,
+
+	/// ID of the object that initially has the focus
+	pub focus: u16,
+
+	/// ID of the button to use if the form is dismissed as a dialog without a button being tapped
+	pub default_button: u16,
+
+	/// Resource ID of the `tSTR` help text for this form, or zero if there is none
+	pub help_rsc_id: u16,
+
+	/// Resource ID of the menu bar for this form, or zero if there is none
+	pub menu_rsc_id: u16,
+
+	pub objects: Vec<FormObject>,
+}
+
+impl Form {
+	/// Return the title of the form, if it has one
+	pub fn title(&self) -> Option<&str> {
+		self.objects.iter().find_map(|x| match x {
+			FormObject::Title(title) => Some(title.text.as_str()),
+			_ => None,
+		})
+	}
+
+	/// Return all of the user-visible text contained within the form's objects
+	pub fn strings(&self) -> Vec<String> {
+		self.objects.iter().flat_map(FormObject::strings).collect()
+	}
+}
+
+impl ResourceType for Form {
+	const TYPE_CODES: &'static [&'static str] = &["tFRM"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		// WindowType
+		rdr.seek(SeekFrom::Current(8))?;
+		let window_flags = rdr.read_u16::<BigEndian>()?;
+		let bounds = Rectangle::from_bytes(rdr)?;
+		rdr.seek(SeekFrom::Current(8 + 4))?;
+		let frame_type = rdr.read_u16::<BigEndian>()?;
+		rdr.seek(SeekFrom::Current(4 + 4))?;
+
+		// FormType
+		let form_id = rdr.read_u16::<BigEndian>()?;
+		let attributes = rdr.read_u16::<BigEndian>()?;
+		rdr.seek(SeekFrom::Current(2 + 4 + 4))?;
+		let focus = rdr.read_u16::<BigEndian>()?;
+		let default_button = rdr.read_u16::<BigEndian>()?;
+		let help_rsc_id = rdr.read_u16::<BigEndian>()?;
+		let menu_rsc_id = rdr.read_u16::<BigEndian>()?;
+		let object_count = rdr.read_u16::<BigEndian>()?;
+		let objects_ptr = rdr.read_u32::<BigEndian>()?;
+
+		// Object list
+		let list_start = if objects_ptr != 0 {
+			objects_ptr as u64
+		} else {
+			FORM_LENGTH
+		};
+		rdr.seek(SeekFrom::Start(list_start))?;
+
+		let mut object_list = Vec::with_capacity(object_count as usize);
+		for _ in 0..object_count {
+			let kind = rdr.read_u8()?;
+			rdr.read_u8()?;
+			let ptr = rdr.read_u32::<BigEndian>()?;
+
+			object_list.push((kind, ptr));
+		}
+
+		// Objects - if an object doesn't have an offset set, it directly follows the previous one
+		let mut next_object = list_start + (object_count as u64 * FORM_OBJ_LIST_ENTRY_LENGTH);
+		let mut objects = Vec::with_capacity(object_count as usize);
+		for (kind, ptr) in object_list {
+			let start = if ptr != 0 { ptr as u64 } else { next_object };
+			rdr.seek(SeekFrom::Start(start))?;
+
+			let (object, end) = FormObject::from_bytes(kind, rdr)?;
+			objects.push(object);
+			next_object = end;
+		}
+
+		Ok(Self {
+			form_id,
+			bounds,
+			window_flags,
+			frame_type,
+			attributes,
+			focus,
+			default_button,
+			help_rsc_id,
+			menu_rsc_id,
+			objects,
+		})
+	}
+}
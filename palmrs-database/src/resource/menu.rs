@@ -0,0 +1,175 @@
+//! Menu bar resources (`MBAR`, `MENU`)
+//!
+//! A menu bar resource contains the entire menu bar: the `MenuBarType` header, followed by an
+//! array of `MenuPullDownType` structures (one per menu), followed by the `MenuItemType` arrays for
+//! each menu, and finally the menu title and item strings.
+
+use std::io::{self, Cursor, Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	resource::{Rectangle, ResourceType},
+	text::{palm_to_string, read_cstr},
+};
+
+/// Length, in bytes, of the 68k `MenuBarType` structure
+const MENU_BAR_LENGTH: u64 = 32;
+
+/// Length, in bytes, of the 68k `MenuPullDownType` structure
+const MENU_PULLDOWN_LENGTH: u64 = 34;
+
+/// Length, in bytes, of the 68k `MenuItemType` structure
+const MENU_ITEM_LENGTH: u64 = 8;
+
+/// The item text used for separator items
+const MENU_SEPARATOR: &str = "-";
+
+/// A single item within a menu
+#[derive(Debug, Clone, PartialEq)]
+pub struct MenuItem {
+	pub item_id: u16,
+
+	/// The Graffiti command-stroke shortcut character for this item, if it has one
+	pub shortcut: Option<char>,
+
+	pub hidden: bool,
+	pub text: String,
+}
+
+impl MenuItem {
+	/// Whether this item is a separator line rather than a selectable item
+	pub fn is_separator(&self) -> bool {
+		self.text == MENU_SEPARATOR
+	}
+}
+
+/// A single pull-down menu within a menu bar
+#[derive(Debug, Clone, PartialEq)]
+pub struct Menu {
+	pub bounds: Rectangle,
+	pub title_bounds: Rectangle,
+	pub hidden: bool,
+	pub title: String,
+	pub items: Vec<MenuItem>,
+}
+
+/// A menu bar resource (`MBAR` or `MENU`)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MenuBar {
+	pub menus: Vec<Menu>,
+}
+
+impl MenuBar {
+	/// Return the menu titles and item text (excluding separators) of this menu bar
+	pub fn strings(&self) -> Vec<String> {
+		let mut strings = Vec::new();
+		for menu in self.menus.iter() {
+			strings.push(menu.title.clone());
+			for item in menu.items.iter().filter(|x| !x.is_separator()) {
+				strings.push(item.text.clone());
+			}
+		}
+
+		strings
+	}
+}
+
+/// Seek to the given structure offset if it is set, otherwise to `fallback`
+///
+/// Returns the offset that was seeked to.
+fn seek_ptr(rdr: &mut Cursor<&[u8]>, ptr: u32, fallback: u64) -> Result<u64, io::Error> {
+	let offset = if ptr != 0 { ptr as u64 } else { fallback };
+	rdr.seek(SeekFrom::Start(offset))
+}
+
+impl ResourceType for MenuBar {
+	const TYPE_CODES: &'static [&'static str] = &["MBAR", "MENU"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		// Skip the window handles, attributes, current selection and command tick
+		rdr.seek(SeekFrom::Start(MENU_BAR_LENGTH - 6))?;
+		let menu_count = rdr.read_u16::<BigEndian>()?;
+		let menus_ptr = rdr.read_u32::<BigEndian>()?;
+
+		// Read the pull-down headers
+		let mut pulldowns = Vec::with_capacity(menu_count as usize);
+		let menus_start = seek_ptr(rdr, menus_ptr, MENU_BAR_LENGTH)?;
+		for _ in 0..menu_count {
+			rdr.seek(SeekFrom::Current(4))?;
+			let bounds = Rectangle::from_bytes(rdr)?;
+			rdr.seek(SeekFrom::Current(4))?;
+			let title_bounds = Rectangle::from_bytes(rdr)?;
+			let title_ptr = rdr.read_u32::<BigEndian>()?;
+			let item_info = rdr.read_u16::<BigEndian>()?;
+			let items_ptr = rdr.read_u32::<BigEndian>()?;
+
+			pulldowns.push((bounds, title_bounds, title_ptr, item_info, items_ptr));
+		}
+
+		// Read the item structures for each menu; if the item pointers aren't set, the item
+		// arrays follow each other directly after the pull-down headers
+		let mut next_items = menus_start + (menu_count as u64 * MENU_PULLDOWN_LENGTH);
+		let mut raw_items = Vec::with_capacity(menu_count as usize);
+		for (_, _, _, item_info, items_ptr) in pulldowns.iter() {
+			let item_count = item_info & 0x7FFF;
+			let items_start = seek_ptr(rdr, *items_ptr, next_items)?;
+
+			let mut items = Vec::with_capacity(item_count as usize);
+			for _ in 0..item_count {
+				let item_id = rdr.read_u16::<BigEndian>()?;
+				let command = rdr.read_u8()?;
+				let attr = rdr.read_u8()?;
+				let text_ptr = rdr.read_u32::<BigEndian>()?;
+
+				items.push((item_id, command, attr, text_ptr));
+			}
+
+			next_items = items_start + (item_count as u64 * MENU_ITEM_LENGTH);
+			raw_items.push(items);
+		}
+
+		// And finally the strings - again, if the pointers aren't set, these follow each other in
+		// order of appearance after the last item array
+		let mut next_string = next_items;
+		let mut read_string = |rdr: &mut Cursor<&[u8]>, ptr: u32| -> Result<String, io::Error> {
+			seek_ptr(rdr, ptr, next_string)?;
+			let text = read_cstr(rdr)?;
+			if ptr == 0 {
+				next_string = rdr.position();
+			}
+
+			Ok(palm_to_string(&text))
+		};
+
+		let mut menus = Vec::with_capacity(menu_count as usize);
+		for ((bounds, title_bounds, title_ptr, item_info, _), items) in
+			pulldowns.into_iter().zip(raw_items)
+		{
+			let title = read_string(rdr, title_ptr)?;
+
+			let mut menu_items = Vec::with_capacity(items.len());
+			for (item_id, command, attr, text_ptr) in items {
+				menu_items.push(MenuItem {
+					item_id,
+					shortcut: match command {
+						0 => None,
+						x => Some(x as char),
+					},
+					hidden: attr & 0x80 != 0,
+					text: read_string(rdr, text_ptr)?,
+				});
+			}
+
+			menus.push(Menu {
+				bounds,
+				title_bounds,
+				hidden: item_info & 0x8000 != 0,
+				title,
+				items: menu_items,
+			});
+		}
+
+		Ok(Self { menus })
+	}
+}
@@ -0,0 +1,189 @@
+//! Typed decoders for PRC resources
+//!
+//! Resources within a PRC file are identified by a four-character type code (available through
+//! [`DatabaseRecord::name_str`]) and a resource ID. This module provides the [`ResourceType`]
+//! trait, implemented by the types that know how to decode a specific kind of resource, and the
+//! [`Resource`] enum, which dispatches a resource to the correct decoder based on its type code.
+//!
+//! The UI resources decoded here use the in-memory layout of the corresponding 68k Palm OS
+//! structures, as produced by PilRC and compatible resource compilers. Any pointer fields in those
+//! structures are either zero, or contain an offset from the start of the resource.
+
+use core::fmt::Debug;
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::record::DatabaseRecord;
+
+pub mod alert;
+pub mod bitmap;
+pub mod code;
+pub mod data;
+pub mod font;
+pub mod form;
+pub mod menu;
+pub mod overlay;
+pub mod string;
+
+use self::{
+	alert::Alert,
+	bitmap::BitmapFamily,
+	code::{CodeSegment, JumpTable},
+	data::DataResource,
+	font::Font,
+	form::Form,
+	menu::MenuBar,
+	overlay::OverlaySpec,
+	string::{StringList, StringResource},
+};
+
+/// Helper trait for types representing a decoded resource
+pub trait ResourceType: Sized + Debug {
+	/// The resource type codes this type can decode
+	const TYPE_CODES: &'static [&'static str];
+
+	/// Decode the resource from the given resource data
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error>;
+}
+
+/// A point on the screen, in standard-density coordinates
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Point {
+	pub x: i16,
+	pub y: i16,
+}
+
+impl Point {
+	pub(crate) fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let x = rdr.read_i16::<BigEndian>()?;
+		let y = rdr.read_i16::<BigEndian>()?;
+
+		Ok(Self { x, y })
+	}
+}
+
+/// A rectangle on the screen, as a top-left point and an extent
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct Rectangle {
+	pub top_left: Point,
+	pub extent: Point,
+}
+
+impl Rectangle {
+	pub(crate) fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let top_left = Point::from_bytes(rdr)?;
+		let extent = Point::from_bytes(rdr)?;
+
+		Ok(Self { top_left, extent })
+	}
+}
+
+/// A decoded PRC resource
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resource {
+	/// Form (`tFRM`)
+	Form(Form),
+
+	/// Menu bar (`MBAR` or `MENU`)
+	MenuBar(MenuBar),
+
+	/// Single string (`tSTR`)
+	String(StringResource),
+
+	/// String list (`tSTL`)
+	StringList(StringList),
+
+	/// Alert (`Talt`)
+	Alert(Alert),
+
+	/// Font (`NFNT` or `nfnt`)
+	Font(Font),
+
+	/// Bitmap family (`Tbmp` or `tAIB`)
+	Bitmap(BitmapFamily),
+
+	/// Overlay specification (`xprf`)
+	OverlaySpec(OverlaySpec),
+
+	/// Jump table (`code` resource `0`)
+	JumpTable(JumpTable),
+
+	/// Code segment (`code` resources `1` and above)
+	Code(CodeSegment),
+
+	/// Initialised globals (`data` resource `0`)
+	Data(DataResource),
+}
+
+impl Resource {
+	/// Decode the given resource, dispatching on the resource type code of the record header
+	///
+	/// Returns `Ok(None)` if the record is not a resource, or if there is no decoder for the
+	/// resource type.
+	pub fn from_record<R: DatabaseRecord>(hdr: &R, data: &[u8]) -> Result<Option<Self>, io::Error> {
+		let (type_code, resource_id) = match (hdr.name_str(), hdr.resource_id()) {
+			(Some(name), Some(id)) => (name, id),
+			_ => return Ok(None),
+		};
+
+		Self::from_type_code(type_code, resource_id, data)
+	}
+
+	/// Decode the given resource data as the resource type given by `type_code`
+	///
+	/// The resource ID is needed to distinguish resources that share a type code but not a
+	/// layout, such as the `code` resources.
+	pub fn from_type_code(
+		type_code: &str,
+		resource_id: u16,
+		data: &[u8],
+	) -> Result<Option<Self>, io::Error> {
+		fn decode<T: ResourceType>(data: &[u8]) -> Result<T, io::Error> {
+			T::from_bytes(&mut Cursor::new(data))
+		}
+
+		let resource = match type_code {
+			x if Form::TYPE_CODES.contains(&x) => Self::Form(decode(data)?),
+			x if MenuBar::TYPE_CODES.contains(&x) => Self::MenuBar(decode(data)?),
+			x if StringResource::TYPE_CODES.contains(&x) => Self::String(decode(data)?),
+			x if StringList::TYPE_CODES.contains(&x) => Self::StringList(decode(data)?),
+			x if Alert::TYPE_CODES.contains(&x) => Self::Alert(decode(data)?),
+			x if Font::TYPE_CODES.contains(&x) => Self::Font(decode(data)?),
+			x if BitmapFamily::TYPE_CODES.contains(&x) => Self::Bitmap(decode(data)?),
+			x if OverlaySpec::TYPE_CODES.contains(&x) => Self::OverlaySpec(decode(data)?),
+
+			"code" if resource_id == 0 => {
+				Self::JumpTable(JumpTable::from_bytes(&mut Cursor::new(data))?)
+			}
+			"code" => Self::Code(CodeSegment::from_bytes(
+				resource_id,
+				&mut Cursor::new(data),
+			)?),
+			"data" if resource_id == 0 => {
+				Self::Data(DataResource::from_bytes(&mut Cursor::new(data))?)
+			}
+
+			_ => return Ok(None),
+		};
+
+		Ok(Some(resource))
+	}
+
+	/// Return all of the user-visible text contained within this resource
+	pub fn strings(&self) -> Vec<String> {
+		match self {
+			Self::Form(x) => x.strings(),
+			Self::MenuBar(x) => x.strings(),
+			Self::String(x) => vec![x.text.clone()],
+			Self::StringList(x) => x.strings(),
+			Self::Alert(x) => x.strings(),
+			Self::Font(_)
+			| Self::Bitmap(_)
+			| Self::OverlaySpec(_)
+			| Self::JumpTable(_)
+			| Self::Code(_)
+			| Self::Data(_) => Vec::new(),
+		}
+	}
+}
@@ -0,0 +1,241 @@
+//! Overlay specification resources (`xprf`)
+//!
+//! Every overlay database contains an `xprf` resource with ID `0`, describing the base database it
+//! applies to, the locale it targets, and each of the resources it overlays. See the
+//! [`overlay`][crate::overlay] module for working with overlay databases as a whole.
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{resource::ResourceType, time::PalmTimestamp};
+
+/// The overlay specification version written by Palm OS 3.5 and later (`omOverlayVersion`)
+pub const OVERLAY_SPEC_VERSION: u16 = 0x0004;
+
+/// Specification flag: the `xprf` describes the base database it is contained in
+pub const OVERLAY_SPEC_ATTR_FOR_BASE: u32 = 0x0000_0001;
+
+/// Specification flag: the base database has been stripped of its localisable resources, and
+/// can't be used without an overlay
+pub const OVERLAY_SPEC_ATTR_STRIPPED: u32 = 0x0000_0002;
+
+/// What an overlay does with a single resource
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OverlayKind {
+	/// Hide the base resource
+	Hide,
+
+	/// Add a resource that isn't in the base
+	Add,
+
+	/// Replace the base resource
+	Replace,
+
+	/// Describes a resource of the base database itself
+	Base,
+
+	Unknown(u16),
+}
+
+impl From<u16> for OverlayKind {
+	fn from(value: u16) -> Self {
+		match value {
+			0 => Self::Hide,
+			1 => Self::Add,
+			2 => Self::Replace,
+			3 => Self::Base,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<OverlayKind> for u16 {
+	fn from(kind: OverlayKind) -> Self {
+		match kind {
+			OverlayKind::Hide => 0,
+			OverlayKind::Add => 1,
+			OverlayKind::Replace => 2,
+			OverlayKind::Base => 3,
+			OverlayKind::Unknown(x) => x,
+		}
+	}
+}
+
+/// ISO 639 codes for the Palm OS language numbers, in order
+const LANGUAGE_CODES: &[&str] = &["en", "fr", "de", "it", "es", "", "ja", "nl"];
+
+/// ISO 3166 codes for the Palm OS country numbers, in order
+const COUNTRY_CODES: &[&str] = &[
+	"AU", "AT", "BE", "BR", "CA", "DK", "FI", "FR", "DE", "HK", "IS", "IE", "IT", "JP", "LU", "MX",
+	"NL", "NZ", "NO", "ES", "SE", "CH", "GB", "US",
+];
+
+/// A Palm OS locale, as language and country numbers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Locale {
+	pub language: u16,
+	pub country: u16,
+}
+
+impl Locale {
+	pub fn new(language: u16, country: u16) -> Self {
+		Self { language, country }
+	}
+
+	/// Return the ISO 639 code of the language, if it is known
+	pub fn language_code(&self) -> Option<&'static str> {
+		LANGUAGE_CODES
+			.get(self.language as usize)
+			.copied()
+			.filter(|x| !x.is_empty())
+	}
+
+	/// Return the ISO 3166 code of the country, if it is known
+	pub fn country_code(&self) -> Option<&'static str> {
+		COUNTRY_CODES.get(self.country as usize).copied()
+	}
+
+	/// Return the locale suffix used in overlay database names (for example `enUS`), if the
+	/// language and country are known
+	pub fn suffix(&self) -> Option<String> {
+		Some(format!("{}{}", self.language_code()?, self.country_code()?))
+	}
+}
+
+/// A single overlaid resource within an overlay specification
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OverlayResource {
+	pub kind: OverlayKind,
+	pub type_code: [u8; 4],
+	pub resource_id: u16,
+
+	/// Length of the resource data - for hidden and replaced resources, this is the length of the
+	/// base resource
+	pub length: u32,
+
+	/// Checksum of the resource data, as with [`length`][OverlayResource::length]
+	pub checksum: u32,
+}
+
+impl OverlayResource {
+	/// Return the resource type code as a string, if it is valid UTF-8
+	pub fn type_code_str(&self) -> Option<&str> {
+		core::str::from_utf8(&self.type_code).ok()
+	}
+
+	/// Whether this entry describes a resource of the base database (hidden or replaced)
+	pub fn describes_base(&self) -> bool {
+		matches!(
+			self.kind,
+			OverlayKind::Hide | OverlayKind::Replace | OverlayKind::Base
+		)
+	}
+}
+
+/// An overlay specification resource (`xprf`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverlaySpec {
+	pub version: u16,
+	pub flags: u32,
+
+	/// Combined checksum of the base resources described by this specification
+	pub base_checksum: u32,
+
+	pub target_locale: Locale,
+	pub base_type_code: [u8; 4],
+	pub base_creator_code: [u8; 4],
+	pub base_creation_time: PalmTimestamp,
+	pub base_modification_time: PalmTimestamp,
+	pub resources: Vec<OverlayResource>,
+}
+
+impl OverlaySpec {
+	/// Whether the database this specification describes has been stripped
+	pub fn is_stripped(&self) -> bool {
+		self.flags & OVERLAY_SPEC_ATTR_STRIPPED != 0
+	}
+
+	/// Find the entry for the given resource
+	pub fn resource(&self, type_code: &[u8; 4], resource_id: u16) -> Option<&OverlayResource> {
+		self.resources
+			.iter()
+			.find(|x| &x.type_code == type_code && x.resource_id == resource_id)
+	}
+
+	/// Write the specification to a new `Vec<u8>`
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u16::<BigEndian>(self.version)?;
+		cursor.write_u32::<BigEndian>(self.flags)?;
+		cursor.write_u32::<BigEndian>(self.base_checksum)?;
+		cursor.write_u16::<BigEndian>(self.target_locale.language)?;
+		cursor.write_u16::<BigEndian>(self.target_locale.country)?;
+		cursor.write_all(&self.base_type_code)?;
+		cursor.write_all(&self.base_creator_code)?;
+		cursor.write_u32::<BigEndian>(self.base_creation_time.0)?;
+		cursor.write_u32::<BigEndian>(self.base_modification_time.0)?;
+		cursor.write_u16::<BigEndian>(self.resources.len() as u16)?;
+
+		for resource in self.resources.iter() {
+			cursor.write_u16::<BigEndian>(resource.kind.into())?;
+			cursor.write_all(&resource.type_code)?;
+			cursor.write_u16::<BigEndian>(resource.resource_id)?;
+			cursor.write_u32::<BigEndian>(resource.length)?;
+			cursor.write_u32::<BigEndian>(resource.checksum)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+impl ResourceType for OverlaySpec {
+	const TYPE_CODES: &'static [&'static str] = &["xprf"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let version = rdr.read_u16::<BigEndian>()?;
+		let flags = rdr.read_u32::<BigEndian>()?;
+		let base_checksum = rdr.read_u32::<BigEndian>()?;
+		let language = rdr.read_u16::<BigEndian>()?;
+		let country = rdr.read_u16::<BigEndian>()?;
+
+		let mut base_type_code = [0_u8; 4];
+		rdr.read_exact(&mut base_type_code)?;
+		let mut base_creator_code = [0_u8; 4];
+		rdr.read_exact(&mut base_creator_code)?;
+
+		let base_creation_time = PalmTimestamp(rdr.read_u32::<BigEndian>()?);
+		let base_modification_time = PalmTimestamp(rdr.read_u32::<BigEndian>()?);
+
+		let count = rdr.read_u16::<BigEndian>()?;
+		let mut resources = Vec::with_capacity(count as usize);
+		for _ in 0..count {
+			let kind = rdr.read_u16::<BigEndian>()?.into();
+			let mut type_code = [0_u8; 4];
+			rdr.read_exact(&mut type_code)?;
+			let resource_id = rdr.read_u16::<BigEndian>()?;
+			let length = rdr.read_u32::<BigEndian>()?;
+			let checksum = rdr.read_u32::<BigEndian>()?;
+
+			resources.push(OverlayResource {
+				kind,
+				type_code,
+				resource_id,
+				length,
+				checksum,
+			});
+		}
+
+		Ok(Self {
+			version,
+			flags,
+			base_checksum,
+			target_locale: Locale::new(language, country),
+			base_type_code,
+			base_creator_code,
+			base_creation_time,
+			base_modification_time,
+			resources,
+		})
+	}
+}
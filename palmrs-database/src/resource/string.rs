@@ -0,0 +1,65 @@
+//! String resources (`tSTR` and `tSTL`)
+
+use std::io::{self, Cursor};
+
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::{
+	resource::ResourceType,
+	text::{palm_to_string, read_cstr},
+};
+
+/// A single string resource (`tSTR`)
+///
+/// These are commonly used for help text (referenced by the `help_rsc_id` of a form or alert), as
+/// well as any other free-form text an application needs.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StringResource {
+	pub text: String,
+}
+
+impl ResourceType for StringResource {
+	const TYPE_CODES: &'static [&'static str] = &["tSTR"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let text = palm_to_string(&read_cstr(rdr)?);
+
+		Ok(Self { text })
+	}
+}
+
+/// A string list resource (`tSTL`)
+///
+/// String lists have a common prefix string, which is prepended to each of the strings in the list
+/// when they are retrieved with `SysStringByIndex`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StringList {
+	pub prefix: String,
+	pub strings: Vec<String>,
+}
+
+impl StringList {
+	/// Return the strings in the list with the prefix prepended, as the application would see them
+	pub fn strings(&self) -> Vec<String> {
+		self.strings
+			.iter()
+			.map(|s| format!("{}{}", &self.prefix, s))
+			.collect()
+	}
+}
+
+impl ResourceType for StringList {
+	const TYPE_CODES: &'static [&'static str] = &["tSTL"];
+
+	fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let prefix = palm_to_string(&read_cstr(rdr)?);
+		let count = rdr.read_u16::<BigEndian>()?;
+
+		let mut strings = Vec::with_capacity(count as usize);
+		for _ in 0..count {
+			strings.push(palm_to_string(&read_cstr(rdr)?));
+		}
+
+		Ok(Self { prefix, strings })
+	}
+}
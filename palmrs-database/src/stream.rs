@@ -0,0 +1,150 @@
+//! File Streaming databases
+//!
+//! Databases created with the Palm OS File Streaming API (`FileOpen` and friends) have the stream
+//! attribute set in their header, and store a single byte stream split across their records. Each
+//! record holds one chunk of the stream, prefixed with a `DBLK` signature.
+//!
+//! [`StreamReader`] reassembles the chunks of a stream database into a [`Read`] implementation,
+//! and [`write_stream`] splits a byte stream back up into a new stream database.
+
+use std::io::{self, Read};
+
+use crate::{
+	header::DatabaseHeader,
+	info::NullExtraInfo,
+	record::pdb_record::RecordAttributes,
+	DatabaseFormat,
+	PalmDatabase,
+	PdbDatabase,
+};
+
+/// Database header attribute marking a database as a file stream (`dmHdrAttrStream`)
+pub const STREAM_ATTRIBUTE: u16 = 0x0080;
+
+/// Signature at the start of each stream database record
+pub const STREAM_BLOCK_SIGNATURE: &[u8; 4] = b"DBLK";
+
+/// Default number of stream bytes stored in each record, as used by the File Streaming API
+pub const STREAM_BLOCK_SIZE: usize = 4096;
+
+/// Return whether the given database header has the stream attribute set
+pub fn is_stream_database(header: &DatabaseHeader) -> bool {
+	header.attributes & STREAM_ATTRIBUTE != 0
+}
+
+/// A [`Read`] implementation over the reassembled contents of a stream database
+#[derive(Debug, Clone)]
+pub struct StreamReader<'a> {
+	blocks: Vec<&'a [u8]>,
+	block: usize,
+	position: usize,
+}
+
+impl<'a> StreamReader<'a> {
+	/// Create a reader over the chunks of the given stream database
+	///
+	/// Returns an error if any record doesn't start with the `DBLK` signature. The stream
+	/// attribute itself isn't checked, as some applications don't set it.
+	pub fn new<T: DatabaseFormat>(database: &'a PalmDatabase<T>) -> Result<Self, io::Error> {
+		let mut blocks = Vec::new();
+		for (idx, (_, data)) in database.list_records_resources().iter().enumerate() {
+			match data.strip_prefix(&STREAM_BLOCK_SIGNATURE[..]) {
+				Some(block) => blocks.push(block),
+				None => {
+					return Err(io::Error::other(format!(
+						"stream database record {} has no DBLK signature",
+						idx
+					)));
+				}
+			}
+		}
+
+		Ok(Self {
+			blocks,
+			block: 0,
+			position: 0,
+		})
+	}
+
+	/// Return the total length of the stream, in bytes
+	pub fn len(&self) -> usize {
+		self.blocks.iter().map(|x| x.len()).sum()
+	}
+
+	/// Return whether the stream is empty
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+}
+
+impl<'a> Read for StreamReader<'a> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while let Some(block) = self.blocks.get(self.block) {
+			if self.position < block.len() {
+				let count = buf.len().min(block.len() - self.position);
+				buf[..count].copy_from_slice(&block[self.position..(self.position + count)]);
+				self.position += count;
+
+				return Ok(count);
+			}
+
+			self.block += 1;
+			self.position = 0;
+		}
+
+		Ok(0)
+	}
+}
+
+/// Read the entire contents of the given stream database
+pub fn read_stream<T: DatabaseFormat>(database: &PalmDatabase<T>) -> Result<Vec<u8>, io::Error> {
+	let mut data = Vec::new();
+	StreamReader::new(database)?.read_to_end(&mut data)?;
+
+	Ok(data)
+}
+
+/// Write a byte stream out as a new stream database, with the default block size
+///
+/// See [`write_stream_with_block_size`].
+pub fn write_stream<R: Read>(
+	header: DatabaseHeader,
+	rdr: &mut R,
+) -> Result<PalmDatabase<PdbDatabase>, io::Error> {
+	write_stream_with_block_size(header, rdr, STREAM_BLOCK_SIZE)
+}
+
+/// Write a byte stream out as a new stream database, storing up to `block_size` bytes per record
+///
+/// The given header is used for the new database, with the stream attribute set and the resource
+/// attribute cleared.
+pub fn write_stream_with_block_size<R: Read>(
+	mut header: DatabaseHeader,
+	rdr: &mut R,
+	block_size: usize,
+) -> Result<PalmDatabase<PdbDatabase>, io::Error> {
+	if block_size == 0 {
+		return Err(io::Error::other("stream block size must be non-zero"));
+	}
+
+	header.attributes = (header.attributes | STREAM_ATTRIBUTE) & !(1 << 0);
+	header.app_info_id = 0;
+	header.sort_info_id = 0;
+	header.next_record_list = 0;
+
+	let mut database = PalmDatabase::<PdbDatabase>::new(header, NullExtraInfo);
+	loop {
+		let mut block = STREAM_BLOCK_SIGNATURE.to_vec();
+		let count = rdr
+			.by_ref()
+			.take(block_size as u64)
+			.read_to_end(&mut block)?;
+		if count == 0 {
+			break;
+		}
+
+		database.insert_record(RecordAttributes::default(), &block);
+	}
+
+	Ok(database)
+}
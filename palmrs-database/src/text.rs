@@ -0,0 +1,95 @@
+//! Palm OS text encoding helpers
+//!
+//! Text stored within Palm OS databases and resources is, on all but the CJK-localised devices,
+//! encoded using the Palm OS Latin character set. This is Windows code page 1252 with a handful of
+//! Palm-specific glyphs, so converting through CP1252 gives the right result for almost all of the
+//! text you'll find in the wild.
+
+use std::io::{self, BufRead, Cursor};
+
+/// Mapping of the `0x80..=0x9F` range of CP1252 to Unicode
+///
+/// Every other byte value maps directly to the Unicode code point of the same value.
+const CP1252_HIGH: [char; 32] = [
+	'\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+	'\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+	'\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+	'\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// Convert a Palm OS Latin encoded byte slice to a [`String`]
+pub fn palm_to_string(data: &[u8]) -> String {
+	data.iter()
+		.map(|&b| match b {
+			0x80..=0x9F => CP1252_HIGH[(b - 0x80) as usize],
+			_ => b as char,
+		})
+		.collect()
+}
+
+/// Convert a [`str`] to Palm OS Latin encoded bytes
+///
+/// Characters that can't be represented in the Palm OS Latin character set are replaced with `?`.
+pub fn string_to_palm(s: &str) -> Vec<u8> {
+	s.chars()
+		.map(|c| match c as u32 {
+			x @ 0x00..=0x7F | x @ 0xA0..=0xFF => x as u8,
+			_ => CP1252_HIGH
+				.iter()
+				.position(|&h| h == c)
+				.map(|idx| 0x80 + idx as u8)
+				.unwrap_or(b'?'),
+		})
+		.collect()
+}
+
+/// Return the given byte slice, truncated at the first null byte (if any)
+pub fn trim_null(data: &[u8]) -> &[u8] {
+	match data.iter().position(|&b| b == 0) {
+		Some(idx) => &data[..idx],
+		None => data,
+	}
+}
+
+/// Read a null-terminated string from the cursor, returning the string bytes without the null
+///
+/// Reaching the end of the data before a null byte is found is not treated as an error - the
+/// remaining data is returned as the string content.
+pub(crate) fn read_cstr(rdr: &mut Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+	let mut buf = Vec::new();
+	rdr.read_until(0x00, &mut buf)?;
+	if buf.last() == Some(&0) {
+		buf.pop();
+	}
+
+	Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+	use test_env_log::test;
+
+	use super::*;
+
+	#[test]
+	fn latin_round_trip() {
+		let text = "Caf\u{e9} \u{2014} \u{20AC}5 \u{2122}";
+		let bytes = string_to_palm(text);
+		assert_eq!(bytes, b"Caf\xE9 \x97 \x805 \x99");
+		assert_eq!(palm_to_string(&bytes), text);
+	}
+
+	#[test]
+	fn unrepresentable_chars_replaced() {
+		assert_eq!(string_to_palm("\u{3042}a"), b"?a");
+	}
+
+	#[test]
+	fn cstr_reading() {
+		let data: &[u8] = b"one\0two";
+		let mut rdr = Cursor::new(data);
+		assert_eq!(read_cstr(&mut rdr).unwrap(), b"one");
+		assert_eq!(read_cstr(&mut rdr).unwrap(), b"two");
+		assert_eq!(read_cstr(&mut rdr).unwrap(), b"");
+	}
+}
@@ -0,0 +1,80 @@
+//! Fixture builders shared between the database tests
+//!
+//! Each test file only uses some of these, hence the `dead_code` allowance.
+
+#![allow(dead_code)]
+
+use std::io::Cursor;
+
+use palmrs_database::{
+	header::DatabaseHeader,
+	info::{category::ExtraInfoCategory, NullExtraInfo},
+	record::pdb_record::RecordAttributes,
+	PalmDatabase,
+	PdbDatabase,
+	PdbWithCategoriesDatabase,
+};
+
+/// Database with categories, used as the base for building other databases with categories
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../../test-data/ToDoDB.pdb");
+
+/// Database without an app info block, used as the base for building other databases
+const MANUAL_PDB: &[u8] = include_bytes!("../../../test-data/tWmanual.pdb");
+
+pub fn push_u16(buf: &mut Vec<u8>, value: u16) {
+	buf.extend_from_slice(&value.to_be_bytes());
+}
+
+pub fn push_u32(buf: &mut Vec<u8>, value: u32) {
+	buf.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Build a category entry for an app info block
+pub fn category(category_id: u8, name: &[u8]) -> ExtraInfoCategory {
+	let mut buf = [0_u8; 16];
+	buf[..name.len()].copy_from_slice(name);
+
+	ExtraInfoCategory {
+		category_id,
+		name: buf,
+		renamed: false,
+	}
+}
+
+/// Start an empty database with categories, with the given creator code
+pub fn categories_database(creator_code: &[u8; 4]) -> PalmDatabase<PdbWithCategoriesDatabase> {
+	let base = PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(EXAMPLE_PDB).unwrap();
+	let mut header = base.header;
+	header.creator_code = *creator_code;
+
+	PalmDatabase::<PdbWithCategoriesDatabase>::new(header, base.app_info)
+}
+
+/// Build a database with categories, containing the given records in the "Unfiled" category
+pub fn build_categories_database(creator_code: &[u8; 4], records: &[Vec<u8>]) -> Vec<u8> {
+	let mut database = categories_database(creator_code);
+	for record in records.iter() {
+		database.insert_record(RecordAttributes::default(), record);
+	}
+
+	database.to_bytes().unwrap()
+}
+
+/// Header for a database without an app info block, with the given type and creator codes
+pub fn pdb_header(type_code: &[u8; 4], creator_code: &[u8; 4]) -> DatabaseHeader {
+	let mut header = DatabaseHeader::from_bytes(&mut Cursor::new(MANUAL_PDB)).unwrap();
+	header.type_code = *type_code;
+	header.creator_code = *creator_code;
+	header
+}
+
+/// Build a database without an app info block, containing the given records
+pub fn build_pdb(type_code: &[u8; 4], creator_code: &[u8; 4], records: &[Vec<u8>]) -> Vec<u8> {
+	let header = pdb_header(type_code, creator_code);
+	let mut database = PalmDatabase::<PdbDatabase>::new(header, NullExtraInfo);
+	for record in records.iter() {
+		database.insert_record(RecordAttributes::default(), record);
+	}
+
+	database.to_bytes().unwrap()
+}
@@ -0,0 +1,155 @@
+#![cfg(feature = "disasm")]
+
+use std::io::Cursor;
+
+use palmrs_database::{
+	disasm::{Disassembler, Instruction, Reference, TrapTable},
+	record::DatabaseRecord,
+	resource::code::{CodeSegment, JumpTable},
+	PalmDatabase,
+	PrcDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PRC: &[u8] = include_bytes!("../../test-data/hello-v1.prc");
+
+fn find_resource<'a>(database: &'a PalmDatabase<PrcDatabase>, name: &str, id: u16) -> &'a [u8] {
+	database
+		.list_records_resources()
+		.iter()
+		.find(|(hdr, _)| hdr.name_str() == Some(name) && hdr.resource_id() == Some(id))
+		.map(|(_, data)| data.as_slice())
+		.unwrap()
+}
+
+fn text(instruction: &Instruction) -> String {
+	format!("{} {}", instruction.mnemonic, instruction.operands)
+		.trim_end()
+		.to_string()
+}
+
+#[test]
+fn disassemble_instructions() {
+	let traps = TrapTable::builtin();
+	let disassembler = Disassembler::new(&traps);
+
+	let cases: &[(&[u8], &str)] = &[
+		(&[0x70, 0xFF], "moveq #-1,d0"),
+		(&[0x4E, 0x75], "rts"),
+		(&[0x4E, 0x56, 0xFF, 0xF4], "link a6,#-12"),
+		(&[0x48, 0xE7, 0x1F, 0x00], "movem.l d3-d7,-(sp)"),
+		(
+			&[0x4C, 0xEE, 0x00, 0xF8, 0xFF, 0xE0],
+			"movem.l -32(a6),d3-d7",
+		),
+		(&[0x0C, 0x6E, 0x00, 0x16, 0xFF, 0xE8], "cmpi.w #$16,-24(a6)"),
+		(&[0x3F, 0x3C, 0x00, 0x03], "move.w #3,-(sp)"),
+		(&[0x20, 0x6E, 0xFF, 0xF4], "movea.l -12(a6),a0"),
+		(&[0xD0, 0x8E], "add.l a6,d0"),
+		(&[0xE5, 0x48], "lsl.w #2,d0"),
+		(&[0xC3, 0x40], "exg d1,d0"),
+		(&[0x41, 0xF1, 0x00, 0x00], "lea 0(a1,d0.w),a0"),
+		(&[0x4E, 0x4F, 0xA0, 0x8F], "systrap SysAppStartup"),
+		(&[0x4E, 0x4F, 0xA2, 0xF0], "systrap $A2F0"),
+		(&[0x4E, 0x4F], "trap #15"),
+		(&[0xFF, 0xFF], "dc.w $FFFF"),
+	];
+
+	for (bytes, expected) in cases.iter() {
+		let instructions = disassembler.disassemble(bytes, 0);
+		assert_eq!(instructions.len(), 1, "{:?}", expected);
+		assert_eq!(&text(&instructions[0]), expected);
+		assert_eq!(instructions[0].bytes.as_slice(), *bytes);
+	}
+}
+
+#[test]
+fn disassemble_branches() {
+	let traps = TrapTable::builtin();
+	let disassembler = Disassembler::new(&traps);
+
+	// bra.w at 0x2A, and lea with a PC-relative source at 0x2E
+	let code = [0x60, 0x00, 0x00, 0x62, 0x41, 0xFA, 0xFF, 0xDA];
+	let instructions = disassembler.disassemble(&code, 0x2A);
+	assert_eq!(text(&instructions[0]), "bra.w $8E");
+	assert_eq!(instructions[0].references, vec![Reference::Code(0x8E)]);
+	assert_eq!(instructions[1].address, 0x2E);
+	assert_eq!(text(&instructions[1]), "lea $A(pc),a0");
+}
+
+#[test]
+fn disassemble_hello_world() {
+	let database = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let jump_table =
+		JumpTable::from_bytes(&mut Cursor::new(find_resource(&database, "code", 0))).unwrap();
+	let segment =
+		CodeSegment::from_bytes(1, &mut Cursor::new(find_resource(&database, "code", 1))).unwrap();
+
+	let traps = TrapTable::builtin();
+	let instructions = Disassembler::new(&traps)
+		.with_jump_table(&jump_table)
+		.disassemble_segment(&segment);
+
+	assert_eq!(instructions[0].entry_points, vec![0]);
+	assert_eq!(text(&instructions[0]), "link a6,#-12");
+	assert_eq!(
+		instructions[0].to_string(),
+		"00000000  4E56 FFF4            link     a6,#-12"
+	);
+
+	let traps_called = instructions
+		.iter()
+		.filter(|x| x.mnemonic == "systrap")
+		.map(|x| x.operands.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(
+		&traps_called[..6],
+		&[
+			"SysAppStartup",
+			"SndPlaySystemSound",
+			"SysAppExit",
+			"WinDrawChars",
+			"EvtGetEvent",
+			"SysHandleEvent",
+		]
+	);
+}
+
+#[test]
+fn annotate_jump_table_calls() {
+	let database = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let jump_table =
+		JumpTable::from_bytes(&mut Cursor::new(find_resource(&database, "code", 0))).unwrap();
+
+	let traps = TrapTable::builtin();
+	let disassembler = Disassembler::new(&traps).with_jump_table(&jump_table);
+
+	// jsr 32(a5), the first jump table entry
+	let instructions = disassembler.disassemble(&[0x4E, 0xAD, 0x00, 0x20], 0);
+	assert_eq!(text(&instructions[0]), "jsr 32(a5)");
+	assert_eq!(
+		instructions[0].comment.as_deref(),
+		Some("jump table entry 0: code 1 + $0")
+	);
+
+	// Globals below A5 aren't annotated
+	let instructions = disassembler.disassemble(&[0x20, 0x2D, 0xFF, 0xFC], 0);
+	assert_eq!(text(&instructions[0]), "move.l -4(a5),d0");
+	assert_eq!(instructions[0].comment, None);
+}
+
+#[test]
+fn parse_trap_table() {
+	let mut traps = TrapTable::builtin();
+	assert_eq!(traps.name(0xA08F), Some("SysAppStartup"));
+	assert_eq!(traps.name(0xA2B6), None);
+
+	traps
+		.parse("# Extra traps\n\nsysTrapLstGetTopItem = 0xA2B6,\n0xA2B7 SomeOtherTrap\n")
+		.unwrap();
+	assert_eq!(traps.name(0xA2B6), Some("LstGetTopItem"));
+	assert_eq!(traps.name(0xA2B7), Some("SomeOtherTrap"));
+
+	assert!(traps.parse("0x1234 NotATrap").is_err());
+	assert!(traps.parse("JustAName").is_err());
+}
@@ -0,0 +1,205 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use palmrs_database::ereader::{
+	pml::{self, PmlChapter, PmlToken},
+	EReaderBook,
+	EREADER_COMPRESSION_PALMDOC,
+	EREADER_COMPRESSION_ZLIB,
+	EREADER_CREATOR_CODE,
+	EREADER_TYPE_CODE,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::build_pdb;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/tWmanual.pdb");
+
+const TEXT: &[&str] = &[
+	"\\x\\cChapter One\\c\\x\nIt was a \\idark\\i & stormy night.\\Fn=\"fn1\"1\\Fn",
+	"\\p\\X1Part \\a169\\X1\n\\m=\"cover.png\"\\vhidden\\v\\U00E9t\\U00E9\\C0=\"End\"",
+];
+
+/// Build a book with two text records, an image, metadata, and a footnote
+fn build_book(compression: u16) -> Vec<u8> {
+	build_book_with_chapters(compression, &[])
+}
+
+/// Build a book as [`build_book`] does, followed by the given chapter records
+fn build_book_with_chapters(compression: u16, chapters: &[Vec<u8>]) -> Vec<u8> {
+	let compress = |text: &str| match compression {
+		EREADER_COMPRESSION_ZLIB => {
+			let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+			encoder.write_all(text.as_bytes()).unwrap();
+			encoder.finish().unwrap()
+		}
+
+		// Plain ASCII text is valid PalmDoc compressed data
+		_ => text.as_bytes().to_vec(),
+	};
+
+	// Record layout: header, 2 text records, image, metadata, footnote IDs, footnote text, chapters
+	let mut record0 = vec![0_u8; 132];
+	for (offset, value) in [
+		(0, compression),
+		(12, 3),
+		(20, 1),
+		(24, 1),
+		(28, 2),
+		(40, 3),
+		(44, 4),
+		(48, 5),
+		(52, 7),
+		(14, chapters.len() as u16),
+		(32, 7),
+	]
+	.iter()
+	{
+		record0[*offset..(*offset + 2)].copy_from_slice(&value.to_be_bytes());
+	}
+
+	let mut image = b"PNG cover.png".to_vec();
+	image.resize(62, 0);
+	image.extend_from_slice(b"\x89PNG\r\n\x1a\n");
+
+	let mut records = vec![
+		record0,
+		compress(TEXT[0]),
+		compress(TEXT[1]),
+		image,
+		b"A Title\0An Author\0(c) 2001\0Publisher\x001234567890\0".to_vec(),
+		b"fn1\0".to_vec(),
+		compress("A \\bfootnote\\b."),
+	];
+	records.extend_from_slice(chapters);
+
+	build_pdb(EREADER_TYPE_CODE, EREADER_CREATOR_CODE, &records)
+}
+
+#[test]
+fn tokenize_pml() {
+	assert_eq!(
+		pml::tokenize("a\\\\b\\Sp2\\Sp\\q=\"#x\"link\\q"),
+		vec![
+			PmlToken::Text("a\\b".to_string()),
+			PmlToken::Tag {
+				name: "Sp".to_string(),
+				argument: None
+			},
+			PmlToken::Text("2".to_string()),
+			PmlToken::Tag {
+				name: "Sp".to_string(),
+				argument: None
+			},
+			PmlToken::Tag {
+				name: "q".to_string(),
+				argument: Some("#x".to_string())
+			},
+			PmlToken::Text("link".to_string()),
+			PmlToken::Tag {
+				name: "q".to_string(),
+				argument: None
+			},
+		]
+	);
+}
+
+#[test]
+fn read_book() {
+	for compression in [EREADER_COMPRESSION_PALMDOC, EREADER_COMPRESSION_ZLIB].iter() {
+		let book = EReaderBook::from_bytes(&build_book(*compression)).unwrap();
+		assert_eq!(book.pml().unwrap(), TEXT.concat());
+
+		let metadata = book.metadata().unwrap().unwrap();
+		assert_eq!(metadata.title, "A Title");
+		assert_eq!(metadata.isbn, "1234567890");
+
+		let images = book.images().unwrap();
+		assert_eq!(images.len(), 1);
+		assert_eq!(images[0].name, "cover.png");
+		assert!(images[0].data.starts_with(b"\x89PNG"));
+
+		let footnotes = book.footnotes().unwrap();
+		assert_eq!(footnotes.len(), 1);
+		assert_eq!(footnotes[0].id, "fn1");
+		assert_eq!(footnotes[0].pml, "A \\bfootnote\\b.");
+		assert!(book.sidebars().unwrap().is_empty());
+
+		assert_eq!(
+			book.chapters().unwrap(),
+			vec![
+				PmlChapter {
+					level: 0,
+					title: "Chapter One".to_string()
+				},
+				PmlChapter {
+					level: 1,
+					title: "Part \u{A9}".to_string()
+				},
+				PmlChapter {
+					level: 0,
+					title: "End".to_string()
+				},
+			]
+		);
+	}
+}
+
+#[test]
+fn read_chapter_index() {
+	let chapters = [
+		b"\0\0\0\0Chapter One\0".to_vec(),
+		b"\0\0\0\x40Part \\a169\0".to_vec(),
+	];
+	let book = EReaderBook::from_bytes(&build_book_with_chapters(
+		EREADER_COMPRESSION_ZLIB,
+		&chapters,
+	))
+	.unwrap();
+
+	// The chapter records take precedence over the chapter markers in the text
+	assert_eq!(
+		book.chapters().unwrap(),
+		vec![
+			PmlChapter {
+				level: 0,
+				title: "Chapter One".to_string()
+			},
+			PmlChapter {
+				level: 0,
+				title: "Part \u{A9}".to_string()
+			},
+		]
+	);
+
+	let short = build_book_with_chapters(EREADER_COMPRESSION_ZLIB, &[b"\0\0".to_vec()]);
+	assert!(EReaderBook::from_bytes(&short).unwrap().chapters().is_err());
+}
+
+#[test]
+fn convert_book() {
+	let book = EReaderBook::from_bytes(&build_book(EREADER_COMPRESSION_ZLIB)).unwrap();
+
+	let text = book.to_text().unwrap();
+	assert_eq!(
+		text,
+		"\nChapter One\n\nIt was a dark & stormy night.1\n\n\nPart \u{A9}\n\n\u{E9}t\u{E9}\n\n[fn1] A footnote."
+	);
+
+	let html = book.to_html().unwrap();
+	assert!(html.contains("<title>A Title</title>"));
+	assert!(html.contains(
+		"<h1><div style=\"text-align: center\">Chapter One</div></h1><br />\nIt was a <i>dark</i> &amp; stormy night.<a href=\"#footnote-fn1\">1</a>"
+	));
+	assert!(html.contains("<h2>Part \u{A9}</h2>"));
+	assert!(html.contains("<img src=\"cover.png\" />"));
+	assert!(!html.contains("hidden"));
+	assert!(html.contains("<div id=\"footnote-fn1\">A <b>footnote</b>.</div>"));
+}
+
+#[test]
+fn reject_encrypted_books() {
+	assert!(EReaderBook::from_bytes(&build_book(260)).is_err());
+	assert!(EReaderBook::from_bytes(EXAMPLE_PDB).is_err());
+}
@@ -0,0 +1,190 @@
+#![cfg(feature = "keyring")]
+
+use palmrs_database::{
+	keyring::{
+		KeyringAppInfo,
+		KeyringCipher,
+		KeyringDatabase,
+		KeyringEntry,
+		KEYRING_CREATOR_CODE,
+		KEYRING_TYPE_CODE,
+		KEYRING_VERSION,
+		KEYRING_VERSION_LEGACY,
+	},
+	pim::PackedDate,
+	record::pdb_record::RecordAttributes,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{categories_database, category};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+/// Salt "ABCD", followed by the MD5 hash of the salt and "secret", padded to 64 bytes
+const LEGACY_PASSWORD_RECORD: &[u8] =
+	b"ABCD\x0F\x43\x5F\x80\x8C\x79\xE2\xB3\xBF\x78\x97\xD6\x11\xA5\xD8\x3B";
+
+/// The entry from [`entry`], encrypted with the MD5 hash of "secret"
+const LEGACY_ENTRY_RECORD: &[u8] =
+	b"Bank\0\xF4\xA7\x11\xBE\x94\x3C\x13\x84\xE9\xB5\xF6\x5B\x72\x2C\
+	\xCB\xDE\x8F\x9F\xCB\x96\x34\xFA\x79\xD9";
+
+/// The entry from [`entry`], encrypted with AES-128 using the key derived from "secret"
+const AES128_ENTRY_RECORD: &[u8] = b"\x00\x04\x00\x00Bank\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\
+	\x1A\x1B\x1C\x1D\x1E\x1F\x9D\x31\xE0\xB1\xA9\x61\xDD\x39\x7D\x65\x47\xD5\xC4\xB8\x92\x0D\x04\xE9\
+	\x9E\xF6\x5E\x76\x29\x92\xEE\xED\x8F\x88\xB4\x9F\x91\x7A\xC2\xF4\xCA\x42\x33\xEB\x7A\x70\xDB\xF5\
+	\x3C\x8F\xEE\x1E\x94\x4C";
+
+fn entry() -> KeyringEntry {
+	KeyringEntry {
+		name: "Bank".to_string(),
+		account: "jo".to_string(),
+		password: "hunter2".to_string(),
+		notes: "PIN 1234".to_string(),
+		date: Some(PackedDate {
+			year: 2004,
+			month: 5,
+			day: 6,
+		}),
+	}
+}
+
+fn empty_database(version: u16) -> KeyringDatabase {
+	let mut database = categories_database(KEYRING_CREATOR_CODE);
+	database.app_info.categories = vec![category(0, b"Unfiled"), category(1, b"Banking")];
+	database.header.version = version;
+	database.header.type_code = *KEYRING_TYPE_CODE;
+
+	KeyringDatabase::from_database(database).unwrap()
+}
+
+#[test]
+fn read_legacy_database() {
+	let mut database = empty_database(KEYRING_VERSION_LEGACY);
+	database
+		.database
+		.insert_record(RecordAttributes::default(), LEGACY_PASSWORD_RECORD);
+	database
+		.database
+		.insert_record(RecordAttributes::default(), LEGACY_ENTRY_RECORD);
+
+	let database = KeyringDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+	assert!(database.is_legacy());
+	assert!(database.unlock("wrong").is_err());
+
+	let key = database.unlock("secret").unwrap();
+	let entries = database.entries(&key).unwrap();
+	assert_eq!(entries.len(), 1);
+	assert_eq!(entries[0].1, entry());
+}
+
+#[test]
+fn read_aes128_database() {
+	let mut database = empty_database(KEYRING_VERSION);
+	database.app_info = Some(KeyringAppInfo {
+		salt: [1, 2, 3, 4, 5, 6, 7, 8],
+		iterations: 1000,
+		cipher: KeyringCipher::Aes128,
+		hash: [0xDF, 0x30, 0xF0, 0x7D, 0xC4, 0x7F, 0x0F, 0x46],
+	});
+	database
+		.database
+		.insert_record(RecordAttributes::default(), AES128_ENTRY_RECORD);
+
+	let database = KeyringDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+	assert!(!database.is_legacy());
+	assert!(database.unlock("wrong").is_err());
+
+	let key = database.unlock("secret").unwrap();
+	assert_eq!(database.entries(&key).unwrap()[0].1, entry());
+}
+
+#[test]
+fn write_databases() {
+	let ciphers = [
+		(KEYRING_VERSION_LEGACY, KeyringCipher::TripleDes),
+		(KEYRING_VERSION, KeyringCipher::None),
+		(KEYRING_VERSION, KeyringCipher::TripleDes),
+		(KEYRING_VERSION, KeyringCipher::Aes128),
+		(KEYRING_VERSION, KeyringCipher::Aes256),
+	];
+
+	for (version, cipher) in ciphers.iter() {
+		let mut database = empty_database(*version);
+		let key = database
+			.set_master_password(None, "secret", *cipher, 500)
+			.unwrap();
+
+		let attributes = RecordAttributes {
+			category: 1,
+			..RecordAttributes::default()
+		};
+		let without_date = KeyringEntry {
+			date: None,
+			notes: String::new(),
+			..entry()
+		};
+		database.insert_entry(&key, attributes, &entry()).unwrap();
+		database
+			.insert_entry(&key, RecordAttributes::default(), &without_date)
+			.unwrap();
+
+		let database = KeyringDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+		let key = database.unlock("secret").unwrap();
+		let entries = database.entries(&key).unwrap();
+		assert_eq!(
+			database
+				.database
+				.app_info
+				.category_name(entries[0].0.category)
+				.as_deref(),
+			Some("Banking")
+		);
+		assert_eq!(
+			entries,
+			vec![
+				(attributes, entry()),
+				(RecordAttributes::default(), without_date)
+			]
+		);
+	}
+}
+
+#[test]
+fn change_master_password() {
+	for version in [KEYRING_VERSION_LEGACY, KEYRING_VERSION].iter() {
+		let mut database = empty_database(*version);
+		let key = database
+			.set_master_password(None, "secret", KeyringCipher::Aes256, 500)
+			.unwrap();
+		database
+			.insert_entry(&key, RecordAttributes::default(), &entry())
+			.unwrap();
+
+		// Entries can't be re-encrypted without the current key
+		assert!(database
+			.set_master_password(None, "changed", KeyringCipher::Aes256, 500)
+			.is_err());
+
+		let key = database
+			.set_master_password(Some(&key), "changed", KeyringCipher::Aes256, 500)
+			.unwrap();
+		let index = database.database.list_records_resources().len() - 1;
+		let renamed = KeyringEntry {
+			name: "Savings".to_string(),
+			..entry()
+		};
+		database.set_entry(&key, index, &renamed).unwrap();
+
+		let database = KeyringDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+		assert!(database.unlock("secret").is_err());
+		let key = database.unlock("changed").unwrap();
+		assert_eq!(database.entries(&key).unwrap()[0].1, renamed);
+	}
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(KeyringDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
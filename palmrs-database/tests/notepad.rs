@@ -0,0 +1,134 @@
+use std::io::Cursor;
+
+use palmrs_database::notepad::{
+	NotePadDatabase,
+	NotePadRecord,
+	NotePadSketch,
+	NOTEPAD_CREATOR_CODE,
+	NOTEPAD_DATA_BITS,
+	NOTEPAD_DATA_PNG,
+	NOTEPAD_FLAG_ALARM,
+	NOTEPAD_FLAG_BODY,
+	NOTEPAD_FLAG_NAME,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{build_categories_database, push_u16, push_u32};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn push_date(buf: &mut Vec<u8>, values: [u16; 7]) {
+	for value in values.iter() {
+		push_u16(buf, *value);
+	}
+}
+
+/// Build a Note Pad record with the given flags, title and sketch
+fn build_note(flags: u16, title: &[u8], data_type: u32, data: &[u8]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	push_date(&mut buf, [30, 15, 9, 4, 3, 2003, 2]);
+	push_date(&mut buf, [0, 45, 17, 5, 3, 2003, 3]);
+	push_u16(&mut buf, flags);
+
+	if flags & NOTEPAD_FLAG_ALARM != 0 {
+		push_date(&mut buf, [0, 0, 8, 6, 3, 2003, 4]);
+	}
+
+	if flags & NOTEPAD_FLAG_NAME != 0 {
+		buf.extend_from_slice(title);
+		buf.push(0);
+		if buf.len() % 2 != 0 {
+			buf.push(0);
+		}
+	}
+
+	if flags & NOTEPAD_FLAG_BODY != 0 {
+		for value in [0, 8, 2, 0, data_type, data.len() as u32].iter() {
+			push_u32(&mut buf, *value);
+		}
+
+		buf.extend_from_slice(data);
+	}
+
+	buf
+}
+
+#[test]
+fn read_note() {
+	let flags = NOTEPAD_FLAG_BODY | NOTEPAD_FLAG_NAME | NOTEPAD_FLAG_ALARM;
+	let data = build_note(flags, b"Sketch", NOTEPAD_DATA_BITS, &[2, 0xFF, 2, 0x00]);
+	let note = NotePadRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+
+	assert_eq!(note.title.as_deref(), Some("Sketch"));
+	assert_eq!(
+		note.created.to_datetime().unwrap().to_string(),
+		"2003-03-04 09:15:30"
+	);
+	assert_eq!(note.modified.weekday, 3);
+	assert_eq!(note.alarm.unwrap().hour, 8);
+
+	let sketch = note.sketch.unwrap();
+	assert_eq!((sketch.width, sketch.height), (8, 2));
+
+	// Rows are padded to two bytes, and cropped to the sketch width: the first all black, the
+	// second all white
+	let image = sketch.to_image().unwrap();
+	assert_eq!((image.width(), image.height()), (8, 2));
+	assert_eq!(image.get(7, 0), [0x00, 0x00, 0x00, 0xFF]);
+	assert_eq!(image.get(0, 1), [0xFF, 0xFF, 0xFF, 0xFF]);
+	assert_eq!(&sketch.to_png().unwrap()[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn read_database() {
+	let png = b"\x89PNG\r\n\x1a\nnot really".to_vec();
+	let records = [
+		build_note(
+			NOTEPAD_FLAG_BODY | NOTEPAD_FLAG_NAME,
+			b"Odd",
+			NOTEPAD_DATA_BITS,
+			&[4, 0x81],
+		),
+		build_note(NOTEPAD_FLAG_BODY, b"", NOTEPAD_DATA_PNG, &png),
+		build_note(0, b"", NOTEPAD_DATA_BITS, &[]),
+	];
+
+	let database = build_categories_database(NOTEPAD_CREATOR_CODE, &records);
+	let database = NotePadDatabase::from_bytes(&database).unwrap();
+	let notes = database.notes().unwrap();
+	assert_eq!(notes.len(), 3);
+
+	assert_eq!(notes[0].title.as_deref(), Some("Odd"));
+	assert_eq!(notes[0].sketch.as_ref().unwrap().data, vec![4, 0x81]);
+	assert_eq!(notes[1].title, None);
+	assert_eq!(notes[1].sketch.as_ref().unwrap().to_png().unwrap(), png);
+	assert_eq!(notes[2].sketch, None);
+}
+
+#[test]
+fn reject_bad_sketches() {
+	// Sketch data running past the end of the record
+	let mut data = build_note(
+		NOTEPAD_FLAG_BODY,
+		b"",
+		NOTEPAD_DATA_BITS,
+		&[2, 0xFF, 2, 0x00],
+	);
+	data.truncate(data.len() - 1);
+	assert!(NotePadRecord::from_bytes(&mut Cursor::new(&data)).is_err());
+
+	// Rows too short for the sketch width
+	let sketch = NotePadSketch {
+		width: 16,
+		height: 2,
+		data_type: NOTEPAD_DATA_BITS,
+		data: vec![1, 0xFF, 1, 0x00],
+	};
+	assert!(sketch.to_bitmap().is_none());
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(NotePadDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
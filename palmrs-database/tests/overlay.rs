@@ -0,0 +1,175 @@
+use palmrs_database::{
+	overlay::{crc32, generate_overlay, OverlaidDatabase, OverlayDatabase, OVERLAY_TYPE_CODE},
+	record::DatabaseRecord,
+	resource::overlay::{Locale, OverlayKind},
+	PalmDatabase,
+	PrcDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PRC: &[u8] = include_bytes!("../../test-data/hello-v1.prc");
+
+/// English (United States)
+const LOCALE_EN_US: Locale = Locale {
+	language: 0,
+	country: 23,
+};
+
+/// French (France)
+const LOCALE_FR_FR: Locale = Locale {
+	language: 1,
+	country: 7,
+};
+
+/// Return the type code, ID and data of every resource in the database
+fn resources(database: &PalmDatabase<PrcDatabase>) -> Vec<([u8; 4], u16, Vec<u8>)> {
+	database
+		.list_records_resources()
+		.iter()
+		.map(|(hdr, data)| {
+			let mut type_code = [0_u8; 4];
+			type_code.copy_from_slice(hdr.name_str().unwrap().as_bytes());
+			(type_code, hdr.resource_id().unwrap(), data.clone())
+		})
+		.collect()
+}
+
+/// Load the example PRC, with a couple of localisable resources added
+fn base() -> PalmDatabase<PrcDatabase> {
+	let mut base = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	base.insert_resource_with_id(b"tSTR", 1000, b"Hello, World\0")
+		.unwrap();
+	base.insert_resource_with_id(b"tAIN", 1000, b"Hello\0")
+		.unwrap();
+
+	base
+}
+
+/// Build a "localised" copy of the base, with one string changed, the app icon name removed, and
+/// a new string added
+fn localise(base: &PalmDatabase<PrcDatabase>) -> PalmDatabase<PrcDatabase> {
+	let mut localised = PalmDatabase::<PrcDatabase>::new(base.header, base.app_info);
+	for (type_code, resource_id, data) in resources(base) {
+		let data = match (&type_code, resource_id) {
+			(b"tSTR", 1000) => b"Bonjour, le monde\0".to_vec(),
+			(b"tAIN", 1000) => continue,
+			_ => data,
+		};
+
+		localised
+			.insert_resource_with_id(&type_code, resource_id, &data)
+			.unwrap();
+	}
+
+	localised
+		.insert_resource_with_id(b"tSTR", 9999, b"Bonjour\0")
+		.unwrap();
+
+	localised
+}
+
+#[test]
+fn crc32_check_value() {
+	assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn locale_suffix() {
+	assert_eq!(LOCALE_EN_US.suffix().as_deref(), Some("enUS"));
+	assert_eq!(LOCALE_FR_FR.suffix().as_deref(), Some("frFR"));
+	assert_eq!(Locale::new(5, 23).suffix(), None);
+}
+
+#[test]
+fn new_database_layout() {
+	let base = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let mut database = PalmDatabase::<PrcDatabase>::new(base.header, base.app_info);
+	database
+		.insert_resource_with_id(b"tSTR", 1000, b"one\0")
+		.unwrap();
+	database
+		.insert_resource_with_id(b"tSTR", 1001, b"two\0")
+		.unwrap();
+	assert!(database
+		.insert_resource_with_id(b"tSTR", 1000, b"three\0")
+		.is_err());
+
+	let bytes = database.to_bytes().unwrap();
+	let reread = PalmDatabase::<PrcDatabase>::from_bytes(&bytes).unwrap();
+	assert_eq!(reread.header.record_count, 2);
+	assert_eq!(resources(&reread), resources(&database));
+}
+
+#[test]
+fn generate_and_apply_overlay() {
+	let base = base();
+	let localised = localise(&base);
+
+	let overlay = generate_overlay(&base, &localised, LOCALE_FR_FR).unwrap();
+	assert_eq!(&overlay.database.header.type_code, OVERLAY_TYPE_CODE);
+	assert_eq!(overlay.database.header.name_trimmed(), b"Hello, World_frFR");
+
+	let kinds = overlay
+		.spec
+		.resources
+		.iter()
+		.map(|x| (x.type_code, x.resource_id, x.kind))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		kinds,
+		vec![
+			(*b"tSTR", 1000, OverlayKind::Replace),
+			(*b"tAIN", 1000, OverlayKind::Hide),
+			(*b"tSTR", 9999, OverlayKind::Add),
+		]
+	);
+
+	// The overlay survives a round trip through its on-disk form
+	let overlay = OverlayDatabase::from_bytes(&overlay.database.to_bytes().unwrap()).unwrap();
+	overlay.validate(&base).unwrap();
+
+	let overlays = vec![overlay];
+	let overlaid = OverlaidDatabase::new(&base, &overlays, LOCALE_FR_FR).unwrap();
+	assert!(overlaid.overlay.is_some());
+	assert_eq!(overlaid.resource(b"tSTR", 9999), Some(&b"Bonjour\0"[..]));
+	assert_eq!(overlaid.resource(b"tAIN", 1000), None);
+	let expected = resources(&localised);
+	for (type_code, resource_id, data) in expected.iter() {
+		assert_eq!(
+			overlaid.resource(type_code, *resource_id),
+			Some(data.as_slice())
+		);
+	}
+
+	assert_eq!(overlaid.resources().len(), expected.len());
+
+	// Overlays for other locales are ignored
+	let overlaid = OverlaidDatabase::new(&base, &overlays, LOCALE_EN_US).unwrap();
+	assert!(overlaid.overlay.is_none());
+	assert_eq!(overlaid.resource(b"tSTR", 9999), None);
+}
+
+#[test]
+fn overlay_rejects_modified_base() {
+	let base = base();
+	let localised = localise(&base);
+	let overlay = generate_overlay(&base, &localised, LOCALE_FR_FR).unwrap();
+
+	// Change the replaced resource in the base, as a newer version of the application would
+	let mut modified = PalmDatabase::<PrcDatabase>::new(base.header, base.app_info);
+	for (type_code, resource_id, mut data) in resources(&base) {
+		if (&type_code, resource_id) == (b"tSTR", 1000) {
+			data.push(0);
+		}
+
+		modified
+			.insert_resource_with_id(&type_code, resource_id, &data)
+			.unwrap();
+	}
+
+	assert!(overlay.validate(&modified).is_err());
+
+	let overlays = vec![overlay];
+	let overlaid = OverlaidDatabase::new(&modified, &overlays, LOCALE_FR_FR).unwrap();
+	assert!(overlaid.overlay.is_none());
+}
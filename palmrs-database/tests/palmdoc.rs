@@ -0,0 +1,25 @@
+use palmrs_database::palmdoc::{decompress, PalmDocDatabase, COMPRESSION_NONE};
+use test_env_log::test;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/tWmanual.pdb");
+
+#[test]
+fn decompress_palmdoc() {
+	// Literals, a literal run, a space-plus-character pair, and an overlapping back-reference
+	let data = b"ab\x02\x01\x02\xE3\x80\x0A";
+	assert_eq!(decompress(data).unwrap(), b"ab\x01\x02 cccccc");
+
+	// Back-references before the start of the output are rejected
+	assert!(decompress(b"a\x80\x18").is_err());
+}
+
+#[test]
+fn read_palmdoc_text() {
+	let doc = PalmDocDatabase::from_bytes(EXAMPLE_PDB).unwrap();
+	assert_eq!(doc.header.compression, COMPRESSION_NONE);
+	assert_eq!(doc.header.record_size, 4096);
+
+	let text = doc.text_bytes().unwrap();
+	assert_eq!(text.len(), doc.header.text_length as usize);
+	assert!(doc.text().unwrap().contains("tejpWriter"));
+}
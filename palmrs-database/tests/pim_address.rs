@@ -0,0 +1,139 @@
+use std::io::Cursor;
+
+use palmrs_database::pim::{
+	address::{
+		AddressDatabase,
+		AddressRecord,
+		ContactAddress,
+		ContactBirthday,
+		ContactRecord,
+		ADDRESS_CREATOR_CODE,
+		CONTACTS_BLOB_PICTURE,
+		CONTACTS_CREATOR_CODE,
+	},
+	PackedDate,
+	PimBlob,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::build_categories_database;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn address() -> AddressRecord {
+	AddressRecord {
+		phone_labels: [0, 1, 2, 7, 4],
+		show_phone: 3,
+		last_name: "Smith".to_string(),
+		first_name: "Jo".to_string(),
+		company: "Acme".to_string(),
+		phones: [
+			"555-0100".to_string(),
+			String::new(),
+			String::new(),
+			"555-0199".to_string(),
+			String::new(),
+		],
+		city: "Springfield".to_string(),
+		note: "Met at the conference".to_string(),
+		..AddressRecord::default()
+	}
+}
+
+fn contact() -> ContactRecord {
+	let mut contact = ContactRecord::from(address());
+	contact.phones[6] = "555-0142".to_string();
+	contact.phone_labels[6] = 7;
+	contact.im_labels = [1, 3];
+	contact.im[1] = "jo.smith".to_string();
+	contact.website = "https://example.com/".to_string();
+	contact.addresses[2] = ContactAddress {
+		label: 2,
+		street: "1 Beach Road".to_string(),
+		country: "Australia".to_string(),
+		..ContactAddress::default()
+	};
+	contact.birthday = Some(ContactBirthday {
+		date: PackedDate {
+			year: 1970,
+			month: 2,
+			day: 3,
+		},
+		reminder_days: Some(5),
+	});
+	contact.blobs = vec![PimBlob {
+		code: *CONTACTS_BLOB_PICTURE,
+		data: b"\0\0\xFF\xD8\xFF\xE0".to_vec(),
+	}];
+
+	contact
+}
+
+#[test]
+fn address_round_trip() {
+	let data = address().to_bytes().unwrap();
+
+	// Phone labels, with the shown phone in bits 20 to 23
+	assert_eq!(&data[..4], &[0x00, 0x34, 0x72, 0x10]);
+
+	// Last name, first name, company, phones 1 and 4, city and note
+	assert_eq!(&data[4..8], &[0x00, 0x04, 0x02, 0x4F]);
+
+	// The company name follows "Smith\0Jo\0"
+	assert_eq!(data[8], 10);
+	assert_eq!(&data[9..23], b"Smith\0Jo\0Acme\0");
+
+	let record = AddressRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, address());
+}
+
+#[test]
+fn contact_round_trip() {
+	let data = contact().to_bytes().unwrap();
+	assert_eq!(&data[..4], &[0x37, 0x04, 0x72, 0x10]);
+
+	// Instant messaging labels, and the label of the third address
+	assert_eq!(&data[4..8], &[0x02, 0x00, 0x00, 0x31]);
+
+	// Phone 7, IM 2, website and the first city in the first bitmask; the third address, note,
+	// birthday and reminder in the second
+	assert_eq!(&data[8..12], &[0x01, 0x00, 0x34, 0x97]);
+	assert_eq!(&data[12..16], &[0x00, 0x00, 0x2E, 0x20]);
+
+	let record = ContactRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, contact());
+	assert_eq!(record.picture(), Some(&b"\xFF\xD8\xFF\xE0"[..]));
+}
+
+#[test]
+fn classic_mapping() {
+	let classic = AddressRecord::from(contact());
+	assert_eq!(classic, address());
+
+	// The shown phone can't be one that the classic record doesn't have
+	let contact = ContactRecord {
+		show_phone: 6,
+		..contact()
+	};
+	assert_eq!(AddressRecord::from(contact).show_phone, 0);
+}
+
+#[test]
+fn read_databases() {
+	let classic = build_categories_database(ADDRESS_CREATOR_CODE, &[address().to_bytes().unwrap()]);
+	let database = AddressDatabase::from_bytes(&classic).unwrap();
+	assert!(!database.is_contacts());
+	assert_eq!(
+		database.contacts().unwrap()[0].1,
+		ContactRecord::from(address())
+	);
+
+	let contacts =
+		build_categories_database(CONTACTS_CREATOR_CODE, &[contact().to_bytes().unwrap()]);
+	let database = AddressDatabase::from_bytes(&contacts).unwrap();
+	assert!(database.is_contacts());
+	assert_eq!(database.contacts().unwrap()[0].1, contact());
+
+	assert!(AddressDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
@@ -0,0 +1,167 @@
+use std::io::Cursor;
+
+use palmrs_database::pim::{
+	datebook::{
+		AlarmUnit,
+		AppointmentRecord,
+		CalendarEvent,
+		CalendarTimeZone,
+		DatebookDatabase,
+		EventAlarm,
+		CALENDAR_CREATOR_CODE,
+		DATEBOOK_CREATOR_CODE,
+	},
+	PackedDate,
+	PimBlob,
+	RepeatInfo,
+	RepeatType,
+	TimeOfDay,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::build_categories_database;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn appointment() -> AppointmentRecord {
+	AppointmentRecord {
+		date: PackedDate {
+			year: 2004,
+			month: 5,
+			day: 6,
+		},
+		start_time: Some(TimeOfDay {
+			hour: 14,
+			minute: 0,
+		}),
+		end_time: Some(TimeOfDay {
+			hour: 15,
+			minute: 30,
+		}),
+		alarm: Some(EventAlarm {
+			advance: 10,
+			unit: AlarmUnit::Minutes,
+		}),
+		repeat: Some(RepeatInfo {
+			repeat_type: RepeatType::MonthlyByDate,
+			end_date: Some(PackedDate {
+				year: 2004,
+				month: 12,
+				day: 31,
+			}),
+			frequency: 1,
+			repeat_on: 0,
+			start_of_week: 0,
+		}),
+		exceptions: vec![PackedDate {
+			year: 2004,
+			month: 8,
+			day: 6,
+		}],
+		description: "Team meeting".to_string(),
+		note: String::new(),
+	}
+}
+
+fn event() -> CalendarEvent {
+	CalendarEvent {
+		appointment: appointment(),
+		location: "Room 4".to_string(),
+		time_zone: Some(CalendarTimeZone {
+			offset: 60,
+			dst_start: [0, 4, 2, 0],
+			dst_end: [0, 4, 9, 0],
+			dst_observed: true,
+			name: "Paris".to_string(),
+			unknown: [3, 1, 0],
+		}),
+		blobs: vec![PimBlob {
+			code: *b"XYZ1",
+			data: vec![1, 2, 3],
+		}],
+	}
+}
+
+#[test]
+fn appointment_round_trip() {
+	let data = appointment().to_bytes().unwrap();
+	assert_eq!(&data[..8], &[14, 0, 15, 30, 0xC8, 0xA6, 0x6C, 0]);
+	assert_eq!(&data[8..10], &[10, 0]);
+	assert_eq!(&data[10..18], &[4, 0, 0xC9, 0x9F, 1, 0, 0, 0]);
+	assert_eq!(&data[18..22], &[0, 1, 0xC9, 0x06]);
+	assert_eq!(&data[22..], b"Team meeting\0");
+
+	let record = AppointmentRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, appointment());
+	assert_eq!(record.start().unwrap().to_string(), "2004-05-06 14:00:00");
+}
+
+#[test]
+fn untimed_appointment() {
+	let untimed = AppointmentRecord {
+		start_time: None,
+		end_time: None,
+		alarm: None,
+		repeat: None,
+		exceptions: Vec::new(),
+		note: "All day".to_string(),
+		..appointment()
+	};
+
+	let data = untimed.to_bytes().unwrap();
+	assert_eq!(&data[..8], &[0xFF, 0xFF, 0xFF, 0xFF, 0xC8, 0xA6, 0x14, 0]);
+	assert!(data.ends_with(b"Team meeting\0All day\0"));
+
+	let record = AppointmentRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, untimed);
+	assert_eq!(record.start(), None);
+}
+
+#[test]
+fn event_round_trip() {
+	let data = event().to_bytes().unwrap();
+
+	// The location flag is set, and the location follows the description
+	assert_eq!(data[6], 0x6E);
+	let location = b"Team meeting\0Room 4\0Bd00";
+	assert!(data.windows(location.len()).any(|x| x == location));
+	assert!(data.ends_with(b"XYZ1\0\x03\x01\x02\x03"));
+
+	let record = CalendarEvent::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, event());
+}
+
+#[test]
+fn classic_mapping() {
+	assert_eq!(AppointmentRecord::from(event()), appointment());
+
+	let mapped = CalendarEvent::from(appointment());
+	assert_eq!(mapped.location, "");
+	assert_eq!(mapped.time_zone, None);
+
+	// An event without Calendar-only fields has the classic layout
+	assert_eq!(
+		mapped.to_bytes().unwrap(),
+		appointment().to_bytes().unwrap()
+	);
+}
+
+#[test]
+fn read_databases() {
+	let classic =
+		build_categories_database(DATEBOOK_CREATOR_CODE, &[appointment().to_bytes().unwrap()]);
+	let database = DatebookDatabase::from_bytes(&classic).unwrap();
+	assert!(!database.is_calendar());
+	assert_eq!(
+		database.events().unwrap()[0].1,
+		CalendarEvent::from(appointment())
+	);
+
+	let calendar = build_categories_database(CALENDAR_CREATOR_CODE, &[event().to_bytes().unwrap()]);
+	let database = DatebookDatabase::from_bytes(&calendar).unwrap();
+	assert!(database.is_calendar());
+	assert_eq!(database.events().unwrap()[0].1, event());
+
+	assert!(DatebookDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
@@ -0,0 +1,209 @@
+use std::io::Cursor;
+
+use palmrs_database::{
+	pim::{
+		expense::{
+			builtin_currency_code,
+			ExpenseAppInfo,
+			ExpenseCustomCurrency,
+			ExpenseDatabase,
+			ExpensePayment,
+			ExpenseRecord,
+			ExpenseSortOrder,
+			ExpenseType,
+			EXPENSE_CREATOR_CODE,
+			EXPENSE_CURRENCY_CUSTOM,
+			EXPENSE_CURRENCY_EURO,
+		},
+		PackedDate,
+	},
+	prefs::NumberFormat,
+	record::pdb_record::RecordAttributes,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{categories_database, category};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn expense() -> ExpenseRecord {
+	ExpenseRecord {
+		date: PackedDate {
+			year: 2003,
+			month: 3,
+			day: 4,
+		},
+		expense_type: ExpenseType::BusinessMeals,
+		payment: ExpensePayment::Visa,
+		currency: 23,
+		amount: "1,234.50".to_string(),
+		vendor: "Chez \"Marcel\"".to_string(),
+		city: "Paris".to_string(),
+		attendees: "Bob, Carol".to_string(),
+		note: "Client dinner\nWith dessert".to_string(),
+	}
+}
+
+fn app_info() -> ExpenseAppInfo {
+	let mut app_info = ExpenseAppInfo {
+		sort_order: ExpenseSortOrder::Type,
+		..ExpenseAppInfo::default()
+	};
+	app_info.currencies[1] = ExpenseCustomCurrency {
+		name: "Narnia".to_string(),
+		symbol: "N1".to_string(),
+		rate: "0.25".to_string(),
+	};
+
+	app_info
+}
+
+fn build_database() -> Vec<u8> {
+	let mut database = categories_database(EXPENSE_CREATOR_CODE);
+	database.app_info.categories = vec![category(0, b"Unfiled"), category(1, b"Trip: Paris")];
+
+	let taxi = ExpenseRecord {
+		date: PackedDate {
+			year: 2003,
+			month: 3,
+			day: 2,
+		},
+		expense_type: ExpenseType::Taxi,
+		payment: ExpensePayment::Cash,
+		currency: EXPENSE_CURRENCY_CUSTOM + 1,
+		amount: "12".to_string(),
+		vendor: String::new(),
+		city: String::new(),
+		attendees: String::new(),
+		note: String::new(),
+	};
+	let broken = ExpenseRecord {
+		amount: String::new(),
+		vendor: "Nowhere".to_string(),
+		..taxi.clone()
+	};
+
+	for (category, record) in [(1, expense()), (0, taxi), (0, broken)].iter() {
+		let attributes = RecordAttributes {
+			category: *category,
+			..RecordAttributes::default()
+		};
+
+		database.insert_record(attributes, &record.to_bytes().unwrap());
+	}
+
+	let database = ExpenseDatabase {
+		database,
+		app_info: app_info(),
+	};
+	database.to_bytes().unwrap()
+}
+
+#[test]
+fn record_round_trip() {
+	let data = expense().to_bytes().unwrap();
+	assert_eq!(&data[..6], &[0xC6, 0x64, 3, 6, 23, 0]);
+	assert!(data.ends_with(b"With dessert\0"));
+
+	let record = ExpenseRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, expense());
+	assert_eq!(ExpenseType::from(28), ExpenseType::Unknown(28));
+	assert_eq!(u8::from(ExpenseType::Train), 27);
+}
+
+#[test]
+fn amount_value() {
+	assert_eq!(
+		expense().amount_value(NumberFormat::CommaPeriod),
+		Some(1234.5)
+	);
+
+	let record = ExpenseRecord {
+		amount: "1.234,50".to_string(),
+		..expense()
+	};
+	assert_eq!(record.amount_value(NumberFormat::PeriodComma), Some(1234.5));
+	assert_eq!(record.amount_value(NumberFormat::CommaPeriod), None);
+}
+
+#[test]
+fn app_info_round_trip() {
+	let data = app_info().to_bytes().unwrap();
+	assert_eq!(data.len(), ExpenseAppInfo::SIZE);
+	assert_eq!(&data[..2], &[1, 0]);
+	assert_eq!(&data[30..34], b"Narn");
+
+	let parsed = ExpenseAppInfo::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(parsed, app_info());
+}
+
+#[test]
+fn database_currencies() {
+	let database = ExpenseDatabase::from_bytes(&build_database()).unwrap();
+	assert_eq!(database.app_info, app_info());
+	assert_eq!(database.expenses().unwrap().len(), 3);
+
+	assert_eq!(builtin_currency_code(0), Some("AUD"));
+	assert_eq!(database.currency_name(23).as_deref(), Some("USD"));
+	assert_eq!(
+		database.currency_name(EXPENSE_CURRENCY_EURO).as_deref(),
+		Some("EUR")
+	);
+	assert_eq!(
+		database
+			.currency_name(EXPENSE_CURRENCY_CUSTOM + 1)
+			.as_deref(),
+		Some("N1")
+	);
+	assert_eq!(database.currency_name(EXPENSE_CURRENCY_CUSTOM), None);
+}
+
+#[test]
+fn export_csv() {
+	let database = ExpenseDatabase::from_bytes(&build_database()).unwrap();
+	let mut out = Vec::new();
+	database.write_csv(&mut out).unwrap();
+	let text = String::from_utf8(out).unwrap();
+
+	let lines = text.split("\r\n").collect::<Vec<_>>();
+	assert_eq!(
+		lines[0],
+		"Date,Category,Type,Payment,Currency,Amount,Vendor,City,Attendees,Note"
+	);
+	assert_eq!(
+		lines[1],
+		"2003-03-04,Trip: Paris,Business Meals,VISA,USD,\"1,234.50\",\"Chez \"\"Marcel\"\"\",Paris,\"Bob, Carol\",\"Client dinner\nWith dessert\""
+	);
+	assert_eq!(lines[2], "2003-03-02,Unfiled,Taxi,Cash,N1,12,,,,");
+	assert_eq!(lines.len(), 5);
+}
+
+#[test]
+fn export_ledger() {
+	let database = ExpenseDatabase::from_bytes(&build_database()).unwrap();
+	let mut out = Vec::new();
+	database
+		.write_ledger(&mut out, NumberFormat::CommaPeriod)
+		.unwrap();
+	let text = String::from_utf8(out).unwrap();
+
+	assert!(
+		text.starts_with("2003-03-02 Taxi\n    Expenses:Taxi  12.00 \"N1\"\n    Assets:Cash\n\n")
+	);
+	assert!(text.contains("; skipped Nowhere: invalid date or amount \"\"\n"));
+	assert!(text.ends_with(concat!(
+		"2003-03-04 Chez \"Marcel\"\n",
+		"    ; City: Paris\n",
+		"    ; Attendees: Bob, Carol\n",
+		"    ; Note: Client dinner\n",
+		"    ; Note: With dessert\n",
+		"    Expenses:Trip- Paris:Business Meals  1234.50 USD\n",
+		"    Liabilities:VISA\n\n",
+	)));
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(ExpenseDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
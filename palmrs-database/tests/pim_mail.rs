@@ -0,0 +1,204 @@
+use std::{fs, io::Cursor, path::PathBuf};
+
+use chrono::NaiveDate;
+use palmrs_database::{
+	pim::{
+		mail::{
+			write_mbox,
+			MailAddressing,
+			MailDatabase,
+			MailDateTime,
+			MailPriority,
+			MailRecord,
+			MAIL_CREATOR_CODE,
+			MAIL_FOLDER_DELETED,
+			MAIL_FOLDER_DRAFT,
+			MAIL_FOLDER_FILED,
+			MAIL_FOLDER_INBOX,
+			MAIL_FOLDER_OUTBOX,
+		},
+		PackedDate,
+	},
+	record::pdb_record::RecordAttributes,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{categories_database, category};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn message() -> MailRecord {
+	MailRecord {
+		date: Some(MailDateTime::from(
+			NaiveDate::from_ymd_opt(2002, 7, 14)
+				.unwrap()
+				.and_hms_opt(16, 5, 0)
+				.unwrap(),
+		)),
+		read: true,
+		confirm_read: true,
+		priority: MailPriority::High,
+		addressing: MailAddressing::Cc,
+		subject: "Caf\u{e9} meeting".to_string(),
+		from: "Alice <alice@example.com>".to_string(),
+		to: "bob@example.com".to_string(),
+		cc: "carol@example.com".to_string(),
+		body: "See you there.\nFrom the office,\nAlice".to_string(),
+		..MailRecord::default()
+	}
+}
+
+fn build_database() -> Vec<u8> {
+	let mut database = categories_database(MAIL_CREATOR_CODE);
+	database.app_info.categories = vec![
+		category(MAIL_FOLDER_INBOX, b"Inbox"),
+		category(MAIL_FOLDER_OUTBOX, b"Outbox"),
+		category(MAIL_FOLDER_DELETED, b"Deleted"),
+		category(MAIL_FOLDER_FILED, b"Filed"),
+		category(MAIL_FOLDER_DRAFT, b"Draft"),
+	];
+
+	let unread = MailRecord {
+		read: false,
+		date: None,
+		subject: "Undated".to_string(),
+		..message()
+	};
+
+	for (folder, record) in [(MAIL_FOLDER_FILED, message()), (MAIL_FOLDER_INBOX, unread)].iter() {
+		let attributes = RecordAttributes {
+			category: *folder,
+			..RecordAttributes::default()
+		};
+
+		database.insert_record(attributes, &record.to_bytes().unwrap());
+	}
+
+	database.to_bytes().unwrap()
+}
+
+/// Create an empty scratch directory for a test
+fn scratch_dir(name: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("palmrs-{}-{}", name, std::process::id()));
+	let _ = fs::remove_dir_all(&path);
+	path
+}
+
+#[test]
+fn packed_date() {
+	let date = PackedDate::from_u16(0xC4EE);
+	assert_eq!(
+		date,
+		PackedDate {
+			year: 2002,
+			month: 7,
+			day: 14
+		}
+	);
+	assert_eq!(date.to_u16(), 0xC4EE);
+	assert_eq!(date.to_naive_date(), NaiveDate::from_ymd_opt(2002, 7, 14));
+}
+
+#[test]
+fn record_round_trip() {
+	let data = message().to_bytes().unwrap();
+	assert_eq!(&data[..6], &[0xC4, 0xEE, 16, 5, 0xA1, 0]);
+	assert!(data.ends_with(b"Alice\0"));
+
+	let record = MailRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, message());
+	assert!(!record.signature);
+	assert_eq!(record.bcc, "");
+}
+
+#[test]
+fn rfc5322() {
+	let text = message().to_rfc5322();
+	assert!(text.starts_with("Date: Sun, 14 Jul 2002 16:05:00 -0000\n"));
+	assert!(text.contains("\nFrom: Alice <alice@example.com>\n"));
+	assert!(text.contains("\nCc: carol@example.com\n"));
+	assert!(!text.contains("Bcc:"));
+	assert!(text.contains("\nSubject: =?utf-8?Q?Caf=C3=A9_meeting?=\n"));
+	assert!(text.contains("\nX-Priority: 1 (Highest)\n"));
+	assert!(text.ends_with("\n\nSee you there.\nFrom the office,\nAlice\n"));
+}
+
+#[test]
+fn mbox() {
+	let mut out = Vec::new();
+	write_mbox(&mut out, &[message(), message()]).unwrap();
+	let text = String::from_utf8(out).unwrap();
+
+	assert!(text.starts_with("From alice@example.com Sun Jul 14 16:05:00 2002\nDate: "));
+	assert!(text.contains("\n>From the office,\n"));
+	assert_eq!(text.matches("\nFrom alice@example.com ").count(), 1);
+	assert!(text.ends_with("Alice\n\n"));
+}
+
+#[test]
+fn database_folders() {
+	let database = MailDatabase::from_bytes(&build_database()).unwrap();
+	assert_eq!(database.messages().unwrap().len(), 2);
+	assert_eq!(
+		database.folder_name(MAIL_FOLDER_FILED).as_deref(),
+		Some("Filed")
+	);
+
+	let folders = database.folders().unwrap();
+	assert_eq!(folders.len(), 2);
+	assert_eq!(folders[0].0, "Inbox");
+	assert_eq!(folders[0].1[0].subject, "Undated");
+	assert_eq!(folders[1].0, "Filed");
+	assert_eq!(folders[1].1[0], message());
+}
+
+#[test]
+fn export_maildir() {
+	let path = scratch_dir("maildir");
+	let database = MailDatabase::from_bytes(&build_database()).unwrap();
+	database.export_maildir(&path).unwrap();
+
+	let filed = fs::read_dir(path.join("Filed").join("cur"))
+		.unwrap()
+		.map(|x| x.unwrap().path())
+		.collect::<Vec<_>>();
+	assert_eq!(filed.len(), 1);
+	assert!(filed[0].to_str().unwrap().ends_with(":2,S"));
+	assert_eq!(
+		fs::read_to_string(&filed[0]).unwrap(),
+		message().to_rfc5322()
+	);
+
+	let inbox = fs::read_dir(path.join("Inbox").join("cur"))
+		.unwrap()
+		.map(|x| x.unwrap().path())
+		.collect::<Vec<_>>();
+	assert!(inbox[0].to_str().unwrap().ends_with(":2,"));
+	assert_eq!(
+		fs::read_dir(path.join("Inbox").join("tmp"))
+			.unwrap()
+			.count(),
+		0
+	);
+
+	fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn export_mbox() {
+	let path = scratch_dir("mbox");
+	let database = MailDatabase::from_bytes(&build_database()).unwrap();
+	database.export_mbox(&path).unwrap();
+
+	let inbox = fs::read_to_string(path.join("Inbox.mbox")).unwrap();
+	assert!(inbox.starts_with("From alice@example.com Thu Jan  1 00:00:00 1970\n"));
+	assert!(path.join("Filed.mbox").exists());
+
+	fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(MailDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
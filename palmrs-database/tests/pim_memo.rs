@@ -0,0 +1,38 @@
+use std::io::Cursor;
+
+use palmrs_database::pim::memo::{MemoDatabase, MemoRecord, MEMOS_CREATOR_CODE, MEMO_CREATOR_CODE};
+use test_env_log::test;
+
+mod common;
+use self::common::build_categories_database;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn memo() -> MemoRecord {
+	MemoRecord {
+		text: "Shopping\nMilk\nCaf\u{e9} beans".to_string(),
+	}
+}
+
+#[test]
+fn memo_round_trip() {
+	let data = memo().to_bytes().unwrap();
+	assert_eq!(data, b"Shopping\nMilk\nCaf\xE9 beans\0");
+
+	let record = MemoRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, memo());
+	assert_eq!(record.title(), "Shopping");
+}
+
+#[test]
+fn read_databases() {
+	for (creator_code, is_memos) in [(MEMO_CREATOR_CODE, false), (MEMOS_CREATOR_CODE, true)].iter()
+	{
+		let data = build_categories_database(creator_code, &[memo().to_bytes().unwrap()]);
+		let database = MemoDatabase::from_bytes(&data).unwrap();
+		assert_eq!(database.is_memos(), *is_memos);
+		assert_eq!(database.memos().unwrap()[0].1, memo());
+	}
+
+	assert!(MemoDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
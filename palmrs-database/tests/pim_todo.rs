@@ -0,0 +1,172 @@
+use std::io::Cursor;
+
+use palmrs_database::{
+	pim::{
+		todo::{
+			TaskAlarm,
+			TaskRecord,
+			ToDoDatabase,
+			ToDoRecord,
+			TASKS_CREATOR_CODE,
+			TASKS_FLAG_ALARM,
+			TASKS_FLAG_DUE_DATE,
+			TASKS_FLAG_REPEAT,
+		},
+		PackedDate,
+		RepeatInfo,
+		RepeatType,
+		TimeOfDay,
+	},
+	record::pdb_record::RecordAttributes,
+	PalmDatabase,
+	PdbWithCategoriesDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+fn task() -> TaskRecord {
+	TaskRecord {
+		due_date: Some(PackedDate {
+			year: 2004,
+			month: 5,
+			day: 6,
+		}),
+		priority: 2,
+		completed: false,
+		completion_date: None,
+		alarm: Some(TaskAlarm {
+			time: TimeOfDay {
+				hour: 9,
+				minute: 30,
+			},
+			advance_days: 1,
+		}),
+		repeat: Some(RepeatInfo {
+			repeat_type: RepeatType::Weekly,
+			end_date: None,
+			frequency: 1,
+			repeat_on: 0x02,
+			start_of_week: 0,
+		}),
+		description: "Water the plants".to_string(),
+		note: "Not the cactus".to_string(),
+	}
+}
+
+#[test]
+fn read_classic_database() {
+	let database = ToDoDatabase::from_bytes(EXAMPLE_PDB).unwrap();
+	assert!(!database.is_tasks());
+
+	// The last record is deleted
+	let tasks = database.tasks().unwrap();
+	assert_eq!(tasks.len(), 9);
+
+	let (attributes, first) = &tasks[0];
+	assert_eq!(
+		database
+			.database
+			.app_info
+			.category_name(attributes.category)
+			.as_deref(),
+		Some("Personal")
+	);
+	assert_eq!(
+		first.description,
+		"Test personal P1 incomplete way-past-due"
+	);
+	assert_eq!(
+		first
+			.due_date
+			.and_then(|x| x.to_naive_date())
+			.unwrap()
+			.to_string(),
+		"2015-01-01"
+	);
+	assert_eq!(first.priority, 1);
+	assert!(!first.completed);
+
+	let (_, noted) = &tasks[6];
+	assert!(noted.completed);
+	assert_eq!(noted.due_date, None);
+	assert!(noted
+		.note
+		.starts_with("This to-do list entry has a note attached\n"));
+}
+
+#[test]
+fn classic_round_trip() {
+	let database = PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(EXAMPLE_PDB).unwrap();
+	for (_, data) in database.list_records_resources().iter() {
+		if data.is_empty() {
+			continue;
+		}
+
+		let record = ToDoRecord::from_bytes(&mut Cursor::new(data)).unwrap();
+		assert_eq!(&record.to_bytes().unwrap(), data);
+	}
+}
+
+#[test]
+fn task_round_trip() {
+	let data = task().to_bytes().unwrap();
+	let flags = TASKS_FLAG_DUE_DATE | TASKS_FLAG_ALARM | TASKS_FLAG_REPEAT;
+	assert_eq!(&data[..4], &[0xC8, 0xA6, 2, 0]);
+	assert_eq!(&data[4..6], &flags.to_be_bytes());
+	assert_eq!(&data[6..10], &[9, 30, 0, 1]);
+	assert_eq!(&data[10..18], &[2, 0, 0xFF, 0xFF, 1, 0x02, 0, 0]);
+	assert!(data.ends_with(b"Water the plants\0Not the cactus\0"));
+
+	let record = TaskRecord::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(record, task());
+}
+
+#[test]
+fn completed_task() {
+	let completed = TaskRecord {
+		due_date: None,
+		completed: true,
+		completion_date: Some(PackedDate {
+			year: 2004,
+			month: 5,
+			day: 7,
+		}),
+		alarm: None,
+		repeat: None,
+		..task()
+	};
+
+	let data = completed.to_bytes().unwrap();
+	assert_eq!(&data[..8], &[0xFF, 0xFF, 0x82, 0, 0x40, 0, 0xC8, 0xA7]);
+	assert_eq!(
+		TaskRecord::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		completed
+	);
+}
+
+#[test]
+fn classic_mapping() {
+	let classic = ToDoRecord::from(task());
+	assert_eq!(classic.due_date, task().due_date);
+	assert_eq!(classic.note, "Not the cactus");
+
+	let mapped = TaskRecord::from(classic);
+	assert_eq!(mapped.alarm, None);
+	assert_eq!(mapped.repeat, None);
+	assert_eq!(mapped.description, task().description);
+}
+
+#[test]
+fn read_tasks_database() {
+	let base = PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(EXAMPLE_PDB).unwrap();
+	let mut header = base.header;
+	header.creator_code = *TASKS_CREATOR_CODE;
+
+	let mut database = PalmDatabase::<PdbWithCategoriesDatabase>::new(header, base.app_info);
+	database.insert_record(RecordAttributes::default(), &task().to_bytes().unwrap());
+
+	let database = ToDoDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+	assert!(database.is_tasks());
+	assert_eq!(database.tasks().unwrap()[0].1, task());
+}
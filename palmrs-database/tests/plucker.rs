@@ -0,0 +1,268 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+use palmrs_database::plucker::{
+	tokenize,
+	PluckerDocument,
+	PluckerRecord,
+	PluckerRecordType,
+	PluckerToken,
+	CHARSET_UTF8,
+	PLUCKER_COMPRESSION_DOC,
+	PLUCKER_COMPRESSION_ZLIB,
+	PLUCKER_CREATOR_CODE,
+	PLUCKER_TYPE_CODE,
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{build_pdb, push_u16};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/tWmanual.pdb");
+
+/// Unique ID of the external link, which isn't part of the document
+const EXTERNAL_UID: u16 = 9;
+
+/// Build a data record from its header fields and data
+fn record(uid: u16, paragraphs: u16, record_type: u8, data: &[u8]) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for value in [uid, paragraphs, data.len() as u16].iter() {
+		push_u16(&mut buf, *value);
+	}
+
+	buf.push(record_type);
+	buf.push(0);
+	buf.extend_from_slice(data);
+	buf
+}
+
+/// Build a text record (compressed or not) from a list of paragraphs
+fn text_record(uid: u16, paragraphs: &[&[u8]], compress: Option<u16>) -> Vec<u8> {
+	let mut header = Vec::new();
+	let mut text = Vec::new();
+	for paragraph in paragraphs.iter() {
+		push_u16(&mut header, paragraph.len() as u16);
+		push_u16(&mut header, 0);
+		text.extend_from_slice(paragraph);
+	}
+
+	let record_type = match compress {
+		Some(PLUCKER_COMPRESSION_ZLIB) => {
+			let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+			encoder.write_all(&text).unwrap();
+			text = encoder.finish().unwrap();
+			1
+		}
+
+		// PalmDoc compressed data made up entirely of literal runs
+		Some(_) => {
+			text = text
+				.chunks(8)
+				.flat_map(|x| [&[x.len() as u8][..], x].concat())
+				.collect();
+			1
+		}
+
+		None => 0,
+	};
+
+	let mut data = header;
+	data.extend_from_slice(&text);
+	record(uid, paragraphs.len() as u16, record_type, &data)
+}
+
+/// Build a document with a home page, a compressed second page, an image, a mailto link, an
+/// external link, and metadata
+fn build_document(version: u16) -> Vec<u8> {
+	let mut index = Vec::new();
+	for value in [1, version, 2, 0, 2, 5, 8].iter() {
+		push_u16(&mut index, *value);
+	}
+
+	let home: [&[u8]; 2] = [
+		b"\x00\x11\x01Welcome\x00\x11\x00",
+		b"See \x00\x0A\x00\x03page two\x00\x08, \x00\x0A\x00\x09the web\x00\x08 or \x00\x0A\x00\x05mail\x00\x08.\x00\x38\x00\x40caf\xC3\xA9\x00\x48 \x00\x1A\x00\x04",
+	];
+	let second: [&[u8]; 1] =
+		[b"Page \x00\x83\x01\x00\x2B?two \x00\x0C\x00\x02\x00\x00back\x00\x08"];
+
+	// 8x2 monochrome version 0 bitmap
+	let mut image = Vec::new();
+	for value in [8, 2, 2, 0, 0, 0, 0, 0].iter() {
+		push_u16(&mut image, *value);
+	}
+	image.extend_from_slice(&[0xF0, 0x00, 0x0F, 0x00]);
+
+	let mut mailto = Vec::new();
+	for value in [8, 0, 28, 0].iter() {
+		push_u16(&mut mailto, *value);
+	}
+	mailto.extend_from_slice(b"someone@example.com\0Hello there\0");
+
+	let mut link_index = Vec::new();
+	push_u16(&mut link_index, EXTERNAL_UID);
+	push_u16(&mut link_index, 7);
+
+	let mut links = vec![0_u8; (EXTERNAL_UID - 1) as usize];
+	links.extend_from_slice(b"http://example.com/\0");
+
+	let mut metadata = Vec::new();
+	for value in [2, 1, 1, CHARSET_UTF8, 5, 6].iter() {
+		push_u16(&mut metadata, *value);
+	}
+	metadata.extend_from_slice(b"A Document\0\0");
+
+	let records = [
+		index,
+		text_record(2, &home, None),
+		text_record(3, &second, Some(version)),
+		record(4, 0, 2, &image),
+		record(5, 0, 4, &mailto),
+		record(6, 1, 5, &link_index),
+		record(7, EXTERNAL_UID, 6, &links),
+		record(8, 0, 10, &metadata),
+	];
+
+	build_pdb(PLUCKER_TYPE_CODE, PLUCKER_CREATOR_CODE, &records)
+}
+
+fn text(value: &str) -> PluckerToken {
+	PluckerToken::Text(value.to_string())
+}
+
+#[test]
+fn read_index_and_metadata() {
+	let document = PluckerDocument::from_bytes(&build_document(PLUCKER_COMPRESSION_ZLIB)).unwrap();
+	assert_eq!(document.index.version, PLUCKER_COMPRESSION_ZLIB);
+	assert_eq!(document.home(), Some(2));
+
+	let metadata = document.metadata().unwrap().unwrap();
+	assert_eq!(metadata.charset, Some(CHARSET_UTF8));
+	assert_eq!(metadata.title.as_deref(), Some("A Document"));
+	assert_eq!(document.title().unwrap(), "A Document");
+
+	let types = document
+		.raw_records()
+		.unwrap()
+		.iter()
+		.map(|(header, _)| header.record_type)
+		.collect::<Vec<_>>();
+	assert_eq!(types[1], PluckerRecordType::TextCompressed);
+	assert_eq!(types[6], PluckerRecordType::Metadata);
+}
+
+#[test]
+fn read_text_records() {
+	for version in [PLUCKER_COMPRESSION_DOC, PLUCKER_COMPRESSION_ZLIB].iter() {
+		let document = PluckerDocument::from_bytes(&build_document(*version)).unwrap();
+
+		let paragraphs = match document.record(2).unwrap() {
+			Some((_, PluckerRecord::Text(x))) => x,
+			other => panic!("unexpected record {:?}", other),
+		};
+		assert_eq!(paragraphs.len(), 2);
+		assert_eq!(
+			paragraphs[0].tokens,
+			vec![
+				PluckerToken::Style(1),
+				text("Welcome"),
+				PluckerToken::Style(0)
+			]
+		);
+		assert_eq!(
+			&paragraphs[1].tokens[..3],
+			&[text("See "), PluckerToken::PageLink(3), text("page two")]
+		);
+		assert!(paragraphs[1].tokens.contains(&text("café")));
+		assert_eq!(paragraphs[1].tokens.last(), Some(&PluckerToken::Image(4)));
+
+		let paragraphs = match document.record(3).unwrap() {
+			Some((_, PluckerRecord::Text(x))) => x,
+			other => panic!("unexpected record {:?}", other),
+		};
+		assert_eq!(
+			paragraphs[0].tokens,
+			vec![
+				text("Page +two "),
+				PluckerToken::ParagraphLink {
+					record: 2,
+					paragraph: 0
+				},
+				text("back"),
+				PluckerToken::LinkEnd,
+			]
+		);
+	}
+}
+
+#[test]
+fn read_links() {
+	let document = PluckerDocument::from_bytes(&build_document(PLUCKER_COMPRESSION_DOC)).unwrap();
+	assert_eq!(
+		document.url(EXTERNAL_UID).unwrap().as_deref(),
+		Some("http://example.com/")
+	);
+	assert_eq!(document.url(EXTERNAL_UID + 1).unwrap(), None);
+
+	let mailto = match document.record(5).unwrap() {
+		Some((_, PluckerRecord::Mailto(x))) => x,
+		other => panic!("unexpected record {:?}", other),
+	};
+	assert_eq!(mailto.to.as_deref(), Some("someone@example.com"));
+	assert_eq!(mailto.cc, None);
+	assert_eq!(
+		mailto.to_url(),
+		"mailto:someone@example.com?subject=Hello%20there"
+	);
+}
+
+#[test]
+fn export_html() {
+	let document = PluckerDocument::from_bytes(&build_document(PLUCKER_COMPRESSION_ZLIB)).unwrap();
+	let (html, images) = document.to_html().unwrap();
+
+	assert!(html.contains("<title>A Document</title>"));
+	assert!(html.contains("<p id=\"record-2-0\"><h1>Welcome</h1></p>"));
+	assert!(html.contains("<a href=\"#record-3\">page two</a>"));
+	assert!(html.contains("<a href=\"http://example.com/\">the web</a>"));
+	assert!(html.contains("<a href=\"mailto:someone@example.com?subject=Hello%20there\">mail</a>"));
+	assert!(html.contains("<br />\n<i>café</i> <img src=\"image-4.png\" />"));
+	assert!(html.contains("<a href=\"#record-2-0\">back</a>"));
+
+	// The home page comes first
+	assert!(html.find("record-2").unwrap() < html.find("<div id=\"record-3\"").unwrap());
+
+	assert_eq!(images.len(), 1);
+	assert_eq!(images[0].name, "image-4.png");
+	assert_eq!(&images[0].data[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn tokenize_function_codes() {
+	let tokens = tokenize(
+		b"a\x00\x85\x00\x00\x01\xF6\x00b\x00\x33\x02\x00\x32\x00\x0F\x01\x02\x03\x04\x05\x06\x07c",
+		None,
+	);
+
+	assert_eq!(
+		tokens,
+		vec![
+			text("a\u{1F600}b"),
+			PluckerToken::HorizontalRule {
+				height: 2,
+				width: 0,
+				percent: 50
+			},
+			PluckerToken::Unknown {
+				code: 0x0F,
+				arguments: vec![1, 2, 3, 4, 5, 6, 7],
+			},
+			text("c"),
+		]
+	);
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(PluckerDocument::from_bytes(EXAMPLE_PDB).is_err());
+}
@@ -0,0 +1,238 @@
+use palmrs_database::{
+	header::DatabaseHeader,
+	pqa::{
+		PqaDatabase,
+		PqaRecord,
+		PqaRecordHeader,
+		PqaToken,
+		COMPRESSION_LZ77,
+		COMPRESSION_NONE,
+		CONTENT_TYPE_CML,
+		CONTENT_TYPE_GIF,
+		CONTENT_TYPE_PALM_BITMAP,
+		PQA_CREATOR_CODE,
+		PQA_TYPE_CODE,
+	},
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{pdb_header, push_u16, push_u32};
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/tWmanual.pdb");
+
+/// CML page with a title, a heading, a link, and an image
+const PAGE: &[u8] = b"\x01Weather\x1F\x02\x01Today\x1F\x05forecast.cml\x00Sunny \xA9 1999\x1F\x04\x06\x00\x08\x00\x02sun.pbm\x00Sun\x00\x00trailing";
+
+/// LZ77 compressed CML page: "abc" three times, then the end of the page
+const COMPRESSED_PAGE: &[u8] = b"abc\x80\x1B\x01\x00";
+
+/// Append a launcher header field, padded to a whole number of words
+fn push_words(buf: &mut Vec<u8>, data: &[u8]) {
+	let mut data = data.to_vec();
+	if !data.len().is_multiple_of(2) {
+		data.push(0);
+	}
+
+	push_u16(buf, (data.len() / 2) as u16);
+	buf.extend_from_slice(&data);
+}
+
+/// A version 0 bitmap, 8x2 pixels, with a diagonal line
+fn icon() -> Vec<u8> {
+	let mut buf = Vec::new();
+	for value in [8, 2, 2, 0, 0, 0, 0, 0].iter() {
+		push_u16(&mut buf, *value);
+	}
+
+	buf.extend_from_slice(&[0x80, 0x00, 0x40, 0x00]);
+	buf
+}
+
+/// Build a PQA record holding the given file
+fn build_record(
+	url: &str,
+	content_type: u16,
+	compression_type: u16,
+	uncompressed_length: usize,
+	data: &[u8],
+) -> Vec<u8> {
+	let url_offset = PqaRecordHeader::SIZE;
+	let data_offset = url_offset + url.len() + 1;
+
+	let mut buf = Vec::new();
+	for value in [
+		url_offset,
+		url.len() + 1,
+		data_offset,
+		data.len(),
+		content_type as usize,
+		compression_type as usize,
+	]
+	.iter()
+	{
+		push_u16(&mut buf, *value as u16);
+	}
+
+	push_u32(&mut buf, uncompressed_length as u32);
+	buf.extend_from_slice(&[0, 0]);
+	buf.extend_from_slice(url.as_bytes());
+	buf.push(0);
+	buf.extend_from_slice(data);
+	buf
+}
+
+fn build_records() -> Vec<Vec<u8>> {
+	vec![
+		build_record(
+			"index.cml",
+			CONTENT_TYPE_CML,
+			COMPRESSION_NONE,
+			PAGE.len(),
+			PAGE,
+		),
+		build_record(
+			"forecast.cml",
+			CONTENT_TYPE_CML,
+			COMPRESSION_LZ77,
+			10,
+			COMPRESSED_PAGE,
+		),
+		build_record(
+			"sun.pbm",
+			CONTENT_TYPE_PALM_BITMAP,
+			COMPRESSION_NONE,
+			icon().len(),
+			&icon(),
+		),
+		build_record("logo.gif", CONTENT_TYPE_GIF, COMPRESSION_NONE, 6, b"GIF89a"),
+	]
+}
+
+fn build_pqa() -> Vec<u8> {
+	let records = build_records();
+
+	let mut app_info = b"lnch\x00\x03\x00\x00".to_vec();
+	push_words(&mut app_info, b"1.2\0");
+	push_words(&mut app_info, b"Weather\0");
+	push_words(&mut app_info, &icon());
+	push_words(&mut app_info, &[]);
+
+	let app_info_offset = DatabaseHeader::SIZE + 8 * records.len();
+	let mut record_offset = app_info_offset + app_info.len();
+
+	let mut header = pdb_header(PQA_TYPE_CODE, PQA_CREATOR_CODE);
+	header.app_info_id = app_info_offset as u32;
+	header.sort_info_id = 0;
+	header.record_count = records.len() as u16;
+
+	let mut buf = header.to_bytes().unwrap();
+	for (idx, record) in records.iter().enumerate() {
+		push_u32(&mut buf, record_offset as u32);
+		push_u32(&mut buf, idx as u32 + 1);
+		record_offset += record.len();
+	}
+
+	buf.extend_from_slice(&app_info);
+	for record in records.iter() {
+		buf.extend_from_slice(record);
+	}
+
+	buf
+}
+
+#[test]
+fn read_pqa_header() {
+	let pqa = PqaDatabase::from_bytes(&build_pqa()).unwrap();
+	assert_eq!(pqa.title(), "Weather");
+	assert_eq!(pqa.version(), "1.2");
+	assert_eq!(pqa.header.header_version, 3);
+	assert!(pqa.header.small_icon.is_empty());
+
+	let icon = pqa.icon_image().unwrap();
+	assert_eq!((icon.width(), icon.height()), (8, 2));
+	assert!(icon.get(0, 0) && icon.get(1, 1));
+	assert!(!icon.get(1, 0) && !icon.get(0, 1));
+}
+
+#[test]
+fn read_pqa_records() {
+	let pqa = PqaDatabase::from_bytes(&build_pqa()).unwrap();
+	assert_eq!(pqa.content(0), Some(build_records()[0].as_slice()));
+	assert!(pqa.content(4).is_none());
+
+	let record = pqa.record(0).unwrap().unwrap();
+	assert_eq!(record.url, "index.cml");
+	assert_eq!(record.header.content_type, CONTENT_TYPE_CML);
+	assert_eq!(record.data, PAGE);
+
+	let record = pqa.find_url("forecast.cml").unwrap().unwrap();
+	assert_eq!(record.decompress().unwrap(), b"abcabcabc\x00");
+	assert!(pqa.find_url("missing.cml").unwrap().is_none());
+}
+
+#[test]
+fn decode_cml_pages() {
+	let pqa = PqaDatabase::from_bytes(&build_pqa()).unwrap();
+	assert_eq!(
+		pqa.content_tokens(0).unwrap().unwrap(),
+		vec![
+			PqaToken::Title,
+			PqaToken::Text("Weather".to_string()),
+			PqaToken::End,
+			PqaToken::Heading(1),
+			PqaToken::Text("Today".to_string()),
+			PqaToken::End,
+			PqaToken::Link("forecast.cml".to_string()),
+			PqaToken::Text("Sunny \u{A9} 1999".to_string()),
+			PqaToken::End,
+			PqaToken::LineBreak,
+			PqaToken::Image {
+				width: 8,
+				height: 2,
+				url: "sun.pbm".to_string(),
+				alt: "Sun".to_string(),
+			},
+		]
+	);
+
+	assert_eq!(
+		pqa.content_tokens(1).unwrap().unwrap(),
+		vec![PqaToken::Text("abcabcabc".to_string())]
+	);
+
+	// Images aren't pages
+	assert!(pqa.content_tokens(2).is_err());
+	assert!(pqa.content_tokens(4).unwrap().is_none());
+}
+
+#[test]
+fn extract_pqa_images() {
+	let pqa = PqaDatabase::from_bytes(&build_pqa()).unwrap();
+	let images = pqa.images().unwrap();
+	assert_eq!(images.len(), 2);
+
+	assert_eq!(images[0].url, "sun.pbm");
+	assert_eq!(images[0].content_type, CONTENT_TYPE_PALM_BITMAP);
+	assert_eq!(&images[0].data[..8], b"\x89PNG\r\n\x1a\n");
+
+	assert_eq!(images[1].url, "logo.gif");
+	assert_eq!(images[1].data, b"GIF89a");
+}
+
+#[test]
+fn reject_bad_pqa_records() {
+	let mut record = build_record("index.cml", CONTENT_TYPE_CML, COMPRESSION_NONE, 4, b"Text");
+	record.truncate(record.len() - 1);
+	assert!(PqaRecord::from_bytes(&record).is_err());
+
+	// Decompressed length doesn't match the header
+	let record = build_record("index.cml", CONTENT_TYPE_CML, COMPRESSION_NONE, 5, b"Text");
+	let record = PqaRecord::from_bytes(&record).unwrap();
+	assert!(record.decompress().is_err());
+}
+
+#[test]
+fn reject_other_databases() {
+	assert!(PqaDatabase::from_bytes(EXAMPLE_PDB).is_err());
+}
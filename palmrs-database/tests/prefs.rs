@@ -0,0 +1,136 @@
+use chrono::{NaiveDate, NaiveTime, Weekday};
+use palmrs_database::{
+	prefs::{
+		DateFormat,
+		NumberFormat,
+		PreferencesDatabase,
+		TimeFormat,
+		PREFS_TYPE_CODE,
+		SYSTEM_PREFS_CREATOR_CODE,
+		SYSTEM_PREFS_ID,
+	},
+	PalmDatabase,
+	PrcDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PRC: &[u8] = include_bytes!("../../test-data/hello-v1.prc");
+
+/// Version 3 system preferences for a device in Germany, one hour east of UTC
+const SYSTEM_PREFS: &[u8] = &[
+	0x00, 0x03, // version
+	0x08, // country (Germany)
+	0x02, // date format (31.12.95)
+	0x09, // long date format (31. Dec 1995)
+	0x01, // week start day (Monday)
+	0x05, // time format (13.00)
+	0x01, // number format (1.000,00)
+	0x02, // auto-off duration
+	0x01, 0x01, 0x01, // sound levels
+	0x00, // hide secret records
+	0x00, // device locked
+	0x00, // reserved
+	0x00, 0x00, // flags
+	0x01, // battery kind
+	0x00, // reserved
+	0xFF, 0xFF, 0xFF, 0xC4, // minutes west of GMT (-60)
+	0x04, // daylight savings (middle European)
+	0x00, // reserved
+	0x00, 0x84, // ronamatic character
+	0xAB, 0xCD, // a later field
+];
+
+fn build_prefs() -> Vec<u8> {
+	let base = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let mut header = base.header;
+	header.type_code = *PREFS_TYPE_CODE;
+	header.creator_code = *SYSTEM_PREFS_CREATOR_CODE;
+
+	let mut database = PalmDatabase::<PrcDatabase>::new(header, base.app_info);
+	database
+		.insert_resource_with_id(SYSTEM_PREFS_CREATOR_CODE, SYSTEM_PREFS_ID, SYSTEM_PREFS)
+		.unwrap();
+	database
+		.insert_resource_with_id(b"ownr", 1, b"\x00\x01Jo Bloggs\x00\x00")
+		.unwrap();
+	database
+		.insert_resource_with_id(b"memo", 1, b"\x00\x02\x12\x34")
+		.unwrap();
+	database.insert_resource_with_id(b"bad!", 1, b"").unwrap();
+
+	database.to_bytes().unwrap()
+}
+
+#[test]
+fn read_system_preferences() {
+	let prefs = PreferencesDatabase::from_bytes(&build_prefs()).unwrap();
+	let system = prefs.system_preferences().unwrap().unwrap();
+
+	assert_eq!(system.version, 3);
+	assert_eq!(system.country_code(), Some("DE"));
+	assert_eq!(system.date_format, DateFormat::DmyWithDots);
+	assert_eq!(system.long_date_format, DateFormat::DmyLongWithDot);
+	assert_eq!(system.time_format, TimeFormat::Dot24h);
+	assert_eq!(system.number_format, NumberFormat::PeriodComma);
+	assert_eq!(system.week_start(), Weekday::Mon);
+	assert_eq!(system.daylight_savings, Some(4));
+	assert_eq!(system.ronamatic_char, Some(0x84));
+	assert_eq!(system.extra, vec![0xAB, 0xCD]);
+	assert_eq!(system.utc_offset().unwrap().local_minus_utc(), 3600);
+
+	let date = NaiveDate::from_ymd(2003, 1, 5);
+	assert_eq!(system.date_format.format(&date).unwrap(), "5.1.03");
+	assert_eq!(
+		system.long_date_format.format(&date).unwrap(),
+		"5. Jan 2003"
+	);
+	assert_eq!(
+		system
+			.time_format
+			.format(&NaiveTime::from_hms(13, 5, 0))
+			.unwrap(),
+		"13.05"
+	);
+	assert_eq!(
+		system.number_format.format(-1234567.891, 2),
+		"-1.234.567,89"
+	);
+}
+
+#[test]
+fn read_app_preferences() {
+	let prefs = PreferencesDatabase::from_bytes(&build_prefs()).unwrap();
+
+	// The empty resource has no version, so isn't listed
+	assert_eq!(prefs.app_preferences().len(), 3);
+
+	let memo = prefs.app_preference(b"memo", 1).unwrap();
+	assert_eq!(memo.creator_code_str(), Some("memo"));
+	assert_eq!(memo.version, 2);
+	assert_eq!(memo.data, vec![0x12, 0x34]);
+	assert!(prefs.app_preference(b"memo", 2).is_none());
+
+	assert_eq!(prefs.owner().as_deref(), Some("Jo Bloggs"));
+}
+
+#[test]
+fn format_helpers() {
+	let date = NaiveDate::from_ymd(1995, 12, 31);
+	assert_eq!(
+		DateFormat::MdyWithSlashes.format(&date).unwrap(),
+		"12/31/95"
+	);
+	assert_eq!(
+		DateFormat::MdyLongWithComma.format(&date).unwrap(),
+		"Dec 31, 1995"
+	);
+	assert_eq!(DateFormat::from(99).format(&date), None);
+
+	let time = NaiveTime::from_hms(13, 0, 0);
+	assert_eq!(TimeFormat::ColonAmPm.format(&time).unwrap(), "1:00 pm");
+	assert!(!TimeFormat::ColonAmPm.is_24_hour());
+
+	assert_eq!(NumberFormat::CommaPeriod.format(1000.0, 2), "1,000.00");
+	assert_eq!(NumberFormat::SpaceComma.format(999.5, 0), "1 000");
+	assert_eq!(NumberFormat::ApostrophePeriod.format(12.0, 0), "12");
+}
@@ -0,0 +1,164 @@
+use std::io::Cursor;
+
+use palmrs_database::{
+	image::RgbaImage,
+	resource::{
+		bitmap::{
+			system_palette,
+			BitmapFamily,
+			BITMAP_FLAG_COMPRESSED,
+			BITMAP_FLAG_HAS_TRANSPARENCY,
+		},
+		Resource,
+		ResourceType,
+	},
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{push_u16, push_u32};
+
+const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0xFF];
+const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0xFF];
+
+/// Build the common start of a bitmap header
+fn header(width: u16, height: u16, row_bytes: u16, flags: u16, depth: u8, version: u8) -> Vec<u8> {
+	let mut buf = Vec::new();
+	for value in [width, height, row_bytes, flags].iter() {
+		push_u16(&mut buf, *value);
+	}
+
+	buf.push(depth);
+	buf.push(version);
+	buf
+}
+
+fn decode(data: &[u8]) -> BitmapFamily {
+	BitmapFamily::from_bytes(&mut Cursor::new(data)).unwrap()
+}
+
+#[test]
+fn palette() {
+	assert_eq!(system_palette(0), [0xFF, 0xFF, 0xFF]);
+	assert_eq!(system_palette(1), [0xFF, 0xCC, 0xFF]);
+	assert_eq!(system_palette(6), [0xFF, 0xFF, 0xCC]);
+	assert_eq!(system_palette(36), [0xCC, 0xFF, 0xFF]);
+	assert_eq!(system_palette(215), [0x00, 0x00, 0x00]);
+	assert_eq!(system_palette(216), [0x11, 0x11, 0x11]);
+	assert_eq!(system_palette(230), [0x00, 0x80, 0x80]);
+	assert_eq!(system_palette(255), [0x00, 0x00, 0x00]);
+}
+
+#[test]
+fn indexed_8bpp() {
+	// Version 2, uncompressed, next bitmap offset 0
+	let mut data = header(2, 1, 2, 0, 8, 2);
+	data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+	data.extend_from_slice(&[0, 5]);
+
+	let image = decode(&data).to_image().unwrap();
+	assert_eq!(image.get(0, 0), WHITE);
+	assert_eq!(image.get(1, 0), [0xFF, 0x00, 0xFF, 0xFF]);
+}
+
+#[test]
+fn greyscale_rle() {
+	// Version 2, RLE compressed; the compressed size includes the size field
+	let mut data = header(4, 2, 2, BITMAP_FLAG_COMPRESSED, 4, 2);
+	data.extend_from_slice(&[0, 0, 0, 1, 0, 0]);
+	push_u16(&mut data, 4);
+	data.extend_from_slice(&[4, 0x0F]);
+
+	let family = decode(&data);
+	assert!(family.bitmaps[0].is_compressed());
+	assert_eq!(family.bitmaps[0].pixels().unwrap(), vec![0x0F; 4]);
+
+	let image = family.to_image().unwrap();
+	assert_eq!(image.get(0, 1), WHITE);
+	assert_eq!(image.get(1, 1), BLACK);
+}
+
+#[test]
+fn monochrome_scanline() {
+	// Version 1, scanline compressed: the second row only changes its second byte
+	let mut data = header(16, 2, 2, BITMAP_FLAG_COMPRESSED, 1, 1);
+	data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+	push_u16(&mut data, 7);
+	data.extend_from_slice(&[0xC0, 0xAA, 0x55, 0x40, 0xFF]);
+
+	let family = decode(&data);
+	assert_eq!(
+		family.bitmaps[0].pixels().unwrap(),
+		vec![0xAA, 0x55, 0xAA, 0xFF]
+	);
+
+	let mono = family.to_mono_image().unwrap();
+	assert!(mono.get(0, 0));
+	assert!(!mono.get(1, 0));
+	assert!(mono.get(15, 1));
+
+	let image = family.to_image().unwrap();
+	assert_eq!(image.get(0, 1), BLACK);
+	assert_eq!(image.get(1, 1), WHITE);
+}
+
+#[test]
+fn direct_color_packbits() {
+	// Version 3, PackBits compressed RGB565, with blue transparent
+	let mut data = header(
+		3,
+		1,
+		6,
+		BITMAP_FLAG_COMPRESSED | BITMAP_FLAG_HAS_TRANSPARENCY,
+		16,
+		3,
+	);
+	data.extend_from_slice(&[24, 2, 0, 2]);
+	push_u16(&mut data, 72);
+	push_u32(&mut data, 0x001F);
+	push_u32(&mut data, 0);
+	push_u32(&mut data, 6);
+	data.extend_from_slice(&[0x00, 0x07, 0xE0, 0xFF, 0x00, 0x1F]);
+
+	let image = decode(&data).to_image().unwrap();
+	assert_eq!(image.get(0, 0), [0x00, 0xFF, 0x00, 0xFF]);
+	assert_eq!(image.get(1, 0), [0x00, 0x00, 0xFF, 0x00]);
+	assert_eq!(image.get(2, 0), [0x00, 0x00, 0xFF, 0x00]);
+}
+
+#[test]
+fn family_prefers_deepest() {
+	// A version 1 monochrome bitmap, linked to a version 2 8-bit bitmap
+	let mut data = header(1, 1, 2, 0, 1, 1);
+	push_u16(&mut data, 5);
+	data.extend_from_slice(&[0, 0, 0, 0]);
+	data.extend_from_slice(&[0x80, 0x00, 0x00, 0x00]);
+
+	data.extend_from_slice(&header(1, 1, 2, 0, 8, 2));
+	data.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+	data.extend_from_slice(&[216, 0]);
+
+	let family = decode(&data);
+	assert_eq!(family.bitmaps.len(), 2);
+	assert_eq!(
+		family.to_image().unwrap().get(0, 0),
+		[0x11, 0x11, 0x11, 0xFF]
+	);
+
+	match Resource::from_type_code("Tbmp", 1000, &data).unwrap() {
+		Some(Resource::Bitmap(x)) => assert_eq!(x, family),
+		other => panic!("unexpected resource {:?}", other),
+	}
+}
+
+#[test]
+fn png_output() {
+	let mut image = RgbaImage::new(2, 1);
+	image.set(1, 0, BLACK);
+
+	let png = image.to_png();
+	assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+	assert_eq!(&png[12..16], b"IHDR");
+	assert_eq!(&png[16..24], &[0, 0, 0, 2, 0, 0, 0, 1]);
+	assert_eq!(&png[(png.len() - 12)..], b"\0\0\0\0IEND\xAE\x42\x60\x82");
+}
@@ -0,0 +1,178 @@
+use std::io::Cursor;
+
+use palmrs_database::{
+	record::DatabaseRecord,
+	resource::{
+		code::{CodeSegment, JumpTable},
+		data::{decompress_block, A5World, DataResource},
+		Resource,
+	},
+	PalmDatabase,
+	PrcDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PRC: &[u8] = include_bytes!("../../test-data/hello-v1.prc");
+
+fn find_resource<'a>(database: &'a PalmDatabase<PrcDatabase>, name: &str, id: u16) -> &'a [u8] {
+	database
+		.list_records_resources()
+		.iter()
+		.find(|(hdr, _)| hdr.name_str() == Some(name) && hdr.resource_id() == Some(id))
+		.map(|(_, data)| data.as_slice())
+		.unwrap()
+}
+
+#[test]
+fn read_jump_table() {
+	let database = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let jump_table =
+		JumpTable::from_bytes(&mut Cursor::new(find_resource(&database, "code", 0))).unwrap();
+
+	assert_eq!(jump_table.above_a5, 0x28);
+	assert_eq!(jump_table.below_a5, 0);
+	assert_eq!(jump_table.offset, 0x20);
+	assert_eq!(jump_table.entries.len(), 1);
+	assert_eq!(jump_table.entries[0].segment, 1);
+	assert!(jump_table.entries[0].well_formed);
+	assert_eq!(
+		jump_table.entry_at_a5_offset(0x20),
+		Some(&jump_table.entries[0])
+	);
+	assert_eq!(jump_table.entry_at_a5_offset(0x24), None);
+
+	// Segment 1 starts directly with code (`link a6,#-12`)
+	let segment =
+		CodeSegment::from_bytes(1, &mut Cursor::new(find_resource(&database, "code", 1))).unwrap();
+	assert_eq!(segment.header(&jump_table), None);
+	assert_eq!(&segment.code(&jump_table)[..4], &[0x4E, 0x56, 0xFF, 0xF4]);
+	assert_eq!(segment.entry_points(&jump_table), vec![(0, 0)]);
+}
+
+#[test]
+fn resource_dispatch_by_id() {
+	let database = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	for (hdr, data) in database.list_records_resources().iter() {
+		let resource = Resource::from_record(hdr, data).unwrap();
+		match (hdr.name_str().unwrap(), hdr.resource_id().unwrap()) {
+			("code", 0) => assert!(matches!(resource, Some(Resource::JumpTable(_)))),
+			("code", _) => assert!(matches!(resource, Some(Resource::Code(_)))),
+			("data", 0) => assert!(matches!(resource, Some(Resource::Data(_)))),
+			_ => assert_eq!(resource, None),
+		}
+	}
+}
+
+#[test]
+fn build_a5_world() {
+	let database = PalmDatabase::<PrcDatabase>::from_bytes(EXAMPLE_PRC).unwrap();
+	let world = A5World::from_database(&database).unwrap();
+
+	assert_eq!(world.memory.len(), 0x28);
+	assert_eq!(world.globals().len(), 0);
+	assert_eq!(
+		world.at_a5_offset(0x20, 8).unwrap(),
+		&[0x00, 0x00, 0x3F, 0x3C, 0x00, 0x01, 0xA9, 0xF0]
+	);
+
+	// Offsets outside the image, in either direction, don't wrap around
+	assert_eq!(world.at_a5_offset(-1, 1), None);
+	assert_eq!(world.at_a5_offset(i32::MIN, 4), None);
+	assert_eq!(world.at_a5_offset(0x20, usize::MAX), None);
+	assert_eq!(world.at_a5_offset(0x24, 8), None);
+
+	let data =
+		DataResource::from_bytes(&mut Cursor::new(find_resource(&database, "data", 0))).unwrap();
+	assert_eq!(data.blocks.len(), 3);
+	assert!(data.blocks.iter().all(|(_, block)| block.is_empty()));
+	assert!(data.data_relocations.is_empty());
+}
+
+#[test]
+fn decompress_data_block() {
+	let compressed: &[u8] = &[
+		0x82, b'a', b'b', b'c', // 3 literal bytes
+		0x41, // 2 zero bytes
+		0x21, 0x7E, // 3 repeats of 0x7E
+		0x10, // 1 0xFF byte
+		0x01, 0x12, 0x34, // 00 00 00 00 FF FF 12 34
+		0x03, 0x01, 0x02, 0x03, // A9 F0 00 00 01 02 00 03
+		0x00,
+	];
+
+	let block = decompress_block(&mut Cursor::new(compressed)).unwrap();
+	assert_eq!(
+		block,
+		vec![
+			b'a', b'b', b'c', 0x00, 0x00, 0x7E, 0x7E, 0x7E, 0xFF, 0x00, 0x00, 0x00, 0x00, 0xFF,
+			0xFF, 0x12, 0x34, 0xA9, 0xF0, 0x00, 0x00, 0x01, 0x02, 0x00, 0x03,
+		]
+	);
+}
+
+#[test]
+fn load_and_relocate_globals() {
+	let mut data = vec![0x00, 0x00, 0x00, 0x00];
+
+	// Block at A5-8: a pointer to A5-4, followed by "hi!\0"
+	data.extend_from_slice(&(-8_i32).to_be_bytes());
+	data.extend_from_slice(&[0x87, 0xFF, 0xFF, 0xFF, 0xFC, b'h', b'i', b'!', 0x00, 0x00]);
+
+	// Two empty blocks
+	data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00]);
+	data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00]);
+
+	// Relocation tables: one data relocation at A5-8
+	let relocations_offset = data.len() as u32;
+	data[0..4].copy_from_slice(&relocations_offset.to_be_bytes());
+	data.extend_from_slice(&1_u32.to_be_bytes());
+	data.push(0xFC);
+	data.extend_from_slice(&0_u32.to_be_bytes());
+	data.extend_from_slice(&0_u32.to_be_bytes());
+
+	let resource = DataResource::from_bytes(&mut Cursor::new(&data)).unwrap();
+	assert_eq!(resource.data_relocations, vec![-8]);
+
+	let jump_table = JumpTable {
+		above_a5: 0x20,
+		below_a5: 8,
+		offset: 0x20,
+		entries: Vec::new(),
+	};
+
+	let mut world = A5World::new(&jump_table).unwrap();
+	world.load_data(&resource).unwrap();
+	assert_eq!(
+		world.globals(),
+		&[0xFF, 0xFF, 0xFF, 0xFC, b'h', b'i', b'!', 0x00][..]
+	);
+
+	world.relocate(&resource, 0x1000, 0x8000).unwrap();
+	assert_eq!(&world.globals()[..4], &[0x00, 0x00, 0x0F, 0xFC]);
+}
+
+#[test]
+fn reject_oversized_resources() {
+	// A jump table claiming far more entries than the resource holds
+	let mut data = Vec::new();
+	for value in [0x20_u32, 8, 0xFFFF_FFF8, 0x20].iter() {
+		data.extend_from_slice(&value.to_be_bytes());
+	}
+	assert!(JumpTable::from_bytes(&mut Cursor::new(&data)).is_err());
+
+	// Relocation tables claiming far more entries than the resource holds
+	let mut data = 4_u32.to_be_bytes().to_vec();
+	data.extend_from_slice(&u32::MAX.to_be_bytes());
+	assert!(DataResource::from_bytes(&mut Cursor::new(&data)).is_err());
+
+	let mut jump_table = JumpTable {
+		above_a5: 0x20,
+		below_a5: u32::MAX,
+		offset: 0x20,
+		entries: Vec::new(),
+	};
+	assert!(A5World::new(&jump_table).is_err());
+
+	jump_table.below_a5 = 0x0100_0000;
+	assert!(A5World::new(&jump_table).is_err());
+}
@@ -0,0 +1,156 @@
+use palmrs_database::{
+	image::MonoImage,
+	record::pdb_record::PdbRecordHeader,
+	resource::{font::Font, Resource},
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{push_u16, push_u32};
+
+/// Standard-density strike rows for a font with `A`, `B` and the missing-character glyph
+const STRIKE_ROWS: [[u8; 2]; 3] = [[0x5E, 0x00], [0xF6, 0x00], [0xBE, 0x00]];
+
+/// Build the `FontType` header for a font containing `A` and `B`, 3 pixels tall
+fn push_header(buf: &mut Vec<u8>, font_type: u16, ow_t_loc: u16) {
+	for value in [font_type, 65, 66, 3, 0, 0, 3, 3, ow_t_loc, 2, 1, 1, 1].iter() {
+		push_u16(buf, *value);
+	}
+}
+
+/// Append the location and offset/width tables
+fn push_tables(buf: &mut Vec<u8>) {
+	for location in [0_u16, 3, 5, 7].iter() {
+		push_u16(buf, *location);
+	}
+
+	for entry in [0x0004_u16, 0x0003, 0x0003, 0xFFFF].iter() {
+		push_u16(buf, *entry);
+	}
+}
+
+/// Scale the standard-density strike up to double density
+fn double_strike() -> Vec<u8> {
+	let mut out = Vec::new();
+	for row in STRIKE_ROWS.iter() {
+		let mut doubled = 0_u32;
+		for bit in 0..16 {
+			if u16::from_be_bytes(*row) & (0x8000 >> bit) != 0 {
+				doubled |= 0xC000_0000 >> (bit * 2);
+			}
+		}
+
+		push_u32(&mut out, doubled);
+		push_u32(&mut out, doubled);
+	}
+
+	out
+}
+
+fn build_font() -> Vec<u8> {
+	let mut buf = Vec::new();
+	push_header(&mut buf, 0x9000, 12);
+	for row in STRIKE_ROWS.iter() {
+		buf.extend_from_slice(row);
+	}
+
+	push_tables(&mut buf);
+	buf
+}
+
+fn build_extended_font() -> Vec<u8> {
+	let mut buf = Vec::new();
+	push_header(&mut buf, 0x9200, 33);
+	push_u16(&mut buf, 0);
+	push_u16(&mut buf, 1);
+	push_u16(&mut buf, 2);
+	for (density, offset) in [(72_u16, 44_u32), (144, 50)].iter() {
+		push_u16(&mut buf, *density);
+		push_u32(&mut buf, *offset);
+	}
+
+	for row in STRIKE_ROWS.iter() {
+		buf.extend_from_slice(row);
+	}
+
+	buf.extend_from_slice(&double_strike());
+	push_tables(&mut buf);
+	buf
+}
+
+fn decode(name: &[u8; 4], data: &[u8]) -> Font {
+	let hdr = PdbRecordHeader::Resource {
+		name: *name,
+		record_id: 9000,
+		data_offset: 0,
+		data_len: None,
+	};
+
+	match Resource::from_record(&hdr, data).unwrap() {
+		Some(Resource::Font(font)) => font,
+		other => panic!("unexpected resource: {:?}", other),
+	}
+}
+
+fn rows(image: &MonoImage) -> Vec<String> {
+	(0..image.height())
+		.map(|y| {
+			(0..image.width())
+				.map(|x| if image.get(x, y) { '#' } else { '.' })
+				.collect()
+		})
+		.collect()
+}
+
+#[test]
+fn decode_font_metrics() {
+	let font = decode(b"NFNT", &build_font());
+	assert!(!font.is_extended());
+	assert_eq!(font.first_char, 65);
+	assert_eq!(font.last_char, 66);
+	assert_eq!(font.ascent, 2);
+	assert_eq!(font.line_height(), 4);
+	assert_eq!(font.densities(), vec![72]);
+	assert_eq!(font.glyphs.len(), 3);
+
+	let glyph = font.glyph(b'B').unwrap();
+	assert_eq!((glyph.location, glyph.image_width, glyph.width), (3, 2, 3));
+
+	// Characters outside the font use the missing-character glyph
+	assert_eq!(font.glyph(b'Z').unwrap().location, 5);
+	assert_eq!(font.string_width("AZ"), 7);
+}
+
+#[test]
+fn render_font_glyphs() {
+	let font = decode(b"NFNT", &build_font());
+
+	let image = font.render_glyph(b'A', 72).unwrap();
+	assert_eq!(rows(&image), vec![".#..", "###.", "#.#."]);
+
+	let image = font.render_string("AB", 72).unwrap();
+	assert_eq!(rows(&image), vec![".#..##.", "###.#..", "#.#.##."]);
+
+	assert!(font.render_glyph(b'A', 144).is_none());
+}
+
+#[test]
+fn decode_extended_font() {
+	let font = decode(b"nfnt", &build_extended_font());
+	assert!(font.is_extended());
+	assert_eq!(font.densities(), vec![72, 144]);
+	assert_eq!(font.strike(144).unwrap().row_bytes, 4);
+
+	let single = font.render_glyph(b'B', 72).unwrap();
+	let double = font.render_glyph(b'B', 144).unwrap();
+	assert_eq!((double.width(), double.height()), (6, 6));
+	for y in 0..double.height() {
+		for x in 0..double.width() {
+			assert_eq!(double.get(x, y), single.get(x / 2, y / 2));
+		}
+	}
+
+	let pbm = double.to_pbm();
+	assert!(pbm.starts_with(b"P4\n6 6\n"));
+	assert_eq!(pbm.len(), 7 + 6);
+}
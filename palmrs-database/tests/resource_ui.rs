@@ -0,0 +1,243 @@
+use palmrs_database::{
+	record::pdb_record::PdbRecordHeader,
+	resource::{
+		alert::AlertKind,
+		form::{ControlStyle, FormObject},
+		Resource,
+	},
+};
+use test_env_log::test;
+
+mod common;
+use self::common::{push_u16, push_u32};
+
+fn resource_header(name: &[u8; 4]) -> PdbRecordHeader {
+	PdbRecordHeader::Resource {
+		name: *name,
+		record_id: 1000,
+		data_offset: 0,
+		data_len: None,
+	}
+}
+
+fn push_rect(buf: &mut Vec<u8>, x: u16, y: u16, w: u16, h: u16) {
+	push_u16(buf, x);
+	push_u16(buf, y);
+	push_u16(buf, w);
+	push_u16(buf, h);
+}
+
+/// Build a `tFRM` the way PilRC lays it out: all object pointers zeroed, objects following the
+/// object list in order, and strings directly following their objects
+fn build_form() -> Vec<u8> {
+	let mut buf = Vec::new();
+
+	// WindowType
+	push_u16(&mut buf, 0);
+	push_u16(&mut buf, 0);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 0x2000);
+	push_rect(&mut buf, 0, 0, 160, 160);
+	push_rect(&mut buf, 0, 0, 0, 0);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 0);
+	push_u32(&mut buf, 0);
+	push_u32(&mut buf, 0);
+
+	// FormType
+	push_u16(&mut buf, 1000);
+	push_u16(&mut buf, 0x8000);
+	push_u16(&mut buf, 0);
+	push_u32(&mut buf, 0);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 0);
+	push_u16(&mut buf, 1001);
+	push_u16(&mut buf, 2000);
+	push_u16(&mut buf, 3000);
+	push_u16(&mut buf, 4);
+	push_u32(&mut buf, 0);
+	assert_eq!(buf.len(), 68);
+
+	// Object list
+	for kind in [9_u8, 1, 8, 2].iter() {
+		buf.push(*kind);
+		buf.push(0);
+		push_u32(&mut buf, 0);
+	}
+
+	// Title
+	push_rect(&mut buf, 0, 0, 160, 15);
+	push_u32(&mut buf, 0);
+	buf.extend_from_slice(b"Hello\0");
+
+	// Button
+	push_u16(&mut buf, 1001);
+	push_rect(&mut buf, 1, 147, 36, 12);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 0xC000);
+	buf.push(0);
+	buf.push(0);
+	buf.push(0);
+	buf.push(0);
+	buf.extend_from_slice(b"OK\0");
+
+	// Label
+	push_u16(&mut buf, 1002);
+	push_u16(&mut buf, 10);
+	push_u16(&mut buf, 20);
+	push_u16(&mut buf, 0x8000);
+	buf.push(1);
+	buf.push(0);
+	push_u32(&mut buf, 0);
+	buf.extend_from_slice(b"Caf\xE9\0");
+
+	// List
+	push_u16(&mut buf, 1003);
+	push_rect(&mut buf, 10, 40, 60, 33);
+	push_u16(&mut buf, 0x8000);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 2);
+	push_u16(&mut buf, 0);
+	push_u16(&mut buf, 0);
+	buf.push(0);
+	buf.push(0);
+	push_u32(&mut buf, 0);
+	push_u32(&mut buf, 0);
+	buf.extend_from_slice(b"One\0Two\0");
+
+	buf
+}
+
+fn build_menu_bar() -> Vec<u8> {
+	let mut buf = vec![0_u8; 26];
+	push_u16(&mut buf, 1);
+	push_u32(&mut buf, 0);
+
+	// MenuPullDownType
+	push_u32(&mut buf, 0);
+	push_rect(&mut buf, 2, 14, 80, 22);
+	push_u32(&mut buf, 0);
+	push_rect(&mut buf, 4, 0, 30, 12);
+	push_u32(&mut buf, 0);
+	push_u16(&mut buf, 3);
+	push_u32(&mut buf, 0);
+
+	// MenuItemType
+	for (id, cmd) in [(1000_u16, b'C'), (1001, 0), (1002, b'P')].iter() {
+		push_u16(&mut buf, *id);
+		buf.push(*cmd);
+		buf.push(0);
+		push_u32(&mut buf, 0);
+	}
+
+	buf.extend_from_slice(b"Edit\0Copy\0-\0Paste\0");
+	buf
+}
+
+#[test]
+fn decode_form() {
+	let data = build_form();
+	let resource = Resource::from_record(&resource_header(b"tFRM"), &data)
+		.unwrap()
+		.unwrap();
+
+	let form = match &resource {
+		Resource::Form(form) => form,
+		other => panic!("unexpected resource: {:?}", other),
+	};
+
+	assert_eq!(form.form_id, 1000);
+	assert_eq!(form.bounds.extent.x, 160);
+	assert_eq!(form.default_button, 1001);
+	assert_eq!(form.help_rsc_id, 2000);
+	assert_eq!(form.menu_rsc_id, 3000);
+	assert_eq!(form.title(), Some("Hello"));
+	assert_eq!(form.objects.len(), 4);
+
+	match &form.objects[1] {
+		FormObject::Control(control) => {
+			assert_eq!(control.id, 1001);
+			assert_eq!(control.style, ControlStyle::Button);
+			assert_eq!(control.bounds.top_left.y, 147);
+		}
+		other => panic!("unexpected object: {:?}", other),
+	}
+
+	assert_eq!(
+		resource.strings(),
+		vec!["Hello", "OK", "Caf\u{e9}", "One", "Two"]
+	);
+}
+
+#[test]
+fn decode_menu_bar() {
+	let data = build_menu_bar();
+	let resource = Resource::from_record(&resource_header(b"MBAR"), &data)
+		.unwrap()
+		.unwrap();
+
+	let menu_bar = match &resource {
+		Resource::MenuBar(menu_bar) => menu_bar,
+		other => panic!("unexpected resource: {:?}", other),
+	};
+
+	assert_eq!(menu_bar.menus.len(), 1);
+	let menu = &menu_bar.menus[0];
+	assert_eq!(menu.title, "Edit");
+	assert_eq!(menu.items[0].shortcut, Some('C'));
+	assert!(menu.items[1].is_separator());
+	assert_eq!(menu.items[2].item_id, 1002);
+	assert_eq!(menu.items[2].text, "Paste");
+
+	assert_eq!(resource.strings(), vec!["Edit", "Copy", "Paste"]);
+}
+
+#[test]
+fn decode_strings() {
+	let resource = Resource::from_record(&resource_header(b"tSTR"), b"Some help text\0")
+		.unwrap()
+		.unwrap();
+	assert_eq!(resource.strings(), vec!["Some help text"]);
+
+	let resource = Resource::from_record(&resource_header(b"tSTL"), b"Day \0\0\x02Mon\0Tue\0")
+		.unwrap()
+		.unwrap();
+	assert_eq!(resource.strings(), vec!["Day Mon", "Day Tue"]);
+}
+
+#[test]
+fn decode_alert() {
+	let mut data = Vec::new();
+	push_u16(&mut data, 1);
+	push_u16(&mut data, 0);
+	push_u16(&mut data, 2);
+	push_u16(&mut data, 1);
+	data.extend_from_slice(b"Delete\0Delete ^1?\0OK\0Cancel\0");
+
+	let resource = Resource::from_record(&resource_header(b"Talt"), &data)
+		.unwrap()
+		.unwrap();
+
+	match &resource {
+		Resource::Alert(alert) => {
+			assert_eq!(alert.kind, AlertKind::Confirmation);
+			assert_eq!(alert.default_button, 1);
+			assert_eq!(alert.buttons, vec!["OK", "Cancel"]);
+		}
+		other => panic!("unexpected resource: {:?}", other),
+	}
+}
+
+#[test]
+fn unknown_resources_not_decoded() {
+	let resource = Resource::from_record(&resource_header(b"zzzz"), &[]).unwrap();
+	assert_eq!(resource, None);
+
+	let record = PdbRecordHeader::Record {
+		attributes: Default::default(),
+		unique_id: 0,
+		data_offset: 0,
+		data_len: None,
+	};
+	assert_eq!(Resource::from_record(&record, b"\0").unwrap(), None);
+}
@@ -0,0 +1,85 @@
+use std::io::{Cursor, Read};
+
+use palmrs_database::{
+	header::DatabaseHeader,
+	record::pdb_record::RecordAttributes,
+	stream::{
+		is_stream_database,
+		read_stream,
+		write_stream,
+		write_stream_with_block_size,
+		StreamReader,
+		STREAM_BLOCK_SIZE,
+	},
+	PalmDatabase,
+	PdbDatabase,
+};
+use test_env_log::test;
+
+const EXAMPLE_PDB: &[u8] = include_bytes!("../../test-data/tWmanual.pdb");
+
+fn header() -> DatabaseHeader {
+	DatabaseHeader::from_bytes(&mut Cursor::new(EXAMPLE_PDB)).unwrap()
+}
+
+fn stream_data(len: usize) -> Vec<u8> {
+	(0..len).map(|x| (x % 251) as u8).collect()
+}
+
+#[test]
+fn write_and_read_stream() {
+	let data = stream_data(10_000);
+	let database = write_stream(header(), &mut Cursor::new(&data)).unwrap();
+	assert!(is_stream_database(&database.header));
+
+	let records = database.list_records_resources();
+	assert_eq!(records.len(), 3);
+	assert_eq!(&records[0].1[..4], b"DBLK");
+	assert_eq!(records[0].1.len(), STREAM_BLOCK_SIZE + 4);
+	assert_eq!(records[2].1.len(), 10_000 - (2 * STREAM_BLOCK_SIZE) + 4);
+
+	// Round trip through the on-disk format
+	let database = PalmDatabase::<PdbDatabase>::from_bytes(&database.to_bytes().unwrap()).unwrap();
+	assert_eq!(database.header.record_count, 3);
+	assert_eq!(read_stream(&database).unwrap(), data);
+}
+
+#[test]
+fn read_stream_in_small_pieces() {
+	let data = stream_data(100);
+	let database = write_stream_with_block_size(header(), &mut Cursor::new(&data), 7).unwrap();
+	assert_eq!(database.list_records_resources().len(), 15);
+
+	let mut reader = StreamReader::new(&database).unwrap();
+	assert_eq!(reader.len(), 100);
+
+	let mut out = Vec::new();
+	let mut buf = [0_u8; 3];
+	loop {
+		let count = reader.read(&mut buf).unwrap();
+		if count == 0 {
+			break;
+		}
+
+		assert!(count <= 3);
+		out.extend_from_slice(&buf[..count]);
+	}
+
+	assert_eq!(out, data);
+}
+
+#[test]
+fn empty_stream() {
+	let database = write_stream(header(), &mut Cursor::new(Vec::new())).unwrap();
+	assert!(database.list_records_resources().is_empty());
+	assert!(StreamReader::new(&database).unwrap().is_empty());
+	assert!(write_stream_with_block_size(header(), &mut Cursor::new(Vec::new()), 0).is_err());
+}
+
+#[test]
+fn reject_records_without_signature() {
+	let mut database = write_stream(header(), &mut Cursor::new(stream_data(10))).unwrap();
+	database.insert_record(RecordAttributes::default(), b"not a block");
+
+	assert!(StreamReader::new(&database).is_err());
+}
@@ -0,0 +1,684 @@
+//! Capturing and replaying sync sessions
+//!
+//! A [`CaptureStream`] wraps the desktop's end of a link (a serial port, an emulator socket, or a
+//! NetSync connection), and records everything sent and received over it, with timestamps, to a
+//! capture file. Captures can be decoded into their SLP, PADP, CMP, NetSync and DLP messages with
+//! [`decode`], or fed back to the desktop side of the protocol stack with a [`ReplayStream`], to
+//! reproduce a sync without the device it was captured from.
+//!
+//! Capture files start with a [`CaptureHeader`], followed by one [`CaptureRecord`] for each read
+//! from or write to the link.
+
+use core::convert::TryFrom;
+use std::{
+	collections::VecDeque,
+	io::{self, Cursor, Read, Write},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+	protocol::{
+		cmp::{CmpPacket, CmpPacketType},
+		dlp::{DlpRequest, DlpResponse},
+		netsync::{NetSyncConnection, NetSyncPacket},
+		padp::{PadpPacket, PadpPacketType, PADP_FLAG_FIRST, PADP_FLAG_LAST},
+		slp::{SlpConnection, SlpPacket, SlpPacketType},
+	},
+	serial::SerialLink,
+};
+
+/// Magic bytes at the start of a capture file
+pub const CAPTURE_MAGIC: &[u8; 8] = b"PALMCAP\0";
+
+/// Version of the capture file format written here
+pub const CAPTURE_VERSION: u16 = 1;
+
+/// Number of messages that make up the NetSync handshake, at the start of a NetSync session
+const NETSYNC_HANDSHAKE_MESSAGES: usize = 3;
+
+/// Kind of link a capture was made on, which decides how it's decoded
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CaptureLink {
+	/// SLP, PADP and CMP, over a serial port or emulator socket
+	Serial,
+
+	/// Network HotSync
+	NetSync,
+
+	Unknown(u8),
+}
+
+impl From<u8> for CaptureLink {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::Serial,
+			0x02 => Self::NetSync,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<CaptureLink> for u8 {
+	fn from(value: CaptureLink) -> Self {
+		match value {
+			CaptureLink::Serial => 0x01,
+			CaptureLink::NetSync => 0x02,
+			CaptureLink::Unknown(x) => x,
+		}
+	}
+}
+
+/// Direction of the data in a capture record
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CaptureDirection {
+	/// Sent by the desktop
+	ToDevice,
+
+	/// Received by the desktop
+	FromDevice,
+
+	Unknown(u8),
+}
+
+impl From<u8> for CaptureDirection {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::ToDevice,
+			0x02 => Self::FromDevice,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<CaptureDirection> for u8 {
+	fn from(value: CaptureDirection) -> Self {
+		match value {
+			CaptureDirection::ToDevice => 0x01,
+			CaptureDirection::FromDevice => 0x02,
+			CaptureDirection::Unknown(x) => x,
+		}
+	}
+}
+
+/// Header at the start of a capture file
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CaptureHeader {
+	pub link: CaptureLink,
+
+	/// When the capture started, in seconds since the Unix epoch
+	pub start_time: u64,
+}
+
+impl CaptureHeader {
+	/// Size of an encoded header, in bytes
+	pub const SIZE: usize = 20;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut magic = [0u8; 8];
+		rdr.read_exact(&mut magic)?;
+		if &magic != CAPTURE_MAGIC {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"not a capture file",
+			));
+		}
+
+		let version = rdr.read_u16::<BigEndian>()?;
+		if version != CAPTURE_VERSION {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("unsupported capture file version {}", version),
+			));
+		}
+
+		let link = CaptureLink::from(rdr.read_u8()?);
+		let _pad = rdr.read_u8()?;
+		let start_time = rdr.read_u64::<BigEndian>()?;
+
+		Ok(Self { link, start_time })
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(Self::SIZE));
+		cursor.write_all(CAPTURE_MAGIC)?;
+		cursor.write_u16::<BigEndian>(CAPTURE_VERSION)?;
+		cursor.write_u8(self.link.into())?;
+		cursor.write_u8(0)?;
+		cursor.write_u64::<BigEndian>(self.start_time)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A single read from, or write to, a captured link
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureRecord {
+	pub direction: CaptureDirection,
+
+	/// Time since the start of the capture, to the microsecond
+	pub timestamp: Duration,
+
+	pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let direction = CaptureDirection::from(rdr.read_u8()?);
+		let timestamp = Duration::from_micros(rdr.read_u64::<BigEndian>()?);
+		let size = rdr.read_u32::<BigEndian>()? as usize;
+
+		let remaining = rdr.get_ref().len().saturating_sub(rdr.position() as usize);
+		if size > remaining {
+			return Err(io::Error::new(
+				io::ErrorKind::UnexpectedEof,
+				"capture record is longer than the file",
+			));
+		}
+
+		let mut data = vec![0u8; size];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			direction,
+			timestamp,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(self.data.len() + 13));
+		cursor.write_u8(self.direction.into())?;
+		cursor.write_u64::<BigEndian>(
+			u64::try_from(self.timestamp.as_micros()).unwrap_or(u64::MAX),
+		)?;
+		cursor.write_u32::<BigEndian>(self.data.len() as u32)?;
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// The contents of a capture file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Capture {
+	pub header: CaptureHeader,
+	pub records: Vec<CaptureRecord>,
+}
+
+impl Capture {
+	/// Read a capture file
+	///
+	/// A record cut short at the end of the file (if the capture wasn't finished cleanly) is
+	/// dropped.
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let header = CaptureHeader::from_bytes(rdr)?;
+
+		let mut records = Vec::new();
+		while (rdr.position() as usize) < rdr.get_ref().len() {
+			match CaptureRecord::from_bytes(rdr) {
+				Ok(record) => records.push(record),
+				Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+				Err(e) => return Err(e),
+			}
+		}
+
+		Ok(Self { header, records })
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut data = self.header.to_bytes()?;
+		for record in self.records.iter() {
+			data.extend(record.to_bytes()?);
+		}
+
+		Ok(data)
+	}
+}
+
+/// Link wrapper that records everything sent and received over it to a capture file
+#[derive(Debug)]
+pub struct CaptureStream<T: Read + Write, W: Write> {
+	inner: T,
+	output: W,
+	start: Instant,
+}
+
+impl<T: Read + Write, W: Write> CaptureStream<T, W> {
+	/// Start capturing the given link, writing the capture to `output`
+	pub fn new(inner: T, mut output: W, link: CaptureLink) -> Result<Self, io::Error> {
+		let start_time = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map(|x| x.as_secs())
+			.unwrap_or(0);
+		output.write_all(&CaptureHeader { link, start_time }.to_bytes()?)?;
+
+		Ok(Self {
+			inner,
+			output,
+			start: Instant::now(),
+		})
+	}
+
+	pub fn get_ref(&self) -> &T {
+		&self.inner
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.inner
+	}
+
+	/// Stop capturing, returning the link and the capture output
+	pub fn into_inner(self) -> (T, W) {
+		(self.inner, self.output)
+	}
+
+	fn record(&mut self, direction: CaptureDirection, data: &[u8]) -> Result<(), io::Error> {
+		let record = CaptureRecord {
+			direction,
+			timestamp: self.start.elapsed(),
+			data: data.to_vec(),
+		};
+		self.output.write_all(&record.to_bytes()?)
+	}
+}
+
+impl<T: Read + Write, W: Write> Read for CaptureStream<T, W> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = self.inner.read(buf)?;
+		if len > 0 {
+			self.record(CaptureDirection::FromDevice, &buf[..len])?;
+		}
+
+		Ok(len)
+	}
+}
+
+impl<T: Read + Write, W: Write> Write for CaptureStream<T, W> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let len = self.inner.write(buf)?;
+		if len > 0 {
+			self.record(CaptureDirection::ToDevice, &buf[..len])?;
+		}
+
+		Ok(len)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.inner.flush()?;
+		self.output.flush()
+	}
+}
+
+impl<T: SerialLink, W: Write> SerialLink for CaptureStream<T, W> {
+	fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), io::Error> {
+		self.inner.set_baud_rate(baud_rate)
+	}
+
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+		self.inner.set_read_timeout(timeout)
+	}
+}
+
+/// Link that plays back the device's side of a capture
+///
+/// Reads return the data the device sent, in the order it was captured, with no delays, and
+/// then the end of the stream. Writes are compared against what the desktop sent in the capture,
+/// to spot where the replayed sync went differently.
+#[derive(Debug, Clone)]
+pub struct ReplayStream {
+	incoming: VecDeque<Vec<u8>>,
+	expected: Vec<u8>,
+	written: usize,
+	mismatch: Option<usize>,
+}
+
+impl ReplayStream {
+	pub fn new(capture: &Capture) -> Self {
+		let incoming = capture
+			.records
+			.iter()
+			.filter(|x| x.direction == CaptureDirection::FromDevice)
+			.map(|x| x.data.clone())
+			.collect();
+		let expected = capture
+			.records
+			.iter()
+			.filter(|x| x.direction == CaptureDirection::ToDevice)
+			.flat_map(|x| x.data.iter().copied())
+			.collect();
+
+		Self {
+			incoming,
+			expected,
+			written: 0,
+			mismatch: None,
+		}
+	}
+
+	/// Offset of the first byte written that differs from the capture, if any
+	///
+	/// Writing more than was captured counts as a difference.
+	pub fn mismatch(&self) -> Option<usize> {
+		self.mismatch
+	}
+
+	/// Whether all of the device's data has been read
+	pub fn is_finished(&self) -> bool {
+		self.incoming.is_empty()
+	}
+}
+
+impl Read for ReplayStream {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let chunk = match self.incoming.front_mut() {
+			Some(x) => x,
+			None => return Ok(0),
+		};
+
+		let len = buf.len().min(chunk.len());
+		buf[..len].copy_from_slice(&chunk[..len]);
+		chunk.drain(..len);
+		if chunk.is_empty() {
+			self.incoming.pop_front();
+		}
+
+		Ok(len)
+	}
+}
+
+impl Write for ReplayStream {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.mismatch.is_none() {
+			let expected = self.expected.iter().skip(self.written);
+			self.mismatch = buf
+				.iter()
+				.zip(expected)
+				.position(|(a, b)| a != b)
+				.or_else(|| {
+					let available = self.expected.len().saturating_sub(self.written);
+					Some(available).filter(|x| *x < buf.len())
+				})
+				.map(|x| self.written + x);
+		}
+		self.written += buf.len();
+
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Baud rate changes and read timeouts don't mean anything for a replay
+impl SerialLink for ReplayStream {
+	fn set_baud_rate(&mut self, _baud_rate: u32) -> Result<(), io::Error> {
+		Ok(())
+	}
+
+	fn set_read_timeout(&mut self, _timeout: Option<Duration>) -> Result<(), io::Error> {
+		Ok(())
+	}
+}
+
+/// A packet or message decoded from a capture
+#[derive(Debug, Clone, PartialEq)]
+pub enum CaptureMessage {
+	Slp(SlpPacket),
+	Padp(PadpPacket),
+	NetSync(NetSyncPacket),
+
+	/// One of the messages exchanged before DLP starts on a NetSync connection
+	NetSyncHandshake(Vec<u8>),
+
+	Cmp(CmpPacket),
+	DlpRequest(DlpRequest),
+	DlpResponse(DlpResponse),
+
+	/// A message that couldn't be decoded
+	Unknown(Vec<u8>),
+}
+
+impl CaptureMessage {
+	/// Whether this is a link-level packet, rather than a whole message
+	pub fn is_packet(&self) -> bool {
+		matches!(self, Self::Slp(_) | Self::Padp(_) | Self::NetSync(_))
+	}
+}
+
+/// A packet or message decoded from a capture, with where it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaptureEvent {
+	/// Index of the capture record that completed the packet or message
+	pub record: usize,
+
+	pub timestamp: Duration,
+	pub direction: CaptureDirection,
+	pub message: CaptureMessage,
+}
+
+/// Reader over the data of the records in one direction, keeping track of the last record read
+struct RecordReader<'a> {
+	records: &'a [CaptureRecord],
+	direction: CaptureDirection,
+	index: usize,
+	offset: usize,
+
+	/// Index of the record the last data read came from
+	current: usize,
+}
+
+impl<'a> RecordReader<'a> {
+	fn new(records: &'a [CaptureRecord], direction: CaptureDirection) -> Self {
+		Self {
+			records,
+			direction,
+			index: 0,
+			offset: 0,
+			current: 0,
+		}
+	}
+}
+
+impl Read for RecordReader<'_> {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		while let Some(record) = self.records.get(self.index) {
+			if record.direction != self.direction || self.offset >= record.data.len() {
+				self.index += 1;
+				self.offset = 0;
+				continue;
+			}
+
+			let len = buf.len().min(record.data.len() - self.offset);
+			buf[..len].copy_from_slice(&record.data[self.offset..self.offset + len]);
+			self.offset += len;
+			self.current = self.index;
+			return Ok(len);
+		}
+
+		Ok(0)
+	}
+}
+
+impl Write for RecordReader<'_> {
+	fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+		Err(io::Error::other("captures can't be written to"))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+/// Decode the link-level packets sent in one direction
+fn decode_packets(capture: &Capture, direction: CaptureDirection) -> Vec<CaptureEvent> {
+	let reader = RecordReader::new(&capture.records, direction);
+	let mut events = Vec::new();
+	let mut event = |reader: &RecordReader, message| {
+		events.push(CaptureEvent {
+			record: reader.current,
+			timestamp: capture.records[reader.current].timestamp,
+			direction,
+			message,
+		})
+	};
+
+	match capture.header.link {
+		CaptureLink::NetSync => {
+			let mut connection = NetSyncConnection::new(reader);
+			while let Ok(packet) = connection.read_packet() {
+				event(connection.get_ref(), CaptureMessage::NetSync(packet));
+			}
+		}
+		_ => {
+			let mut connection = SlpConnection::new(reader);
+			while let Ok(packet) = connection.read_packet() {
+				event(connection.get_ref(), CaptureMessage::Slp(packet));
+			}
+		}
+	}
+
+	events
+}
+
+/// Decodes messages, once they've been reassembled from the link-level packets
+#[derive(Default)]
+struct MessageDecoder {
+	link_messages: usize,
+	cmp_done: bool,
+}
+
+impl MessageDecoder {
+	fn decode(
+		&mut self,
+		link: CaptureLink,
+		direction: CaptureDirection,
+		data: Vec<u8>,
+	) -> CaptureMessage {
+		self.link_messages += 1;
+		if link == CaptureLink::NetSync && self.link_messages <= NETSYNC_HANDSHAKE_MESSAGES {
+			return CaptureMessage::NetSyncHandshake(data);
+		}
+
+		// CMP comes first on a serial link, until the desktop accepts (or aborts) the session
+		if link != CaptureLink::NetSync && !self.cmp_done {
+			if let Ok(packet) = CmpPacket::from_bytes(&mut Cursor::new(&data)) {
+				self.cmp_done = matches!(
+					packet.packet_type,
+					CmpPacketType::Init | CmpPacketType::Abort
+				);
+				return CaptureMessage::Cmp(packet);
+			}
+		}
+
+		let decoded = match direction {
+			CaptureDirection::ToDevice => {
+				DlpRequest::from_bytes(&mut Cursor::new(&data)).map(CaptureMessage::DlpRequest)
+			}
+			_ => DlpResponse::from_bytes(&mut Cursor::new(&data)).map(CaptureMessage::DlpResponse),
+		};
+
+		decoded.unwrap_or(CaptureMessage::Unknown(data))
+	}
+}
+
+/// Message being reassembled from PADP fragments, in one direction
+#[derive(Default)]
+struct PadpAssembly {
+	message: Option<(u8, usize, Vec<u8>)>,
+	last: Option<(u8, u8, u32)>,
+}
+
+impl PadpAssembly {
+	/// Add a fragment, returning the message if it's now complete
+	fn add(&mut self, transaction_id: u8, packet: &PadpPacket) -> Option<Vec<u8>> {
+		if packet.packet_type != PadpPacketType::Data {
+			return None;
+		}
+
+		// Fragments sent again, because their ack went missing
+		let key = (transaction_id, packet.flags, packet.size);
+		if self.last == Some(key) {
+			return None;
+		}
+		self.last = Some(key);
+
+		if packet.flags & PADP_FLAG_FIRST != 0 {
+			self.message = Some((transaction_id, packet.size as usize, Vec::new()));
+		}
+
+		let (id, size, data) = self.message.as_mut()?;
+		if *id != transaction_id {
+			return None;
+		}
+
+		data.extend_from_slice(&packet.data);
+		if packet.flags & PADP_FLAG_LAST != 0 || data.len() >= *size {
+			let (_, size, mut data) = self.message.take()?;
+			data.truncate(size);
+			return Some(data);
+		}
+
+		None
+	}
+}
+
+/// Decode a capture into its packets and messages, in the order they were sent
+///
+/// Each message is preceded by the link-level packets it was carried in. Data that isn't part
+/// of a valid packet is skipped.
+pub fn decode(capture: &Capture) -> Vec<CaptureEvent> {
+	let mut packets = decode_packets(capture, CaptureDirection::ToDevice);
+	packets.extend(decode_packets(capture, CaptureDirection::FromDevice));
+	packets.sort_by_key(|x| x.record);
+
+	let link = capture.header.link;
+	let mut decoder = MessageDecoder::default();
+	let mut to_device = PadpAssembly::default();
+	let mut from_device = PadpAssembly::default();
+	let mut events = Vec::new();
+
+	for event in packets {
+		let message = match &event.message {
+			CaptureMessage::NetSync(packet) => Some(packet.data.clone()),
+			CaptureMessage::Slp(slp) if slp.packet_type == SlpPacketType::Padp => {
+				match PadpPacket::from_bytes(&mut Cursor::new(&slp.data)) {
+					Ok(packet) => {
+						let assembly = match event.direction {
+							CaptureDirection::ToDevice => &mut to_device,
+							_ => &mut from_device,
+						};
+						let message = assembly.add(slp.transaction_id, &packet);
+
+						events.push(event.clone());
+						events.push(CaptureEvent {
+							message: CaptureMessage::Padp(packet),
+							..event.clone()
+						});
+						if let Some(data) = message {
+							events.push(CaptureEvent {
+								message: decoder.decode(link, event.direction, data),
+								..event
+							});
+						}
+						continue;
+					}
+					Err(_) => None,
+				}
+			}
+			_ => None,
+		};
+
+		events.push(event.clone());
+		if let Some(data) = message {
+			events.push(CaptureEvent {
+				message: decoder.decode(link, event.direction, data),
+				..event
+			});
+		}
+	}
+
+	events
+}
@@ -38,6 +38,16 @@
 //! CONDUIT_TEST__HELLO_WORLD="Hello, world!"
 //! ```
 //!
+//! ### Requirements
+//!
+//! Before the sync, each conduit is run with the `--palmrs-dump-requirements` argument, and prints
+//! the databases it needs from the device as TOML (which [`WithinConduit`] takes care of):
+//!
+//! ```toml
+//! [conduit-requirements]
+//! databases = ["ToDoDB"]
+//! ```
+//!
 //! ### Local data
 //!
 //! Conduits are free to store whatever data they would like, in whatever format they would like,
@@ -67,10 +77,12 @@ use core::{
 	cmp::PartialEq,
 	default::Default,
 	fmt::{self, Debug, Display},
+	str::FromStr,
 };
-use std::{collections::HashMap, env, ffi::OsString, path::PathBuf};
+use std::{collections::HashMap, env, ffi::OsString, io, path::PathBuf};
 
-use subprocess::{self, Popen, PopenConfig, Redirection};
+use serde::{Deserialize, Serialize};
+use subprocess::{self, Popen, PopenConfig, PopenError, Redirection};
 
 use crate::SyncMode;
 
@@ -78,7 +90,8 @@ mod within;
 pub use self::within::{WithinConduit, WithinConduitConfig};
 
 /// Container (with builder API) for sync conduit requirements
-#[derive(Debug, Clone, PartialEq, Default)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ConduitRequirements {
 	pub databases: Vec<String>,
 }
@@ -99,11 +112,31 @@ impl ConduitRequirements {
 	}
 }
 
+/// Layout of the requirements a conduit prints when run with `--palmrs-dump-requirements`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct RequirementsDocument {
+	#[serde(rename = "conduit-requirements", default)]
+	requirements: ConduitRequirements,
+}
+
+/// Requirements are written as TOML
 impl Display for ConduitRequirements {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-		writeln!(f, "[conduit-requirements]")?;
-		writeln!(f, "databases = {:?}", &self.databases)?;
-		Ok(())
+		let document = RequirementsDocument {
+			requirements: self.clone(),
+		};
+		f.write_str(&toml::to_string(&document).map_err(|_| fmt::Error)?)
+	}
+}
+
+impl FromStr for ConduitRequirements {
+	type Err = String;
+
+	/// Parse requirements, as printed by a conduit run with `--palmrs-dump-requirements`
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		toml::from_str::<RequirementsDocument>(s)
+			.map(|x| x.requirements.finish())
+			.map_err(|e| e.to_string())
 	}
 }
 
@@ -206,4 +239,36 @@ impl ConduitHandler {
 
 		Popen::create(&self.make_argv(), config)
 	}
+
+	/// Ask the conduit which databases it needs, by running it with `--palmrs-dump-requirements`
+	pub fn requirements(&self) -> Result<ConduitRequirements, io::Error> {
+		let mut argv = self.make_argv();
+		argv.push("--palmrs-dump-requirements".into());
+		let config = PopenConfig {
+			stdout: Redirection::Pipe,
+			env: Some(self.make_environment()),
+			..Default::default()
+		};
+
+		let to_io_error = |e: PopenError| match e {
+			PopenError::IoError(e) => e,
+			e => io::Error::other(e),
+		};
+		let mut popen = Popen::create(&argv, config).map_err(to_io_error)?;
+		let (output, _) = popen.communicate(None)?;
+		let status = popen.wait().map_err(to_io_error)?;
+		if !status.success() {
+			return Err(io::Error::other(format!(
+				"conduit {} failed to report its requirements: {:?}",
+				self.conduit_name, status
+			)));
+		}
+
+		output.unwrap_or_default().parse().map_err(|e| {
+			io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("bad conduit requirements: {}", e),
+			)
+		})
+	}
 }
@@ -0,0 +1,218 @@
+//! Sync configuration
+//!
+//! The configuration is a TOML file, with a `[[device]]` table for each device to sync, matched
+//! by the user ID or user name set on the device. For example:
+//!
+//! ```toml
+//! # Where to keep data for devices that don't set their own `data_root` (each device gets a
+//! # subdirectory named after its user)
+//! data_root = "/home/user/Documents/palm.rs"
+//!
+//! [[device]]
+//! user_name = "Jo Bloggs"
+//! user_id = 0x1234
+//! data_root = "/home/user/palm/jo"
+//! backup = false
+//! include = ["MemoDB"]
+//! exclude = ["Graffiti*"]
+//!
+//! [[device.conduit]]
+//! name = "palmrs-conduit-todotxt"
+//! sync_mode = "merge"
+//!
+//! [device.conduit.config]
+//! todo_file = "todo.txt"
+//! ```
+//!
+//! Each device's conduits are run in the order they're listed, with the given sync mode (merge,
+//! if it isn't set). Their `config` values are passed to them as environment variables, as
+//! described in the [`conduit`][crate::conduit] module. Relative paths are relative to the
+//! directory the configuration file is in.
+
+use std::{
+	collections::BTreeMap,
+	ffi::OsString,
+	fs,
+	io,
+	path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+
+use crate::{
+	conduit::ConduitHandler,
+	hotsync::{user_directory_name, HotSync},
+	protocol::dlp::info::DlpUserInfo,
+	SyncMode,
+};
+
+fn default_true() -> bool {
+	true
+}
+
+/// Configuration for a single conduit, on a single device
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConduitConfig {
+	/// Name of the conduit executable
+	pub name: String,
+
+	#[serde(default)]
+	pub sync_mode: SyncMode,
+
+	/// Whether to run the conduit
+	#[serde(default = "default_true")]
+	pub enabled: bool,
+
+	/// Conduit-specific settings
+	#[serde(default)]
+	pub config: BTreeMap<String, toml::Value>,
+}
+
+impl ConduitConfig {
+	/// Build the handler to call the conduit with
+	///
+	/// String settings are passed as they are, and anything else as it's written in TOML.
+	pub fn handler(&self) -> ConduitHandler {
+		let mut handler = ConduitHandler::new(&self.name, self.sync_mode);
+		for (key, value) in self.config.iter() {
+			let value = match value {
+				toml::Value::String(x) => x.clone(),
+				x => x.to_string(),
+			};
+			handler
+				.conduit_config
+				.insert(OsString::from(key), OsString::from(value));
+		}
+
+		handler
+	}
+}
+
+/// Configuration for a single device
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceConfig {
+	/// User ID set on the device, which takes priority over the user name when matching
+	pub user_id: Option<u32>,
+
+	/// User name set on the device
+	pub user_name: Option<String>,
+
+	/// Directory to keep this device's data in
+	pub data_root: Option<PathBuf>,
+
+	/// Pull every database from the device, rather than only the ones the conduits need
+	#[serde(default)]
+	pub backup: bool,
+
+	/// Databases to pull even if no conduit needs them (`*` matches any run of characters)
+	#[serde(default)]
+	pub include: Vec<String>,
+
+	/// Databases to never pull or push
+	#[serde(default)]
+	pub exclude: Vec<String>,
+
+	#[serde(default, rename = "conduit")]
+	pub conduits: Vec<ConduitConfig>,
+}
+
+impl DeviceConfig {
+	/// Whether this is the configuration for the given user
+	pub fn matches(&self, user: &DlpUserInfo) -> bool {
+		match (self.user_id, &self.user_name) {
+			(Some(user_id), _) if user.user_id != 0 => user_id == user.user_id,
+			(_, Some(user_name)) => *user_name == user.user_name,
+			_ => false,
+		}
+	}
+
+	/// Find the configuration for a conduit
+	pub fn conduit(&self, name: &str) -> Option<&ConduitConfig> {
+		self.conduits.iter().find(|x| x.name == name)
+	}
+}
+
+/// Sync configuration, for all devices
+#[derive(Debug, Clone, PartialEq, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyncConfig {
+	/// Directory to keep data in, for devices that don't set their own
+	pub data_root: Option<PathBuf>,
+
+	#[serde(default, rename = "device")]
+	pub devices: Vec<DeviceConfig>,
+}
+
+impl SyncConfig {
+	/// Parse a configuration from TOML
+	pub fn from_toml(s: &str) -> Result<Self, io::Error> {
+		toml::from_str(s).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+	}
+
+	/// Load a configuration file, resolving the relative paths in it
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+		let mut config = Self::from_toml(&fs::read_to_string(path.as_ref())?)?;
+
+		let base = path.as_ref().parent().unwrap_or_else(|| Path::new(""));
+		let resolve = |x: &mut Option<PathBuf>| {
+			if let Some(path) = x.as_mut() {
+				*path = base.join(&*path);
+			}
+		};
+		resolve(&mut config.data_root);
+		for device in config.devices.iter_mut() {
+			resolve(&mut device.data_root);
+		}
+
+		Ok(config)
+	}
+
+	/// Find the configuration for the given user
+	pub fn device(&self, user: &DlpUserInfo) -> Option<&DeviceConfig> {
+		self.devices.iter().find(|x| x.matches(user))
+	}
+
+	/// Directory to keep a device's data in
+	///
+	/// Without one set for the device, this is a directory named after the user within the
+	/// configuration's data root, or within `default_root` if there isn't one.
+	pub fn data_root(
+		&self,
+		device: Option<&DeviceConfig>,
+		user: &DlpUserInfo,
+		default_root: &Path,
+	) -> PathBuf {
+		match device.and_then(|x| x.data_root.as_ref()) {
+			Some(path) => path.clone(),
+			None => self
+				.data_root
+				.as_deref()
+				.unwrap_or(default_root)
+				.join(user_directory_name(user)),
+		}
+	}
+
+	/// Set up the sync for the given user
+	///
+	/// Users without a configuration are synced without any conduits.
+	pub fn hotsync(&self, user: &DlpUserInfo, default_root: &Path) -> HotSync {
+		let device = self.device(user);
+		let mut hotsync = HotSync::new(self.data_root(device, user, default_root));
+
+		if let Some(device) = device {
+			hotsync.backup = device.backup;
+			hotsync.include = device.include.clone();
+			hotsync.exclude = device.exclude.clone();
+			hotsync.conduits = device
+				.conduits
+				.iter()
+				.filter(|x| x.enabled)
+				.map(|x| x.handler())
+				.collect();
+		}
+
+		hotsync
+	}
+}
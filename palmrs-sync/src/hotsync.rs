@@ -0,0 +1,427 @@
+//! Running a HotSync
+//!
+//! Once a device has connected, [`HotSync::sync`] runs the desktop side of the whole sync:
+//!
+//! 1. The databases the conduits need (or all of them, for a backup), along with any that are
+//!    explicitly included, are pulled from the device into the device data directory, which is
+//!    passed to conduits as `PALMRS_DATA_DEVICE`. Excluded databases are never pulled or pushed.
+//! 2. Each conduit is run in turn, with [`ConduitHandler::popen`].
+//! 3. Databases the conduits changed or added are pushed back to the device.
+//! 4. The sync flags of each database needed by a conduit are reset, so that the next sync only
+//!    sees the changes made after this one, as long as every conduit that needs it worked.
+//!    Databases that were only pulled for a backup or an include are left alone.
+//! 5. The outcome is added to the device's sync log, and the sync is ended.
+//!
+//! Changes made by a conduit that fails, or that runs in `keep-device` mode (where it isn't
+//! allowed to change the device's data), are undone before the next conduit runs.
+
+use std::{
+	collections::{BTreeMap, BTreeSet},
+	env,
+	fs,
+	io::{self, Cursor},
+	path::{Path, PathBuf},
+};
+
+use chrono::{Datelike, Local, Timelike};
+use palmrs_database::{header::DatabaseHeader, PalmDatabase, PdbDatabase, PrcDatabase};
+use subprocess::Redirection;
+
+use crate::{
+	conduit::ConduitHandler,
+	protocol::{
+		dlp::{
+			client::{DlpClient, DlpSyncStatus, DLP_OPEN_READ_WRITE},
+			info::{
+				DlpDatabaseInfo,
+				DlpUserInfo,
+				DlpUserInfoUpdate,
+				DLP_DB_LIST_RAM,
+				DLP_USER_INFO_MODIFIED_SYNC_DATE,
+			},
+			DlpDateTime,
+		},
+		MessageTransport,
+	},
+	SyncMode,
+};
+
+/// Name of the device data directory, within the data root
+pub const DEVICE_DIRECTORY: &str = "device";
+
+/// Name of the directory holding the conduits' local data directories, within the data root
+pub const CONDUITS_DIRECTORY: &str = "conduits";
+
+/// Directory to keep synced data in, when one isn't configured (`~/Documents/palm.rs`)
+pub fn default_data_root() -> Option<PathBuf> {
+	env::var_os("HOME").map(|x| PathBuf::from(x).join("Documents").join("palm.rs"))
+}
+
+/// Name of a directory to keep a user's data in, based on their user name (or user ID, if the
+/// device hasn't been given a user name)
+pub fn user_directory_name(user: &DlpUserInfo) -> String {
+	let name = user
+		.user_name
+		.trim()
+		.chars()
+		.map(|x| {
+			if x.is_alphanumeric() || x == '-' || x == '.' {
+				x
+			} else {
+				'_'
+			}
+		})
+		.collect::<String>();
+
+	if name.is_empty() || name.starts_with('.') {
+		format!("user-{:08x}", user.user_id)
+	} else {
+		name
+	}
+}
+
+/// Whether a database name matches a pattern, where `*` matches any run of characters
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+	let mut parts = pattern.split('*');
+	let first = parts.next().unwrap_or_default();
+	let mut rest = match name.strip_prefix(first) {
+		Some(x) => x,
+		None => return false,
+	};
+
+	let parts = parts.collect::<Vec<_>>();
+	let last = match parts.split_last() {
+		Some((last, middle)) => {
+			for part in middle.iter() {
+				match rest.find(part) {
+					Some(index) => rest = &rest[index + part.len()..],
+					None => return false,
+				}
+			}
+			last
+		}
+		None => return rest.is_empty(),
+	};
+
+	rest.ends_with(last)
+}
+
+/// The current local time, as used by DLP
+fn now() -> DlpDateTime {
+	let now = Local::now();
+	DlpDateTime {
+		year: now.year() as u16,
+		month: now.month() as u8,
+		day: now.day() as u8,
+		hour: now.hour() as u8,
+		minute: now.minute() as u8,
+		second: now.second() as u8,
+	}
+}
+
+/// Read the contents of all the database files in a directory, by file name
+fn read_databases(path: &Path) -> Result<BTreeMap<String, Vec<u8>>, io::Error> {
+	let mut databases = BTreeMap::new();
+	for entry in fs::read_dir(path)? {
+		let entry = entry?;
+		let file_name = entry.file_name().to_string_lossy().into_owned();
+		let extension = Path::new(&file_name)
+			.extension()
+			.map(|x| x.to_string_lossy().to_ascii_lowercase());
+		if !matches!(extension.as_deref(), Some("pdb") | Some("prc"))
+			|| !entry.file_type()?.is_file()
+		{
+			continue;
+		}
+
+		databases.insert(file_name, fs::read(entry.path())?);
+	}
+
+	Ok(databases)
+}
+
+/// Put the database files in a directory back to how they were
+fn restore_databases(path: &Path, databases: &BTreeMap<String, Vec<u8>>) -> Result<(), io::Error> {
+	for (file_name, data) in read_databases(path)? {
+		match databases.get(&file_name) {
+			Some(original) if *original == data => {}
+			Some(original) => fs::write(path.join(&file_name), original)?,
+			None => fs::remove_file(path.join(&file_name))?,
+		}
+	}
+	for (file_name, data) in databases.iter() {
+		if !path.join(file_name).exists() {
+			fs::write(path.join(file_name), data)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// What happened to a conduit during a sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConduitOutcome {
+	pub conduit_name: String,
+	pub success: bool,
+
+	/// Why the conduit failed, if it did
+	pub error: Option<String>,
+}
+
+/// Summary of a finished sync
+#[derive(Debug, Clone, PartialEq)]
+pub struct HotSyncReport {
+	pub user: DlpUserInfo,
+
+	/// Names of the databases pulled from the device
+	pub pulled: Vec<String>,
+
+	/// Names of the databases pushed back to the device
+	pub pushed: Vec<String>,
+
+	pub conduits: Vec<ConduitOutcome>,
+
+	/// Status the sync was ended with
+	pub status: DlpSyncStatus,
+}
+
+/// Desktop side of a HotSync
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct HotSync {
+	/// Directory to keep the data for the device being synced in
+	pub data_root: PathBuf,
+
+	/// Conduits to run, in order
+	///
+	/// Their local and device data paths are filled in for each sync.
+	pub conduits: Vec<ConduitHandler>,
+
+	/// Pull every database from the device, rather than only the ones the conduits need
+	pub backup: bool,
+
+	/// Databases to pull even if no conduit needs them (`*` matches any run of characters)
+	pub include: Vec<String>,
+
+	/// Databases to never pull or push, even if a conduit needs them
+	pub exclude: Vec<String>,
+}
+
+impl HotSync {
+	pub fn new<P: AsRef<Path>>(data_root: P) -> Self {
+		Self {
+			data_root: data_root.as_ref().to_path_buf(),
+			..Self::default()
+		}
+	}
+
+	/// Path to the device data directory
+	pub fn device_path(&self) -> PathBuf {
+		self.data_root.join(DEVICE_DIRECTORY)
+	}
+
+	/// Path to the local data directory for a conduit
+	pub fn conduit_path(&self, conduit_name: &str) -> PathBuf {
+		let name = Path::new(conduit_name)
+			.file_name()
+			.map(|x| x.to_os_string())
+			.unwrap_or_else(|| conduit_name.into());
+
+		self.data_root.join(CONDUITS_DIRECTORY).join(name)
+	}
+
+	fn is_excluded(&self, name: &str) -> bool {
+		self.exclude.iter().any(|x| matches_pattern(x, name))
+	}
+
+	/// Whether a database should be pulled, given the ones the conduits need
+	fn is_wanted(&self, name: &str, needed: &BTreeSet<String>) -> bool {
+		let included = self.backup
+			|| needed.contains(name)
+			|| self.include.iter().any(|x| matches_pattern(x, name));
+
+		included && !self.is_excluded(name)
+	}
+
+	/// Run the sync with a connected device, whose user info has already been read
+	///
+	/// The sync is always ended, even if it fails part of the way through.
+	pub fn sync<T: MessageTransport>(
+		&self,
+		client: &mut DlpClient<T>,
+		user: &DlpUserInfo,
+	) -> Result<HotSyncReport, io::Error> {
+		let mut report = HotSyncReport {
+			user: user.clone(),
+			pulled: Vec::new(),
+			pushed: Vec::new(),
+			conduits: Vec::new(),
+			status: DlpSyncStatus::Other,
+		};
+
+		match self.run(client, &mut report) {
+			Ok(()) => {
+				client.end_of_sync(report.status)?;
+				Ok(report)
+			}
+			Err(e) => {
+				let _ = client.add_sync_log_entry(&format!("Sync failed: {}\n", e));
+				let _ = client.end_of_sync(DlpSyncStatus::Other);
+				Err(e)
+			}
+		}
+	}
+
+	fn run<T: MessageTransport>(
+		&self,
+		client: &mut DlpClient<T>,
+		report: &mut HotSyncReport,
+	) -> Result<(), io::Error> {
+		client.open_conduit()?;
+		let device_path = self.device_path();
+		fs::create_dir_all(&device_path)?;
+
+		// Find out which databases are needed, leaving out conduits that can't say
+		let mut needed = BTreeSet::new();
+		let mut conduits = Vec::new();
+		for conduit in self.conduits.iter() {
+			let mut conduit = conduit.clone();
+			conduit.path_local = self.conduit_path(&conduit.conduit_name);
+			conduit.path_device = device_path.clone();
+
+			match conduit.requirements() {
+				Ok(requirements) => {
+					needed.extend(requirements.databases.iter().cloned());
+					conduits.push((conduit, requirements.databases));
+				}
+				Err(e) => report.conduits.push(ConduitOutcome {
+					conduit_name: conduit.conduit_name.clone(),
+					success: false,
+					error: Some(e.to_string()),
+				}),
+			}
+		}
+
+		// Pull the databases
+		let mut synced = Vec::new();
+		for info in client.list_databases(DLP_DB_LIST_RAM, 0)? {
+			if !self.is_wanted(&info.name, &needed) {
+				continue;
+			}
+
+			let data = if info.is_resource_database() {
+				client.read_database::<PrcDatabase>(0, &info)?.to_bytes()?
+			} else {
+				client.read_database::<PdbDatabase>(0, &info)?.to_bytes()?
+			};
+			fs::write(device_path.join(info.file_name()), data)?;
+
+			report.pulled.push(info.name.clone());
+			synced.push(info);
+		}
+
+		// Run the conduits, keeping only the changes they're allowed to make
+		let original = read_databases(&device_path)?;
+		let mut current = original.clone();
+		let mut synced_by = BTreeSet::new();
+		let mut failed_by = BTreeSet::new();
+		for (conduit, databases) in conduits.iter() {
+			let mut outcome = ConduitOutcome {
+				conduit_name: conduit.conduit_name.clone(),
+				success: false,
+				error: None,
+			};
+
+			fs::create_dir_all(&conduit.path_local)?;
+			match conduit
+				.popen(Redirection::None, Redirection::None)
+				.and_then(|mut x| x.wait())
+			{
+				Ok(status) if status.success() => outcome.success = true,
+				Ok(status) => outcome.error = Some(format!("exited with {:?}", status)),
+				Err(e) => outcome.error = Some(e.to_string()),
+			}
+
+			let changed = read_databases(&device_path)?;
+			if changed != current {
+				if outcome.success && conduit.sync_mode != SyncMode::KeepDevice {
+					current = changed;
+				} else {
+					restore_databases(&device_path, &current)?;
+				}
+			}
+
+			if outcome.success {
+				synced_by.extend(databases.iter().cloned());
+			} else {
+				failed_by.extend(databases.iter().cloned());
+			}
+
+			report.conduits.push(outcome);
+		}
+
+		// Push back whatever changed
+		for (file_name, data) in current.iter() {
+			if original.get(file_name) == Some(data) {
+				continue;
+			}
+
+			let header = DatabaseHeader::from_bytes(&mut Cursor::new(data))?;
+			let info = DlpDatabaseInfo::from_header(&header);
+			if self.is_excluded(&info.name) {
+				continue;
+			}
+
+			if info.is_resource_database() {
+				client.write_database(0, &PalmDatabase::<PrcDatabase>::from_bytes(data)?)?;
+			} else {
+				client.write_database(0, &PalmDatabase::<PdbDatabase>::from_bytes(data)?)?;
+			}
+
+			report.pushed.push(info.name.clone());
+			if !synced.iter().any(|x| x.name == info.name) {
+				synced.push(info);
+			}
+		}
+
+		// Databases that have been synced by the conduits that need them only need what changes
+		// after this next time; any that a failed conduit needs keep their flags for a retry
+		for info in synced.iter().filter(|x| !x.is_resource_database()) {
+			if !synced_by.contains(&info.name) || failed_by.contains(&info.name) {
+				continue;
+			}
+
+			let handle = client.open_database(0, DLP_OPEN_READ_WRITE, &info.name)?;
+			let result = client
+				.clean_up_database(handle)
+				.and_then(|_| client.reset_sync_flags(handle));
+			client.close_database(handle)?;
+			result?;
+		}
+
+		let success = report.conduits.iter().all(|x| x.success);
+		if success {
+			client.write_user_info(&DlpUserInfoUpdate {
+				last_sync: now(),
+				flags: DLP_USER_INFO_MODIFIED_SYNC_DATE,
+				..DlpUserInfoUpdate::default()
+			})?;
+		}
+
+		let mut log = String::new();
+		for outcome in report.conduits.iter() {
+			let status = if outcome.success { "OK" } else { "Failed" };
+			log.push_str(&format!("{} {}\n", status, outcome.conduit_name));
+		}
+		if !log.is_empty() {
+			client.add_sync_log_entry(&log)?;
+		}
+
+		report.status = if success {
+			DlpSyncStatus::Normal
+		} else {
+			DlpSyncStatus::Other
+		};
+
+		Ok(())
+	}
+}
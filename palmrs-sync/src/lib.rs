@@ -7,7 +7,15 @@ use core::{
 	str::FromStr,
 };
 
+use serde::{de, Deserialize, Deserializer};
+
+pub mod capture;
 pub mod conduit;
+pub mod config;
+pub mod hotsync;
+pub mod mock;
+pub mod protocol;
+pub mod serial;
 
 /// Sync mode
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -42,6 +50,13 @@ impl FromStr for SyncMode {
 	}
 }
 
+impl<'de> Deserialize<'de> for SyncMode {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let s = String::deserialize(deserializer)?;
+		Self::from_str(&s).map_err(|e| de::Error::custom(format!("unknown sync mode {:?}", e)))
+	}
+}
+
 impl Display for SyncMode {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
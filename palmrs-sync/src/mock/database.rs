@@ -0,0 +1,205 @@
+//! Databases stored on a [`MockDevice`][super::MockDevice]
+
+use std::io::{self, Cursor};
+
+use palmrs_database::{
+	header::DatabaseHeader,
+	info::NullExtraInfo,
+	record::{
+		pdb_record::{PdbRecordHeader, RecordAttributes},
+		DatabaseRecord,
+	},
+	DatabaseFormat,
+	PalmDatabase,
+	PdbDatabase,
+	PrcDatabase,
+};
+
+use crate::protocol::dlp::{
+	client::{app_and_sort_blocks, set_app_and_sort_blocks},
+	info::DlpDatabaseInfo,
+	record::{DlpRecord, DlpResource},
+};
+
+/// A database on a mock device
+///
+/// Records keep their attributes as the device would: editing them on the device (with
+/// [`add_record`][Self::add_record], [`edit_record`][Self::edit_record] and
+/// [`delete_record`][Self::delete_record]) marks them as dirty, so that they're found by fast
+/// syncs, while records written by the desktop keep the attributes it sends.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MockDatabase {
+	/// Details of the database (its `index` is ignored, as that's its position on the device)
+	pub info: DlpDatabaseInfo,
+
+	pub app_block: Vec<u8>,
+	pub sort_block: Vec<u8>,
+
+	/// Records, for a record database (their `index` is ignored, as that's their position here)
+	pub records: Vec<DlpRecord>,
+
+	/// Resources, for a resource database (their `index` is ignored in the same way)
+	pub resources: Vec<DlpResource>,
+}
+
+impl MockDatabase {
+	/// Create an empty database with the given details
+	pub fn new(info: DlpDatabaseInfo) -> Self {
+		Self {
+			info,
+			..Self::default()
+		}
+	}
+
+	/// Load a database from the contents of a PDB or PRC file
+	pub fn from_bytes(data: &[u8]) -> Result<Self, io::Error> {
+		let header = DatabaseHeader::from_bytes(&mut Cursor::new(data))?;
+		if DlpDatabaseInfo::from_header(&header).is_resource_database() {
+			Self::from_database(&PalmDatabase::<PrcDatabase>::from_bytes(data)?)
+		} else {
+			Self::from_database(&PalmDatabase::<PdbDatabase>::from_bytes(data)?)
+		}
+	}
+
+	fn from_database<F>(database: &PalmDatabase<F>) -> Result<Self, io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader>,
+	{
+		let info = DlpDatabaseInfo::from_header(&database.header);
+		let (app_block, sort_block) = app_and_sort_blocks(database)?;
+
+		let mut this = Self {
+			info,
+			app_block,
+			sort_block,
+			..Self::default()
+		};
+
+		for (header, data) in database.list_records_resources().iter() {
+			if let Some(resource) = DlpResource::from_resource(header, data) {
+				this.resources.push(resource);
+			} else if let Some(record) = DlpRecord::from_record(header, data) {
+				this.records.push(record);
+			}
+		}
+
+		Ok(this)
+	}
+
+	/// Write the database out, as the contents of a PDB or PRC file
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		if self.is_resource_database() {
+			self.to_database::<PrcDatabase>()?.to_bytes()
+		} else {
+			self.to_database::<PdbDatabase>()?.to_bytes()
+		}
+	}
+
+	fn to_database<F>(&self) -> Result<PalmDatabase<F>, io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader, AppInfoRecord = NullExtraInfo>,
+	{
+		let mut database = PalmDatabase::<F>::new(self.info.to_header(), NullExtraInfo);
+
+		for resource in self.resources.iter() {
+			database.insert_resource_with_id(
+				&resource.type_code,
+				resource.resource_id,
+				&resource.data,
+			)?;
+		}
+		for record in self.records.iter() {
+			let (header, data) = record.clone().into_record();
+			database.insert_record_with_id(
+				header.attributes().unwrap_or_default(),
+				record.unique_id,
+				&data,
+			)?;
+		}
+
+		set_app_and_sort_blocks(&mut database, &self.app_block, &self.sort_block)?;
+		Ok(database)
+	}
+
+	/// Name to save the database under, with the extension for its type
+	pub fn file_name(&self) -> String {
+		self.info.file_name()
+	}
+
+	pub fn is_resource_database(&self) -> bool {
+		self.info.is_resource_database()
+	}
+
+	pub fn record(&self, unique_id: u32) -> Option<&DlpRecord> {
+		self.records.iter().find(|x| x.unique_id == unique_id)
+	}
+
+	fn record_mut(&mut self, unique_id: u32) -> Result<&mut DlpRecord, io::Error> {
+		self.records
+			.iter_mut()
+			.find(|x| x.unique_id == unique_id)
+			.ok_or_else(|| {
+				io::Error::new(
+					io::ErrorKind::NotFound,
+					format!("no record with unique ID {}", unique_id),
+				)
+			})
+	}
+
+	/// Return an unused unique ID for a new record
+	pub(crate) fn next_unique_id(&self) -> u32 {
+		self.records
+			.iter()
+			.map(|x| x.unique_id + 1)
+			.max()
+			.unwrap_or(1)
+			.max(1)
+	}
+
+	/// Records that have changed since the last sync
+	pub fn modified_records(&self) -> impl Iterator<Item = &DlpRecord> {
+		self.records.iter().filter(|x| x.attributes.dirty)
+	}
+
+	/// Add a record on the device, returning its unique ID
+	pub fn add_record(&mut self, category: u8, data: &[u8]) -> u32 {
+		let unique_id = self.next_unique_id();
+		self.records.push(DlpRecord {
+			unique_id,
+			attributes: RecordAttributes {
+				dirty: true,
+				category,
+				..RecordAttributes::default()
+			},
+			data: data.to_vec(),
+			..DlpRecord::default()
+		});
+
+		unique_id
+	}
+
+	/// Change a record's data on the device
+	pub fn edit_record(&mut self, unique_id: u32, data: &[u8]) -> Result<(), io::Error> {
+		let record = self.record_mut(unique_id)?;
+		record.data = data.to_vec();
+		record.attributes.dirty = true;
+
+		Ok(())
+	}
+
+	/// Delete a record on the device
+	///
+	/// The record stays in the database until it's cleaned up after the next sync, so that the
+	/// deletion can be synced. Archived records keep their data, for the desktop to save.
+	pub fn delete_record(&mut self, unique_id: u32, archive: bool) -> Result<(), io::Error> {
+		let record = self.record_mut(unique_id)?;
+		record.attributes.delete = true;
+		record.attributes.dirty = true;
+		record.archived = archive;
+		if !archive {
+			record.data.clear();
+		}
+
+		Ok(())
+	}
+}
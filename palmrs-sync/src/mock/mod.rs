@@ -0,0 +1,914 @@
+//! Simulated Palm OS device, for testing syncs without hardware
+//!
+//! [`MockDevice`] answers DLP requests from databases held in memory, normally loaded from a
+//! directory of PDB and PRC files with [`MockDevice::load_directory`]. It can be driven in three
+//! ways:
+//!
+//! - directly, as a [`MessageTransport`] that a [`DlpClient`][crate::protocol::dlp::client::DlpClient]
+//!   talks to in-process
+//! - over a serial link (or anything else that carries bytes), starting the session with CMP,
+//!   using [`MockDevice::sync_serial`]
+//! - over a Network HotSync connection, using [`MockDevice::sync_network`]
+//!
+//! Everything about the device is deterministic, including its clock, which only changes when the
+//! desktop sets it.
+
+use std::{
+	collections::VecDeque,
+	fs,
+	io::{self, Cursor, Read, Write},
+	path::Path,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use palmrs_database::{
+	record::pdb_record::RecordAttributes,
+	text::{palm_to_string, trim_null},
+};
+
+use crate::protocol::{
+	cmp::{self, CMP_INITIAL_BAUD_RATE},
+	dlp::{
+		client::{
+			DlpSyncStatus,
+			DLP_OPEN_WRITE,
+			DLP_VERSION_MAJOR,
+			DLP_VERSION_MINOR,
+			DLP_WRITE_RECORD_DATA_INCLUDED,
+		},
+		info::{
+			DlpCardInfo,
+			DlpDatabaseInfo,
+			DlpDatabaseList,
+			DlpStorageInfo,
+			DlpSysInfo,
+			DlpUserInfo,
+			DlpUserInfoUpdate,
+			DlpVersionInfo,
+			DLP_DB_LIST_MULTIPLE,
+			DLP_DB_LIST_RAM,
+			DLP_USER_INFO_MODIFIED_SYNC_DATE,
+			DLP_USER_INFO_MODIFIED_SYNC_PC,
+			DLP_USER_INFO_MODIFIED_USER_ID,
+			DLP_USER_INFO_MODIFIED_USER_NAME,
+			DLP_USER_INFO_MODIFIED_VIEWER_ID,
+		},
+		record::{DlpRecord, DlpResource, DLP_RECORD_ATTRIBUTE_ARCHIVED},
+		DlpDateTime,
+		DlpError,
+		DlpFunction,
+		DlpRequest,
+		DlpResponse,
+		DLP_FIRST_ARGUMENT_ID,
+	},
+	netsync::NetSyncConnection,
+	padp::PadpConnection,
+	slp::{SlpConnection, SlpSocket},
+	MessageTransport,
+};
+
+mod database;
+
+pub use self::database::MockDatabase;
+
+/// Number of databases returned by each `ReadDbList` request, when more than one is asked for
+const DB_LIST_BATCH_SIZE: usize = 8;
+
+const DELETE_RECORD_ALL: u8 = 0x80;
+
+/// A database opened by the desktop
+#[derive(Debug, Clone, PartialEq)]
+struct OpenDatabase {
+	handle: u8,
+
+	/// Index of the database in [`MockDevice::databases`]
+	index: usize,
+
+	mode: u8,
+
+	/// Index of the record to continue looking for modified records from
+	next_modified: usize,
+}
+
+/// A simulated Palm OS device
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockDevice {
+	pub user_info: DlpUserInfo,
+	pub sys_info: DlpSysInfo,
+	pub version_info: DlpVersionInfo,
+
+	/// The device's memory (card 0, the only card it has)
+	pub card_info: DlpCardInfo,
+
+	pub date_time: DlpDateTime,
+	pub databases: Vec<MockDatabase>,
+
+	/// Fastest baud rate offered when starting a serial session
+	///
+	/// The device doesn't change the rate of the link itself, so this only matters for links that
+	/// don't have one, such as sockets.
+	pub max_baud_rate: u32,
+
+	/// Entries added to the HotSync log during the sync
+	pub sync_log: Vec<String>,
+
+	/// Number of times the desktop has opened a conduit
+	pub conduits_opened: u32,
+
+	/// Status the desktop ended the sync with, if it has
+	pub sync_status: Option<DlpSyncStatus>,
+
+	open: Vec<OpenDatabase>,
+
+	/// Responses waiting to be received, when used as a [`MessageTransport`]
+	responses: VecDeque<Vec<u8>>,
+}
+
+impl Default for MockDevice {
+	fn default() -> Self {
+		let date_time = DlpDateTime {
+			year: 2004,
+			month: 1,
+			day: 1,
+			hour: 12,
+			minute: 0,
+			second: 0,
+		};
+
+		Self {
+			user_info: DlpUserInfo::default(),
+			sys_info: DlpSysInfo {
+				rom_version: 0x0400_3000,
+				locale: 0,
+				product_id: Vec::new(),
+			},
+			version_info: DlpVersionInfo {
+				dlp_version: (DLP_VERSION_MAJOR, DLP_VERSION_MINOR),
+				compatible_version: (1, 0),
+				max_record_size: 0xFFFF,
+			},
+			card_info: DlpCardInfo {
+				card_number: 0,
+				card_version: 1,
+				creation_date: date_time,
+				rom_size: 4 * 1024 * 1024,
+				ram_size: 8 * 1024 * 1024,
+				free_ram: 4 * 1024 * 1024,
+				name: "PalmCard".to_string(),
+				manufacturer: "Palm Computing".to_string(),
+			},
+			date_time,
+			databases: Vec::new(),
+			max_baud_rate: CMP_INITIAL_BAUD_RATE,
+			sync_log: Vec::new(),
+			conduits_opened: 0,
+			sync_status: None,
+			open: Vec::new(),
+			responses: VecDeque::new(),
+		}
+	}
+}
+
+impl MockDevice {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Create a device holding the PDB and PRC files in the given directory
+	///
+	/// Databases are added in order of file name, so that they're always listed in the same
+	/// order.
+	pub fn load_directory<P: AsRef<Path>>(path: P) -> Result<Self, io::Error> {
+		let mut paths = Vec::new();
+		for entry in fs::read_dir(path)? {
+			let path = entry?.path();
+			let extension = path
+				.extension()
+				.and_then(|x| x.to_str())
+				.map(str::to_ascii_lowercase);
+			if matches!(extension.as_deref(), Some("pdb") | Some("prc")) {
+				paths.push(path);
+			}
+		}
+		paths.sort();
+
+		let mut device = Self::default();
+		for path in paths {
+			let database = MockDatabase::from_bytes(&fs::read(&path)?).map_err(|e| {
+				io::Error::new(e.kind(), format!("failed to load {:?}: {}", path, e))
+			})?;
+			device.databases.push(database);
+		}
+
+		Ok(device)
+	}
+
+	/// Write all of the databases out to the given directory, as PDB and PRC files
+	pub fn save_directory<P: AsRef<Path>>(&self, path: P) -> Result<(), io::Error> {
+		let path = path.as_ref();
+		fs::create_dir_all(path)?;
+		for database in self.databases.iter() {
+			fs::write(path.join(database.file_name()), database.to_bytes()?)?;
+		}
+
+		Ok(())
+	}
+
+	pub fn database(&self, name: &str) -> Option<&MockDatabase> {
+		self.databases.iter().find(|x| x.info.name == name)
+	}
+
+	pub fn database_mut(&mut self, name: &str) -> Option<&mut MockDatabase> {
+		self.databases.iter_mut().find(|x| x.info.name == name)
+	}
+
+	/// Start a session over a serial link with CMP, and answer requests until the sync ends
+	pub fn sync_serial<T: Read + Write>(&mut self, link: T) -> Result<DlpSyncStatus, io::Error> {
+		let mut padp = PadpConnection::new(
+			SlpConnection::new(link),
+			SlpSocket::DesktopLink,
+			SlpSocket::DesktopLink,
+		);
+		cmp::wakeup(&mut padp, self.max_baud_rate)?;
+
+		self.serve(&mut padp)
+	}
+
+	/// Start a Network HotSync session over the given stream, and answer requests until the sync
+	/// ends
+	pub fn sync_network<T: Read + Write>(&mut self, stream: T) -> Result<DlpSyncStatus, io::Error> {
+		let mut connection = NetSyncConnection::new(stream);
+		connection.connect_handshake()?;
+
+		self.serve(&mut connection)
+	}
+
+	/// Answer requests from the desktop until it ends the sync, returning the status it ended
+	/// the sync with
+	pub fn serve<T: MessageTransport>(
+		&mut self,
+		transport: &mut T,
+	) -> Result<DlpSyncStatus, io::Error> {
+		self.sync_status = None;
+		loop {
+			let data = transport.receive_message()?;
+			let request = DlpRequest::from_bytes(&mut Cursor::new(&data))?;
+			let response = self.handle_request(&request);
+			transport.reply_message(&response.to_bytes()?)?;
+
+			if let Some(status) = self.sync_status {
+				return Ok(status);
+			}
+		}
+	}
+
+	/// Carry out a request, and return the response to it
+	pub fn handle_request(&mut self, request: &DlpRequest) -> DlpResponse {
+		match self.dispatch(request) {
+			Ok(response) => response,
+			Err(e) => {
+				let error = match DlpError::from_io_error(&e) {
+					Some(x) => x,
+					None if e.kind() == io::ErrorKind::UnexpectedEof => DlpError::ArgumentSize,
+					None => DlpError::System,
+				};
+
+				DlpResponse {
+					error: Some(error),
+					..DlpResponse::new(request.function)
+				}
+			}
+		}
+	}
+
+	fn dispatch(&mut self, request: &DlpRequest) -> Result<DlpResponse, io::Error> {
+		let function = request.function;
+		let data = match function {
+			DlpFunction::ReadUserInfo => self.user_info.to_bytes()?,
+			DlpFunction::WriteUserInfo => self.write_user_info(first_argument(request)?)?,
+			DlpFunction::ReadSysInfo => {
+				return Ok(DlpResponse::new(function)
+					.with_argument(DLP_FIRST_ARGUMENT_ID, self.sys_info.to_bytes()?)
+					.with_argument(DLP_FIRST_ARGUMENT_ID + 1, self.version_info.to_bytes()?))
+			}
+			DlpFunction::GetSysDateTime => self.date_time.to_bytes()?,
+			DlpFunction::SetSysDateTime => {
+				self.date_time = DlpDateTime::from_bytes(&mut first_argument(request)?)?;
+				Vec::new()
+			}
+			DlpFunction::ReadStorageInfo => self.read_storage_info(first_argument(request)?)?,
+			DlpFunction::ReadDbList => self.read_database_list(first_argument(request)?)?,
+			DlpFunction::OpenDb => self.open_database(first_argument(request)?)?,
+			DlpFunction::CreateDb => self.create_database(first_argument(request)?)?,
+			DlpFunction::CloseDb => {
+				let handle = first_argument(request)?.read_u8()?;
+				self.open_database_index(handle)?;
+				self.open.retain(|x| x.handle != handle);
+				Vec::new()
+			}
+			DlpFunction::DeleteDb => self.delete_database(first_argument(request)?)?,
+			DlpFunction::ReadOpenDbInfo => {
+				let handle = first_argument(request)?.read_u8()?;
+				let database = &self.databases[self.open_database_index(handle)?];
+				let count = database.records.len() + database.resources.len();
+
+				let mut data = Vec::new();
+				data.write_u16::<BigEndian>(count as u16)?;
+				data
+			}
+			DlpFunction::ReadAppBlock | DlpFunction::ReadSortBlock => {
+				self.read_block(function, first_argument(request)?)?
+			}
+			DlpFunction::WriteAppBlock | DlpFunction::WriteSortBlock => {
+				self.write_block(function, first_argument(request)?)?
+			}
+			DlpFunction::ReadNextModifiedRecord => {
+				self.read_next_modified_record(first_argument(request)?)?
+			}
+			DlpFunction::ResetRecordIndex => {
+				let handle = first_argument(request)?.read_u8()?;
+				self.open_database_mut(handle)?.next_modified = 0;
+				Vec::new()
+			}
+			DlpFunction::ReadRecord => self.read_record(request)?,
+			DlpFunction::WriteRecord => self.write_record(first_argument(request)?)?,
+			DlpFunction::DeleteRecord => self.delete_record(first_argument(request)?)?,
+			DlpFunction::ReadRecordIdList => self.read_record_id_list(first_argument(request)?)?,
+			DlpFunction::ReadResource => self.read_resource(request)?,
+			DlpFunction::WriteResource => self.write_resource(first_argument(request)?)?,
+			DlpFunction::DeleteResource => self.delete_resource(first_argument(request)?)?,
+			DlpFunction::ResetSyncFlags => {
+				let handle = first_argument(request)?.read_u8()?;
+				let index = self.writable_database_index(handle)?;
+				for record in self.databases[index].records.iter_mut() {
+					record.attributes.dirty = false;
+				}
+				Vec::new()
+			}
+			DlpFunction::CleanUpDatabase => {
+				let handle = first_argument(request)?.read_u8()?;
+				let index = self.writable_database_index(handle)?;
+				self.databases[index]
+					.records
+					.retain(|x| !x.attributes.delete);
+				self.open_database_mut(handle)?.next_modified = 0;
+				Vec::new()
+			}
+			DlpFunction::AddSyncLogEntry => {
+				let data = request.argument(DLP_FIRST_ARGUMENT_ID).unwrap_or_default();
+				self.sync_log.push(read_cstring(data));
+				Vec::new()
+			}
+			DlpFunction::OpenConduit => {
+				self.conduits_opened += 1;
+				Vec::new()
+			}
+			DlpFunction::EndOfSync => {
+				let status = first_argument(request)?.read_u16::<BigEndian>()?;
+				self.sync_status = Some(DlpSyncStatus::from(status));
+				self.open.clear();
+				Vec::new()
+			}
+			_ => return Err(DlpError::IllegalRequest.into()),
+		};
+
+		let mut response = DlpResponse::new(function);
+		if !data.is_empty() {
+			response = response.with_argument(DLP_FIRST_ARGUMENT_ID, data);
+		}
+
+		Ok(response)
+	}
+
+	fn write_user_info(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let update = DlpUserInfoUpdate::from_bytes(&mut rdr)?;
+		let info = &mut self.user_info;
+
+		if update.flags & DLP_USER_INFO_MODIFIED_USER_ID != 0 {
+			info.user_id = update.user_id;
+		}
+		if update.flags & DLP_USER_INFO_MODIFIED_VIEWER_ID != 0 {
+			info.viewer_id = update.viewer_id;
+		}
+		if update.flags & DLP_USER_INFO_MODIFIED_SYNC_PC != 0 {
+			info.last_sync_pc = update.last_sync_pc;
+		}
+		if update.flags & DLP_USER_INFO_MODIFIED_SYNC_DATE != 0 {
+			info.last_sync = update.last_sync;
+			info.last_successful_sync = update.last_sync;
+		}
+		if update.flags & DLP_USER_INFO_MODIFIED_USER_NAME != 0 {
+			info.user_name = update.user_name;
+		}
+
+		Ok(Vec::new())
+	}
+
+	fn read_storage_info(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		if rdr.read_u8()? != 0 {
+			return Err(DlpError::NotFound.into());
+		}
+
+		DlpStorageInfo {
+			last_card: 0,
+			more: false,
+			cards: vec![self.card_info.clone()],
+		}
+		.to_bytes()
+	}
+
+	fn read_database_list(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let flags = rdr.read_u8()?;
+		let card = rdr.read_u8()?;
+		let start_index = rdr.read_u16::<BigEndian>()? as usize;
+
+		// Everything is in RAM, on the only card
+		if card != 0 || flags & DLP_DB_LIST_RAM == 0 || start_index >= self.databases.len() {
+			return Err(DlpError::NotFound.into());
+		}
+
+		let count = if flags & DLP_DB_LIST_MULTIPLE != 0 {
+			DB_LIST_BATCH_SIZE
+		} else {
+			1
+		};
+		let databases = self
+			.databases
+			.iter()
+			.enumerate()
+			.skip(start_index)
+			.take(count)
+			.map(|(index, database)| DlpDatabaseInfo {
+				index: index as u16,
+				..database.info.clone()
+			})
+			.collect::<Vec<_>>();
+		let last_index = start_index + databases.len() - 1;
+
+		DlpDatabaseList {
+			last_index: last_index as u16,
+			more: last_index + 1 < self.databases.len(),
+			databases,
+		}
+		.to_bytes()
+	}
+
+	fn open_database(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let card = rdr.read_u8()?;
+		let mode = rdr.read_u8()?;
+		let name = read_cstring(remaining(&rdr));
+
+		let index = self
+			.databases
+			.iter()
+			.position(|x| x.info.name == name)
+			.filter(|_| card == 0)
+			.ok_or(DlpError::NotFound)?;
+
+		Ok(vec![self.add_open_database(index, mode)?])
+	}
+
+	fn create_database(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let mut creator_code = [0u8; 4];
+		rdr.read_exact(&mut creator_code)?;
+		let mut type_code = [0u8; 4];
+		rdr.read_exact(&mut type_code)?;
+		let card = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+		let attributes = rdr.read_u16::<BigEndian>()?;
+		let version = rdr.read_u16::<BigEndian>()?;
+		let name = read_cstring(remaining(&rdr));
+
+		if card != 0 {
+			return Err(DlpError::Param.into());
+		}
+		if self.database(&name).is_some() {
+			return Err(DlpError::AlreadyExists.into());
+		}
+
+		self.databases.push(MockDatabase::new(DlpDatabaseInfo {
+			attributes,
+			type_code,
+			creator_code,
+			version,
+			creation_time: self.date_time,
+			modification_time: self.date_time,
+			name,
+			..DlpDatabaseInfo::default()
+		}));
+
+		let index = self.databases.len() - 1;
+		Ok(vec![self.add_open_database(index, DLP_OPEN_WRITE)?])
+	}
+
+	fn delete_database(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let card = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+		let name = read_cstring(remaining(&rdr));
+
+		let index = self
+			.databases
+			.iter()
+			.position(|x| x.info.name == name)
+			.filter(|_| card == 0)
+			.ok_or(DlpError::NotFound)?;
+		if self.open.iter().any(|x| x.index == index) {
+			return Err(DlpError::AlreadyOpen.into());
+		}
+
+		self.databases.remove(index);
+		for open in self.open.iter_mut().filter(|x| x.index > index) {
+			open.index -= 1;
+		}
+
+		Ok(Vec::new())
+	}
+
+	fn add_open_database(&mut self, index: usize, mode: u8) -> Result<u8, io::Error> {
+		let handle = (1..=u8::MAX)
+			.find(|x| self.open.iter().all(|open| open.handle != *x))
+			.ok_or(DlpError::TooManyOpen)?;
+
+		self.open.push(OpenDatabase {
+			handle,
+			index,
+			mode,
+			next_modified: 0,
+		});
+
+		Ok(handle)
+	}
+
+	fn open_database_mut(&mut self, handle: u8) -> Result<&mut OpenDatabase, io::Error> {
+		self.open
+			.iter_mut()
+			.find(|x| x.handle == handle)
+			.ok_or_else(|| DlpError::Param.into())
+	}
+
+	fn open_database_index(&self, handle: u8) -> Result<usize, io::Error> {
+		self.open
+			.iter()
+			.find(|x| x.handle == handle)
+			.map(|x| x.index)
+			.ok_or_else(|| DlpError::Param.into())
+	}
+
+	/// Return the index of an open database, if it was opened for writing
+	///
+	/// The database's modification number and time are updated, as it's about to change.
+	fn writable_database_index(&mut self, handle: u8) -> Result<usize, io::Error> {
+		let open = self
+			.open
+			.iter()
+			.find(|x| x.handle == handle)
+			.ok_or(DlpError::Param)?;
+		if open.mode & DLP_OPEN_WRITE == 0 {
+			return Err(DlpError::ReadOnly.into());
+		}
+
+		let info = &mut self.databases[open.index].info;
+		info.modification_number = info.modification_number.wrapping_add(1);
+		info.modification_time = self.date_time;
+
+		Ok(open.index)
+	}
+
+	fn read_block(
+		&mut self,
+		function: DlpFunction,
+		mut rdr: Cursor<&[u8]>,
+	) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+		let offset = rdr.read_u16::<BigEndian>()?;
+		let len = rdr.read_u16::<BigEndian>()?;
+
+		let database = &self.databases[self.open_database_index(handle)?];
+		let block = match function {
+			DlpFunction::ReadAppBlock => &database.app_block,
+			_ => &database.sort_block,
+		};
+		if block.is_empty() {
+			return Err(DlpError::NotFound.into());
+		}
+
+		let mut data = Vec::new();
+		data.write_u16::<BigEndian>(block.len() as u16)?;
+		data.extend(slice(block, offset, len));
+
+		Ok(data)
+	}
+
+	fn write_block(
+		&mut self,
+		function: DlpFunction,
+		mut rdr: Cursor<&[u8]>,
+	) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+		let len = rdr.read_u16::<BigEndian>()? as usize;
+		let mut block = vec![0u8; len];
+		rdr.read_exact(&mut block)?;
+
+		let index = self.writable_database_index(handle)?;
+		let database = &mut self.databases[index];
+		match function {
+			DlpFunction::WriteAppBlock => database.app_block = block,
+			_ => database.sort_block = block,
+		}
+
+		Ok(Vec::new())
+	}
+
+	fn read_next_modified_record(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let open = self
+			.open
+			.iter_mut()
+			.find(|x| x.handle == handle)
+			.ok_or(DlpError::Param)?;
+		let database = &self.databases[open.index];
+		if database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let index = (open.next_modified..database.records.len())
+			.find(|x| database.records[*x].attributes.dirty)
+			.ok_or(DlpError::NotFound)?;
+		open.next_modified = index + 1;
+
+		DlpRecord {
+			index: index as u16,
+			..database.records[index].clone()
+		}
+		.to_bytes()
+	}
+
+	fn read_record(&mut self, request: &DlpRequest) -> Result<Vec<u8>, io::Error> {
+		let (mut rdr, by_index) = match request.argument(DLP_FIRST_ARGUMENT_ID + 1) {
+			Some(data) => (Cursor::new(data), true),
+			None => (first_argument(request)?, false),
+		};
+		let handle = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+
+		let database = &self.databases[self.open_database_index(handle)?];
+		if database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let index = if by_index {
+			Some(rdr.read_u16::<BigEndian>()? as usize).filter(|x| *x < database.records.len())
+		} else {
+			let unique_id = rdr.read_u32::<BigEndian>()?;
+			database
+				.records
+				.iter()
+				.position(|x| x.unique_id == unique_id)
+		}
+		.ok_or(DlpError::NotFound)?;
+		let offset = rdr.read_u16::<BigEndian>()?;
+		let len = rdr.read_u16::<BigEndian>()?;
+
+		let record = &database.records[index];
+		DlpRecord {
+			index: index as u16,
+			data: slice(&record.data, offset, len),
+			..record.clone()
+		}
+		.to_bytes()
+	}
+
+	fn write_record(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		if rdr.read_u8()? & DLP_WRITE_RECORD_DATA_INCLUDED == 0 {
+			return Err(DlpError::Param.into());
+		}
+		let unique_id = rdr.read_u32::<BigEndian>()?;
+		let flags = rdr.read_u8()?;
+		let category = rdr.read_u8()?;
+		let data = remaining(&rdr).to_vec();
+
+		let index = self.writable_database_index(handle)?;
+		let database = &mut self.databases[index];
+		if database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let unique_id = match unique_id {
+			0 => database.next_unique_id(),
+			x => x,
+		};
+		let record = DlpRecord {
+			unique_id,
+			index: 0,
+			attributes: RecordAttributes::from((flags & 0xF0) | (category & 0x0F)),
+			archived: flags & DLP_RECORD_ATTRIBUTE_ARCHIVED != 0,
+			data,
+		};
+		match database
+			.records
+			.iter_mut()
+			.find(|x| x.unique_id == unique_id)
+		{
+			Some(existing) => *existing = record,
+			None => database.records.push(record),
+		}
+
+		let mut data = Vec::new();
+		data.write_u32::<BigEndian>(unique_id)?;
+		Ok(data)
+	}
+
+	fn delete_record(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let flags = rdr.read_u8()?;
+		let unique_id = rdr.read_u32::<BigEndian>()?;
+
+		let index = self.writable_database_index(handle)?;
+		let records = &mut self.databases[index].records;
+		if flags & DELETE_RECORD_ALL != 0 {
+			records.clear();
+		} else {
+			let position = records
+				.iter()
+				.position(|x| x.unique_id == unique_id)
+				.ok_or(DlpError::NotFound)?;
+			records.remove(position);
+		}
+
+		Ok(Vec::new())
+	}
+
+	fn read_record_id_list(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let _flags = rdr.read_u8()?;
+		let start = rdr.read_u16::<BigEndian>()? as usize;
+		let max = rdr.read_u16::<BigEndian>()? as usize;
+
+		let database = &self.databases[self.open_database_index(handle)?];
+		if database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let ids = database
+			.records
+			.iter()
+			.skip(start)
+			.take(max)
+			.map(|x| x.unique_id)
+			.collect::<Vec<_>>();
+
+		let mut data = Vec::new();
+		data.write_u16::<BigEndian>(ids.len() as u16)?;
+		for unique_id in ids {
+			data.write_u32::<BigEndian>(unique_id)?;
+		}
+
+		Ok(data)
+	}
+
+	fn read_resource(&mut self, request: &DlpRequest) -> Result<Vec<u8>, io::Error> {
+		let (mut rdr, by_type) = match request.argument(DLP_FIRST_ARGUMENT_ID + 1) {
+			Some(data) => (Cursor::new(data), true),
+			None => (first_argument(request)?, false),
+		};
+		let handle = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+
+		let database = &self.databases[self.open_database_index(handle)?];
+		if !database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let index = if by_type {
+			let mut type_code = [0u8; 4];
+			rdr.read_exact(&mut type_code)?;
+			let resource_id = rdr.read_u16::<BigEndian>()?;
+			database
+				.resources
+				.iter()
+				.position(|x| x.type_code == type_code && x.resource_id == resource_id)
+		} else {
+			Some(rdr.read_u16::<BigEndian>()? as usize).filter(|x| *x < database.resources.len())
+		}
+		.ok_or(DlpError::NotFound)?;
+		let offset = rdr.read_u16::<BigEndian>()?;
+		let len = rdr.read_u16::<BigEndian>()?;
+
+		let resource = &database.resources[index];
+		DlpResource {
+			index: index as u16,
+			data: slice(&resource.data, offset, len),
+			..resource.clone()
+		}
+		.to_bytes()
+	}
+
+	fn write_resource(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+		let mut type_code = [0u8; 4];
+		rdr.read_exact(&mut type_code)?;
+		let resource_id = rdr.read_u16::<BigEndian>()?;
+		let len = rdr.read_u16::<BigEndian>()? as usize;
+		let mut data = vec![0u8; len];
+		rdr.read_exact(&mut data)?;
+
+		let index = self.writable_database_index(handle)?;
+		let database = &mut self.databases[index];
+		if !database.is_resource_database() {
+			return Err(DlpError::NotSupported.into());
+		}
+
+		let resource = DlpResource {
+			type_code,
+			resource_id,
+			index: 0,
+			data,
+		};
+		match database
+			.resources
+			.iter_mut()
+			.find(|x| x.type_code == type_code && x.resource_id == resource_id)
+		{
+			Some(existing) => *existing = resource,
+			None => database.resources.push(resource),
+		}
+
+		Ok(Vec::new())
+	}
+
+	fn delete_resource(&mut self, mut rdr: Cursor<&[u8]>) -> Result<Vec<u8>, io::Error> {
+		let handle = rdr.read_u8()?;
+		let flags = rdr.read_u8()?;
+		let mut type_code = [0u8; 4];
+		rdr.read_exact(&mut type_code)?;
+		let resource_id = rdr.read_u16::<BigEndian>()?;
+
+		let index = self.writable_database_index(handle)?;
+		let resources = &mut self.databases[index].resources;
+		if flags & DELETE_RECORD_ALL != 0 {
+			resources.clear();
+		} else {
+			let position = resources
+				.iter()
+				.position(|x| x.type_code == type_code && x.resource_id == resource_id)
+				.ok_or(DlpError::NotFound)?;
+			resources.remove(position);
+		}
+
+		Ok(Vec::new())
+	}
+}
+
+/// Requests sent to the device are answered straight away, with the response waiting to be
+/// received
+impl MessageTransport for MockDevice {
+	fn send_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		let request = DlpRequest::from_bytes(&mut Cursor::new(data))?;
+		let response = self.handle_request(&request);
+		self.responses.push_back(response.to_bytes()?);
+
+		Ok(())
+	}
+
+	fn reply_message(&mut self, _data: &[u8]) -> Result<(), io::Error> {
+		Err(io::Error::other("the device doesn't take replies"))
+	}
+
+	fn receive_message(&mut self) -> Result<Vec<u8>, io::Error> {
+		self.responses
+			.pop_front()
+			.ok_or_else(|| io::Error::other("no request has been sent to the device"))
+	}
+}
+
+/// Return a reader over the first argument of a request
+fn first_argument(request: &DlpRequest) -> Result<Cursor<&[u8]>, io::Error> {
+	request
+		.argument(DLP_FIRST_ARGUMENT_ID)
+		.map(Cursor::new)
+		.ok_or_else(|| DlpError::ArgumentMissing.into())
+}
+
+/// Return the rest of the data after a reader's position
+fn remaining<'a>(rdr: &Cursor<&'a [u8]>) -> &'a [u8] {
+	let data = *rdr.get_ref();
+	&data[(rdr.position() as usize).min(data.len())..]
+}
+
+/// Decode a null-terminated Palm OS Latin string
+fn read_cstring(data: &[u8]) -> String {
+	palm_to_string(trim_null(data))
+}
+
+/// Return up to `len` bytes of `data` from `offset`, as for the partial reads DLP allows
+fn slice(data: &[u8], offset: u16, len: u16) -> Vec<u8> {
+	data.iter()
+		.skip(offset as usize)
+		.take(len as usize)
+		.copied()
+		.collect()
+}
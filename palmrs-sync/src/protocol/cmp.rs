@@ -0,0 +1,248 @@
+//! Connection Management Protocol (CMP)
+//!
+//! CMP starts a serial HotSync session. The device sends a wakeup packet, carrying its CMP
+//! version and the fastest baud rate it supports, and the desktop answers with an init packet,
+//! choosing the baud rate that both ends switch to for the rest of the session, or with an abort
+//! packet if it can't talk to the device. All CMP packets are sent as [PADP][crate::protocol::padp]
+//! messages, at the initial rate of [`CMP_INITIAL_BAUD_RATE`].
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::protocol::padp::PadpConnection;
+
+/// Major version of CMP implemented here
+pub const CMP_VERSION_MAJOR: u8 = 1;
+
+/// Minor version of CMP implemented here (1.2 added long PADP packets)
+pub const CMP_VERSION_MINOR: u8 = 2;
+
+/// Baud rate that every session starts at
+pub const CMP_INITIAL_BAUD_RATE: u32 = 9600;
+
+/// Baud rates supported by the desktop by default
+pub const CMP_DEFAULT_BAUD_RATES: &[u32] = &[9600, 19200, 38400, 57600, 115_200];
+
+/// Init flag: switch to the baud rate in the packet
+pub const CMP_FLAG_CHANGE_BAUD: u8 = 0x80;
+
+/// Init flag: use a receive timeout of one minute
+pub const CMP_FLAG_RECEIVE_TIMEOUT_1MIN: u8 = 0x40;
+
+/// Init flag: use a receive timeout of two minutes
+pub const CMP_FLAG_RECEIVE_TIMEOUT_2MIN: u8 = 0x20;
+
+/// Wakeup and init flag: long form PADP headers are supported
+pub const CMP_FLAG_LONG_PACKETS: u8 = 0x10;
+
+/// Abort flag: the CMP versions of the two ends aren't compatible
+pub const CMP_ABORT_VERSION_MISMATCH: u8 = 0x80;
+
+/// Type of a CMP packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CmpPacketType {
+	/// Sent by the device to start a session
+	Wakeup,
+
+	/// Sent by the desktop to accept the session
+	Init,
+
+	/// Sent by either end to refuse the session
+	Abort,
+
+	/// Extended packet, carrying additional data after the header
+	Extended,
+
+	Unknown(u8),
+}
+
+impl From<u8> for CmpPacketType {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::Wakeup,
+			0x02 => Self::Init,
+			0x03 => Self::Abort,
+			0x04 => Self::Extended,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<CmpPacketType> for u8 {
+	fn from(value: CmpPacketType) -> Self {
+		match value {
+			CmpPacketType::Wakeup => 0x01,
+			CmpPacketType::Init => 0x02,
+			CmpPacketType::Abort => 0x03,
+			CmpPacketType::Extended => 0x04,
+			CmpPacketType::Unknown(x) => x,
+		}
+	}
+}
+
+/// A single CMP packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CmpPacket {
+	pub packet_type: CmpPacketType,
+	pub flags: u8,
+	pub major_version: u8,
+	pub minor_version: u8,
+
+	/// Fastest supported rate (in a wakeup), or the rate to switch to (in an init)
+	pub baud_rate: u32,
+}
+
+impl CmpPacket {
+	/// Size of a CMP packet, in bytes
+	pub const SIZE: usize = 10;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let packet_type = CmpPacketType::from(rdr.read_u8()?);
+		let flags = rdr.read_u8()?;
+		let major_version = rdr.read_u8()?;
+		let minor_version = rdr.read_u8()?;
+		let _reserved = rdr.read_u16::<BigEndian>()?;
+		let baud_rate = rdr.read_u32::<BigEndian>()?;
+
+		Ok(Self {
+			packet_type,
+			flags,
+			major_version,
+			minor_version,
+			baud_rate,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(Self::SIZE));
+		cursor.write_u8(self.packet_type.into())?;
+		cursor.write_u8(self.flags)?;
+		cursor.write_u8(self.major_version)?;
+		cursor.write_u8(self.minor_version)?;
+		cursor.write_u16::<BigEndian>(0)?;
+		cursor.write_u32::<BigEndian>(self.baud_rate)?;
+
+		Ok(cursor.into_inner())
+	}
+
+	fn new(packet_type: CmpPacketType, flags: u8, baud_rate: u32) -> Self {
+		Self {
+			packet_type,
+			flags,
+			major_version: CMP_VERSION_MAJOR,
+			minor_version: CMP_VERSION_MINOR,
+			baud_rate,
+		}
+	}
+}
+
+/// The parameters of a session, agreed with CMP
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CmpSession {
+	/// CMP version of the other end, as (major, minor)
+	pub remote_version: (u8, u8),
+
+	/// Baud rate to use for the rest of the session
+	pub baud_rate: u32,
+
+	/// Whether long form PADP headers can be used
+	pub long_packets: bool,
+}
+
+/// Return the fastest of the given baud rates that the device supports, falling back to the
+/// initial rate
+pub fn negotiate_baud_rate(device_max: u32, supported: &[u32]) -> u32 {
+	supported
+		.iter()
+		.copied()
+		.filter(|&x| x <= device_max)
+		.max()
+		.unwrap_or(CMP_INITIAL_BAUD_RATE)
+}
+
+fn receive_packet<T: Read + Write>(padp: &mut PadpConnection<T>) -> Result<CmpPacket, io::Error> {
+	let data = padp.receive()?;
+	CmpPacket::from_bytes(&mut Cursor::new(&data))
+}
+
+/// Wait for a device's wakeup packet, and accept the session (on the desktop side)
+///
+/// The fastest of the supported baud rates that the device also supports is chosen. Once this
+/// returns, both ends switch to the session's baud rate. Devices with a newer major CMP version
+/// are sent an abort packet, and an error is returned.
+pub fn accept<T: Read + Write>(
+	padp: &mut PadpConnection<T>,
+	supported_baud_rates: &[u32],
+) -> Result<CmpSession, io::Error> {
+	let wakeup = loop {
+		let packet = receive_packet(padp)?;
+		if packet.packet_type == CmpPacketType::Wakeup {
+			break packet;
+		}
+	};
+
+	if wakeup.major_version > CMP_VERSION_MAJOR {
+		let abort = CmpPacket::new(CmpPacketType::Abort, CMP_ABORT_VERSION_MISMATCH, 0);
+		padp.reply(&abort.to_bytes()?)?;
+
+		return Err(io::Error::other(format!(
+			"device CMP version {}.{} is not supported",
+			wakeup.major_version, wakeup.minor_version
+		)));
+	}
+
+	let session = CmpSession {
+		remote_version: (wakeup.major_version, wakeup.minor_version),
+		baud_rate: negotiate_baud_rate(wakeup.baud_rate, supported_baud_rates),
+		long_packets: wakeup.flags & CMP_FLAG_LONG_PACKETS != 0,
+	};
+
+	let mut flags = 0;
+	if session.baud_rate != CMP_INITIAL_BAUD_RATE {
+		flags |= CMP_FLAG_CHANGE_BAUD;
+	}
+	if session.long_packets {
+		flags |= CMP_FLAG_LONG_PACKETS;
+	}
+
+	let init = CmpPacket::new(CmpPacketType::Init, flags, session.baud_rate);
+	padp.reply(&init.to_bytes()?)?;
+
+	Ok(session)
+}
+
+/// Send a wakeup packet, and wait for the desktop to accept the session (on the device side)
+pub fn wakeup<T: Read + Write>(
+	padp: &mut PadpConnection<T>,
+	max_baud_rate: u32,
+) -> Result<CmpSession, io::Error> {
+	let wakeup = CmpPacket::new(CmpPacketType::Wakeup, CMP_FLAG_LONG_PACKETS, max_baud_rate);
+	padp.send(&wakeup.to_bytes()?)?;
+
+	loop {
+		let packet = receive_packet(padp)?;
+		match packet.packet_type {
+			CmpPacketType::Init => {
+				let baud_rate = if packet.flags & CMP_FLAG_CHANGE_BAUD != 0 {
+					packet.baud_rate
+				} else {
+					CMP_INITIAL_BAUD_RATE
+				};
+
+				return Ok(CmpSession {
+					remote_version: (packet.major_version, packet.minor_version),
+					baud_rate,
+					long_packets: packet.flags & CMP_FLAG_LONG_PACKETS != 0,
+				});
+			}
+			CmpPacketType::Abort => {
+				return Err(io::Error::new(
+					io::ErrorKind::ConnectionRefused,
+					format!("desktop aborted the session (flags {:#04X})", packet.flags),
+				))
+			}
+			_ => {}
+		}
+	}
+}
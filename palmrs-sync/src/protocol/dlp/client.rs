@@ -0,0 +1,769 @@
+//! Desktop side of DLP
+//!
+//! [`DlpClient`] sends requests over any [`MessageTransport`], and decodes the device's responses.
+//! Errors reported by the device are returned as [`io::Error`]s wrapping a [`DlpError`].
+
+use std::io::{self, Cursor, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use palmrs_database::{
+	header::DatabaseHeader,
+	info::ExtraInfoRecord,
+	record::{pdb_record::PdbRecordHeader, DatabaseRecord},
+	text::string_to_palm,
+	DatabaseFormat,
+	PalmDatabase,
+};
+
+use super::{
+	info::{
+		DlpDatabaseInfo,
+		DlpDatabaseList,
+		DlpStorageInfo,
+		DlpSysInfo,
+		DlpUserInfo,
+		DlpUserInfoUpdate,
+		DlpVersionInfo,
+		DLP_DB_LIST_MULTIPLE,
+	},
+	record::{DlpRecord, DlpResource},
+	DlpDateTime,
+	DlpError,
+	DlpFunction,
+	DlpRequest,
+	DlpResponse,
+	DLP_FIRST_ARGUMENT_ID,
+};
+use crate::protocol::MessageTransport;
+
+/// Major version of DLP implemented here
+pub const DLP_VERSION_MAJOR: u16 = 1;
+
+/// Minor version of DLP implemented here
+pub const DLP_VERSION_MINOR: u16 = 4;
+
+/// Open mode: read from the database
+pub const DLP_OPEN_READ: u8 = 0x80;
+
+/// Open mode: write to the database
+pub const DLP_OPEN_WRITE: u8 = 0x40;
+
+/// Open mode: don't let anything else open the database
+pub const DLP_OPEN_EXCLUSIVE: u8 = 0x20;
+
+/// Open mode: include private records
+pub const DLP_OPEN_SECRET: u8 = 0x10;
+
+/// Open mode: read from and write to the database
+pub const DLP_OPEN_READ_WRITE: u8 = DLP_OPEN_READ | DLP_OPEN_WRITE;
+
+/// WriteRecord flag: the request includes the record data (which devices require)
+pub const DLP_WRITE_RECORD_DATA_INCLUDED: u8 = 0x80;
+
+/// Length to request to read the whole of a record or block
+const READ_TO_END: u16 = 0xFFFF;
+
+const DELETE_RECORD_ALL: u8 = 0x80;
+
+/// Number of record IDs to ask for in each ReadRecordIDList request
+const RECORD_ID_LIST_CHUNK: u16 = 500;
+
+/// Outcome of a sync, reported to the device at the end
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DlpSyncStatus {
+	Normal,
+	OutOfMemory,
+	Cancelled,
+	Other,
+
+	Unknown(u16),
+}
+
+impl From<u16> for DlpSyncStatus {
+	fn from(value: u16) -> Self {
+		match value {
+			0x00 => Self::Normal,
+			0x01 => Self::OutOfMemory,
+			0x02 => Self::Cancelled,
+			0x03 => Self::Other,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<DlpSyncStatus> for u16 {
+	fn from(value: DlpSyncStatus) -> Self {
+		match value {
+			DlpSyncStatus::Normal => 0x00,
+			DlpSyncStatus::OutOfMemory => 0x01,
+			DlpSyncStatus::Cancelled => 0x02,
+			DlpSyncStatus::Other => 0x03,
+			DlpSyncStatus::Unknown(x) => x,
+		}
+	}
+}
+
+/// Encode a string as a null-terminated Palm OS Latin string
+fn cstring(s: &str) -> Vec<u8> {
+	let mut data = string_to_palm(s);
+	data.push(0);
+	data
+}
+
+/// Whether the given error is the device reporting that something wasn't found
+fn is_not_found(e: &io::Error) -> bool {
+	DlpError::from_io_error(e) == Some(DlpError::NotFound)
+}
+
+/// Split the data stored after a database's records into its app info block and sort block
+///
+/// The sort block, if the header gives one, is everything from the sort info offset onwards.
+pub(crate) fn app_and_sort_blocks<F: DatabaseFormat>(
+	database: &PalmDatabase<F>,
+) -> Result<(Vec<u8>, Vec<u8>), io::Error> {
+	let mut app_block = database.app_info.to_bytes()?;
+	app_block.extend_from_slice(database.application_reserved());
+
+	let header = &database.header;
+	let sort_block = match header.sort_info_id.checked_sub(header.app_info_id) {
+		Some(offset) if header.sort_info_id != 0 && offset as usize <= app_block.len() => {
+			app_block.split_off(offset as usize)
+		}
+		_ => Vec::new(),
+	};
+
+	Ok((app_block, sort_block))
+}
+
+/// Store the rest of the app info block (after the app info record) and the sort block as a
+/// database's application reserved data
+///
+/// This has to be done once all of the records have been added, as the sort info offset in the
+/// header isn't kept up to date as records are added.
+pub(crate) fn set_app_and_sort_blocks<F: DatabaseFormat>(
+	database: &mut PalmDatabase<F>,
+	reserved: &[u8],
+	sort_block: &[u8],
+) -> Result<(), io::Error> {
+	let mut data = reserved.to_vec();
+	data.extend_from_slice(sort_block);
+	database.set_application_reserved(data);
+
+	database.header.sort_info_id = if sort_block.is_empty() {
+		0
+	} else {
+		let app_block_len = database.app_info.to_bytes()?.len() + reserved.len();
+		database.header.app_info_id + app_block_len as u32
+	};
+
+	Ok(())
+}
+
+/// DLP client, for the desktop side of a sync
+#[derive(Debug)]
+pub struct DlpClient<T: MessageTransport> {
+	transport: T,
+}
+
+impl<T: MessageTransport> DlpClient<T> {
+	pub fn new(transport: T) -> Self {
+		Self { transport }
+	}
+
+	pub fn get_ref(&self) -> &T {
+		&self.transport
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.transport
+	}
+
+	pub fn into_inner(self) -> T {
+		self.transport
+	}
+
+	/// Send a request, and wait for the response to it
+	///
+	/// If the device reports an error, it's returned as an [`io::Error`] wrapping the
+	/// [`DlpError`].
+	pub fn execute(&mut self, request: &DlpRequest) -> Result<DlpResponse, io::Error> {
+		self.transport.send_message(&request.to_bytes()?)?;
+		let data = self.transport.receive_message()?;
+		let response = DlpResponse::from_bytes(&mut Cursor::new(&data))?;
+
+		if response.function != request.function {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!(
+					"expected response to {:?}, got {:?}",
+					request.function, response.function
+				),
+			));
+		}
+		if let Some(error) = response.error {
+			return Err(error.into());
+		}
+
+		Ok(response)
+	}
+
+	/// Send a request with a single argument (if any), and return the response's first argument
+	fn call(
+		&mut self,
+		function: DlpFunction,
+		argument: Option<Vec<u8>>,
+	) -> Result<Vec<u8>, io::Error> {
+		let mut request = DlpRequest::new(function);
+		if let Some(data) = argument {
+			request = request.with_argument(DLP_FIRST_ARGUMENT_ID, data);
+		}
+
+		let response = self.execute(&request)?;
+		Ok(response
+			.argument(DLP_FIRST_ARGUMENT_ID)
+			.map(<[u8]>::to_vec)
+			.unwrap_or_default())
+	}
+
+	/// Send a request that only has the database handle as its argument
+	fn call_with_handle(
+		&mut self,
+		function: DlpFunction,
+		handle: u8,
+	) -> Result<Vec<u8>, io::Error> {
+		self.call(function, Some(vec![handle]))
+	}
+
+	pub fn read_user_info(&mut self) -> Result<DlpUserInfo, io::Error> {
+		let data = self.call(DlpFunction::ReadUserInfo, None)?;
+		DlpUserInfo::from_bytes(&mut Cursor::new(&data))
+	}
+
+	pub fn write_user_info(&mut self, update: &DlpUserInfoUpdate) -> Result<(), io::Error> {
+		self.call(DlpFunction::WriteUserInfo, Some(update.to_bytes()?))?;
+		Ok(())
+	}
+
+	/// Read the device's system details, and the DLP versions it supports (DLP 1.2 and later)
+	pub fn read_sys_info(&mut self) -> Result<(DlpSysInfo, Option<DlpVersionInfo>), io::Error> {
+		let mut host_version = Vec::new();
+		host_version.write_u16::<BigEndian>(DLP_VERSION_MAJOR)?;
+		host_version.write_u16::<BigEndian>(DLP_VERSION_MINOR)?;
+
+		let request = DlpRequest::new(DlpFunction::ReadSysInfo)
+			.with_argument(DLP_FIRST_ARGUMENT_ID, host_version);
+		let response = self.execute(&request)?;
+
+		let sys_info = DlpSysInfo::from_bytes(&mut Cursor::new(
+			response.argument(DLP_FIRST_ARGUMENT_ID).unwrap_or_default(),
+		))?;
+		let version_info = match response.argument(DLP_FIRST_ARGUMENT_ID + 1) {
+			Some(data) => Some(DlpVersionInfo::from_bytes(&mut Cursor::new(data))?),
+			None => None,
+		};
+
+		Ok((sys_info, version_info))
+	}
+
+	pub fn get_sys_date_time(&mut self) -> Result<DlpDateTime, io::Error> {
+		let data = self.call(DlpFunction::GetSysDateTime, None)?;
+		DlpDateTime::from_bytes(&mut Cursor::new(&data))
+	}
+
+	pub fn set_sys_date_time(&mut self, date_time: &DlpDateTime) -> Result<(), io::Error> {
+		self.call(DlpFunction::SetSysDateTime, Some(date_time.to_bytes()?))?;
+		Ok(())
+	}
+
+	/// Read the details of the memory cards, starting with the given card number
+	pub fn read_storage_info(&mut self, start_card: u8) -> Result<DlpStorageInfo, io::Error> {
+		let data = self.call(DlpFunction::ReadStorageInfo, Some(vec![start_card, 0]))?;
+		DlpStorageInfo::from_bytes(&mut Cursor::new(&data))
+	}
+
+	/// Read a batch of database details, starting with the database at the given index
+	///
+	/// `flags` selects which databases to list, as a combination of the `DLP_DB_LIST_*` flags.
+	pub fn read_database_list(
+		&mut self,
+		flags: u8,
+		card: u8,
+		start_index: u16,
+	) -> Result<DlpDatabaseList, io::Error> {
+		let mut argument = vec![flags, card];
+		argument.write_u16::<BigEndian>(start_index)?;
+
+		let data = self.call(DlpFunction::ReadDbList, Some(argument))?;
+		DlpDatabaseList::from_bytes(&mut Cursor::new(&data))
+	}
+
+	/// List all of the databases on the given card
+	pub fn list_databases(
+		&mut self,
+		flags: u8,
+		card: u8,
+	) -> Result<Vec<DlpDatabaseInfo>, io::Error> {
+		let mut databases = Vec::new();
+		let mut start_index = 0;
+
+		loop {
+			let list =
+				match self.read_database_list(flags | DLP_DB_LIST_MULTIPLE, card, start_index) {
+					Ok(x) => x,
+					Err(e) if is_not_found(&e) => break,
+					Err(e) => return Err(e),
+				};
+
+			databases.extend(list.databases);
+			if !list.more {
+				break;
+			}
+			start_index = list.last_index + 1;
+		}
+
+		Ok(databases)
+	}
+
+	/// Open a database, returning its handle
+	///
+	/// `mode` is a combination of the `DLP_OPEN_*` flags.
+	pub fn open_database(&mut self, card: u8, mode: u8, name: &str) -> Result<u8, io::Error> {
+		let mut argument = vec![card, mode];
+		argument.extend(cstring(name));
+
+		let data = self.call(DlpFunction::OpenDb, Some(argument))?;
+		Cursor::new(&data).read_u8()
+	}
+
+	/// Create a database with the name, codes, attributes and version in the given header, and
+	/// open it, returning its handle
+	pub fn create_database(&mut self, card: u8, header: &DatabaseHeader) -> Result<u8, io::Error> {
+		let mut argument = Cursor::new(Vec::new());
+		argument.write_all(&header.creator_code)?;
+		argument.write_all(&header.type_code)?;
+		argument.write_u8(card)?;
+		argument.write_u8(0)?;
+		argument.write_u16::<BigEndian>(header.attributes)?;
+		argument.write_u16::<BigEndian>(header.version)?;
+		argument.write_all(header.name_trimmed())?;
+		argument.write_u8(0)?;
+
+		let data = self.call(DlpFunction::CreateDb, Some(argument.into_inner()))?;
+		Cursor::new(&data).read_u8()
+	}
+
+	pub fn close_database(&mut self, handle: u8) -> Result<(), io::Error> {
+		self.call_with_handle(DlpFunction::CloseDb, handle)?;
+		Ok(())
+	}
+
+	pub fn delete_database(&mut self, card: u8, name: &str) -> Result<(), io::Error> {
+		let mut argument = vec![card, 0];
+		argument.extend(cstring(name));
+
+		self.call(DlpFunction::DeleteDb, Some(argument))?;
+		Ok(())
+	}
+
+	/// Return the number of records (or resources) in an open database
+	pub fn read_open_database_info(&mut self, handle: u8) -> Result<u16, io::Error> {
+		let data = self.call_with_handle(DlpFunction::ReadOpenDbInfo, handle)?;
+		Cursor::new(&data).read_u16::<BigEndian>()
+	}
+
+	fn read_block(&mut self, function: DlpFunction, handle: u8) -> Result<Vec<u8>, io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u16::<BigEndian>(0)?;
+		argument.write_u16::<BigEndian>(READ_TO_END)?;
+
+		let data = self.call(function, Some(argument))?;
+		let mut rdr = Cursor::new(data.as_slice());
+		let size = rdr.read_u16::<BigEndian>()? as usize;
+		let start = rdr.position() as usize;
+
+		Ok(data[start..].iter().copied().take(size).collect())
+	}
+
+	fn write_block(
+		&mut self,
+		function: DlpFunction,
+		handle: u8,
+		data: &[u8],
+	) -> Result<(), io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u16::<BigEndian>(data.len() as u16)?;
+		argument.extend_from_slice(data);
+
+		self.call(function, Some(argument))?;
+		Ok(())
+	}
+
+	pub fn read_app_block(&mut self, handle: u8) -> Result<Vec<u8>, io::Error> {
+		self.read_block(DlpFunction::ReadAppBlock, handle)
+	}
+
+	pub fn write_app_block(&mut self, handle: u8, data: &[u8]) -> Result<(), io::Error> {
+		self.write_block(DlpFunction::WriteAppBlock, handle, data)
+	}
+
+	pub fn read_sort_block(&mut self, handle: u8) -> Result<Vec<u8>, io::Error> {
+		self.read_block(DlpFunction::ReadSortBlock, handle)
+	}
+
+	pub fn write_sort_block(&mut self, handle: u8, data: &[u8]) -> Result<(), io::Error> {
+		self.write_block(DlpFunction::WriteSortBlock, handle, data)
+	}
+
+	pub fn read_record_by_id(
+		&mut self,
+		handle: u8,
+		unique_id: u32,
+	) -> Result<DlpRecord, io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u32::<BigEndian>(unique_id)?;
+		argument.write_u16::<BigEndian>(0)?;
+		argument.write_u16::<BigEndian>(READ_TO_END)?;
+
+		let data = self.call(DlpFunction::ReadRecord, Some(argument))?;
+		DlpRecord::from_bytes(&mut Cursor::new(&data))
+	}
+
+	pub fn read_record_by_index(&mut self, handle: u8, index: u16) -> Result<DlpRecord, io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u16::<BigEndian>(index)?;
+		argument.write_u16::<BigEndian>(0)?;
+		argument.write_u16::<BigEndian>(READ_TO_END)?;
+
+		let request = DlpRequest::new(DlpFunction::ReadRecord)
+			.with_argument(DLP_FIRST_ARGUMENT_ID + 1, argument);
+		let response = self.execute(&request)?;
+		DlpRecord::from_bytes(&mut Cursor::new(
+			response.argument(DLP_FIRST_ARGUMENT_ID).unwrap_or_default(),
+		))
+	}
+
+	/// Read the next record that has been modified since the last sync, returning `None` once
+	/// there are no more
+	pub fn read_next_modified_record(
+		&mut self,
+		handle: u8,
+	) -> Result<Option<DlpRecord>, io::Error> {
+		match self.call_with_handle(DlpFunction::ReadNextModifiedRecord, handle) {
+			Ok(data) => Ok(Some(DlpRecord::from_bytes(&mut Cursor::new(&data))?)),
+			Err(e) if is_not_found(&e) => Ok(None),
+			Err(e) => Err(e),
+		}
+	}
+
+	/// Write a record, returning its unique ID
+	///
+	/// A unique ID of zero creates a new record, with an ID chosen by the device.
+	pub fn write_record(&mut self, handle: u8, record: &DlpRecord) -> Result<u32, io::Error> {
+		let mut argument = vec![handle, DLP_WRITE_RECORD_DATA_INCLUDED];
+		argument.write_u32::<BigEndian>(record.unique_id)?;
+		argument.write_u8(record.flags())?;
+		argument.write_u8(record.attributes.category & 0x0F)?;
+		argument.extend_from_slice(&record.data);
+
+		let data = self.call(DlpFunction::WriteRecord, Some(argument))?;
+		Cursor::new(&data).read_u32::<BigEndian>()
+	}
+
+	/// Return the unique IDs of all of the records in an open database
+	pub fn read_record_id_list(&mut self, handle: u8) -> Result<Vec<u32>, io::Error> {
+		let mut ids = Vec::new();
+		loop {
+			let mut argument = vec![handle, 0];
+			argument.write_u16::<BigEndian>(ids.len() as u16)?;
+			argument.write_u16::<BigEndian>(RECORD_ID_LIST_CHUNK)?;
+
+			let data = match self.call(DlpFunction::ReadRecordIdList, Some(argument)) {
+				Ok(x) => x,
+				Err(e) if is_not_found(&e) => break,
+				Err(e) => return Err(e),
+			};
+			let mut rdr = Cursor::new(data.as_slice());
+			let count = rdr.read_u16::<BigEndian>()?;
+			for _ in 0..count {
+				ids.push(rdr.read_u32::<BigEndian>()?);
+			}
+
+			if count < RECORD_ID_LIST_CHUNK {
+				break;
+			}
+		}
+
+		Ok(ids)
+	}
+
+	pub fn delete_record(&mut self, handle: u8, unique_id: u32) -> Result<(), io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u32::<BigEndian>(unique_id)?;
+
+		self.call(DlpFunction::DeleteRecord, Some(argument))?;
+		Ok(())
+	}
+
+	pub fn delete_all_records(&mut self, handle: u8) -> Result<(), io::Error> {
+		let mut argument = vec![handle, DELETE_RECORD_ALL];
+		argument.write_u32::<BigEndian>(0)?;
+
+		self.call(DlpFunction::DeleteRecord, Some(argument))?;
+		Ok(())
+	}
+
+	pub fn read_resource_by_index(
+		&mut self,
+		handle: u8,
+		index: u16,
+	) -> Result<DlpResource, io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.write_u16::<BigEndian>(index)?;
+		argument.write_u16::<BigEndian>(0)?;
+		argument.write_u16::<BigEndian>(READ_TO_END)?;
+
+		let data = self.call(DlpFunction::ReadResource, Some(argument))?;
+		DlpResource::from_bytes(&mut Cursor::new(&data))
+	}
+
+	pub fn read_resource_by_type(
+		&mut self,
+		handle: u8,
+		type_code: &[u8; 4],
+		resource_id: u16,
+	) -> Result<DlpResource, io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.extend_from_slice(type_code);
+		argument.write_u16::<BigEndian>(resource_id)?;
+		argument.write_u16::<BigEndian>(0)?;
+		argument.write_u16::<BigEndian>(READ_TO_END)?;
+
+		let request = DlpRequest::new(DlpFunction::ReadResource)
+			.with_argument(DLP_FIRST_ARGUMENT_ID + 1, argument);
+		let response = self.execute(&request)?;
+		DlpResource::from_bytes(&mut Cursor::new(
+			response.argument(DLP_FIRST_ARGUMENT_ID).unwrap_or_default(),
+		))
+	}
+
+	pub fn write_resource(&mut self, handle: u8, resource: &DlpResource) -> Result<(), io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.extend_from_slice(&resource.type_code);
+		argument.write_u16::<BigEndian>(resource.resource_id)?;
+		argument.write_u16::<BigEndian>(resource.data.len() as u16)?;
+		argument.extend_from_slice(&resource.data);
+
+		self.call(DlpFunction::WriteResource, Some(argument))?;
+		Ok(())
+	}
+
+	pub fn delete_resource(
+		&mut self,
+		handle: u8,
+		type_code: &[u8; 4],
+		resource_id: u16,
+	) -> Result<(), io::Error> {
+		let mut argument = vec![handle, 0];
+		argument.extend_from_slice(type_code);
+		argument.write_u16::<BigEndian>(resource_id)?;
+
+		self.call(DlpFunction::DeleteResource, Some(argument))?;
+		Ok(())
+	}
+
+	/// Clear the dirty flags of all the records in a database, once it has been synced
+	pub fn reset_sync_flags(&mut self, handle: u8) -> Result<(), io::Error> {
+		self.call_with_handle(DlpFunction::ResetSyncFlags, handle)?;
+		Ok(())
+	}
+
+	/// Purge the deleted and archived records from a database, once it has been synced
+	pub fn clean_up_database(&mut self, handle: u8) -> Result<(), io::Error> {
+		self.call_with_handle(DlpFunction::CleanUpDatabase, handle)?;
+		Ok(())
+	}
+
+	/// Add a line of text to the HotSync log shown on the device
+	pub fn add_sync_log_entry(&mut self, text: &str) -> Result<(), io::Error> {
+		self.call(DlpFunction::AddSyncLogEntry, Some(cstring(text)))?;
+		Ok(())
+	}
+
+	/// Show the "Synchronizing" status on the device for the next conduit
+	pub fn open_conduit(&mut self) -> Result<(), io::Error> {
+		self.call(DlpFunction::OpenConduit, None)?;
+		Ok(())
+	}
+
+	/// End the sync, with the given outcome
+	pub fn end_of_sync(&mut self, status: DlpSyncStatus) -> Result<(), io::Error> {
+		let mut argument = Vec::new();
+		argument.write_u16::<BigEndian>(status.into())?;
+
+		self.call(DlpFunction::EndOfSync, Some(argument))?;
+		Ok(())
+	}
+
+	/// Read a whole database from the device
+	///
+	/// The app info block is decoded as `T`'s app info record, with anything after it kept as the
+	/// database's application reserved data. Records keep their unique IDs and attributes.
+	pub fn read_database<F>(
+		&mut self,
+		card: u8,
+		info: &DlpDatabaseInfo,
+	) -> Result<PalmDatabase<F>, io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader>,
+	{
+		let handle = self.open_database(card, DLP_OPEN_READ | DLP_OPEN_SECRET, &info.name)?;
+		let result = self.read_open_database(handle, info);
+		let closed = self.close_database(handle);
+
+		let database = result?;
+		closed?;
+		Ok(database)
+	}
+
+	/// Write a whole database to the device, replacing the contents of any database with the
+	/// same name
+	///
+	/// An existing database is updated in place, rather than deleted and created again, so that
+	/// it's never lost if the write fails part of the way through, and it keeps its creation
+	/// date and (unless `database` has one) its sort block. Records and resources are written
+	/// over the existing ones with the same unique ID or type and ID, and then any that aren't
+	/// in `database` are deleted.
+	///
+	/// Records keep their unique IDs and attributes, apart from deleted records, which are left
+	/// out.
+	pub fn write_database<F>(
+		&mut self,
+		card: u8,
+		database: &PalmDatabase<F>,
+	) -> Result<(), io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader>,
+	{
+		let info = DlpDatabaseInfo::from_header(&database.header);
+		let mode = DLP_OPEN_READ_WRITE | DLP_OPEN_SECRET;
+		let (handle, existing) = match self.open_database(card, mode, &info.name) {
+			Ok(handle) => (handle, true),
+			Err(e) if is_not_found(&e) => (self.create_database(card, &database.header)?, false),
+			Err(e) => return Err(e),
+		};
+
+		let result = self.write_open_database(handle, database, existing);
+		let closed = self.close_database(handle);
+
+		result?;
+		closed
+	}
+
+	fn write_open_database<F>(
+		&mut self,
+		handle: u8,
+		database: &PalmDatabase<F>,
+		existing: bool,
+	) -> Result<(), io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader>,
+	{
+		let (app_block, sort_block) = app_and_sort_blocks(database)?;
+		if !app_block.is_empty() {
+			self.write_app_block(handle, &app_block)?;
+		}
+		if !sort_block.is_empty() {
+			self.write_sort_block(handle, &sort_block)?;
+		}
+
+		let mut records = Vec::new();
+		let mut resources = Vec::new();
+		for (header, data) in database.list_records_resources().iter() {
+			if let Some(resource) = DlpResource::from_resource(header, data) {
+				self.write_resource(handle, &resource)?;
+				resources.push((resource.type_code, resource.resource_id));
+			} else if let Some(record) = DlpRecord::from_record(header, data) {
+				if !record.attributes.delete {
+					records.push(self.write_record(handle, &record)?);
+				}
+			}
+		}
+
+		if !existing {
+			return Ok(());
+		}
+
+		// Remove whatever is left over from the device's copy
+		if DlpDatabaseInfo::from_header(&database.header).is_resource_database() {
+			let count = self.read_open_database_info(handle)?;
+			let mut stale = Vec::new();
+			for index in 0..count {
+				let resource = self.read_resource_by_index(handle, index)?;
+				if !resources.contains(&(resource.type_code, resource.resource_id)) {
+					stale.push(resource);
+				}
+			}
+
+			for resource in stale.iter() {
+				self.delete_resource(handle, &resource.type_code, resource.resource_id)?;
+			}
+		} else {
+			for unique_id in self.read_record_id_list(handle)? {
+				if !records.contains(&unique_id) {
+					self.delete_record(handle, unique_id)?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn read_open_database<F>(
+		&mut self,
+		handle: u8,
+		info: &DlpDatabaseInfo,
+	) -> Result<PalmDatabase<F>, io::Error>
+	where
+		F: DatabaseFormat<RecordHeader = PdbRecordHeader>,
+	{
+		let header = info.to_header();
+
+		let app_block = match self.read_app_block(handle) {
+			Ok(x) => x,
+			Err(e) if is_not_found(&e) => Vec::new(),
+			Err(e) => return Err(e),
+		};
+		let sort_block = match self.read_sort_block(handle) {
+			Ok(x) => x,
+			Err(e) if is_not_found(&e) => Vec::new(),
+			Err(e) => return Err(e),
+		};
+		let mut rdr = Cursor::new(app_block.as_slice());
+		let app_info = F::AppInfoRecord::from_bytes(&header, &mut rdr)?;
+		let app_info_len = (rdr.position() as usize).min(app_block.len());
+
+		let mut database = PalmDatabase::<F>::new(header, app_info);
+
+		let count = self.read_open_database_info(handle)?;
+		for index in 0..count {
+			if info.is_resource_database() {
+				let resource = self.read_resource_by_index(handle, index)?;
+				database.insert_resource_with_id(
+					&resource.type_code,
+					resource.resource_id,
+					&resource.data,
+				)?;
+			} else {
+				let (record, data) = self.read_record_by_index(handle, index)?.into_record();
+				database.insert_record_with_id(
+					record.attributes().unwrap_or_default(),
+					record.unique_id().unwrap_or_default(),
+					&data,
+				)?;
+			}
+		}
+
+		set_app_and_sort_blocks(&mut database, &app_block[app_info_len..], &sort_block)?;
+		Ok(database)
+	}
+}
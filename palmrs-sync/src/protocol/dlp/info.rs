@@ -0,0 +1,535 @@
+//! Device and database information structures used by DLP
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use palmrs_database::{
+	header::DatabaseHeader,
+	text::{palm_to_string, string_to_palm, trim_null},
+};
+
+use super::DlpDateTime;
+
+/// User info flag: the user ID has changed
+pub const DLP_USER_INFO_MODIFIED_USER_ID: u8 = 0x80;
+
+/// User info flag: the last sync PC ID has changed
+pub const DLP_USER_INFO_MODIFIED_SYNC_PC: u8 = 0x40;
+
+/// User info flag: the last sync date has changed
+pub const DLP_USER_INFO_MODIFIED_SYNC_DATE: u8 = 0x20;
+
+/// User info flag: the user name has changed
+pub const DLP_USER_INFO_MODIFIED_USER_NAME: u8 = 0x10;
+
+/// User info flag: the viewer ID has changed
+pub const DLP_USER_INFO_MODIFIED_VIEWER_ID: u8 = 0x08;
+
+/// Database list flag: list the databases in RAM
+pub const DLP_DB_LIST_RAM: u8 = 0x80;
+
+/// Database list flag: list the databases in ROM
+pub const DLP_DB_LIST_ROM: u8 = 0x40;
+
+/// Database list flag: return as many databases as fit in the response (DLP 1.2 and later)
+pub const DLP_DB_LIST_MULTIPLE: u8 = 0x20;
+
+/// Read a byte string of the given length, dropping anything from the first null byte
+fn read_string(rdr: &mut Cursor<&[u8]>, len: usize) -> Result<String, io::Error> {
+	let mut buf = vec![0u8; len];
+	rdr.read_exact(&mut buf)?;
+	Ok(palm_to_string(trim_null(&buf)))
+}
+
+/// Read a string of up to the given length, stopping at the end of the data
+fn read_string_lossy(rdr: &mut Cursor<&[u8]>, len: usize) -> Result<String, io::Error> {
+	let remaining = rdr.get_ref().len().saturating_sub(rdr.position() as usize);
+	read_string(rdr, len.min(remaining))
+}
+
+/// The user and last sync details stored on the device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpUserInfo {
+	pub user_id: u32,
+	pub viewer_id: u32,
+
+	/// ID of the desktop that last synced with the device
+	pub last_sync_pc: u32,
+
+	pub last_successful_sync: DlpDateTime,
+	pub last_sync: DlpDateTime,
+	pub user_name: String,
+
+	/// Encrypted password, empty if there isn't one
+	pub password: Vec<u8>,
+}
+
+impl DlpUserInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let user_id = rdr.read_u32::<BigEndian>()?;
+		let viewer_id = rdr.read_u32::<BigEndian>()?;
+		let last_sync_pc = rdr.read_u32::<BigEndian>()?;
+		let last_successful_sync = DlpDateTime::from_bytes(rdr)?;
+		let last_sync = DlpDateTime::from_bytes(rdr)?;
+		let user_name_len = rdr.read_u8()? as usize;
+		let password_len = rdr.read_u8()? as usize;
+		let user_name = read_string(rdr, user_name_len)?;
+		let mut password = vec![0u8; password_len];
+		rdr.read_exact(&mut password)?;
+
+		Ok(Self {
+			user_id,
+			viewer_id,
+			last_sync_pc,
+			last_successful_sync,
+			last_sync,
+			user_name,
+			password,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut user_name = string_to_palm(&self.user_name);
+		user_name.push(0);
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u32::<BigEndian>(self.user_id)?;
+		cursor.write_u32::<BigEndian>(self.viewer_id)?;
+		cursor.write_u32::<BigEndian>(self.last_sync_pc)?;
+		cursor.write_all(&self.last_successful_sync.to_bytes()?)?;
+		cursor.write_all(&self.last_sync.to_bytes()?)?;
+		cursor.write_u8(user_name.len() as u8)?;
+		cursor.write_u8(self.password.len() as u8)?;
+		cursor.write_all(&user_name)?;
+		cursor.write_all(&self.password)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Changes to the user info stored on the device
+///
+/// Only the fields marked as modified in `flags` (a combination of the
+/// `DLP_USER_INFO_MODIFIED_*` flags) are changed.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpUserInfoUpdate {
+	pub user_id: u32,
+	pub viewer_id: u32,
+	pub last_sync_pc: u32,
+	pub last_sync: DlpDateTime,
+	pub flags: u8,
+	pub user_name: String,
+}
+
+impl DlpUserInfoUpdate {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let user_id = rdr.read_u32::<BigEndian>()?;
+		let viewer_id = rdr.read_u32::<BigEndian>()?;
+		let last_sync_pc = rdr.read_u32::<BigEndian>()?;
+		let last_sync = DlpDateTime::from_bytes(rdr)?;
+		let flags = rdr.read_u8()?;
+		let user_name_len = rdr.read_u8()? as usize;
+		let user_name = read_string(rdr, user_name_len)?;
+
+		Ok(Self {
+			user_id,
+			viewer_id,
+			last_sync_pc,
+			last_sync,
+			flags,
+			user_name,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut user_name = string_to_palm(&self.user_name);
+		user_name.push(0);
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u32::<BigEndian>(self.user_id)?;
+		cursor.write_u32::<BigEndian>(self.viewer_id)?;
+		cursor.write_u32::<BigEndian>(self.last_sync_pc)?;
+		cursor.write_all(&self.last_sync.to_bytes()?)?;
+		cursor.write_u8(self.flags)?;
+		cursor.write_u8(user_name.len() as u8)?;
+		cursor.write_all(&user_name)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Details of the device's operating system
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpSysInfo {
+	pub rom_version: u32,
+	pub locale: u32,
+	pub product_id: Vec<u8>,
+}
+
+impl DlpSysInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let rom_version = rdr.read_u32::<BigEndian>()?;
+		let locale = rdr.read_u32::<BigEndian>()?;
+		let _pad = rdr.read_u8()?;
+		let product_id_len = rdr.read_u8()? as usize;
+		let mut product_id = vec![0u8; product_id_len];
+		rdr.read_exact(&mut product_id)?;
+
+		Ok(Self {
+			rom_version,
+			locale,
+			product_id,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u32::<BigEndian>(self.rom_version)?;
+		cursor.write_u32::<BigEndian>(self.locale)?;
+		cursor.write_u8(0)?;
+		cursor.write_u8(self.product_id.len() as u8)?;
+		cursor.write_all(&self.product_id)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// DLP versions supported by the device, returned by DLP 1.2 and later
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DlpVersionInfo {
+	/// Version of DLP implemented by the device, as (major, minor)
+	pub dlp_version: (u16, u16),
+
+	/// Oldest version of DLP the device is compatible with, as (major, minor)
+	pub compatible_version: (u16, u16),
+
+	/// Largest record the device can store
+	pub max_record_size: u32,
+}
+
+impl DlpVersionInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let dlp_version = (rdr.read_u16::<BigEndian>()?, rdr.read_u16::<BigEndian>()?);
+		let compatible_version = (rdr.read_u16::<BigEndian>()?, rdr.read_u16::<BigEndian>()?);
+		let max_record_size = rdr.read_u32::<BigEndian>()?;
+
+		Ok(Self {
+			dlp_version,
+			compatible_version,
+			max_record_size,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u16::<BigEndian>(self.dlp_version.0)?;
+		cursor.write_u16::<BigEndian>(self.dlp_version.1)?;
+		cursor.write_u16::<BigEndian>(self.compatible_version.0)?;
+		cursor.write_u16::<BigEndian>(self.compatible_version.1)?;
+		cursor.write_u32::<BigEndian>(self.max_record_size)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Details of a memory card (including the built-in memory, card 0)
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpCardInfo {
+	pub card_number: u8,
+	pub card_version: u16,
+	pub creation_date: DlpDateTime,
+	pub rom_size: u32,
+	pub ram_size: u32,
+	pub free_ram: u32,
+	pub name: String,
+	pub manufacturer: String,
+}
+
+impl DlpCardInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let start = rdr.position();
+		let total_size = rdr.read_u8()? as u64;
+		let card_number = rdr.read_u8()?;
+		let card_version = rdr.read_u16::<BigEndian>()?;
+		let creation_date = DlpDateTime::from_bytes(rdr)?;
+		let rom_size = rdr.read_u32::<BigEndian>()?;
+		let ram_size = rdr.read_u32::<BigEndian>()?;
+		let free_ram = rdr.read_u32::<BigEndian>()?;
+		let name_len = rdr.read_u8()? as usize;
+		let manufacturer_len = rdr.read_u8()? as usize;
+		let name = read_string(rdr, name_len)?;
+		let manufacturer = read_string(rdr, manufacturer_len)?;
+		rdr.set_position(start + total_size);
+
+		Ok(Self {
+			card_number,
+			card_version,
+			creation_date,
+			rom_size,
+			ram_size,
+			free_ram,
+			name,
+			manufacturer,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let name = string_to_palm(&self.name);
+		let manufacturer = string_to_palm(&self.manufacturer);
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(0)?;
+		cursor.write_u8(self.card_number)?;
+		cursor.write_u16::<BigEndian>(self.card_version)?;
+		cursor.write_all(&self.creation_date.to_bytes()?)?;
+		cursor.write_u32::<BigEndian>(self.rom_size)?;
+		cursor.write_u32::<BigEndian>(self.ram_size)?;
+		cursor.write_u32::<BigEndian>(self.free_ram)?;
+		cursor.write_u8(name.len() as u8)?;
+		cursor.write_u8(manufacturer.len() as u8)?;
+		cursor.write_all(&name)?;
+		cursor.write_all(&manufacturer)?;
+
+		let mut data = cursor.into_inner();
+		if data.len() % 2 != 0 {
+			data.push(0);
+		}
+		data[0] = data.len() as u8;
+
+		Ok(data)
+	}
+}
+
+/// A batch of memory card details, from a single `ReadStorageInfo` request
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpStorageInfo {
+	pub last_card: u8,
+
+	/// Whether there are more cards after these
+	pub more: bool,
+
+	pub cards: Vec<DlpCardInfo>,
+}
+
+impl DlpStorageInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let last_card = rdr.read_u8()?;
+		let more = rdr.read_u8()? != 0;
+		let _pad = rdr.read_u8()?;
+		let count = rdr.read_u8()?;
+		let cards = (0..count)
+			.map(|_| DlpCardInfo::from_bytes(rdr))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			last_card,
+			more,
+			cards,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(self.last_card)?;
+		cursor.write_u8(self.more as u8)?;
+		cursor.write_u8(0)?;
+		cursor.write_u8(self.cards.len() as u8)?;
+		for card in self.cards.iter() {
+			cursor.write_all(&card.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Details of a database on the device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpDatabaseInfo {
+	pub misc_flags: u8,
+
+	/// Database attributes, as in [`DatabaseHeader::attributes`]
+	pub attributes: u16,
+
+	pub type_code: [u8; 4],
+	pub creator_code: [u8; 4],
+	pub version: u16,
+	pub modification_number: u32,
+	pub creation_time: DlpDateTime,
+	pub modification_time: DlpDateTime,
+	pub backup_time: DlpDateTime,
+
+	/// Index of the database on its card
+	pub index: u16,
+
+	pub name: String,
+}
+
+impl DlpDatabaseInfo {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let start = rdr.position();
+		let total_size = rdr.read_u8()? as u64;
+		let misc_flags = rdr.read_u8()?;
+		let attributes = rdr.read_u16::<BigEndian>()?;
+		let mut type_code = [0u8; 4];
+		rdr.read_exact(&mut type_code)?;
+		let mut creator_code = [0u8; 4];
+		rdr.read_exact(&mut creator_code)?;
+		let version = rdr.read_u16::<BigEndian>()?;
+		let modification_number = rdr.read_u32::<BigEndian>()?;
+		let creation_time = DlpDateTime::from_bytes(rdr)?;
+		let modification_time = DlpDateTime::from_bytes(rdr)?;
+		let backup_time = DlpDateTime::from_bytes(rdr)?;
+		let index = rdr.read_u16::<BigEndian>()?;
+		let name_len = (start + total_size).saturating_sub(rdr.position()) as usize;
+		let name = read_string_lossy(rdr, name_len)?;
+		rdr.set_position(start + total_size);
+
+		Ok(Self {
+			misc_flags,
+			attributes,
+			type_code,
+			creator_code,
+			version,
+			modification_number,
+			creation_time,
+			modification_time,
+			backup_time,
+			index,
+			name,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(0)?;
+		cursor.write_u8(self.misc_flags)?;
+		cursor.write_u16::<BigEndian>(self.attributes)?;
+		cursor.write_all(&self.type_code)?;
+		cursor.write_all(&self.creator_code)?;
+		cursor.write_u16::<BigEndian>(self.version)?;
+		cursor.write_u32::<BigEndian>(self.modification_number)?;
+		cursor.write_all(&self.creation_time.to_bytes()?)?;
+		cursor.write_all(&self.modification_time.to_bytes()?)?;
+		cursor.write_all(&self.backup_time.to_bytes()?)?;
+		cursor.write_u16::<BigEndian>(self.index)?;
+		cursor.write_all(&string_to_palm(&self.name))?;
+		cursor.write_u8(0)?;
+
+		let mut data = cursor.into_inner();
+		if data.len() % 2 != 0 {
+			data.push(0);
+		}
+		data[0] = data.len() as u8;
+
+		Ok(data)
+	}
+
+	/// Build a database header from these details
+	///
+	/// The record count and offsets are left empty, to be filled in as records are added to a
+	/// [`PalmDatabase`][palmrs_database::PalmDatabase].
+	pub fn to_header(&self) -> DatabaseHeader {
+		let mut name = [0u8; 32];
+		let encoded = string_to_palm(&self.name);
+		let len = encoded.len().min(name.len() - 1);
+		name[..len].copy_from_slice(&encoded[..len]);
+
+		DatabaseHeader {
+			name,
+			attributes: self.attributes,
+			version: self.version,
+			creation_time: self.creation_time.to_timestamp(),
+			modification_time: self.modification_time.to_timestamp(),
+			backup_time: self.backup_time.to_timestamp(),
+			modification_number: self.modification_number,
+			app_info_id: 0,
+			sort_info_id: 0,
+			type_code: self.type_code,
+			creator_code: self.creator_code,
+			unique_id_seed: 0,
+			next_record_list: 0,
+			record_count: 0,
+		}
+	}
+
+	/// Build database details from a database header
+	pub fn from_header(header: &DatabaseHeader) -> Self {
+		Self {
+			misc_flags: 0,
+			attributes: header.attributes,
+			type_code: header.type_code,
+			creator_code: header.creator_code,
+			version: header.version,
+			modification_number: header.modification_number,
+			creation_time: DlpDateTime::from_timestamp(header.creation_time),
+			modification_time: DlpDateTime::from_timestamp(header.modification_time),
+			backup_time: DlpDateTime::from_timestamp(header.backup_time),
+			index: 0,
+			name: palm_to_string(header.name_trimmed()),
+		}
+	}
+
+	/// Whether this is a resource database (a PRC), rather than a record database (a PDB)
+	pub fn is_resource_database(&self) -> bool {
+		self.attributes & 0x0001 != 0
+	}
+
+	/// Name to save the database under, with the extension for its type
+	pub fn file_name(&self) -> String {
+		let name = self
+			.name
+			.chars()
+			.map(|x| if x == '/' || x.is_control() { '_' } else { x })
+			.collect::<String>();
+		let extension = if self.is_resource_database() {
+			"prc"
+		} else {
+			"pdb"
+		};
+
+		format!("{}.{}", name, extension)
+	}
+}
+
+/// A batch of database details, from a single `ReadDBList` request
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpDatabaseList {
+	/// Index of the last database returned, to continue the listing from
+	pub last_index: u16,
+
+	/// Whether there are more databases after these
+	pub more: bool,
+
+	pub databases: Vec<DlpDatabaseInfo>,
+}
+
+impl DlpDatabaseList {
+	const MORE: u8 = 0x80;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let last_index = rdr.read_u16::<BigEndian>()?;
+		let more = rdr.read_u8()? & Self::MORE != 0;
+		let count = rdr.read_u8()?;
+		let databases = (0..count)
+			.map(|_| DlpDatabaseInfo::from_bytes(rdr))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			last_index,
+			more,
+			databases,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u16::<BigEndian>(self.last_index)?;
+		cursor.write_u8(if self.more { Self::MORE } else { 0 })?;
+		cursor.write_u8(self.databases.len() as u8)?;
+		for database in self.databases.iter() {
+			cursor.write_all(&database.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
@@ -0,0 +1,607 @@
+//! Desktop Link Protocol (DLP)
+//!
+//! DLP is the request/response protocol that makes up the sync itself, once a session has been
+//! set up. The desktop sends requests, each made up of a function ID and a list of arguments, and
+//! the device answers each one with a response, carrying an error code and its own arguments.
+//!
+//! Arguments are numbered from [`DLP_FIRST_ARGUMENT_ID`], and are encoded in one of three forms
+//! depending on their size: tiny (up to 255 bytes), small (up to 64 KiB), or long.
+//!
+//! [`client::DlpClient`] implements the desktop side of the commands that HotSync needs, using
+//! the structures in [`info`] and [`record`].
+
+use core::fmt::{self, Display};
+use std::{
+	error::Error,
+	io::{self, Cursor, Read, Write},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{Datelike, NaiveDate, TimeZone, Timelike, Utc};
+use palmrs_database::time::{unix_ts_to_palm_ts, PalmTimestamp};
+
+pub mod client;
+pub mod info;
+pub mod record;
+
+/// ID of the first argument of a request or response
+pub const DLP_FIRST_ARGUMENT_ID: u8 = 0x20;
+
+/// Set in the function ID of a response
+pub const DLP_RESPONSE_FLAG: u8 = 0x80;
+
+const ARGUMENT_FLAG_SMALL: u8 = 0x80;
+const ARGUMENT_FLAG_LONG: u8 = 0x40;
+const ARGUMENT_FLAG_MASK: u8 = 0xC0;
+
+/// DLP request function IDs
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DlpFunction {
+	ReadUserInfo,
+	WriteUserInfo,
+	ReadSysInfo,
+	GetSysDateTime,
+	SetSysDateTime,
+	ReadStorageInfo,
+	ReadDbList,
+	OpenDb,
+	CreateDb,
+	CloseDb,
+	DeleteDb,
+	ReadAppBlock,
+	WriteAppBlock,
+	ReadSortBlock,
+	WriteSortBlock,
+	ReadNextModifiedRecord,
+	ReadRecord,
+	WriteRecord,
+	DeleteRecord,
+	ReadResource,
+	WriteResource,
+	DeleteResource,
+	CleanUpDatabase,
+	ResetSyncFlags,
+	CallApplication,
+	ResetSystem,
+	AddSyncLogEntry,
+	ReadOpenDbInfo,
+	MoveCategory,
+	ProcessRpc,
+	OpenConduit,
+	EndOfSync,
+	ResetRecordIndex,
+	ReadRecordIdList,
+
+	Unknown(u8),
+}
+
+impl From<u8> for DlpFunction {
+	fn from(value: u8) -> Self {
+		match value {
+			0x10 => Self::ReadUserInfo,
+			0x11 => Self::WriteUserInfo,
+			0x12 => Self::ReadSysInfo,
+			0x13 => Self::GetSysDateTime,
+			0x14 => Self::SetSysDateTime,
+			0x15 => Self::ReadStorageInfo,
+			0x16 => Self::ReadDbList,
+			0x17 => Self::OpenDb,
+			0x18 => Self::CreateDb,
+			0x19 => Self::CloseDb,
+			0x1A => Self::DeleteDb,
+			0x1B => Self::ReadAppBlock,
+			0x1C => Self::WriteAppBlock,
+			0x1D => Self::ReadSortBlock,
+			0x1E => Self::WriteSortBlock,
+			0x1F => Self::ReadNextModifiedRecord,
+			0x20 => Self::ReadRecord,
+			0x21 => Self::WriteRecord,
+			0x22 => Self::DeleteRecord,
+			0x23 => Self::ReadResource,
+			0x24 => Self::WriteResource,
+			0x25 => Self::DeleteResource,
+			0x26 => Self::CleanUpDatabase,
+			0x27 => Self::ResetSyncFlags,
+			0x28 => Self::CallApplication,
+			0x29 => Self::ResetSystem,
+			0x2A => Self::AddSyncLogEntry,
+			0x2B => Self::ReadOpenDbInfo,
+			0x2C => Self::MoveCategory,
+			0x2D => Self::ProcessRpc,
+			0x2E => Self::OpenConduit,
+			0x2F => Self::EndOfSync,
+			0x30 => Self::ResetRecordIndex,
+			0x31 => Self::ReadRecordIdList,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<DlpFunction> for u8 {
+	fn from(value: DlpFunction) -> Self {
+		match value {
+			DlpFunction::ReadUserInfo => 0x10,
+			DlpFunction::WriteUserInfo => 0x11,
+			DlpFunction::ReadSysInfo => 0x12,
+			DlpFunction::GetSysDateTime => 0x13,
+			DlpFunction::SetSysDateTime => 0x14,
+			DlpFunction::ReadStorageInfo => 0x15,
+			DlpFunction::ReadDbList => 0x16,
+			DlpFunction::OpenDb => 0x17,
+			DlpFunction::CreateDb => 0x18,
+			DlpFunction::CloseDb => 0x19,
+			DlpFunction::DeleteDb => 0x1A,
+			DlpFunction::ReadAppBlock => 0x1B,
+			DlpFunction::WriteAppBlock => 0x1C,
+			DlpFunction::ReadSortBlock => 0x1D,
+			DlpFunction::WriteSortBlock => 0x1E,
+			DlpFunction::ReadNextModifiedRecord => 0x1F,
+			DlpFunction::ReadRecord => 0x20,
+			DlpFunction::WriteRecord => 0x21,
+			DlpFunction::DeleteRecord => 0x22,
+			DlpFunction::ReadResource => 0x23,
+			DlpFunction::WriteResource => 0x24,
+			DlpFunction::DeleteResource => 0x25,
+			DlpFunction::CleanUpDatabase => 0x26,
+			DlpFunction::ResetSyncFlags => 0x27,
+			DlpFunction::CallApplication => 0x28,
+			DlpFunction::ResetSystem => 0x29,
+			DlpFunction::AddSyncLogEntry => 0x2A,
+			DlpFunction::ReadOpenDbInfo => 0x2B,
+			DlpFunction::MoveCategory => 0x2C,
+			DlpFunction::ProcessRpc => 0x2D,
+			DlpFunction::OpenConduit => 0x2E,
+			DlpFunction::EndOfSync => 0x2F,
+			DlpFunction::ResetRecordIndex => 0x30,
+			DlpFunction::ReadRecordIdList => 0x31,
+			DlpFunction::Unknown(x) => x,
+		}
+	}
+}
+
+/// Error returned by the device in a DLP response
+///
+/// When a DLP command fails on the device, the [`io::Error`] returned has this as its inner
+/// error, which can be retrieved with [`DlpError::from_io_error`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DlpError {
+	/// General system error on the device
+	System,
+
+	/// Unknown function ID
+	IllegalRequest,
+
+	/// Not enough memory
+	Memory,
+
+	/// Invalid parameter
+	Param,
+
+	/// Database, record or resource not found
+	NotFound,
+
+	/// No databases are open
+	NoneOpen,
+
+	/// Database is already open
+	AlreadyOpen,
+
+	/// Too many databases are open
+	TooManyOpen,
+
+	/// Database already exists
+	AlreadyExists,
+
+	/// Database couldn't be opened
+	CantOpen,
+
+	/// Record has been deleted
+	RecordDeleted,
+
+	/// Record is busy
+	RecordBusy,
+
+	/// Request isn't supported by this version of Palm OS
+	NotSupported,
+
+	/// Database is read-only
+	ReadOnly,
+
+	/// Not enough space on the device
+	NotEnoughSpace,
+
+	/// Size limit exceeded
+	LimitExceeded,
+
+	/// Sync was cancelled by the user
+	Cancelled,
+
+	/// Bad request wrapper
+	BadWrapper,
+
+	/// Required argument missing
+	ArgumentMissing,
+
+	/// Argument has the wrong size
+	ArgumentSize,
+
+	Unknown(u16),
+}
+
+impl DlpError {
+	/// Return the device error that caused the given I/O error, if any
+	pub fn from_io_error(error: &io::Error) -> Option<Self> {
+		error
+			.get_ref()
+			.and_then(|x| x.downcast_ref::<Self>())
+			.copied()
+	}
+}
+
+impl From<u16> for DlpError {
+	fn from(value: u16) -> Self {
+		match value {
+			0x01 => Self::System,
+			0x02 => Self::IllegalRequest,
+			0x03 => Self::Memory,
+			0x04 => Self::Param,
+			0x05 => Self::NotFound,
+			0x06 => Self::NoneOpen,
+			0x07 => Self::AlreadyOpen,
+			0x08 => Self::TooManyOpen,
+			0x09 => Self::AlreadyExists,
+			0x0A => Self::CantOpen,
+			0x0B => Self::RecordDeleted,
+			0x0C => Self::RecordBusy,
+			0x0D => Self::NotSupported,
+			0x0F => Self::ReadOnly,
+			0x10 => Self::NotEnoughSpace,
+			0x11 => Self::LimitExceeded,
+			0x12 => Self::Cancelled,
+			0x13 => Self::BadWrapper,
+			0x14 => Self::ArgumentMissing,
+			0x15 => Self::ArgumentSize,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<DlpError> for u16 {
+	fn from(value: DlpError) -> Self {
+		match value {
+			DlpError::System => 0x01,
+			DlpError::IllegalRequest => 0x02,
+			DlpError::Memory => 0x03,
+			DlpError::Param => 0x04,
+			DlpError::NotFound => 0x05,
+			DlpError::NoneOpen => 0x06,
+			DlpError::AlreadyOpen => 0x07,
+			DlpError::TooManyOpen => 0x08,
+			DlpError::AlreadyExists => 0x09,
+			DlpError::CantOpen => 0x0A,
+			DlpError::RecordDeleted => 0x0B,
+			DlpError::RecordBusy => 0x0C,
+			DlpError::NotSupported => 0x0D,
+			DlpError::ReadOnly => 0x0F,
+			DlpError::NotEnoughSpace => 0x10,
+			DlpError::LimitExceeded => 0x11,
+			DlpError::Cancelled => 0x12,
+			DlpError::BadWrapper => 0x13,
+			DlpError::ArgumentMissing => 0x14,
+			DlpError::ArgumentSize => 0x15,
+			DlpError::Unknown(x) => x,
+		}
+	}
+}
+
+impl Display for DlpError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::System => write!(f, "system error"),
+			Self::IllegalRequest => write!(f, "illegal request"),
+			Self::Memory => write!(f, "out of memory"),
+			Self::Param => write!(f, "invalid parameter"),
+			Self::NotFound => write!(f, "not found"),
+			Self::NoneOpen => write!(f, "no databases open"),
+			Self::AlreadyOpen => write!(f, "database already open"),
+			Self::TooManyOpen => write!(f, "too many databases open"),
+			Self::AlreadyExists => write!(f, "database already exists"),
+			Self::CantOpen => write!(f, "can't open database"),
+			Self::RecordDeleted => write!(f, "record deleted"),
+			Self::RecordBusy => write!(f, "record busy"),
+			Self::NotSupported => write!(f, "request not supported"),
+			Self::ReadOnly => write!(f, "database is read-only"),
+			Self::NotEnoughSpace => write!(f, "not enough space"),
+			Self::LimitExceeded => write!(f, "size limit exceeded"),
+			Self::Cancelled => write!(f, "sync cancelled"),
+			Self::BadWrapper => write!(f, "bad request wrapper"),
+			Self::ArgumentMissing => write!(f, "argument missing"),
+			Self::ArgumentSize => write!(f, "bad argument size"),
+			Self::Unknown(x) => write!(f, "unknown error {:#06X}", x),
+		}
+	}
+}
+
+impl Error for DlpError {}
+
+impl From<DlpError> for io::Error {
+	fn from(value: DlpError) -> Self {
+		io::Error::other(value)
+	}
+}
+
+/// A single argument of a request or response
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlpArgument {
+	pub id: u8,
+	pub data: Vec<u8>,
+}
+
+impl DlpArgument {
+	pub fn new(id: u8, data: Vec<u8>) -> Self {
+		Self { id, data }
+	}
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let tag = rdr.read_u8()?;
+		let len = match tag & ARGUMENT_FLAG_MASK {
+			ARGUMENT_FLAG_SMALL => {
+				let _pad = rdr.read_u8()?;
+				rdr.read_u16::<BigEndian>()? as usize
+			}
+			ARGUMENT_FLAG_LONG => {
+				let _pad = rdr.read_u8()?;
+				rdr.read_u32::<BigEndian>()? as usize
+			}
+			_ => rdr.read_u8()? as usize,
+		};
+
+		let remaining = rdr.get_ref().len().saturating_sub(rdr.position() as usize);
+		if len > remaining {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"DLP argument is longer than the message",
+			));
+		}
+
+		let mut data = vec![0u8; len];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			id: tag & !ARGUMENT_FLAG_MASK,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(self.data.len() + 6));
+		let id = self.id & !ARGUMENT_FLAG_MASK;
+		match self.data.len() {
+			len if len <= 0xFF => {
+				cursor.write_u8(id)?;
+				cursor.write_u8(len as u8)?;
+			}
+			len if len <= 0xFFFF => {
+				cursor.write_u8(id | ARGUMENT_FLAG_SMALL)?;
+				cursor.write_u8(0)?;
+				cursor.write_u16::<BigEndian>(len as u16)?;
+			}
+			len => {
+				cursor.write_u8(id | ARGUMENT_FLAG_LONG)?;
+				cursor.write_u8(0)?;
+				cursor.write_u32::<BigEndian>(len as u32)?;
+			}
+		}
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A request, sent from the desktop to the device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlpRequest {
+	pub function: DlpFunction,
+	pub arguments: Vec<DlpArgument>,
+}
+
+impl DlpRequest {
+	pub fn new(function: DlpFunction) -> Self {
+		Self {
+			function,
+			arguments: Vec::new(),
+		}
+	}
+
+	/// Add an argument with the given ID
+	pub fn with_argument(mut self, id: u8, data: Vec<u8>) -> Self {
+		self.arguments.push(DlpArgument::new(id, data));
+		self
+	}
+
+	/// Return the data of the argument with the given ID, if there is one
+	pub fn argument(&self, id: u8) -> Option<&[u8]> {
+		find_argument(&self.arguments, id)
+	}
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let function = DlpFunction::from(rdr.read_u8()?);
+		let count = rdr.read_u8()?;
+		let arguments = (0..count)
+			.map(|_| DlpArgument::from_bytes(rdr))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			function,
+			arguments,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(self.function.into())?;
+		cursor.write_u8(self.arguments.len() as u8)?;
+		for argument in self.arguments.iter() {
+			cursor.write_all(&argument.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// A response, sent from the device to the desktop
+#[derive(Debug, Clone, PartialEq)]
+pub struct DlpResponse {
+	pub function: DlpFunction,
+	pub error: Option<DlpError>,
+	pub arguments: Vec<DlpArgument>,
+}
+
+impl DlpResponse {
+	/// Create a successful response to the given function
+	pub fn new(function: DlpFunction) -> Self {
+		Self {
+			function,
+			error: None,
+			arguments: Vec::new(),
+		}
+	}
+
+	/// Add an argument with the given ID
+	pub fn with_argument(mut self, id: u8, data: Vec<u8>) -> Self {
+		self.arguments.push(DlpArgument::new(id, data));
+		self
+	}
+
+	/// Return the data of the argument with the given ID, if there is one
+	pub fn argument(&self, id: u8) -> Option<&[u8]> {
+		find_argument(&self.arguments, id)
+	}
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let function = rdr.read_u8()?;
+		if function & DLP_RESPONSE_FLAG == 0 {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"DLP message is not a response",
+			));
+		}
+
+		let count = rdr.read_u8()?;
+		let error = match rdr.read_u16::<BigEndian>()? {
+			0 => None,
+			x => Some(DlpError::from(x)),
+		};
+		let arguments = (0..count)
+			.map(|_| DlpArgument::from_bytes(rdr))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		Ok(Self {
+			function: DlpFunction::from(function & !DLP_RESPONSE_FLAG),
+			error,
+			arguments,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u8(u8::from(self.function) | DLP_RESPONSE_FLAG)?;
+		cursor.write_u8(self.arguments.len() as u8)?;
+		cursor.write_u16::<BigEndian>(self.error.map(u16::from).unwrap_or(0))?;
+		for argument in self.arguments.iter() {
+			cursor.write_all(&argument.to_bytes()?)?;
+		}
+
+		Ok(cursor.into_inner())
+	}
+}
+
+fn find_argument(arguments: &[DlpArgument], id: u8) -> Option<&[u8]> {
+	arguments
+		.iter()
+		.find(|x| x.id == id)
+		.map(|x| x.data.as_slice())
+}
+
+/// Date and time, as used by DLP
+///
+/// A year of zero means that the date isn't set.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct DlpDateTime {
+	pub year: u16,
+	pub month: u8,
+	pub day: u8,
+	pub hour: u8,
+	pub minute: u8,
+	pub second: u8,
+}
+
+impl DlpDateTime {
+	/// Size of an encoded date and time, in bytes
+	pub const SIZE: usize = 8;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let year = rdr.read_u16::<BigEndian>()?;
+		let month = rdr.read_u8()?;
+		let day = rdr.read_u8()?;
+		let hour = rdr.read_u8()?;
+		let minute = rdr.read_u8()?;
+		let second = rdr.read_u8()?;
+		let _pad = rdr.read_u8()?;
+
+		Ok(Self {
+			year,
+			month,
+			day,
+			hour,
+			minute,
+			second,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(Self::SIZE));
+		cursor.write_u16::<BigEndian>(self.year)?;
+		cursor.write_u8(self.month)?;
+		cursor.write_u8(self.day)?;
+		cursor.write_u8(self.hour)?;
+		cursor.write_u8(self.minute)?;
+		cursor.write_u8(self.second)?;
+		cursor.write_u8(0)?;
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Convert to a timestamp, as stored in a database header
+	///
+	/// Unset (or invalid) dates become a zero timestamp.
+	pub fn to_timestamp(&self) -> PalmTimestamp {
+		NaiveDate::from_ymd_opt(self.year as i32, self.month as u32, self.day as u32)
+			.and_then(|x| x.and_hms_opt(self.hour as u32, self.minute as u32, self.second as u32))
+			.map(|x| {
+				PalmTimestamp(unix_ts_to_palm_ts(
+					Utc.from_utc_datetime(&x).timestamp() as i32
+				))
+			})
+			.unwrap_or_default()
+	}
+
+	/// Convert from a timestamp, as stored in a database header
+	pub fn from_timestamp(timestamp: PalmTimestamp) -> Self {
+		if timestamp.0 == 0 {
+			return Self::default();
+		}
+
+		Utc.timestamp_opt(timestamp.as_unix_ts() as i64, 0)
+			.single()
+			.map(|x| Self {
+				year: x.year() as u16,
+				month: x.month() as u8,
+				day: x.day() as u8,
+				hour: x.hour() as u8,
+				minute: x.minute() as u8,
+				second: x.second() as u8,
+			})
+			.unwrap_or_default()
+	}
+}
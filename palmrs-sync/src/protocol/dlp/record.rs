@@ -0,0 +1,185 @@
+//! Records and resources, as transferred by DLP
+//!
+//! Both types convert to and from the [`PdbRecordHeader`] and data pairs stored in a
+//! [`PalmDatabase`][palmrs_database::PalmDatabase].
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use palmrs_database::record::{
+	pdb_record::{PdbRecordHeader, RecordAttributes},
+	DatabaseRecord,
+};
+
+/// Record attribute: the record has been archived
+///
+/// DLP sends the category separately, so this has a bit of its own. In a database file it shares
+/// the bit with the category, as the category of a deleted record doesn't matter.
+pub const DLP_RECORD_ATTRIBUTE_ARCHIVED: u8 = 0x08;
+
+const ATTRIBUTE_FLAGS_MASK: u8 = 0xF0;
+const CATEGORY_MASK: u8 = 0x0F;
+
+/// A record read from, or to be written to, the device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpRecord {
+	pub unique_id: u32,
+
+	/// Index of the record in its database (ignored when writing)
+	pub index: u16,
+
+	pub attributes: RecordAttributes,
+	pub archived: bool,
+	pub data: Vec<u8>,
+}
+
+impl DlpRecord {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let unique_id = rdr.read_u32::<BigEndian>()?;
+		let index = rdr.read_u16::<BigEndian>()?;
+		let size = rdr.read_u16::<BigEndian>()? as usize;
+		let flags = rdr.read_u8()?;
+		let category = rdr.read_u8()?;
+		let mut data = vec![0u8; size];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			unique_id,
+			index,
+			attributes: RecordAttributes::from(
+				(flags & ATTRIBUTE_FLAGS_MASK) | (category & CATEGORY_MASK),
+			),
+			archived: flags & DLP_RECORD_ATTRIBUTE_ARCHIVED != 0,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(self.data.len() + 10));
+		cursor.write_u32::<BigEndian>(self.unique_id)?;
+		cursor.write_u16::<BigEndian>(self.index)?;
+		cursor.write_u16::<BigEndian>(self.data.len() as u16)?;
+		cursor.write_u8(self.flags())?;
+		cursor.write_u8(self.attributes.category & CATEGORY_MASK)?;
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+
+	/// The attribute flags byte, without the category
+	pub(crate) fn flags(&self) -> u8 {
+		let mut flags = u8::from(self.attributes) & ATTRIBUTE_FLAGS_MASK;
+		if self.archived {
+			flags |= DLP_RECORD_ATTRIBUTE_ARCHIVED;
+		}
+
+		flags
+	}
+
+	/// Convert to a record header and data pair, as stored in a database file
+	pub fn into_record(self) -> (PdbRecordHeader, Vec<u8>) {
+		let mut attributes = self.attributes;
+		if self.archived {
+			attributes.category |= DLP_RECORD_ATTRIBUTE_ARCHIVED;
+		}
+
+		let data_len = match self.data.len() {
+			0 => None,
+			other => Some(other as u32),
+		};
+		let header = PdbRecordHeader::construct_record(attributes, self.unique_id, 0, data_len);
+
+		(header, self.data)
+	}
+
+	/// Convert from a record header and data pair, as stored in a database file
+	///
+	/// Returns `None` if the header is for a resource.
+	pub fn from_record(header: &PdbRecordHeader, data: &[u8]) -> Option<Self> {
+		let mut attributes = header.attributes()?;
+		let archived =
+			attributes.delete && attributes.category & DLP_RECORD_ATTRIBUTE_ARCHIVED != 0;
+		if archived {
+			attributes.category &= !DLP_RECORD_ATTRIBUTE_ARCHIVED;
+		}
+
+		Some(Self {
+			unique_id: header.unique_id()?,
+			index: 0,
+			attributes,
+			archived,
+			data: data.to_vec(),
+		})
+	}
+}
+
+/// A resource read from, or to be written to, the device
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct DlpResource {
+	pub type_code: [u8; 4],
+	pub resource_id: u16,
+
+	/// Index of the resource in its database (ignored when writing)
+	pub index: u16,
+
+	pub data: Vec<u8>,
+}
+
+impl DlpResource {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let mut type_code = [0u8; 4];
+		rdr.read_exact(&mut type_code)?;
+		let resource_id = rdr.read_u16::<BigEndian>()?;
+		let index = rdr.read_u16::<BigEndian>()?;
+		let size = rdr.read_u16::<BigEndian>()? as usize;
+		let mut data = vec![0u8; size];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			type_code,
+			resource_id,
+			index,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(self.data.len() + 10));
+		cursor.write_all(&self.type_code)?;
+		cursor.write_u16::<BigEndian>(self.resource_id)?;
+		cursor.write_u16::<BigEndian>(self.index)?;
+		cursor.write_u16::<BigEndian>(self.data.len() as u16)?;
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Convert to a resource header and data pair, as stored in a database file
+	pub fn into_resource(self) -> (PdbRecordHeader, Vec<u8>) {
+		let data_len = match self.data.len() {
+			0 => None,
+			other => Some(other as u32),
+		};
+		let header =
+			PdbRecordHeader::construct_resource(&self.type_code, self.resource_id, 0, data_len);
+
+		(header, self.data)
+	}
+
+	/// Convert from a resource header and data pair, as stored in a database file
+	///
+	/// Returns `None` if the header is for a record.
+	pub fn from_resource(header: &PdbRecordHeader, data: &[u8]) -> Option<Self> {
+		match header {
+			PdbRecordHeader::Resource {
+				name, record_id, ..
+			} => Some(Self {
+				type_code: *name,
+				resource_id: *record_id,
+				index: 0,
+				data: data.to_vec(),
+			}),
+			PdbRecordHeader::Record { .. } => None,
+		}
+	}
+}
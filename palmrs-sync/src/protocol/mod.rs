@@ -0,0 +1,38 @@
+//! HotSync protocol stack
+//!
+//! Serial (and emulator) HotSync layers several protocols on top of each other:
+//!
+//! - [`slp`] - the Serial Link Protocol, which frames packets on the wire, addressing them to
+//!   sockets on each end of the link, and checksums them
+//! - [`padp`] - the Packet Assembly/Disassembly Protocol, which reliably delivers messages of any
+//!   size over SLP, splitting them into acknowledged fragments
+//! - [`cmp`] - the Connection Management Protocol, which starts a session over PADP and negotiates
+//!   the baud rate to use for it
+//! - [`dlp`] - the Desktop Link Protocol, the requests and responses that make up the sync itself
+//!
+//! Network HotSync ([`netsync`]) replaces SLP, PADP and CMP with a single TCP connection, and
+//! carries DLP in the same way.
+//!
+//! Anything that carries whole messages, such as a [`padp::PadpConnection`] or a
+//! [`netsync::NetSyncConnection`], implements [`MessageTransport`], which is all that [`dlp`]
+//! needs.
+
+use std::io;
+
+pub mod cmp;
+pub mod dlp;
+pub mod netsync;
+pub mod padp;
+pub mod slp;
+
+/// A connection that carries whole messages, such as DLP requests and responses
+pub trait MessageTransport {
+	/// Send a message that starts a new exchange
+	fn send_message(&mut self, data: &[u8]) -> Result<(), io::Error>;
+
+	/// Send a message in reply to the last message received
+	fn reply_message(&mut self, data: &[u8]) -> Result<(), io::Error>;
+
+	/// Wait for the next message
+	fn receive_message(&mut self) -> Result<Vec<u8>, io::Error>;
+}
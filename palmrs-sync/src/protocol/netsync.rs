@@ -0,0 +1,423 @@
+//! Network HotSync (NetSync)
+//!
+//! Network HotSync replaces SLP, PADP and CMP with a single TCP connection, to port
+//! [`NETSYNC_DATA_PORT`] on the desktop. Each message is framed with a 6-byte header: the packet
+//! type, a transaction ID, and the length of the message. DLP requests and responses are carried
+//! as messages, as they are over PADP, once both ends have exchanged the fixed handshake
+//! messages.
+//!
+//! Before connecting, a device may broadcast a wakeup packet to UDP port
+//! [`NETSYNC_WAKEUP_PORT`], naming the desktop it wants to sync with. The desktop acknowledges it
+//! by sending the packet back, with its type changed.
+
+use std::{
+	io::{self, Cursor, Read, Write},
+	net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+	time::Duration,
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::protocol::MessageTransport;
+
+/// UDP port that wakeup packets are sent to
+pub const NETSYNC_WAKEUP_PORT: u16 = 14237;
+
+/// TCP port that the desktop listens on for NetSync connections
+pub const NETSYNC_DATA_PORT: u16 = 14238;
+
+/// Size of the header before each message
+pub const NETSYNC_HEADER_SIZE: usize = 6;
+
+/// Largest message that will be accepted
+pub const NETSYNC_MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Default time to wait for a newly connected device to send each handshake message
+pub const NETSYNC_DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Default time to wait for the device to send anything, once the sync has started
+pub const NETSYNC_DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Magic number at the start of a wakeup packet
+pub const NETSYNC_WAKEUP_MAGIC: u16 = 0xFADE;
+
+/// First handshake message, sent by the device
+pub const NETSYNC_HANDSHAKE_DEVICE_1: &[u8; 50] = b"\x90\x01\x00\x00\x00\x00\x00\x00\x00\x20\
+	\x00\x00\x00\x08\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\
+	\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+/// Handshake message sent by the desktop, in reply to the first message from the device
+///
+/// The same bytes as pilot-link's desktop sends, including the address and port it carries,
+/// which devices don't appear to use.
+pub const NETSYNC_HANDSHAKE_DESKTOP: &[u8; 50] = b"\x12\x01\x00\x00\x00\x00\x00\x00\x00\x20\
+	\x00\x00\x00\x24\xff\xff\xff\xff\x3c\x00\x3c\x00\x00\x00\x00\x00\x00\x00\x00\x00\xc0\xa8\
+	\xa5\x1f\x04\x27\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+/// Second handshake message, sent by the device
+pub const NETSYNC_HANDSHAKE_DEVICE_2: &[u8; 22] =
+	b"\x93\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+
+/// Type of a NetSync packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NetSyncPacketType {
+	/// A message
+	Data,
+
+	Unknown(u8),
+}
+
+impl From<u8> for NetSyncPacketType {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::Data,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<NetSyncPacketType> for u8 {
+	fn from(value: NetSyncPacketType) -> Self {
+		match value {
+			NetSyncPacketType::Data => 0x01,
+			NetSyncPacketType::Unknown(x) => x,
+		}
+	}
+}
+
+/// A single NetSync packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetSyncPacket {
+	pub packet_type: NetSyncPacketType,
+	pub transaction_id: u8,
+	pub data: Vec<u8>,
+}
+
+impl NetSyncPacket {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let packet_type = NetSyncPacketType::from(rdr.read_u8()?);
+		let transaction_id = rdr.read_u8()?;
+		let size = rdr.read_u32::<BigEndian>()? as usize;
+		if size > NETSYNC_MAX_MESSAGE_SIZE {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("NetSync message of {} bytes is too large", size),
+			));
+		}
+
+		let mut data = vec![0u8; size];
+		rdr.read_exact(&mut data)?;
+
+		Ok(Self {
+			packet_type,
+			transaction_id,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(NETSYNC_HEADER_SIZE + self.data.len()));
+		cursor.write_u8(self.packet_type.into())?;
+		cursor.write_u8(self.transaction_id)?;
+		cursor.write_u32::<BigEndian>(self.data.len() as u32)?;
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// NetSync message reader and writer, over a byte stream (usually a [`TcpStream`])
+#[derive(Debug)]
+pub struct NetSyncConnection<T: Read + Write> {
+	stream: T,
+
+	/// Transaction ID of the last message sent or received
+	transaction_id: u8,
+}
+
+impl<T: Read + Write> NetSyncConnection<T> {
+	pub fn new(stream: T) -> Self {
+		Self {
+			stream,
+			transaction_id: 0,
+		}
+	}
+
+	pub fn get_ref(&self) -> &T {
+		&self.stream
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.stream
+	}
+
+	pub fn into_inner(self) -> T {
+		self.stream
+	}
+
+	/// Write a packet to the stream
+	pub fn write_packet(&mut self, packet: &NetSyncPacket) -> Result<(), io::Error> {
+		self.stream.write_all(&packet.to_bytes()?)?;
+		self.stream.flush()
+	}
+
+	/// Read the next packet from the stream
+	pub fn read_packet(&mut self) -> Result<NetSyncPacket, io::Error> {
+		let mut header = [0u8; NETSYNC_HEADER_SIZE];
+		self.stream.read_exact(&mut header)?;
+
+		let size = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+		if size > NETSYNC_MAX_MESSAGE_SIZE {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				format!("NetSync message of {} bytes is too large", size),
+			));
+		}
+
+		let mut data = vec![0u8; size];
+		self.stream.read_exact(&mut data)?;
+
+		Ok(NetSyncPacket {
+			packet_type: NetSyncPacketType::from(header[0]),
+			transaction_id: header[1],
+			data,
+		})
+	}
+
+	/// Send a message with the given transaction ID
+	fn write_message(&mut self, transaction_id: u8, data: &[u8]) -> Result<(), io::Error> {
+		self.transaction_id = transaction_id;
+		self.write_packet(&NetSyncPacket {
+			packet_type: NetSyncPacketType::Data,
+			transaction_id,
+			data: data.to_vec(),
+		})
+	}
+
+	/// Exchange the handshake messages, on the desktop side
+	pub fn accept_handshake(&mut self) -> Result<(), io::Error> {
+		self.receive_message()?;
+		self.reply_message(NETSYNC_HANDSHAKE_DESKTOP)?;
+		self.receive_message()?;
+
+		Ok(())
+	}
+
+	/// Exchange the handshake messages, on the device side
+	pub fn connect_handshake(&mut self) -> Result<(), io::Error> {
+		self.send_message(NETSYNC_HANDSHAKE_DEVICE_1)?;
+		self.receive_message()?;
+		self.send_message(NETSYNC_HANDSHAKE_DEVICE_2)?;
+
+		Ok(())
+	}
+}
+
+impl<T: Read + Write> MessageTransport for NetSyncConnection<T> {
+	fn send_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		let transaction_id = match self.transaction_id {
+			0xFE | 0xFF => 0x01,
+			x => x + 1,
+		};
+
+		self.write_message(transaction_id, data)
+	}
+
+	fn reply_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		self.write_message(self.transaction_id, data)
+	}
+
+	fn receive_message(&mut self) -> Result<Vec<u8>, io::Error> {
+		loop {
+			let packet = self.read_packet()?;
+			if packet.packet_type == NetSyncPacketType::Data {
+				self.transaction_id = packet.transaction_id;
+				return Ok(packet.data);
+			}
+		}
+	}
+}
+
+/// Listener for NetSync connections, on the desktop side
+///
+/// Accepted connections have a read timeout, so a device that stops responding fails the sync
+/// rather than holding it up forever.
+#[derive(Debug)]
+pub struct NetSyncListener {
+	listener: TcpListener,
+
+	/// Time to wait for each of the device's handshake messages
+	pub handshake_timeout: Duration,
+
+	/// Time to wait for the device to send anything, once the handshake is done
+	pub timeout: Duration,
+}
+
+impl NetSyncListener {
+	/// Listen on the given address (usually port [`NETSYNC_DATA_PORT`] on all interfaces)
+	pub fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, io::Error> {
+		Ok(Self {
+			listener: TcpListener::bind(addr)?,
+			handshake_timeout: NETSYNC_DEFAULT_HANDSHAKE_TIMEOUT,
+			timeout: NETSYNC_DEFAULT_TIMEOUT,
+		})
+	}
+
+	pub fn get_ref(&self) -> &TcpListener {
+		&self.listener
+	}
+
+	pub fn local_addr(&self) -> Result<SocketAddr, io::Error> {
+		self.listener.local_addr()
+	}
+
+	/// Wait for a device to connect, and exchange the handshake messages with it
+	pub fn accept(&self) -> Result<(NetSyncConnection<TcpStream>, SocketAddr), io::Error> {
+		self.accept_with(Ok)
+	}
+
+	/// Wait for a device to connect, wrap the stream (such as in a
+	/// [`CaptureStream`][crate::capture::CaptureStream]), and exchange the handshake messages over
+	/// it
+	pub fn accept_with<T, F>(
+		&self,
+		wrap: F,
+	) -> Result<(NetSyncConnection<T>, SocketAddr), io::Error>
+	where
+		T: Read + Write,
+		F: FnOnce(TcpStream) -> Result<T, io::Error>,
+	{
+		let (stream, addr) = self.listener.accept()?;
+		stream.set_nodelay(true)?;
+		stream.set_read_timeout(Some(self.handshake_timeout))?;
+
+		// Clones share the socket, so the timeout can still be changed once it's wrapped
+		let socket = stream.try_clone()?;
+		let mut connection = NetSyncConnection::new(wrap(stream)?);
+		connection.accept_handshake()?;
+		socket.set_read_timeout(Some(self.timeout))?;
+
+		Ok((connection, addr))
+	}
+}
+
+/// Type of a wakeup packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NetSyncWakeupType {
+	/// Sent by the device, to ask a desktop to sync
+	Request,
+
+	/// Sent back by the desktop, to accept
+	Acknowledge,
+
+	Unknown(u8),
+}
+
+impl From<u8> for NetSyncWakeupType {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::Request,
+			0x02 => Self::Acknowledge,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<NetSyncWakeupType> for u8 {
+	fn from(value: NetSyncWakeupType) -> Self {
+		match value {
+			NetSyncWakeupType::Request => 0x01,
+			NetSyncWakeupType::Acknowledge => 0x02,
+			NetSyncWakeupType::Unknown(x) => x,
+		}
+	}
+}
+
+/// A wakeup (discovery) packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetSyncWakeup {
+	pub packet_type: NetSyncWakeupType,
+
+	/// Address of the desktop that the device wants to sync with
+	pub host_address: Ipv4Addr,
+	pub host_netmask: Ipv4Addr,
+
+	/// Name of the desktop that the device wants to sync with
+	pub host_name: String,
+}
+
+impl NetSyncWakeup {
+	/// Longest host name that fits in a wakeup packet, excluding the null terminator
+	pub const MAX_HOST_NAME_LEN: usize = 128;
+
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		if rdr.read_u16::<BigEndian>()? != NETSYNC_WAKEUP_MAGIC {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"not a NetSync wakeup packet",
+			));
+		}
+
+		let packet_type = NetSyncWakeupType::from(rdr.read_u8()?);
+		let _unknown = rdr.read_u8()?;
+		let host_address = Ipv4Addr::from(rdr.read_u32::<BigEndian>()?);
+		let host_netmask = Ipv4Addr::from(rdr.read_u32::<BigEndian>()?);
+
+		let mut host_name = Vec::new();
+		rdr.read_to_end(&mut host_name)?;
+		if let Some(end) = host_name.iter().position(|&x| x == 0) {
+			host_name.truncate(end);
+		}
+
+		Ok(Self {
+			packet_type,
+			host_address,
+			host_netmask,
+			host_name: String::from_utf8_lossy(&host_name).into_owned(),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		if self.host_name.len() > Self::MAX_HOST_NAME_LEN {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"host name is too long for a NetSync wakeup packet",
+			));
+		}
+
+		let mut cursor = Cursor::new(Vec::new());
+		cursor.write_u16::<BigEndian>(NETSYNC_WAKEUP_MAGIC)?;
+		cursor.write_u8(self.packet_type.into())?;
+		cursor.write_u8(0)?;
+		cursor.write_u32::<BigEndian>(self.host_address.into())?;
+		cursor.write_u32::<BigEndian>(self.host_netmask.into())?;
+		cursor.write_all(self.host_name.as_bytes())?;
+		cursor.write_u8(0)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Wait for a wakeup request on the given socket, and acknowledge it
+///
+/// Packets that aren't wakeup requests are ignored. Returns the request, and the address it came
+/// from; the device will then connect to [`NETSYNC_DATA_PORT`].
+pub fn answer_wakeup(socket: &UdpSocket) -> Result<(NetSyncWakeup, SocketAddr), io::Error> {
+	let mut buf = [0u8; 512];
+
+	loop {
+		let (len, addr) = socket.recv_from(&mut buf)?;
+		let wakeup = match NetSyncWakeup::from_bytes(&mut Cursor::new(&buf[..len])) {
+			Ok(x) if x.packet_type == NetSyncWakeupType::Request => x,
+			_ => continue,
+		};
+
+		let ack = NetSyncWakeup {
+			packet_type: NetSyncWakeupType::Acknowledge,
+			..wakeup.clone()
+		};
+		socket.send_to(&ack.to_bytes()?, addr)?;
+
+		return Ok((wakeup, addr));
+	}
+}
@@ -0,0 +1,438 @@
+//! Packet Assembly/Disassembly Protocol (PADP)
+//!
+//! PADP adds reliable delivery of messages of any size on top of [SLP][crate::protocol::slp].
+//! Messages are split into fragments of up to [`PADP_MAX_FRAGMENT_SIZE`] bytes, each sent in its
+//! own SLP packet with a PADP header: the packet type, flags marking the first and last fragments
+//! of a message, and a size. The size is the total length of the message in the first fragment,
+//! and the offset of the fragment within the message in the others. PADP 2.0 added a long form of
+//! the header, with a 32-bit size, for messages of 64 KiB or more.
+//!
+//! Every data fragment is acknowledged by the receiver with an ack packet, with the same
+//! transaction ID, flags and size. Fragments that aren't acknowledged in time are sent again, and
+//! fragments that are received twice (because the ack was lost) are acknowledged again but
+//! otherwise ignored. Tickle packets keep the connection alive while one side is busy, and aren't
+//! acknowledged.
+
+use std::{
+	io::{self, Cursor, Read, Write},
+	time::{Duration, Instant},
+};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::protocol::{
+	slp::{SlpConnection, SlpPacket, SlpPacketType, SlpSocket},
+	MessageTransport,
+};
+
+/// Flag: this is the first fragment of a message
+pub const PADP_FLAG_FIRST: u8 = 0x80;
+
+/// Flag: this is the last fragment of a message
+pub const PADP_FLAG_LAST: u8 = 0x40;
+
+/// Flag (in acks): the receiver ran out of memory for the message
+pub const PADP_FLAG_MEMORY_ERROR: u8 = 0x20;
+
+/// Flag: the header has a 32-bit size (PADP 2.0)
+pub const PADP_FLAG_LONG_FORM: u8 = 0x10;
+
+/// Largest amount of message data sent in a single fragment
+pub const PADP_MAX_FRAGMENT_SIZE: usize = 1024;
+
+/// Default time to wait for a fragment to be acknowledged before sending it again
+pub const PADP_DEFAULT_ACK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Default number of times to send a fragment again before giving up
+pub const PADP_DEFAULT_RETRIES: u32 = 10;
+
+/// Default time to wait for the next packet of an incoming message before giving up
+pub const PADP_DEFAULT_RECEIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Type of a PADP packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PadpPacketType {
+	/// A fragment of a message
+	Data,
+
+	/// Acknowledgement of a data fragment
+	Ack,
+
+	/// Keep-alive, which isn't acknowledged
+	Tickle,
+
+	/// The sender is abandoning the connection
+	Abort,
+
+	Unknown(u8),
+}
+
+impl From<u8> for PadpPacketType {
+	fn from(value: u8) -> Self {
+		match value {
+			0x01 => Self::Data,
+			0x02 => Self::Ack,
+			0x04 => Self::Tickle,
+			0x08 => Self::Abort,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<PadpPacketType> for u8 {
+	fn from(value: PadpPacketType) -> Self {
+		match value {
+			PadpPacketType::Data => 0x01,
+			PadpPacketType::Ack => 0x02,
+			PadpPacketType::Tickle => 0x04,
+			PadpPacketType::Abort => 0x08,
+			PadpPacketType::Unknown(x) => x,
+		}
+	}
+}
+
+/// A single PADP packet, as carried in the body of an SLP packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct PadpPacket {
+	pub packet_type: PadpPacketType,
+	pub flags: u8,
+
+	/// Total size of the message in the first fragment, otherwise the offset of this fragment
+	pub size: u32,
+	pub data: Vec<u8>,
+}
+
+impl PadpPacket {
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let packet_type = PadpPacketType::from(rdr.read_u8()?);
+		let flags = rdr.read_u8()?;
+		let size = if flags & PADP_FLAG_LONG_FORM != 0 {
+			rdr.read_u32::<BigEndian>()?
+		} else {
+			rdr.read_u16::<BigEndian>()? as u32
+		};
+
+		let mut data = Vec::new();
+		rdr.read_to_end(&mut data)?;
+
+		Ok(Self {
+			packet_type,
+			flags,
+			size,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		let mut cursor = Cursor::new(Vec::with_capacity(6 + self.data.len()));
+		cursor.write_u8(self.packet_type.into())?;
+		if self.flags & PADP_FLAG_LONG_FORM != 0 {
+			cursor.write_u8(self.flags)?;
+			cursor.write_u32::<BigEndian>(self.size)?;
+		} else if self.size > 0xFFFF {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"PADP size needs the long form header",
+			));
+		} else {
+			cursor.write_u8(self.flags)?;
+			cursor.write_u16::<BigEndian>(self.size as u16)?;
+		}
+		cursor.write_all(&self.data)?;
+
+		Ok(cursor.into_inner())
+	}
+
+	/// Build the acknowledgement of this packet
+	fn ack(&self) -> Self {
+		Self {
+			packet_type: PadpPacketType::Ack,
+			flags: self.flags,
+			size: self.size,
+			data: Vec::new(),
+		}
+	}
+}
+
+/// Whether the error is a transport read timing out, rather than failing
+fn is_timeout(e: &io::Error) -> bool {
+	matches!(
+		e.kind(),
+		io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+	)
+}
+
+/// Reliable message delivery over an SLP connection
+///
+/// Waiting for acks relies on the transport's reads timing out (with [`io::ErrorKind::TimedOut`]
+/// or [`io::ErrorKind::WouldBlock`]) when no data arrives, such as a serial port with a read
+/// timeout set. Reads that time out before [`ack_timeout`][Self::ack_timeout] has passed are
+/// retried, so the transport's timeout can be shorter.
+#[derive(Debug)]
+pub struct PadpConnection<T: Read + Write> {
+	slp: SlpConnection<T>,
+
+	/// Socket of this end of the connection
+	pub local_socket: SlpSocket,
+
+	/// Socket of the other end of the connection
+	pub remote_socket: SlpSocket,
+
+	/// Time to wait for a fragment to be acknowledged before sending it again
+	pub ack_timeout: Duration,
+
+	/// Number of times to send a fragment again before giving up
+	pub retries: u32,
+
+	/// Time to wait for the next packet while receiving, before giving up on the other end
+	pub receive_timeout: Duration,
+
+	/// Transaction ID, flags and size of the last data fragment received, to spot duplicates
+	last_received: Option<(u8, u8, u32)>,
+
+	/// Transaction ID of the last message received, which replies are sent with
+	reply_transaction_id: Option<u8>,
+}
+
+impl<T: Read + Write> PadpConnection<T> {
+	/// Start a connection between the given sockets (normally both [`SlpSocket::DesktopLink`])
+	pub fn new(slp: SlpConnection<T>, local_socket: SlpSocket, remote_socket: SlpSocket) -> Self {
+		Self {
+			slp,
+			local_socket,
+			remote_socket,
+			ack_timeout: PADP_DEFAULT_ACK_TIMEOUT,
+			retries: PADP_DEFAULT_RETRIES,
+			receive_timeout: PADP_DEFAULT_RECEIVE_TIMEOUT,
+			last_received: None,
+			reply_transaction_id: None,
+		}
+	}
+
+	pub fn get_ref(&self) -> &SlpConnection<T> {
+		&self.slp
+	}
+
+	pub fn get_mut(&mut self) -> &mut SlpConnection<T> {
+		&mut self.slp
+	}
+
+	pub fn into_inner(self) -> SlpConnection<T> {
+		self.slp
+	}
+
+	/// Send a message, starting a new transaction
+	pub fn send(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		// Don't reuse the ID of the last received message, which the other end would take to be a
+		// duplicate
+		let mut transaction_id = self.slp.next_transaction_id();
+		if Some(transaction_id) == self.reply_transaction_id {
+			transaction_id = self.slp.next_transaction_id();
+		}
+
+		self.send_with_id(data, transaction_id)
+	}
+
+	/// Send a message in reply to the last message received, using its transaction ID
+	pub fn reply(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		let transaction_id = self
+			.reply_transaction_id
+			.ok_or_else(|| io::Error::other("no PADP message has been received to reply to"))?;
+
+		self.send_with_id(data, transaction_id)
+	}
+
+	/// Send a tickle, to keep the connection alive
+	pub fn tickle(&mut self) -> Result<(), io::Error> {
+		let transaction_id = self.slp.next_transaction_id();
+		let packet = PadpPacket {
+			packet_type: PadpPacketType::Tickle,
+			flags: PADP_FLAG_FIRST | PADP_FLAG_LAST,
+			size: 0,
+			data: Vec::new(),
+		};
+
+		self.write(&packet, transaction_id)
+	}
+
+	fn send_with_id(&mut self, data: &[u8], transaction_id: u8) -> Result<(), io::Error> {
+		let long_form = if data.len() > 0xFFFF {
+			PADP_FLAG_LONG_FORM
+		} else {
+			0
+		};
+
+		let mut offset = 0;
+		loop {
+			let end = data.len().min(offset + PADP_MAX_FRAGMENT_SIZE);
+			let mut flags = long_form;
+			if offset == 0 {
+				flags |= PADP_FLAG_FIRST;
+			}
+			if end == data.len() {
+				flags |= PADP_FLAG_LAST;
+			}
+
+			let packet = PadpPacket {
+				packet_type: PadpPacketType::Data,
+				flags,
+				size: if offset == 0 { data.len() } else { offset } as u32,
+				data: data[offset..end].to_vec(),
+			};
+			self.send_fragment(&packet, transaction_id)?;
+
+			offset = end;
+			if offset >= data.len() {
+				return Ok(());
+			}
+		}
+	}
+
+	/// Send a data fragment, and wait for it to be acknowledged, sending it again if needed
+	fn send_fragment(&mut self, packet: &PadpPacket, transaction_id: u8) -> Result<(), io::Error> {
+		for _attempt in 0..=self.retries {
+			self.write(packet, transaction_id)?;
+
+			let deadline = Instant::now() + self.ack_timeout;
+			while let Some((slp, received)) = self.read(deadline)? {
+				match received.packet_type {
+					PadpPacketType::Ack if slp.transaction_id == transaction_id => {
+						if received.flags & PADP_FLAG_MEMORY_ERROR != 0 {
+							return Err(io::Error::other(
+								"remote ran out of memory for the PADP message",
+							));
+						}
+
+						return Ok(());
+					}
+					// The other end didn't get our ack for its last fragment
+					PadpPacketType::Data if self.is_duplicate(&slp, &received) => {
+						self.write(&received.ack(), slp.transaction_id)?;
+					}
+					PadpPacketType::Abort => {
+						return Err(io::Error::new(
+							io::ErrorKind::ConnectionAborted,
+							"remote aborted the PADP connection",
+						))
+					}
+					_ => {}
+				}
+			}
+		}
+
+		Err(io::Error::new(
+			io::ErrorKind::TimedOut,
+			"PADP fragment was not acknowledged",
+		))
+	}
+
+	/// Receive the next message
+	///
+	/// This fails with [`io::ErrorKind::TimedOut`] if nothing arrives for
+	/// [`receive_timeout`][Self::receive_timeout], including tickles.
+	pub fn receive(&mut self) -> Result<Vec<u8>, io::Error> {
+		let mut message: Option<(u8, usize, Vec<u8>)> = None;
+
+		loop {
+			let deadline = Instant::now() + self.receive_timeout;
+			let (slp, packet) = self.read(deadline)?.ok_or_else(|| {
+				io::Error::new(io::ErrorKind::TimedOut, "timed out waiting for PADP data")
+			})?;
+
+			match packet.packet_type {
+				PadpPacketType::Data => {}
+				PadpPacketType::Abort => {
+					return Err(io::Error::new(
+						io::ErrorKind::ConnectionAborted,
+						"remote aborted the PADP connection",
+					))
+				}
+				_ => continue,
+			}
+
+			// Acknowledge everything, but only use fragments we haven't seen before
+			self.write(&packet.ack(), slp.transaction_id)?;
+			if self.is_duplicate(&slp, &packet) {
+				continue;
+			}
+			self.last_received = Some((slp.transaction_id, packet.flags, packet.size));
+
+			if packet.flags & PADP_FLAG_FIRST != 0 {
+				message = Some((slp.transaction_id, packet.size as usize, Vec::new()));
+			}
+
+			let complete = match &mut message {
+				Some((transaction_id, size, data))
+					if *transaction_id == slp.transaction_id
+						&& (packet.flags & PADP_FLAG_FIRST != 0
+							|| packet.size as usize == data.len()) =>
+				{
+					data.extend_from_slice(&packet.data);
+					packet.flags & PADP_FLAG_LAST != 0 || data.len() >= *size
+				}
+
+				// A fragment from the middle of a message we didn't see the start of
+				_ => continue,
+			};
+
+			if complete {
+				let (transaction_id, size, mut data) = message.take().unwrap();
+				data.truncate(size);
+				self.reply_transaction_id = Some(transaction_id);
+				return Ok(data);
+			}
+		}
+	}
+
+	fn is_duplicate(&self, slp: &SlpPacket, packet: &PadpPacket) -> bool {
+		self.last_received == Some((slp.transaction_id, packet.flags, packet.size))
+	}
+
+	fn write(&mut self, packet: &PadpPacket, transaction_id: u8) -> Result<(), io::Error> {
+		self.slp.write_packet(&SlpPacket {
+			destination: self.remote_socket,
+			source: self.local_socket,
+			packet_type: SlpPacketType::Padp,
+			transaction_id,
+			data: packet.to_bytes()?,
+		})
+	}
+
+	/// Read the next PADP packet addressed to this socket, returning `None` if the deadline
+	/// passes first
+	fn read(&mut self, deadline: Instant) -> Result<Option<(SlpPacket, PadpPacket)>, io::Error> {
+		loop {
+			if Instant::now() >= deadline {
+				return Ok(None);
+			}
+
+			let slp = match self.slp.read_packet() {
+				Ok(x) => x,
+				Err(e) if is_timeout(&e) => continue,
+				Err(e) => return Err(e),
+			};
+
+			if slp.packet_type != SlpPacketType::Padp || slp.destination != self.local_socket {
+				continue;
+			}
+
+			if let Ok(packet) = PadpPacket::from_bytes(&mut Cursor::new(&slp.data)) {
+				return Ok(Some((slp, packet)));
+			}
+		}
+	}
+}
+
+impl<T: Read + Write> MessageTransport for PadpConnection<T> {
+	fn send_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		self.send(data)
+	}
+
+	fn reply_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		self.reply(data)
+	}
+
+	fn receive_message(&mut self) -> Result<Vec<u8>, io::Error> {
+		self.receive()
+	}
+}
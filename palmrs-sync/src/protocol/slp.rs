@@ -0,0 +1,332 @@
+//! Serial Link Protocol (SLP)
+//!
+//! SLP is the lowest layer of the serial HotSync protocol stack. Each packet is framed with a
+//! 10-byte header, made up of the `BE EF ED` preamble, the destination and source socket IDs, the
+//! packet type, the length of the body, a transaction ID, and an 8-bit checksum (the sum of the
+//! preceding header bytes). The body follows, and then a CRC-16 (CCITT, as used by XMODEM) of the
+//! header and body.
+//!
+//! [`SlpConnection`] reads and writes packets over any `Read + Write` transport. When reading,
+//! bytes that aren't part of a valid packet (such as line noise, or a packet with a bad checksum
+//! or CRC) are skipped, resynchronising on the next preamble.
+
+use std::io::{self, Cursor, Read, Write};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+/// Preamble that starts every SLP packet
+pub const SLP_PREAMBLE: &[u8; 3] = &[0xBE, 0xEF, 0xED];
+
+/// Size of the packet header, including the preamble and the header checksum
+pub const SLP_HEADER_SIZE: usize = 10;
+
+/// Size of the CRC that follows the packet body
+pub const SLP_FOOTER_SIZE: usize = 2;
+
+/// Largest packet body that can be sent
+pub const SLP_MAX_BODY_SIZE: usize = 0xFFFF;
+
+/// Calculate the CRC-16 (CCITT polynomial `0x1021`, initial value zero) of the given data
+pub fn crc16(data: &[u8]) -> u16 {
+	data.iter().fold(0u16, |crc, &b| {
+		(0..8).fold(crc ^ ((b as u16) << 8), |crc, _| {
+			if crc & 0x8000 != 0 {
+				(crc << 1) ^ 0x1021
+			} else {
+				crc << 1
+			}
+		})
+	})
+}
+
+/// Well-known SLP socket IDs
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SlpSocket {
+	/// Debugger
+	Debugger,
+
+	/// Console
+	Console,
+
+	/// Remote UI
+	RemoteUi,
+
+	/// Desktop Link, which HotSync uses
+	DesktopLink,
+
+	Unknown(u8),
+}
+
+impl From<u8> for SlpSocket {
+	fn from(value: u8) -> Self {
+		match value {
+			0x00 => Self::Debugger,
+			0x01 => Self::Console,
+			0x02 => Self::RemoteUi,
+			0x03 => Self::DesktopLink,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<SlpSocket> for u8 {
+	fn from(value: SlpSocket) -> Self {
+		match value {
+			SlpSocket::Debugger => 0x00,
+			SlpSocket::Console => 0x01,
+			SlpSocket::RemoteUi => 0x02,
+			SlpSocket::DesktopLink => 0x03,
+			SlpSocket::Unknown(x) => x,
+		}
+	}
+}
+
+/// Type of the data in an SLP packet
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SlpPacketType {
+	/// Remote debugger and console system packet
+	System,
+
+	/// Packetized Assembly/Disassembly Protocol (PADP) packet
+	Padp,
+
+	/// Loop-back test packet
+	LoopBack,
+
+	Unknown(u8),
+}
+
+impl From<u8> for SlpPacketType {
+	fn from(value: u8) -> Self {
+		match value {
+			0x00 => Self::System,
+			0x02 => Self::Padp,
+			0x03 => Self::LoopBack,
+			x => Self::Unknown(x),
+		}
+	}
+}
+
+impl From<SlpPacketType> for u8 {
+	fn from(value: SlpPacketType) -> Self {
+		match value {
+			SlpPacketType::System => 0x00,
+			SlpPacketType::Padp => 0x02,
+			SlpPacketType::LoopBack => 0x03,
+			SlpPacketType::Unknown(x) => x,
+		}
+	}
+}
+
+/// A single SLP packet
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlpPacket {
+	pub destination: SlpSocket,
+	pub source: SlpSocket,
+	pub packet_type: SlpPacketType,
+	pub transaction_id: u8,
+	pub data: Vec<u8>,
+}
+
+impl SlpPacket {
+	/// Read a packet, checking the preamble, header checksum and CRC
+	pub fn from_bytes(rdr: &mut Cursor<&[u8]>) -> Result<Self, io::Error> {
+		let start = rdr.position() as usize;
+
+		let mut header = [0u8; SLP_HEADER_SIZE];
+		rdr.read_exact(&mut header)?;
+		if &header[..3] != SLP_PREAMBLE {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"SLP packet does not start with the preamble",
+			));
+		}
+		if header_checksum(&header) != header[9] {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"SLP header checksum mismatch",
+			));
+		}
+
+		let mut hdr = Cursor::new(&header[3..]);
+		let destination = SlpSocket::from(hdr.read_u8()?);
+		let source = SlpSocket::from(hdr.read_u8()?);
+		let packet_type = SlpPacketType::from(hdr.read_u8()?);
+		let size = hdr.read_u16::<BigEndian>()?;
+		let transaction_id = hdr.read_u8()?;
+
+		let mut data = vec![0u8; size as usize];
+		rdr.read_exact(&mut data)?;
+
+		let end = rdr.position() as usize;
+		let crc = rdr.read_u16::<BigEndian>()?;
+		if crc16(&rdr.get_ref()[start..end]) != crc {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidData,
+				"SLP packet CRC mismatch",
+			));
+		}
+
+		Ok(Self {
+			destination,
+			source,
+			packet_type,
+			transaction_id,
+			data,
+		})
+	}
+
+	pub fn to_bytes(&self) -> Result<Vec<u8>, io::Error> {
+		if self.data.len() > SLP_MAX_BODY_SIZE {
+			return Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				"SLP packet body is too large",
+			));
+		}
+
+		let mut cursor = Cursor::new(Vec::with_capacity(
+			SLP_HEADER_SIZE + self.data.len() + SLP_FOOTER_SIZE,
+		));
+		cursor.write_all(SLP_PREAMBLE)?;
+		cursor.write_u8(self.destination.into())?;
+		cursor.write_u8(self.source.into())?;
+		cursor.write_u8(self.packet_type.into())?;
+		cursor.write_u16::<BigEndian>(self.data.len() as u16)?;
+		cursor.write_u8(self.transaction_id)?;
+		let checksum = header_checksum(cursor.get_ref());
+		cursor.write_u8(checksum)?;
+		cursor.write_all(&self.data)?;
+		let crc = crc16(cursor.get_ref());
+		cursor.write_u16::<BigEndian>(crc)?;
+
+		Ok(cursor.into_inner())
+	}
+}
+
+/// Sum of the header bytes before the checksum
+fn header_checksum(header: &[u8]) -> u8 {
+	header[..SLP_HEADER_SIZE - 1]
+		.iter()
+		.fold(0u8, |sum, &b| sum.wrapping_add(b))
+}
+
+/// SLP packet reader and writer, over a byte stream transport
+#[derive(Debug)]
+pub struct SlpConnection<T: Read + Write> {
+	transport: T,
+
+	/// Received bytes that haven't been consumed as a packet yet
+	buffer: Vec<u8>,
+
+	/// Last transaction ID handed out by [`SlpConnection::next_transaction_id`]
+	transaction_id: u8,
+}
+
+impl<T: Read + Write> SlpConnection<T> {
+	pub fn new(transport: T) -> Self {
+		Self {
+			transport,
+			buffer: Vec::new(),
+			transaction_id: 0,
+		}
+	}
+
+	pub fn get_ref(&self) -> &T {
+		&self.transport
+	}
+
+	pub fn get_mut(&mut self) -> &mut T {
+		&mut self.transport
+	}
+
+	pub fn into_inner(self) -> T {
+		self.transport
+	}
+
+	/// Return a new transaction ID, for a packet that starts a new transaction
+	///
+	/// Transaction IDs `0x00` and `0xFF` are reserved, so these are skipped.
+	pub fn next_transaction_id(&mut self) -> u8 {
+		self.transaction_id = match self.transaction_id {
+			0xFE | 0xFF => 0x01,
+			x => x + 1,
+		};
+
+		self.transaction_id
+	}
+
+	/// Write a packet to the transport
+	pub fn write_packet(&mut self, packet: &SlpPacket) -> Result<(), io::Error> {
+		self.transport.write_all(&packet.to_bytes()?)?;
+		self.transport.flush()
+	}
+
+	/// Read the next valid packet from the transport, skipping any invalid data before it
+	///
+	/// Returns an [`io::ErrorKind::UnexpectedEof`] error if the transport reaches the end of its
+	/// data before a complete packet has been read.
+	pub fn read_packet(&mut self) -> Result<SlpPacket, io::Error> {
+		loop {
+			if let Some(packet) = self.take_packet()? {
+				return Ok(packet);
+			}
+
+			let mut buf = [0u8; 1024];
+			let len = match self.transport.read(&mut buf) {
+				Ok(0) => {
+					return Err(io::Error::new(
+						io::ErrorKind::UnexpectedEof,
+						"transport closed before a complete SLP packet was received",
+					))
+				}
+				Ok(len) => len,
+				Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+				Err(e) => return Err(e),
+			};
+			self.buffer.extend_from_slice(&buf[..len]);
+		}
+	}
+
+	/// Take the first valid packet out of the receive buffer, if there is a complete one
+	fn take_packet(&mut self) -> Result<Option<SlpPacket>, io::Error> {
+		loop {
+			// Drop everything before the first preamble, keeping a partial preamble at the end
+			let start = self
+				.buffer
+				.windows(SLP_PREAMBLE.len())
+				.position(|x| x == SLP_PREAMBLE)
+				.unwrap_or_else(|| {
+					let tail = self.buffer.len().min(SLP_PREAMBLE.len() - 1);
+					self.buffer.len() - tail
+				});
+			self.buffer.drain(..start);
+
+			if self.buffer.len() < SLP_HEADER_SIZE {
+				return Ok(None);
+			}
+
+			// If the header is corrupt, the preamble was probably noise, so look for the next one
+			if header_checksum(&self.buffer) != self.buffer[SLP_HEADER_SIZE - 1] {
+				self.buffer.drain(..1);
+				continue;
+			}
+
+			let size = u16::from_be_bytes([self.buffer[6], self.buffer[7]]) as usize;
+			let len = SLP_HEADER_SIZE + size + SLP_FOOTER_SIZE;
+			if self.buffer.len() < len {
+				return Ok(None);
+			}
+
+			match SlpPacket::from_bytes(&mut Cursor::new(&self.buffer[..len])) {
+				Ok(packet) => {
+					self.buffer.drain(..len);
+					return Ok(Some(packet));
+				}
+				Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+					self.buffer.drain(..1);
+				}
+				Err(e) => return Err(e),
+			}
+		}
+	}
+}
@@ -0,0 +1,277 @@
+//! Serial transports for HotSync
+//!
+//! Serial HotSync runs the [protocol stack][crate::protocol] over a [`SerialLink`]: either a
+//! serial device (such as a cradle on `/dev/ttyS0`, or a USB-serial adapter on `/dev/ttyUSB0`),
+//! opened in raw mode with [`SerialPort`], or a TCP socket, which is how emulators such as POSE
+//! and CloudpilotEmu expose their emulated serial port.
+//!
+//! Every session starts at [`CMP_INITIAL_BAUD_RATE`], and then switches to the rate agreed with
+//! CMP, which [`accept_session`] takes care of.
+//!
+//! PADP relies on reads from the link timing out to notice lost acks, so links opened with
+//! [`SerialEndpoint::open`] have a read timeout of [`PADP_DEFAULT_ACK_TIMEOUT`].
+
+use core::str::FromStr;
+use std::{
+	io::{self, Read, Write},
+	net::{TcpListener, TcpStream},
+	path::PathBuf,
+	time::Duration,
+};
+
+use crate::protocol::{
+	cmp::{self, CmpSession, CMP_INITIAL_BAUD_RATE},
+	padp::{PadpConnection, PADP_DEFAULT_ACK_TIMEOUT},
+};
+
+/// A byte stream that a serial HotSync session can run over
+pub trait SerialLink: Read + Write {
+	/// Switch to the given baud rate, once everything written so far has been sent
+	fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), io::Error>;
+
+	/// Set how long reads wait for data before failing with [`io::ErrorKind::TimedOut`] (or
+	/// [`io::ErrorKind::WouldBlock`]), or `None` to wait forever
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error>;
+}
+
+/// Emulators don't have a baud rate, so switching is a no-op
+impl SerialLink for TcpStream {
+	fn set_baud_rate(&mut self, _baud_rate: u32) -> Result<(), io::Error> {
+		Ok(())
+	}
+
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+		TcpStream::set_read_timeout(self, timeout)
+	}
+}
+
+impl<T: SerialLink + ?Sized> SerialLink for Box<T> {
+	fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), io::Error> {
+		(**self).set_baud_rate(baud_rate)
+	}
+
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+		(**self).set_read_timeout(timeout)
+	}
+}
+
+impl<T: SerialLink + ?Sized> SerialLink for &mut T {
+	fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), io::Error> {
+		(**self).set_baud_rate(baud_rate)
+	}
+
+	fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+		(**self).set_read_timeout(timeout)
+	}
+}
+
+/// Wait for a device to start a session over a serial link, and switch the link to the agreed
+/// baud rate
+pub fn accept_session<T: SerialLink>(
+	padp: &mut PadpConnection<T>,
+	supported_baud_rates: &[u32],
+) -> Result<CmpSession, io::Error> {
+	let session = cmp::accept(padp, supported_baud_rates)?;
+	padp.get_mut().get_mut().set_baud_rate(session.baud_rate)?;
+
+	Ok(session)
+}
+
+/// Where to find the device (or emulator) for a serial sync
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerialEndpoint {
+	/// A serial device, such as `/dev/ttyUSB0`
+	Device(PathBuf),
+
+	/// Connect to an emulator listening on the given address (`tcp:host:port`)
+	TcpConnect(String),
+
+	/// Listen on the given address for an emulator to connect (`tcp-listen:host:port`)
+	TcpListen(String),
+}
+
+impl SerialEndpoint {
+	/// Open the link, at the initial baud rate, with reads timing out after
+	/// [`PADP_DEFAULT_ACK_TIMEOUT`]
+	///
+	/// For [`SerialEndpoint::TcpListen`], this waits for the emulator to connect.
+	pub fn open(&self) -> Result<Box<dyn SerialLink>, io::Error> {
+		let mut link: Box<dyn SerialLink> = match self {
+			#[cfg(unix)]
+			Self::Device(path) => Box::new(SerialPort::open(path, CMP_INITIAL_BAUD_RATE)?),
+			#[cfg(not(unix))]
+			Self::Device(_) => {
+				return Err(io::Error::other(
+					"serial devices are only supported on Unix",
+				))
+			}
+			Self::TcpConnect(addr) => Box::new(TcpStream::connect(addr)?),
+			Self::TcpListen(addr) => {
+				let (stream, _) = TcpListener::bind(addr)?.accept()?;
+				Box::new(stream)
+			}
+		};
+		link.set_read_timeout(Some(PADP_DEFAULT_ACK_TIMEOUT))?;
+
+		Ok(link)
+	}
+}
+
+impl FromStr for SerialEndpoint {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if let Some(addr) = s.strip_prefix("tcp-listen:") {
+			Ok(Self::TcpListen(addr.to_string()))
+		} else if let Some(addr) = s.strip_prefix("tcp:") {
+			Ok(Self::TcpConnect(addr.to_string()))
+		} else if s.is_empty() {
+			Err(String::from(s))
+		} else {
+			Ok(Self::Device(PathBuf::from(s)))
+		}
+	}
+}
+
+#[cfg(unix)]
+pub use self::unix::SerialPort;
+
+#[cfg(unix)]
+mod unix {
+	use std::{
+		fs::{File, OpenOptions},
+		io::{self, Read, Write},
+		os::unix::{fs::OpenOptionsExt, io::AsRawFd},
+		path::Path,
+		time::Duration,
+	};
+
+	use nix::{
+		fcntl::OFlag,
+		sys::termios::{
+			cfmakeraw,
+			cfsetspeed,
+			tcflush,
+			tcgetattr,
+			tcsetattr,
+			BaudRate,
+			ControlFlags,
+			FlushArg,
+			SetArg,
+			SpecialCharacterIndices,
+			Termios,
+		},
+	};
+
+	use super::SerialLink;
+
+	fn baud_rate_constant(baud_rate: u32) -> Result<BaudRate, io::Error> {
+		match baud_rate {
+			9600 => Ok(BaudRate::B9600),
+			19200 => Ok(BaudRate::B19200),
+			38400 => Ok(BaudRate::B38400),
+			57600 => Ok(BaudRate::B57600),
+			115_200 => Ok(BaudRate::B115200),
+			230_400 => Ok(BaudRate::B230400),
+			x => Err(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("unsupported baud rate {}", x),
+			)),
+		}
+	}
+
+	/// A serial device, in raw mode
+	///
+	/// Reads block until data arrives, unless a timeout has been set with
+	/// [`SerialLink::set_read_timeout`], in which case they return an
+	/// [`io::ErrorKind::TimedOut`] error once it passes.
+	#[derive(Debug)]
+	pub struct SerialPort {
+		file: File,
+		termios: Termios,
+		baud_rate: u32,
+		timeout: Option<Duration>,
+	}
+
+	impl SerialPort {
+		/// Open the serial device at the given path, in raw mode, at the given baud rate
+		pub fn open<P: AsRef<Path>>(path: P, baud_rate: u32) -> Result<Self, io::Error> {
+			let file = OpenOptions::new()
+				.read(true)
+				.write(true)
+				.custom_flags(OFlag::O_NOCTTY.bits())
+				.open(path)?;
+
+			let mut termios = tcgetattr(file.as_raw_fd())?;
+			cfmakeraw(&mut termios);
+			termios.control_flags |= ControlFlags::CLOCAL | ControlFlags::CREAD;
+			termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+			termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+			cfsetspeed(&mut termios, baud_rate_constant(baud_rate)?)?;
+			tcsetattr(file.as_raw_fd(), SetArg::TCSANOW, &termios)?;
+			tcflush(file.as_raw_fd(), FlushArg::TCIOFLUSH)?;
+
+			Ok(Self {
+				file,
+				termios,
+				baud_rate,
+				timeout: None,
+			})
+		}
+
+		pub fn get_ref(&self) -> &File {
+			&self.file
+		}
+
+		pub fn baud_rate(&self) -> u32 {
+			self.baud_rate
+		}
+	}
+
+	impl Read for SerialPort {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			match self.file.read(buf)? {
+				0 if self.timeout.is_some() && !buf.is_empty() => Err(io::Error::new(
+					io::ErrorKind::TimedOut,
+					"timed out waiting for serial data",
+				)),
+				len => Ok(len),
+			}
+		}
+	}
+
+	impl Write for SerialPort {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.file.write(buf)
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			self.file.flush()
+		}
+	}
+
+	impl SerialLink for SerialPort {
+		fn set_baud_rate(&mut self, baud_rate: u32) -> Result<(), io::Error> {
+			cfsetspeed(&mut self.termios, baud_rate_constant(baud_rate)?)?;
+			tcsetattr(self.file.as_raw_fd(), SetArg::TCSADRAIN, &self.termios)?;
+			self.baud_rate = baud_rate;
+
+			Ok(())
+		}
+
+		/// Timeouts are rounded up to the nearest tenth of a second, up to 25.5s
+		fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), io::Error> {
+			let (vmin, vtime) = match timeout {
+				Some(x) => (0, x.as_millis().div_ceil(100).clamp(1, 255) as u8),
+				None => (1, 0),
+			};
+
+			self.termios.control_chars[SpecialCharacterIndices::VMIN as usize] = vmin;
+			self.termios.control_chars[SpecialCharacterIndices::VTIME as usize] = vtime;
+			tcsetattr(self.file.as_raw_fd(), SetArg::TCSANOW, &self.termios)?;
+			self.timeout = timeout;
+
+			Ok(())
+		}
+	}
+}
@@ -0,0 +1,262 @@
+use std::{
+	io::{self, Cursor, Read, Write},
+	net::{TcpListener, TcpStream},
+	path::PathBuf,
+	thread,
+	time::Duration,
+};
+
+use palmrs_sync::{
+	capture::{
+		decode,
+		Capture,
+		CaptureDirection,
+		CaptureHeader,
+		CaptureLink,
+		CaptureMessage,
+		CaptureRecord,
+		CaptureStream,
+		ReplayStream,
+	},
+	mock::MockDevice,
+	protocol::{
+		cmp::{CmpPacketType, CMP_DEFAULT_BAUD_RATES},
+		dlp::{
+			client::{DlpClient, DlpSyncStatus, DLP_OPEN_READ},
+			DlpFunction,
+		},
+		netsync::{NetSyncConnection, NetSyncListener},
+		padp::PadpConnection,
+		slp::{SlpConnection, SlpSocket},
+	},
+	serial::accept_session,
+};
+use test_env_log::test;
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+fn device() -> MockDevice {
+	MockDevice::load_directory(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-data"))
+		.unwrap()
+}
+
+fn padp_connection<T: Read + Write>(transport: T) -> PadpConnection<T> {
+	PadpConnection::new(
+		SlpConnection::new(transport),
+		SlpSocket::DesktopLink,
+		SlpSocket::DesktopLink,
+	)
+}
+
+/// The desktop's side of a short sync, returning the user name it read
+fn desktop_sync<T: Read + Write>(padp: PadpConnection<T>) -> (String, PadpConnection<T>) {
+	let mut client = DlpClient::new(padp);
+	let user_name = client.read_user_info().unwrap().user_name;
+	client.open_database(0, DLP_OPEN_READ, "ToDoDB").unwrap();
+	client.add_sync_log_entry("OK ToDo").unwrap();
+	client.end_of_sync(DlpSyncStatus::Normal).unwrap();
+
+	(user_name, client.into_inner())
+}
+
+/// Capture a sync over a serial-style link with a mock device
+fn capture_serial_sync() -> Capture {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+		let mut device = device();
+		device.user_info.user_name = "Jo Bloggs".to_string();
+		device.sync_serial(stream).unwrap()
+	});
+
+	let (stream, _) = listener.accept().unwrap();
+	stream.set_read_timeout(TIMEOUT).unwrap();
+	let capture = CaptureStream::new(stream, Vec::new(), CaptureLink::Serial).unwrap();
+
+	let mut padp = padp_connection(capture);
+	accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+	let (user_name, padp) = desktop_sync(padp);
+	assert_eq!(user_name, "Jo Bloggs");
+	assert_eq!(device.join().unwrap(), DlpSyncStatus::Normal);
+
+	let (_, data) = padp.into_inner().into_inner().into_inner();
+	Capture::from_bytes(&mut Cursor::new(&data)).unwrap()
+}
+
+fn dlp_functions(capture: &Capture) -> Vec<(CaptureDirection, DlpFunction)> {
+	decode(capture)
+		.into_iter()
+		.filter_map(|x| match x.message {
+			CaptureMessage::DlpRequest(request) => Some((x.direction, request.function)),
+			CaptureMessage::DlpResponse(response) => Some((x.direction, response.function)),
+			_ => None,
+		})
+		.collect()
+}
+
+#[test]
+fn decode_serial_sync() {
+	let capture = capture_serial_sync();
+	assert_eq!(capture.header.link, CaptureLink::Serial);
+	assert!(capture
+		.records
+		.windows(2)
+		.all(|x| x[0].timestamp <= x[1].timestamp));
+
+	let events = decode(&capture);
+	let cmp = events
+		.iter()
+		.filter_map(|x| match &x.message {
+			CaptureMessage::Cmp(packet) => Some((x.direction, packet.packet_type)),
+			_ => None,
+		})
+		.collect::<Vec<_>>();
+	assert_eq!(
+		cmp,
+		vec![
+			(CaptureDirection::FromDevice, CmpPacketType::Wakeup),
+			(CaptureDirection::ToDevice, CmpPacketType::Init),
+		]
+	);
+
+	// Every request is followed by its response
+	use CaptureDirection::*;
+	assert_eq!(
+		dlp_functions(&capture),
+		vec![
+			(ToDevice, DlpFunction::ReadUserInfo),
+			(FromDevice, DlpFunction::ReadUserInfo),
+			(ToDevice, DlpFunction::OpenDb),
+			(FromDevice, DlpFunction::OpenDb),
+			(ToDevice, DlpFunction::AddSyncLogEntry),
+			(FromDevice, DlpFunction::AddSyncLogEntry),
+			(ToDevice, DlpFunction::EndOfSync),
+			(FromDevice, DlpFunction::EndOfSync),
+		]
+	);
+
+	// Messages come after the packets that carried them
+	let first = events
+		.iter()
+		.position(|x| matches!(x.message, CaptureMessage::DlpRequest(_)))
+		.unwrap();
+	assert!(events[first - 1].message.is_packet());
+	assert!(!events
+		.iter()
+		.any(|x| matches!(x.message, CaptureMessage::Unknown(_))));
+}
+
+#[test]
+fn replay_serial_sync() {
+	let capture = capture_serial_sync();
+
+	let mut replay = ReplayStream::new(&capture);
+	{
+		let mut padp = padp_connection(&mut replay);
+		accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+		let (user_name, _) = desktop_sync(padp);
+		assert_eq!(user_name, "Jo Bloggs");
+	}
+	assert!(replay.is_finished());
+	assert_eq!(replay.mismatch(), None);
+
+	// A desktop that goes a different way is spotted
+	let mut replay = ReplayStream::new(&capture);
+	{
+		let mut padp = padp_connection(&mut replay);
+		accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+		let mut client = DlpClient::new(padp);
+		let _ = client.end_of_sync(DlpSyncStatus::Cancelled);
+	}
+	assert!(replay.mismatch().is_some());
+}
+
+#[test]
+fn decode_network_sync() {
+	let listener = NetSyncListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+		MockDevice::new().sync_network(stream).unwrap()
+	});
+
+	let (connection, _) = listener
+		.accept_with(|stream| CaptureStream::new(stream, Vec::new(), CaptureLink::NetSync))
+		.unwrap();
+
+	let mut client = DlpClient::new(connection);
+	client.end_of_sync(DlpSyncStatus::Cancelled).unwrap();
+	assert_eq!(device.join().unwrap(), DlpSyncStatus::Cancelled);
+
+	let (_, data) = client.into_inner().into_inner().into_inner();
+	let capture = Capture::from_bytes(&mut Cursor::new(&data)).unwrap();
+
+	// The replayed sync goes the same way
+	let mut replay = ReplayStream::new(&capture);
+	let mut connection = NetSyncConnection::new(&mut replay);
+	connection.accept_handshake().unwrap();
+	let mut client = DlpClient::new(connection);
+	client.end_of_sync(DlpSyncStatus::Cancelled).unwrap();
+	assert!(replay.is_finished());
+	assert_eq!(replay.mismatch(), None);
+
+	let events = decode(&capture);
+	let handshake = events
+		.iter()
+		.filter(|x| matches!(x.message, CaptureMessage::NetSyncHandshake(_)))
+		.map(|x| x.direction)
+		.collect::<Vec<_>>();
+	use CaptureDirection::*;
+	assert_eq!(handshake, vec![FromDevice, ToDevice, FromDevice]);
+	assert_eq!(
+		dlp_functions(&capture),
+		vec![
+			(ToDevice, DlpFunction::EndOfSync),
+			(FromDevice, DlpFunction::EndOfSync),
+		]
+	);
+}
+
+#[test]
+fn file_format() {
+	let capture = Capture {
+		header: CaptureHeader {
+			link: CaptureLink::Serial,
+			start_time: 1_000_000_000,
+		},
+		records: vec![
+			CaptureRecord {
+				direction: CaptureDirection::FromDevice,
+				timestamp: Duration::from_micros(1500),
+				data: vec![0xBE, 0xEF, 0xED],
+			},
+			CaptureRecord {
+				direction: CaptureDirection::ToDevice,
+				timestamp: Duration::from_micros(2500),
+				data: Vec::new(),
+			},
+		],
+	};
+
+	let mut data = capture.to_bytes().unwrap();
+	assert_eq!(&data[..8], b"PALMCAP\0");
+	assert_eq!(
+		Capture::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		capture
+	);
+
+	// A record cut short by the end of the file is dropped
+	data.extend(&capture.records[0].to_bytes().unwrap()[..5]);
+	assert_eq!(
+		Capture::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		capture
+	);
+
+	let e = Capture::from_bytes(&mut Cursor::new(b"PALMDB\0\0\0\x01")).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+}
@@ -0,0 +1,164 @@
+use std::{
+	env,
+	ffi::OsString,
+	fs,
+	io,
+	path::{Path, PathBuf},
+	process,
+};
+
+use palmrs_sync::{config::SyncConfig, protocol::dlp::info::DlpUserInfo, SyncMode};
+use test_env_log::test;
+
+const CONFIG: &str = r#"
+data_root = "/home/user/Documents/palm.rs"
+
+[[device]]
+user_name = "Jo Bloggs"
+user_id = 0x1234
+data_root = "/home/user/palm/jo"
+backup = true
+include = ["MemoDB"]
+exclude = ["Graffiti*"]
+
+[[device.conduit]]
+name = "palmrs-conduit-todotxt"
+sync_mode = "keep-device"
+
+[device.conduit.config]
+todo_file = "todo.txt"
+max_priority = 3
+archive = true
+
+[[device.conduit]]
+name = "palmrs-conduit-disabled"
+enabled = false
+
+[[device]]
+user_name = "Sam"
+
+[[device.conduit]]
+name = "palmrs-conduit-todotxt"
+"#;
+
+fn user(user_name: &str, user_id: u32) -> DlpUserInfo {
+	DlpUserInfo {
+		user_name: user_name.to_string(),
+		user_id,
+		..DlpUserInfo::default()
+	}
+}
+
+#[test]
+fn parse() {
+	let config = SyncConfig::from_toml(CONFIG).unwrap();
+	assert_eq!(
+		config.data_root,
+		Some(PathBuf::from("/home/user/Documents/palm.rs"))
+	);
+	assert_eq!(config.devices.len(), 2);
+
+	let device = &config.devices[0];
+	assert_eq!(device.user_id, Some(0x1234));
+	assert!(device.backup);
+	assert_eq!(device.include, vec!["MemoDB"]);
+	assert_eq!(device.exclude, vec!["Graffiti*"]);
+	assert_eq!(device.conduits.len(), 2);
+
+	let conduit = device.conduit("palmrs-conduit-todotxt").unwrap();
+	assert_eq!(conduit.sync_mode, SyncMode::KeepDevice);
+	assert!(conduit.enabled);
+	assert!(!device.conduit("palmrs-conduit-disabled").unwrap().enabled);
+
+	// Settings become the conduit's environment, with non-string values written as TOML
+	let handler = conduit.handler();
+	assert_eq!(handler.sync_mode, SyncMode::KeepDevice);
+	let setting = |key: &str| handler.conduit_config.get(&OsString::from(key)).cloned();
+	assert_eq!(setting("todo_file"), Some("todo.txt".into()));
+	assert_eq!(setting("max_priority"), Some("3".into()));
+	assert_eq!(setting("archive"), Some("true".into()));
+
+	// The sync mode defaults to merging
+	let conduit = &config.devices[1].conduits[0];
+	assert_eq!(conduit.sync_mode, SyncMode::Merge);
+	assert!(conduit.config.is_empty());
+}
+
+#[test]
+fn invalid() {
+	let e = SyncConfig::from_toml("[[device]]\nuser_nmae = \"Jo\"\n").unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+	let e = SyncConfig::from_toml(
+		"[[device]]\n[[device.conduit]]\nname = \"x\"\nsync_mode = \"sideways\"\n",
+	)
+	.unwrap_err();
+	assert!(e.to_string().contains("sideways"));
+
+	assert_eq!(SyncConfig::from_toml("").unwrap(), SyncConfig::default());
+}
+
+#[test]
+fn devices() {
+	let config = SyncConfig::from_toml(CONFIG).unwrap();
+
+	// The user ID is matched first, and the name only if the device doesn't have an ID
+	let device = config.device(&user("Someone Else", 0x1234)).unwrap();
+	assert_eq!(device.user_name.as_deref(), Some("Jo Bloggs"));
+	assert!(config.device(&user("Jo Bloggs", 0x5678)).is_none());
+	assert!(config.device(&user("Jo Bloggs", 0)).is_some());
+	let device = config.device(&user("Sam", 0x9999)).unwrap();
+	assert_eq!(device.user_name.as_deref(), Some("Sam"));
+	assert!(config.device(&user("Nobody", 1)).is_none());
+
+	// Disabled conduits aren't run
+	let default_root = Path::new("/default");
+	let hotsync = config.hotsync(&user("Jo Bloggs", 0x1234), default_root);
+	assert_eq!(hotsync.data_root, PathBuf::from("/home/user/palm/jo"));
+	assert!(hotsync.backup);
+	assert_eq!(hotsync.include, vec!["MemoDB"]);
+	assert_eq!(hotsync.exclude, vec!["Graffiti*"]);
+	let names = hotsync
+		.conduits
+		.iter()
+		.map(|x| x.conduit_name.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(names, vec!["palmrs-conduit-todotxt"]);
+
+	// Devices without their own data root get a directory within the shared one
+	let hotsync = config.hotsync(&user("Sam", 0), default_root);
+	assert_eq!(
+		hotsync.data_root,
+		PathBuf::from("/home/user/Documents/palm.rs/Sam")
+	);
+	assert_eq!(hotsync.conduits.len(), 1);
+
+	// Unknown devices are synced without conduits
+	let hotsync = SyncConfig::default().hotsync(&user("Nobody", 1), default_root);
+	assert_eq!(hotsync.data_root, PathBuf::from("/default/Nobody"));
+	assert!(hotsync.conduits.is_empty());
+}
+
+#[test]
+fn load() {
+	let dir = env::temp_dir().join(format!("palmrs-config-{}", process::id()));
+	fs::create_dir_all(&dir).unwrap();
+	let path = dir.join("sync.toml");
+	fs::write(
+		&path,
+		"data_root = \"data\"\n[[device]]\nuser_name = \"Jo\"\ndata_root = \"/absolute\"\n",
+	)
+	.unwrap();
+
+	// Relative paths are relative to the configuration file
+	let config = SyncConfig::load(&path).unwrap();
+	assert_eq!(config.data_root, Some(dir.join("data")));
+	assert_eq!(
+		config.devices[0].data_root,
+		Some(PathBuf::from("/absolute"))
+	);
+
+	fs::remove_dir_all(&dir).unwrap();
+	let e = SyncConfig::load(&path).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::NotFound);
+}
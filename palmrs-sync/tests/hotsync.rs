@@ -0,0 +1,346 @@
+#![cfg(unix)]
+
+use std::{
+	env,
+	fs,
+	os::unix::fs::PermissionsExt,
+	path::{Path, PathBuf},
+	process,
+	sync::Mutex,
+};
+
+use palmrs_sync::{
+	conduit::{ConduitHandler, ConduitRequirements},
+	hotsync::{user_directory_name, HotSync},
+	mock::{MockDatabase, MockDevice},
+	protocol::dlp::{
+		client::{DlpClient, DlpSyncStatus},
+		info::{DlpDatabaseInfo, DlpUserInfo},
+	},
+	SyncMode,
+};
+use test_env_log::test;
+
+/// Writing a script while another thread is starting a process can leave the script open in the
+/// child, so that it can't be run
+static PROCESSES: Mutex<()> = Mutex::new(());
+
+fn fixtures() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-data")
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+	let path = env::temp_dir().join(format!("palmrs-hotsync-{}-{}", process::id(), name));
+	let _ = fs::remove_dir_all(&path);
+	fs::create_dir_all(&path).unwrap();
+	path
+}
+
+/// Write a conduit script, that needs the given databases and runs the given commands
+fn conduit(dir: &Path, name: &str, databases: &[&str], commands: &str) -> String {
+	let requirements = ConduitRequirements::new().with_databases(databases);
+	let script = format!(
+		"#!/bin/sh\n\
+		if [ \"$1\" = \"--palmrs-dump-requirements\" ]; then\n\
+		cat <<'EOF'\n{}EOF\n\
+		exit 0\n\
+		fi\n\
+		{}\n",
+		requirements, commands
+	);
+
+	let path = dir.join(name);
+	fs::write(&path, script).unwrap();
+	fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+	path.to_string_lossy().into_owned()
+}
+
+/// A database for a conduit to add to the device
+fn memo_database(dir: &Path) -> PathBuf {
+	let mut database = MockDatabase::new(DlpDatabaseInfo {
+		type_code: *b"DATA",
+		creator_code: *b"memo",
+		name: "MemoDB".to_string(),
+		..DlpDatabaseInfo::default()
+	});
+	database.add_record(1, b"Hello from the desktop\0");
+
+	let path = dir.join("MemoDB.pdb");
+	fs::write(&path, database.to_bytes().unwrap()).unwrap();
+	path
+}
+
+#[test]
+fn requirements_format() {
+	let requirements = ConduitRequirements::new()
+		.with_databases(&["ToDoDB", "Quote \" and \\ slash", "Caf\u{e9}"])
+		.finish();
+	let text = requirements.to_string();
+	assert!(text.starts_with("[conduit-requirements]\n"));
+	assert_eq!(text.parse::<ConduitRequirements>().unwrap(), requirements);
+
+	assert_eq!(
+		"".parse::<ConduitRequirements>().unwrap(),
+		ConduitRequirements::new()
+	);
+	assert!("databases = [\"unterminated]"
+		.parse::<ConduitRequirements>()
+		.is_err());
+}
+
+#[test]
+fn user_directories() {
+	let mut user = DlpUserInfo {
+		user_name: "Jo Bloggs/Palm".to_string(),
+		user_id: 0x1234,
+		..DlpUserInfo::default()
+	};
+	assert_eq!(user_directory_name(&user), "Jo_Bloggs_Palm");
+
+	user.user_name = String::new();
+	assert_eq!(user_directory_name(&user), "user-00001234");
+	user.user_name = "..".to_string();
+	assert_eq!(user_directory_name(&user), "user-00001234");
+}
+
+#[test]
+fn sync_with_conduits() {
+	let _lock = PROCESSES.lock().unwrap();
+	let dir = temp_dir("sync");
+	let memo = memo_database(&dir);
+
+	let mut hotsync = HotSync::new(dir.join("data"));
+	let adds_memos = conduit(
+		&dir,
+		"conduit-memo",
+		&["ToDoDB"],
+		&format!(
+			"echo \"$PALMRS_SYNC_MODE\" > \"$PALMRS_DATA_LOCAL/mode\"\n\
+			ls \"$PALMRS_DATA_DEVICE\" > \"$PALMRS_DATA_LOCAL/files\"\n\
+			cp {:?} \"$PALMRS_DATA_DEVICE\"",
+			memo
+		),
+	);
+	let mut conduit_memo = ConduitHandler::new(&adds_memos, SyncMode::Merge);
+	conduit_memo
+		.conduit_config
+		.insert("unused".into(), "value".into());
+	hotsync.conduits.push(conduit_memo);
+
+	// Not allowed to change anything, so its deletion is undone
+	let deletes = conduit(
+		&dir,
+		"conduit-delete",
+		&[],
+		"rm \"$PALMRS_DATA_DEVICE/ToDoDB.pdb\"",
+	);
+	hotsync
+		.conduits
+		.push(ConduitHandler::new(&deletes, SyncMode::KeepDevice));
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let user = client.read_user_info().unwrap();
+	let report = hotsync.sync(&mut client, &user).unwrap();
+
+	assert_eq!(report.status, DlpSyncStatus::Normal);
+	assert_eq!(report.pulled, vec!["ToDoDB"]);
+	assert_eq!(report.pushed, vec!["MemoDB"]);
+	assert!(report.conduits.iter().all(|x| x.success));
+
+	// The conduit ran with its own data directory, and the pulled databases
+	let local = hotsync.conduit_path(&adds_memos);
+	assert_eq!(local, dir.join("data/conduits/conduit-memo"));
+	assert_eq!(fs::read_to_string(local.join("mode")).unwrap(), "merge\n");
+	assert_eq!(
+		fs::read_to_string(local.join("files")).unwrap(),
+		"ToDoDB.pdb\n"
+	);
+	assert!(hotsync.device_path().join("ToDoDB.pdb").exists());
+
+	let device = client.into_inner();
+	let memos = device.database("MemoDB").unwrap();
+	assert_eq!(memos.records.len(), 1);
+	assert_eq!(memos.records[0].data, b"Hello from the desktop\0");
+
+	// Flags are reset, and deleted records purged, once everything has synced
+	let todo = device.database("ToDoDB").unwrap();
+	assert_eq!(todo.modified_records().count(), 0);
+	assert_eq!(todo.records.len(), 9);
+
+	assert_eq!(device.sync_status, Some(DlpSyncStatus::Normal));
+	assert_eq!(
+		device.sync_log,
+		vec![format!("OK {}\nOK {}\n", adds_memos, deletes)]
+	);
+	assert_ne!(
+		device.user_info.last_successful_sync,
+		user.last_successful_sync
+	);
+
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_conduits() {
+	let _lock = PROCESSES.lock().unwrap();
+	let dir = temp_dir("failed");
+	let memo = memo_database(&dir);
+
+	let mut hotsync = HotSync::new(dir.join("data"));
+	let fails = conduit(
+		&dir,
+		"conduit-fails",
+		&["ToDoDB"],
+		&format!("cp {:?} \"$PALMRS_DATA_DEVICE\"\nexit 1", memo),
+	);
+	hotsync
+		.conduits
+		.push(ConduitHandler::new(&fails, SyncMode::Merge));
+	let missing = dir.join("conduit-missing").to_string_lossy().into_owned();
+	hotsync
+		.conduits
+		.push(ConduitHandler::new(&missing, SyncMode::Merge));
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let user = client.read_user_info().unwrap();
+	let report = hotsync.sync(&mut client, &user).unwrap();
+
+	assert_eq!(report.status, DlpSyncStatus::Other);
+	assert_eq!(report.pulled, vec!["ToDoDB"]);
+	assert!(report.pushed.is_empty());
+	let failed = report
+		.conduits
+		.iter()
+		.map(|x| (x.conduit_name.as_str(), x.success))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		failed,
+		vec![(missing.as_str(), false), (fails.as_str(), false)]
+	);
+	assert!(!hotsync.device_path().join("MemoDB.pdb").exists());
+
+	// Nothing is marked as synced, so the changes are picked up again next time
+	let device = client.into_inner();
+	assert!(device.database("MemoDB").is_none());
+	assert_eq!(
+		device
+			.database("ToDoDB")
+			.unwrap()
+			.modified_records()
+			.count(),
+		10
+	);
+	assert_eq!(device.sync_status, Some(DlpSyncStatus::Other));
+	assert_eq!(device.user_info, user);
+
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn reset_flags_of_synced_databases() {
+	let _lock = PROCESSES.lock().unwrap();
+	let dir = temp_dir("reset");
+
+	let mut hotsync = HotSync {
+		include: vec!["tWmanual".to_string()],
+		..HotSync::new(dir.join("data"))
+	};
+	let succeeds = conduit(&dir, "conduit-succeeds", &["ToDoDB"], "true");
+	hotsync
+		.conduits
+		.push(ConduitHandler::new(&succeeds, SyncMode::Merge));
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let user = client.read_user_info().unwrap();
+	hotsync.sync(&mut client, &user).unwrap();
+
+	// Only the database the conduit needed is marked as synced, not the included one
+	let device = client.into_inner();
+	let original = MockDevice::load_directory(fixtures()).unwrap();
+	assert_eq!(
+		device
+			.database("ToDoDB")
+			.unwrap()
+			.modified_records()
+			.count(),
+		0
+	);
+	assert_eq!(
+		device.database("tWmanual").unwrap().records,
+		original.database("tWmanual").unwrap().records
+	);
+
+	// A database that a failed conduit needs keeps its flags, even if another conduit synced it
+	let fails = conduit(&dir, "conduit-fails", &["ToDoDB"], "exit 1");
+	hotsync
+		.conduits
+		.push(ConduitHandler::new(&fails, SyncMode::Merge));
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let report = hotsync.sync(&mut client, &user).unwrap();
+	assert_eq!(report.status, DlpSyncStatus::Other);
+	assert_eq!(
+		client
+			.into_inner()
+			.database("ToDoDB")
+			.unwrap()
+			.modified_records()
+			.count(),
+		10
+	);
+
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn backup() {
+	let dir = temp_dir("backup");
+	let hotsync = HotSync {
+		backup: true,
+		..HotSync::new(&dir)
+	};
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let user = client.read_user_info().unwrap();
+	let report = hotsync.sync(&mut client, &user).unwrap();
+	assert_eq!(report.status, DlpSyncStatus::Normal);
+	assert_eq!(report.pulled, vec!["ToDoDB", "Hello, World", "tWmanual"]);
+
+	// The backed up databases are just as they were on the device
+	let device = MockDevice::load_directory(hotsync.device_path()).unwrap();
+	let original = MockDevice::load_directory(fixtures()).unwrap();
+	for database in original.databases.iter() {
+		let backup = device.database(&database.info.name).unwrap();
+		assert_eq!(backup.records, database.records);
+		assert_eq!(backup.resources, database.resources);
+		assert_eq!(backup.app_block, database.app_block);
+	}
+
+	fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn include_and_exclude() {
+	let dir = temp_dir("include");
+	let hotsync = HotSync {
+		include: vec!["tW*".to_string(), "Hello, World".to_string()],
+		exclude: vec!["*World".to_string()],
+		..HotSync::new(&dir)
+	};
+
+	let mut client = DlpClient::new(MockDevice::load_directory(fixtures()).unwrap());
+	let user = client.read_user_info().unwrap();
+	let report = hotsync.sync(&mut client, &user).unwrap();
+	assert_eq!(report.pulled, vec!["tWmanual"]);
+
+	// Exclusions apply to backups too
+	let hotsync = HotSync {
+		backup: true,
+		exclude: vec!["To*B".to_string()],
+		..HotSync::new(&dir)
+	};
+	let report = hotsync.sync(&mut client, &user).unwrap();
+	assert_eq!(report.pulled, vec!["Hello, World", "tWmanual"]);
+
+	fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,393 @@
+use std::{
+	env,
+	fs,
+	io,
+	net::{TcpListener, TcpStream},
+	path::PathBuf,
+	process,
+	thread,
+	time::Duration,
+};
+
+use palmrs_database::{
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	PalmDatabase,
+	PdbDatabase,
+	PrcDatabase,
+};
+use palmrs_sync::{
+	mock::{MockDatabase, MockDevice},
+	protocol::{
+		cmp::{self, CMP_DEFAULT_BAUD_RATES},
+		dlp::{
+			client::{DlpClient, DlpSyncStatus, DLP_OPEN_READ, DLP_OPEN_READ_WRITE},
+			info::{
+				DlpDatabaseInfo,
+				DlpUserInfoUpdate,
+				DLP_DB_LIST_RAM,
+				DLP_USER_INFO_MODIFIED_USER_NAME,
+			},
+			record::{DlpRecord, DlpResource},
+			DlpError,
+			DlpFunction,
+			DlpRequest,
+			DLP_FIRST_ARGUMENT_ID,
+		},
+		netsync::NetSyncListener,
+		padp::PadpConnection,
+		slp::{SlpConnection, SlpSocket},
+	},
+};
+use test_env_log::test;
+
+const TODO_PDB: &[u8] = include_bytes!("../../test-data/ToDoDB.pdb");
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+fn fixtures() -> PathBuf {
+	PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../test-data")
+}
+
+fn device() -> MockDevice {
+	MockDevice::load_directory(fixtures()).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+	let path = env::temp_dir().join(format!("palmrs-mock-{}-{}", process::id(), name));
+	let _ = fs::remove_dir_all(&path);
+	path
+}
+
+#[test]
+fn list_and_read_databases() {
+	let mut client = DlpClient::new(device());
+
+	let databases = client.list_databases(DLP_DB_LIST_RAM, 0).unwrap();
+	let names = databases
+		.iter()
+		.map(|x| x.name.as_str())
+		.collect::<Vec<_>>();
+	assert_eq!(names, vec!["ToDoDB", "Hello, World", "tWmanual"]);
+	assert!(databases[1].is_resource_database());
+
+	// Records come back just as they are in the file
+	let database = client
+		.read_database::<PdbDatabase>(0, &databases[0])
+		.unwrap();
+	let expected = PalmDatabase::<PdbDatabase>::from_bytes(TODO_PDB).unwrap();
+	assert_eq!(database.header.name_trimmed(), b"ToDoDB");
+	assert_eq!(
+		database.application_reserved(),
+		expected.application_reserved()
+	);
+
+	let records = database.list_records_resources();
+	let expected_records = expected.list_records_resources();
+	assert_eq!(records.len(), expected_records.len());
+	for ((header, data), (expected_header, expected_data)) in
+		records.iter().zip(expected_records.iter())
+	{
+		assert_eq!(header.unique_id(), expected_header.unique_id());
+		assert_eq!(header.attributes(), expected_header.attributes());
+		assert_eq!(data, expected_data);
+	}
+
+	let application = client
+		.read_database::<PrcDatabase>(0, &databases[1])
+		.unwrap();
+	assert_eq!(application.list_records_resources().len(), 5);
+
+	// Only the RAM on card 0 exists
+	let e = client
+		.read_database_list(DLP_DB_LIST_RAM, 1, 0)
+		.unwrap_err();
+	assert_eq!(DlpError::from_io_error(&e), Some(DlpError::NotFound));
+}
+
+#[test]
+fn fast_sync() {
+	let mut client = DlpClient::new(device());
+
+	// Everything in the fixture is dirty, including one deleted record
+	let handle = client
+		.open_database(0, DLP_OPEN_READ_WRITE, "ToDoDB")
+		.unwrap();
+	let mut modified = Vec::new();
+	while let Some(record) = client.read_next_modified_record(handle).unwrap() {
+		modified.push(record);
+	}
+	assert_eq!(modified.len(), 10);
+	assert_eq!(modified.iter().filter(|x| x.attributes.delete).count(), 1);
+
+	client.reset_sync_flags(handle).unwrap();
+	client.clean_up_database(handle).unwrap();
+	assert_eq!(client.read_next_modified_record(handle).unwrap(), None);
+	assert_eq!(client.read_open_database_info(handle).unwrap(), 9);
+	client.close_database(handle).unwrap();
+
+	// Changes made on the device are picked up by the next sync
+	let database = client.get_mut().database_mut("ToDoDB").unwrap();
+	let first = database.records[0].unique_id;
+	let second = database.records[1].unique_id;
+	database.edit_record(first, b"changed\0\0").unwrap();
+	database.delete_record(second, true).unwrap();
+	let added = database.add_record(2, b"new\0\0");
+
+	let handle = client.open_database(0, DLP_OPEN_READ, "ToDoDB").unwrap();
+	let mut modified = Vec::new();
+	while let Some(record) = client.read_next_modified_record(handle).unwrap() {
+		modified.push(record);
+	}
+	let ids = modified.iter().map(|x| x.unique_id).collect::<Vec<_>>();
+	assert_eq!(ids, vec![first, second, added]);
+	assert_eq!(modified[0].data, b"changed\0\0");
+	assert!(modified[1].attributes.delete && modified[1].archived);
+	assert!(!modified[1].data.is_empty());
+	assert_eq!(modified[2].attributes.category, 2);
+	assert_eq!(modified[2].index, 9);
+
+	// A read-only database can't have its flags reset
+	let e = client.reset_sync_flags(handle).unwrap_err();
+	assert_eq!(DlpError::from_io_error(&e), Some(DlpError::ReadOnly));
+}
+
+#[test]
+fn desktop_changes() {
+	let mut client = DlpClient::new(MockDevice::new());
+
+	let header = DlpDatabaseInfo {
+		type_code: *b"DATA",
+		creator_code: *b"memo",
+		name: "MemoDB".to_string(),
+		..DlpDatabaseInfo::default()
+	}
+	.to_header();
+	let handle = client.create_database(0, &header).unwrap();
+
+	let record = DlpRecord {
+		attributes: RecordAttributes {
+			category: 1,
+			..RecordAttributes::default()
+		},
+		data: b"first".to_vec(),
+		..DlpRecord::default()
+	};
+	let first = client.write_record(handle, &record).unwrap();
+	let second = client.write_record(handle, &record).unwrap();
+	assert_ne!(first, second);
+
+	// Writing a record with an existing ID replaces it
+	let replacement = DlpRecord {
+		unique_id: first,
+		data: b"replaced".to_vec(),
+		..record.clone()
+	};
+	assert_eq!(client.write_record(handle, &replacement).unwrap(), first);
+	client.delete_record(handle, second).unwrap();
+	client.write_app_block(handle, b"app info").unwrap();
+
+	// Writes without the data included flag are refused, as they are by real devices
+	let request = DlpRequest::new(DlpFunction::WriteRecord).with_argument(
+		DLP_FIRST_ARGUMENT_ID,
+		vec![handle, 0, 0, 0, 0, 0, 0, 1, b'x'],
+	);
+	let response = client.get_mut().handle_request(&request);
+	assert_eq!(response.error, Some(DlpError::Param));
+	client.close_database(handle).unwrap();
+
+	let e = client.create_database(0, &header).unwrap_err();
+	assert_eq!(DlpError::from_io_error(&e), Some(DlpError::AlreadyExists));
+
+	let database = client.get_ref().database("MemoDB").unwrap();
+	assert_eq!(database.records.len(), 1);
+	assert_eq!(database.record(first).unwrap().data, b"replaced");
+	assert!(!database.record(first).unwrap().attributes.dirty);
+	assert_eq!(database.app_block, b"app info");
+	assert_eq!(database.info.modification_number, 5);
+}
+
+#[test]
+fn write_database_in_place() {
+	let mut device = device();
+	let todo = device.database_mut("ToDoDB").unwrap();
+	todo.sort_block = b"sort order".to_vec();
+	let original = todo.clone();
+	let mut client = DlpClient::new(device);
+
+	// The desktop copy has lost the first record, changed the second, and has no sort block
+	let expected = PalmDatabase::<PdbDatabase>::from_bytes(TODO_PDB).unwrap();
+	let mut desktop = PalmDatabase::<PdbDatabase>::new(expected.header, expected.app_info);
+	desktop.set_application_reserved(expected.application_reserved().to_vec());
+	for (idx, (header, data)) in expected.list_records_resources().iter().enumerate().skip(1) {
+		let data = if idx == 1 {
+			b"changed\0".to_vec()
+		} else {
+			data.clone()
+		};
+		desktop
+			.insert_record_with_id(
+				header.attributes().unwrap(),
+				header.unique_id().unwrap(),
+				&data,
+			)
+			.unwrap();
+	}
+
+	client.write_database(0, &desktop).unwrap();
+	let todo = client.get_ref().database("ToDoDB").unwrap();
+	assert!(todo.record(original.records[0].unique_id).is_none());
+	assert_eq!(
+		todo.record(original.records[1].unique_id).unwrap().data,
+		b"changed\0"
+	);
+
+	// Deleted records are left out as well
+	let kept = original.records[2..]
+		.iter()
+		.filter(|x| !x.attributes.delete)
+		.collect::<Vec<_>>();
+	assert!(kept.len() < original.records.len() - 2);
+	assert_eq!(todo.records.len(), kept.len() + 1);
+	for record in kept {
+		assert_eq!(todo.record(record.unique_id), Some(record));
+	}
+
+	// It's the same database, so it keeps its creation date and sort block
+	assert_eq!(todo.info.creation_time, original.info.creation_time);
+	assert_eq!(todo.sort_block, b"sort order");
+	assert_eq!(todo.app_block, original.app_block);
+
+	// Sort blocks are read back, and written out with the database
+	let databases = client.list_databases(DLP_DB_LIST_RAM, 0).unwrap();
+	let database = client
+		.read_database::<PdbDatabase>(0, &databases[0])
+		.unwrap();
+	let reloaded = MockDatabase::from_bytes(&database.to_bytes().unwrap()).unwrap();
+	assert_eq!(reloaded.sort_block, b"sort order");
+	assert_eq!(reloaded.app_block, original.app_block);
+
+	// Resources the desktop copy doesn't have are deleted
+	let application = client
+		.read_database::<PrcDatabase>(0, &databases[1])
+		.unwrap();
+	let mut desktop = PalmDatabase::<PrcDatabase>::new(application.header, application.app_info);
+	for (header, data) in application.list_records_resources().iter().skip(2) {
+		let resource = DlpResource::from_resource(header, data).unwrap();
+		desktop
+			.insert_resource_with_id(&resource.type_code, resource.resource_id, data)
+			.unwrap();
+	}
+
+	client.write_database(0, &desktop).unwrap();
+	let resources = &client.get_ref().database("Hello, World").unwrap().resources;
+	assert_eq!(resources.len(), 3);
+}
+
+#[test]
+fn save_and_load() {
+	let mut device = device();
+	let database = device.database_mut("ToDoDB").unwrap();
+	let unique_id = database.records[3].unique_id;
+	database.delete_record(unique_id, true).unwrap();
+
+	let path = temp_dir("save");
+	device.save_directory(&path).unwrap();
+	let reloaded = MockDevice::load_directory(&path).unwrap();
+	fs::remove_dir_all(&path).unwrap();
+
+	let mut names = reloaded
+		.databases
+		.iter()
+		.map(|x| x.file_name())
+		.collect::<Vec<_>>();
+	names.sort();
+	assert_eq!(
+		names,
+		vec!["Hello, World.prc", "ToDoDB.pdb", "tWmanual.pdb"]
+	);
+
+	for database in device.databases.iter() {
+		assert_eq!(reloaded.database(&database.info.name), Some(database));
+	}
+}
+
+#[test]
+fn serial_session() {
+	let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+
+		let mut device = device();
+		device.user_info.user_name = "Jo Bloggs".to_string();
+		let status = device.sync_serial(stream).unwrap();
+		(status, device)
+	});
+
+	let (stream, _) = listener.accept().unwrap();
+	stream.set_read_timeout(TIMEOUT).unwrap();
+	let mut padp = PadpConnection::new(
+		SlpConnection::new(stream),
+		SlpSocket::DesktopLink,
+		SlpSocket::DesktopLink,
+	);
+	cmp::accept(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+
+	let mut client = DlpClient::new(padp);
+	assert_eq!(client.read_user_info().unwrap().user_name, "Jo Bloggs");
+	client.open_conduit().unwrap();
+	client.add_sync_log_entry("OK ToDo").unwrap();
+	client.end_of_sync(DlpSyncStatus::Normal).unwrap();
+
+	let (status, device) = device.join().unwrap();
+	assert_eq!(status, DlpSyncStatus::Normal);
+	assert_eq!(device.conduits_opened, 1);
+	assert_eq!(device.sync_log, vec!["OK ToDo"]);
+}
+
+#[test]
+fn network_session() {
+	let listener = NetSyncListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+
+		let mut device = MockDevice::new();
+		let status = device.sync_network(stream).unwrap();
+		(status, device)
+	});
+
+	let (connection, _) = listener.accept().unwrap();
+	connection.get_ref().set_read_timeout(TIMEOUT).unwrap();
+
+	let mut client = DlpClient::new(connection);
+	client
+		.write_user_info(&DlpUserInfoUpdate {
+			user_name: "Jo Bloggs".to_string(),
+			flags: DLP_USER_INFO_MODIFIED_USER_NAME,
+			..DlpUserInfoUpdate::default()
+		})
+		.unwrap();
+	client.end_of_sync(DlpSyncStatus::Cancelled).unwrap();
+
+	let (status, device) = device.join().unwrap();
+	assert_eq!(status, DlpSyncStatus::Cancelled);
+	assert_eq!(device.user_info.user_name, "Jo Bloggs");
+}
+
+#[test]
+fn in_memory_transport() {
+	use palmrs_sync::protocol::MessageTransport;
+
+	// Nothing to receive until a request is sent, and the device never takes replies
+	let mut device = MockDevice::new();
+	assert!(device.receive_message().is_err());
+	assert_eq!(
+		device.reply_message(b"").unwrap_err().kind(),
+		io::ErrorKind::Other
+	);
+}
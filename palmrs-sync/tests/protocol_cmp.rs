@@ -0,0 +1,193 @@
+use std::{
+	io::{self, Cursor, Read, Write},
+	sync::mpsc::{channel, Receiver, Sender},
+	thread,
+	time::Duration,
+};
+
+use palmrs_sync::protocol::{
+	cmp::{
+		self,
+		negotiate_baud_rate,
+		CmpPacket,
+		CmpPacketType,
+		CMP_ABORT_VERSION_MISMATCH,
+		CMP_DEFAULT_BAUD_RATES,
+		CMP_FLAG_CHANGE_BAUD,
+		CMP_FLAG_LONG_PACKETS,
+		CMP_VERSION_MAJOR,
+	},
+	padp::PadpConnection,
+	slp::{SlpConnection, SlpSocket},
+};
+use test_env_log::test;
+
+/// One end of an in-memory byte pipe, with reads timing out when there's no data
+struct PipeEnd {
+	tx: Sender<Vec<u8>>,
+	rx: Receiver<Vec<u8>>,
+	pending: Vec<u8>,
+}
+
+fn pipe() -> (PipeEnd, PipeEnd) {
+	let (tx_a, rx_a) = channel();
+	let (tx_b, rx_b) = channel();
+	let a = PipeEnd {
+		tx: tx_a,
+		rx: rx_b,
+		pending: Vec::new(),
+	};
+	let b = PipeEnd {
+		tx: tx_b,
+		rx: rx_a,
+		pending: Vec::new(),
+	};
+	(a, b)
+}
+
+impl Read for PipeEnd {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.pending.is_empty() {
+			match self.rx.recv_timeout(Duration::from_millis(50)) {
+				Ok(data) => self.pending = data,
+				Err(_) => return Err(io::Error::new(io::ErrorKind::TimedOut, "no data")),
+			}
+		}
+
+		let len = buf.len().min(self.pending.len());
+		buf[..len].copy_from_slice(&self.pending[..len]);
+		self.pending.drain(..len);
+		Ok(len)
+	}
+}
+
+impl Write for PipeEnd {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.tx
+			.send(buf.to_vec())
+			.map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "pipe closed"))?;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn padp_connection(end: PipeEnd) -> PadpConnection<PipeEnd> {
+	let mut connection = PadpConnection::new(
+		SlpConnection::new(end),
+		SlpSocket::DesktopLink,
+		SlpSocket::DesktopLink,
+	);
+	connection.ack_timeout = Duration::from_millis(200);
+	connection
+}
+
+#[test]
+fn packet_round_trip() {
+	let packet = CmpPacket {
+		packet_type: CmpPacketType::Init,
+		flags: CMP_FLAG_CHANGE_BAUD,
+		major_version: 1,
+		minor_version: 1,
+		baud_rate: 57600,
+	};
+	let data = packet.to_bytes().unwrap();
+	assert_eq!(data, b"\x02\x80\x01\x01\x00\x00\x00\x00\xE1\x00");
+	assert_eq!(
+		CmpPacket::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		packet
+	);
+	assert_eq!(data.len(), CmpPacket::SIZE);
+}
+
+#[test]
+fn baud_rate_negotiation() {
+	assert_eq!(
+		negotiate_baud_rate(115_200, CMP_DEFAULT_BAUD_RATES),
+		115_200
+	);
+	assert_eq!(negotiate_baud_rate(57600, CMP_DEFAULT_BAUD_RATES), 57600);
+	assert_eq!(negotiate_baud_rate(230_400, &[9600, 38400]), 38400);
+	assert_eq!(negotiate_baud_rate(50000, &[19200, 115_200]), 19200);
+
+	// With no rate in common, the session stays at the initial rate
+	assert_eq!(negotiate_baud_rate(4800, CMP_DEFAULT_BAUD_RATES), 9600);
+}
+
+#[test]
+fn handshake() {
+	let (desktop, device) = pipe();
+	let device = thread::spawn(move || {
+		let mut padp = padp_connection(device);
+		cmp::wakeup(&mut padp, 57600).unwrap()
+	});
+
+	let mut padp = padp_connection(desktop);
+	let session = cmp::accept(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+	assert_eq!(session.baud_rate, 57600);
+	assert!(session.long_packets);
+
+	let device_session = device.join().unwrap();
+	assert_eq!(device_session.baud_rate, 57600);
+	assert!(device_session.long_packets);
+	assert_eq!(device_session.remote_version.0, CMP_VERSION_MAJOR);
+}
+
+#[test]
+fn handshake_initial_rate() {
+	let (desktop, device) = pipe();
+	let device = thread::spawn(move || {
+		let mut padp = padp_connection(device);
+		let wakeup = CmpPacket {
+			packet_type: CmpPacketType::Wakeup,
+			flags: 0,
+			major_version: 1,
+			minor_version: 0,
+			baud_rate: 9600,
+		};
+		padp.send(&wakeup.to_bytes().unwrap()).unwrap();
+		let data = padp.receive().unwrap();
+		CmpPacket::from_bytes(&mut Cursor::new(&data)).unwrap()
+	});
+
+	let mut padp = padp_connection(desktop);
+	let session = cmp::accept(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+	assert_eq!(session.remote_version, (1, 0));
+	assert_eq!(session.baud_rate, 9600);
+	assert!(!session.long_packets);
+
+	// The rate doesn't change, so the init packet doesn't ask for it to
+	let init = device.join().unwrap();
+	assert_eq!(init.packet_type, CmpPacketType::Init);
+	assert_eq!(
+		init.flags & (CMP_FLAG_CHANGE_BAUD | CMP_FLAG_LONG_PACKETS),
+		0
+	);
+}
+
+#[test]
+fn version_mismatch() {
+	let (desktop, device) = pipe();
+	let device = thread::spawn(move || {
+		let mut padp = padp_connection(device);
+		let wakeup = CmpPacket {
+			packet_type: CmpPacketType::Wakeup,
+			flags: 0,
+			major_version: CMP_VERSION_MAJOR + 1,
+			minor_version: 0,
+			baud_rate: 115_200,
+		};
+		padp.send(&wakeup.to_bytes().unwrap()).unwrap();
+		let data = padp.receive().unwrap();
+		CmpPacket::from_bytes(&mut Cursor::new(&data)).unwrap()
+	});
+
+	let mut padp = padp_connection(desktop);
+	assert!(cmp::accept(&mut padp, CMP_DEFAULT_BAUD_RATES).is_err());
+
+	let abort = device.join().unwrap();
+	assert_eq!(abort.packet_type, CmpPacketType::Abort);
+	assert_eq!(abort.flags, CMP_ABORT_VERSION_MISMATCH);
+}
@@ -0,0 +1,334 @@
+use std::{
+	collections::VecDeque,
+	io::{self, Cursor},
+};
+
+use palmrs_database::{
+	record::{pdb_record::RecordAttributes, DatabaseRecord},
+	PdbDatabase,
+};
+use palmrs_sync::protocol::{
+	dlp::{
+		client::{DlpClient, DlpSyncStatus, DLP_OPEN_READ_WRITE},
+		info::{DlpDatabaseInfo, DlpDatabaseList, DlpUserInfo, DLP_DB_LIST_RAM},
+		record::DlpRecord,
+		DlpArgument,
+		DlpDateTime,
+		DlpError,
+		DlpFunction,
+		DlpRequest,
+		DlpResponse,
+		DLP_FIRST_ARGUMENT_ID,
+	},
+	MessageTransport,
+};
+use test_env_log::test;
+
+/// Transport that plays back canned responses, recording the requests it's sent
+struct ScriptedDevice {
+	responses: VecDeque<DlpResponse>,
+	requests: Vec<DlpRequest>,
+}
+
+impl ScriptedDevice {
+	fn new(responses: Vec<DlpResponse>) -> Self {
+		Self {
+			responses: responses.into(),
+			requests: Vec::new(),
+		}
+	}
+}
+
+impl MessageTransport for ScriptedDevice {
+	fn send_message(&mut self, data: &[u8]) -> Result<(), io::Error> {
+		self.requests
+			.push(DlpRequest::from_bytes(&mut Cursor::new(data)).unwrap());
+		Ok(())
+	}
+
+	fn reply_message(&mut self, _data: &[u8]) -> Result<(), io::Error> {
+		unimplemented!()
+	}
+
+	fn receive_message(&mut self) -> Result<Vec<u8>, io::Error> {
+		self.responses.pop_front().unwrap().to_bytes()
+	}
+}
+
+fn response(function: DlpFunction, data: Vec<u8>) -> DlpResponse {
+	DlpResponse::new(function).with_argument(DLP_FIRST_ARGUMENT_ID, data)
+}
+
+fn error_response(function: DlpFunction, error: DlpError) -> DlpResponse {
+	DlpResponse {
+		error: Some(error),
+		..DlpResponse::new(function)
+	}
+}
+
+fn database_info(name: &str, index: u16) -> DlpDatabaseInfo {
+	DlpDatabaseInfo {
+		type_code: *b"DATA",
+		creator_code: *b"memo",
+		version: 1,
+		modification_number: 7,
+		creation_time: DlpDateTime {
+			year: 2003,
+			month: 2,
+			day: 1,
+			hour: 12,
+			minute: 30,
+			second: 0,
+		},
+		index,
+		name: name.to_string(),
+		..DlpDatabaseInfo::default()
+	}
+}
+
+#[test]
+fn argument_forms() {
+	for (len, header) in [
+		(3, vec![0x20, 0x03]),
+		(300, vec![0xA0, 0x00, 0x01, 0x2C]),
+		(70000, vec![0x60, 0x00, 0x00, 0x01, 0x11, 0x70]),
+	]
+	.iter()
+	{
+		let argument = DlpArgument::new(DLP_FIRST_ARGUMENT_ID, vec![0x55; *len]);
+		let data = argument.to_bytes().unwrap();
+		assert_eq!(&data[..header.len()], header.as_slice());
+		assert_eq!(data.len(), header.len() + len);
+		assert_eq!(
+			DlpArgument::from_bytes(&mut Cursor::new(&data)).unwrap(),
+			argument
+		);
+	}
+}
+
+#[test]
+fn request_response_encoding() {
+	let request = DlpRequest::new(DlpFunction::OpenDb)
+		.with_argument(DLP_FIRST_ARGUMENT_ID, b"\x00\xC0MemoDB\0".to_vec());
+	let data = request.to_bytes().unwrap();
+	assert_eq!(data, b"\x17\x01\x20\x09\x00\xC0MemoDB\0");
+	assert_eq!(
+		DlpRequest::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		request
+	);
+
+	let data = b"\x97\x01\x00\x00\x20\x01\x03";
+	let response = DlpResponse::from_bytes(&mut Cursor::new(data)).unwrap();
+	assert_eq!(response.function, DlpFunction::OpenDb);
+	assert_eq!(response.error, None);
+	assert_eq!(response.argument(DLP_FIRST_ARGUMENT_ID), Some(&[3u8][..]));
+	assert_eq!(response.to_bytes().unwrap(), data);
+
+	let data = b"\x97\x00\x00\x05";
+	let response = DlpResponse::from_bytes(&mut Cursor::new(data)).unwrap();
+	assert_eq!(response.error, Some(DlpError::NotFound));
+
+	// Requests aren't responses
+	assert!(DlpResponse::from_bytes(&mut Cursor::new(b"\x17\x00\x00\x00")).is_err());
+}
+
+#[test]
+fn device_errors() {
+	let mut client = DlpClient::new(ScriptedDevice::new(vec![
+		error_response(DlpFunction::OpenDb, DlpError::NotFound),
+		error_response(DlpFunction::ReadNextModifiedRecord, DlpError::NotFound),
+		error_response(DlpFunction::ReadNextModifiedRecord, DlpError::Cancelled),
+		response(DlpFunction::CloseDb, Vec::new()),
+	]));
+
+	let e = client
+		.open_database(0, DLP_OPEN_READ_WRITE, "Missing")
+		.unwrap_err();
+	assert_eq!(DlpError::from_io_error(&e), Some(DlpError::NotFound));
+
+	// Running out of modified records isn't an error, but other errors are
+	assert_eq!(client.read_next_modified_record(1).unwrap(), None);
+	let e = client.read_next_modified_record(1).unwrap_err();
+	assert_eq!(DlpError::from_io_error(&e), Some(DlpError::Cancelled));
+
+	// Responses to the wrong request are rejected
+	let e = client.end_of_sync(DlpSyncStatus::Normal).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+	assert_eq!(DlpError::from_io_error(&e), None);
+}
+
+#[test]
+fn user_info() {
+	let info = DlpUserInfo {
+		user_id: 1234,
+		viewer_id: 0,
+		last_sync_pc: 0xCAFE,
+		last_successful_sync: DlpDateTime {
+			year: 2004,
+			month: 5,
+			day: 6,
+			hour: 7,
+			minute: 8,
+			second: 9,
+		},
+		last_sync: DlpDateTime::default(),
+		user_name: "Jo Bloggs".to_string(),
+		password: Vec::new(),
+	};
+
+	let mut client = DlpClient::new(ScriptedDevice::new(vec![response(
+		DlpFunction::ReadUserInfo,
+		info.to_bytes().unwrap(),
+	)]));
+	assert_eq!(client.read_user_info().unwrap(), info);
+	assert!(client.get_ref().requests[0].arguments.is_empty());
+}
+
+#[test]
+fn database_info_round_trip() {
+	let info = database_info("MemoDB", 3);
+	let data = info.to_bytes().unwrap();
+	assert_eq!(data[0] as usize, data.len());
+	assert_eq!(data.len() % 2, 0);
+	assert_eq!(
+		DlpDatabaseInfo::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		info
+	);
+
+	let header = info.to_header();
+	assert_eq!(header.name_trimmed(), b"MemoDB");
+	assert_eq!(header.type_code, *b"DATA");
+	assert_eq!(
+		DlpDateTime::from_timestamp(header.creation_time),
+		info.creation_time
+	);
+	assert_eq!(
+		DlpDatabaseInfo::from_header(&header),
+		DlpDatabaseInfo { index: 0, ..info }
+	);
+}
+
+#[test]
+fn list_databases() {
+	let first = DlpDatabaseList {
+		last_index: 1,
+		more: true,
+		databases: vec![database_info("AddressDB", 0), database_info("MemoDB", 1)],
+	};
+	let second = DlpDatabaseList {
+		last_index: 2,
+		more: true,
+		databases: vec![database_info("ToDoDB", 2)],
+	};
+
+	let mut client = DlpClient::new(ScriptedDevice::new(vec![
+		response(DlpFunction::ReadDbList, first.to_bytes().unwrap()),
+		response(DlpFunction::ReadDbList, second.to_bytes().unwrap()),
+		error_response(DlpFunction::ReadDbList, DlpError::NotFound),
+	]));
+
+	let names = client
+		.list_databases(DLP_DB_LIST_RAM, 0)
+		.unwrap()
+		.into_iter()
+		.map(|x| x.name)
+		.collect::<Vec<_>>();
+	assert_eq!(names, vec!["AddressDB", "MemoDB", "ToDoDB"]);
+
+	// Each request continues after the last database returned
+	let start_indices = client
+		.get_ref()
+		.requests
+		.iter()
+		.map(|x| x.argument(DLP_FIRST_ARGUMENT_ID).unwrap()[2..].to_vec())
+		.collect::<Vec<_>>();
+	assert_eq!(start_indices, vec![vec![0, 0], vec![0, 2], vec![0, 3]]);
+}
+
+#[test]
+fn write_record() {
+	let record = DlpRecord {
+		unique_id: 0,
+		index: 0,
+		attributes: RecordAttributes {
+			dirty: true,
+			category: 2,
+			..RecordAttributes::default()
+		},
+		archived: false,
+		data: b"hello".to_vec(),
+	};
+
+	let mut client = DlpClient::new(ScriptedDevice::new(vec![response(
+		DlpFunction::WriteRecord,
+		vec![0x00, 0x12, 0x34, 0x56],
+	)]));
+	assert_eq!(client.write_record(4, &record).unwrap(), 0x123456);
+	assert_eq!(
+		client.get_ref().requests[0].argument(DLP_FIRST_ARGUMENT_ID),
+		Some(&b"\x04\x80\x00\x00\x00\x00\x40\x02hello"[..])
+	);
+}
+
+#[test]
+fn read_database() {
+	let records = [
+		DlpRecord {
+			unique_id: 0x1001,
+			index: 0,
+			attributes: RecordAttributes {
+				category: 1,
+				..RecordAttributes::default()
+			},
+			archived: false,
+			data: b"first".to_vec(),
+		},
+		DlpRecord {
+			unique_id: 0x1002,
+			index: 1,
+			attributes: RecordAttributes {
+				delete: true,
+				..RecordAttributes::default()
+			},
+			archived: true,
+			data: b"second".to_vec(),
+		},
+	];
+
+	let mut client = DlpClient::new(ScriptedDevice::new(vec![
+		response(DlpFunction::OpenDb, vec![5]),
+		error_response(DlpFunction::ReadAppBlock, DlpError::NotFound),
+		error_response(DlpFunction::ReadSortBlock, DlpError::NotFound),
+		response(DlpFunction::ReadOpenDbInfo, vec![0, 2]),
+		response(DlpFunction::ReadRecord, records[0].to_bytes().unwrap()),
+		response(DlpFunction::ReadRecord, records[1].to_bytes().unwrap()),
+		response(DlpFunction::CloseDb, Vec::new()),
+	]));
+
+	let database = client
+		.read_database::<PdbDatabase>(0, &database_info("MemoDB", 1))
+		.unwrap();
+	assert_eq!(database.header.name_trimmed(), b"MemoDB");
+
+	let entries = database.list_records_resources();
+	assert_eq!(entries.len(), 2);
+	assert_eq!(entries[0].0.unique_id(), Some(0x1001));
+	assert_eq!(entries[0].0.attributes().unwrap().category, 1);
+	assert_eq!(entries[0].1, b"first");
+
+	// Records convert back to the same DLP records, archive flag and all
+	for (record, (header, data)) in records.iter().zip(entries.iter()) {
+		assert_eq!(
+			DlpRecord::from_record(header, data).unwrap(),
+			DlpRecord {
+				index: 0,
+				..record.clone()
+			}
+		);
+	}
+
+	// The database is closed again, and was read by index
+	let requests = &client.get_ref().requests;
+	assert_eq!(requests.last().unwrap().function, DlpFunction::CloseDb);
+	assert!(requests[4].argument(DLP_FIRST_ARGUMENT_ID + 1).is_some());
+}
@@ -0,0 +1,177 @@
+use std::{
+	io::{self, Cursor},
+	net::{Ipv4Addr, TcpStream, UdpSocket},
+	thread,
+	time::Duration,
+};
+
+use palmrs_sync::protocol::{
+	dlp::{client::DlpClient, info::DlpUserInfo, DlpFunction, DlpRequest, DlpResponse},
+	netsync::{
+		answer_wakeup,
+		NetSyncConnection,
+		NetSyncListener,
+		NetSyncPacket,
+		NetSyncPacketType,
+		NetSyncWakeup,
+		NetSyncWakeupType,
+		NETSYNC_DEFAULT_TIMEOUT,
+		NETSYNC_HANDSHAKE_DESKTOP,
+	},
+	MessageTransport,
+};
+use test_env_log::test;
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+#[test]
+fn packet_round_trip() {
+	let packet = NetSyncPacket {
+		packet_type: NetSyncPacketType::Data,
+		transaction_id: 3,
+		data: b"hello".to_vec(),
+	};
+	let data = packet.to_bytes().unwrap();
+	assert_eq!(data, b"\x01\x03\x00\x00\x00\x05hello");
+	assert_eq!(
+		NetSyncPacket::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		packet
+	);
+
+	// Absurdly large messages are rejected before anything is allocated for them
+	assert!(NetSyncPacket::from_bytes(&mut Cursor::new(b"\x01\x03\xFF\xFF\xFF\xFF")).is_err());
+}
+
+#[test]
+fn transaction_ids() {
+	let mut connection = NetSyncConnection::new(Cursor::new(Vec::new()));
+	connection.send_message(b"first").unwrap();
+	connection.send_message(b"second").unwrap();
+	connection.reply_message(b"reply").unwrap();
+
+	let data = connection.into_inner().into_inner();
+	let mut rdr = Cursor::new(data.as_slice());
+	let ids = (0..3)
+		.map(|_| NetSyncPacket::from_bytes(&mut rdr).unwrap().transaction_id)
+		.collect::<Vec<_>>();
+	assert_eq!(ids, vec![1, 2, 2]);
+}
+
+#[test]
+fn handshake_and_dlp() {
+	let listener = NetSyncListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let user_info = DlpUserInfo {
+		user_id: 42,
+		user_name: "Jo Bloggs".to_string(),
+		..DlpUserInfo::default()
+	};
+	let response = DlpResponse::new(DlpFunction::ReadUserInfo)
+		.with_argument(0x20, user_info.to_bytes().unwrap());
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+
+		// Play the device side of the handshake by hand, to check the desktop's message
+		let mut connection = NetSyncConnection::new(stream);
+		connection.send_message(&[0x90, 0x01]).unwrap();
+		let message = connection.receive_message().unwrap();
+		assert_eq!(message, &NETSYNC_HANDSHAKE_DESKTOP[..]);
+		assert_eq!(message.len(), 50);
+		assert_eq!(
+			&message[..22],
+			b"\x12\x01\x00\x00\x00\x00\x00\x00\x00\x20\x00\x00\x00\x24\xff\xff\xff\xff\x3c\x00\x3c\x00"
+		);
+		connection.send_message(&[0x93, 0x00]).unwrap();
+
+		let data = connection.receive_message().unwrap();
+		let request = DlpRequest::from_bytes(&mut Cursor::new(&data)).unwrap();
+		assert_eq!(request.function, DlpFunction::ReadUserInfo);
+		connection
+			.reply_message(&response.to_bytes().unwrap())
+			.unwrap();
+	});
+
+	let (connection, _) = listener.accept().unwrap();
+	connection.get_ref().set_read_timeout(TIMEOUT).unwrap();
+
+	let mut client = DlpClient::new(connection);
+	assert_eq!(client.read_user_info().unwrap().user_name, "Jo Bloggs");
+	device.join().unwrap();
+}
+
+#[test]
+fn device_handshake() {
+	let listener = NetSyncListener::bind("127.0.0.1:0").unwrap();
+	let addr = listener.local_addr().unwrap();
+
+	let device = thread::spawn(move || {
+		let stream = TcpStream::connect(addr).unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+		NetSyncConnection::new(stream).connect_handshake().unwrap();
+	});
+
+	// The session timeout replaces the handshake's
+	let (connection, _) = listener.accept().unwrap();
+	assert_eq!(
+		connection.get_ref().read_timeout().unwrap(),
+		Some(NETSYNC_DEFAULT_TIMEOUT)
+	);
+	device.join().unwrap();
+}
+
+#[test]
+fn stalled_handshake() {
+	let mut listener = NetSyncListener::bind("127.0.0.1:0").unwrap();
+	listener.handshake_timeout = Duration::from_millis(100);
+	let addr = listener.local_addr().unwrap();
+
+	// The device connects, but never says anything
+	let device = TcpStream::connect(addr).unwrap();
+	let e = listener.accept().unwrap_err();
+	assert!(matches!(
+		e.kind(),
+		io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+	));
+	drop(device);
+}
+
+#[test]
+fn wakeup() {
+	let wakeup = NetSyncWakeup {
+		packet_type: NetSyncWakeupType::Request,
+		host_address: Ipv4Addr::new(192, 168, 1, 10),
+		host_netmask: Ipv4Addr::new(255, 255, 255, 0),
+		host_name: "desktop".to_string(),
+	};
+	let data = wakeup.to_bytes().unwrap();
+	assert_eq!(&data[..4], b"\xFA\xDE\x01\x00");
+	assert_eq!(
+		NetSyncWakeup::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		wakeup
+	);
+
+	let desktop = UdpSocket::bind("127.0.0.1:0").unwrap();
+	desktop.set_read_timeout(TIMEOUT).unwrap();
+	let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+	device.set_read_timeout(TIMEOUT).unwrap();
+
+	// Noise is ignored, and the request is acknowledged
+	device
+		.send_to(b"noise", desktop.local_addr().unwrap())
+		.unwrap();
+	device
+		.send_to(&data, desktop.local_addr().unwrap())
+		.unwrap();
+	let (received, addr) = answer_wakeup(&desktop).unwrap();
+	assert_eq!(received, wakeup);
+	assert_eq!(addr, device.local_addr().unwrap());
+
+	let mut buf = [0u8; 512];
+	let (len, _) = device.recv_from(&mut buf).unwrap();
+	let ack = NetSyncWakeup::from_bytes(&mut Cursor::new(&buf[..len])).unwrap();
+	assert_eq!(ack.packet_type, NetSyncWakeupType::Acknowledge);
+	assert_eq!(ack.host_name, "desktop");
+}
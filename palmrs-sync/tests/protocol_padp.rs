@@ -0,0 +1,258 @@
+use std::{
+	io::{self, Cursor, Read, Write},
+	time::Duration,
+};
+
+use palmrs_sync::protocol::{
+	padp::{
+		PadpConnection,
+		PadpPacket,
+		PadpPacketType,
+		PADP_FLAG_FIRST,
+		PADP_FLAG_LAST,
+		PADP_FLAG_LONG_FORM,
+	},
+	slp::{SlpConnection, SlpPacket, SlpPacketType, SlpSocket},
+};
+use test_env_log::test;
+
+/// Transport that plays the other end of a PADP connection
+///
+/// Data fragments written to it are recorded and acknowledged, apart from the first
+/// `unacknowledged` of them. Reads return the queued packets, then time out.
+struct TestPeer {
+	input: Vec<u8>,
+	unacknowledged: usize,
+	received: Vec<(u8, PadpPacket)>,
+}
+
+impl TestPeer {
+	fn new(unacknowledged: usize) -> Self {
+		Self {
+			input: Vec::new(),
+			unacknowledged,
+			received: Vec::new(),
+		}
+	}
+
+	fn queue(&mut self, transaction_id: u8, packet: &PadpPacket) {
+		let slp = SlpPacket {
+			destination: SlpSocket::DesktopLink,
+			source: SlpSocket::DesktopLink,
+			packet_type: SlpPacketType::Padp,
+			transaction_id,
+			data: packet.to_bytes().unwrap(),
+		};
+		self.input.extend(slp.to_bytes().unwrap());
+	}
+}
+
+impl Read for TestPeer {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.input.is_empty() {
+			return Err(io::Error::new(io::ErrorKind::TimedOut, "no data"));
+		}
+
+		let len = buf.len().min(self.input.len());
+		buf[..len].copy_from_slice(&self.input[..len]);
+		self.input.drain(..len);
+		Ok(len)
+	}
+}
+
+impl Write for TestPeer {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		let slp = SlpPacket::from_bytes(&mut Cursor::new(buf)).unwrap();
+		let packet = PadpPacket::from_bytes(&mut Cursor::new(&slp.data)).unwrap();
+
+		if packet.packet_type == PadpPacketType::Data {
+			if self.unacknowledged > 0 {
+				self.unacknowledged -= 1;
+			} else {
+				let ack = PadpPacket {
+					packet_type: PadpPacketType::Ack,
+					data: Vec::new(),
+					..packet.clone()
+				};
+				self.queue(slp.transaction_id, &ack);
+			}
+		}
+
+		self.received.push((slp.transaction_id, packet));
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn padp_connection(peer: TestPeer) -> PadpConnection<TestPeer> {
+	let mut connection = PadpConnection::new(
+		SlpConnection::new(peer),
+		SlpSocket::DesktopLink,
+		SlpSocket::DesktopLink,
+	);
+	connection.ack_timeout = Duration::from_millis(1);
+	connection
+}
+
+fn data_packet(flags: u8, size: u32, data: &[u8]) -> PadpPacket {
+	PadpPacket {
+		packet_type: PadpPacketType::Data,
+		flags,
+		size,
+		data: data.to_vec(),
+	}
+}
+
+fn message(len: usize) -> Vec<u8> {
+	(0..len).map(|x| x as u8).collect()
+}
+
+#[test]
+fn packet_round_trip() {
+	let packet = data_packet(PADP_FLAG_FIRST | PADP_FLAG_LAST, 3, b"abc");
+	let data = packet.to_bytes().unwrap();
+	assert_eq!(data, b"\x01\xC0\x00\x03abc");
+	assert_eq!(
+		PadpPacket::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		packet
+	);
+
+	let packet = data_packet(PADP_FLAG_FIRST | PADP_FLAG_LONG_FORM, 0x12345, b"abc");
+	let data = packet.to_bytes().unwrap();
+	assert_eq!(data, b"\x01\x90\x00\x01\x23\x45abc");
+	assert_eq!(
+		PadpPacket::from_bytes(&mut Cursor::new(&data)).unwrap(),
+		packet
+	);
+
+	// A large size needs the long form
+	assert!(data_packet(PADP_FLAG_FIRST, 0x12345, b"abc")
+		.to_bytes()
+		.is_err());
+}
+
+#[test]
+fn send_fragments() {
+	let mut connection = padp_connection(TestPeer::new(0));
+	connection.send(&message(2500)).unwrap();
+
+	let peer = connection.into_inner().into_inner();
+	let fragments = peer
+		.received
+		.iter()
+		.map(|(_, x)| (x.flags, x.size, x.data.len()))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		fragments,
+		vec![
+			(PADP_FLAG_FIRST, 2500, 1024),
+			(0, 1024, 1024),
+			(PADP_FLAG_LAST, 2048, 452)
+		]
+	);
+
+	// All of the fragments are part of the same transaction
+	assert!(peer.received.iter().all(|(x, _)| *x == peer.received[0].0));
+}
+
+#[test]
+fn send_long_form() {
+	let mut connection = padp_connection(TestPeer::new(0));
+	connection.send(&message(70000)).unwrap();
+
+	let peer = connection.into_inner().into_inner();
+	assert_eq!(peer.received.len(), 69);
+	assert_eq!(
+		peer.received[0].1.flags,
+		PADP_FLAG_FIRST | PADP_FLAG_LONG_FORM
+	);
+	assert_eq!(peer.received[0].1.size, 70000);
+	assert_eq!(
+		peer.received[68].1.flags,
+		PADP_FLAG_LAST | PADP_FLAG_LONG_FORM
+	);
+	assert_eq!(peer.received[68].1.size, 68 * 1024);
+}
+
+#[test]
+fn retransmit_unacknowledged() {
+	let mut connection = padp_connection(TestPeer::new(2));
+	connection.send(b"hello").unwrap();
+	let peer = connection.into_inner().into_inner();
+	assert_eq!(peer.received.len(), 3);
+	assert!(peer.received.iter().all(|(_, x)| x.data == b"hello"));
+
+	let mut connection = padp_connection(TestPeer::new(10));
+	connection.retries = 2;
+	let e = connection.send(b"hello").unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+	assert_eq!(connection.into_inner().into_inner().received.len(), 3);
+}
+
+#[test]
+fn receive_fragments() {
+	let expected = message(2500);
+	let mut peer = TestPeer::new(0);
+	peer.queue(7, &data_packet(PADP_FLAG_FIRST, 2500, &expected[..1024]));
+
+	// The second fragment is sent twice, as if the ack was lost, with a tickle in between
+	let second = data_packet(0, 1024, &expected[1024..2048]);
+	peer.queue(7, &second);
+	peer.queue(
+		8,
+		&PadpPacket {
+			packet_type: PadpPacketType::Tickle,
+			flags: PADP_FLAG_FIRST | PADP_FLAG_LAST,
+			size: 0,
+			data: Vec::new(),
+		},
+	);
+	peer.queue(7, &second);
+	peer.queue(7, &data_packet(PADP_FLAG_LAST, 2048, &expected[2048..]));
+
+	let mut connection = padp_connection(peer);
+	assert_eq!(connection.receive().unwrap(), expected);
+
+	// Every data fragment was acknowledged, including the duplicate
+	let peer = connection.get_ref().get_ref();
+	let acks = peer
+		.received
+		.iter()
+		.map(|(id, x)| (*id, x.packet_type, x.size))
+		.collect::<Vec<_>>();
+	assert_eq!(
+		acks,
+		vec![
+			(7, PadpPacketType::Ack, 2500),
+			(7, PadpPacketType::Ack, 1024),
+			(7, PadpPacketType::Ack, 1024),
+			(7, PadpPacketType::Ack, 2048)
+		]
+	);
+
+	// Replies use the transaction ID of the received message, and new messages don't
+	connection.reply(b"reply").unwrap();
+	connection.send(b"request").unwrap();
+	let peer = connection.into_inner().into_inner();
+	assert_eq!(peer.received[4].0, 7);
+	assert_eq!(peer.received[4].1.data, b"reply");
+	assert_ne!(peer.received[5].0, 7);
+	assert_eq!(peer.received[5].1.data, b"request");
+}
+
+#[test]
+fn reply_needs_message() {
+	let mut connection = padp_connection(TestPeer::new(0));
+	assert!(connection.reply(b"reply").is_err());
+}
+
+#[test]
+fn receive_timeout() {
+	let mut connection = padp_connection(TestPeer::new(0));
+	connection.receive_timeout = Duration::from_millis(10);
+	let e = connection.receive().unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
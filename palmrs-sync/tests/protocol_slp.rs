@@ -0,0 +1,121 @@
+use std::io::{self, Cursor, Read, Write};
+
+use palmrs_sync::protocol::slp::{crc16, SlpConnection, SlpPacket, SlpPacketType, SlpSocket};
+use test_env_log::test;
+
+/// A PADP packet from socket 3 to socket 3, with transaction ID 0x11 and a four byte body
+const EXAMPLE_PACKET: &[u8] = b"\xBE\xEF\xED\x03\x03\x02\x00\x04\x11\xB7\x01\x02\x03\x04\x36\x11";
+
+/// Transport that reads from a fixed buffer, in chunks of the given size, and records writes
+struct TestTransport {
+	input: Cursor<Vec<u8>>,
+	chunk_size: usize,
+	output: Vec<u8>,
+}
+
+impl TestTransport {
+	fn new(input: &[u8], chunk_size: usize) -> Self {
+		Self {
+			input: Cursor::new(input.to_vec()),
+			chunk_size,
+			output: Vec::new(),
+		}
+	}
+}
+
+impl Read for TestTransport {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		let len = buf.len().min(self.chunk_size);
+		self.input.read(&mut buf[..len])
+	}
+}
+
+impl Write for TestTransport {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.output.write(buf)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+}
+
+fn packet() -> SlpPacket {
+	SlpPacket {
+		destination: SlpSocket::DesktopLink,
+		source: SlpSocket::DesktopLink,
+		packet_type: SlpPacketType::Padp,
+		transaction_id: 0x11,
+		data: vec![1, 2, 3, 4],
+	}
+}
+
+#[test]
+fn crc() {
+	assert_eq!(crc16(b"123456789"), 0x31C3);
+}
+
+#[test]
+fn packet_round_trip() {
+	assert_eq!(packet().to_bytes().unwrap(), EXAMPLE_PACKET);
+	assert_eq!(
+		SlpPacket::from_bytes(&mut Cursor::new(EXAMPLE_PACKET)).unwrap(),
+		packet()
+	);
+
+	let mut corrupt = EXAMPLE_PACKET.to_vec();
+	corrupt[11] ^= 0xFF;
+	assert!(SlpPacket::from_bytes(&mut Cursor::new(&corrupt)).is_err());
+}
+
+#[test]
+fn write_packets() {
+	let mut connection = SlpConnection::new(TestTransport::new(&[], 1));
+	connection.write_packet(&packet()).unwrap();
+	assert_eq!(connection.into_inner().output, EXAMPLE_PACKET);
+}
+
+#[test]
+fn read_packets_in_pieces() {
+	let stream = [EXAMPLE_PACKET, EXAMPLE_PACKET].concat();
+	for chunk_size in [1, 3, 7, 1024].iter() {
+		let mut connection = SlpConnection::new(TestTransport::new(&stream, *chunk_size));
+		assert_eq!(connection.read_packet().unwrap(), packet());
+		assert_eq!(connection.read_packet().unwrap(), packet());
+
+		let e = connection.read_packet().unwrap_err();
+		assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+	}
+}
+
+#[test]
+fn resynchronise_after_noise() {
+	// A stray preamble byte, a truncated packet, a packet with a corrupted body, and a false
+	// preamble followed by an invalid header
+	let mut corrupt = EXAMPLE_PACKET.to_vec();
+	corrupt[12] ^= 0x55;
+	let stream = [
+		&b"\x00\xBE\xEF\x42"[..],
+		&EXAMPLE_PACKET[..6],
+		&corrupt,
+		b"\xBE\xEF\xED\x01\x02\x03\x04\x05\x06\x07",
+		EXAMPLE_PACKET,
+	]
+	.concat();
+
+	let mut connection = SlpConnection::new(TestTransport::new(&stream, 5));
+	assert_eq!(connection.read_packet().unwrap(), packet());
+	assert!(connection.read_packet().is_err());
+}
+
+#[test]
+fn transaction_ids() {
+	let mut connection = SlpConnection::new(TestTransport::new(&[], 1));
+	let ids = (0..300)
+		.map(|_| connection.next_transaction_id())
+		.collect::<Vec<_>>();
+	assert_eq!(ids[0], 1);
+	assert_eq!(ids[253], 0xFE);
+	assert_eq!(ids[254], 1);
+	assert!(!ids.contains(&0) && !ids.contains(&0xFF));
+}
@@ -0,0 +1,185 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+	io::{self, Cursor, Read, Write},
+	net::TcpListener,
+	os::unix::io::AsRawFd,
+	path::PathBuf,
+	thread,
+	time::Duration,
+};
+
+use nix::{
+	fcntl::OFlag,
+	pty::{grantpt, posix_openpt, ptsname_r, unlockpt, PtyMaster},
+	sys::termios::{cfgetospeed, tcgetattr, BaudRate},
+};
+use palmrs_sync::{
+	protocol::{
+		cmp::{self, CMP_DEFAULT_BAUD_RATES},
+		padp::{PadpConnection, PadpPacket, PadpPacketType},
+		slp::{SlpConnection, SlpPacket, SlpSocket},
+	},
+	serial::{accept_session, SerialEndpoint, SerialLink, SerialPort},
+};
+use test_env_log::test;
+
+const TIMEOUT: Option<Duration> = Some(Duration::from_secs(5));
+
+/// Open a pseudo-terminal, returning the master end (playing the device) and the slave's path
+fn pty() -> (PtyMaster, String) {
+	let master = posix_openpt(OFlag::O_RDWR | OFlag::O_NOCTTY).unwrap();
+	grantpt(&master).unwrap();
+	unlockpt(&master).unwrap();
+	let path = ptsname_r(&master).unwrap();
+	(master, path)
+}
+
+fn padp_connection<T: Read + Write>(transport: T) -> PadpConnection<T> {
+	PadpConnection::new(
+		SlpConnection::new(transport),
+		SlpSocket::DesktopLink,
+		SlpSocket::DesktopLink,
+	)
+}
+
+#[test]
+fn endpoints() {
+	assert_eq!(
+		"/dev/ttyUSB0".parse::<SerialEndpoint>().unwrap(),
+		SerialEndpoint::Device(PathBuf::from("/dev/ttyUSB0"))
+	);
+	assert_eq!(
+		"tcp:localhost:6416".parse::<SerialEndpoint>().unwrap(),
+		SerialEndpoint::TcpConnect("localhost:6416".to_string())
+	);
+	assert_eq!(
+		"tcp-listen:0.0.0.0:6416".parse::<SerialEndpoint>().unwrap(),
+		SerialEndpoint::TcpListen("0.0.0.0:6416".to_string())
+	);
+	assert!("".parse::<SerialEndpoint>().is_err());
+}
+
+#[test]
+fn raw_mode() {
+	let (mut master, path) = pty();
+	let mut port = SerialPort::open(&path, 9600).unwrap();
+	port.set_read_timeout(TIMEOUT).unwrap();
+
+	// Control characters and line endings pass through untouched, both ways
+	let data = b"\x03\x04\x0D\x0A\x11\x13\x1A\x7F\xBE\xEF";
+	master.write_all(data).unwrap();
+	let mut buf = [0u8; 10];
+	port.read_exact(&mut buf).unwrap();
+	assert_eq!(&buf, data);
+
+	port.write_all(data).unwrap();
+	master.read_exact(&mut buf).unwrap();
+	assert_eq!(&buf, data);
+
+	// Reads time out rather than looking like the end of the stream
+	port.set_read_timeout(Some(Duration::from_millis(100)))
+		.unwrap();
+	let e = port.read(&mut buf).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+}
+
+#[test]
+fn baud_rates() {
+	let (_master, path) = pty();
+	let mut port = SerialPort::open(&path, 9600).unwrap();
+	assert_eq!(port.baud_rate(), 9600);
+
+	port.set_baud_rate(115_200).unwrap();
+	assert_eq!(port.baud_rate(), 115_200);
+	let termios = tcgetattr(port.get_ref().as_raw_fd()).unwrap();
+	assert_eq!(cfgetospeed(&termios), BaudRate::B115200);
+
+	// Rates that don't exist are refused, and the port stays as it was
+	let e = port.set_baud_rate(12345).unwrap_err();
+	assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+	assert_eq!(port.baud_rate(), 115_200);
+}
+
+#[test]
+fn session_over_pty() {
+	let (master, path) = pty();
+	let mut port = SerialPort::open(&path, 9600).unwrap();
+	port.set_read_timeout(TIMEOUT).unwrap();
+
+	let device = thread::spawn(move || {
+		let mut padp = padp_connection(master);
+		let session = cmp::wakeup(&mut padp, 57600).unwrap();
+		let message = padp.receive().unwrap();
+
+		// Closing the master end would fail the desktop's reads before it sees the ack
+		(session, message, padp)
+	});
+
+	let mut padp = padp_connection(port);
+	let session = accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+	assert_eq!(session.baud_rate, 57600);
+	assert_eq!(padp.get_ref().get_ref().baud_rate(), 57600);
+
+	// The session carries on at the new rate
+	padp.send(b"hello").unwrap();
+	let (device_session, message, _padp) = device.join().unwrap();
+	assert_eq!(device_session.baud_rate, 57600);
+	assert_eq!(message, b"hello");
+}
+
+#[test]
+fn session_over_emulator_socket() {
+	let emulator = TcpListener::bind("127.0.0.1:0").unwrap();
+	let endpoint = SerialEndpoint::TcpConnect(emulator.local_addr().unwrap().to_string());
+
+	let device = thread::spawn(move || {
+		let (stream, _) = emulator.accept().unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+		let mut padp = padp_connection(stream);
+		cmp::wakeup(&mut padp, 115_200).unwrap()
+	});
+
+	let mut padp = padp_connection(endpoint.open().unwrap());
+	let session = accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES).unwrap();
+	assert_eq!(session.baud_rate, 115_200);
+	assert_eq!(device.join().unwrap().baud_rate, 115_200);
+}
+
+#[test]
+fn lost_ack_over_emulator_socket() {
+	let emulator = TcpListener::bind("127.0.0.1:0").unwrap();
+	let endpoint = SerialEndpoint::TcpConnect(emulator.local_addr().unwrap().to_string());
+
+	// The device doesn't acknowledge the first copy of the fragment
+	let device = thread::spawn(move || {
+		let (stream, _) = emulator.accept().unwrap();
+		stream.set_read_timeout(TIMEOUT).unwrap();
+		let mut slp = SlpConnection::new(stream);
+		let first = slp.read_packet().unwrap();
+		let second = slp.read_packet().unwrap();
+
+		let packet = PadpPacket::from_bytes(&mut Cursor::new(&second.data)).unwrap();
+		let ack = PadpPacket {
+			packet_type: PadpPacketType::Ack,
+			data: Vec::new(),
+			..packet
+		};
+		slp.write_packet(&SlpPacket {
+			data: ack.to_bytes().unwrap(),
+			..second.clone()
+		})
+		.unwrap();
+
+		(first, second, slp)
+	});
+
+	// Waiting for the ack relies on the link's reads timing out
+	let mut padp = padp_connection(endpoint.open().unwrap());
+	padp.send(b"hello").unwrap();
+
+	let (first, second, _slp) = device.join().unwrap();
+	assert_eq!(first, second);
+	let packet = PadpPacket::from_bytes(&mut Cursor::new(&first.data)).unwrap();
+	assert_eq!(packet.data, b"hello");
+}
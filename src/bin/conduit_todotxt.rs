@@ -2,19 +2,11 @@
 
 use std::{
 	fs,
-	io::{BufRead, Cursor},
-	path::Path,
-	str,
+	path::{Path, PathBuf},
 };
 
-use byteorder::{BigEndian, ReadBytesExt};
 use palmrs::{
-	database::{
-		info::ExtraInfoRecord,
-		record::DatabaseRecord,
-		PalmDatabase,
-		PdbWithCategoriesDatabase,
-	},
+	database::pim::todo::{ToDoDatabase, ToDoRecord, TASKS_DATABASE_NAME, TODO_DATABASE_NAME},
 	sync::{
 		conduit::{ConduitRequirements, WithinConduit},
 		SyncMode,
@@ -84,116 +76,50 @@ impl ToDoTask {
 	}
 }
 
-/// Parse tasks out of a `ToDoDB` database
+/// Parse tasks out of a `ToDoDB` or `TasksDB-PTod` database
 pub fn device_database_parse(db_path: &Path) -> Result<Vec<ToDoTask>, Report> {
 	let db_content =
 		fs::read(db_path).wrap_err_with(|| eyre!("Failed to read database: {:?}", db_path))?;
-	let database = PalmDatabase::<PdbWithCategoriesDatabase>::from_bytes(&db_content)
-		.wrap_err_with(|| eyre!("Failed to parse device ToDoDB: {:?}", db_path))?;
-
-	let categories: Vec<(u8, String)> = {
-		let mut categories = Vec::new();
-		if let Some(db_cats) = database.app_info.data_item_categories() {
-			for cat in db_cats.iter() {
-				categories.push((
-					cat.category_id,
-					String::from(cat.name_try_str().unwrap_or("ErrCategory")),
-				));
-			}
-		}
-
-		categories
-	};
+	let database = ToDoDatabase::from_bytes(&db_content)
+		.wrap_err_with(|| eyre!("Failed to parse device tasks database: {:?}", db_path))?;
 
 	let mut tasks = Vec::new();
-	'dbtaskparse: for (idx, (rec_hdr, rec_data)) in
-		(0..).zip(database.list_records_resources().iter())
-	{
-		log::trace!("records[{}].rec_hdr = {:?}", idx, &rec_hdr);
-		if rec_hdr.data_len().unwrap_or(0) == 0 {
-			break 'dbtaskparse;
-		}
-
-		// Get category from record attributes
-		let rec_attributes = rec_hdr.attributes().unwrap_or_default();
-		let category = {
-			let mut catname: Option<String> = None;
-			let catid = rec_attributes.category;
-			'catsearch: for (x_catid, x_catname) in categories.iter() {
-				if catid == *x_catid {
-					catname = Some(x_catname.clone());
-					break 'catsearch;
-				}
-			}
-
-			catname
-		};
-
-		// Parse out the due date, priority, and completion flag
-		let mut cursor = Cursor::new(&rec_data);
-		let due_date = {
-			let mut due_date: Option<(u16, u8, u8)> = None;
-			let raw_date = cursor.read_u16::<BigEndian>()?;
-			if raw_date != 0xFFFF {
-				due_date = Some((
-					((raw_date >> 9) & 0x007F) + 1904,
-					((raw_date >> 5) & 0x000F) as u8,
-					(raw_date & 0x001F) as u8,
-				));
-			}
-
-			due_date
-		};
-
-		let priority = cursor.read_u8()?;
-		let completed = priority & 0x80 != 0;
-		let priority = priority & 0x7F;
-
-		// Read out the task text
-		let task_text = {
-			let mut buf: Vec<u8> = Vec::new();
-			let count = cursor
-				.read_until(0x00, &mut buf)
-				.expect("cursor read fail?");
-
-			str::from_utf8(&buf[0..(count - 1)])
-				.wrap_err("UTF-8 conversion failed")?
-				.to_string()
-		};
-
-		// Read out the note text
-		let note_text = {
-			let mut buf: Vec<u8> = Vec::new();
-			let count = cursor
-				.read_until(0x00, &mut buf)
-				.expect("cursor read fail?");
-
-			if count > 1 {
-				let s = str::from_utf8(&buf[0..(count - 1)])
-					.wrap_err("UTF-8 conversion failed")?
-					.to_string();
-
-				Some(s)
-			} else {
-				None
-			}
-		};
+	for (idx, (attributes, task)) in database.tasks()?.into_iter().enumerate() {
+		log::trace!("records[{}] = {:?}, {:?}", idx, &attributes, &task);
 
-		// And construct our task object!
+		// Tasks-only fields (alarms, repeats and completion dates) have no todo.txt equivalent
+		let record = ToDoRecord::from(task);
 		tasks.push(ToDoTask {
-			task_text,
-			note_text,
-			category,
-			priority,
-			completed,
-			due_date,
+			task_text: record.description,
+			note_text: Some(record.note).filter(|x| !x.is_empty()),
+			category: database
+				.database
+				.app_info
+				.category_name(attributes.category),
+			priority: record.priority,
+			completed: record.completed,
+			due_date: record.due_date.map(|x| (x.year, x.month, x.day)),
 		});
 	}
 
 	Ok(tasks)
 }
 
-/// Perform a one-way conversion from the Palm OS `ToDoDB` to the `todo.txt` format
+/// Return the path to the tasks database in the device data directory
+///
+/// The Palm OS 5 Tasks database is used if it exists, as devices with both databases only keep
+/// the classic To Do List database for compatibility.
+pub fn device_database_path(path_device: &Path) -> PathBuf {
+	let tasks = path_device.join(format!("{}.pdb", TASKS_DATABASE_NAME));
+	if tasks.exists() {
+		tasks
+	} else {
+		path_device.join(format!("{}.pdb", TODO_DATABASE_NAME))
+	}
+}
+
+/// Perform a one-way conversion from the Palm OS `ToDoDB` (or `TasksDB-PTod`) to the `todo.txt`
+/// format
 ///
 /// If `conduit.config.environment["SINGLE_FILE"]` is set to `"1"`, this method will store both
 /// incomplete and complete tasks in the same file (named `todo.txt`). If it is set to any other
@@ -202,8 +128,8 @@ pub fn device_database_parse(db_path: &Path) -> Result<Vec<ToDoTask>, Report> {
 pub fn palm_to_todotxt(conduit: &WithinConduit) -> Result<(), Report> {
 	log::info!("palm_to_todotxt: parsing Palm OS tasks database");
 
-	let mut device_database = conduit.config.path_device.clone();
-	device_database.push("ToDoDB.pdb");
+	let device_database = device_database_path(&conduit.config.path_device);
+	log::debug!("device_database = {:?}", &device_database);
 	let device_tasks = device_database_parse(&device_database)?;
 	log::trace!("device_tasks = {:#?}", &device_tasks);
 
@@ -277,7 +203,7 @@ pub fn main() -> Result<(), Report> {
 	let conduit = WithinConduit::new(
 		"palmrs-conduit-todotxt",
 		ConduitRequirements::new()
-			.with_databases(&["ToDoDB"])
+			.with_databases(&[TODO_DATABASE_NAME, TASKS_DATABASE_NAME])
 			.finish(),
 	)
 	.from_env()
@@ -1,9 +1,19 @@
-use std::path::PathBuf;
+use std::{
+	io::Cursor,
+	path::{Path, PathBuf},
+};
 
 use palmrs::database::{
+	disasm::{Disassembler, TrapTable},
 	header::DatabaseHeader,
 	info::ExtraInfoRecord,
+	keyring::KeyringDatabase,
+	notepad::NotePadDatabase,
+	pim::{expense::ExpenseDatabase, mail::MailDatabase},
+	plucker::{PluckerDocument, PluckerRecordHeader, PLUCKER_CREATOR_CODE, PLUCKER_TYPE_CODE},
+	prefs::NumberFormat,
 	record::DatabaseRecord,
+	resource::{code::JumpTable, Resource},
 	DatabaseFormat,
 	PalmDatabase,
 	PdbWithCategoriesDatabase,
@@ -13,6 +23,9 @@ use pretty_hex::{config_hex, HexConfig};
 use stable_eyre::eyre::{eyre, Report, WrapErr};
 use structopt::StructOpt;
 
+/// Environment variable to read the KeyRing master password from, instead of prompting for it
+const KEYRING_PASSWORD_VAR: &str = "PALMRS_KEYRING_PASSWORD";
+
 /// Dump the headers, and optionally the record contents, of a Palm OS database file
 #[derive(Debug, StructOpt)]
 #[structopt(name = "palmrs-db-dump")]
@@ -21,6 +34,58 @@ struct Opt {
 	#[structopt(short, long)]
 	hexdump_records: bool,
 
+	/// Decode and print the contents of known resource types (forms, menus, strings, alerts,
+	/// fonts, bitmaps, jump tables and initialised data), and of the records of Plucker
+	/// documents
+	#[structopt(short, long)]
+	decode_resources: bool,
+
+	/// Disassemble the 68k code resources (`code` 1 and above)
+	#[structopt(long)]
+	disasm: bool,
+
+	/// Load additional system trap names for the disassembler from the given file, with one
+	/// "<trap number> <name>" pair per line
+	#[structopt(long, parse(from_os_str))]
+	trap_table: Option<PathBuf>,
+
+	/// Export a Plucker document as HTML to the given directory, with its images alongside it
+	/// as PNG files
+	#[structopt(long, parse(from_os_str))]
+	export_html: Option<PathBuf>,
+
+	/// Export the sketches of a Note Pad database as PNG files to the given directory
+	#[structopt(long, parse(from_os_str))]
+	export_png: Option<PathBuf>,
+
+	/// Export the folders of a Mail database as Maildirs within the given directory
+	#[structopt(long, parse(from_os_str))]
+	export_maildir: Option<PathBuf>,
+
+	/// Export the folders of a Mail database as mbox files within the given directory
+	#[structopt(long, parse(from_os_str))]
+	export_mbox: Option<PathBuf>,
+
+	/// Export the expenses of an Expense database as CSV to the given file
+	#[structopt(long, parse(from_os_str))]
+	export_csv: Option<PathBuf>,
+
+	/// Export the expenses of an Expense database as a ledger journal to the given file
+	#[structopt(long, parse(from_os_str))]
+	export_ledger: Option<PathBuf>,
+
+	/// Number format the Expense amounts were entered in, for the ledger export, as a Palm OS
+	/// `NumberFormatType` (0 for "1,000.00", 1 for "1.000,00", 2 for "1 000,00", 3 for
+	/// "1'000.00", 4 for "1'000,00")
+	#[structopt(long, default_value = "0")]
+	number_format: u8,
+
+	/// Decrypt and print the entries of a GNU KeyRing database. The master password is taken
+	/// from the `PALMRS_KEYRING_PASSWORD` environment variable if set, and is prompted for
+	/// otherwise
+	#[structopt(long)]
+	keyring: bool,
+
 	/// Path to the Palm OS database to dump
 	#[structopt(name = "FILE", parse(from_os_str))]
 	filename: PathBuf,
@@ -121,7 +186,41 @@ where
 	Ok(())
 }
 
-fn perform_dump_record<T>(idx: usize, rec_hdr: &T, rec_data: &[u8], opt: &Opt) -> Result<(), Report>
+fn perform_disasm(
+	resource: &Resource,
+	jump_table: Option<&JumpTable>,
+	traps: &TrapTable,
+) -> Result<(), Report> {
+	let segment = match resource {
+		Resource::Code(segment) => segment,
+		_ => return Ok(()),
+	};
+
+	let mut disassembler = Disassembler::new(traps);
+	if let Some(jump_table) = jump_table {
+		disassembler = disassembler.with_jump_table(jump_table);
+	}
+
+	for instruction in disassembler.disassemble_segment(segment) {
+		for entry in instruction.entry_points.iter() {
+			println!("  ; jump table entry {}", entry);
+		}
+
+		println!("  {}", instruction);
+	}
+
+	Ok(())
+}
+
+fn perform_dump_record<T>(
+	idx: usize,
+	rec_hdr: &T,
+	rec_data: &[u8],
+	jump_table: Option<&JumpTable>,
+	traps: &TrapTable,
+	plucker: Option<&PluckerDocument>,
+	opt: &Opt,
+) -> Result<(), Report>
 where
 	T: DatabaseRecord,
 {
@@ -140,6 +239,32 @@ where
 		u8::from(attributes),
 	);
 
+	// The first record of a Plucker document is the index, which is decoded separately
+	if let (true, Some(document), true) = (opt.decode_resources, plucker, idx > 0) {
+		match PluckerRecordHeader::from_bytes(&mut Cursor::new(rec_data)) {
+			Ok(header) => match document.decode_record(&header, &rec_data[8..]) {
+				Ok(record) => println!("{:#?}\n{:#?}", header, record),
+				Err(e) => println!("  Failed to decode Plucker record: {}", e),
+			},
+			Err(e) => println!("  Failed to decode Plucker record header: {}", e),
+		}
+	} else if opt.decode_resources {
+		match Resource::from_record(rec_hdr, rec_data) {
+			// Code segments are better viewed as a hex dump
+			Ok(Some(Resource::Code(_))) | Ok(None) => {}
+			Ok(Some(resource)) => println!("{:#?}", resource),
+			Err(e) => println!("  Failed to decode resource: {}", e),
+		}
+	}
+
+	if opt.disasm {
+		match Resource::from_record(rec_hdr, rec_data) {
+			Ok(Some(resource)) => perform_disasm(&resource, jump_table, traps)?,
+			Ok(None) => {}
+			Err(e) => println!("  Failed to decode resource: {}", e),
+		}
+	}
+
 	if opt.hexdump_records {
 		println!(
 			"{}",
@@ -156,6 +281,72 @@ where
 	Ok(())
 }
 
+fn is_plucker(header: &DatabaseHeader) -> bool {
+	&header.type_code == PLUCKER_TYPE_CODE && &header.creator_code == PLUCKER_CREATOR_CODE
+}
+
+fn perform_export_html(data: &[u8], dir: &Path) -> Result<(), Report> {
+	let document = PluckerDocument::from_bytes(data).wrap_err("Failed to read Plucker document")?;
+	let (html, images) = document
+		.to_html()
+		.wrap_err("Failed to convert Plucker document to HTML")?;
+
+	std::fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create {:?}", dir))?;
+	let path = dir.join("index.html");
+	std::fs::write(&path, html).wrap_err_with(|| format!("Failed to write {:?}", path))?;
+	for image in images.iter() {
+		let path = dir.join(&image.name);
+		std::fs::write(&path, &image.data)
+			.wrap_err_with(|| format!("Failed to write {:?}", path))?;
+	}
+
+	println!(
+		"Exported {:?} ({} images) to {:?}",
+		document.title()?,
+		images.len(),
+		dir
+	);
+
+	Ok(())
+}
+
+fn perform_export_png(data: &[u8], dir: &Path) -> Result<(), Report> {
+	let database =
+		NotePadDatabase::from_bytes(data).wrap_err("Failed to read Note Pad database")?;
+	let notes = database
+		.notes()
+		.wrap_err("Failed to decode Note Pad records")?;
+
+	std::fs::create_dir_all(dir).wrap_err_with(|| format!("Failed to create {:?}", dir))?;
+	let mut count = 0;
+	for (idx, note) in notes.iter().enumerate() {
+		let sketch = match &note.sketch {
+			Some(x) => x,
+			None => continue,
+		};
+
+		let png = sketch
+			.to_png()
+			.wrap_err_with(|| format!("Failed to convert sketch of note {}", idx))?;
+		let path = dir.join(format!("note-{}.png", idx));
+		std::fs::write(&path, png).wrap_err_with(|| format!("Failed to write {:?}", path))?;
+
+		println!(
+			"{:?}: {:?} ({})",
+			path,
+			note.title.as_deref().unwrap_or(""),
+			note.modified
+				.to_datetime()
+				.map(|x| x.to_string())
+				.unwrap_or_default(),
+		);
+		count += 1;
+	}
+
+	println!("Exported {} sketches to {:?}", count, dir);
+	Ok(())
+}
+
 fn perform_dump<T: DatabaseFormat>(data: &[u8], opt: &Opt) -> Result<(), Report> {
 	let database = PalmDatabase::<T>::from_bytes(&data)
 		.wrap_err_with(|| format!("Failed to initialize PalmDatabase for {:?}", &opt.filename))?;
@@ -164,6 +355,52 @@ fn perform_dump<T: DatabaseFormat>(data: &[u8], opt: &Opt) -> Result<(), Report>
 	log::trace!("database.header = {:#?}", &database.header);
 	perform_dump_header(&database.header)?;
 
+	// Load the jump table and trap names for the disassembler, which is the only user of them
+	let jump_table = if opt.disasm {
+		database
+			.list_records_resources()
+			.iter()
+			.find(|(hdr, _)| hdr.name_str() == Some("code") && hdr.resource_id() == Some(0))
+			.and_then(
+				|(_, data)| match JumpTable::from_bytes(&mut Cursor::new(data.as_slice())) {
+					Ok(x) => Some(x),
+					Err(e) => {
+						log::warn!("Failed to read jump table, disassembling without it: {}", e);
+						None
+					}
+				},
+			)
+	} else {
+		None
+	};
+
+	let mut traps = TrapTable::builtin();
+	if let Some(path) = &opt.trap_table {
+		let text = std::fs::read_to_string(path)
+			.wrap_err_with(|| format!("Failed to read trap table from {:?}", path))?;
+		traps
+			.parse(&text)
+			.wrap_err_with(|| format!("Failed to parse trap table from {:?}", path))?;
+	}
+
+	// Plucker records are only decoded on request, so a broken document still gets its headers
+	// and hex dumps printed
+	let plucker = if opt.decode_resources && is_plucker(&database.header) {
+		match PluckerDocument::from_bytes(data) {
+			Ok(document) => {
+				println!();
+				println!("Plucker index:         {:#?}", document.index);
+				Some(document)
+			}
+			Err(e) => {
+				log::warn!("Failed to read Plucker document: {}", e);
+				None
+			}
+		}
+	} else {
+		None
+	};
+
 	// Dump each record, additionally dumping app info before the first record
 	for (idx, (rec_hdr, rec_data)) in (0..).zip(database.list_records_resources().iter()) {
 		if idx == 0 {
@@ -171,7 +408,39 @@ fn perform_dump<T: DatabaseFormat>(data: &[u8], opt: &Opt) -> Result<(), Report>
 		}
 
 		println!();
-		perform_dump_record(idx, rec_hdr, &rec_data, &opt)?;
+		perform_dump_record(
+			idx,
+			rec_hdr,
+			rec_data,
+			jump_table.as_ref(),
+			&traps,
+			plucker.as_ref(),
+			opt,
+		)?;
+	}
+
+	Ok(())
+}
+
+fn perform_dump_keyring(data: &[u8], password: &str) -> Result<(), Report> {
+	let database = KeyringDatabase::from_bytes(data).wrap_err("Failed to read KeyRing database")?;
+	let key = database
+		.unlock(password)
+		.wrap_err("Failed to unlock KeyRing database")?;
+
+	for (attributes, entry) in database
+		.entries(&key)
+		.wrap_err("Failed to decrypt KeyRing entries")?
+	{
+		println!(
+			"[{}] {:#?}",
+			database
+				.database
+				.app_info
+				.category_name(attributes.category)
+				.unwrap_or_default(),
+			entry
+		);
 	}
 
 	Ok(())
@@ -206,6 +475,62 @@ fn main() -> Result<(), Report> {
 		&opt.filename
 	);
 
+	if let Some(dir) = &opt.export_html {
+		return perform_export_html(&content[..], dir);
+	}
+
+	if let Some(dir) = &opt.export_png {
+		return perform_export_png(&content[..], dir);
+	}
+
+	if opt.export_maildir.is_some() || opt.export_mbox.is_some() {
+		let database =
+			MailDatabase::from_bytes(&content[..]).wrap_err("Failed to read Mail database")?;
+		if let Some(dir) = &opt.export_maildir {
+			database
+				.export_maildir(dir)
+				.wrap_err_with(|| format!("Failed to export Maildirs to {:?}", dir))?;
+		}
+		if let Some(dir) = &opt.export_mbox {
+			database
+				.export_mbox(dir)
+				.wrap_err_with(|| format!("Failed to export mbox files to {:?}", dir))?;
+		}
+
+		return Ok(());
+	}
+
+	if opt.export_csv.is_some() || opt.export_ledger.is_some() {
+		let database = ExpenseDatabase::from_bytes(&content[..])
+			.wrap_err("Failed to read Expense database")?;
+		if let Some(path) = &opt.export_csv {
+			let mut file = std::fs::File::create(path)
+				.wrap_err_with(|| format!("Failed to create CSV file {:?}", path))?;
+			database
+				.write_csv(&mut file)
+				.wrap_err_with(|| format!("Failed to export CSV to {:?}", path))?;
+		}
+		if let Some(path) = &opt.export_ledger {
+			let mut file = std::fs::File::create(path)
+				.wrap_err_with(|| format!("Failed to create ledger file {:?}", path))?;
+			database
+				.write_ledger(&mut file, NumberFormat::from(opt.number_format))
+				.wrap_err_with(|| format!("Failed to export ledger journal to {:?}", path))?;
+		}
+
+		return Ok(());
+	}
+
+	if opt.keyring {
+		let password = match std::env::var(KEYRING_PASSWORD_VAR) {
+			Ok(x) => x,
+			Err(_) => rpassword::prompt_password("KeyRing master password: ")
+				.wrap_err("Failed to read KeyRing master password")?,
+		};
+
+		return perform_dump_keyring(&content[..], &password);
+	}
+
 	match db_type {
 		"prc" => perform_dump::<PrcDatabase>(&content[..], &opt),
 		"pdb" => perform_dump::<PdbWithCategoriesDatabase>(&content[..], &opt),
@@ -0,0 +1,294 @@
+use std::{
+	fs::{self, File},
+	io::{self, Cursor},
+	net::UdpSocket,
+	path::{Path, PathBuf},
+	thread,
+};
+
+use palmrs::sync::{
+	capture::{Capture, CaptureLink, CaptureStream, ReplayStream},
+	conduit::ConduitHandler,
+	config::SyncConfig,
+	hotsync::{default_data_root, HotSyncReport},
+	protocol::{
+		cmp::CMP_DEFAULT_BAUD_RATES,
+		dlp::client::DlpClient,
+		netsync::{
+			answer_wakeup,
+			NetSyncConnection,
+			NetSyncListener,
+			NETSYNC_DATA_PORT,
+			NETSYNC_WAKEUP_PORT,
+		},
+		padp::PadpConnection,
+		slp::{SlpConnection, SlpSocket},
+		MessageTransport,
+	},
+	serial::{accept_session, SerialEndpoint, SerialLink},
+	SyncMode,
+};
+use stable_eyre::eyre::{eyre, Report, WrapErr};
+use structopt::StructOpt;
+
+/// HotSync with a Palm OS device, running the given sync conduits
+#[derive(Debug, StructOpt)]
+#[structopt(name = "palmrs-sync")]
+struct Opt {
+	/// Path to sync configuration
+	#[structopt(short = "C", long, parse(from_os_str))]
+	config: Option<PathBuf>,
+
+	/// Serial port to wait for the device on (or `tcp:host:port` to connect to an emulator, or
+	/// `tcp-listen:host:port` to wait for one); without this, wait for a network HotSync
+	#[structopt(short, long)]
+	port: Option<SerialEndpoint>,
+
+	/// Address to listen for network HotSyncs on
+	#[structopt(long, default_value = "0.0.0.0")]
+	listen: String,
+
+	/// Directory to keep synced data in, with a subdirectory for each user, for devices without
+	/// their own in the configuration (overrides the configuration's, and defaults to
+	/// `~/Documents/palm.rs`)
+	#[structopt(short, long, parse(from_os_str))]
+	data_root: Option<PathBuf>,
+
+	/// Name of a sync conduit to run, as well as the configured ones (can be given more than
+	/// once)
+	#[structopt(short, long = "conduit", number_of_values = 1)]
+	conduits: Vec<String>,
+
+	/// Sync mode to run the conduits given with `--conduit` in
+	#[structopt(short = "m", long, default_value = "merge", parse(try_from_str))]
+	sync_mode: SyncMode,
+
+	/// Pull every database from the device, not just the ones the conduits need (or the
+	/// configuration includes)
+	#[structopt(short, long)]
+	backup: bool,
+
+	/// Exit after one sync, rather than waiting for the next device
+	#[structopt(long)]
+	once: bool,
+
+	/// Record the sync session to a capture file, which `palmrs-sync-capture-dump` can print
+	/// (implies `--once`)
+	#[structopt(long, parse(from_os_str))]
+	capture: Option<PathBuf>,
+
+	/// Run the sync against a capture file rather than a device, and report where it went
+	/// differently to the captured session
+	#[structopt(long, parse(from_os_str), conflicts_with_all = &["port", "capture"])]
+	replay: Option<PathBuf>,
+}
+
+/// Start recording a link to the `--capture` file, if one was given
+fn capture<T: SerialLink + 'static>(
+	opt: &Opt,
+	link: T,
+	kind: CaptureLink,
+) -> Result<Box<dyn SerialLink>, io::Error> {
+	match &opt.capture {
+		Some(path) => Ok(Box::new(CaptureStream::new(
+			link,
+			File::create(path)?,
+			kind,
+		)?)),
+		None => Ok(Box::new(link)),
+	}
+}
+
+/// Identify the user, and sync with their configuration
+fn sync<T: MessageTransport>(
+	opt: &Opt,
+	config: &SyncConfig,
+	data_root: &Path,
+	transport: T,
+) -> Result<HotSyncReport, Report> {
+	let mut client = DlpClient::new(transport);
+	let user = client
+		.read_user_info()
+		.wrap_err("failed to read user info")?;
+	log::info!(
+		"syncing user {:?} (ID {:#010X})",
+		user.user_name,
+		user.user_id
+	);
+
+	if opt.config.is_some() && config.device(&user).is_none() {
+		log::warn!("no configuration for this device, so only running the given conduits");
+	}
+
+	let mut hotsync = config.hotsync(&user, data_root);
+	hotsync.backup |= opt.backup;
+	for conduit in opt.conduits.iter() {
+		hotsync
+			.conduits
+			.push(ConduitHandler::new(conduit, opt.sync_mode));
+	}
+	log::trace!("hotsync = {:#?}", &hotsync);
+
+	Ok(hotsync.sync(&mut client, &user)?)
+}
+
+fn print_report(report: &HotSyncReport) {
+	println!(
+		"Synced {:?}: {} databases pulled, {} pushed, ended with {:?}",
+		report.user.user_name,
+		report.pulled.len(),
+		report.pushed.len(),
+		report.status
+	);
+	for outcome in report.conduits.iter().filter(|x| !x.success) {
+		println!(
+			"Conduit {} failed: {}",
+			outcome.conduit_name,
+			outcome.error.as_deref().unwrap_or("unknown error")
+		);
+	}
+}
+
+/// Sync with the device's side of a captured session, comparing what's sent to the capture
+fn replay(opt: &Opt, config: &SyncConfig, data_root: &Path, path: &Path) -> Result<(), Report> {
+	let data = fs::read(path).wrap_err("failed to read capture file")?;
+	let capture =
+		Capture::from_bytes(&mut Cursor::new(&data)).wrap_err("failed to parse capture file")?;
+
+	let mut replay = ReplayStream::new(&capture);
+	let result = match capture.header.link {
+		CaptureLink::Serial => {
+			let mut padp = PadpConnection::new(
+				SlpConnection::new(&mut replay),
+				SlpSocket::DesktopLink,
+				SlpSocket::DesktopLink,
+			);
+			accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES)
+				.wrap_err("failed to start the session")
+				.and_then(|_| sync(opt, config, data_root, padp))
+		}
+		CaptureLink::NetSync => {
+			let mut connection = NetSyncConnection::new(&mut replay);
+			connection
+				.accept_handshake()
+				.wrap_err("failed to exchange the handshake")
+				.and_then(|_| sync(opt, config, data_root, connection))
+		}
+		CaptureLink::Unknown(x) => return Err(eyre!("unknown capture link type {:#04X}", x)),
+	};
+
+	match &result {
+		Ok(report) => print_report(report),
+		Err(e) => eprintln!("Sync failed: {:?}", e),
+	}
+	match replay.mismatch() {
+		Some(offset) => println!(
+			"The desktop went differently to the capture, from byte {} of what it sent",
+			offset
+		),
+		None => println!("The desktop sent the same as in the capture"),
+	}
+	if !replay.is_finished() {
+		println!("Some of the device's side of the capture wasn't used");
+	}
+
+	result.map(|_| ())
+}
+
+fn main() -> Result<(), Report> {
+	env_logger::init();
+	stable_eyre::install()?;
+	let opt = Opt::from_args();
+	log::trace!("opt = {:#?}", &opt);
+
+	let mut config = match &opt.config {
+		Some(path) => SyncConfig::load(path).wrap_err("failed to load sync configuration")?,
+		None => SyncConfig::default(),
+	};
+	log::trace!("config = {:#?}", &config);
+
+	if opt.data_root.is_some() {
+		config.data_root = opt.data_root.clone();
+	}
+	let data_root = match &config.data_root {
+		Some(path) => path.clone(),
+		None => {
+			default_data_root().ok_or_else(|| eyre!("HOME isn't set, so --data-root is needed"))?
+		}
+	};
+
+	if let Some(path) = &opt.replay {
+		return replay(&opt, &config, &data_root, path);
+	}
+	let once = opt.once || opt.capture.is_some();
+
+	// Answer network HotSync wakeups in the background
+	let listener = match &opt.port {
+		Some(_) => None,
+		None => {
+			let socket = UdpSocket::bind((opt.listen.as_str(), NETSYNC_WAKEUP_PORT))
+				.wrap_err("failed to listen for network HotSync wakeups")?;
+			thread::spawn(move || loop {
+				match answer_wakeup(&socket) {
+					Ok((wakeup, addr)) => {
+						log::info!("wakeup from {:?} at {}", wakeup.host_name, addr)
+					}
+					Err(e) => log::warn!("failed to answer wakeup: {}", e),
+				}
+			});
+
+			Some(
+				NetSyncListener::bind((opt.listen.as_str(), NETSYNC_DATA_PORT))
+					.wrap_err("failed to listen for network HotSyncs")?,
+			)
+		}
+	};
+
+	loop {
+		println!("Waiting for a device...");
+		let result = match (&opt.port, &listener) {
+			(Some(endpoint), _) => {
+				let link = endpoint.open().wrap_err("failed to open the serial port")?;
+				let link = capture(&opt, link, CaptureLink::Serial)
+					.wrap_err("failed to create the capture file")?;
+				let mut padp = PadpConnection::new(
+					SlpConnection::new(link),
+					SlpSocket::DesktopLink,
+					SlpSocket::DesktopLink,
+				);
+
+				// Keep waiting for as long as it takes the device to start the session
+				let session = loop {
+					match accept_session(&mut padp, CMP_DEFAULT_BAUD_RATES) {
+						Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+						x => break x,
+					}
+				};
+				session
+					.wrap_err("failed to start the session")
+					.and_then(|session| {
+						log::info!("session started at {} baud", session.baud_rate);
+						sync(&opt, &config, &data_root, padp)
+					})
+			}
+			(None, Some(listener)) => listener
+				.accept_with(|stream| capture(&opt, stream, CaptureLink::NetSync))
+				.wrap_err("failed to accept the connection")
+				.and_then(|(connection, addr)| {
+					log::info!("connection from {}", addr);
+					sync(&opt, &config, &data_root, connection)
+				}),
+			(None, None) => unreachable!(),
+		};
+
+		match result {
+			Ok(report) => print_report(&report),
+			Err(e) if once => return Err(e),
+			Err(e) => eprintln!("Sync failed: {:?}", e),
+		}
+
+		if once {
+			return Ok(());
+		}
+	}
+}
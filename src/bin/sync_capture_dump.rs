@@ -0,0 +1,227 @@
+use std::{io::Cursor, path::PathBuf};
+
+use palmrs::{
+	database::text::{palm_to_string, trim_null},
+	sync::{
+		capture::{decode, Capture, CaptureDirection, CaptureEvent, CaptureMessage},
+		protocol::dlp::{
+			info::{DlpDatabaseList, DlpSysInfo, DlpUserInfo},
+			record::DlpRecord,
+			DlpArgument,
+			DlpFunction,
+		},
+	},
+};
+use pretty_hex::{config_hex, HexConfig};
+use stable_eyre::eyre::{Report, WrapErr};
+use structopt::StructOpt;
+
+/// Print a capture of a sync session as annotated DLP commands
+#[derive(Debug, StructOpt)]
+#[structopt(name = "palmrs-sync-capture-dump")]
+struct Opt {
+	/// Also print the SLP, PADP and NetSync packets the messages were carried in
+	#[structopt(short, long)]
+	packets: bool,
+
+	/// Print a hex dump of the arguments of each DLP command
+	#[structopt(short, long)]
+	hexdump: bool,
+
+	/// Path to the capture file
+	#[structopt(name = "FILE", parse(from_os_str))]
+	file: PathBuf,
+}
+
+/// Short description of the contents of a command's first argument, for the commands where
+/// that's useful
+fn summarize(function: DlpFunction, request: bool, arguments: &[DlpArgument]) -> Option<String> {
+	let data = &arguments.first()?.data;
+	let mut rdr = Cursor::new(data.as_slice());
+
+	let summary = match (function, request) {
+		(DlpFunction::OpenDb, true) => {
+			format!("\"{}\"", palm_to_string(trim_null(data.get(2..)?)))
+		}
+		(DlpFunction::DeleteDb, true) => {
+			format!("\"{}\"", palm_to_string(trim_null(data.get(2..)?)))
+		}
+		(DlpFunction::CreateDb, true) => {
+			format!("\"{}\"", palm_to_string(trim_null(data.get(14..)?)))
+		}
+		(DlpFunction::AddSyncLogEntry, true) => {
+			format!("\"{}\"", palm_to_string(trim_null(data)))
+		}
+		(DlpFunction::ReadUserInfo, false) => {
+			let info = DlpUserInfo::from_bytes(&mut rdr).ok()?;
+			format!("user \"{}\" (ID {:#010X})", info.user_name, info.user_id)
+		}
+		(DlpFunction::ReadSysInfo, false) => {
+			format!("{:?}", DlpSysInfo::from_bytes(&mut rdr).ok()?)
+		}
+		(DlpFunction::ReadDbList, false) => {
+			let list = DlpDatabaseList::from_bytes(&mut rdr).ok()?;
+			let names = list
+				.databases
+				.iter()
+				.map(|x| format!("\"{}\"", x.name))
+				.collect::<Vec<_>>();
+			format!(
+				"{}{}",
+				names.join(", "),
+				if list.more { ", ..." } else { "" }
+			)
+		}
+		(DlpFunction::ReadRecord, false) | (DlpFunction::ReadNextModifiedRecord, false) => {
+			let record = DlpRecord::from_bytes(&mut rdr).ok()?;
+			format!(
+				"record {:#010X}, {} bytes{}{}",
+				record.unique_id,
+				record.data.len(),
+				if record.attributes.dirty {
+					", dirty"
+				} else {
+					""
+				},
+				if record.attributes.delete {
+					", deleted"
+				} else {
+					""
+				},
+			)
+		}
+		_ => return None,
+	};
+
+	Some(summary)
+}
+
+fn print_arguments(opt: &Opt, arguments: &[DlpArgument]) {
+	for argument in arguments.iter() {
+		println!(
+			"      argument {:#04X}, {} bytes",
+			argument.id,
+			argument.data.len()
+		);
+		if opt.hexdump && !argument.data.is_empty() {
+			println!(
+				"{}",
+				config_hex(
+					&argument.data,
+					HexConfig {
+						title: false,
+						..HexConfig::default()
+					},
+				)
+			);
+		}
+	}
+}
+
+fn print_event(opt: &Opt, event: &CaptureEvent) {
+	let direction = match event.direction {
+		CaptureDirection::ToDevice => "->",
+		CaptureDirection::FromDevice => "<-",
+		CaptureDirection::Unknown(_) => "??",
+	};
+	let timestamp = event.timestamp.as_secs_f64();
+	let prefix = format!("{:10.6} #{:<5} {}", timestamp, event.record, direction);
+
+	match &event.message {
+		CaptureMessage::Slp(packet) if opt.packets => println!(
+			"{}   SLP {:?}, {:?} -> {:?}, transaction {}, {} bytes",
+			prefix,
+			packet.packet_type,
+			packet.source,
+			packet.destination,
+			packet.transaction_id,
+			packet.data.len()
+		),
+		CaptureMessage::Padp(packet) if opt.packets => println!(
+			"{}   PADP {:?}, flags {:#04X}, size {}, {} bytes",
+			prefix,
+			packet.packet_type,
+			packet.flags,
+			packet.size,
+			packet.data.len()
+		),
+		CaptureMessage::NetSync(packet) if opt.packets => println!(
+			"{}   NetSync {:?}, transaction {}, {} bytes",
+			prefix,
+			packet.packet_type,
+			packet.transaction_id,
+			packet.data.len()
+		),
+		CaptureMessage::Slp(_) | CaptureMessage::Padp(_) | CaptureMessage::NetSync(_) => {}
+		CaptureMessage::NetSyncHandshake(data) => {
+			println!("{} NetSync handshake, {} bytes", prefix, data.len())
+		}
+		CaptureMessage::Cmp(packet) => println!(
+			"{} CMP {:?}, version {}.{}, {} baud, flags {:#04X}",
+			prefix,
+			packet.packet_type,
+			packet.major_version,
+			packet.minor_version,
+			packet.baud_rate,
+			packet.flags
+		),
+		CaptureMessage::DlpRequest(request) => {
+			print!("{} {:?}", prefix, request.function);
+			match summarize(request.function, true, &request.arguments) {
+				Some(summary) => println!(" {}", summary),
+				None => println!(),
+			}
+			print_arguments(opt, &request.arguments);
+		}
+		CaptureMessage::DlpResponse(response) => {
+			print!("{} {:?} response", prefix, response.function);
+			match (
+				response.error,
+				summarize(response.function, false, &response.arguments),
+			) {
+				(Some(error), _) => println!(", error {:?}", error),
+				(None, Some(summary)) => println!(": {}", summary),
+				(None, None) => println!(),
+			}
+			print_arguments(opt, &response.arguments);
+		}
+		CaptureMessage::Unknown(data) => {
+			println!("{} unknown message, {} bytes", prefix, data.len());
+			if opt.hexdump {
+				println!(
+					"{}",
+					config_hex(
+						&data,
+						HexConfig {
+							title: false,
+							..HexConfig::default()
+						},
+					)
+				);
+			}
+		}
+	}
+}
+
+fn main() -> Result<(), Report> {
+	env_logger::init();
+	stable_eyre::install()?;
+	let opt = Opt::from_args();
+	log::trace!("opt = {:#?}", &opt);
+
+	let data = std::fs::read(&opt.file).wrap_err("failed to read capture file")?;
+	let capture =
+		Capture::from_bytes(&mut Cursor::new(&data)).wrap_err("failed to parse capture file")?;
+
+	println!(
+		"{:?} capture started at {} (Unix time), {} records",
+		capture.header.link,
+		capture.header.start_time,
+		capture.records.len()
+	);
+	for event in decode(&capture).iter() {
+		print_event(&opt, event);
+	}
+
+	Ok(())
+}
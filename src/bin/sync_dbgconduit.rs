@@ -1,7 +1,13 @@
 use std::path::PathBuf;
 
-use palmrs_sync::{conduit::ConduitHandler, SyncMode};
-use stable_eyre::eyre::{Report, WrapErr};
+use palmrs_sync::{
+	conduit::ConduitHandler,
+	config::SyncConfig,
+	hotsync::default_data_root,
+	protocol::dlp::info::DlpUserInfo,
+	SyncMode,
+};
+use stable_eyre::eyre::{eyre, Report, WrapErr};
 use structopt::StructOpt;
 use subprocess::Redirection;
 
@@ -9,11 +15,16 @@ use subprocess::Redirection;
 #[derive(Debug, StructOpt)]
 #[structopt(name = "palmrs-sync-dbgconduit")]
 struct Opt {
-	/// Path to sync configuration
-	#[allow(unused)]
+	/// Path to sync configuration, to call the conduit with its settings and data directories
+	/// from
 	#[structopt(short, long)]
 	config: Option<PathBuf>,
 
+	/// User name or ID of the configured device to use (defaults to the first device with the
+	/// conduit)
+	#[structopt(short, long)]
+	device: Option<String>,
+
 	/// Name of the conduit to call
 	#[structopt(name = "CONDUIT")]
 	conduit: String,
@@ -29,8 +40,40 @@ fn main() -> Result<(), Report> {
 	let opt = Opt::from_args();
 	log::trace!("opt = {:#?}", &opt);
 
-	// Construct a ConduitHandler
-	let conduit = ConduitHandler::new(&opt.conduit, opt.sync_mode);
+	// Construct a ConduitHandler, from the configuration if there is one
+	let conduit = match &opt.config {
+		Some(path) => {
+			let config = SyncConfig::load(path).wrap_err("failed to load sync configuration")?;
+			let device = config
+				.devices
+				.iter()
+				.filter(|x| x.conduit(&opt.conduit).is_some())
+				.find(|x| match &opt.device {
+					Some(device) => {
+						x.user_name.as_ref() == Some(device)
+							|| x.user_id.map(|x| x.to_string()).as_ref() == Some(device)
+					}
+					None => true,
+				})
+				.ok_or_else(|| eyre!("no configured device has the conduit {}", &opt.conduit))?;
+			log::trace!("device = {:#?}", &device);
+
+			let user = DlpUserInfo {
+				user_id: device.user_id.unwrap_or_default(),
+				user_name: device.user_name.clone().unwrap_or_default(),
+				..DlpUserInfo::default()
+			};
+			let default_root = default_data_root().unwrap_or_default();
+			let hotsync = config.hotsync(&user, &default_root);
+
+			let mut conduit = device.conduit(&opt.conduit).unwrap().handler();
+			conduit.sync_mode = opt.sync_mode;
+			conduit.path_local = hotsync.conduit_path(&opt.conduit);
+			conduit.path_device = hotsync.device_path();
+			conduit
+		}
+		None => ConduitHandler::new(&opt.conduit, opt.sync_mode),
+	};
 	log::trace!("conduit = {:#?}", &conduit);
 
 	// Do the popen